@@ -1,3 +1,53 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 採点前に適用されるマイグレーションと同じファイルを、起動時の自動マイグレーション用にバイナリへ埋め込む
+const MIGRATION_DIR: &str = "../mysql/migration";
+
 fn main() {
     println!("cargo:rustc-env=RUST_LOG=INFO");
+    println!("cargo:rerun-if-changed={}", MIGRATION_DIR);
+
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("migrations.rs");
+    fs::write(out_path, generate_migrations(Path::new(MIGRATION_DIR))).unwrap();
+}
+
+fn generate_migrations(dir: &Path) -> String {
+    let mut migrations = Vec::new();
+    match fs::read_dir(dir) {
+        Ok(entries) => {
+            for entry in entries.flatten() {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let version = file_name
+                    .strip_suffix(".sql")
+                    .and_then(|name| name.split('_').next())
+                    .and_then(|version| version.parse::<i64>().ok());
+                if let Some(version) = version {
+                    let path = fs::canonicalize(entry.path()).unwrap();
+                    migrations.push((version, file_name, path));
+                }
+            }
+        }
+        Err(_) => println!(
+            "cargo:warning={} が見つからないため、マイグレーションを埋め込まずにビルドします",
+            MIGRATION_DIR
+        ),
+    }
+    migrations.sort();
+
+    let entries: String = migrations
+        .iter()
+        .map(|(version, file_name, path)| {
+            format!(
+                "    ({}, {:?}, include_str!({:?})),\n",
+                version, file_name, path
+            )
+        })
+        .collect();
+
+    format!(
+        "pub const MIGRATIONS: &[(i64, &str, &str)] = &[\n{}];\n",
+        entries
+    )
 }
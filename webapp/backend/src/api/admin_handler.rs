@@ -0,0 +1,193 @@
+use crate::app_state::{
+    AppApiKeyService, AppAuthService, AppBackupService, AppExportService, AppReportService,
+    AppRetentionService, AppUserImportService,
+};
+use crate::config::AppConfig;
+use crate::domains::dto::api_key::CreateApiKeyRequestDto;
+use crate::domains::dto::auth::{ChangeRoleRequestDto, CreateUserRequestDto};
+use crate::domains::dto::export::{ExportFormat, ExportQueryDto};
+use crate::domains::dto::user_import::UserImportFormat;
+use crate::domains::export_service::ExportFilter;
+use crate::errors::AppError;
+use crate::models::organization::OrganizationScope;
+use actix_web::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures_util::TryStreamExt;
+use serde::Serialize;
+
+#[derive(Serialize, Debug)]
+struct PurgeSessionsResponse {
+    deleted: u64,
+}
+
+pub async fn purge_sessions_handler(
+    service: web::Data<AppAuthService>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse, AppError> {
+    let deleted = service
+        .purge_expired_sessions(config.session.gc_batch_size)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(PurgeSessionsResponse { deleted }))
+}
+
+pub async fn create_api_key_handler(
+    service: web::Data<AppApiKeyService>,
+    organization: web::ReqData<OrganizationScope>,
+    req: web::Json<CreateApiKeyRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .issue_api_key(*organization, &req.name, &req.scopes)
+        .await
+    {
+        Ok(response) => Ok(HttpResponse::Created().json(response)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn revoke_api_key_handler(
+    service: web::Data<AppApiKeyService>,
+    organization: web::ReqData<OrganizationScope>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .revoke_api_key(*organization, path.into_inner())
+        .await
+    {
+        Ok(_) => Ok(HttpResponse::NoContent().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn create_user_handler(
+    service: web::Data<AppAuthService>,
+    organization: web::ReqData<OrganizationScope>,
+    req: web::Json<CreateUserRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let user = service
+        .create_user_with_temporary_password(*organization, &req.username, req.role, req.area_id)
+        .await?;
+
+    Ok(HttpResponse::Created().json(user))
+}
+
+pub async fn change_user_role_handler(
+    service: web::Data<AppAuthService>,
+    organization: web::ReqData<OrganizationScope>,
+    path: web::Path<i32>,
+    req: web::Json<ChangeRoleRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .change_role(*organization, path.into_inner(), req.role, req.area_id)
+        .await
+    {
+        Ok(_) => Ok(HttpResponse::NoContent().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn deactivate_user_handler(
+    service: web::Data<AppAuthService>,
+    organization: web::ReqData<OrganizationScope>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .deactivate_user(*organization, path.into_inner())
+        .await
+    {
+        Ok(_) => Ok(HttpResponse::NoContent().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+// Content-Type が text/csv の場合は CSV、それ以外は JSON の配列として読む
+pub async fn import_users_handler(
+    service: web::Data<AppUserImportService>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> Result<HttpResponse, AppError> {
+    let format = match req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(content_type) if content_type.starts_with("text/csv") => UserImportFormat::Csv,
+        _ => UserImportFormat::Json,
+    };
+    let response = service.import_users(&body, format).await?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+pub async fn get_reports_handler(
+    service: web::Data<AppReportService>,
+) -> Result<HttpResponse, AppError> {
+    let report = service.get_report().await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// 保持期間のポリシーごとに、次の実行で処理の対象になる件数を返す。何も変更しない
+pub async fn get_retention_report_handler(
+    service: web::Data<AppRetentionService>,
+) -> Result<HttpResponse, AppError> {
+    let report = service.report().await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+pub async fn export_orders_handler(
+    service: web::Data<AppExportService>,
+    query: web::Query<ExportQueryDto>,
+) -> Result<HttpResponse, AppError> {
+    let format = query.format.unwrap_or(ExportFormat::Csv);
+    let body = service
+        .export_orders(ExportFilter::default(), format)
+        .map_ok(web::Bytes::from);
+
+    Ok(export_response("orders", format).streaming(body))
+}
+
+pub async fn export_audit_logs_handler(
+    service: web::Data<AppExportService>,
+    query: web::Query<ExportQueryDto>,
+) -> Result<HttpResponse, AppError> {
+    let format = query.format.unwrap_or(ExportFormat::Csv);
+    let body = service.export_audit_logs(format).map_ok(web::Bytes::from);
+
+    Ok(export_response("audit_logs", format).streaming(body))
+}
+
+// 主要なテーブルを同じ時点の内容で読み、gzip で圧縮したアーカイブとして返す
+pub async fn export_backup_handler(
+    service: web::Data<AppBackupService>,
+) -> Result<HttpResponse, AppError> {
+    let body = service.export().map_ok(web::Bytes::from);
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/gzip")
+        .insert_header((
+            CONTENT_DISPOSITION,
+            "attachment; filename=\"backup.ndjson.gz\"",
+        ))
+        .streaming(body))
+}
+
+// export_backup_handler で書き出したアーカイブの時点へ、テーブルの内容を戻す
+pub async fn restore_backup_handler(
+    service: web::Data<AppBackupService>,
+    body: web::Bytes,
+) -> Result<HttpResponse, AppError> {
+    let response = service.restore(&body).await?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+fn export_response(name: &str, format: ExportFormat) -> actix_web::HttpResponseBuilder {
+    let mut response = HttpResponse::Ok();
+    response.content_type(format.content_type()).insert_header((
+        CONTENT_DISPOSITION,
+        format!("attachment; filename=\"{}.{}\"", name, format.extension()),
+    ));
+    response
+}
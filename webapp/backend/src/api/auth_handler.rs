@@ -1,8 +1,19 @@
-use crate::domains::auth_service::AuthService;
-use crate::domains::dto::auth::{LoginRequestDto, LogoutRequestDto, RegisterRequestDto};
+use crate::api::versioning::ApiVersion;
+use crate::app_state::{AppAuthService, AppInviteCodeService};
+use crate::config::{AppConfig, SessionConfig, SessionTransport};
+use crate::domains::dto::auth::{
+    ChangePasswordRequestDto, ChangeUsernameRequestDto, CsrfTokenResponseDto, LoginRequestDto,
+    LoginResponseDto, LoginResponseV2Dto, LogoutRequestDto, RegisterRequestDto,
+    RegisterResponseDto, TotpSetupRequestDto, TotpVerifyRequestDto, VerifyEmailRequestDto,
+};
 use crate::errors::AppError;
-use crate::repositories::auth_repository::AuthRepositoryImpl;
-use actix_web::{web, HttpResponse};
+use crate::middlewares::csrf_middleware::CSRF_COOKIE_NAME;
+use crate::models::user::Session;
+use crate::utils::{
+    generate_session_token, session_client_from_request, session_token_from_request,
+};
+use actix_web::cookie::{time, Cookie, SameSite};
+use actix_web::{web, HttpRequest, HttpResponse, HttpResponseBuilder};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug)]
@@ -16,10 +27,20 @@ pub struct ValidationResponse {
 }
 
 pub async fn validate_session_handler(
-    service: web::Data<AuthService<AuthRepositoryImpl>>,
+    service: web::Data<AppAuthService>,
+    config: web::Data<AppConfig>,
+    http_req: HttpRequest,
     query: web::Query<ValidateSessionQueryParams>,
 ) -> Result<HttpResponse, AppError> {
-    match &query.session_token {
+    let session_token = match &query.session_token {
+        Some(session_token) => Some(session_token.clone()),
+        None if config.session.transport == SessionTransport::Cookie => {
+            session_token_from_request(&http_req, &config.session)
+        }
+        None => None,
+    };
+
+    match &session_token {
         Some(session_token) => match service.validate_session(session_token.as_str()).await {
             Ok(is_valid) => Ok(HttpResponse::Ok().json(ValidationResponse { is_valid })),
             Err(_) => Ok(HttpResponse::Ok().json(ValidationResponse { is_valid: false })),
@@ -29,56 +50,228 @@ pub async fn validate_session_handler(
 }
 
 pub async fn register_handler(
-    service: web::Data<AuthService<AuthRepositoryImpl>>,
+    service: web::Data<AppAuthService>,
+    invite_codes: web::Data<AppInviteCodeService>,
+    config: web::Data<AppConfig>,
+    version: ApiVersion,
+    http_req: HttpRequest,
     req: web::Json<RegisterRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    // 招待コードを使う前に確かめる
+    service.ensure_self_registration_enabled()?;
+    let invite_code_id = invite_codes
+        .redeem(req.role, req.invite_code.as_deref())
+        .await?;
+    let result = service
+        .register_user(
+            &req.username,
+            &req.password,
+            req.role,
+            req.area_id,
+            req.email.as_deref(),
+            &session_client_from_request(&http_req),
+        )
+        .await;
+    // 登録できなかった場合は、使った招待コードの回数を戻す
+    if let (Err(_), Some(invite_code_id)) = (&result, invite_code_id) {
+        invite_codes.release(invite_code_id).await?;
+    }
+
+    match result {
+        Ok(RegisterResponseDto::LoggedIn(response)) => Ok(session_response(
+            HttpResponse::Created(),
+            &config.session,
+            version,
+            response,
+        )),
+        Ok(RegisterResponseDto::PendingVerification(response)) => {
+            Ok(HttpResponse::Accepted().json(response))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn verify_email_handler(
+    service: web::Data<AppAuthService>,
+    req: web::Json<VerifyEmailRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    match service.verify_email(&req.token).await {
+        Ok(_) => Ok(HttpResponse::NoContent().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn login_handler(
+    service: web::Data<AppAuthService>,
+    config: web::Data<AppConfig>,
+    version: ApiVersion,
+    http_req: HttpRequest,
+    req: web::Json<LoginRequestDto>,
 ) -> Result<HttpResponse, AppError> {
     match service
-        .register_user(&req.username, &req.password, &req.role, req.area_id)
+        .login_user(
+            &req.username,
+            &req.password,
+            req.totp_code.as_deref(),
+            req.new_password.as_deref(),
+            &session_client_from_request(&http_req),
+        )
         .await
     {
-        Ok(response) => Ok(HttpResponse::Created().json(response)),
+        Ok(response) => Ok(session_response(
+            HttpResponse::Ok(),
+            &config.session,
+            version,
+            response,
+        )),
         Err(err) => Err(err),
     }
 }
 
-pub async fn login_handler(
-    service: web::Data<AuthService<AuthRepositoryImpl>>,
-    req: web::Json<LoginRequestDto>,
+pub async fn refresh_handler(
+    service: web::Data<AppAuthService>,
+    config: web::Data<AppConfig>,
+    version: ApiVersion,
+    session: web::ReqData<Session>,
+) -> Result<HttpResponse, AppError> {
+    match service.refresh_session(&session).await {
+        Ok(response) => Ok(session_response(
+            HttpResponse::Ok(),
+            &config.session,
+            version,
+            response,
+        )),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn change_username_handler(
+    service: web::Data<AppAuthService>,
+    session: web::ReqData<Session>,
+    req: web::Json<ChangeUsernameRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    match service
+        .change_username(session.user_id, &req.username)
+        .await
+    {
+        Ok(_) => Ok(HttpResponse::Ok().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn change_password_handler(
+    service: web::Data<AppAuthService>,
+    session: web::ReqData<Session>,
+    req: web::Json<ChangePasswordRequestDto>,
 ) -> Result<HttpResponse, AppError> {
-    match service.login_user(&req.username, &req.password).await {
+    match service
+        .change_password(
+            session.user_id,
+            &req.current_password,
+            &req.new_password,
+            &session.session_token,
+        )
+        .await
+    {
+        Ok(_) => Ok(HttpResponse::Ok().finish()),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn setup_totp_handler(
+    service: web::Data<AppAuthService>,
+    session: web::ReqData<Session>,
+    req: Option<web::Json<TotpSetupRequestDto>>,
+) -> Result<HttpResponse, AppError> {
+    let code = req.and_then(|req| req.into_inner().code);
+    match service.setup_totp(session.user_id, code.as_deref()).await {
         Ok(response) => Ok(HttpResponse::Ok().json(response)),
         Err(err) => Err(err),
     }
 }
 
-pub async fn logout_handler(
-    service: web::Data<AuthService<AuthRepositoryImpl>>,
-    req: web::Json<LogoutRequestDto>,
+pub async fn enable_totp_handler(
+    service: web::Data<AppAuthService>,
+    session: web::ReqData<Session>,
+    req: web::Json<TotpVerifyRequestDto>,
 ) -> Result<HttpResponse, AppError> {
-    match service.logout_user(&req.session_token).await {
+    match service.enable_totp(session.user_id, &req.code).await {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
-        Err(_) => Ok(HttpResponse::Ok().finish()),
+        Err(err) => Err(err),
     }
 }
 
-#[derive(Deserialize, Debug)]
-pub struct UserProfileImageQueryParams {
-    w: Option<i32>,
-    h: Option<i32>,
+pub async fn get_sessions_handler(
+    service: web::Data<AppAuthService>,
+    session: web::ReqData<Session>,
+) -> Result<HttpResponse, AppError> {
+    match service.get_sessions(&session).await {
+        Ok(sessions) => Ok(HttpResponse::Ok().json(sessions)),
+        Err(err) => Err(err),
+    }
 }
 
-pub async fn user_profile_image_handler(
-    service: web::Data<AuthService<AuthRepositoryImpl>>,
-    path: web::Path<i32>,
-    query: web::Query<UserProfileImageQueryParams>,
+pub async fn logout_handler(
+    service: web::Data<AppAuthService>,
+    config: web::Data<AppConfig>,
+    http_req: HttpRequest,
+    req: web::Json<LogoutRequestDto>,
 ) -> Result<HttpResponse, AppError> {
-    let user_id = path.into_inner();
-    let width = query.w.unwrap_or(500);
-    let height = query.h.unwrap_or(500);
-    let profile_image_byte = service
-        .get_resized_profile_image_byte(user_id, width, height)
-        .await?;
+    let session_token = match req.session_token.is_empty() {
+        true => session_token_from_request(&http_req, &config.session).unwrap_or_default(),
+        false => req.session_token.clone(),
+    };
+
+    let _ = service.logout_user(&session_token).await;
+
+    let mut response = HttpResponse::Ok();
+    if config.session.transport == SessionTransport::Cookie {
+        let mut cookie = session_cookie(&config.session, String::new());
+        cookie.make_removal();
+        response.cookie(cookie);
+    }
+    Ok(response.finish())
+}
+
+pub async fn csrf_token_handler(config: web::Data<AppConfig>) -> Result<HttpResponse, AppError> {
+    let csrf_token = generate_session_token();
+
+    // フロントエンドのスクリプトからヘッダーに載せられるよう、HttpOnly にはしない
+    let cookie = Cookie::build(CSRF_COOKIE_NAME, csrf_token.clone())
+        .path("/")
+        .secure(config.session.cookie_secure)
+        .same_site(SameSite::Strict)
+        .finish();
+
     Ok(HttpResponse::Ok()
-        .content_type("image/png")
-        .body(profile_image_byte))
+        .cookie(cookie)
+        .json(CsrfTokenResponseDto { csrf_token }))
+}
+
+pub fn session_response(
+    mut builder: HttpResponseBuilder,
+    config: &SessionConfig,
+    version: ApiVersion,
+    mut response: LoginResponseDto,
+) -> HttpResponse {
+    if config.transport == SessionTransport::Cookie {
+        let mut cookie = session_cookie(config, std::mem::take(&mut response.session_token));
+        let max_age = (response.expires_at - response.server_time).num_seconds();
+        cookie.set_max_age(time::Duration::seconds(max_age));
+        builder.cookie(cookie);
+    }
+
+    match version {
+        ApiVersion::V1 => builder.json(response),
+        ApiVersion::V2 => builder.json(LoginResponseV2Dto::from(response)),
+    }
+}
+
+fn session_cookie(config: &SessionConfig, session_token: String) -> Cookie<'static> {
+    Cookie::build(config.cookie_name.clone(), session_token)
+        .path("/")
+        .http_only(true)
+        .secure(config.cookie_secure)
+        .same_site(SameSite::Strict)
+        .finish()
 }
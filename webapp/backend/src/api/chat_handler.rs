@@ -0,0 +1,97 @@
+use crate::api::websocket;
+use crate::app_state::AppChatService;
+use crate::domains::dto::chat::{MessageHistoryQueryDto, SendMessageRequestDto};
+use crate::errors::AppError;
+use crate::models::organization::OrganizationScope;
+use crate::models::user::Session;
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::warn;
+use tokio::sync::mpsc;
+
+pub async fn get_order_messages_handler(
+    service: web::Data<AppChatService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+    query: web::Query<MessageHistoryQueryDto>,
+) -> Result<HttpResponse, AppError> {
+    let messages = service
+        .get_messages(
+            *organization,
+            path.into_inner(),
+            session.user_id,
+            query.into_inner(),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(messages))
+}
+
+pub async fn send_order_message_handler(
+    service: web::Data<AppChatService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+    req: web::Json<SendMessageRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let message = service
+        .send_message(*organization, path.into_inner(), session.user_id, &req.body)
+        .await?;
+
+    Ok(HttpResponse::Created().json(message))
+}
+
+pub async fn mark_order_messages_read_handler(
+    service: web::Data<AppChatService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .mark_read(*organization, path.into_inner(), session.user_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub async fn get_unread_counts_handler(
+    service: web::Data<AppChatService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+) -> Result<HttpResponse, AppError> {
+    let counts = service
+        .get_unread_counts(*organization, session.user_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(counts))
+}
+
+// 注文のチャットの部屋に接続する。テキストフレームで送られた本文は POST と同じく保存してから部屋に配る
+pub async fn order_chat_ws_handler(
+    req: HttpRequest,
+    payload: web::Payload,
+    service: web::Data<AppChatService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let organization = *organization;
+    let order_id = path.into_inner();
+    let user_id = session.user_id;
+    let receiver = service.join(organization, order_id, user_id).await?;
+
+    let (incoming_tx, mut incoming_rx) = mpsc::unbounded_channel::<String>();
+    actix_web::rt::spawn(async move {
+        // 送られた順に保存する。不正な本文は捨てて接続は続ける
+        while let Some(body) = incoming_rx.recv().await {
+            if let Err(err) = service
+                .send_message(organization, order_id, user_id, &body)
+                .await
+            {
+                warn!("チャットのメッセージを送信できませんでした: {:?}", err);
+            }
+        }
+    });
+
+    websocket::upgrade(&req, payload, receiver, Some(incoming_tx))
+}
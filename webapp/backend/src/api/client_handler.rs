@@ -0,0 +1,83 @@
+use crate::app_state::AppClientService;
+use crate::domains::dto::client::{
+    ClientLookupQueryDto, CreateClientOrderRequestDto, CreateClientRequestDto,
+    UpdateClientRequestDto,
+};
+use crate::errors::AppError;
+use crate::models::organization::OrganizationScope;
+use crate::models::user::Session;
+use actix_web::{web, HttpResponse};
+
+pub async fn create_client_handler(
+    service: web::Data<AppClientService>,
+    organization: web::ReqData<OrganizationScope>,
+    req: web::Json<CreateClientRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let client = service
+        .create_client(*organization, req.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Created().json(client))
+}
+
+pub async fn lookup_client_handler(
+    service: web::Data<AppClientService>,
+    query: web::Query<ClientLookupQueryDto>,
+) -> Result<HttpResponse, AppError> {
+    let client = service.find_client_by_phone_number(&query.phone).await?;
+
+    Ok(HttpResponse::Ok().json(client))
+}
+
+pub async fn get_client_handler(
+    service: web::Data<AppClientService>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let client = service.get_client(path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(client))
+}
+
+pub async fn update_client_handler(
+    service: web::Data<AppClientService>,
+    organization: web::ReqData<OrganizationScope>,
+    path: web::Path<i32>,
+    req: web::Json<UpdateClientRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let client = service
+        .update_client(*organization, path.into_inner(), req.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(client))
+}
+
+pub async fn get_client_orders_handler(
+    service: web::Data<AppClientService>,
+    organization: web::ReqData<OrganizationScope>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let orders = service
+        .get_client_orders(*organization, path.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(orders))
+}
+
+pub async fn create_client_order_handler(
+    service: web::Data<AppClientService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+    req: web::Json<CreateClientOrderRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let order = service
+        .create_client_order(
+            *organization,
+            path.into_inner(),
+            session.user_id,
+            req.into_inner(),
+        )
+        .await?;
+
+    Ok(HttpResponse::Created().json(order))
+}
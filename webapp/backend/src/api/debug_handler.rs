@@ -0,0 +1,32 @@
+use crate::app_state::AppFixtureService;
+use crate::errors::AppError;
+use actix_web::{web, HttpResponse};
+
+// DEBUG_ENDPOINTS_ENABLED が設定されていない場合、フィクスチャ用のサービスは登録されず 404 を返す
+pub async fn seed_handler(
+    service: Option<web::Data<AppFixtureService>>,
+) -> Result<HttpResponse, AppError> {
+    let service = match service {
+        Some(service) => service,
+        None => return Err(AppError::NotFound),
+    };
+
+    match service.seed().await {
+        Ok(response) => Ok(HttpResponse::Ok().json(response)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn reset_handler(
+    service: Option<web::Data<AppFixtureService>>,
+) -> Result<HttpResponse, AppError> {
+    let service = match service {
+        Some(service) => service,
+        None => return Err(AppError::NotFound),
+    };
+
+    match service.reset().await {
+        Ok(_) => Ok(HttpResponse::NoContent().finish()),
+        Err(err) => Err(err),
+    }
+}
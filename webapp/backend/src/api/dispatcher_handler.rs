@@ -0,0 +1,55 @@
+use crate::app_state::AppDispatcherService;
+use crate::domains::dto::dispatcher::{
+    AvailableDispatchersQueryDto, TransferDispatcherRequestDto, UpdateAvailabilityRequestDto,
+};
+use crate::errors::AppError;
+use crate::models::organization::OrganizationScope;
+use crate::models::user::Session;
+use actix_web::{web, HttpResponse};
+
+pub async fn update_availability_handler(
+    service: web::Data<AppDispatcherService>,
+    session: web::ReqData<Session>,
+    organization: web::ReqData<OrganizationScope>,
+    req: web::Json<UpdateAvailabilityRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .set_availability(*organization, session.user_id, req.available)
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn get_available_dispatchers_handler(
+    service: web::Data<AppDispatcherService>,
+    organization: web::ReqData<OrganizationScope>,
+    query: web::Query<AvailableDispatchersQueryDto>,
+) -> Result<HttpResponse, AppError> {
+    let dispatchers = service
+        .get_available_dispatchers(*organization, query.area_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(dispatchers))
+}
+
+pub async fn get_area_load_handler(
+    service: web::Data<AppDispatcherService>,
+    organization: web::ReqData<OrganizationScope>,
+) -> Result<HttpResponse, AppError> {
+    let load = service.get_area_load(*organization).await?;
+
+    Ok(HttpResponse::Ok().json(load))
+}
+
+pub async fn transfer_dispatcher_handler(
+    service: web::Data<AppDispatcherService>,
+    organization: web::ReqData<OrganizationScope>,
+    path: web::Path<i32>,
+    req: web::Json<TransferDispatcherRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let transfer = service
+        .transfer_dispatcher(*organization, path.into_inner(), req.area_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(transfer))
+}
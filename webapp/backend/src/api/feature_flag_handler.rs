@@ -0,0 +1,29 @@
+use crate::app_state::AppFeatureFlagService;
+use crate::domains::dto::feature_flag::UpdateFeatureFlagRequestDto;
+use crate::errors::AppError;
+use actix_web::{web, HttpResponse};
+
+pub async fn get_feature_flags_handler(
+    service: web::Data<AppFeatureFlagService>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(service.get_flags()))
+}
+
+pub async fn update_feature_flag_handler(
+    service: web::Data<AppFeatureFlagService>,
+    path: web::Path<String>,
+    req: web::Json<UpdateFeatureFlagRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let flag = service.set_flag(&path.into_inner(), req.enabled).await?;
+
+    Ok(HttpResponse::Ok().json(flag))
+}
+
+pub async fn reset_feature_flag_handler(
+    service: web::Data<AppFeatureFlagService>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let flag = service.reset_flag(&path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(flag))
+}
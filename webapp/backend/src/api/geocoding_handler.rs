@@ -0,0 +1,22 @@
+use crate::app_state::AppGeocodingService;
+use crate::domains::dto::geocode::{GeocodeQueryDto, ReverseGeocodeQueryDto};
+use crate::errors::AppError;
+use actix_web::{web, HttpResponse};
+
+pub async fn geocode_handler(
+    service: web::Data<AppGeocodingService>,
+    query: web::Query<GeocodeQueryDto>,
+) -> Result<HttpResponse, AppError> {
+    let result = service.geocode(&query.address).await?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub async fn reverse_geocode_handler(
+    service: web::Data<AppGeocodingService>,
+    query: web::Query<ReverseGeocodeQueryDto>,
+) -> Result<HttpResponse, AppError> {
+    let result = service.reverse_geocode(query.x, query.y).await?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
@@ -0,0 +1,24 @@
+use crate::api::graphql_schema::GraphqlApi;
+use crate::errors::AppError;
+use crate::models::organization::OrganizationScope;
+use crate::models::user::Session;
+use actix_web::{web, HttpResponse};
+
+// 設定で無効にしている場合は 404 を返す
+pub async fn graphql_handler(
+    graphql_api: Option<web::Data<GraphqlApi>>,
+    session: web::ReqData<Session>,
+    organization: web::ReqData<OrganizationScope>,
+    req: web::Json<async_graphql::Request>,
+) -> Result<HttpResponse, AppError> {
+    let graphql_api = match graphql_api {
+        Some(graphql_api) => graphql_api,
+        None => return Err(AppError::NotFound),
+    };
+
+    let response = graphql_api
+        .execute(req.into_inner(), session.user_id, *organization)
+        .await;
+
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Error, Object, Request, Response, Schema,
+};
+use chrono::{DateTime, Utc};
+use log::error;
+
+use crate::config::GraphqlConfig;
+use crate::errors::AppError;
+use crate::models::area::Area;
+use crate::models::order::OrderWithArea;
+use crate::models::organization::OrganizationScope;
+use crate::models::user::{Dispatcher, UserSummary};
+use crate::repositories::graphql_repository::GraphqlRepositoryImpl;
+
+const ORDERS_MAX_PAGE_SIZE: i32 = 100;
+
+type GraphqlSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub struct GraphqlApi {
+    schema: GraphqlSchema,
+    repository: GraphqlRepositoryImpl,
+}
+
+impl std::fmt::Debug for GraphqlApi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GraphqlApi")
+            .field("repository", &self.repository)
+            .finish()
+    }
+}
+
+impl GraphqlApi {
+    pub fn new(repository: GraphqlRepositoryImpl, config: &GraphqlConfig) -> Self {
+        let schema = Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+            .data(repository.clone())
+            .limit_depth(config.max_depth)
+            .limit_complexity(config.max_complexity)
+            .finish();
+
+        GraphqlApi { schema, repository }
+    }
+
+    // データローダーはリクエストごとに作り、同じリクエストの中で発生した読み込みだけをまとめる
+    // ローダーもリクエストの組織のデータだけを読み込む
+    pub async fn execute(
+        &self,
+        request: Request,
+        user_id: i32,
+        organization: OrganizationScope,
+    ) -> Response {
+        let organization_id = organization.organization_id;
+        let request = request
+            .data(CurrentUserId(user_id))
+            .data(organization)
+            .data(DataLoader::new(
+                UserLoader(self.repository.clone(), organization_id),
+                actix_web::rt::spawn,
+            ))
+            .data(DataLoader::new(
+                DispatcherLoader(self.repository.clone(), organization_id),
+                actix_web::rt::spawn,
+            ))
+            .data(DataLoader::new(
+                DispatcherByUserLoader(self.repository.clone(), organization_id),
+                actix_web::rt::spawn,
+            ))
+            .data(DataLoader::new(
+                DispatchersByAreaLoader(self.repository.clone(), organization_id),
+                actix_web::rt::spawn,
+            ))
+            .data(DataLoader::new(
+                AreaLoader(self.repository.clone(), organization_id),
+                actix_web::rt::spawn,
+            ));
+
+        self.schema.execute(request).await
+    }
+}
+
+struct CurrentUserId(i32);
+
+// SQL のエラーなどの詳細はクライアントに返さず、ログにだけ残す
+fn internal_error(err: impl std::fmt::Debug) -> Error {
+    error!("GraphQL のクエリの実行に失敗しました: {:?}", err);
+    Error::new("Internal Server Error")
+}
+
+// ローダーの 2 つ目の値はリクエストの組織の ID
+pub struct UserLoader(GraphqlRepositoryImpl, i32);
+
+impl Loader<i32> for UserLoader {
+    type Value = UserSummary;
+    type Error = Arc<AppError>;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let users = self
+            .0
+            .find_users_by_ids(self.1, keys)
+            .await
+            .map_err(Arc::new)?;
+
+        Ok(users.into_iter().map(|user| (user.id, user)).collect())
+    }
+}
+
+pub struct DispatcherLoader(GraphqlRepositoryImpl, i32);
+
+impl Loader<i32> for DispatcherLoader {
+    type Value = Dispatcher;
+    type Error = Arc<AppError>;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let dispatchers = self
+            .0
+            .find_dispatchers_by_ids(self.1, keys)
+            .await
+            .map_err(Arc::new)?;
+
+        Ok(dispatchers
+            .into_iter()
+            .map(|dispatcher| (dispatcher.id, dispatcher))
+            .collect())
+    }
+}
+
+// ユーザー ID をキーにディスパッチャーを引く
+pub struct DispatcherByUserLoader(GraphqlRepositoryImpl, i32);
+
+impl Loader<i32> for DispatcherByUserLoader {
+    type Value = Dispatcher;
+    type Error = Arc<AppError>;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let dispatchers = self
+            .0
+            .find_dispatchers_by_user_ids(self.1, keys)
+            .await
+            .map_err(Arc::new)?;
+
+        Ok(dispatchers
+            .into_iter()
+            .map(|dispatcher| (dispatcher.user_id, dispatcher))
+            .collect())
+    }
+}
+
+// エリア ID をキーに、そのエリアのディスパッチャーをすべて引く
+pub struct DispatchersByAreaLoader(GraphqlRepositoryImpl, i32);
+
+impl Loader<i32> for DispatchersByAreaLoader {
+    type Value = Vec<Dispatcher>;
+    type Error = Arc<AppError>;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let dispatchers = self
+            .0
+            .find_dispatchers_by_area_ids(self.1, keys)
+            .await
+            .map_err(Arc::new)?;
+
+        let mut by_area: HashMap<i32, Vec<Dispatcher>> = HashMap::new();
+        for dispatcher in dispatchers {
+            by_area
+                .entry(dispatcher.area_id)
+                .or_default()
+                .push(dispatcher);
+        }
+        Ok(by_area)
+    }
+}
+
+pub struct AreaLoader(GraphqlRepositoryImpl, i32);
+
+impl Loader<i32> for AreaLoader {
+    type Value = Area;
+    type Error = Arc<AppError>;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let areas = self
+            .0
+            .find_areas_by_ids(self.1, keys)
+            .await
+            .map_err(Arc::new)?;
+
+        Ok(areas.into_iter().map(|area| (area.id, area)).collect())
+    }
+}
+
+pub struct UserObject(UserSummary);
+
+#[Object(name = "User")]
+impl UserObject {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn username(&self) -> &str {
+        &self.0.username
+    }
+
+    async fn role(&self) -> &str {
+        self.0.role.as_str()
+    }
+
+    // ディスパッチャーでないユーザーでは null
+    async fn dispatcher(&self, ctx: &Context<'_>) -> Result<Option<DispatcherObject>, Error> {
+        let dispatcher = ctx
+            .data_unchecked::<DataLoader<DispatcherByUserLoader>>()
+            .load_one(self.0.id)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(dispatcher.map(DispatcherObject))
+    }
+}
+
+pub struct DispatcherObject(Dispatcher);
+
+#[Object(name = "Dispatcher")]
+impl DispatcherObject {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn user(&self, ctx: &Context<'_>) -> Result<Option<UserObject>, Error> {
+        let user = ctx
+            .data_unchecked::<DataLoader<UserLoader>>()
+            .load_one(self.0.user_id)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(user.map(UserObject))
+    }
+
+    async fn area(&self, ctx: &Context<'_>) -> Result<Option<AreaObject>, Error> {
+        let area = ctx
+            .data_unchecked::<DataLoader<AreaLoader>>()
+            .load_one(self.0.area_id)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(area.map(AreaObject))
+    }
+}
+
+pub struct AreaObject(Area);
+
+#[Object(name = "Area")]
+impl AreaObject {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn dispatchers(&self, ctx: &Context<'_>) -> Result<Vec<DispatcherObject>, Error> {
+        let dispatchers = ctx
+            .data_unchecked::<DataLoader<DispatchersByAreaLoader>>()
+            .load_one(self.0.id)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(dispatchers
+            .unwrap_or_default()
+            .into_iter()
+            .map(DispatcherObject)
+            .collect())
+    }
+}
+
+pub struct OrderObject(OrderWithArea);
+
+#[Object(name = "Order")]
+impl OrderObject {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn status(&self) -> &str {
+        &self.0.status
+    }
+
+    async fn car_value(&self) -> f64 {
+        self.0.car_value
+    }
+
+    async fn tow_truck_id(&self) -> Option<i32> {
+        self.0.tow_truck_id
+    }
+
+    async fn order_time(&self) -> DateTime<Utc> {
+        self.0.order_time
+    }
+
+    async fn completed_time(&self) -> Option<DateTime<Utc>> {
+        self.0.completed_time
+    }
+
+    async fn client(&self, ctx: &Context<'_>) -> Result<Option<UserObject>, Error> {
+        let client = ctx
+            .data_unchecked::<DataLoader<UserLoader>>()
+            .load_one(self.0.client_id)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(client.map(UserObject))
+    }
+
+    async fn dispatcher(&self, ctx: &Context<'_>) -> Result<Option<DispatcherObject>, Error> {
+        let Some(dispatcher_id) = self.0.dispatcher_id else {
+            return Ok(None);
+        };
+        let dispatcher = ctx
+            .data_unchecked::<DataLoader<DispatcherLoader>>()
+            .load_one(dispatcher_id)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(dispatcher.map(DispatcherObject))
+    }
+
+    async fn area(&self, ctx: &Context<'_>) -> Result<Option<AreaObject>, Error> {
+        let area = ctx
+            .data_unchecked::<DataLoader<AreaLoader>>()
+            .load_one(self.0.area_id)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(area.map(AreaObject))
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    // ログイン中のユーザー
+    async fn me(&self, ctx: &Context<'_>) -> Result<Option<UserObject>, Error> {
+        let user_id = ctx.data_unchecked::<CurrentUserId>().0;
+        let user = ctx
+            .data_unchecked::<DataLoader<UserLoader>>()
+            .load_one(user_id)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(user.map(UserObject))
+    }
+
+    async fn user(&self, ctx: &Context<'_>, id: i32) -> Result<Option<UserObject>, Error> {
+        let user = ctx
+            .data_unchecked::<DataLoader<UserLoader>>()
+            .load_one(id)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(user.map(UserObject))
+    }
+
+    async fn areas(&self, ctx: &Context<'_>) -> Result<Vec<AreaObject>, Error> {
+        let organization = ctx.data_unchecked::<OrganizationScope>();
+        let areas = ctx
+            .data_unchecked::<GraphqlRepositoryImpl>()
+            .find_areas(organization.organization_id)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(areas.into_iter().map(AreaObject).collect())
+    }
+
+    async fn area(&self, ctx: &Context<'_>, id: i32) -> Result<Option<AreaObject>, Error> {
+        let area = ctx
+            .data_unchecked::<DataLoader<AreaLoader>>()
+            .load_one(id)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(area.map(AreaObject))
+    }
+
+    async fn order(&self, ctx: &Context<'_>, id: i32) -> Result<Option<OrderObject>, Error> {
+        let organization = ctx.data_unchecked::<OrganizationScope>();
+        let orders = ctx
+            .data_unchecked::<GraphqlRepositoryImpl>()
+            .find_orders_by_ids(organization.organization_id, &[id])
+            .await
+            .map_err(internal_error)?;
+
+        Ok(orders.into_iter().next().map(OrderObject))
+    }
+
+    // 新しい注文から順に返す
+    async fn orders(
+        &self,
+        ctx: &Context<'_>,
+        status: Option<String>,
+        area_id: Option<i32>,
+        #[graphql(default = 0)] page: i32,
+        #[graphql(default = 20)] page_size: i32,
+    ) -> Result<Vec<OrderObject>, Error> {
+        if page < 0 || !(1..=ORDERS_MAX_PAGE_SIZE).contains(&page_size) {
+            return Err(Error::new("Bad Request"));
+        }
+
+        let organization = ctx.data_unchecked::<OrganizationScope>();
+        let orders = ctx
+            .data_unchecked::<GraphqlRepositoryImpl>()
+            .find_orders(
+                organization.organization_id,
+                status.as_deref(),
+                area_id,
+                page_size,
+                page * page_size,
+            )
+            .await
+            .map_err(internal_error)?;
+
+        Ok(orders.into_iter().map(OrderObject).collect())
+    }
+}
@@ -0,0 +1,186 @@
+// proto/internal.proto に対応するメッセージとサーバー。
+// ビルド環境に protoc を要求しないよう、tonic-build が生成するものと同じ形のコードを手で書いている
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ValidateSessionRequest {
+    #[prost(string, tag = "1")]
+    pub session_token: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ValidateSessionResponse {
+    #[prost(int32, tag = "1")]
+    pub user_id: i32,
+    #[prost(string, tag = "2")]
+    pub role: String,
+    #[prost(int64, tag = "3")]
+    pub expires_at: i64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetDispatcherRequest {
+    #[prost(oneof = "get_dispatcher_request::Key", tags = "1, 2")]
+    pub key: Option<get_dispatcher_request::Key>,
+}
+
+pub mod get_dispatcher_request {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Key {
+        #[prost(int32, tag = "1")]
+        DispatcherId(i32),
+        #[prost(int32, tag = "2")]
+        UserId(i32),
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Dispatcher {
+    #[prost(int32, tag = "1")]
+    pub id: i32,
+    #[prost(int32, tag = "2")]
+    pub user_id: i32,
+    #[prost(int32, tag = "3")]
+    pub area_id: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListOnDutyDispatchersRequest {
+    #[prost(int32, tag = "1")]
+    pub area_id: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OnDutyDispatcher {
+    #[prost(int32, tag = "1")]
+    pub dispatcher_id: i32,
+    #[prost(int32, tag = "2")]
+    pub user_id: i32,
+    #[prost(string, tag = "3")]
+    pub username: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ListOnDutyDispatchersResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub dispatchers: Vec<OnDutyDispatcher>,
+}
+
+pub mod internal_server {
+    use tonic::codegen::*;
+
+    use super::{
+        Dispatcher, GetDispatcherRequest, ListOnDutyDispatchersRequest,
+        ListOnDutyDispatchersResponse, ValidateSessionRequest, ValidateSessionResponse,
+    };
+
+    #[async_trait]
+    pub trait Internal: Send + Sync + 'static {
+        async fn validate_session(
+            &self,
+            request: tonic::Request<ValidateSessionRequest>,
+        ) -> Result<tonic::Response<ValidateSessionResponse>, tonic::Status>;
+        async fn get_dispatcher(
+            &self,
+            request: tonic::Request<GetDispatcherRequest>,
+        ) -> Result<tonic::Response<Dispatcher>, tonic::Status>;
+        async fn list_on_duty_dispatchers(
+            &self,
+            request: tonic::Request<ListOnDutyDispatchersRequest>,
+        ) -> Result<tonic::Response<ListOnDutyDispatchersResponse>, tonic::Status>;
+    }
+
+    #[derive(Debug)]
+    pub struct InternalServer<T: Internal> {
+        inner: Arc<T>,
+    }
+
+    impl<T: Internal> InternalServer<T> {
+        pub fn new(inner: T) -> Self {
+            InternalServer {
+                inner: Arc::new(inner),
+            }
+        }
+    }
+
+    impl<T: Internal> Clone for InternalServer<T> {
+        fn clone(&self) -> Self {
+            InternalServer {
+                inner: self.inner.clone(),
+            }
+        }
+    }
+
+    // 1 つの RPC を tonic の UnaryService として呼び出せるようにする
+    macro_rules! unary_method {
+        ($svc:ident, $method:ident, $request:ty, $response:ty) => {
+            struct $svc<T: Internal>(Arc<T>);
+
+            impl<T: Internal> tonic::server::UnaryService<$request> for $svc<T> {
+                type Response = $response;
+                type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+
+                fn call(&mut self, request: tonic::Request<$request>) -> Self::Future {
+                    let inner = self.0.clone();
+                    Box::pin(async move { inner.$method(request).await })
+                }
+            }
+        };
+    }
+
+    unary_method!(
+        ValidateSessionSvc,
+        validate_session,
+        ValidateSessionRequest,
+        ValidateSessionResponse
+    );
+    unary_method!(
+        GetDispatcherSvc,
+        get_dispatcher,
+        GetDispatcherRequest,
+        Dispatcher
+    );
+    unary_method!(
+        ListOnDutyDispatchersSvc,
+        list_on_duty_dispatchers,
+        ListOnDutyDispatchersRequest,
+        ListOnDutyDispatchersResponse
+    );
+
+    impl<T, B> Service<http::Request<B>> for InternalServer<T>
+    where
+        T: Internal,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/internal.v1.Internal/ValidateSession" => Box::pin(async move {
+                    let mut grpc = tonic::server::Grpc::new(tonic::codec::ProstCodec::default());
+                    Ok(grpc.unary(ValidateSessionSvc(inner), req).await)
+                }),
+                "/internal.v1.Internal/GetDispatcher" => Box::pin(async move {
+                    let mut grpc = tonic::server::Grpc::new(tonic::codec::ProstCodec::default());
+                    Ok(grpc.unary(GetDispatcherSvc(inner), req).await)
+                }),
+                "/internal.v1.Internal/ListOnDutyDispatchers" => Box::pin(async move {
+                    let mut grpc = tonic::server::Grpc::new(tonic::codec::ProstCodec::default());
+                    Ok(grpc.unary(ListOnDutyDispatchersSvc(inner), req).await)
+                }),
+                _ => Box::pin(async move { Ok(tonic::Status::unimplemented("").into_http()) }),
+            }
+        }
+    }
+
+    impl<T: Internal> tonic::server::NamedService for InternalServer<T> {
+        const NAME: &'static str = "internal.v1.Internal";
+    }
+}
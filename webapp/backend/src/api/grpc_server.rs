@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use log::{error, info};
+use tonic::service::interceptor::InterceptedService;
+use tonic::{Request, Response, Status};
+
+use crate::api::grpc_proto::get_dispatcher_request::Key;
+use crate::api::grpc_proto::internal_server::{Internal, InternalServer};
+use crate::api::grpc_proto::{
+    Dispatcher, GetDispatcherRequest, ListOnDutyDispatchersRequest, ListOnDutyDispatchersResponse,
+    OnDutyDispatcher, ValidateSessionRequest, ValidateSessionResponse,
+};
+use crate::app_state::AppAuthService;
+use crate::config::GrpcConfig;
+use crate::errors::AppError;
+use crate::utils::constant_time_eq;
+
+// HTTP の API と同じ AuthService を使い、内部コンポーネントからの問い合わせに答える
+#[derive(Debug)]
+pub struct InternalGrpcService {
+    auth_service: Arc<AppAuthService>,
+}
+
+#[tonic::async_trait]
+impl Internal for InternalGrpcService {
+    async fn validate_session(
+        &self,
+        request: Request<ValidateSessionRequest>,
+    ) -> Result<Response<ValidateSessionResponse>, Status> {
+        let (session, role) = self
+            .auth_service
+            .authenticate_with_role(&request.get_ref().session_token)
+            .await
+            .map_err(to_status)?;
+
+        Ok(Response::new(ValidateSessionResponse {
+            user_id: session.user_id,
+            role: role.as_str().to_string(),
+            expires_at: session.expires_at.timestamp(),
+        }))
+    }
+
+    async fn get_dispatcher(
+        &self,
+        request: Request<GetDispatcherRequest>,
+    ) -> Result<Response<Dispatcher>, Status> {
+        let dispatcher = match request.get_ref().key {
+            Some(Key::DispatcherId(dispatcher_id)) => {
+                self.auth_service.get_dispatcher(dispatcher_id).await
+            }
+            Some(Key::UserId(user_id)) => {
+                self.auth_service.get_dispatcher_by_user_id(user_id).await
+            }
+            None => Err(AppError::BadRequest),
+        }
+        .map_err(to_status)?;
+
+        Ok(Response::new(Dispatcher {
+            id: dispatcher.id,
+            user_id: dispatcher.user_id,
+            area_id: dispatcher.area_id,
+        }))
+    }
+
+    async fn list_on_duty_dispatchers(
+        &self,
+        request: Request<ListOnDutyDispatchersRequest>,
+    ) -> Result<Response<ListOnDutyDispatchersResponse>, Status> {
+        let dispatchers = self
+            .auth_service
+            .get_on_duty_dispatchers(request.get_ref().area_id)
+            .await
+            .map_err(to_status)?;
+
+        Ok(Response::new(ListOnDutyDispatchersResponse {
+            dispatchers: dispatchers
+                .into_iter()
+                .map(|dispatcher| OnDutyDispatcher {
+                    dispatcher_id: dispatcher.dispatcher_id,
+                    user_id: dispatcher.user_id,
+                    username: dispatcher.username,
+                })
+                .collect(),
+        }))
+    }
+}
+
+fn to_status(err: AppError) -> Status {
+    match err.root() {
+        AppError::BadRequest => Status::invalid_argument("bad request"),
+        AppError::OutsideServiceArea(_) => Status::invalid_argument("outside service area"),
+        AppError::Unauthorized => Status::unauthenticated("invalid session"),
+        AppError::Forbidden => Status::permission_denied("forbidden"),
+        AppError::NotFound => Status::not_found("not found"),
+        AppError::ServiceUnavailable(_) => Status::unavailable("service unavailable"),
+        AppError::GatewayTimeout => Status::deadline_exceeded("deadline exceeded"),
+        _ => {
+            error!("gRPC の呼び出しに失敗しました: {}", err.chain());
+            Status::internal("internal server error")
+        }
+    }
+}
+
+// HTTP のサーバーとは別のポートで待ち受ける。停止するとエラーをログに残して終わる
+pub async fn serve(config: GrpcConfig, auth_service: Arc<AppAuthService>) {
+    info!("gRPC サーバーを {} で起動します", config.addr);
+    let auth_token = config.auth_token;
+    // 呼び出し元は authorization メタデータに共有トークンを Bearer として付ける。
+    // 戻り値の型は tonic のインターセプターとして決められている
+    #[allow(clippy::result_large_err)]
+    let check_token = move |request: Request<()>| {
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match token {
+            Some(token) if constant_time_eq(token.as_bytes(), auth_token.as_bytes()) => Ok(request),
+            _ => Err(Status::unauthenticated("invalid token")),
+        }
+    };
+    let service = InternalServer::new(InternalGrpcService { auth_service });
+    if let Err(err) = tonic::transport::Server::builder()
+        .add_service(InterceptedService::new(service, check_token))
+        .serve(config.addr)
+        .await
+    {
+        error!("gRPC サーバーが停止しました: {:?}", err);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::errors::AppError;
-use actix_web::HttpResponse;
+use crate::infrastructure::readiness::Readiness;
+use actix_web::{web, HttpResponse};
 use serde::Serialize;
 
 #[derive(Serialize)]
@@ -12,3 +13,18 @@ pub async fn health_check_handler() -> Result<HttpResponse, AppError> {
         status: "OK".to_string(),
     }))
 }
+
+// プロセスが動いているかは health_check で、リクエストを受けてよいかはこちらで確認する
+pub async fn readiness_handler(readiness: web::Data<Readiness>) -> Result<HttpResponse, AppError> {
+    if !readiness.is_ready() {
+        return Ok(
+            HttpResponse::ServiceUnavailable().json(HealthCheckResponse {
+                status: "WARMING_UP".to_string(),
+            }),
+        );
+    }
+
+    Ok(HttpResponse::Ok().json(HealthCheckResponse {
+        status: "READY".to_string(),
+    }))
+}
@@ -0,0 +1,101 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::http::header::{
+    self, CacheControl, CacheDirective, HttpDate, IfModifiedSince, LastModified,
+};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder};
+use chrono::{DateTime, Utc};
+
+use crate::utils::{compute_etag, if_none_match_satisfied};
+
+// 公開されている読み取り専用のレスポンスに付けるキャッシュの方針
+#[derive(Debug, Clone, Copy)]
+pub enum CachePolicy {
+    // リバースプロキシとブラウザの両方でキャッシュしてよい
+    Public(Duration),
+    // キャッシュしてよいが、使う前に必ず再検証させる
+    Revalidate,
+    // ブラウザだけでキャッシュさせる。セッションで認可したレスポンスを共有のキャッシュに残さない
+    Private(Duration),
+}
+
+impl CachePolicy {
+    fn cache_control(&self) -> CacheControl {
+        match self {
+            CachePolicy::Public(max_age) => CacheControl(vec![
+                CacheDirective::Public,
+                CacheDirective::MaxAge(max_age.as_secs() as u32),
+            ]),
+            CachePolicy::Revalidate => CacheControl(vec![CacheDirective::NoCache]),
+            CachePolicy::Private(max_age) => CacheControl(vec![
+                CacheDirective::Private,
+                CacheDirective::MaxAge(max_age.as_secs() as u32),
+            ]),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CacheValidators {
+    etag: String,
+    last_modified: Option<SystemTime>,
+}
+
+impl CacheValidators {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        CacheValidators {
+            etag: compute_etag(bytes),
+            last_modified: None,
+        }
+    }
+
+    // Last-Modified は秒単位でしか表せないため、比較できるよう切り捨てておく
+    pub fn last_modified(mut self, last_modified: DateTime<Utc>) -> Self {
+        let secs = last_modified.timestamp().max(0) as u64;
+        self.last_modified = Some(UNIX_EPOCH + Duration::from_secs(secs));
+        self
+    }
+
+    // If-None-Match がある場合は If-Modified-Since より優先する (RFC 9110 13.2.2)
+    pub fn is_not_modified(&self, req: &HttpRequest) -> bool {
+        if let Some(if_none_match) = req
+            .headers()
+            .get(header::IF_NONE_MATCH)
+            .and_then(|h| h.to_str().ok())
+        {
+            return if_none_match_satisfied(if_none_match, &self.etag);
+        }
+
+        match (self.last_modified, req.get_header::<IfModifiedSince>()) {
+            (Some(last_modified), Some(IfModifiedSince(since))) => {
+                last_modified <= SystemTime::from(since)
+            }
+            _ => false,
+        }
+    }
+}
+
+// 条件付きリクエストで変更がなければ 304 を返し、そうでなければ build で組み立てたレスポンスにキャッシュ用のヘッダーを付ける
+pub fn cached_response(
+    req: &HttpRequest,
+    policy: CachePolicy,
+    validators: &CacheValidators,
+    build: impl FnOnce(&mut HttpResponseBuilder) -> HttpResponse,
+) -> HttpResponse {
+    let is_not_modified = validators.is_not_modified(req);
+    let mut builder = match is_not_modified {
+        true => HttpResponse::NotModified(),
+        false => HttpResponse::Ok(),
+    };
+    builder
+        .insert_header(policy.cache_control())
+        .insert_header((header::ETAG, validators.etag.clone()));
+    if let Some(last_modified) = validators.last_modified {
+        builder.insert_header(LastModified(HttpDate::from(last_modified)));
+    }
+
+    match is_not_modified {
+        true => builder.finish(),
+        false => build(&mut builder),
+    }
+}
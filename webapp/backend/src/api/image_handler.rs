@@ -0,0 +1,238 @@
+use crate::api::http_cache::{cached_response, CachePolicy, CacheValidators};
+use crate::app_state::{AppAuthService, AppImageService};
+use crate::config::{AppConfig, ImageOffloadMode};
+use crate::domains::dto::auth::ResizeMode;
+use crate::domains::image_service::ProfileImage;
+use crate::errors::{AppError, ResultExt};
+use crate::models::organization::OrganizationScope;
+use crate::models::user::Session;
+use crate::utils::{generate_session_token, session_token_from_request};
+use actix_multipart::Multipart;
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use futures_util::StreamExt;
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// 版を含む URL の画像は変わらないため、1 年間キャッシュさせる
+const VERSIONED_IMAGE_MAX_AGE: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+#[derive(Deserialize, Debug)]
+pub struct UserProfileImageQueryParams {
+    w: Option<i32>,
+    h: Option<i32>,
+    mode: Option<ResizeMode>,
+    // API が返す画像の URL に付く image_version。現在の版と一致すれば長期間キャッシュさせる
+    v: Option<i32>,
+    // 署名付きの URL の期限 (UNIX 時刻の秒) と署名
+    exp: Option<i64>,
+    sig: Option<String>,
+}
+
+// 画像の取得を認めた根拠。共有のキャッシュに残してよい期間が変わる
+enum ImageAccess {
+    // 署名を行わない設定で、誰でも取得できる
+    Public,
+    // 署名付きの URL。期限を過ぎたら CDN などのキャッシュからも使わせない
+    Signed { expires_in: Duration },
+    Session,
+}
+
+impl ImageAccess {
+    // 版を含む URL の画像は変わらないため長期間キャッシュさせ、
+    // 版を指定しない URL は、アップロードで画像が差し替わるため毎回 ETag で再検証させる
+    fn cache_policy(&self, versioned: bool) -> CachePolicy {
+        let max_age = match versioned {
+            true => VERSIONED_IMAGE_MAX_AGE,
+            false => Duration::ZERO,
+        };
+        match self {
+            ImageAccess::Public if versioned => CachePolicy::Public(max_age),
+            ImageAccess::Public => CachePolicy::Revalidate,
+            ImageAccess::Signed { expires_in } if versioned => {
+                CachePolicy::Public(max_age.min(*expires_in))
+            }
+            ImageAccess::Signed { .. } => CachePolicy::Revalidate,
+            ImageAccess::Session => CachePolicy::Private(max_age),
+        }
+    }
+}
+
+// 署名を行う設定の場合は、有効な署名か、ログインしているユーザーのセッションを求める
+async fn authorize_image_access(
+    service: &AppImageService,
+    auth_service: &AppAuthService,
+    config: &AppConfig,
+    http_req: &HttpRequest,
+    user_id: i32,
+    query: &UserProfileImageQueryParams,
+) -> Result<ImageAccess, AppError> {
+    if !service.requires_authorization() {
+        return Ok(ImageAccess::Public);
+    }
+    if let (Some(expires_at), Some(signature)) = (query.exp, &query.sig) {
+        if service.verify_signed_url(user_id, expires_at, signature) {
+            let expires_in = (expires_at - Utc::now().timestamp()).max(0) as u64;
+            return Ok(ImageAccess::Signed {
+                expires_in: Duration::from_secs(expires_in),
+            });
+        }
+    }
+
+    let token =
+        session_token_from_request(http_req, &config.session).ok_or(AppError::Unauthorized)?;
+    auth_service
+        .authenticate(&token)
+        .await
+        .map_err(|_| AppError::Unauthorized)?;
+
+    Ok(ImageAccess::Session)
+}
+
+pub async fn user_profile_image_handler(
+    service: web::Data<AppImageService>,
+    auth_service: web::Data<AppAuthService>,
+    config: web::Data<AppConfig>,
+    http_req: HttpRequest,
+    path: web::Path<i32>,
+    query: web::Query<UserProfileImageQueryParams>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = path.into_inner();
+    let access =
+        authorize_image_access(&service, &auth_service, &config, &http_req, user_id, &query)
+            .await?;
+    let width = query.w.unwrap_or(500);
+    let height = query.h.unwrap_or(500);
+    let mode = query.mode.unwrap_or_default();
+
+    let offload = &config.image_offload;
+    if offload.mode != ImageOffloadMode::Disabled {
+        let thumbnail_name = service
+            .get_offloaded_profile_image_name(user_id, width, height, mode, &offload.public_dir)
+            .await?;
+        let location = format!("{}/{}", offload.url_prefix, thumbnail_name);
+
+        return Ok(match offload.mode {
+            ImageOffloadMode::XAccelRedirect => HttpResponse::Ok()
+                .content_type("image/png")
+                .insert_header(("X-Accel-Redirect", location))
+                .finish(),
+            _ => HttpResponse::Found()
+                .insert_header((header::LOCATION, location))
+                .finish(),
+        });
+    }
+    let (image_name, thumbnail_name, policy) = match service
+        .find_profile_image(user_id, width, height, mode)
+        .await?
+    {
+        ProfileImage::Default(avatar) => {
+            let validators = CacheValidators::from_bytes(&avatar);
+            return Ok(cached_response(
+                &http_req,
+                access.cache_policy(false),
+                &validators,
+                |builder| builder.content_type("image/png").body(avatar),
+            ));
+        }
+        ProfileImage::Stored {
+            image_name,
+            image_version,
+            thumbnail_name,
+        } => {
+            let policy = access.cache_policy(query.v == Some(image_version));
+            (image_name, thumbnail_name, policy)
+        }
+    };
+
+    // 変換結果は送りながら作るため中身から ETag を求められない。
+    // 画像のファイル名はアップロードのたびに変わるため、サムネイルのファイル名から求める
+    let validators = CacheValidators::from_bytes(thumbnail_name.as_bytes());
+    if validators.is_not_modified(&http_req) {
+        return Ok(cached_response(&http_req, policy, &validators, |builder| {
+            builder.finish()
+        }));
+    }
+    let body = service
+        .stream_profile_image(&image_name, width, height, mode)
+        .await?;
+
+    Ok(cached_response(&http_req, policy, &validators, |builder| {
+        builder.content_type("image/png").streaming(body)
+    }))
+}
+
+// CDN やメールに載せる、セッションなしで画像を取得できる期限付きの URL を発行する
+pub async fn signed_profile_image_url_handler(
+    service: web::Data<AppImageService>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let url = service
+        .issue_signed_profile_image_url(path.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(url))
+}
+
+// 同時に大きな画像がアップロードされてもメモリを圧迫しないよう、受信したチャンクを順に一時ファイルへ書き出す
+pub async fn upload_profile_image_handler(
+    service: web::Data<AppImageService>,
+    config: web::Data<AppConfig>,
+    session: web::ReqData<Session>,
+    organization: web::ReqData<OrganizationScope>,
+    mut payload: Multipart,
+) -> Result<HttpResponse, AppError> {
+    let upload = TempUpload::new(&config.payload.upload_tmp_dir);
+    let mut file = upload.open()?;
+    let mut written = 0;
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(|_| AppError::BadRequest)?;
+        if field.name() != Some("image") {
+            continue;
+        }
+
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk.map_err(|_| AppError::BadRequest)?;
+            written += chunk.len();
+            if written > config.payload.profile_image_limit {
+                return Err(AppError::PayloadTooLarge);
+            }
+            file.write_all(&chunk)
+                .context_with_id("image_handler.write_upload", session.user_id.into())?;
+        }
+    }
+    drop(file);
+
+    let profile_image = service
+        .upload_profile_image(*organization, session.user_id, &upload.path)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(profile_image))
+}
+
+// 処理が途中で失敗した場合も含め、スコープを抜けたら一時ファイルを削除する
+struct TempUpload {
+    path: PathBuf,
+}
+
+impl TempUpload {
+    fn new(dir: &Path) -> Self {
+        TempUpload {
+            path: dir.join(format!("upload_{}", generate_session_token())),
+        }
+    }
+
+    fn open(&self) -> Result<File, AppError> {
+        File::create(&self.path).context("image_handler.create_temp_upload")
+    }
+}
+
+impl Drop for TempUpload {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
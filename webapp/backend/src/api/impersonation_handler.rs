@@ -0,0 +1,89 @@
+use crate::app_state::{AppAuditLogService, AppAuthService};
+use crate::domains::dto::auth::ImpersonateUserRequestDto;
+use crate::errors::AppError;
+use crate::models::audit_log::NewAuditLog;
+use crate::models::organization::OrganizationScope;
+use crate::models::user::Session;
+use crate::utils::session_client_from_request;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde_json::json;
+
+pub async fn impersonate_user_handler(
+    service: web::Data<AppAuthService>,
+    audit_log: web::Data<AppAuditLogService>,
+    session: web::ReqData<Session>,
+    organization: web::ReqData<OrganizationScope>,
+    http_req: HttpRequest,
+    req: web::Json<ImpersonateUserRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let reason = req.reason.trim();
+    if reason.is_empty() {
+        return Err(AppError::BadRequest);
+    }
+
+    let impersonation = service
+        .impersonate_user(
+            &session,
+            *organization,
+            req.user_id,
+            &session_client_from_request(&http_req),
+        )
+        .await?;
+    // 監査ログに残せなかったなりかわりは使わせない
+    if let Err(err) = audit_log
+        .record(NewAuditLog {
+            actor_user_id: Some(session.user_id),
+            action: "impersonation.start",
+            target_type: Some("user"),
+            target_id: Some(impersonation.user_id.to_string()),
+            detail: Some(json!({
+                "session_id": impersonation.session_id,
+                "reason": reason,
+                "expires_at": impersonation.expires_at,
+            })),
+        })
+        .await
+    {
+        service
+            .revoke_impersonation(*organization, impersonation.session_id)
+            .await?;
+        return Err(err);
+    }
+
+    Ok(HttpResponse::Created().json(impersonation))
+}
+
+pub async fn get_impersonations_handler(
+    service: web::Data<AppAuthService>,
+    organization: web::ReqData<OrganizationScope>,
+) -> Result<HttpResponse, AppError> {
+    let impersonations = service.get_impersonations(*organization).await?;
+
+    Ok(HttpResponse::Ok().json(impersonations))
+}
+
+pub async fn revoke_impersonation_handler(
+    service: web::Data<AppAuthService>,
+    audit_log: web::Data<AppAuditLogService>,
+    session: web::ReqData<Session>,
+    organization: web::ReqData<OrganizationScope>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let revoked = service
+        .revoke_impersonation(*organization, path.into_inner())
+        .await?;
+    audit_log
+        .record(NewAuditLog {
+            actor_user_id: Some(session.user_id),
+            action: "impersonation.revoke",
+            target_type: Some("user"),
+            target_id: Some(revoked.user_id.to_string()),
+            detail: Some(json!({
+                "session_id": revoked.id,
+                "impersonator_user_id": revoked.impersonator_user_id,
+            })),
+        })
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
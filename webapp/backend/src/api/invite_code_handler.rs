@@ -0,0 +1,34 @@
+use crate::app_state::AppInviteCodeService;
+use crate::domains::dto::invite_code::CreateInviteCodeRequestDto;
+use crate::errors::AppError;
+use crate::models::user::Session;
+use actix_web::{web, HttpResponse};
+
+pub async fn create_invite_code_handler(
+    service: web::Data<AppInviteCodeService>,
+    session: web::ReqData<Session>,
+    req: web::Json<CreateInviteCodeRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let invite_code = service
+        .issue_invite_code(req.into_inner(), session.user_id)
+        .await?;
+
+    Ok(HttpResponse::Created().json(invite_code))
+}
+
+pub async fn get_invite_codes_handler(
+    service: web::Data<AppInviteCodeService>,
+) -> Result<HttpResponse, AppError> {
+    let invite_codes = service.get_invite_codes().await?;
+
+    Ok(HttpResponse::Ok().json(invite_codes))
+}
+
+pub async fn revoke_invite_code_handler(
+    service: web::Data<AppInviteCodeService>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    service.revoke_invite_code(path.into_inner()).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
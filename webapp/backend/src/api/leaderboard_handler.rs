@@ -0,0 +1,15 @@
+use crate::app_state::AppLeaderboardService;
+use crate::domains::dto::leaderboard::LeaderboardQueryDto;
+use crate::errors::AppError;
+use actix_web::{web, HttpResponse};
+
+pub async fn get_leaderboard_handler(
+    service: web::Data<AppLeaderboardService>,
+    query: web::Query<LeaderboardQueryDto>,
+) -> Result<HttpResponse, AppError> {
+    let leaderboard = service
+        .get_leaderboard(query.page.unwrap_or(0), query.page_size.unwrap_or(20))
+        .await?;
+
+    Ok(HttpResponse::Ok().json(leaderboard))
+}
@@ -1,19 +1,67 @@
+use crate::api::http_cache::{cached_response, CachePolicy, CacheValidators};
+use crate::app_state::AppMapService;
+use crate::config::AppConfig;
+use crate::models::organization::OrganizationScope;
 use crate::{
-    domains::{dto::map::UpdateEdgeRequestDto, map_service::MapService},
+    domains::dto::map::{TravelTimeRequestDto, UpdateAreaBoundaryRequestDto, UpdateEdgeRequestDto},
     errors::AppError,
-    repositories::map_repository::MapRepositoryImpl,
 };
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
+
+// 組織ごとにエリアが異なるため、共有のキャッシュには残さない
+pub async fn get_areas_handler(
+    service: web::Data<AppMapService>,
+    config: web::Data<AppConfig>,
+    organization: web::ReqData<OrganizationScope>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let area_list = service.get_organization_areas(*organization).await?;
+    let body = serde_json::to_vec(&area_list.areas).map_err(|_| AppError::InternalServerError)?;
+    let validators = CacheValidators::from_bytes(&body).last_modified(area_list.loaded_at);
+
+    Ok(cached_response(
+        &http_req,
+        CachePolicy::Private(config.http_cache.static_max_age),
+        &validators,
+        |builder| builder.content_type("application/json").body(body),
+    ))
+}
 
 pub async fn update_edge_handler(
-    service: web::Data<MapService<MapRepositoryImpl>>,
+    service: web::Data<AppMapService>,
+    organization: web::ReqData<OrganizationScope>,
     req: web::Json<UpdateEdgeRequestDto>,
 ) -> Result<HttpResponse, AppError> {
     match service
-        .update_edge(req.node_a_id, req.node_b_id, req.weight)
+        .update_edge(*organization, req.node_a_id, req.node_b_id, req.weight)
         .await
     {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
         Err(err) => Err(err),
     }
 }
+
+pub async fn update_area_boundary_handler(
+    service: web::Data<AppMapService>,
+    organization: web::ReqData<OrganizationScope>,
+    path: web::Path<i32>,
+    req: web::Json<UpdateAreaBoundaryRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .update_area_boundary(*organization, path.into_inner(), req.into_inner().boundary)
+        .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub async fn get_travel_times_handler(
+    service: web::Data<AppMapService>,
+    organization: web::ReqData<OrganizationScope>,
+    req: web::Json<TravelTimeRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let travel_times = service
+        .get_travel_times(*organization, req.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(travel_times))
+}
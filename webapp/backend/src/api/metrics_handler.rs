@@ -0,0 +1,50 @@
+use crate::config::AppConfig;
+use crate::domains::notification_hub::NotificationHub;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::infrastructure::metrics::{collect_pool_metrics, PoolMetrics, RepositoryMethodMetrics};
+use crate::infrastructure::worker_pool::{WorkerPoolMetrics, WorkerPools};
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+
+#[derive(Serialize, Debug)]
+struct MetricsResponse {
+    db_pool: PoolMetrics,
+    db_replica_pool: Option<PoolMetrics>,
+    repository_methods: Vec<RepositoryMethodMetrics>,
+    db_circuit_breaker: &'static str,
+    // WebSocket で通知を受け取っているユーザー数
+    notification_connected_users: usize,
+    // 画像の変換やパスワードのハッシュ化を行うプールの実行中・待ちの数と待ち時間
+    worker_pools: Vec<WorkerPoolMetrics>,
+}
+
+pub async fn metrics_handler(
+    pools: web::Data<DbPools>,
+    config: web::Data<AppConfig>,
+    notification_hub: web::Data<NotificationHub>,
+    worker_pools: web::Data<WorkerPools>,
+) -> Result<HttpResponse, AppError> {
+    let max_connections = config.db.max_connections;
+    let db_pool = collect_pool_metrics(
+        &pools.primary,
+        max_connections,
+        &pools.primary_acquire_metrics,
+    );
+    let db_replica_pool = config.db.replica_url.as_ref().map(|_| {
+        collect_pool_metrics(
+            &pools.replica,
+            max_connections,
+            &pools.replica_acquire_metrics,
+        )
+    });
+
+    Ok(HttpResponse::Ok().json(MetricsResponse {
+        db_pool,
+        db_replica_pool,
+        repository_methods: pools.query_metrics.snapshot(),
+        db_circuit_breaker: pools.circuit_breaker.state_name(),
+        notification_connected_users: notification_hub.connected_users(),
+        worker_pools: worker_pools.metrics(),
+    }))
+}
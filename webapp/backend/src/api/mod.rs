@@ -1,5 +1,35 @@
+pub mod admin_handler;
 pub mod auth_handler;
+pub mod chat_handler;
+pub mod client_handler;
+pub mod debug_handler;
+pub mod dispatcher_handler;
+pub mod feature_flag_handler;
+pub mod geocoding_handler;
+pub mod graphql_handler;
+pub mod graphql_schema;
+pub mod grpc_proto;
+pub mod grpc_server;
 pub mod health_check_handler;
+pub mod http_cache;
+pub mod image_handler;
+pub mod impersonation_handler;
+pub mod invite_code_handler;
+pub mod leaderboard_handler;
 pub mod map_handler;
+pub mod metrics_handler;
+pub mod notification_handler;
+pub mod oidc_handler;
 pub mod order_handler;
+pub mod order_note_handler;
+pub mod organization_handler;
+pub mod profile_handler;
+pub mod quota_handler;
+pub mod runtime_config_handler;
+pub mod stats_handler;
+pub mod sync_handler;
 pub mod tow_truck_handler;
+pub mod vehicle_handler;
+pub mod versioning;
+pub mod webhook_handler;
+pub mod websocket;
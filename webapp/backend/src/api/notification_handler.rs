@@ -0,0 +1,83 @@
+use crate::api::websocket;
+use crate::app_state::AppNotificationService;
+use crate::domains::dto::notification::{
+    DeadLetterQueryDto, PushTokenRequestDto, UpdateNotificationPreferencesRequestDto,
+};
+use crate::domains::notification_hub::NotificationHub;
+use crate::errors::AppError;
+use crate::models::user::Session;
+use actix_web::{web, HttpRequest, HttpResponse};
+
+pub async fn get_notification_preferences_handler(
+    service: web::Data<AppNotificationService>,
+    session: web::ReqData<Session>,
+) -> Result<HttpResponse, AppError> {
+    let preferences = service.get_preferences(session.user_id).await?;
+
+    Ok(HttpResponse::Ok().json(preferences))
+}
+
+pub async fn update_notification_preferences_handler(
+    service: web::Data<AppNotificationService>,
+    session: web::ReqData<Session>,
+    req: web::Json<UpdateNotificationPreferencesRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let preferences = service
+        .update_preferences(session.user_id, req.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(preferences))
+}
+
+pub async fn register_push_token_handler(
+    service: web::Data<AppNotificationService>,
+    session: web::ReqData<Session>,
+    req: web::Json<PushTokenRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .register_push_token(session.user_id, req.into_inner())
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn unregister_push_token_handler(
+    service: web::Data<AppNotificationService>,
+    session: web::ReqData<Session>,
+    req: web::Json<PushTokenRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .unregister_push_token(session.user_id, req.into_inner())
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn get_web_push_key_handler(
+    service: web::Data<AppNotificationService>,
+) -> Result<HttpResponse, AppError> {
+    let key = service.get_web_push_key()?;
+
+    Ok(HttpResponse::Ok().json(key))
+}
+
+pub async fn get_dead_letters_handler(
+    service: web::Data<AppNotificationService>,
+    query: web::Query<DeadLetterQueryDto>,
+) -> Result<HttpResponse, AppError> {
+    let dead_letters = service.get_dead_letters(query.limit.unwrap_or(100)).await?;
+
+    Ok(HttpResponse::Ok().json(dead_letters))
+}
+
+// WebSocket に切り替え、ログイン中のユーザー宛ての通知をテキストフレームで送り続ける
+pub async fn notification_ws_handler(
+    req: HttpRequest,
+    payload: web::Payload,
+    session: web::ReqData<Session>,
+    hub: web::Data<NotificationHub>,
+) -> Result<HttpResponse, AppError> {
+    let receiver = hub.subscribe(session.user_id);
+
+    websocket::upgrade(&req, payload, receiver, None)
+}
@@ -0,0 +1,102 @@
+use crate::api::auth_handler::session_response;
+use crate::api::versioning::ApiVersion;
+use crate::app_state::AppAuthService;
+use crate::config::AppConfig;
+use crate::errors::AppError;
+use crate::infrastructure::oidc::{OidcClient, PENDING_AUTHORIZATION_TTL};
+use crate::utils::{constant_time_eq, session_client_from_request};
+use actix_web::cookie::{time, Cookie, SameSite};
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+
+// 認可を始めたブラウザと同じブラウザからのコールバックだけを受け付けるため、state を Cookie にも保持させる。
+// 他人が始めた認可のコールバックを踏ませて、その人のアカウントでログインさせる攻撃を防ぐ
+const OIDC_STATE_COOKIE_NAME: &str = "oidc_state";
+
+#[derive(Deserialize, Debug)]
+pub struct OidcCallbackQueryParams {
+    code: String,
+    state: String,
+}
+
+pub async fn oidc_authorize_handler(
+    oidc_client: Option<web::Data<OidcClient>>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse, AppError> {
+    let oidc_client = match oidc_client {
+        Some(oidc_client) => oidc_client,
+        None => return Err(AppError::NotFound),
+    };
+
+    let (authorization_url, state) = oidc_client.authorization_url()?;
+    let mut cookie = state_cookie(&config, state);
+    cookie.set_max_age(time::Duration::seconds(
+        PENDING_AUTHORIZATION_TTL.as_secs() as i64
+    ));
+
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, authorization_url))
+        .cookie(cookie)
+        .finish())
+}
+
+pub async fn oidc_callback_handler(
+    oidc_client: Option<web::Data<OidcClient>>,
+    service: web::Data<AppAuthService>,
+    config: web::Data<AppConfig>,
+    version: ApiVersion,
+    http_req: HttpRequest,
+    query: web::Query<OidcCallbackQueryParams>,
+) -> Result<HttpResponse, AppError> {
+    let oidc_client = match oidc_client {
+        Some(oidc_client) => oidc_client,
+        None => return Err(AppError::NotFound),
+    };
+
+    match http_req.cookie(OIDC_STATE_COOKIE_NAME) {
+        Some(cookie) if constant_time_eq(cookie.value().as_bytes(), query.state.as_bytes()) => {}
+        _ => return Err(AppError::Unauthorized),
+    }
+
+    let user_info = oidc_client.exchange_code(&query.code, &query.state).await?;
+    let preferred_username = user_info
+        .preferred_username
+        .as_deref()
+        .or(user_info.email.as_deref());
+
+    match service
+        .login_with_external_identity(
+            oidc_client.provider(),
+            &user_info.sub,
+            preferred_username,
+            oidc_client.default_role(),
+            &session_client_from_request(&http_req),
+        )
+        .await
+    {
+        Ok(response) => {
+            let mut cookie = state_cookie(&config, String::new());
+            cookie.make_removal();
+            let mut builder = HttpResponse::Ok();
+            builder.cookie(cookie);
+            Ok(session_response(
+                builder,
+                &config.session,
+                version,
+                response,
+            ))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+// IdP からのリダイレクトはサイトをまたぐ遷移のため、Strict ではなく Lax にして送らせる
+fn state_cookie(config: &AppConfig, state: String) -> Cookie<'static> {
+    Cookie::build(OIDC_STATE_COOKIE_NAME, state)
+        .path("/")
+        .http_only(true)
+        .secure(config.session.cookie_secure)
+        .same_site(SameSite::Lax)
+        .finish()
+}
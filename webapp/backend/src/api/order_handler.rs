@@ -1,49 +1,58 @@
+use crate::app_state::{AppExportService, AppOrderService};
+use crate::domains::dto::export::ExportFormat;
 use crate::domains::dto::order::{
-    ClientOrderRequestDto, DispatcherOrderRequestDto, UpdateOrderStatusRequestDto,
+    CancelOrderRequestDto, ClientOrderRequestDto, CreateOrderRequestDto,
+    CreateScheduledOrderRequestDto, DispatcherOrderRequestDto, OrderSearchQueryDto,
+    UpdateOrderPriorityRequestDto, UpdateOrderStatusRequestDto, UpdateScheduledOrderRequestDto,
 };
-use crate::domains::order_service::OrderService;
+use crate::domains::export_service::ExportFilter;
 use crate::errors::AppError;
-use crate::repositories::auth_repository::AuthRepositoryImpl;
-use crate::repositories::map_repository::MapRepositoryImpl;
-use crate::repositories::order_repository::OrderRepositoryImpl;
-use crate::repositories::tow_truck_repository::TowTruckRepositoryImpl;
+use crate::models::organization::OrganizationScope;
+use crate::models::user::Session;
 use actix_web::{web, HttpResponse};
+use futures_util::TryStreamExt;
 use serde::Deserialize;
 
 pub async fn update_order_status_handler(
-    service: web::Data<
-        OrderService<
-            OrderRepositoryImpl,
-            TowTruckRepositoryImpl,
-            AuthRepositoryImpl,
-            MapRepositoryImpl,
-        >,
-    >,
+    service: web::Data<AppOrderService>,
+    organization: web::ReqData<OrganizationScope>,
     req: web::Json<UpdateOrderStatusRequestDto>,
 ) -> Result<HttpResponse, AppError> {
-    match service.update_order_status(req.order_id, &req.status).await {
+    match service
+        .update_order_status(*organization, req.order_id, &req.status)
+        .await
+    {
         Ok(_) => Ok(HttpResponse::Ok().finish()),
         Err(err) => Err(err),
     }
 }
 
 pub async fn get_order_handler(
-    service: web::Data<
-        OrderService<
-            OrderRepositoryImpl,
-            TowTruckRepositoryImpl,
-            AuthRepositoryImpl,
-            MapRepositoryImpl,
-        >,
-    >,
+    service: web::Data<AppOrderService>,
+    organization: web::ReqData<OrganizationScope>,
     path: web::Path<i32>,
 ) -> Result<HttpResponse, AppError> {
-    match service.get_order_by_id(path.into_inner()).await {
+    match service
+        .get_order_by_id(*organization, path.into_inner())
+        .await
+    {
         Ok(order) => Ok(HttpResponse::Ok().json(order)),
         Err(err) => Err(err),
     }
 }
 
+pub async fn get_dispatcher_dashboard_handler(
+    service: web::Data<AppOrderService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+) -> Result<HttpResponse, AppError> {
+    let dashboard = service
+        .get_dispatcher_dashboard(*organization, session.user_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(dashboard))
+}
+
 #[derive(Deserialize, Debug)]
 pub struct PaginatedOrderQuery {
     page: Option<i32>,
@@ -52,21 +61,36 @@ pub struct PaginatedOrderQuery {
     sort_order: Option<String>,
     status: Option<String>,
     area: Option<i32>,
+    // true の場合はページに分けず、条件に合うすべての注文を ID の順に書き出しながら返す
+    stream: Option<bool>,
+    format: Option<ExportFormat>,
 }
 
 pub async fn get_paginated_orders_handler(
-    service: web::Data<
-        OrderService<
-            OrderRepositoryImpl,
-            TowTruckRepositoryImpl,
-            AuthRepositoryImpl,
-            MapRepositoryImpl,
-        >,
-    >,
+    service: web::Data<AppOrderService>,
+    export_service: web::Data<AppExportService>,
+    organization: web::ReqData<OrganizationScope>,
     query: web::Query<PaginatedOrderQuery>,
 ) -> Result<HttpResponse, AppError> {
+    if query.stream.unwrap_or(false) {
+        let format = query.format.unwrap_or(ExportFormat::Ndjson);
+        let filter = ExportFilter {
+            organization_id: Some(organization.organization_id),
+            status: query.status.clone(),
+            area_id: query.area,
+        };
+        let body = export_service
+            .export_orders(filter, format)
+            .map_ok(web::Bytes::from);
+
+        return Ok(HttpResponse::Ok()
+            .content_type(format.content_type())
+            .streaming(body));
+    }
+
     match service
         .get_paginated_orders(
+            *organization,
             query.page.unwrap_or(0),
             query.page_size.unwrap_or(10),
             query.sort_by.clone(),
@@ -81,19 +105,25 @@ pub async fn get_paginated_orders_handler(
     }
 }
 
+pub async fn search_orders_handler(
+    service: web::Data<AppOrderService>,
+    organization: web::ReqData<OrganizationScope>,
+    query: web::Query<OrderSearchQueryDto>,
+) -> Result<HttpResponse, AppError> {
+    let orders = service
+        .search_orders(*organization, query.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(orders))
+}
+
 pub async fn create_client_order_handler(
-    service: web::Data<
-        OrderService<
-            OrderRepositoryImpl,
-            TowTruckRepositoryImpl,
-            AuthRepositoryImpl,
-            MapRepositoryImpl,
-        >,
-    >,
+    service: web::Data<AppOrderService>,
+    organization: web::ReqData<OrganizationScope>,
     req: web::Json<ClientOrderRequestDto>,
 ) -> Result<HttpResponse, AppError> {
     match service
-        .create_client_order(req.client_id, req.node_id, req.car_value)
+        .create_client_order(*organization, req.client_id, req.node_id, req.car_value)
         .await
     {
         Ok(_) => Ok(HttpResponse::Created().finish()),
@@ -101,23 +131,122 @@ pub async fn create_client_order_handler(
     }
 }
 
+pub async fn create_order_handler(
+    service: web::Data<AppOrderService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    req: web::Json<CreateOrderRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let quote = service
+        .create_order(
+            *organization,
+            req.pickup,
+            req.dropoff,
+            session.user_id,
+            req.car_value,
+        )
+        .await?;
+
+    Ok(HttpResponse::Created().json(quote))
+}
+
+pub async fn get_scheduled_orders_handler(
+    service: web::Data<AppOrderService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+) -> Result<HttpResponse, AppError> {
+    let orders = service
+        .get_scheduled_orders(*organization, session.user_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(orders))
+}
+
+pub async fn create_scheduled_order_handler(
+    service: web::Data<AppOrderService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    req: web::Json<CreateScheduledOrderRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let order = service
+        .create_scheduled_order(*organization, session.user_id, req.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Created().json(order))
+}
+
+pub async fn update_scheduled_order_handler(
+    service: web::Data<AppOrderService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+    req: web::Json<UpdateScheduledOrderRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let order = service
+        .update_scheduled_order(
+            *organization,
+            path.into_inner(),
+            req.into_inner(),
+            session.user_id,
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(order))
+}
+
+// 予約注文も通常の注文と同じくキャンセルできる
+pub async fn cancel_order_handler(
+    service: web::Data<AppOrderService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+    req: web::Json<CancelOrderRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .cancel_order(
+            *organization,
+            path.into_inner(),
+            &req.reason,
+            session.user_id,
+        )
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn update_order_priority_handler(
+    service: web::Data<AppOrderService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+    req: web::Json<UpdateOrderPriorityRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .update_order_priority(
+            *organization,
+            path.into_inner(),
+            &req.priority,
+            req.version,
+            session.user_id,
+        )
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
 pub async fn create_dispatcher_order_handler(
-    service: web::Data<
-        OrderService<
-            OrderRepositoryImpl,
-            TowTruckRepositoryImpl,
-            AuthRepositoryImpl,
-            MapRepositoryImpl,
-        >,
-    >,
+    service: web::Data<AppOrderService>,
+    organization: web::ReqData<OrganizationScope>,
     req: web::Json<DispatcherOrderRequestDto>,
 ) -> Result<HttpResponse, AppError> {
     match service
         .create_dispatcher_order(
+            *organization,
             req.order_id,
             req.dispatcher_id,
             req.tow_truck_id,
             req.order_time,
+            req.version,
         )
         .await
     {
@@ -0,0 +1,85 @@
+use crate::app_state::AppOrderNoteService;
+use crate::domains::dto::order_note::CreateOrderNoteRequestDto;
+use crate::errors::AppError;
+use crate::models::organization::OrganizationScope;
+use crate::models::user::Session;
+use actix_web::http::header;
+use actix_web::{web, HttpResponse};
+
+pub async fn get_order_notes_handler(
+    service: web::Data<AppOrderNoteService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let notes = service
+        .get_notes(*organization, path.into_inner(), session.user_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(notes))
+}
+
+pub async fn create_order_note_handler(
+    service: web::Data<AppOrderNoteService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+    req: web::Json<CreateOrderNoteRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let note = service
+        .add_note(
+            *organization,
+            path.into_inner(),
+            session.user_id,
+            req.into_inner(),
+        )
+        .await?;
+
+    Ok(HttpResponse::Created().json(note))
+}
+
+pub async fn get_order_attachments_handler(
+    service: web::Data<AppOrderNoteService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let attachments = service
+        .get_attachments(*organization, path.into_inner(), session.user_id)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(attachments))
+}
+
+// 添付ファイルは画像をそのまま本文として送る。大きさの上限はルートの PayloadConfig で設定する
+pub async fn upload_order_attachment_handler(
+    service: web::Data<AppOrderNoteService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    path: web::Path<i32>,
+    body: web::Bytes,
+) -> Result<HttpResponse, AppError> {
+    let attachment = service
+        .add_attachment(*organization, path.into_inner(), session.user_id, &body)
+        .await?;
+
+    Ok(HttpResponse::Created().json(attachment))
+}
+
+pub async fn get_order_attachment_handler(
+    service: web::Data<AppOrderNoteService>,
+    organization: web::ReqData<OrganizationScope>,
+    session: web::ReqData<Session>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, AppError> {
+    let (order_id, attachment_id) = path.into_inner();
+    let (content_type, bytes) = service
+        .get_attachment_content(*organization, order_id, attachment_id, session.user_id)
+        .await?;
+
+    // 関係者以外には見せないため、共有のキャッシュには保存させない
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((header::CACHE_CONTROL, "private, max-age=3600"))
+        .body(bytes))
+}
@@ -0,0 +1,41 @@
+use crate::app_state::AppOrganizationService;
+use crate::domains::dto::organization::{
+    CreateOrganizationRequestDto, UpdateUserOrganizationRequestDto,
+};
+use crate::errors::AppError;
+use crate::models::organization::OrganizationScope;
+use actix_web::{web, HttpResponse};
+
+pub async fn get_organizations_handler(
+    service: web::Data<AppOrganizationService>,
+    organization: web::ReqData<OrganizationScope>,
+) -> Result<HttpResponse, AppError> {
+    let organizations = service.get_organizations(*organization).await?;
+
+    Ok(HttpResponse::Ok().json(organizations))
+}
+
+pub async fn create_organization_handler(
+    service: web::Data<AppOrganizationService>,
+    organization: web::ReqData<OrganizationScope>,
+    req: web::Json<CreateOrganizationRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let created = service
+        .create_organization(*organization, req.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Created().json(created))
+}
+
+pub async fn update_user_organization_handler(
+    service: web::Data<AppOrganizationService>,
+    organization: web::ReqData<OrganizationScope>,
+    path: web::Path<i32>,
+    req: web::Json<UpdateUserOrganizationRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .move_user(*organization, path.into_inner(), req.organization_id)
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,26 @@
+use crate::app_state::AppProfileService;
+use crate::domains::dto::profile::UpdateProfileRequestDto;
+use crate::errors::AppError;
+use crate::models::user::Session;
+use actix_web::{web, HttpResponse};
+
+pub async fn get_profile_handler(
+    service: web::Data<AppProfileService>,
+    session: web::ReqData<Session>,
+) -> Result<HttpResponse, AppError> {
+    let profile = service.get_profile(session.user_id).await?;
+
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+pub async fn update_profile_handler(
+    service: web::Data<AppProfileService>,
+    session: web::ReqData<Session>,
+    req: web::Json<UpdateProfileRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let profile = service
+        .update_profile(session.user_id, req.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(profile))
+}
@@ -0,0 +1,31 @@
+use crate::app_state::AppQuotaService;
+use crate::domains::dto::quota::UpdateQuotaRequestDto;
+use crate::errors::AppError;
+use crate::models::organization::OrganizationScope;
+use actix_web::{web, HttpResponse};
+
+pub async fn get_usage_handler(
+    service: web::Data<AppQuotaService>,
+    organization: web::ReqData<OrganizationScope>,
+) -> Result<HttpResponse, AppError> {
+    let usage = service.get_usage(*organization).await?;
+
+    Ok(HttpResponse::Ok().json(usage))
+}
+
+pub async fn update_quota_handler(
+    service: web::Data<AppQuotaService>,
+    organization: web::ReqData<OrganizationScope>,
+    path: web::Path<i32>,
+    req: web::Json<UpdateQuotaRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    service
+        .update_quota(
+            *organization,
+            path.into_inner(),
+            req.into_inner().into_limits(),
+        )
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
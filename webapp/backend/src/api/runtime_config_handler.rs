@@ -0,0 +1,51 @@
+use crate::domains::dto::runtime_config::{
+    UpdateLogLevelRequestDto, UpdateRuntimeConfigRequestDto,
+};
+use crate::domains::runtime_config_service::RuntimeConfigService;
+use crate::errors::AppError;
+use actix_web::{web, HttpResponse};
+use serde_json::Value;
+
+pub async fn get_runtime_config_handler(
+    service: web::Data<RuntimeConfigService>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(service.get_config()))
+}
+
+pub async fn update_runtime_config_handler(
+    service: web::Data<RuntimeConfigService>,
+    req: web::Json<UpdateRuntimeConfigRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let changes = req
+        .into_inner()
+        .0
+        .into_iter()
+        .map(|(key, value)| match value {
+            Value::String(value) => Ok((key, value)),
+            Value::Number(value) => Ok((key, value.to_string())),
+            _ => Err(AppError::BadRequest),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let config = service.update(
+        changes
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.clone())),
+    )?;
+
+    Ok(HttpResponse::Ok().json(config))
+}
+
+pub async fn reload_runtime_config_handler(
+    service: web::Data<RuntimeConfigService>,
+) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok().json(service.reload_file()?))
+}
+
+pub async fn update_log_level_handler(
+    service: web::Data<RuntimeConfigService>,
+    req: web::Json<UpdateLogLevelRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let level = service.set_log_level(&req.level)?;
+
+    Ok(HttpResponse::Ok().json(level))
+}
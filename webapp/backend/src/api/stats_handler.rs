@@ -0,0 +1,13 @@
+use crate::app_state::AppStatsService;
+use crate::errors::AppError;
+use crate::models::organization::OrganizationScope;
+use actix_web::{web, HttpResponse};
+
+pub async fn get_stats_summary_handler(
+    service: web::Data<AppStatsService>,
+    organization: web::ReqData<OrganizationScope>,
+) -> Result<HttpResponse, AppError> {
+    let summary = service.get_summary(*organization).await?;
+
+    Ok(HttpResponse::Ok().json(summary))
+}
@@ -0,0 +1,17 @@
+use crate::app_state::AppSyncService;
+use crate::domains::dto::sync::SyncQueryDto;
+use crate::errors::AppError;
+use crate::models::organization::OrganizationScope;
+use actix_web::{web, HttpResponse};
+
+pub async fn get_sync_handler(
+    service: web::Data<AppSyncService>,
+    organization: web::ReqData<OrganizationScope>,
+    query: web::Query<SyncQueryDto>,
+) -> Result<HttpResponse, AppError> {
+    let changes = service
+        .get_changes(*organization, query.into_inner())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(changes))
+}
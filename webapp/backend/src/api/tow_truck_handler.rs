@@ -1,12 +1,11 @@
-use crate::domains::tow_truck_service::TowTruckService;
+use crate::app_state::{AppExportService, AppTowTruckService};
+use crate::domains::dto::export::ExportFormat;
+use crate::domains::dto::tow_truck::UpdateLocationRequestDto;
+use crate::domains::export_service::ExportFilter;
 use crate::errors::AppError;
-use crate::repositories::order_repository::OrderRepositoryImpl;
-use crate::repositories::tow_truck_repository::TowTruckRepositoryImpl;
-use crate::{
-    domains::dto::tow_truck::UpdateLocationRequestDto,
-    repositories::map_repository::MapRepositoryImpl,
-};
+use crate::models::organization::OrganizationScope;
 use actix_web::{web, HttpResponse};
+use futures_util::TryStreamExt;
 use serde::Deserialize;
 
 #[derive(Deserialize, Debug)]
@@ -15,16 +14,36 @@ pub struct PaginatedTowTruckQuery {
     page_size: Option<i32>,
     status: Option<String>,
     area: Option<i32>,
+    // true の場合はページに分けず、条件に合うすべての車両を ID の順に書き出しながら返す
+    stream: Option<bool>,
+    format: Option<ExportFormat>,
 }
 
 pub async fn get_paginated_tow_trucks_handler(
-    service: web::Data<
-        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
-    >,
+    service: web::Data<AppTowTruckService>,
+    export_service: web::Data<AppExportService>,
+    organization: web::ReqData<OrganizationScope>,
     query: web::Query<PaginatedTowTruckQuery>,
 ) -> Result<HttpResponse, AppError> {
+    if query.stream.unwrap_or(false) {
+        let format = query.format.unwrap_or(ExportFormat::Ndjson);
+        let filter = ExportFilter {
+            organization_id: Some(organization.organization_id),
+            status: query.status.clone(),
+            area_id: query.area,
+        };
+        let body = export_service
+            .export_tow_trucks(filter, format)
+            .map_ok(web::Bytes::from);
+
+        return Ok(HttpResponse::Ok()
+            .content_type(format.content_type())
+            .streaming(body));
+    }
+
     let tow_trucks = service
         .get_all_tow_trucks(
+            *organization,
             query.page.unwrap_or(0),
             query.page_size.unwrap_or(-1),
             query.status.clone(),
@@ -36,13 +55,12 @@ pub async fn get_paginated_tow_trucks_handler(
 }
 
 pub async fn get_tow_truck_handler(
-    service: web::Data<
-        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
-    >,
+    service: web::Data<AppTowTruckService>,
+    organization: web::ReqData<OrganizationScope>,
     path: web::Path<i32>,
 ) -> Result<HttpResponse, AppError> {
     let id = path.into_inner();
-    match service.get_tow_truck_by_id(id).await {
+    match service.get_tow_truck_by_id(*organization, id).await {
         Ok(Some(tow_truck)) => Ok(HttpResponse::Ok().json(tow_truck)),
         Ok(None) => Ok(HttpResponse::NotFound().finish()),
         Err(err) => Err(err),
@@ -50,13 +68,12 @@ pub async fn get_tow_truck_handler(
 }
 
 pub async fn update_location_handler(
-    service: web::Data<
-        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
-    >,
+    service: web::Data<AppTowTruckService>,
+    organization: web::ReqData<OrganizationScope>,
     req: web::Json<UpdateLocationRequestDto>,
 ) -> Result<HttpResponse, AppError> {
     service
-        .update_location(req.tow_truck_id, req.node_id)
+        .update_location(*organization, req.tow_truck_id, req.node_id)
         .await?;
     Ok(HttpResponse::Ok().finish())
 }
@@ -67,13 +84,12 @@ pub struct TowTruckQuery {
 }
 
 pub async fn get_nearest_available_tow_trucks_handler(
-    service: web::Data<
-        TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>,
-    >,
+    service: web::Data<AppTowTruckService>,
+    organization: web::ReqData<OrganizationScope>,
     query: web::Query<TowTruckQuery>,
 ) -> Result<HttpResponse, AppError> {
     match service
-        .get_nearest_available_tow_trucks(query.order_id)
+        .get_nearest_available_tow_trucks(*organization, query.order_id)
         .await
     {
         Ok(Some(tow_truck)) => Ok(HttpResponse::Ok().json(tow_truck)),
@@ -0,0 +1,66 @@
+use crate::app_state::AppVehicleService;
+use crate::domains::dto::vehicle::{
+    RegisterVehicleRequestDto, UpdateVehicleRequestDto, UpdateVehicleStatusRequestDto,
+};
+use crate::errors::AppError;
+use crate::models::vehicle::VehicleStatus;
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct VehicleListQuery {
+    area: Option<i32>,
+    status: Option<VehicleStatus>,
+}
+
+pub async fn register_vehicle_handler(
+    service: web::Data<AppVehicleService>,
+    req: web::Json<RegisterVehicleRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let vehicle = service.register_vehicle(&req).await?;
+    Ok(HttpResponse::Created().json(vehicle))
+}
+
+pub async fn get_vehicles_handler(
+    service: web::Data<AppVehicleService>,
+    query: web::Query<VehicleListQuery>,
+) -> Result<HttpResponse, AppError> {
+    let vehicles = service.get_vehicles(query.area, query.status).await?;
+    Ok(HttpResponse::Ok().json(vehicles))
+}
+
+pub async fn get_vehicle_handler(
+    service: web::Data<AppVehicleService>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    let vehicle = service.get_vehicle(path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(vehicle))
+}
+
+pub async fn update_vehicle_handler(
+    service: web::Data<AppVehicleService>,
+    path: web::Path<i32>,
+    req: web::Json<UpdateVehicleRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let vehicle = service.update_vehicle(path.into_inner(), &req).await?;
+    Ok(HttpResponse::Ok().json(vehicle))
+}
+
+pub async fn update_vehicle_status_handler(
+    service: web::Data<AppVehicleService>,
+    path: web::Path<i32>,
+    req: web::Json<UpdateVehicleStatusRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let vehicle = service
+        .update_vehicle_status(path.into_inner(), req.status)
+        .await?;
+    Ok(HttpResponse::Ok().json(vehicle))
+}
+
+pub async fn delete_vehicle_handler(
+    service: web::Data<AppVehicleService>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    service.delete_vehicle(path.into_inner()).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,36 @@
+use std::future::{ready, Ready};
+
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpRequest};
+
+use crate::errors::AppError;
+
+// /api/v1 と /api/v2 のどちらのスコープで受け付けたリクエストか。
+// スコープの app_data に登録し、レスポンスの形がバージョンで異なるハンドラだけが受け取る
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl FromRequest for ApiVersion {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(req
+            .app_data::<ApiVersion>()
+            .copied()
+            .unwrap_or(ApiVersion::V1)))
+    }
+}
+
+// パスから /v1 や /v2 を取り除く。API キーのスコープのように、バージョンなしのパスで登録された設定と比べるときに使う
+pub fn unversioned_path(path: &str) -> String {
+    for prefix in ["/api/v1/", "/api/v2/"] {
+        if let Some(rest) = path.strip_prefix(prefix) {
+            return format!("/api/{}", rest);
+        }
+    }
+    path.to_string()
+}
@@ -0,0 +1,42 @@
+use crate::app_state::AppWebhookService;
+use crate::domains::dto::webhook::{CreateWebhookSubscriptionRequestDto, WebhookDeliveryQueryDto};
+use crate::errors::AppError;
+use actix_web::{web, HttpResponse};
+
+pub async fn create_webhook_subscription_handler(
+    service: web::Data<AppWebhookService>,
+    req: web::Json<CreateWebhookSubscriptionRequestDto>,
+) -> Result<HttpResponse, AppError> {
+    let subscription = service.create_subscription(req.into_inner()).await?;
+
+    Ok(HttpResponse::Created().json(subscription))
+}
+
+pub async fn get_webhook_subscriptions_handler(
+    service: web::Data<AppWebhookService>,
+) -> Result<HttpResponse, AppError> {
+    let subscriptions = service.get_subscriptions().await?;
+
+    Ok(HttpResponse::Ok().json(subscriptions))
+}
+
+pub async fn delete_webhook_subscription_handler(
+    service: web::Data<AppWebhookService>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, AppError> {
+    service.delete_subscription(path.into_inner()).await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn get_webhook_deliveries_handler(
+    service: web::Data<AppWebhookService>,
+    path: web::Path<i32>,
+    query: web::Query<WebhookDeliveryQueryDto>,
+) -> Result<HttpResponse, AppError> {
+    let deliveries = service
+        .get_deliveries(path.into_inner(), query.limit.unwrap_or(100))
+        .await?;
+
+    Ok(HttpResponse::Ok().json(deliveries))
+}
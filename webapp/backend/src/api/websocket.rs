@@ -0,0 +1,99 @@
+use crate::errors::AppError;
+use actix_codec::{Decoder, Encoder};
+use actix_http::ws::{hash_key, verify_handshake, Codec, Frame, Message};
+use actix_web::http::header::{SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY};
+use actix_web::web::BytesMut;
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures_util::{future, stream, StreamExt};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+
+// WebSocket に切り替え、receiver に届いたメッセージをテキストフレームで送り続ける。
+// クライアントから届くテキストフレームは incoming があればそこへ順に渡し、それ以外は Ping と Close だけを扱う
+pub fn upgrade(
+    req: &HttpRequest,
+    mut payload: web::Payload,
+    receiver: broadcast::Receiver<String>,
+    incoming: Option<mpsc::UnboundedSender<String>>,
+) -> Result<HttpResponse, AppError> {
+    verify_handshake(req.head()).map_err(|_| AppError::BadRequest)?;
+    let accept = req
+        .headers()
+        .get(SEC_WEBSOCKET_KEY)
+        .map(|key| hash_key(key.as_bytes()))
+        .ok_or(AppError::BadRequest)?;
+
+    let (control_tx, control_rx) = mpsc::unbounded_channel::<Message>();
+    actix_web::rt::spawn(async move {
+        let mut codec = Codec::new();
+        let mut buffer = BytesMut::new();
+        while let Some(Ok(chunk)) = payload.next().await {
+            buffer.extend_from_slice(&chunk);
+            loop {
+                match codec.decode(&mut buffer) {
+                    Ok(Some(Frame::Ping(data))) => {
+                        let _ = control_tx.send(Message::Pong(data));
+                    }
+                    Ok(Some(Frame::Close(reason))) => {
+                        let _ = control_tx.send(Message::Close(reason));
+                        return;
+                    }
+                    Ok(Some(Frame::Text(text))) => {
+                        if let Some(incoming) = &incoming {
+                            match String::from_utf8(text.to_vec()) {
+                                Ok(text) => {
+                                    let _ = incoming.send(text);
+                                }
+                                Err(_) => {
+                                    let _ = control_tx.send(Message::Close(None));
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) => break,
+                    Err(_) => {
+                        let _ = control_tx.send(Message::Close(None));
+                        return;
+                    }
+                }
+            }
+        }
+        // 接続が切れた場合も送信側を終わらせる
+        let _ = control_tx.send(Message::Close(None));
+    });
+
+    let control = stream::unfold(control_rx, |mut control_rx| async move {
+        control_rx.recv().await.map(|message| (message, control_rx))
+    });
+    let messages = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(payload) => return Some((Message::Text(payload.into()), receiver)),
+                // 読み出しが遅れて捨てられたメッセージは飛ばして続ける
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    let mut codec = Codec::new();
+    let frames = stream::select(control, messages)
+        .scan(false, |closed, message| {
+            if *closed {
+                return future::ready(None);
+            }
+            *closed = matches!(message, Message::Close(_));
+            future::ready(Some(message))
+        })
+        .map(move |message| {
+            let mut buffer = BytesMut::new();
+            codec.encode(message, &mut buffer).map(|_| buffer.freeze())
+        });
+
+    Ok(HttpResponse::SwitchingProtocols()
+        .upgrade("websocket")
+        .insert_header((SEC_WEBSOCKET_ACCEPT, &accept[..]))
+        .streaming(frames))
+}
@@ -0,0 +1,928 @@
+use std::sync::Arc;
+
+use actix_web::web;
+use log::{info, warn};
+
+use crate::api::graphql_schema::GraphqlApi;
+use crate::config::{AppConfig, TunableConfig};
+use crate::domains::api_key_service::ApiKeyService;
+use crate::domains::audit_log_service::AuditLogService;
+use crate::domains::auth_service::AuthService;
+use crate::domains::backup_service::BackupService;
+use crate::domains::chat_hub::ChatHub;
+use crate::domains::chat_service::ChatService;
+use crate::domains::client_service::ClientService;
+use crate::domains::dispatcher_availability::DispatcherAvailability;
+use crate::domains::dispatcher_service::DispatcherService;
+use crate::domains::eta_service::EtaService;
+use crate::domains::events::DomainEvent;
+use crate::domains::export_service::ExportService;
+use crate::domains::feature_flag_service::FeatureFlagService;
+use crate::domains::fixture_service::FixtureService;
+use crate::domains::geocoding_service::GeocodingService;
+use crate::domains::geofence::Geofence;
+use crate::domains::image_moderation_service::ImageModerationService;
+use crate::domains::image_service::ImageService;
+use crate::domains::image_url_signer::ImageUrlSigner;
+use crate::domains::invite_code_service::InviteCodeService;
+use crate::domains::leaderboard_service::LeaderboardService;
+use crate::domains::login_anomaly_service::LoginAnomalyService;
+use crate::domains::map_service::MapService;
+use crate::domains::notification_hub::NotificationHub;
+use crate::domains::notification_service::NotificationService;
+use crate::domains::order_note_service::OrderNoteService;
+use crate::domains::order_service::OrderService;
+use crate::domains::organization_service::OrganizationService;
+use crate::domains::profile_service::ProfileService;
+use crate::domains::quota_service::QuotaService;
+use crate::domains::report_service::ReportService;
+use crate::domains::retention_service::RetentionService;
+use crate::domains::route_planner::RoutePlanner;
+use crate::domains::runtime_config_service::RuntimeConfigService;
+use crate::domains::state_snapshot::StateSnapshot;
+use crate::domains::stats_service::StatsService;
+use crate::domains::sync_service::SyncService;
+use crate::domains::tow_truck_service::TowTruckService;
+use crate::domains::user_import_service::UserImportService;
+use crate::domains::vehicle_service::VehicleService;
+use crate::domains::webhook_service::WebhookService;
+use crate::errors;
+use crate::infrastructure::db::{self, DbPools};
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::feature_flags::FeatureFlags;
+use crate::infrastructure::geocoder::GeocoderImpl;
+use crate::infrastructure::image_moderator::ImageModeratorImpl;
+use crate::infrastructure::image_store::ImageStoreImpl;
+use crate::infrastructure::index_advisor;
+use crate::infrastructure::job_runner::spawn_periodic_job;
+use crate::infrastructure::logger;
+use crate::infrastructure::mailer::MailerImpl;
+use crate::infrastructure::migrations;
+use crate::infrastructure::oidc::OidcClient;
+use crate::infrastructure::push::PushSenderImpl;
+use crate::infrastructure::readiness::Readiness;
+use crate::infrastructure::worker_pool::{WorkerPool, WorkerPools};
+use crate::repositories::api_key_repository::ApiKeyRepositoryImpl;
+use crate::repositories::audit_log_repository::AuditLogRepositoryImpl;
+use crate::repositories::auth_repository::AuthRepositoryImpl;
+use crate::repositories::auth_repository_backend::AuthRepositoryBackend;
+use crate::repositories::backup_repository::BackupRepositoryImpl;
+use crate::repositories::cached_auth_repository::CachedAuthRepository;
+use crate::repositories::chat_repository::ChatRepositoryImpl;
+use crate::repositories::client_repository::ClientRepositoryImpl;
+use crate::repositories::eta_repository::EtaRepositoryImpl;
+use crate::repositories::export_repository::ExportRepositoryImpl;
+use crate::repositories::feature_flag_repository::FeatureFlagRepositoryImpl;
+use crate::repositories::fixture_repository::FixtureRepositoryImpl;
+use crate::repositories::geocoding_repository::GeocodingRepositoryImpl;
+use crate::repositories::graphql_repository::GraphqlRepositoryImpl;
+use crate::repositories::invite_code_repository::InviteCodeRepositoryImpl;
+use crate::repositories::leaderboard_repository::LeaderboardRepositoryImpl;
+use crate::repositories::login_attempt_repository::LoginAttemptRepositoryImpl;
+use crate::repositories::map_repository::MapRepositoryImpl;
+use crate::repositories::memory_auth_repository::MemoryAuthRepository;
+use crate::repositories::notification_repository::NotificationRepositoryImpl;
+use crate::repositories::order_note_repository::OrderNoteRepositoryImpl;
+use crate::repositories::order_repository::OrderRepositoryImpl;
+use crate::repositories::organization_repository::OrganizationRepositoryImpl;
+use crate::repositories::profile_repository::ProfileRepositoryImpl;
+use crate::repositories::quota_repository::QuotaRepositoryImpl;
+use crate::repositories::report_repository::ReportRepositoryImpl;
+use crate::repositories::retention_repository::RetentionRepositoryImpl;
+use crate::repositories::stats_repository::StatsRepositoryImpl;
+use crate::repositories::sync_repository::SyncRepositoryImpl;
+use crate::repositories::tow_truck_repository::TowTruckRepositoryImpl;
+use crate::repositories::user_import_repository::UserImportRepositoryImpl;
+use crate::repositories::vehicle_repository::VehicleRepositoryImpl;
+use crate::repositories::webhook_repository::WebhookRepositoryImpl;
+
+pub type AppAuthService =
+    AuthService<CachedAuthRepository<AuthRepositoryBackend>, QuotaRepositoryImpl>;
+pub type AppApiKeyService = ApiKeyService<ApiKeyRepositoryImpl>;
+pub type AppTowTruckService =
+    TowTruckService<TowTruckRepositoryImpl, OrderRepositoryImpl, MapRepositoryImpl>;
+pub type AppOrderService = OrderService<
+    OrderRepositoryImpl,
+    TowTruckRepositoryImpl,
+    AuthRepositoryBackend,
+    MapRepositoryImpl,
+    QuotaRepositoryImpl,
+>;
+pub type AppOrderNoteService =
+    OrderNoteService<OrderNoteRepositoryImpl, AuthRepositoryBackend, ImageStoreImpl>;
+pub type AppChatService = ChatService<ChatRepositoryImpl>;
+pub type AppMapService = MapService<MapRepositoryImpl>;
+pub type AppRoutePlanner = RoutePlanner<MapRepositoryImpl>;
+pub type AppClientService =
+    ClientService<ClientRepositoryImpl, MapRepositoryImpl, QuotaRepositoryImpl>;
+pub type AppGeocodingService = GeocodingService<GeocodingRepositoryImpl>;
+pub type AppImageService = ImageService<AuthRepositoryBackend, ImageStoreImpl, QuotaRepositoryImpl>;
+pub type AppFixtureService = FixtureService<FixtureRepositoryImpl>;
+pub type AppExportService = ExportService<ExportRepositoryImpl>;
+pub type AppBackupService = BackupService<BackupRepositoryImpl>;
+pub type AppReportService = ReportService<ReportRepositoryImpl>;
+pub type AppRetentionService = RetentionService<RetentionRepositoryImpl, ImageStoreImpl>;
+pub type AppLeaderboardService = LeaderboardService<LeaderboardRepositoryImpl>;
+pub type AppStatsService = StatsService<StatsRepositoryImpl>;
+pub type AppSyncService = SyncService<SyncRepositoryImpl>;
+pub type AppDispatcherService = DispatcherService<AuthRepositoryBackend, OrderRepositoryImpl>;
+pub type AppEtaService = EtaService<EtaRepositoryImpl, MapRepositoryImpl>;
+pub type AppNotificationService = NotificationService<NotificationRepositoryImpl>;
+pub type AppProfileService = ProfileService<ProfileRepositoryImpl, NotificationRepositoryImpl>;
+pub type AppWebhookService = WebhookService<WebhookRepositoryImpl>;
+pub type AppInviteCodeService = InviteCodeService<InviteCodeRepositoryImpl>;
+pub type AppAuditLogService = AuditLogService<AuditLogRepositoryImpl>;
+pub type AppOrganizationService =
+    OrganizationService<OrganizationRepositoryImpl, AuthRepositoryBackend>;
+pub type AppQuotaService = QuotaService<QuotaRepositoryImpl>;
+pub type AppFeatureFlagService = FeatureFlagService<FeatureFlagRepositoryImpl>;
+pub type AppUserImportService = UserImportService<UserImportRepositoryImpl>;
+pub type AppVehicleService =
+    VehicleService<VehicleRepositoryImpl, AuthRepositoryBackend, MapRepositoryImpl>;
+
+// アプリケーション全体で共有するインスタンスをまとめたもの。
+// ミドルウェアとハンドラでキャッシュを共有する必要があるサービスは Arc で保持する
+#[derive(Debug)]
+pub struct AppState {
+    pub config: web::Data<AppConfig>,
+    pub pools: web::Data<DbPools>,
+    pub event_bus: Arc<EventBus>,
+    pub feature_flag_service: web::Data<AppFeatureFlagService>,
+    pub runtime_config_service: web::Data<RuntimeConfigService>,
+    pub auth_service: Arc<AppAuthService>,
+    pub api_key_service: Arc<AppApiKeyService>,
+    pub tow_truck_service: web::Data<AppTowTruckService>,
+    pub order_service: web::Data<AppOrderService>,
+    pub order_note_service: web::Data<AppOrderNoteService>,
+    pub chat_service: web::Data<AppChatService>,
+    pub map_service: web::Data<AppMapService>,
+    pub client_service: web::Data<AppClientService>,
+    pub geocoding_service: web::Data<AppGeocodingService>,
+    pub image_service: web::Data<AppImageService>,
+    pub vehicle_service: web::Data<AppVehicleService>,
+    pub export_service: web::Data<AppExportService>,
+    pub backup_service: web::Data<AppBackupService>,
+    pub report_service: web::Data<AppReportService>,
+    pub retention_service: web::Data<AppRetentionService>,
+    pub leaderboard_service: web::Data<AppLeaderboardService>,
+    pub stats_service: web::Data<AppStatsService>,
+    pub sync_service: web::Data<AppSyncService>,
+    pub dispatcher_service: web::Data<AppDispatcherService>,
+    pub eta_service: Arc<AppEtaService>,
+    pub route_planner: Arc<AppRoutePlanner>,
+    pub availability: Arc<DispatcherAvailability>,
+    pub readiness: Arc<Readiness>,
+    pub worker_pools: Arc<WorkerPools>,
+    pub user_import_service: web::Data<AppUserImportService>,
+    pub notification_hub: Arc<NotificationHub>,
+    pub notification_service: web::Data<AppNotificationService>,
+    pub profile_service: web::Data<AppProfileService>,
+    pub webhook_service: web::Data<AppWebhookService>,
+    pub invite_code_service: web::Data<AppInviteCodeService>,
+    pub audit_log_service: web::Data<AppAuditLogService>,
+    pub organization_service: web::Data<AppOrganizationService>,
+    pub quota_service: web::Data<AppQuotaService>,
+    pub oidc_client: Option<web::Data<OidcClient>>,
+    pub fixture_service: Option<web::Data<AppFixtureService>>,
+    pub graphql_api: Option<web::Data<GraphqlApi>>,
+}
+
+impl AppState {
+    pub fn builder(config: AppConfig) -> AppStateBuilder {
+        AppStateBuilder {
+            config,
+            pools: None,
+            event_bus: None,
+            auth_repository: None,
+            image_store: None,
+        }
+    }
+
+    // 前回の停止時に保存した状態があれば、サーバーを起動する前に戻す
+    pub fn restore_snapshot(&self) {
+        let Some(path) = &self.config.snapshot.path else {
+            return;
+        };
+        let Some(snapshot) = StateSnapshot::load(path, self.config.snapshot.max_age) else {
+            return;
+        };
+
+        let dispatchers = self.availability.restore(snapshot.available_dispatchers);
+        if let Some(areas) = snapshot.areas {
+            self.map_service.restore_areas(areas);
+        }
+        self.geocoding_service.restore_cache(snapshot.geocode_cache);
+        info!(
+            "{} 時点の状態を戻しました (受付中のディスパッチャー: {} 人)",
+            snapshot.saved_at, dispatchers
+        );
+    }
+
+    // 正規化すると同じになるユーザー名が残っているとログインするアカウントが決まらないため、サーバーを起動する前に解消する
+    pub async fn resolve_username_collisions(&self) {
+        match self.auth_service.resolve_username_collisions().await {
+            Ok(renames) => {
+                for rename in renames {
+                    warn!(
+                        "ユーザー {} のユーザー名が他のユーザーと重複していたため、{} から {} に変更しました",
+                        rename.user_id, rename.old_username, rename.new_username
+                    );
+                }
+            }
+            Err(e) => warn!("ユーザー名の重複を確認できませんでした: {:?}", e),
+        }
+    }
+
+    // サーバーが停止した後に呼び出し、メモリに溜めている書き込みを DB に反映する
+    pub async fn flush_pending_writes(&self) {
+        if let Err(e) = self.auth_service.flush_session_activities().await {
+            warn!("セッションの利用状況を書き込めませんでした: {:?}", e);
+        }
+        if let Err(e) = self.audit_log_service.flush().await {
+            warn!("監査ログを書き込めませんでした: {:?}", e);
+        }
+        if let Err(e) = self.tow_truck_service.flush_locations().await {
+            warn!("車両の位置を書き込めませんでした: {:?}", e);
+        }
+    }
+
+    // サーバーが停止した後に呼び出す
+    pub fn save_snapshot(&self) {
+        let Some(path) = &self.config.snapshot.path else {
+            return;
+        };
+
+        StateSnapshot::new(
+            self.availability.snapshot(),
+            self.map_service.cached_areas(),
+            self.geocoding_service.cache_snapshot(),
+        )
+        .save(path);
+    }
+
+    // 最初のリクエストで DB への問い合わせや前処理を待たないよう、よく使うデータをキャッシュに読み込む。
+    // 読み込みが済むか時間切れになった時点で準備完了とし、失敗しても起動は続ける
+    pub fn spawn_warm_up(&self) {
+        let warmup = self.config.warmup.clone();
+        if !warmup.enabled || self.config.no_db {
+            self.readiness.mark_ready();
+            return;
+        }
+
+        let auth_service = self.auth_service.clone();
+        let map_service = self.map_service.clone();
+        let route_planner = self.route_planner.clone();
+        let preload_routes = self.config.routing.preload;
+        let readiness = self.readiness.clone();
+        actix_web::rt::spawn(async move {
+            let started_at = std::time::Instant::now();
+            let warm_up = async {
+                match auth_service
+                    .warm_up_sessions(warmup.session_limit, warmup.concurrency)
+                    .await
+                {
+                    Ok(sessions) => info!("{} 件のセッションを読み込みました", sessions),
+                    Err(e) => warn!("セッションを読み込めませんでした: {:?}", e),
+                }
+
+                let area_ids: Vec<i32> = match map_service.get_areas().await {
+                    Ok(areas) => areas.areas.iter().map(|area| area.id).collect(),
+                    Err(e) => {
+                        warn!("エリアを読み込めませんでした: {:?}", e);
+                        Vec::new()
+                    }
+                };
+                match auth_service
+                    .warm_up_dispatchers(&area_ids, warmup.concurrency)
+                    .await
+                {
+                    Ok(dispatchers) => {
+                        info!("{} 人のディスパッチャーを読み込みました", dispatchers)
+                    }
+                    Err(e) => warn!("ディスパッチャーを読み込めませんでした: {:?}", e),
+                }
+
+                if preload_routes {
+                    match route_planner.preload().await {
+                        Ok(areas) => info!("{} エリアのグラフを前処理しました", areas),
+                        Err(e) => warn!("グラフを前処理できませんでした: {:?}", e),
+                    }
+                }
+            };
+            match actix_web::rt::time::timeout(warmup.timeout, warm_up).await {
+                Ok(()) => info!(
+                    "キャッシュの読み込みが完了しました ({:?})",
+                    started_at.elapsed()
+                ),
+                Err(_) => warn!(
+                    "キャッシュの読み込みが {:?} で終わらなかったため、打ち切って受付を始めます",
+                    warmup.timeout
+                ),
+            }
+            readiness.mark_ready();
+        });
+    }
+
+    // App::configure から呼び出し、ハンドラが受け取る app_data をすべて登録する
+    pub fn configure_app_data(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(self.config.clone())
+            .app_data(
+                web::JsonConfig::default()
+                    .limit(self.config.payload.json_limit)
+                    .error_handler(errors::json_error_handler),
+            )
+            .app_data(web::QueryConfig::default().error_handler(errors::query_error_handler))
+            .app_data(self.pools.clone())
+            .app_data(web::Data::from(self.event_bus.clone()))
+            .app_data(web::Data::from(self.readiness.clone()))
+            .app_data(web::Data::from(self.worker_pools.clone()))
+            .app_data(self.feature_flag_service.clone())
+            .app_data(self.runtime_config_service.clone())
+            .app_data(web::Data::from(self.auth_service.clone()))
+            .app_data(web::Data::from(self.api_key_service.clone()))
+            .app_data(self.tow_truck_service.clone())
+            .app_data(self.order_service.clone())
+            .app_data(self.order_note_service.clone())
+            .app_data(self.chat_service.clone())
+            .app_data(self.map_service.clone())
+            .app_data(self.client_service.clone())
+            .app_data(self.geocoding_service.clone())
+            .app_data(self.vehicle_service.clone())
+            .app_data(self.export_service.clone())
+            .app_data(self.backup_service.clone())
+            .app_data(self.report_service.clone())
+            .app_data(self.retention_service.clone())
+            .app_data(self.leaderboard_service.clone())
+            .app_data(self.stats_service.clone())
+            .app_data(self.sync_service.clone())
+            .app_data(self.dispatcher_service.clone())
+            .app_data(self.user_import_service.clone())
+            .app_data(web::Data::from(self.notification_hub.clone()))
+            .app_data(self.notification_service.clone())
+            .app_data(self.profile_service.clone())
+            .app_data(self.webhook_service.clone())
+            .app_data(self.invite_code_service.clone())
+            .app_data(self.audit_log_service.clone())
+            .app_data(self.organization_service.clone())
+            .app_data(self.quota_service.clone())
+            .app_data(self.image_service.clone());
+        // OIDC が未設定の場合、ハンドラは 404 を返す
+        if let Some(oidc_client) = &self.oidc_client {
+            cfg.app_data(oidc_client.clone());
+        }
+        if let Some(fixture_service) = &self.fixture_service {
+            cfg.app_data(fixture_service.clone());
+        }
+        if let Some(graphql_api) = &self.graphql_api {
+            cfg.app_data(graphql_api.clone());
+        }
+    }
+
+    pub fn spawn_background_jobs(&self) {
+        self.audit_log_service.spawn_flusher();
+        self.tow_truck_service.spawn_location_flusher();
+
+        let session_gc_service = self.auth_service.clone();
+        let session_gc_batch_size = self.config.session.gc_batch_size;
+        spawn_periodic_job("session_gc", self.config.session.gc_interval, move || {
+            let service = session_gc_service.clone();
+            async move {
+                let deleted = service
+                    .purge_expired_sessions(session_gc_batch_size)
+                    .await?;
+                if deleted > 0 {
+                    info!("期限切れセッションを {} 件削除しました", deleted);
+                }
+                Ok(())
+            }
+        });
+
+        let session_activity_service = self.auth_service.clone();
+        spawn_periodic_job(
+            "session_activity_flush",
+            self.config.session.activity_flush_interval,
+            move || {
+                let service = session_activity_service.clone();
+                async move {
+                    service.flush_session_activities().await?;
+                    Ok(())
+                }
+            },
+        );
+
+        let webhook_service = self.webhook_service.clone();
+        spawn_periodic_job(
+            "webhook_delivery",
+            self.config.webhook.poll_interval,
+            move || {
+                let service = webhook_service.clone();
+                async move {
+                    service.deliver_due().await?;
+                    Ok(())
+                }
+            },
+        );
+
+        let feature_flag_service = self.feature_flag_service.clone();
+        spawn_periodic_job(
+            "feature_flag_reload",
+            self.config.feature_flags.reload_interval,
+            move || {
+                let service = feature_flag_service.clone();
+                async move { service.reload().await }
+            },
+        );
+
+        if self.config.config_reload.file.is_some() {
+            let runtime_config_service = self.runtime_config_service.clone();
+            spawn_periodic_job(
+                "config_file_watch",
+                self.config.config_reload.watch_interval,
+                move || {
+                    let service = runtime_config_service.clone();
+                    async move { service.reload_file_if_modified() }
+                },
+            );
+        }
+
+        let eta_service = self.eta_service.clone();
+        spawn_periodic_job(
+            "eta_recalculation",
+            self.config.eta.recalculation_interval,
+            move || {
+                let service = eta_service.clone();
+                async move {
+                    service.recalculate().await?;
+                    Ok(())
+                }
+            },
+        );
+
+        let scheduled_order_service = self.order_service.clone();
+        spawn_periodic_job(
+            "scheduled_order_activation",
+            self.config.order_queue.scheduled_activation_interval,
+            move || {
+                let service = scheduled_order_service.clone();
+                async move {
+                    let activated = service.activate_scheduled_orders().await?;
+                    if activated > 0 {
+                        info!("予約注文を {} 件有効にしました", activated);
+                    }
+                    Ok(())
+                }
+            },
+        );
+
+        let report_service = self.report_service.clone();
+        spawn_periodic_job(
+            "report_refresh",
+            self.config.report.refresh_interval,
+            move || {
+                let service = report_service.clone();
+                async move {
+                    service.refresh().await?;
+                    Ok(())
+                }
+            },
+        );
+
+        let sync_service = self.sync_service.clone();
+        spawn_periodic_job(
+            "change_log_prune",
+            self.config.sync.prune_interval,
+            move || {
+                let service = sync_service.clone();
+                async move {
+                    let deleted = service.prune_change_log().await?;
+                    if deleted > 0 {
+                        info!("差分同期の変更記録を {} 件削除しました", deleted);
+                    }
+                    Ok(())
+                }
+            },
+        );
+
+        let retention_service = self.retention_service.clone();
+        spawn_periodic_job("retention", self.config.retention.interval, move || {
+            let service = retention_service.clone();
+            async move {
+                let report = service.run().await?;
+                for policy in report.policies.iter().filter(|policy| policy.count > 0) {
+                    if report.dry_run {
+                        info!(
+                                "保持期間のポリシー {} の対象が {} 件あります (dry run のため {} していません)",
+                                policy.policy, policy.count, policy.action
+                            );
+                    } else {
+                        info!(
+                            "保持期間のポリシー {} により {} 件を {} しました",
+                            policy.policy, policy.count, policy.action
+                        );
+                    }
+                }
+                Ok(())
+            }
+        });
+    }
+}
+
+// 指定されなかった依存は設定から組み立てる。テストではフェイクを差し込んで使う
+pub struct AppStateBuilder {
+    config: AppConfig,
+    pools: Option<DbPools>,
+    event_bus: Option<Arc<EventBus>>,
+    auth_repository: Option<AuthRepositoryBackend>,
+    image_store: Option<ImageStoreImpl>,
+}
+
+// 差し替え用のメソッドは main からは使わない
+#[allow(dead_code)]
+impl AppStateBuilder {
+    pub fn pools(mut self, pools: DbPools) -> Self {
+        self.pools = Some(pools);
+        self
+    }
+
+    pub fn event_bus(mut self, event_bus: Arc<EventBus>) -> Self {
+        self.event_bus = Some(event_bus);
+        self
+    }
+
+    pub fn auth_repository(mut self, auth_repository: AuthRepositoryBackend) -> Self {
+        self.auth_repository = Some(auth_repository);
+        self
+    }
+
+    pub fn image_store(mut self, image_store: ImageStoreImpl) -> Self {
+        self.image_store = Some(image_store);
+        self
+    }
+
+    pub async fn build(self) -> AppState {
+        let config = self.config;
+        let pools = match self.pools {
+            Some(pools) => pools,
+            None if config.no_db => db::create_lazy_pools(&config.db),
+            None => {
+                let pools = db::create_pools(&config.db).await;
+                if config.db.auto_migrate {
+                    migrations::run_migrations(&pools.primary, config.db.migration_baseline)
+                        .await
+                        .expect("failed to run migrations");
+                }
+                index_advisor::check_indexes(&pools.primary, config.db.create_missing_indexes)
+                    .await;
+                db::warm_up_pool(&pools.primary, config.db.warmup_connections).await;
+                if config.db.replica_url.is_some() {
+                    db::warm_up_pool(&pools.replica, config.db.warmup_connections).await;
+                }
+                pools
+            }
+        };
+        let event_bus = self.event_bus.unwrap_or_else(|| Arc::new(EventBus::new()));
+        // 受け付けた注文をアクセスログとは別に追えるよう、ログに残す
+        event_bus.subscribe(|event| match event {
+            DomainEvent::OrderCreated { order_id, area_id } => {
+                info!("注文 {} をエリア {} で受け付けました", order_id, area_id);
+            }
+            DomainEvent::OrderDispatched {
+                order_id,
+                dispatcher_id,
+                tow_truck_id,
+            } => {
+                info!(
+                    "注文 {} をディスパッチャー {} が車両 {} に割り当てました",
+                    order_id, dispatcher_id, tow_truck_id
+                );
+            }
+            DomainEvent::OrderCancelled {
+                order_id,
+                tow_truck_id,
+                reason,
+                ..
+            } => {
+                info!(
+                    "注文 {} がキャンセルされました (車両: {:?}, 理由: {})",
+                    order_id, tow_truck_id, reason
+                );
+            }
+            DomainEvent::DispatcherTransferred {
+                dispatcher_id,
+                from_area_id,
+                to_area_id,
+                ..
+            } => {
+                info!(
+                    "ディスパッチャー {} の担当エリアをエリア {} からエリア {} に変更しました",
+                    dispatcher_id, from_area_id, to_area_id
+                );
+            }
+            DomainEvent::AreaBoundaryChanged { area_id } => {
+                info!("エリア {} の境界を変更しました", area_id);
+            }
+            DomainEvent::UserOrganizationChanged {
+                user_id,
+                organization_id,
+            } => {
+                info!(
+                    "ユーザー {} の所属を組織 {} に変更しました",
+                    user_id, organization_id
+                );
+            }
+            _ => {}
+        });
+        // 実行中に変更できる設定のうち、特定のサービスに属さないものはここで反映する
+        let query_metrics = pools.query_metrics.clone();
+        event_bus.subscribe(move |event| {
+            if let DomainEvent::ConfigChanged { tunables } = event {
+                logger::set_filter(&tunables.log_filter);
+                query_metrics.set_slow_threshold(tunables.slow_query_threshold);
+            }
+        });
+        let runtime_config_service = web::Data::new(RuntimeConfigService::new(
+            TunableConfig::from_config(&config),
+            &config.config_reload,
+            event_bus.clone(),
+        ));
+        let feature_flags = Arc::new(FeatureFlags::new(&config.feature_flags));
+        let feature_flag_service = web::Data::new(FeatureFlagService::new(
+            FeatureFlagRepositoryImpl::new(pools.clone()),
+            feature_flags.clone(),
+        ));
+        // 読み込めなくても環境変数と既定値で起動できるため、失敗は警告に留める
+        if !config.no_db {
+            if let Err(e) = feature_flag_service.reload().await {
+                warn!("フィーチャーフラグを読み込めませんでした: {:?}", e);
+            }
+        }
+        let auth_repository = match self.auth_repository {
+            Some(auth_repository) => auth_repository,
+            None if config.no_db => {
+                info!("--no-db が指定されたため、認証データをメモリに保持します");
+                AuthRepositoryBackend::Memory(MemoryAuthRepository::new())
+            }
+            None => AuthRepositoryBackend::MySql(AuthRepositoryImpl::new(pools.clone())),
+        };
+        let image_store = self
+            .image_store
+            .unwrap_or_else(|| ImageStoreImpl::from_config(&config.image_store));
+
+        let mailer = Arc::new(MailerImpl::from_config(&config.mailer));
+        let worker_pools = Arc::new(WorkerPools {
+            image: WorkerPool::new("image", &config.image_workers),
+            password: WorkerPool::new("password", &config.password_workers),
+        });
+
+        // 上限のキャッシュを共有するため、上限を確認するサービスには同じインスタンスを渡す
+        let quota_service = web::Data::new(QuotaService::new(
+            QuotaRepositoryImpl::new(pools.clone()),
+            &config.quota,
+        ));
+        let auth_service = Arc::new(AuthService::new(
+            CachedAuthRepository::new(
+                auth_repository.clone(),
+                &config.db,
+                &event_bus,
+                feature_flags.clone(),
+            ),
+            quota_service.clone().into_inner(),
+            &config.session,
+            &config.email_verification,
+            &config.registration,
+            mailer.clone(),
+            worker_pools.password.clone(),
+            event_bus.clone(),
+        ));
+        let api_key_service =
+            Arc::new(ApiKeyService::new(ApiKeyRepositoryImpl::new(pools.clone())));
+        let geofence = Geofence::new(&event_bus);
+        let route_planner = Arc::new(RoutePlanner::new(
+            MapRepositoryImpl::new(pools.clone()),
+            &config.routing,
+            &event_bus,
+        ));
+        let tow_truck_service = web::Data::new(TowTruckService::new(
+            TowTruckRepositoryImpl::new(pools.clone()),
+            OrderRepositoryImpl::new(pools.clone()),
+            MapRepositoryImpl::new(pools.clone()),
+            geofence.clone(),
+            route_planner.clone(),
+            feature_flags.clone(),
+            &config.write_behind,
+        ));
+        let order_service = web::Data::new(OrderService::new(
+            OrderRepositoryImpl::new(pools.clone()),
+            TowTruckRepositoryImpl::new(pools.clone()),
+            auth_repository.clone(),
+            MapRepositoryImpl::new(pools.clone()),
+            geofence.clone(),
+            quota_service.clone().into_inner(),
+            &config.order_queue,
+            event_bus.clone(),
+        ));
+        let map_service = web::Data::new(MapService::new(
+            MapRepositoryImpl::new(pools.clone()),
+            route_planner.clone(),
+            event_bus.clone(),
+        ));
+        let client_service = web::Data::new(ClientService::new(
+            ClientRepositoryImpl::new(pools.clone()),
+            MapRepositoryImpl::new(pools.clone()),
+            geofence,
+            quota_service.clone().into_inner(),
+            event_bus.clone(),
+        ));
+        let geocoding_service = web::Data::new(GeocodingService::new(
+            GeocodingRepositoryImpl::new(pools.clone()),
+            GeocoderImpl::from_config(&config.geocoding.provider),
+            &config.geocoding,
+        ));
+        let vehicle_service = web::Data::new(VehicleService::new(
+            VehicleRepositoryImpl::new(pools.clone()),
+            auth_repository.clone(),
+            MapRepositoryImpl::new(pools.clone()),
+        ));
+        let export_service =
+            web::Data::new(ExportService::new(ExportRepositoryImpl::new(pools.clone())));
+        let backup_service = web::Data::new(BackupService::new(
+            BackupRepositoryImpl::new(pools.clone()),
+            event_bus.clone(),
+        ));
+        let report_service = web::Data::new(ReportService::new(
+            ReportRepositoryImpl::new(pools.clone()),
+            &config.report,
+            &event_bus,
+        ));
+        let retention_service = web::Data::new(RetentionService::new(
+            RetentionRepositoryImpl::new(pools.clone()),
+            image_store.clone(),
+            &config.retention,
+        ));
+        let leaderboard_service = web::Data::new(LeaderboardService::new(
+            LeaderboardRepositoryImpl::new(pools.clone()),
+            &event_bus,
+        ));
+        let availability =
+            DispatcherAvailability::new(config.dispatcher.max_active_assignments, &event_bus);
+        let dispatcher_service = web::Data::new(DispatcherService::new(
+            auth_repository.clone(),
+            OrderRepositoryImpl::new(pools.clone()),
+            availability.clone(),
+            event_bus.clone(),
+        ));
+        let stats_service = web::Data::new(StatsService::new(
+            StatsRepositoryImpl::new(pools.clone()),
+            &event_bus,
+        ));
+        let sync_service = web::Data::new(SyncService::new(
+            SyncRepositoryImpl::new(pools.clone()),
+            availability.clone(),
+            &config.sync,
+            &event_bus,
+        ));
+        let eta_service = Arc::new(EtaService::new(
+            EtaRepositoryImpl::new(pools.clone()),
+            MapRepositoryImpl::new(pools.clone()),
+            event_bus.clone(),
+            &config.eta,
+        ));
+        let user_import_service = web::Data::new(UserImportService::new(
+            UserImportRepositoryImpl::new(pools.clone()),
+            &config.user_import,
+            worker_pools.password.clone(),
+        ));
+        let notification_hub = Arc::new(NotificationHub::new());
+        let notification_service = web::Data::new(NotificationService::new(
+            NotificationRepositoryImpl::new(pools.clone()),
+            notification_hub.clone(),
+            mailer,
+            Arc::new(PushSenderImpl::from_config(&config.push)),
+            &config.notification,
+            &event_bus,
+        ));
+        let profile_service = web::Data::new(ProfileService::new(
+            ProfileRepositoryImpl::new(pools.clone()),
+            NotificationRepositoryImpl::new(pools.clone()),
+        ));
+        let webhook_service = web::Data::new(WebhookService::new(
+            WebhookRepositoryImpl::new(pools.clone()),
+            &config.webhook,
+            &event_bus,
+        ));
+        let invite_code_service = web::Data::new(InviteCodeService::new(
+            InviteCodeRepositoryImpl::new(pools.clone()),
+            &config.registration,
+        ));
+        let audit_log_service = web::Data::new(AuditLogService::new(
+            AuditLogRepositoryImpl::new(pools.clone()),
+            &config.write_behind,
+        ));
+        let organization_service = web::Data::new(OrganizationService::new(
+            OrganizationRepositoryImpl::new(pools.clone()),
+            auth_repository.clone(),
+            event_bus.clone(),
+        ));
+        if config.login_anomaly.enabled {
+            LoginAnomalyService::new(
+                LoginAttemptRepositoryImpl::new(pools.clone()),
+                audit_log_service.clone().into_inner(),
+                &config.login_anomaly,
+                event_bus.clone(),
+            );
+        }
+        let image_moderator = ImageModeratorImpl::from_config(&config.image_moderator);
+        if image_moderator.is_enabled() {
+            ImageModerationService::new(
+                auth_repository.clone(),
+                image_store.clone(),
+                image_moderator,
+                event_bus.clone(),
+            );
+        }
+        let order_note_service = web::Data::new(OrderNoteService::new(
+            OrderNoteRepositoryImpl::new(pools.clone()),
+            auth_repository.clone(),
+            image_store.clone(),
+        ));
+        let chat_service = web::Data::new(ChatService::new(
+            ChatRepositoryImpl::new(pools.clone()),
+            Arc::new(ChatHub::new()),
+        ));
+        let image_service = web::Data::new(ImageService::new(
+            auth_repository,
+            image_store,
+            worker_pools.image.clone(),
+            quota_service.clone().into_inner(),
+            ImageUrlSigner::from_config(&config.image_url),
+            event_bus.clone(),
+        ));
+        let oidc_client = config
+            .oidc
+            .clone()
+            .map(|oidc_config| web::Data::new(OidcClient::new(oidc_config)));
+        let fixture_service = config.fixture.as_ref().map(|fixture_config| {
+            web::Data::new(FixtureService::new(
+                FixtureRepositoryImpl::new(pools.clone()),
+                fixture_config,
+                event_bus.clone(),
+            ))
+        });
+        let graphql_api = config.graphql.as_ref().map(|graphql_config| {
+            web::Data::new(GraphqlApi::new(
+                GraphqlRepositoryImpl::new(pools.clone()),
+                graphql_config,
+            ))
+        });
+        // 購読者がそろってから読み込み、ファイルの値をすべてのコンポーネントに反映する
+        if config.config_reload.file.is_some() {
+            if let Err(e) = runtime_config_service.reload_file() {
+                warn!("設定ファイルを読み込めませんでした: {:?}", e);
+            }
+        }
+
+        AppState {
+            config: web::Data::new(config),
+            pools: web::Data::new(pools),
+            event_bus,
+            feature_flag_service,
+            runtime_config_service,
+            auth_service,
+            api_key_service,
+            tow_truck_service,
+            order_service,
+            order_note_service,
+            chat_service,
+            map_service,
+            client_service,
+            geocoding_service,
+            image_service,
+            vehicle_service,
+            export_service,
+            backup_service,
+            report_service,
+            retention_service,
+            leaderboard_service,
+            stats_service,
+            sync_service,
+            dispatcher_service,
+            eta_service,
+            route_planner,
+            availability,
+            readiness: Arc::new(Readiness::default()),
+            worker_pools,
+            user_import_service,
+            notification_hub,
+            notification_service,
+            profile_service,
+            webhook_service,
+            invite_code_service,
+            audit_log_service,
+            organization_service,
+            quota_service,
+            oidc_client,
+            fixture_service,
+            graphql_api,
+        }
+    }
+}
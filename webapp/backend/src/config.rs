@@ -0,0 +1,1326 @@
+use std::collections::HashMap;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::models::role::Role;
+
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub db: DbConfig,
+    pub image_store: ImageStoreConfig,
+    pub image_offload: ImageOffloadConfig,
+    pub image_url: ImageUrlConfig,
+    pub image_moderator: ImageModeratorConfig,
+    pub image_workers: WorkerPoolConfig,
+    // パスワードのハッシュ化と照合。ログインを断らないよう、待ちの上限は大きめにする
+    pub password_workers: WorkerPoolConfig,
+    pub session: SessionConfig,
+    pub registration: RegistrationConfig,
+    pub quota: QuotaConfig,
+    pub login_anomaly: LoginAnomalyConfig,
+    pub email_verification: EmailVerificationConfig,
+    pub mailer: MailerConfig,
+    pub push: PushConfig,
+    pub oidc: Option<OidcConfig>,
+    pub fixture: Option<FixtureConfig>,
+    pub graphql: Option<GraphqlConfig>,
+    pub grpc: Option<GrpcConfig>,
+    pub payload: PayloadConfig,
+    // リクエストの処理に許す時間。0 の場合は期限を設けない
+    pub request_timeout: Duration,
+    pub compression: CompressionConfig,
+    pub http_cache: HttpCacheConfig,
+    pub report: ReportConfig,
+    pub dispatcher: DispatcherConfig,
+    pub order_queue: OrderQueueConfig,
+    pub eta: EtaConfig,
+    pub geocoding: GeocodingConfig,
+    pub routing: RoutingConfig,
+    pub snapshot: SnapshotConfig,
+    pub warmup: WarmupConfig,
+    pub user_import: UserImportConfig,
+    pub notification: NotificationConfig,
+    pub webhook: WebhookConfig,
+    pub sync: SyncConfig,
+    pub write_behind: WriteBehindConfig,
+    pub retention: RetentionConfig,
+    pub feature_flags: FeatureFlagConfig,
+    pub config_reload: ConfigReloadConfig,
+    pub i18n: I18nConfig,
+    // env_logger の書式のフィルタ。実行中に変更できる
+    pub log_filter: String,
+    // --no-db で起動した場合、認証まわりのデータを MySQL ではなくメモリに保持する
+    pub no_db: bool,
+}
+
+impl AppConfig {
+    pub fn from_env() -> Self {
+        Self::load(env::args().any(|arg| arg == "--no-db"))
+    }
+
+    // テストでは DATABASE_URL がなくても読み込めるよう、--no-db を指定した場合と同じ設定にする
+    #[cfg(test)]
+    pub fn from_env_without_db() -> Self {
+        Self::load(true)
+    }
+
+    fn load(no_db: bool) -> Self {
+        AppConfig {
+            db: DbConfig::from_env(no_db),
+            image_store: ImageStoreConfig::from_env(),
+            image_offload: ImageOffloadConfig::from_env(),
+            image_url: ImageUrlConfig::from_env(),
+            image_moderator: ImageModeratorConfig::from_env(),
+            image_workers: WorkerPoolConfig::from_env("IMAGE", 64),
+            password_workers: WorkerPoolConfig::from_env("PASSWORD", 1024),
+            session: SessionConfig::from_env(),
+            registration: RegistrationConfig::from_env(),
+            quota: QuotaConfig::from_env(),
+            login_anomaly: LoginAnomalyConfig::from_env(),
+            email_verification: EmailVerificationConfig::from_env(),
+            mailer: MailerConfig::from_env(),
+            push: PushConfig::from_env(),
+            oidc: OidcConfig::from_env(),
+            fixture: FixtureConfig::from_env(),
+            graphql: GraphqlConfig::from_env(),
+            grpc: GrpcConfig::from_env(),
+            payload: PayloadConfig::from_env(),
+            request_timeout: Duration::from_millis(env_parse_or("REQUEST_TIMEOUT_MS", 30_000)),
+            compression: CompressionConfig::from_env(),
+            http_cache: HttpCacheConfig::from_env(),
+            report: ReportConfig::from_env(),
+            dispatcher: DispatcherConfig::from_env(),
+            order_queue: OrderQueueConfig::from_env(),
+            eta: EtaConfig::from_env(),
+            geocoding: GeocodingConfig::from_env(),
+            routing: RoutingConfig::from_env(),
+            snapshot: SnapshotConfig::from_env(),
+            warmup: WarmupConfig::from_env(),
+            user_import: UserImportConfig::from_env(),
+            notification: NotificationConfig::from_env(),
+            webhook: WebhookConfig::from_env(),
+            sync: SyncConfig::from_env(),
+            write_behind: WriteBehindConfig::from_env(),
+            retention: RetentionConfig::from_env(),
+            feature_flags: FeatureFlagConfig::from_env(),
+            config_reload: ConfigReloadConfig::from_env(),
+            i18n: I18nConfig::from_env(),
+            log_filter: env_or("RUST_LOG", "info"),
+            no_db,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbConfig {
+    pub url: String,
+    pub replica_url: Option<String>,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout: Duration,
+    pub warmup_connections: u32,
+    pub statement_cache_capacity: usize,
+    pub query_cache_ttl: Duration,
+    pub negative_cache_ttl: Duration,
+    pub user_cache_ttl: Duration,
+    pub slow_query_threshold: Duration,
+    // デバッグ用。スロークエリの実行計画を EXPLAIN で取得してログに出力する。取得のためにクエリをもう一度計画させることになる
+    pub explain_slow_queries: bool,
+    // breaker_window 内に DB のエラーがこの件数に達したら breaker_open_duration の間遮断する。0 の場合は遮断しない
+    pub breaker_failure_threshold: usize,
+    pub breaker_window: Duration,
+    pub breaker_open_duration: Duration,
+    pub retry: RetryConfig,
+    pub auto_migrate: bool,
+    pub migration_baseline: Option<i64>,
+    // 起動時に足りないと判定したインデックスを作成する。false の場合は作成する DDL を警告として出力するだけにする
+    pub create_missing_indexes: bool,
+}
+
+impl DbConfig {
+    fn from_env(no_db: bool) -> Self {
+        let min_connections = env_parse_or("DB_MIN_CONNECTIONS", 0);
+        let url = match no_db {
+            true => env_or("DATABASE_URL", "mysql://localhost/app"),
+            false => env::var("DATABASE_URL").expect("DATABASE_URL must be set"),
+        };
+
+        DbConfig {
+            url,
+            replica_url: env::var("DATABASE_READ_URL").ok(),
+            max_connections: env_parse_or("DB_MAX_CONNECTIONS", 10),
+            min_connections,
+            acquire_timeout: Duration::from_secs(env_parse_or("DB_ACQUIRE_TIMEOUT_SECS", 30)),
+            warmup_connections: env_parse_or("DB_WARMUP_CONNECTIONS", min_connections),
+            statement_cache_capacity: env_parse_or("DB_STATEMENT_CACHE_CAPACITY", 100),
+            query_cache_ttl: Duration::from_millis(env_parse_or("QUERY_CACHE_TTL_MS", 0)),
+            negative_cache_ttl: Duration::from_millis(env_parse_or("NEGATIVE_CACHE_TTL_MS", 500)),
+            user_cache_ttl: Duration::from_millis(env_parse_or("USER_CACHE_TTL_MS", 60_000)),
+            slow_query_threshold: Duration::from_millis(env_parse_or(
+                "DB_SLOW_QUERY_THRESHOLD_MS",
+                100,
+            )),
+            explain_slow_queries: env_parse_or("DB_EXPLAIN_SLOW_QUERIES", false),
+            breaker_failure_threshold: env_parse_or("DB_BREAKER_FAILURE_THRESHOLD", 20),
+            breaker_window: Duration::from_millis(env_parse_or("DB_BREAKER_WINDOW_MS", 10_000)),
+            breaker_open_duration: Duration::from_millis(env_parse_or("DB_BREAKER_OPEN_MS", 5_000)),
+            retry: RetryConfig::from_env(),
+            auto_migrate: env_parse_or("DB_AUTO_MIGRATE", false),
+            migration_baseline: env::var("DB_MIGRATION_BASELINE")
+                .ok()
+                .and_then(|value| value.parse().ok()),
+            create_missing_indexes: env_parse_or("DB_CREATE_MISSING_INDEXES", false),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    // 1 の場合は再試行しない
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // リポジトリのメソッドごとの試行回数。"order_repository.find_order_by_id=5,..." の形式で指定する
+    pub method_max_attempts: HashMap<String, u32>,
+}
+
+impl RetryConfig {
+    fn from_env() -> Self {
+        RetryConfig {
+            max_attempts: env_parse_or("DB_RETRY_MAX_ATTEMPTS", 3),
+            base_delay: Duration::from_millis(env_parse_or("DB_RETRY_BASE_DELAY_MS", 10)),
+            max_delay: Duration::from_millis(env_parse_or("DB_RETRY_MAX_DELAY_MS", 200)),
+            method_max_attempts: env_or("DB_RETRY_METHOD_MAX_ATTEMPTS", "")
+                .split(',')
+                .filter_map(|entry| {
+                    let (method, attempts) = entry.split_once('=')?;
+                    Some((method.trim().to_string(), attempts.trim().parse().ok()?))
+                })
+                .collect(),
+        }
+    }
+
+    pub fn max_attempts_for(&self, method: &str) -> u32 {
+        self.method_max_attempts
+            .get(method)
+            .copied()
+            .unwrap_or(self.max_attempts)
+            .max(1)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ImageStoreConfig {
+    Local { root_dir: PathBuf },
+    S3(S3Config),
+}
+
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+impl ImageStoreConfig {
+    fn from_env() -> Self {
+        match env::var("IMAGE_STORE").as_deref() {
+            Ok("s3") => ImageStoreConfig::S3(S3Config {
+                endpoint: env::var("S3_ENDPOINT").expect("S3_ENDPOINT must be set"),
+                bucket: env::var("S3_BUCKET").expect("S3_BUCKET must be set"),
+                region: env_or("S3_REGION", "us-east-1"),
+                access_key_id: env::var("S3_ACCESS_KEY_ID").expect("S3_ACCESS_KEY_ID must be set"),
+                secret_access_key: env::var("S3_SECRET_ACCESS_KEY")
+                    .expect("S3_SECRET_ACCESS_KEY must be set"),
+            }),
+            _ => ImageStoreConfig::Local {
+                root_dir: env_or("IMAGE_STORE_DIR", "images/user_profile").into(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageOffloadMode {
+    Disabled,
+    Redirect,
+    XAccelRedirect,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageOffloadConfig {
+    pub mode: ImageOffloadMode,
+    pub public_dir: PathBuf,
+    pub url_prefix: String,
+}
+
+impl ImageOffloadConfig {
+    fn from_env() -> Self {
+        let mode = match env::var("IMAGE_OFFLOAD_MODE").as_deref() {
+            Ok("redirect") => ImageOffloadMode::Redirect,
+            Ok("x-accel") => ImageOffloadMode::XAccelRedirect,
+            _ => ImageOffloadMode::Disabled,
+        };
+
+        ImageOffloadConfig {
+            mode,
+            public_dir: env_or("IMAGE_OFFLOAD_DIR", "images/thumbnails").into(),
+            url_prefix: env_or("IMAGE_OFFLOAD_URL_PREFIX", "/thumbnails"),
+        }
+    }
+}
+
+// 署名付きの画像の URL。secret を設定すると、画像の取得に有効な署名かセッションが必要になる
+#[derive(Debug, Clone)]
+pub struct ImageUrlConfig {
+    pub secret: Option<String>,
+    // 発行した URL の有効期間
+    pub ttl: Duration,
+}
+
+impl ImageUrlConfig {
+    fn from_env() -> Self {
+        ImageUrlConfig {
+            secret: env::var("IMAGE_URL_SECRET")
+                .ok()
+                .filter(|secret| !secret.is_empty()),
+            ttl: Duration::from_secs(env_parse_or("IMAGE_URL_TTL_SECS", 3600)),
+        }
+    }
+}
+
+// アップロードされたプロフィール画像の審査。審査はアップロードの完了後に非同期に行う
+#[derive(Debug, Clone)]
+pub enum ImageModeratorConfig {
+    Disabled,
+    // 判定モデルの代わりに、不適切とされた画像の SHA-256 の一覧と照合する
+    Local { blocklist: Option<PathBuf> },
+    Http(HttpImageModeratorConfig),
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpImageModeratorConfig {
+    pub url: String,
+    pub api_key: Option<String>,
+    pub timeout: Duration,
+}
+
+impl ImageModeratorConfig {
+    fn from_env() -> Self {
+        match env::var("IMAGE_MODERATOR").as_deref() {
+            Ok("local") => ImageModeratorConfig::Local {
+                blocklist: env::var("IMAGE_MODERATOR_BLOCKLIST")
+                    .ok()
+                    .map(PathBuf::from),
+            },
+            Ok("http") => ImageModeratorConfig::Http(HttpImageModeratorConfig {
+                url: env::var("IMAGE_MODERATOR_URL").expect("IMAGE_MODERATOR_URL must be set"),
+                api_key: env::var("IMAGE_MODERATOR_API_KEY").ok(),
+                timeout: Duration::from_secs(env_parse_or("IMAGE_MODERATOR_TIMEOUT_SECS", 10)),
+            }),
+            _ => ImageModeratorConfig::Disabled,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkerPoolConfig {
+    // 同時に実行する数。既定は CPU のコア数
+    pub concurrency: usize,
+    // 実行を待つ数がこれに達した場合は、待たせずに 503 を返す
+    pub queue_limit: usize,
+}
+
+impl WorkerPoolConfig {
+    fn from_env(prefix: &str, default_queue_limit: usize) -> Self {
+        let cores = std::thread::available_parallelism()
+            .map(|cores| cores.get())
+            .unwrap_or(1);
+
+        WorkerPoolConfig {
+            concurrency: env_parse_or(&format!("{}_WORKERS", prefix), cores).max(1),
+            queue_limit: env_parse_or(
+                &format!("{}_WORKER_QUEUE_LIMIT", prefix),
+                default_queue_limit,
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionTransport {
+    Header,
+    Cookie,
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    pub ttl: Duration,
+    pub gc_interval: Duration,
+    pub gc_batch_size: u32,
+    pub validation_cache_ttl: Duration,
+    // 最後に使われた日時などをまとめて DB に書き込む間隔
+    pub activity_flush_interval: Duration,
+    // 管理者がなりかわって発行するセッションの有効期間。延長はできない
+    pub impersonation_ttl: Duration,
+    pub transport: SessionTransport,
+    pub cookie_name: String,
+    pub cookie_secure: bool,
+    pub jwt_secret: Option<String>,
+}
+
+impl SessionConfig {
+    fn from_env() -> Self {
+        let transport = match env::var("SESSION_TRANSPORT").as_deref() {
+            Ok("cookie") => SessionTransport::Cookie,
+            _ => SessionTransport::Header,
+        };
+        let jwt_secret = match env::var("AUTH_MODE").as_deref() {
+            Ok("jwt") => Some(env::var("JWT_SECRET").expect("JWT_SECRET must be set")),
+            _ => None,
+        };
+
+        SessionConfig {
+            ttl: Duration::from_secs(env_parse_or("SESSION_TTL_SECS", 24 * 60 * 60)),
+            gc_interval: Duration::from_secs(env_parse_or("SESSION_GC_INTERVAL_SECS", 10 * 60)),
+            gc_batch_size: env_parse_or("SESSION_GC_BATCH_SIZE", 1000).max(1),
+            validation_cache_ttl: Duration::from_millis(env_parse_or(
+                "SESSION_VALIDATION_CACHE_TTL_MS",
+                2000,
+            )),
+            activity_flush_interval: Duration::from_secs(env_parse_or(
+                "SESSION_ACTIVITY_FLUSH_INTERVAL_SECS",
+                30,
+            )),
+            impersonation_ttl: Duration::from_secs(env_parse_or(
+                "SESSION_IMPERSONATION_TTL_SECS",
+                30 * 60,
+            )),
+            transport,
+            cookie_name: env_or("SESSION_COOKIE_NAME", "session_token"),
+            cookie_secure: env_parse_or("SESSION_COOKIE_SECURE", true),
+            jwt_secret,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EmailVerificationConfig {
+    // 有効な場合、登録時にメールアドレスを必須にし、確認が済むまでログインさせない
+    pub required: bool,
+    pub token_ttl: Duration,
+    // 確認用のトークンをこの URL の末尾に付けてメールで送る
+    pub verify_url: String,
+}
+
+impl EmailVerificationConfig {
+    fn from_env() -> Self {
+        EmailVerificationConfig {
+            required: env_parse_or("EMAIL_VERIFICATION_REQUIRED", false),
+            token_ttl: Duration::from_secs(env_parse_or(
+                "EMAIL_VERIFICATION_TOKEN_TTL_SECS",
+                24 * 60 * 60,
+            )),
+            verify_url: env_or(
+                "EMAIL_VERIFICATION_URL",
+                "http://localhost:3000/verify_email?token=",
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MailerConfig {
+    Log,
+    Smtp(SmtpConfig),
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub from: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub timeout: Duration,
+}
+
+impl MailerConfig {
+    fn from_env() -> Self {
+        match env::var("MAILER").as_deref() {
+            Ok("smtp") => MailerConfig::Smtp(SmtpConfig {
+                host: env::var("SMTP_HOST").expect("SMTP_HOST must be set"),
+                port: env_parse_or("SMTP_PORT", 25),
+                from: env::var("MAIL_FROM").expect("MAIL_FROM must be set"),
+                username: env::var("SMTP_USERNAME").ok(),
+                password: env::var("SMTP_PASSWORD").ok(),
+                timeout: Duration::from_secs(env_parse_or("SMTP_TIMEOUT_SECS", 10)),
+            }),
+            _ => MailerConfig::Log,
+        }
+    }
+}
+
+// プロバイダーを 1 つも設定していない場合は、送信せずにログへ出力する
+#[derive(Debug, Clone)]
+pub struct PushConfig {
+    // FCM_SERVICE_ACCOUNT_PATH が設定されている場合のみ有効にする
+    pub fcm: Option<FcmConfig>,
+    // VAPID_PRIVATE_KEY_PATH が設定されている場合のみ有効にする
+    pub web_push: Option<WebPushConfig>,
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct FcmConfig {
+    // Firebase のサービスアカウントの鍵 (JSON)
+    pub service_account_path: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebPushConfig {
+    // VAPID の署名に使う P-256 の秘密鍵 (PKCS#8 の PEM)
+    pub private_key_path: PathBuf,
+    // 秘密鍵に対応する公開鍵 (非圧縮形式を base64url で表したもの)。ブラウザの購読にも使う
+    pub public_key: String,
+    pub subject: String,
+    // プッシュサービスが端末に届けるまで保持する時間
+    pub ttl: Duration,
+}
+
+impl PushConfig {
+    fn from_env() -> Self {
+        PushConfig {
+            fcm: env::var("FCM_SERVICE_ACCOUNT_PATH")
+                .ok()
+                .map(|path| FcmConfig {
+                    service_account_path: PathBuf::from(path),
+                }),
+            web_push: env::var("VAPID_PRIVATE_KEY_PATH")
+                .ok()
+                .map(|path| WebPushConfig {
+                    private_key_path: PathBuf::from(path),
+                    public_key: env::var("VAPID_PUBLIC_KEY").expect("VAPID_PUBLIC_KEY must be set"),
+                    subject: env::var("VAPID_SUBJECT").expect("VAPID_SUBJECT must be set"),
+                    ttl: Duration::from_secs(env_parse_or("WEB_PUSH_TTL_SECS", 3_600)),
+                }),
+            timeout: Duration::from_millis(env_parse_or("PUSH_TIMEOUT_MS", 5_000)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub provider: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub scopes: String,
+    pub default_role: Role,
+}
+
+impl OidcConfig {
+    // OIDC_CLIENT_ID が設定されている場合のみ有効にする
+    fn from_env() -> Option<Self> {
+        let client_id = env::var("OIDC_CLIENT_ID").ok()?;
+
+        Some(OidcConfig {
+            provider: env_or("OIDC_PROVIDER", "oidc"),
+            client_id,
+            client_secret: env::var("OIDC_CLIENT_SECRET").expect("OIDC_CLIENT_SECRET must be set"),
+            redirect_uri: env::var("OIDC_REDIRECT_URI").expect("OIDC_REDIRECT_URI must be set"),
+            authorization_endpoint: env::var("OIDC_AUTHORIZATION_ENDPOINT")
+                .expect("OIDC_AUTHORIZATION_ENDPOINT must be set"),
+            token_endpoint: env::var("OIDC_TOKEN_ENDPOINT")
+                .expect("OIDC_TOKEN_ENDPOINT must be set"),
+            userinfo_endpoint: env::var("OIDC_USERINFO_ENDPOINT")
+                .expect("OIDC_USERINFO_ENDPOINT must be set"),
+            scopes: env_or("OIDC_SCOPES", "openid profile email"),
+            default_role: parse_oidc_default_role(&env_or("OIDC_DEFAULT_ROLE", "client")),
+        })
+    }
+}
+
+// 外部アカウントの初回ログインで作るユーザーのロール。管理者は誰でも IdP のアカウントを作れば権限を得られてしまい、
+// ディスパッチャーは担当エリアを決められないため、追加の情報なしで作れるロールに限る
+fn parse_oidc_default_role(value: &str) -> Role {
+    match value.parse() {
+        Ok(role @ (Role::Client | Role::Driver)) => role,
+        _ => panic!("OIDC_DEFAULT_ROLE must be client or driver"),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FixtureConfig {
+    pub dir: PathBuf,
+}
+
+impl FixtureConfig {
+    // 負荷試験用の /debug エンドポイントは DEBUG_ENDPOINTS_ENABLED=true の場合のみ有効にする
+    fn from_env() -> Option<Self> {
+        if !env_parse_or("DEBUG_ENDPOINTS_ENABLED", false) {
+            return None;
+        }
+
+        Some(FixtureConfig {
+            dir: env_or("FIXTURE_DIR", "../mysql/init/csv").into(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphqlConfig {
+    // 1 回のクエリで許すネストの深さと、フィールド数の合計
+    pub max_depth: usize,
+    pub max_complexity: usize,
+}
+
+impl GraphqlConfig {
+    // /api/graphql は GRAPHQL_ENABLED=true の場合のみ有効にする
+    fn from_env() -> Option<Self> {
+        if !env_parse_or("GRAPHQL_ENABLED", false) {
+            return None;
+        }
+
+        Some(GraphqlConfig {
+            max_depth: env_parse_or("GRAPHQL_MAX_DEPTH", 8),
+            max_complexity: env_parse_or("GRAPHQL_MAX_COMPLEXITY", 1_000),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GrpcConfig {
+    pub addr: SocketAddr,
+    // 呼び出し元と共有するトークン
+    pub auth_token: String,
+}
+
+impl GrpcConfig {
+    // 内部コンポーネント向けの gRPC サーバーは GRPC_ADDR が設定されている場合のみ起動する
+    fn from_env() -> Option<Self> {
+        let addr = env::var("GRPC_ADDR").ok()?;
+
+        Some(GrpcConfig {
+            addr: addr
+                .parse()
+                .expect("GRPC_ADDR must be a valid socket address"),
+            auth_token: env::var("GRPC_AUTH_TOKEN").expect("GRPC_AUTH_TOKEN must be set"),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PayloadConfig {
+    pub json_limit: usize,
+    // 未認証で呼び出せるログイン・登録などは小さい上限にする
+    pub auth_json_limit: usize,
+    pub profile_image_limit: usize,
+    pub user_import_limit: usize,
+    pub order_attachment_limit: usize,
+    // 復元するアーカイブ。圧縮された大きさで制限する
+    pub backup_import_limit: usize,
+    // アップロードされた画像はメモリに溜めず、変換するまでこのディレクトリに書き出す
+    pub upload_tmp_dir: PathBuf,
+}
+
+impl PayloadConfig {
+    fn from_env() -> Self {
+        PayloadConfig {
+            json_limit: env_parse_or("JSON_PAYLOAD_LIMIT_BYTES", 256 * 1024),
+            auth_json_limit: env_parse_or("AUTH_JSON_PAYLOAD_LIMIT_BYTES", 4 * 1024),
+            profile_image_limit: env_parse_or("PROFILE_IMAGE_UPLOAD_LIMIT_BYTES", 5 * 1024 * 1024),
+            user_import_limit: env_parse_or("USER_IMPORT_PAYLOAD_LIMIT_BYTES", 5 * 1024 * 1024),
+            order_attachment_limit: env_parse_or("ORDER_ATTACHMENT_LIMIT_BYTES", 2 * 1024 * 1024),
+            backup_import_limit: env_parse_or("BACKUP_IMPORT_LIMIT_BYTES", 256 * 1024 * 1024),
+            upload_tmp_dir: env::var("UPLOAD_TMP_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| env::temp_dir()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    pub min_bytes: u64,
+    // 圧縮対象にする Content-Type。画像などはすでに圧縮されているため含めない
+    pub content_types: Vec<String>,
+}
+
+impl CompressionConfig {
+    fn from_env() -> Self {
+        CompressionConfig {
+            enabled: env_parse_or("COMPRESSION_ENABLED", true),
+            min_bytes: env_parse_or("COMPRESSION_MIN_BYTES", 1024),
+            content_types: env_or(
+                "COMPRESSION_CONTENT_TYPES",
+                "application/json,application/x-ndjson,text/csv,text/plain",
+            )
+            .split(',')
+            .map(|content_type| content_type.trim().to_string())
+            .filter(|content_type| !content_type.is_empty())
+            .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpCacheConfig {
+    // エリア一覧など、ほとんど変わらない参照用データをキャッシュさせる時間
+    pub static_max_age: Duration,
+}
+
+impl HttpCacheConfig {
+    fn from_env() -> Self {
+        HttpCacheConfig {
+            static_max_age: Duration::from_secs(env_parse_or(
+                "HTTP_CACHE_STATIC_MAX_AGE_SECS",
+                3600,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReportConfig {
+    // 0 の場合は定期的に作成せず、最初に要求されたときだけ作成する
+    pub refresh_interval: Duration,
+    // 集計する期間。それぞれ現在時刻からさかのぼった期間を集計する
+    pub windows: Vec<Duration>,
+}
+
+impl ReportConfig {
+    fn from_env() -> Self {
+        ReportConfig {
+            refresh_interval: Duration::from_secs(env_parse_or(
+                "REPORT_REFRESH_INTERVAL_SECS",
+                5 * 60,
+            )),
+            windows: env_or("REPORT_WINDOWS_SECS", "3600,86400,604800")
+                .split(',')
+                .filter_map(|window| window.trim().parse().ok())
+                .filter(|window| *window > 0)
+                .map(Duration::from_secs)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DispatcherConfig {
+    // 担当中の注文がこの件数に達したディスパッチャーは、受付中でも候補に含めない
+    pub max_active_assignments: usize,
+}
+
+impl DispatcherConfig {
+    fn from_env() -> Self {
+        DispatcherConfig {
+            max_active_assignments: env_parse_or("DISPATCHER_MAX_ACTIVE_ASSIGNMENTS", 3),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OrderQueueConfig {
+    // 優先度 1 段階に相当する待ち時間。VIP の注文もこの 2 倍より長く待った通常の注文は追い越せない
+    pub priority_aging: Duration,
+    // 予約注文は希望日時のこの時間前に未割り当ての注文になり、配車の対象になる
+    pub scheduled_activation_lead: Duration,
+    // 予約を受け付ける最も先の日時 (現在からの時間)
+    pub scheduled_max_ahead: Duration,
+    // 予約注文を有効にするジョブの間隔。0 の場合は実行しない
+    pub scheduled_activation_interval: Duration,
+    // 1 回のジョブで有効にする予約注文の数
+    pub scheduled_activation_batch_size: i64,
+}
+
+impl OrderQueueConfig {
+    fn from_env() -> Self {
+        OrderQueueConfig {
+            priority_aging: Duration::from_secs(env_parse_or("ORDER_PRIORITY_AGING_SECS", 300)),
+            scheduled_activation_lead: Duration::from_secs(
+                env_parse_or("SCHEDULED_ORDER_ACTIVATION_LEAD_MINUTES", 30) * 60,
+            ),
+            scheduled_max_ahead: Duration::from_secs(
+                env_parse_or("SCHEDULED_ORDER_MAX_DAYS_AHEAD", 30) * 24 * 60 * 60,
+            ),
+            scheduled_activation_interval: Duration::from_secs(env_parse_or(
+                "SCHEDULED_ORDER_ACTIVATION_INTERVAL_SECS",
+                30,
+            )),
+            scheduled_activation_batch_size: env_parse_or(
+                "SCHEDULED_ORDER_ACTIVATION_BATCH_SIZE",
+                500,
+            )
+            .max(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EtaConfig {
+    // 配車中の注文の到着予定時刻を計算し直す間隔。0 の場合は計算し直さない
+    pub recalculation_interval: Duration,
+    // 到着予定時刻がこの分数以上変わった場合だけ関係者に通知する
+    pub notify_threshold_minutes: i64,
+    // 1 回の UPDATE で書き込む注文の数
+    pub batch_size: usize,
+}
+
+impl EtaConfig {
+    fn from_env() -> Self {
+        EtaConfig {
+            recalculation_interval: Duration::from_secs(env_parse_or(
+                "ETA_RECALCULATION_INTERVAL_SECS",
+                30,
+            )),
+            notify_threshold_minutes: env_parse_or("ETA_NOTIFY_THRESHOLD_MINUTES", 5),
+            batch_size: env_parse_or("ETA_BATCH_SIZE", 500).max(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SnapshotConfig {
+    // 停止時にメモリ上の状態を保存し、起動時に読み込むファイル。指定しない場合は保存しない
+    pub path: Option<PathBuf>,
+    // これより古いスナップショットは、DB と食い違っている恐れがあるため読み込まない
+    pub max_age: Duration,
+}
+
+impl SnapshotConfig {
+    fn from_env() -> Self {
+        SnapshotConfig {
+            path: env::var("STATE_SNAPSHOT_PATH").ok().map(PathBuf::from),
+            max_age: Duration::from_secs(env_parse_or("STATE_SNAPSHOT_MAX_AGE_SECS", 600)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WarmupConfig {
+    // 起動時にキャッシュを読み込み終えるまで、準備完了を報告しない
+    pub enabled: bool,
+    // 読み込む最近のセッションの数
+    pub session_limit: u32,
+    pub concurrency: usize,
+    // 読み込みがこれを超えた場合は打ち切り、残りはリクエストのたびに読み込む
+    pub timeout: Duration,
+}
+
+impl WarmupConfig {
+    fn from_env() -> Self {
+        WarmupConfig {
+            enabled: env_parse_or("WARMUP_ENABLED", true),
+            session_limit: env_parse_or("WARMUP_SESSION_LIMIT", 1000),
+            concurrency: env_parse_or("WARMUP_CONCURRENCY", 16),
+            timeout: Duration::from_secs(env_parse_or("WARMUP_TIMEOUT_SECS", 60)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RoutingConfig {
+    // 前処理したグラフを保存するディレクトリ。指定しない場合は起動のたびに前処理する
+    pub cache_dir: Option<PathBuf>,
+    // 起動時に全エリアのグラフを前処理しておく
+    pub preload: bool,
+}
+
+impl RoutingConfig {
+    fn from_env() -> Self {
+        RoutingConfig {
+            cache_dir: env::var("ROUTE_CACHE_DIR").ok().map(PathBuf::from),
+            preload: env_parse_or("ROUTE_PRELOAD", true),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum GeocoderConfig {
+    Disabled,
+    Http(HttpGeocoderConfig),
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpGeocoderConfig {
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeocodingConfig {
+    pub provider: GeocoderConfig,
+    // DB に保存した結果の手前に置く、メモリのキャッシュ
+    pub memory_cache_ttl: Duration,
+    pub memory_cache_capacity: usize,
+    // 見つからなかった結果を保持する期間。見つかった結果は期限なく保持する
+    pub negative_cache_ttl: Duration,
+}
+
+impl GeocodingConfig {
+    fn from_env() -> Self {
+        let provider = match env::var("GEOCODER").as_deref() {
+            Ok("http") => GeocoderConfig::Http(HttpGeocoderConfig {
+                base_url: env::var("GEOCODER_URL").expect("GEOCODER_URL must be set"),
+                api_key: env::var("GEOCODER_API_KEY").ok(),
+                timeout: Duration::from_secs(env_parse_or("GEOCODER_TIMEOUT_SECS", 5)),
+            }),
+            _ => GeocoderConfig::Disabled,
+        };
+
+        GeocodingConfig {
+            provider,
+            memory_cache_ttl: Duration::from_secs(env_parse_or(
+                "GEOCODE_MEMORY_CACHE_TTL_SECS",
+                3600,
+            )),
+            memory_cache_capacity: env_parse_or("GEOCODE_MEMORY_CACHE_CAPACITY", 10_000),
+            negative_cache_ttl: Duration::from_secs(env_parse_or(
+                "GEOCODE_NEGATIVE_CACHE_TTL_SECS",
+                86_400,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegistrationConfig {
+    // false の場合は /register と外部アカウントの初回ログインでユーザーを作らず、管理者が /admin/users でアカウントを作成する
+    pub self_registration_enabled: bool,
+    // 登録に招待コードが必要なロール
+    pub invite_required_roles: Vec<Role>,
+}
+
+impl RegistrationConfig {
+    fn from_env() -> Self {
+        RegistrationConfig {
+            self_registration_enabled: env_parse_or("SELF_REGISTRATION_ENABLED", true),
+            invite_required_roles: env_or("REGISTRATION_INVITE_ROLES", "dispatcher,admin")
+                .split(',')
+                .filter_map(|role| role.trim().parse().ok())
+                .collect(),
+        }
+    }
+}
+
+// 組織ごとの利用上限の既定値。組織に個別の上限を設定していない場合に使う。None の場合は上限なし
+#[derive(Debug, Clone)]
+pub struct QuotaConfig {
+    // UTC の 0 時から数えた 1 日の注文数
+    pub orders_per_day: Option<i64>,
+    // ディスパッチャーのロールを持つ有効なユーザーの数
+    pub active_dispatchers: Option<i64>,
+    // アップロードされたプロフィール画像の合計のバイト数
+    pub avatar_storage_bytes: Option<i64>,
+}
+
+impl QuotaConfig {
+    fn from_env() -> Self {
+        QuotaConfig {
+            orders_per_day: env_limit("QUOTA_ORDERS_PER_DAY"),
+            active_dispatchers: env_limit("QUOTA_ACTIVE_DISPATCHERS"),
+            avatar_storage_bytes: env_limit("QUOTA_AVATAR_STORAGE_BYTES"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoginAnomalyConfig {
+    pub enabled: bool,
+    // 接続元や端末が初めてかどうかを、この期間に成功したログインと比べて判断する
+    pub history: Duration,
+    // この期間に失敗がこの回数以上続いた後の成功を不審とする。0 の場合は判定しない
+    pub failure_burst_threshold: i64,
+    pub failure_burst_window: Duration,
+    // 直前のログインから、この時間内に別のエリアからログインした場合を不審とする
+    pub impossible_travel_window: Duration,
+    // 接続元の IP アドレスからエリアを推定する対応表。LOGIN_IP_AREAS=192.0.2.0/24=1,198.51.100.0/24=2
+    pub ip_areas: Vec<IpAreaRule>,
+}
+
+impl LoginAnomalyConfig {
+    fn from_env() -> Self {
+        LoginAnomalyConfig {
+            enabled: env_parse_or("LOGIN_ANOMALY_DETECTION_ENABLED", true),
+            history: Duration::from_secs(
+                env_parse_or("LOGIN_ANOMALY_HISTORY_DAYS", 90) * 24 * 60 * 60,
+            ),
+            failure_burst_threshold: env_parse_or("LOGIN_FAILURE_BURST_THRESHOLD", 5),
+            failure_burst_window: Duration::from_secs(env_parse_or(
+                "LOGIN_FAILURE_BURST_WINDOW_SECS",
+                10 * 60,
+            )),
+            impossible_travel_window: Duration::from_secs(env_parse_or(
+                "LOGIN_IMPOSSIBLE_TRAVEL_WINDOW_SECS",
+                60 * 60,
+            )),
+            ip_areas: env_or("LOGIN_IP_AREAS", "")
+                .split(',')
+                .filter_map(|rule| IpAreaRule::parse(rule.trim()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IpAreaRule {
+    network: IpAddr,
+    prefix_len: u32,
+    pub area_id: i32,
+}
+
+impl IpAreaRule {
+    // 192.0.2.0/24=1 の形式
+    fn parse(rule: &str) -> Option<Self> {
+        let (network, area_id) = rule.split_once('=')?;
+        let (address, prefix_len) = network.trim().split_once('/')?;
+        let network: IpAddr = address.parse().ok()?;
+        let prefix_len: u32 = prefix_len.parse().ok()?;
+        let max_prefix_len = match network {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix_len > max_prefix_len {
+            return None;
+        }
+
+        Some(IpAreaRule {
+            network,
+            prefix_len,
+            area_id: area_id.trim().parse().ok()?,
+        })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_len).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_len).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserImportConfig {
+    pub max_rows: usize,
+    // パスワードのハッシュ化を同時に行う数
+    pub hash_parallelism: usize,
+    // 1 回の INSERT で登録する行数
+    pub batch_size: usize,
+}
+
+impl UserImportConfig {
+    fn from_env() -> Self {
+        UserImportConfig {
+            max_rows: env_parse_or("USER_IMPORT_MAX_ROWS", 10_000),
+            hash_parallelism: env_parse_or("USER_IMPORT_HASH_PARALLELISM", 4),
+            batch_size: env_parse_or("USER_IMPORT_BATCH_SIZE", 500),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NotificationConfig {
+    // Webhook への送信を試みる回数。すべて失敗した通知はデッドレターに記録する
+    pub webhook_max_attempts: u32,
+    pub webhook_retry_base_delay: Duration,
+    pub webhook_timeout: Duration,
+    // プッシュ通知の送信を試みる回数。使えなくなったトークンには再送しない
+    pub push_max_attempts: u32,
+    pub push_retry_base_delay: Duration,
+}
+
+impl NotificationConfig {
+    fn from_env() -> Self {
+        NotificationConfig {
+            webhook_max_attempts: env_parse_or("NOTIFICATION_WEBHOOK_MAX_ATTEMPTS", 3).max(1),
+            webhook_retry_base_delay: Duration::from_millis(env_parse_or(
+                "NOTIFICATION_WEBHOOK_RETRY_BASE_DELAY_MS",
+                1_000,
+            )),
+            webhook_timeout: Duration::from_millis(env_parse_or(
+                "NOTIFICATION_WEBHOOK_TIMEOUT_MS",
+                5_000,
+            )),
+            push_max_attempts: env_parse_or("NOTIFICATION_PUSH_MAX_ATTEMPTS", 3).max(1),
+            push_retry_base_delay: Duration::from_millis(env_parse_or(
+                "NOTIFICATION_PUSH_RETRY_BASE_DELAY_MS",
+                1_000,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncConfig {
+    // 差分同期の変更の記録を残す期間。これより古い位置から同期するクライアントには読み込み直しを求める
+    pub change_log_retention: Duration,
+    // 古い記録を削除する間隔と、1 回に削除する件数。間隔が 0 の場合は削除しない
+    pub prune_interval: Duration,
+    pub prune_batch_size: u32,
+    // この時間より新しい記録はまだ返さない。ID の順とコミットの順が入れ替わっても読み飛ばさないための待ち時間
+    pub change_log_safety_lag: Duration,
+}
+
+impl SyncConfig {
+    fn from_env() -> Self {
+        SyncConfig {
+            change_log_retention: Duration::from_secs(
+                env_parse_or("SYNC_CHANGE_LOG_RETENTION_HOURS", 72) * 60 * 60,
+            ),
+            prune_interval: Duration::from_secs(env_parse_or("SYNC_PRUNE_INTERVAL_SECS", 60 * 60)),
+            prune_batch_size: env_parse_or("SYNC_PRUNE_BATCH_SIZE", 10_000),
+            // created_at は秒単位のため、1 秒より短くはしない
+            change_log_safety_lag: Duration::from_secs(
+                env_parse_or("SYNC_CHANGE_LOG_SAFETY_LAG_SECS", 2).max(1),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WriteBehindConfig {
+    // 監査ログと車両の位置をまとめて書き込む間隔。0 の場合は溜めずにその場で書き込む。
+    // 位置はディスパッチで最新のものを読むため、既定では溜めない
+    pub audit_flush_interval: Duration,
+    pub location_flush_interval: Duration,
+    // 1 回の INSERT で書き込む件数。この件数が溜まった時点で間隔を待たずに書き込む
+    pub batch_size: usize,
+    // 書き込めずに溜まった件数の上限。超えた分は古いものから捨てる
+    pub max_pending: usize,
+}
+
+impl WriteBehindConfig {
+    fn from_env() -> Self {
+        WriteBehindConfig {
+            audit_flush_interval: Duration::from_millis(env_parse_or(
+                "AUDIT_LOG_FLUSH_INTERVAL_MS",
+                1000,
+            )),
+            location_flush_interval: Duration::from_millis(env_parse_or(
+                "LOCATION_FLUSH_INTERVAL_MS",
+                0,
+            )),
+            batch_size: env_parse_or("WRITE_BEHIND_BATCH_SIZE", 500),
+            max_pending: env_parse_or("WRITE_BEHIND_MAX_PENDING", 100_000),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetentionConfig {
+    // 保持期間を過ぎたデータを処理する間隔。0 の場合は処理しない
+    pub interval: Duration,
+    // 監査ログを削除するまでと、完了した注文を匿名化するまでの日数。0 の場合は期限なく残す
+    pub audit_log_days: u32,
+    pub completed_order_days: u32,
+    // 1 回に処理する件数と、次の件数を処理するまでの待ち時間。
+    // 1 回の実行で処理しきれなかった分は次の実行に回す
+    pub batch_size: u32,
+    pub batch_pause: Duration,
+    pub max_batches_per_run: u32,
+    // true の場合は処理せず、対象になる件数をログに出力するだけにする
+    pub dry_run: bool,
+}
+
+impl RetentionConfig {
+    fn from_env() -> Self {
+        RetentionConfig {
+            interval: Duration::from_secs(env_parse_or("RETENTION_INTERVAL_SECS", 60 * 60)),
+            audit_log_days: env_parse_or("RETENTION_AUDIT_LOG_DAYS", 0),
+            completed_order_days: env_parse_or("RETENTION_COMPLETED_ORDER_DAYS", 0),
+            batch_size: env_parse_or("RETENTION_BATCH_SIZE", 1000),
+            batch_pause: Duration::from_millis(env_parse_or("RETENTION_BATCH_PAUSE_MS", 100)),
+            max_batches_per_run: env_parse_or("RETENTION_MAX_BATCHES_PER_RUN", 100),
+            dry_run: env_parse_or("RETENTION_DRY_RUN", false),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    // 送信待ちの配信を確認する間隔。0 の場合は送信しない
+    pub poll_interval: Duration,
+    // 1 回の確認で送信する配信の数と、そのうち同時に送信する数
+    pub batch_size: i32,
+    pub concurrency: usize,
+    // 送信を試みる回数。すべて失敗した配信は failed として残す
+    pub max_attempts: i32,
+    pub retry_base_delay: Duration,
+    pub retry_max_delay: Duration,
+    pub timeout: Duration,
+}
+
+impl WebhookConfig {
+    fn from_env() -> Self {
+        WebhookConfig {
+            poll_interval: Duration::from_millis(env_parse_or("WEBHOOK_POLL_INTERVAL_MS", 5_000)),
+            batch_size: env_parse_or("WEBHOOK_BATCH_SIZE", 100),
+            concurrency: env_parse_or("WEBHOOK_CONCURRENCY", 8).max(1),
+            max_attempts: env_parse_or("WEBHOOK_MAX_ATTEMPTS", 8).max(1),
+            retry_base_delay: Duration::from_secs(env_parse_or(
+                "WEBHOOK_RETRY_BASE_DELAY_SECS",
+                10,
+            )),
+            retry_max_delay: Duration::from_secs(env_parse_or(
+                "WEBHOOK_RETRY_MAX_DELAY_SECS",
+                60 * 60,
+            )),
+            timeout: Duration::from_millis(env_parse_or("WEBHOOK_TIMEOUT_MS", 10_000)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FeatureFlagConfig {
+    // FEATURE_FLAGS で指定した初期値。"name" で有効、"-name" で無効にする
+    pub defaults: HashMap<String, bool>,
+    // DB のフラグを読み直す間隔。0 の場合は起動時と管理 API での変更時にだけ反映する
+    pub reload_interval: Duration,
+}
+
+impl FeatureFlagConfig {
+    fn from_env() -> Self {
+        let defaults = env::var("FEATURE_FLAGS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| match name.strip_prefix('-') {
+                Some(name) => (name.to_string(), false),
+                None => (name.to_string(), true),
+            })
+            .collect();
+
+        FeatureFlagConfig {
+            defaults,
+            reload_interval: Duration::from_millis(env_parse_or(
+                "FEATURE_FLAG_RELOAD_INTERVAL_MS",
+                5_000,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigReloadConfig {
+    // 実行中に変更する値を KEY=VALUE の形式で書いたファイル。キーは起動時の環境変数と同じ名前
+    pub file: Option<PathBuf>,
+    // ファイルの更新を確認する間隔。0 の場合は管理 API からの再読み込みだけを受け付ける
+    pub watch_interval: Duration,
+}
+
+impl ConfigReloadConfig {
+    fn from_env() -> Self {
+        ConfigReloadConfig {
+            file: env::var("CONFIG_RELOAD_FILE").ok().map(PathBuf::from),
+            watch_interval: Duration::from_millis(env_parse_or(
+                "CONFIG_RELOAD_WATCH_INTERVAL_MS",
+                2_000,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I18nConfig {
+    // Accept-Language に対応するカタログがない場合に使うロケール
+    pub default_locale: String,
+    // <ロケール>.json の形式で、組み込みのカタログを上書きするファイルを置くディレクトリ
+    pub dir: Option<PathBuf>,
+}
+
+impl I18nConfig {
+    fn from_env() -> Self {
+        I18nConfig {
+            default_locale: env_or("DEFAULT_LOCALE", "en").to_ascii_lowercase(),
+            dir: env::var("LOCALE_DIR").ok().map(PathBuf::from),
+        }
+    }
+}
+
+// 再起動せずに変更できる設定。接続プールの大きさは sqlx のプールを作り直す必要があるため含めない
+#[derive(Debug, Clone, PartialEq)]
+pub struct TunableConfig {
+    pub log_filter: String,
+    pub user_cache_ttl: Duration,
+    pub query_cache_ttl: Duration,
+    pub negative_cache_ttl: Duration,
+    pub slow_query_threshold: Duration,
+    pub max_active_assignments: usize,
+}
+
+impl TunableConfig {
+    pub fn from_config(config: &AppConfig) -> Self {
+        TunableConfig {
+            log_filter: config.log_filter.clone(),
+            user_cache_ttl: config.db.user_cache_ttl,
+            query_cache_ttl: config.db.query_cache_ttl,
+            negative_cache_ttl: config.db.negative_cache_ttl,
+            slow_query_threshold: config.db.slow_query_threshold,
+            max_active_assignments: config.dispatcher.max_active_assignments,
+        }
+    }
+
+    // キーは環境変数と同じ名前で、大文字と小文字を区別しない
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        match key.to_ascii_uppercase().as_str() {
+            "RUST_LOG" if !value.is_empty() => self.log_filter = value.to_string(),
+            "USER_CACHE_TTL_MS" => self.user_cache_ttl = parse_millis(key, value)?,
+            "QUERY_CACHE_TTL_MS" => self.query_cache_ttl = parse_millis(key, value)?,
+            "NEGATIVE_CACHE_TTL_MS" => self.negative_cache_ttl = parse_millis(key, value)?,
+            "DB_SLOW_QUERY_THRESHOLD_MS" => self.slow_query_threshold = parse_millis(key, value)?,
+            "DISPATCHER_MAX_ACTIVE_ASSIGNMENTS" => {
+                self.max_active_assignments = value
+                    .parse()
+                    .map_err(|_| format!("{} の値が不正です: {}", key, value))?
+            }
+            _ => return Err(format!("{} は実行中に変更できません", key)),
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_millis(key: &str, value: &str) -> Result<Duration, String> {
+    value
+        .parse()
+        .map(Duration::from_millis)
+        .map_err(|_| format!("{} の値が不正です: {}", key, value))
+}
+
+fn env_or(key: &str, default: &str) -> String {
+    env::var(key).unwrap_or_else(|_| default.to_string())
+}
+
+// 未設定や負の値は上限なしとして扱う
+fn env_limit(key: &str) -> Option<i64> {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|limit: &i64| *limit >= 0)
+}
+
+fn env_parse_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
@@ -0,0 +1,102 @@
+use sha2::{Digest, Sha256};
+
+use crate::errors::AppError;
+use crate::models::api_key::ApiKey;
+use crate::models::organization::OrganizationScope;
+use crate::utils::generate_session_token;
+
+use super::dto::api_key::ApiKeyCreatedDto;
+
+const API_KEY_PREFIX: &str = "ak_";
+
+pub trait ApiKeyRepository {
+    async fn create_api_key(
+        &self,
+        organization_id: i32,
+        name: &str,
+        key_prefix: &str,
+        key_hash: &str,
+        scopes: &str,
+    ) -> Result<i32, AppError>;
+    async fn find_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, AppError>;
+    // 他の組織のキーは失効させず、false を返す
+    async fn deactivate_api_key(&self, organization_id: i32, id: i32) -> Result<bool, AppError>;
+}
+
+#[derive(Debug)]
+pub struct ApiKeyService<T: ApiKeyRepository + std::fmt::Debug> {
+    repository: T,
+}
+
+impl<T: ApiKeyRepository + std::fmt::Debug> ApiKeyService<T> {
+    pub fn new(repository: T) -> Self {
+        ApiKeyService { repository }
+    }
+
+    pub async fn issue_api_key(
+        &self,
+        organization: OrganizationScope,
+        name: &str,
+        scopes: &[String],
+    ) -> Result<ApiKeyCreatedDto, AppError> {
+        if name.is_empty() || scopes.is_empty() || scopes.iter().any(|s| s.contains(',')) {
+            return Err(AppError::BadRequest);
+        }
+
+        let api_key = format!("{}{}", API_KEY_PREFIX, generate_session_token());
+        let key_prefix = &api_key[..API_KEY_PREFIX.len() + 6];
+        let id = self
+            .repository
+            .create_api_key(
+                organization.organization_id,
+                name,
+                key_prefix,
+                &hash_api_key(&api_key),
+                &scopes.join(","),
+            )
+            .await?;
+
+        // 平文のキーを返すのは発行時のこの一度だけ
+        Ok(ApiKeyCreatedDto {
+            id,
+            name: name.to_string(),
+            api_key,
+            scopes: scopes.to_vec(),
+        })
+    }
+
+    pub async fn revoke_api_key(
+        &self,
+        organization: OrganizationScope,
+        id: i32,
+    ) -> Result<(), AppError> {
+        match self
+            .repository
+            .deactivate_api_key(organization.organization_id, id)
+            .await?
+        {
+            true => Ok(()),
+            false => Err(AppError::NotFound),
+        }
+    }
+
+    pub async fn authenticate(&self, api_key: &str, path: &str) -> Result<ApiKey, AppError> {
+        let key = match self
+            .repository
+            .find_api_key_by_hash(&hash_api_key(api_key))
+            .await?
+        {
+            Some(key) if key.is_active => key,
+            _ => return Err(AppError::Unauthorized),
+        };
+
+        match key.allows_path(path) {
+            true => Ok(key),
+            false => Err(AppError::Forbidden),
+        }
+    }
+}
+
+fn hash_api_key(api_key: &str) -> String {
+    format!("{:x}", Sha256::digest(api_key.as_bytes()))
+}
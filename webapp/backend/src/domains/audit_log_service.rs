@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use crate::config::WriteBehindConfig;
+use crate::errors::AppError;
+use crate::infrastructure::write_behind::{BatchWriter, WriteBehindBuffer};
+use crate::models::audit_log::NewAuditLog;
+
+pub trait AuditLogRepository {
+    async fn create_audit_log(&self, entry: &NewAuditLog) -> Result<(), AppError>;
+    // 複数行の INSERT でまとめて書き込む
+    async fn create_audit_logs(&self, entries: &[NewAuditLog]) -> Result<(), AppError>;
+}
+
+// 溜めた監査ログの書き込み先
+#[derive(Debug)]
+pub struct AuditLogWriter<T>(T);
+
+impl<T: AuditLogRepository> BatchWriter for AuditLogWriter<T> {
+    type Item = NewAuditLog;
+
+    async fn write_batch(&self, items: &[NewAuditLog]) -> Result<(), AppError> {
+        self.0.create_audit_logs(items).await
+    }
+}
+
+// 管理者の操作やセキュリティ上の出来事を audit_logs に残す。参照は /admin/audit_logs/export から行う。
+// 操作のたびに書き込まないよう、記録はメモリに溜めてまとめて書き込む
+#[derive(Debug)]
+pub struct AuditLogService<T: AuditLogRepository + std::fmt::Debug> {
+    buffer: Arc<WriteBehindBuffer<AuditLogWriter<T>>>,
+}
+
+impl<T: AuditLogRepository + std::fmt::Debug> AuditLogService<T> {
+    pub fn new(repository: T, config: &WriteBehindConfig) -> Self {
+        AuditLogService {
+            buffer: Arc::new(WriteBehindBuffer::new(
+                "監査ログ",
+                AuditLogWriter(repository),
+                config.audit_flush_interval,
+                config,
+            )),
+        }
+    }
+
+    pub async fn record(&self, entry: NewAuditLog) -> Result<(), AppError> {
+        if !self.buffer.is_enabled() {
+            return self.buffer.writer().0.create_audit_log(&entry).await;
+        }
+
+        self.buffer.push(entry);
+        Ok(())
+    }
+
+    // 停止時に、溜まっている記録を書き込む
+    pub async fn flush(&self) -> Result<usize, AppError> {
+        self.buffer.flush().await
+    }
+}
+
+impl<T: AuditLogRepository + std::fmt::Debug + 'static> AuditLogService<T> {
+    pub fn spawn_flusher(&self) {
+        self.buffer.spawn_flusher();
+    }
+}
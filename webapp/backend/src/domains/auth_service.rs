@@ -1,82 +1,402 @@
-use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use actix_web::web::Bytes;
-use log::error;
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, StreamExt};
+use totp_rs::{Algorithm, Secret, TOTP};
 
+use crate::config::{EmailVerificationConfig, RegistrationConfig, SessionConfig};
 use crate::errors::AppError;
-use crate::models::user::{Dispatcher, Session, User};
-use crate::utils::{generate_session_token, hash_password, verify_password};
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::jwt::{JwtCodec, SessionClaims};
+use crate::infrastructure::mailer::{Mail, Mailer, MailerImpl};
+use crate::infrastructure::request_id;
+use crate::infrastructure::ttl_cache::TtlCache;
+use crate::infrastructure::worker_pool::WorkerPool;
+use crate::models::organization::{OrganizationScope, DEFAULT_ORGANIZATION_ID};
+use crate::models::role::{Permission, Role};
+use crate::models::user::{
+    Dispatcher, OnDutyDispatcher, Session, SessionActivity, SessionClient, StoredProfileImage,
+    TotpBackupCode, User,
+};
+use crate::utils::{
+    constant_time_eq, generate_session_token, hash_password, is_valid_email, normalize_username,
+    sha256_hex, verify_password,
+};
 
-use super::dto::auth::LoginResponseDto;
+use super::dto::auth::{
+    CreatedUserDto, ImpersonationDto, ImpersonationResponseDto, LoginResponseDto,
+    PendingRegistrationResponseDto, RegisterResponseDto, SessionDto, TotpSetupResponseDto,
+};
+use super::events::DomainEvent;
+use super::quota_service::{QuotaRepository, QuotaService};
+
+const TOTP_ISSUER: &str = "HiroshimaUniv-Tuning-2409";
+const TOTP_BACKUP_CODE_COUNT: usize = 10;
+const TOTP_BACKUP_CODE_LENGTH: usize = 10;
+// 6 桁のコードを総当たりされないよう、続けて失敗した場合はしばらく受け付けない
+const TOTP_MAX_FAILED_ATTEMPTS: i32 = 5;
+const TOTP_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+const EXTERNAL_USERNAME_MAX_ATTEMPTS: usize = 5;
+pub const USERNAME_RESERVATION_DAYS: i64 = 30;
+const SESSION_VALIDATION_CACHE_CAPACITY: usize = 100_000;
+// 1 回の UPDATE でまとめて書き込むセッションの数
+const SESSION_ACTIVITY_BATCH_SIZE: usize = 500;
 
 pub trait AuthRepository {
-    async fn create_user(&self, username: &str, password: &str, role: &str)
-        -> Result<(), AppError>;
+    async fn create_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        organization_id: i32,
+    ) -> Result<(), AppError>;
+    // メールアドレスの確認が済んでいない状態で作成する
+    async fn create_unverified_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        email: &str,
+    ) -> Result<(), AppError>;
     async fn find_user_by_id(&self, id: i32) -> Result<Option<User>, AppError>;
     async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, AppError>;
+    async fn find_user_by_username_from_primary(
+        &self,
+        username: &str,
+    ) -> Result<Option<User>, AppError>;
+    // 起動時にユーザー名の重複を調べるため、すべてのユーザーの ID とユーザー名を ID 順にプライマリから読む
+    async fn find_all_usernames(&self) -> Result<Vec<(i32, String)>, AppError>;
+    // エリアが存在しない場合は None
+    async fn find_area_organization_id(&self, area_id: i32) -> Result<Option<i32>, AppError>;
+    // ユーザーと異なる組織のエリアは担当させず、BadRequest を返す
     async fn create_dispatcher(&self, user_id: i32, area_id: i32) -> Result<(), AppError>;
     async fn find_dispatcher_by_id(&self, id: i32) -> Result<Option<Dispatcher>, AppError>;
     async fn find_dispatcher_by_user_id(
         &self,
         user_id: i32,
     ) -> Result<Option<Dispatcher>, AppError>;
-    async fn find_profile_image_name_by_user_id(
+    async fn find_dispatcher_by_user_id_from_primary(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<Dispatcher>, AppError>;
+    // 期限内の有効なセッションを持つ、有効なユーザーのディスパッチャーだけを返す
+    async fn find_on_duty_dispatchers_by_area_id(
+        &self,
+        area_id: i32,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<OnDutyDispatcher>, AppError>;
+    async fn find_profile_image_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<StoredProfileImage>, AppError>;
+    // 画像を差し替えて image_version を上げ、上げた後の値を返す
+    async fn update_profile_image_name(
+        &self,
+        user_id: i32,
+        profile_image_name: &str,
+    ) -> Result<i32, AppError>;
+    // 画像が current のままの場合だけ replacement に差し替え、上げた後の image_version を返す。
+    // すでに別の画像に差し替わっていた場合は None
+    async fn replace_profile_image_name(
+        &self,
+        user_id: i32,
+        current: &str,
+        replacement: &str,
+    ) -> Result<Option<i32>, AppError>;
+    async fn find_recent_username_owner(
+        &self,
+        username: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<i32>, AppError>;
+    async fn update_username(
+        &self,
+        user_id: i32,
+        old_username: &str,
+        new_username: &str,
+        changed_at: DateTime<Utc>,
+    ) -> Result<(), AppError>;
+    // 仮のパスワードだった場合は、新しいパスワードの設定が済んだものとして扱う
+    async fn update_password(&self, user_id: i32, password: &str) -> Result<(), AppError>;
+    async fn require_password_reset(&self, user_id: i32) -> Result<(), AppError>;
+    async fn update_user_role(&self, user_id: i32, role: Role) -> Result<(), AppError>;
+    async fn deactivate_user(&self, user_id: i32) -> Result<(), AppError>;
+    // ディスパッチャーとして登録されている場合は、その担当も同じ組織に移す
+    async fn update_user_organization(
+        &self,
+        user_id: i32,
+        organization_id: i32,
+    ) -> Result<(), AppError>;
+    async fn update_totp_secret(
+        &self,
+        user_id: i32,
+        totp_secret: Option<&str>,
+    ) -> Result<(), AppError>;
+    async fn enable_totp(&self, user_id: i32) -> Result<(), AppError>;
+    async fn replace_totp_backup_codes(
         &self,
         user_id: i32,
-    ) -> Result<Option<String>, AppError>;
-    async fn create_session(&self, user_id: i32, session_token: &str) -> Result<(), AppError>;
+        code_hashes: &[String],
+    ) -> Result<(), AppError>;
+    async fn find_unused_totp_backup_codes(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<TotpBackupCode>, AppError>;
+    async fn mark_totp_backup_code_used(&self, id: i32) -> Result<bool, AppError>;
+    async fn find_totp_locked_until(&self, user_id: i32)
+        -> Result<Option<DateTime<Utc>>, AppError>;
+    // 続けて失敗した回数が max_attempts に達したら、回数を戻して locked_until まで受け付けないようにする
+    async fn record_totp_failure(
+        &self,
+        user_id: i32,
+        max_attempts: i32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), AppError>;
+    // これまでに受け付けたものより新しい時間枠の場合だけ記録し、失敗した回数を戻す
+    async fn accept_totp_step(&self, user_id: i32, step: i64) -> Result<bool, AppError>;
+    async fn reset_totp_failures(&self, user_id: i32) -> Result<(), AppError>;
+    async fn find_user_id_by_external_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<i32>, AppError>;
+    async fn create_external_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+        user_id: i32,
+    ) -> Result<(), AppError>;
+    async fn create_session(
+        &self,
+        user_id: i32,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+        client: &SessionClient,
+    ) -> Result<(), AppError>;
+    async fn create_impersonation_session(
+        &self,
+        user_id: i32,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+        impersonator_user_id: i32,
+        client: &SessionClient,
+    ) -> Result<(), AppError>;
+    // 最後に使われた日時は、すでに記録されているものより新しい場合だけ更新する
+    async fn update_session_activities(
+        &self,
+        activities: &[SessionActivity],
+    ) -> Result<(), AppError>;
+    async fn update_session_expires_at(
+        &self,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError>;
     async fn delete_session(&self, session_token: &str) -> Result<(), AppError>;
+    async fn delete_other_sessions_by_user_id(
+        &self,
+        user_id: i32,
+        keep_session_token: &str,
+    ) -> Result<(), AppError>;
+    async fn delete_expired_sessions(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<u64, AppError>;
     async fn find_session_by_session_token(&self, session_token: &str)
         -> Result<Session, AppError>;
+    // 期限内の有効なセッションを、作成が新しい順に返す
+    async fn find_sessions_by_user_id(
+        &self,
+        user_id: i32,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, AppError>;
+    // 期限内のなりかわりのセッションを、作成が新しい順に返す
+    async fn find_impersonation_sessions(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, AppError>;
+    // 起動時のキャッシュの準備に使う。有効期限が遅い、つまり最近使われたセッションから返す
+    async fn find_recent_session_tokens(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<String>, AppError>;
+    async fn create_email_verification_token(
+        &self,
+        user_id: i32,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError>;
+    // 期限内のトークンであれば使用済みにしてユーザーを確認済みにし、そのユーザーの ID を返す
+    async fn verify_email(
+        &self,
+        token_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<i32>, AppError>;
+}
+
+// 認証済みのセッションと、その時点のユーザーのロールと組織
+#[derive(Debug, Clone)]
+struct ValidatedSession {
+    session: Session,
+    role: Role,
+    organization_id: i32,
+}
+
+#[derive(Debug)]
+pub struct UsernameRename {
+    pub user_id: i32,
+    pub old_username: String,
+    pub new_username: String,
 }
 
 #[derive(Debug)]
-pub struct AuthService<T: AuthRepository + std::fmt::Debug> {
+pub struct AuthService<T: AuthRepository + std::fmt::Debug, U: QuotaRepository + std::fmt::Debug> {
     repository: T,
+    quotas: Arc<QuotaService<U>>,
+    session_ttl: Duration,
+    impersonation_ttl: Duration,
+    jwt_codec: Option<JwtCodec>,
+    event_bus: Arc<EventBus>,
+    email_verification: EmailVerificationConfig,
+    // false の場合は /register と外部アカウントの初回ログインのどちらでもユーザーを作らない
+    self_registration_enabled: bool,
+    mailer: Arc<MailerImpl>,
+    // Argon2 の計算はワーカーのスレッドを止めないよう、専用のプールで行う
+    password_workers: Arc<WorkerPool>,
+    // 認証が必要なリクエストのたびに DB を引かないよう、検証結果を短時間だけ保持する
+    validated_sessions: Arc<TtlCache<String, ValidatedSession>>,
+    // リクエストのたびに UPDATE しないよう、セッションの利用状況は定期的にまとめて書き込む
+    pending_activities: Mutex<HashMap<String, SessionActivity>>,
 }
 
-impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
-    pub fn new(repository: T) -> Self {
-        AuthService { repository }
+impl<T: AuthRepository + std::fmt::Debug, U: QuotaRepository + std::fmt::Debug> AuthService<T, U> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        repository: T,
+        quotas: Arc<QuotaService<U>>,
+        config: &SessionConfig,
+        email_verification: &EmailVerificationConfig,
+        registration: &RegistrationConfig,
+        mailer: Arc<MailerImpl>,
+        password_workers: Arc<WorkerPool>,
+        event_bus: Arc<EventBus>,
+    ) -> Self {
+        let validated_sessions = Arc::new(TtlCache::new(
+            config.validation_cache_ttl,
+            SESSION_VALIDATION_CACHE_CAPACITY,
+        ));
+        let subscriber = validated_sessions.clone();
+        event_bus.subscribe(move |event| match (event, event.user_id()) {
+            (DomainEvent::DataReset, _) => subscriber.clear(),
+            (_, Some(user_id)) => subscriber
+                .retain(|_, validated: &ValidatedSession| validated.session.user_id != user_id),
+            _ => {}
+        });
+
+        AuthService {
+            repository,
+            quotas,
+            session_ttl: config.ttl,
+            impersonation_ttl: config.impersonation_ttl,
+            jwt_codec: config.jwt_secret.as_deref().map(JwtCodec::new),
+            event_bus,
+            email_verification: email_verification.clone(),
+            self_registration_enabled: registration.self_registration_enabled,
+            mailer,
+            password_workers,
+            validated_sessions,
+            pending_activities: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // 管理者が /admin/users で作成する場合は対象外
+    pub fn ensure_self_registration_enabled(&self) -> Result<(), AppError> {
+        if !self.self_registration_enabled {
+            return Err(AppError::Forbidden);
+        }
+
+        Ok(())
     }
 
     pub async fn register_user(
         &self,
         username: &str,
         password: &str,
-        role: &str,
+        role: Role,
         area: Option<i32>,
-    ) -> Result<LoginResponseDto, AppError> {
-        if role == "dispatcher" && area.is_none() {
+        email: Option<&str>,
+        client: &SessionClient,
+    ) -> Result<RegisterResponseDto, AppError> {
+        self.ensure_self_registration_enabled()?;
+        if role == Role::Dispatcher && area.is_none() {
             return Err(AppError::BadRequest);
         }
-
-        if (self.repository.find_user_by_username(username).await?).is_some() {
-            return Err(AppError::Conflict);
+        // 自分で登録したユーザーは既定の組織に属する
+        let organization = OrganizationScope::new(DEFAULT_ORGANIZATION_ID);
+        if role == Role::Dispatcher {
+            self.ensure_area_in_organization(area, organization).await?;
+            self.quotas.check_dispatcher_quota(organization).await?;
         }
+        // 確認が無効な場合、メールアドレスは受け取っても保存しない
+        let email = match (self.email_verification.required, email) {
+            (true, Some(email)) if is_valid_email(email) => Some(email),
+            (true, _) => return Err(AppError::BadRequest),
+            (false, _) => None,
+        };
 
-        let hashed_password = hash_password(password).unwrap();
+        let username = &self.new_username(username).await?;
+        let hashed_password = self.hash_password(password).await?;
+
+        if let Some(email) = email {
+            self.repository
+                .create_unverified_user(username, &hashed_password, role, email)
+                .await?;
+            let user = self
+                .repository
+                .find_user_by_username_from_primary(username)
+                .await?
+                .ok_or(AppError::InternalServerError)?;
+            if let (Role::Dispatcher, Some(area)) = (role, area) {
+                self.repository.create_dispatcher(user.id, area).await?;
+            }
+            self.send_verification_email(user.id, email).await?;
+
+            return Ok(RegisterResponseDto::PendingVerification(
+                PendingRegistrationResponseDto {
+                    user_id: user.id,
+                    username: user.username,
+                    email_verification_required: true,
+                },
+            ));
+        }
 
         self.repository
-            .create_user(username, &hashed_password, role)
+            .create_user(username, &hashed_password, role, DEFAULT_ORGANIZATION_ID)
             .await?;
 
         let session_token = generate_session_token();
+        let server_time = Utc::now();
+        let expires_at = self.session_expires_at(server_time);
 
-        match self.repository.find_user_by_username(username).await? {
+        let response = match self
+            .repository
+            .find_user_by_username_from_primary(username)
+            .await?
+        {
             Some(user) => {
                 self.repository
-                    .create_session(user.id, &session_token)
+                    .create_session(user.id, &session_token, expires_at, client)
                     .await?;
-                match user.role.as_str() {
-                    "dispatcher" => {
+                match user.role {
+                    Role::Dispatcher => {
                         self.repository
                             .create_dispatcher(user.id, area.unwrap())
                             .await?;
                         let dispatcher = self
                             .repository
-                            .find_dispatcher_by_user_id(user.id)
+                            .find_dispatcher_by_user_id_from_primary(user.id)
                             .await?
                             .unwrap();
                         Ok(LoginResponseDto {
@@ -86,6 +406,8 @@ impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
                             role: user.role,
                             dispatcher_id: Some(dispatcher.id),
                             area_id: Some(dispatcher.area_id),
+                            expires_at,
+                            server_time,
                         })
                     }
                     _ => Ok(LoginResponseDto {
@@ -95,40 +417,124 @@ impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
                         role: user.role,
                         dispatcher_id: None,
                         area_id: None,
+                        expires_at,
+                        server_time,
                     }),
                 }
             }
             None => Err(AppError::InternalServerError),
+        }?;
+
+        Ok(RegisterResponseDto::LoggedIn(
+            self.issue_session_token(response)?,
+        ))
+    }
+
+    pub async fn verify_email(&self, token: &str) -> Result<(), AppError> {
+        match self
+            .repository
+            .verify_email(&sha256_hex(token), Utc::now())
+            .await?
+        {
+            Some(_) => Ok(()),
+            None => Err(AppError::BadRequest),
         }
     }
 
+    // 送信の完了は待たずに応答する。送信に失敗した場合はメーラーがログに残す
+    async fn send_verification_email(&self, user_id: i32, email: &str) -> Result<(), AppError> {
+        let token = generate_session_token();
+        let expires_at = Utc::now()
+            + chrono::Duration::from_std(self.email_verification.token_ttl)
+                .map_err(|_| AppError::InternalServerError)?;
+        self.repository
+            .create_email_verification_token(user_id, &sha256_hex(&token), expires_at)
+            .await?;
+
+        let mail = Mail {
+            to: email.to_string(),
+            subject: "メールアドレスの確認".to_string(),
+            body: format!(
+                "以下の URL を開いて登録を完了してください。\n{}{}\n",
+                self.email_verification.verify_url, token
+            ),
+        };
+        let mailer = self.mailer.clone();
+        request_id::spawn(async move {
+            let _ = mailer.send(&mail).await;
+        });
+
+        Ok(())
+    }
+
+    // 管理者が作成したアカウントの初回のログインでは、new_password で仮のパスワードを置き換えてからセッションを発行する
     pub async fn login_user(
         &self,
         username: &str,
         password: &str,
+        totp_code: Option<&str>,
+        new_password: Option<&str>,
+        client: &SessionClient,
     ) -> Result<LoginResponseDto, AppError> {
-        match self.repository.find_user_by_username(username).await? {
+        match self.find_user_for_login(username).await? {
             Some(user) => {
-                let is_password_valid = verify_password(&user.password, password).unwrap();
+                let is_password_valid = self.verify_password(&user.password, password).await?;
                 if !is_password_valid {
+                    self.publish_login_failed(user.id, client);
+                }
+                if !is_password_valid || !user.is_active {
                     return Err(AppError::Unauthorized);
                 }
+                if !user.email_verified {
+                    return Err(AppError::Forbidden);
+                }
+
+                if let Err(err) = self.verify_second_factor(&user, totp_code).await {
+                    if matches!(err, AppError::Unauthorized) {
+                        self.publish_login_failed(user.id, client);
+                    }
+                    return Err(err);
+                }
+                if user.password_reset_required {
+                    match new_password {
+                        Some(new_password)
+                            if !new_password.is_empty() && new_password != password =>
+                        {
+                            let hashed_password = self.hash_password(new_password).await?;
+                            self.repository
+                                .update_password(user.id, &hashed_password)
+                                .await?;
+                            self.event_bus
+                                .publish(DomainEvent::PasswordChanged { user_id: user.id });
+                        }
+                        Some(_) => return Err(AppError::BadRequest),
+                        None => return Err(AppError::PasswordChangeRequired),
+                    }
+                }
 
                 let session_token = generate_session_token();
+                let server_time = Utc::now();
+                let expires_at = self.session_expires_at(server_time);
                 self.repository
-                    .create_session(user.id, &session_token)
+                    .create_session(user.id, &session_token, expires_at, client)
                     .await?;
+                self.event_bus.publish(DomainEvent::LoginSucceeded {
+                    user_id: user.id,
+                    client: client.clone(),
+                });
 
-                match user.role.as_str() {
-                    "dispatcher" => {
+                let response = match user.role {
+                    Role::Dispatcher => {
                         match self.repository.find_dispatcher_by_user_id(user.id).await? {
                             Some(dispatcher) => Ok(LoginResponseDto {
                                 user_id: user.id,
                                 username: user.username,
                                 session_token,
-                                role: user.role.clone(),
+                                role: user.role,
                                 dispatcher_id: Some(dispatcher.id),
                                 area_id: Some(dispatcher.area_id),
+                                expires_at,
+                                server_time,
                             }),
                             None => Err(AppError::InternalServerError),
                         }
@@ -137,69 +543,1043 @@ impl<T: AuthRepository + std::fmt::Debug> AuthService<T> {
                         user_id: user.id,
                         username: user.username,
                         session_token,
-                        role: user.role.clone(),
+                        role: user.role,
                         dispatcher_id: None,
                         area_id: None,
+                        expires_at,
+                        server_time,
                     }),
-                }
+                }?;
+
+                self.issue_session_token(response)
             }
             None => Err(AppError::Unauthorized),
         }
     }
 
-    pub async fn logout_user(&self, session_token: &str) -> Result<(), AppError> {
-        self.repository.delete_session(session_token).await?;
+    pub async fn change_username(&self, user_id: i32, new_username: &str) -> Result<(), AppError> {
+        let new_username = normalize_username(new_username);
+        if new_username.is_empty() {
+            return Err(AppError::BadRequest);
+        }
+
+        let user = match self.repository.find_user_by_id(user_id).await? {
+            Some(user) => user,
+            None => return Err(AppError::NotFound),
+        };
+        if user.username == new_username {
+            return Ok(());
+        }
+        if !self
+            .is_username_available(&new_username, Some(user.id))
+            .await?
+        {
+            return Err(AppError::Conflict);
+        }
+
+        self.repository
+            .update_username(user.id, &user.username, &new_username, Utc::now())
+            .await
+    }
+
+    pub async fn change_password(
+        &self,
+        user_id: i32,
+        old_password: &str,
+        new_password: &str,
+        current_session_token: &str,
+    ) -> Result<(), AppError> {
+        if new_password.is_empty() {
+            return Err(AppError::BadRequest);
+        }
+
+        let user = match self.repository.find_user_by_id(user_id).await? {
+            Some(user) => user,
+            None => return Err(AppError::NotFound),
+        };
+        if !self.verify_password(&user.password, old_password).await? {
+            return Err(AppError::Unauthorized);
+        }
+
+        let hashed_password = self.hash_password(new_password).await?;
+        self.repository
+            .update_password(user.id, &hashed_password)
+            .await?;
+        self.event_bus
+            .publish(DomainEvent::PasswordChanged { user_id: user.id });
+
+        // パスワードが漏れていた場合に備え、変更操作をしたセッション以外はすべて無効にする
+        self.repository
+            .delete_other_sessions_by_user_id(user.id, current_session_token)
+            .await?;
+        self.validated_sessions.retain(|session_token, validated| {
+            validated.session.user_id != user.id || session_token == current_session_token
+        });
+
         Ok(())
     }
 
-    pub async fn get_resized_profile_image_byte(
+    pub async fn change_role(
         &self,
+        organization: OrganizationScope,
         user_id: i32,
-        width: i32,
-        height: i32,
-    ) -> Result<Bytes, AppError> {
-        let profile_image_name = match self
+        role: Role,
+        area: Option<i32>,
+    ) -> Result<(), AppError> {
+        let user = match self.repository.find_user_by_id(user_id).await? {
+            Some(user) if user.organization_id == organization.organization_id => user,
+            _ => return Err(AppError::NotFound),
+        };
+        if user.role == role {
+            return Ok(());
+        }
+        if role == Role::Dispatcher {
+            self.quotas.check_dispatcher_quota(organization).await?;
+        }
+
+        // ディスパッチャーへの変更時は担当エリアが必要。過去にディスパッチャーだった場合はその行を再利用する
+        if role == Role::Dispatcher
+            && self
+                .repository
+                .find_dispatcher_by_user_id_from_primary(user.id)
+                .await?
+                .is_none()
+        {
+            self.ensure_area_in_organization(area, organization).await?;
+            match area {
+                Some(area_id) => self.repository.create_dispatcher(user.id, area_id).await?,
+                None => return Err(AppError::BadRequest),
+            }
+        }
+
+        self.repository.update_user_role(user.id, role).await?;
+        self.event_bus
+            .publish(DomainEvent::UserRoleChanged { user_id: user.id });
+
+        Ok(())
+    }
+
+    // 自己登録を無効にしている場合に、管理者がアカウントを作成する。
+    // 仮のパスワードを生成して返し、初回のログインで新しいパスワードを設定させる
+    pub async fn create_user_with_temporary_password(
+        &self,
+        organization: OrganizationScope,
+        username: &str,
+        role: Role,
+        area: Option<i32>,
+    ) -> Result<CreatedUserDto, AppError> {
+        if role == Role::Dispatcher && area.is_none() {
+            return Err(AppError::BadRequest);
+        }
+        if role == Role::Dispatcher {
+            self.ensure_area_in_organization(area, organization).await?;
+            self.quotas.check_dispatcher_quota(organization).await?;
+        }
+        let username = self.new_username(username).await?;
+        let temporary_password = generate_session_token();
+        let hashed_password = self.hash_password(&temporary_password).await?;
+
+        // 作成したアカウントは、作成した管理者と同じ組織に属する
+        self.repository
+            .create_user(
+                &username,
+                &hashed_password,
+                role,
+                organization.organization_id,
+            )
+            .await?;
+        let user = self
             .repository
-            .find_profile_image_name_by_user_id(user_id)
-            .await
+            .find_user_by_username_from_primary(&username)
+            .await?
+            .ok_or(AppError::InternalServerError)?;
+        if let (Role::Dispatcher, Some(area)) = (role, area) {
+            self.repository.create_dispatcher(user.id, area).await?;
+        }
+        self.repository.require_password_reset(user.id).await?;
+
+        Ok(CreatedUserDto {
+            user_id: user.id,
+            username: user.username,
+            role,
+            temporary_password,
+        })
+    }
+
+    pub async fn deactivate_user(
+        &self,
+        organization: OrganizationScope,
+        user_id: i32,
+    ) -> Result<(), AppError> {
+        match self.repository.find_user_by_id(user_id).await? {
+            Some(user) if user.organization_id == organization.organization_id => {}
+            _ => return Err(AppError::NotFound),
+        }
+
+        self.repository.deactivate_user(user_id).await?;
+        self.event_bus
+            .publish(DomainEvent::UserDeactivated { user_id });
+
+        Ok(())
+    }
+
+    pub async fn login_with_external_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+        preferred_username: Option<&str>,
+        default_role: Role,
+        client: &SessionClient,
+    ) -> Result<LoginResponseDto, AppError> {
+        let user_id = match self
+            .repository
+            .find_user_id_by_external_identity(provider, subject)
+            .await?
         {
-            Ok(Some(name)) => name,
-            Ok(None) => return Err(AppError::NotFound),
-            Err(_) => return Err(AppError::NotFound),
+            Some(user_id) => user_id,
+            None => {
+                self.create_external_user(provider, subject, preferred_username, default_role)
+                    .await?
+            }
         };
 
-        let path: PathBuf =
-            Path::new(&format!("images/user_profile/{}", profile_image_name)).to_path_buf();
+        let user = match self.repository.find_user_by_id(user_id).await? {
+            Some(user) if user.is_active => user,
+            _ => return Err(AppError::Unauthorized),
+        };
+
+        let session_token = generate_session_token();
+        let server_time = Utc::now();
+        let expires_at = self.session_expires_at(server_time);
+        self.repository
+            .create_session(user.id, &session_token, expires_at, client)
+            .await?;
+        self.event_bus.publish(DomainEvent::LoginSucceeded {
+            user_id: user.id,
+            client: client.clone(),
+        });
+
+        let dispatcher = match user.role {
+            Role::Dispatcher => self.repository.find_dispatcher_by_user_id(user.id).await?,
+            _ => None,
+        };
 
-        let output = Command::new("convert")
-            .arg(&path)
-            .arg("-resize")
-            .arg(format!("{}x{}!", width, height))
-            .arg("png:-")
-            .output()
-            .map_err(|e| {
-                error!("画像リサイズのコマンド実行に失敗しました: {:?}", e);
-                AppError::InternalServerError
-            })?;
+        self.issue_session_token(LoginResponseDto {
+            user_id: user.id,
+            username: user.username,
+            session_token,
+            role: user.role,
+            dispatcher_id: dispatcher.as_ref().map(|dispatcher| dispatcher.id),
+            area_id: dispatcher.as_ref().map(|dispatcher| dispatcher.area_id),
+            expires_at,
+            server_time,
+        })
+    }
 
-        match output.status.success() {
-            true => Ok(Bytes::from(output.stdout)),
-            false => {
-                error!(
-                    "画像リサイズのコマンド実行に失敗しました: {:?}",
-                    String::from_utf8_lossy(&output.stderr)
-                );
-                Err(AppError::InternalServerError)
+    // 初回ログイン時にローカルユーザーを作成し、外部アカウントと紐付ける
+    async fn create_external_user(
+        &self,
+        provider: &str,
+        subject: &str,
+        preferred_username: Option<&str>,
+        role: Role,
+    ) -> Result<i32, AppError> {
+        self.ensure_self_registration_enabled()?;
+        let base_username = match preferred_username.map(normalize_username) {
+            Some(username) if !username.is_empty() => username,
+            _ => normalize_username(&format!("{}_{}", provider, subject)),
+        };
+        // 外部アカウントはパスワードでログインさせないため、推測できない値をハッシュ化して保存する
+        let hashed_password = self.hash_password(&generate_session_token()).await?;
+
+        for attempt in 0..EXTERNAL_USERNAME_MAX_ATTEMPTS {
+            let username = match attempt {
+                0 => base_username.clone(),
+                _ => format!("{}_{}", base_username, rand::random::<u16>()),
+            };
+            if !self.is_username_available(&username, None).await? {
+                continue;
             }
+
+            self.repository
+                .create_user(&username, &hashed_password, role, DEFAULT_ORGANIZATION_ID)
+                .await?;
+            let user = match self
+                .repository
+                .find_user_by_username_from_primary(&username)
+                .await?
+            {
+                Some(user) => user,
+                None => return Err(AppError::InternalServerError),
+            };
+            self.repository
+                .create_external_identity(provider, subject, user.id)
+                .await?;
+
+            return Ok(user.id);
         }
+
+        Err(AppError::Conflict)
     }
 
-    pub async fn validate_session(&self, session_token: &str) -> Result<bool, AppError> {
+    pub async fn setup_totp(
+        &self,
+        user_id: i32,
+        code: Option<&str>,
+    ) -> Result<TotpSetupResponseDto, AppError> {
+        let user = match self.repository.find_user_by_id(user_id).await? {
+            Some(user) => user,
+            None => return Err(AppError::NotFound),
+        };
+        if !user.role.has_permission(Permission::UseTotp) {
+            return Err(AppError::Forbidden);
+        }
+        // 設定し直すと秘密鍵が入れ替わり、有効化するまで二要素認証が外れる。
+        // セッションを持つだけの第三者に外されないよう、有効な場合は現在の要素を確認する
+        self.verify_second_factor(&user, code).await?;
+
+        let secret = match Secret::Raw(rand::random::<[u8; 20]>().to_vec()).to_encoded() {
+            Secret::Encoded(secret) => secret,
+            Secret::Raw(_) => return Err(AppError::InternalServerError),
+        };
+        let provisioning_uri = build_totp(&secret, &user.username)?.get_url();
+
+        let backup_codes: Vec<String> = (0..TOTP_BACKUP_CODE_COUNT)
+            .map(|_| generate_session_token()[..TOTP_BACKUP_CODE_LENGTH].to_string())
+            .collect();
+        let codes = backup_codes.clone();
+        let code_hashes = self
+            .password_workers
+            .run(move || {
+                codes
+                    .iter()
+                    .map(|code| hash_password(code))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .await??;
+
+        // 確認コードで有効化されるまでは、ログイン時に二要素認証を要求しない
+        self.repository
+            .update_totp_secret(user.id, Some(&secret))
+            .await?;
+        self.repository
+            .replace_totp_backup_codes(user.id, &code_hashes)
+            .await?;
+
+        Ok(TotpSetupResponseDto {
+            secret,
+            provisioning_uri,
+            backup_codes,
+        })
+    }
+
+    pub async fn enable_totp(&self, user_id: i32, code: &str) -> Result<(), AppError> {
+        let user = match self.repository.find_user_by_id(user_id).await? {
+            Some(user) => user,
+            None => return Err(AppError::NotFound),
+        };
+        let secret = match &user.totp_secret {
+            Some(secret) => secret,
+            None => return Err(AppError::BadRequest),
+        };
+
+        // 有効化に使ったコードをそのままログインに使い回せないよう、時間枠を記録する
+        match matching_totp_step(&build_totp(secret, &user.username)?, code) {
+            Some(step) if self.repository.accept_totp_step(user.id, step).await? => {
+                self.repository.enable_totp(user.id).await
+            }
+            _ => Err(AppError::Unauthorized),
+        }
+    }
+
+    pub async fn refresh_session(&self, session: &Session) -> Result<LoginResponseDto, AppError> {
+        let server_time = Utc::now();
+        let expires_at = self.session_expires_at(server_time);
+        self.repository
+            .update_session_expires_at(&session.session_token, expires_at)
+            .await?;
+        self.validated_sessions.remove(&session.session_token);
+
+        let user = match self.repository.find_user_by_id(session.user_id).await? {
+            Some(user) => user,
+            None => return Err(AppError::Unauthorized),
+        };
+        let dispatcher = match user.role {
+            Role::Dispatcher => self.repository.find_dispatcher_by_user_id(user.id).await?,
+            _ => None,
+        };
+
+        self.issue_session_token(LoginResponseDto {
+            user_id: user.id,
+            username: user.username,
+            session_token: session.session_token.clone(),
+            role: user.role,
+            dispatcher_id: dispatcher.as_ref().map(|dispatcher| dispatcher.id),
+            area_id: dispatcher.as_ref().map(|dispatcher| dispatcher.area_id),
+            expires_at,
+            server_time,
+        })
+    }
+
+    // 管理者が利用者の環境で問題を再現するためのセッションを発行する。
+    // 発行したセッションではパスワードやロールの変更、期限の延長はできない (AuthMiddleware で拒否する)
+    pub async fn impersonate_user(
+        &self,
+        impersonator: &Session,
+        organization: OrganizationScope,
+        user_id: i32,
+        client: &SessionClient,
+    ) -> Result<ImpersonationResponseDto, AppError> {
+        // なりかわったセッションから、さらに別のユーザーになりかわることはできない
+        if impersonator.is_impersonation() {
+            return Err(AppError::Forbidden);
+        }
+        if user_id == impersonator.user_id {
+            return Err(AppError::BadRequest);
+        }
+        // 他の組織のユーザーにはなりかわれない
+        let user = match self.repository.find_user_by_id(user_id).await? {
+            Some(user)
+                if user.is_active && user.organization_id == organization.organization_id =>
+            {
+                user
+            }
+            _ => return Err(AppError::NotFound),
+        };
+        // 管理者の権限を別の管理者の名前で使えないよう、管理者にはなりかわれない
+        if user.role == Role::Admin {
+            return Err(AppError::Forbidden);
+        }
+
+        let session_token = generate_session_token();
+        let server_time = Utc::now();
+        let expires_at = expires_after(server_time, self.impersonation_ttl);
+        self.repository
+            .create_impersonation_session(
+                user.id,
+                &session_token,
+                expires_at,
+                impersonator.user_id,
+                client,
+            )
+            .await?;
+        let session_id = self
+            .repository
+            .find_session_by_session_token(&session_token)
+            .await?
+            .id;
+        let dispatcher = match user.role {
+            Role::Dispatcher => self.repository.find_dispatcher_by_user_id(user.id).await?,
+            _ => None,
+        };
+
+        let response = self.issue_session_token(LoginResponseDto {
+            user_id: user.id,
+            username: user.username,
+            session_token,
+            role: user.role,
+            dispatcher_id: dispatcher.as_ref().map(|dispatcher| dispatcher.id),
+            area_id: dispatcher.as_ref().map(|dispatcher| dispatcher.area_id),
+            expires_at,
+            server_time,
+        })?;
+
+        Ok(ImpersonationResponseDto {
+            session_id,
+            session_token: response.session_token,
+            user_id: response.user_id,
+            username: response.username,
+            role: response.role,
+            dispatcher_id: response.dispatcher_id,
+            area_id: response.area_id,
+            impersonator_user_id: impersonator.user_id,
+            expires_at,
+        })
+    }
+
+    pub async fn get_impersonations(
+        &self,
+        organization: OrganizationScope,
+    ) -> Result<Vec<ImpersonationDto>, AppError> {
+        let sessions = self.find_impersonation_sessions(organization).await?;
+
+        Ok(sessions
+            .into_iter()
+            .filter_map(ImpersonationDto::from_entity)
+            .collect())
+    }
+
+    // 取り消したセッションを返す。監査ログに記録するために使う
+    pub async fn revoke_impersonation(
+        &self,
+        organization: OrganizationScope,
+        session_id: i32,
+    ) -> Result<Session, AppError> {
         let session = self
+            .find_impersonation_sessions(organization)
+            .await?
+            .into_iter()
+            .find(|session| session.id == session_id)
+            .ok_or(AppError::NotFound)?;
+        self.repository
+            .delete_session(&session.session_token)
+            .await?;
+        self.validated_sessions.remove(&session.session_token);
+
+        Ok(session)
+    }
+
+    // なりかわられたユーザーが organization の組織に属するセッションだけを返す
+    async fn find_impersonation_sessions(
+        &self,
+        organization: OrganizationScope,
+    ) -> Result<Vec<Session>, AppError> {
+        let mut sessions = Vec::new();
+        for session in self
+            .repository
+            .find_impersonation_sessions(Utc::now())
+            .await?
+        {
+            if let Some(user) = self.repository.find_user_by_id(session.user_id).await? {
+                if user.organization_id == organization.organization_id {
+                    sessions.push(session);
+                }
+            }
+        }
+
+        Ok(sessions)
+    }
+
+    pub async fn logout_user(&self, session_token: &str) -> Result<(), AppError> {
+        let session_id = self.resolve_session_id(session_token)?;
+        self.repository.delete_session(&session_id).await?;
+        self.validated_sessions.remove(&session_id);
+        Ok(())
+    }
+
+    pub async fn authenticate(&self, session_token: &str) -> Result<Session, AppError> {
+        // JWT の場合も、ログアウト済みでないかを DB のセッションで確認する
+        let session_id = self.resolve_session_id(session_token)?;
+        let validated = self.validate_session_id(&session_id).await?;
+
+        Ok(validated.session)
+    }
+
+    // 他のサービスからの問い合わせ向けに、セッションとあわせてユーザーのロールを返す
+    pub async fn authenticate_with_role(
+        &self,
+        session_token: &str,
+    ) -> Result<(Session, Role), AppError> {
+        let session_id = self.resolve_session_id(session_token)?;
+        let validated = self.validate_session_id(&session_id).await?;
+
+        Ok((validated.session, validated.role))
+    }
+
+    // 認証ミドルウェア向けに、セッションとあわせてリクエストの対象とする組織を返す
+    pub async fn authenticate_with_organization(
+        &self,
+        session_token: &str,
+    ) -> Result<(Session, OrganizationScope), AppError> {
+        let session_id = self.resolve_session_id(session_token)?;
+        let validated = self.validate_session_id(&session_id).await?;
+
+        Ok((
+            validated.session,
+            OrganizationScope::new(validated.organization_id),
+        ))
+    }
+
+    // 認証が必要なリクエストのたびに呼ばれる。メモリに記録するだけで、DB には flush_session_activities で書き込む
+    pub fn record_session_activity(&self, session: &Session, client: SessionClient) {
+        let activity = SessionActivity {
+            session_token: session.session_token.clone(),
+            client,
+            last_seen_at: Utc::now(),
+        };
+        self.pending_activities
+            .lock()
+            .unwrap()
+            .insert(activity.session_token.clone(), activity);
+    }
+
+    // 溜まっている利用状況を書き込み、書き込んだセッションの数を返す
+    pub async fn flush_session_activities(&self) -> Result<usize, AppError> {
+        let activities: Vec<SessionActivity> = self
+            .pending_activities
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, activity)| activity)
+            .collect();
+
+        for (i, batch) in activities.chunks(SESSION_ACTIVITY_BATCH_SIZE).enumerate() {
+            if let Err(err) = self.repository.update_session_activities(batch).await {
+                // 書き込めなかった分は、その間に新しく記録されたものを上書きしないよう戻しておく
+                let mut pending = self.pending_activities.lock().unwrap();
+                for activity in &activities[i * SESSION_ACTIVITY_BATCH_SIZE..] {
+                    pending
+                        .entry(activity.session_token.clone())
+                        .or_insert_with(|| activity.clone());
+                }
+                return Err(err);
+            }
+        }
+
+        Ok(activities.len())
+    }
+
+    // まだ書き込んでいない利用状況も反映して返す
+    pub async fn get_sessions(&self, current: &Session) -> Result<Vec<SessionDto>, AppError> {
+        let sessions = self
             .repository
-            .find_session_by_session_token(session_token)
+            .find_sessions_by_user_id(current.user_id, Utc::now())
             .await?;
+        let pending = self.pending_activities.lock().unwrap();
+
+        Ok(sessions
+            .into_iter()
+            .map(|mut session| {
+                if let Some(activity) = pending.get(&session.session_token) {
+                    session.client_ip = activity.client.ip.clone().or(session.client_ip);
+                    session.user_agent = activity.client.user_agent.clone().or(session.user_agent);
+                    session.last_seen_at = Some(activity.last_seen_at);
+                }
+                let is_current = session.session_token == current.session_token;
+                SessionDto::from_entity(session, is_current)
+            })
+            .collect())
+    }
 
-        Ok(session.is_valid)
+    pub async fn get_dispatcher(&self, dispatcher_id: i32) -> Result<Dispatcher, AppError> {
+        self.repository
+            .find_dispatcher_by_id(dispatcher_id)
+            .await?
+            .ok_or(AppError::NotFound)
     }
+
+    pub async fn get_dispatcher_by_user_id(&self, user_id: i32) -> Result<Dispatcher, AppError> {
+        self.repository
+            .find_dispatcher_by_user_id(user_id)
+            .await?
+            .ok_or(AppError::NotFound)
+    }
+
+    pub async fn get_on_duty_dispatchers(
+        &self,
+        area_id: i32,
+    ) -> Result<Vec<OnDutyDispatcher>, AppError> {
+        self.repository
+            .find_on_duty_dispatchers_by_area_id(area_id, Utc::now())
+            .await
+    }
+
+    pub async fn authorize(
+        &self,
+        session: &Session,
+        permission: Permission,
+    ) -> Result<(), AppError> {
+        let user_role = match self.validated_sessions.get(&session.session_token) {
+            Some(validated) => validated.role,
+            None => match self.repository.find_user_by_id(session.user_id).await? {
+                Some(user) => user.role,
+                None => return Err(AppError::Forbidden),
+            },
+        };
+
+        match user_role.has_permission(permission) {
+            true => Ok(()),
+            false => Err(AppError::Forbidden),
+        }
+    }
+
+    pub async fn validate_session(&self, session_token: &str) -> Result<bool, AppError> {
+        // JWT の場合は署名と有効期限だけをローカルで検証し、DB には問い合わせない
+        if let Some(jwt_codec) = &self.jwt_codec {
+            return Ok(jwt_codec.decode(session_token).is_some());
+        }
+
+        match self.validate_session_id(session_token).await {
+            Ok(_) => Ok(true),
+            Err(AppError::Unauthorized) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub async fn purge_expired_sessions(&self, batch_size: u32) -> Result<u64, AppError> {
+        let now = Utc::now();
+        let mut deleted = 0;
+
+        // 一度に大量の行を削除するとロックが長引くため、バッチに分けて削除する
+        loop {
+            let count = self
+                .repository
+                .delete_expired_sessions(now, batch_size)
+                .await?;
+            deleted += count;
+            if count < u64::from(batch_size) {
+                break;
+            }
+        }
+
+        Ok(deleted)
+    }
+
+    // 起動時に、最近使われたセッションを検証済みのセッションとユーザーのキャッシュに読み込む。
+    // 読み込んだセッションの数を返す
+    pub async fn warm_up_sessions(
+        &self,
+        limit: u32,
+        concurrency: usize,
+    ) -> Result<usize, AppError> {
+        let session_ids = self
+            .repository
+            .find_recent_session_tokens(Utc::now(), limit)
+            .await?;
+
+        let loaded = stream::iter(session_ids)
+            .map(|session_id| async move { self.validate_session_id(&session_id).await })
+            .buffer_unordered(concurrency.max(1))
+            .filter(|result| std::future::ready(result.is_ok()))
+            .count()
+            .await;
+
+        Ok(loaded)
+    }
+
+    // 起動時に、各エリアで勤務中のディスパッチャーをキャッシュに読み込む。読み込んだ人数を返す
+    pub async fn warm_up_dispatchers(
+        &self,
+        area_ids: &[i32],
+        concurrency: usize,
+    ) -> Result<usize, AppError> {
+        let now = Utc::now();
+        let mut user_ids = Vec::new();
+        for area_id in area_ids {
+            user_ids.extend(
+                self.repository
+                    .find_on_duty_dispatchers_by_area_id(*area_id, now)
+                    .await?
+                    .into_iter()
+                    .map(|dispatcher| dispatcher.user_id),
+            );
+        }
+
+        let loaded = stream::iter(user_ids)
+            .map(|user_id| async move { self.repository.find_dispatcher_by_user_id(user_id).await })
+            .buffer_unordered(concurrency.max(1))
+            .fold(Ok(0), |total, result| async move {
+                match (total, result) {
+                    (Ok(total), Ok(dispatcher)) => Ok(total + usize::from(dispatcher.is_some())),
+                    (Err(err), _) | (_, Err(err)) => Err(err),
+                }
+            })
+            .await?;
+
+        Ok(loaded)
+    }
+
+    // 正規化した形で探し、見つからなければ正規化より前に登録された入力どおりの形で探す。
+    // 正規化すると同じになるユーザー名は起動時に resolve_username_collisions で解消しているため、どちらの形でも 1 人に決まる
+    async fn find_user_for_login(&self, username: &str) -> Result<Option<User>, AppError> {
+        let normalized = normalize_username(username);
+        if let Some(user) = self.repository.find_user_by_username(&normalized).await? {
+            return Ok(Some(user));
+        }
+        if normalized == username {
+            return Ok(None);
+        }
+
+        self.repository.find_user_by_username(username).await
+    }
+
+    // 正規化より前に登録されたユーザー名のうち、正規化すると他のユーザーと同じになるものを探して付け替える。
+    // 正規化した形どおりのユーザー名 (なければ最も古いアカウント) はそのまま残し、ほかは ID を付けた名前に変える。
+    // 付け替えは username_history に残り、旧ユーザー名での問い合わせに答えられる
+    pub async fn resolve_username_collisions(&self) -> Result<Vec<UsernameRename>, AppError> {
+        let mut groups: HashMap<String, Vec<(i32, String)>> = HashMap::new();
+        for (user_id, username) in self.repository.find_all_usernames().await? {
+            groups
+                .entry(normalize_username(&username))
+                .or_default()
+                .push((user_id, username));
+        }
+
+        let mut renames = Vec::new();
+        let changed_at = Utc::now();
+        for (normalized, mut users) in groups {
+            if users.len() < 2 {
+                continue;
+            }
+            users.sort_by_key(|(user_id, username)| (*username != normalized, *user_id));
+            for (user_id, old_username) in users.into_iter().skip(1) {
+                let mut new_username = format!("{}_{}", normalized, user_id);
+                while !self.is_username_available(&new_username, None).await? {
+                    new_username.push('_');
+                }
+                self.repository
+                    .update_username(user_id, &old_username, &new_username, changed_at)
+                    .await?;
+                renames.push(UsernameRename {
+                    user_id,
+                    old_username,
+                    new_username,
+                });
+            }
+        }
+
+        Ok(renames)
+    }
+
+    // 他の組織のエリアを担当させると、その組織の注文を閲覧・配車できてしまうため受け付けない
+    async fn ensure_area_in_organization(
+        &self,
+        area: Option<i32>,
+        organization: OrganizationScope,
+    ) -> Result<(), AppError> {
+        let Some(area_id) = area else {
+            return Ok(());
+        };
+        match self.repository.find_area_organization_id(area_id).await? {
+            Some(organization_id) if organization_id == organization.organization_id => Ok(()),
+            _ => Err(AppError::BadRequest),
+        }
+    }
+
+    // 新しく登録するユーザー名を正規化し、取得できるかを確認する
+    async fn new_username(&self, raw_username: &str) -> Result<String, AppError> {
+        let username = normalize_username(raw_username);
+        if username.is_empty() {
+            return Err(AppError::BadRequest);
+        }
+        // 正規化より前に登録されたユーザー名は入力どおりの形で保存されているため、両方を確認する
+        if !self.is_username_available(&username, None).await?
+            || (raw_username != username && !self.is_username_available(raw_username, None).await?)
+        {
+            return Err(AppError::Conflict);
+        }
+
+        Ok(username)
+    }
+
+    // 使用中のユーザー名に加え、最近ほかのユーザーが手放したユーザー名も取得できない
+    async fn is_username_available(
+        &self,
+        username: &str,
+        user_id: Option<i32>,
+    ) -> Result<bool, AppError> {
+        // 書き込み直前の確認のため、レプリカ遅延の影響を受けないようプライマリから読む
+        if let Some(user) = self
+            .repository
+            .find_user_by_username_from_primary(username)
+            .await?
+        {
+            return Ok(Some(user.id) == user_id);
+        }
+
+        let since = Utc::now() - chrono::Duration::days(USERNAME_RESERVATION_DAYS);
+        match self
+            .repository
+            .find_recent_username_owner(username, since)
+            .await?
+        {
+            Some(owner_id) => Ok(Some(owner_id) == user_id),
+            None => Ok(true),
+        }
+    }
+
+    fn publish_login_failed(&self, user_id: i32, client: &SessionClient) {
+        self.event_bus.publish(DomainEvent::LoginFailed {
+            user_id,
+            client: client.clone(),
+        });
+    }
+
+    async fn verify_second_factor(&self, user: &User, code: Option<&str>) -> Result<(), AppError> {
+        if !user.totp_enabled {
+            return Ok(());
+        }
+
+        let code = match code {
+            Some(code) => code,
+            None => return Err(AppError::TwoFactorRequired),
+        };
+
+        let now = Utc::now();
+        if self
+            .repository
+            .find_totp_locked_until(user.id)
+            .await?
+            .is_some_and(|locked_until| locked_until > now)
+        {
+            return Err(AppError::Unauthorized);
+        }
+
+        if self.check_second_factor(user, code).await? {
+            return Ok(());
+        }
+
+        self.repository
+            .record_totp_failure(
+                user.id,
+                TOTP_MAX_FAILED_ATTEMPTS,
+                expires_after(now, TOTP_LOCKOUT),
+            )
+            .await?;
+        Err(AppError::Unauthorized)
+    }
+
+    async fn check_second_factor(&self, user: &User, code: &str) -> Result<bool, AppError> {
+        if let Some(secret) = &user.totp_secret {
+            // 同じ時間枠のコードは一度しか受け付けない
+            if let Some(step) = matching_totp_step(&build_totp(secret, &user.username)?, code) {
+                return self.repository.accept_totp_step(user.id, step).await;
+            }
+        }
+
+        // バックアップコードの形でない入力では、Argon2 の照合をしない
+        if code.len() != TOTP_BACKUP_CODE_LENGTH || !code.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Ok(false);
+        }
+
+        // 認証アプリが使えない場合に備え、未使用のバックアップコードも受け付ける
+        for backup_code in self
+            .repository
+            .find_unused_totp_backup_codes(user.id)
+            .await?
+        {
+            if self.verify_password(&backup_code.code_hash, code).await?
+                && self
+                    .repository
+                    .mark_totp_backup_code_used(backup_code.id)
+                    .await?
+            {
+                self.repository.reset_totp_failures(user.id).await?;
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    async fn hash_password(&self, password: &str) -> Result<String, AppError> {
+        let password = password.to_string();
+        self.password_workers
+            .run(move || hash_password(&password))
+            .await?
+    }
+
+    async fn verify_password(
+        &self,
+        hashed_password: &str,
+        input_password: &str,
+    ) -> Result<bool, AppError> {
+        let hashed_password = hashed_password.to_string();
+        let input_password = input_password.to_string();
+        self.password_workers
+            .run(move || verify_password(&hashed_password, &input_password))
+            .await?
+    }
+
+    fn issue_session_token(
+        &self,
+        mut response: LoginResponseDto,
+    ) -> Result<LoginResponseDto, AppError> {
+        if let Some(jwt_codec) = &self.jwt_codec {
+            let claims = SessionClaims {
+                sub: response.user_id,
+                sid: std::mem::take(&mut response.session_token),
+                role: response.role,
+                dispatcher_id: response.dispatcher_id,
+                area_id: response.area_id,
+                iat: response.server_time.timestamp(),
+                exp: response.expires_at.timestamp(),
+            };
+            response.session_token = jwt_codec.encode(&claims)?;
+        }
+
+        Ok(response)
+    }
+
+    async fn validate_session_id(&self, session_id: &str) -> Result<ValidatedSession, AppError> {
+        let now = Utc::now();
+        if let Some(validated) = self.validated_sessions.get(session_id) {
+            if validated.session.expires_at > now {
+                return Ok(validated);
+            }
+        }
+
+        let session = self
+            .repository
+            .find_session_by_session_token(session_id)
+            .await
+            .map_err(|_| AppError::Unauthorized)?;
+        if !session.is_valid || session.expires_at <= now {
+            return Err(AppError::Unauthorized);
+        }
+
+        // 停止されたユーザーの既存セッションも拒否する。ユーザーの行はキャッシュから引けるため軽い
+        let user = match self.repository.find_user_by_id(session.user_id).await? {
+            Some(user) if user.is_active => user,
+            _ => return Err(AppError::Unauthorized),
+        };
+
+        let validated = ValidatedSession {
+            session,
+            role: user.role,
+            organization_id: user.organization_id,
+        };
+        self.validated_sessions
+            .insert(session_id.to_string(), validated.clone());
+
+        Ok(validated)
+    }
+
+    fn resolve_session_id(&self, session_token: &str) -> Result<String, AppError> {
+        match &self.jwt_codec {
+            Some(jwt_codec) => match jwt_codec.decode(session_token) {
+                Some(claims) => Ok(claims.sid),
+                None => Err(AppError::Unauthorized),
+            },
+            None => Ok(session_token.to_string()),
+        }
+    }
+
+    fn session_expires_at(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        expires_after(now, self.session_ttl)
+    }
+}
+
+fn expires_after(now: DateTime<Utc>, ttl: Duration) -> DateTime<Utc> {
+    let ttl = chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX);
+    now.checked_add_signed(ttl)
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+// 前後の時間枠も含めてコードと一致するものを探し、その時間枠の番号を返す
+fn matching_totp_step(totp: &TOTP, code: &str) -> Option<i64> {
+    let current = u64::try_from(Utc::now().timestamp()).ok()? / totp.step;
+    let skew = u64::from(totp.skew);
+    (current.saturating_sub(skew)..=current + skew)
+        .find(|step| constant_time_eq(totp.generate(step * totp.step).as_bytes(), code.as_bytes()))
+        .and_then(|step| i64::try_from(step).ok())
+}
+
+fn build_totp(secret: &str, username: &str) -> Result<TOTP, AppError> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|_| AppError::InternalServerError)?;
+
+    // otpauth URI のラベルでは ':' が区切り文字になるため、ユーザー名から取り除く
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        username.replace(':', "_"),
+    )
+    .map_err(|_| AppError::InternalServerError)
 }
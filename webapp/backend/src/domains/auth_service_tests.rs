@@ -0,0 +1,329 @@
+// AuthService の組織をまたぐ操作や二要素認証の扱いを、メモリに保持するリポジトリで確認する
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use super::auth_service::{AuthRepository, AuthService};
+use super::quota_service::{QuotaRepository, QuotaService};
+use crate::config::{
+    EmailVerificationConfig, MailerConfig, QuotaConfig, RegistrationConfig, SessionConfig,
+    SessionTransport, WorkerPoolConfig,
+};
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::mailer::MailerImpl;
+use crate::infrastructure::worker_pool::WorkerPool;
+use crate::models::organization::{OrganizationScope, QuotaLimits, DEFAULT_ORGANIZATION_ID};
+use crate::models::role::Role;
+use crate::repositories::memory_auth_repository::MemoryAuthRepository;
+use crate::utils::hash_password;
+
+const OTHER_ORGANIZATION_ID: i32 = DEFAULT_ORGANIZATION_ID + 1;
+const DEFAULT_AREA_ID: i32 = 1;
+const OTHER_AREA_ID: i32 = 2;
+
+// 上限を設定しない組織として振る舞う
+#[derive(Debug)]
+struct UnlimitedQuotaRepository;
+
+impl QuotaRepository for UnlimitedQuotaRepository {
+    async fn find_quota_limits(
+        &self,
+        _organization_id: i32,
+    ) -> Result<Option<QuotaLimits>, AppError> {
+        Ok(None)
+    }
+
+    async fn update_quota_limits(
+        &self,
+        _organization_id: i32,
+        _limits: &QuotaLimits,
+    ) -> Result<bool, AppError> {
+        Ok(false)
+    }
+
+    async fn count_orders_since(
+        &self,
+        _organization_id: i32,
+        _since: DateTime<Utc>,
+    ) -> Result<i64, AppError> {
+        Ok(0)
+    }
+
+    async fn count_active_dispatchers(&self, _organization_id: i32) -> Result<i64, AppError> {
+        Ok(0)
+    }
+
+    async fn sum_profile_image_bytes(&self, _organization_id: i32) -> Result<i64, AppError> {
+        Ok(0)
+    }
+
+    async fn find_profile_image_bytes(&self, _user_id: i32) -> Result<i64, AppError> {
+        Ok(0)
+    }
+
+    async fn record_profile_image(
+        &self,
+        _user_id: i32,
+        _name: &str,
+        _bytes: i64,
+    ) -> Result<(), AppError> {
+        Ok(())
+    }
+}
+
+type TestAuthService = AuthService<MemoryAuthRepository, UnlimitedQuotaRepository>;
+
+pub(crate) fn session_config() -> SessionConfig {
+    SessionConfig {
+        ttl: Duration::from_secs(60 * 60),
+        gc_interval: Duration::ZERO,
+        gc_batch_size: 1,
+        validation_cache_ttl: Duration::ZERO,
+        activity_flush_interval: Duration::ZERO,
+        impersonation_ttl: Duration::from_secs(60),
+        transport: SessionTransport::Header,
+        cookie_name: "session".to_string(),
+        cookie_secure: false,
+        jwt_secret: None,
+    }
+}
+
+// 既定の組織と別の組織に、エリアを 1 つずつ登録した状態で作る。
+// 書き込まれた内容を確かめられるよう、サービスと同じデータを共有するリポジトリも返す
+fn auth_service() -> (TestAuthService, MemoryAuthRepository) {
+    let repository = MemoryAuthRepository::new();
+    repository.insert_area(DEFAULT_AREA_ID, DEFAULT_ORGANIZATION_ID);
+    repository.insert_area(OTHER_AREA_ID, OTHER_ORGANIZATION_ID);
+
+    let service = AuthService::new(
+        repository.clone(),
+        Arc::new(QuotaService::new(
+            UnlimitedQuotaRepository,
+            &QuotaConfig {
+                orders_per_day: None,
+                active_dispatchers: None,
+                avatar_storage_bytes: None,
+            },
+        )),
+        &session_config(),
+        &EmailVerificationConfig {
+            required: false,
+            token_ttl: Duration::from_secs(60),
+            verify_url: String::new(),
+        },
+        &RegistrationConfig {
+            self_registration_enabled: true,
+            invite_required_roles: Vec::new(),
+        },
+        Arc::new(MailerImpl::from_config(&MailerConfig::Log)),
+        WorkerPool::new(
+            "password",
+            &WorkerPoolConfig {
+                concurrency: 1,
+                queue_limit: 16,
+            },
+        ),
+        Arc::new(EventBus::new()),
+    );
+
+    (service, repository)
+}
+
+#[actix_rt::test]
+async fn create_user_rejects_area_of_another_organization() {
+    let (service, repository) = auth_service();
+    let organization = OrganizationScope::new(OTHER_ORGANIZATION_ID);
+
+    let result = service
+        .create_user_with_temporary_password(
+            organization,
+            "dispatcher_b",
+            Role::Dispatcher,
+            Some(DEFAULT_AREA_ID),
+        )
+        .await;
+
+    assert!(matches!(result, Err(AppError::BadRequest)));
+    assert!(repository
+        .find_user_by_username("dispatcher_b")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[actix_rt::test]
+async fn create_user_places_user_and_dispatcher_in_the_admins_organization() {
+    let (service, repository) = auth_service();
+    let organization = OrganizationScope::new(OTHER_ORGANIZATION_ID);
+
+    let created = service
+        .create_user_with_temporary_password(
+            organization,
+            "dispatcher_b",
+            Role::Dispatcher,
+            Some(OTHER_AREA_ID),
+        )
+        .await
+        .unwrap();
+
+    let user = repository
+        .find_user_by_id(created.user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(user.organization_id, OTHER_ORGANIZATION_ID);
+    let dispatcher = repository
+        .find_dispatcher_by_user_id(created.user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(dispatcher.area_id, OTHER_AREA_ID);
+}
+
+#[actix_rt::test]
+async fn change_role_rejects_area_of_another_organization() {
+    let (service, repository) = auth_service();
+    let organization = OrganizationScope::new(OTHER_ORGANIZATION_ID);
+    let created = service
+        .create_user_with_temporary_password(organization, "client_b", Role::Client, None)
+        .await
+        .unwrap();
+
+    let result = service
+        .change_role(
+            organization,
+            created.user_id,
+            Role::Dispatcher,
+            Some(DEFAULT_AREA_ID),
+        )
+        .await;
+
+    assert!(matches!(result, Err(AppError::BadRequest)));
+    let user = repository
+        .find_user_by_id(created.user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(user.role, Role::Client);
+}
+
+#[actix_rt::test]
+async fn register_user_rejects_area_outside_the_default_organization() {
+    let (service, _) = auth_service();
+
+    let result = service
+        .register_user(
+            "dispatcher_a",
+            "password",
+            Role::Dispatcher,
+            Some(OTHER_AREA_ID),
+            None,
+            &Default::default(),
+        )
+        .await;
+
+    assert!(matches!(result, Err(AppError::BadRequest)));
+}
+
+// 二要素認証を有効にした管理者を作り、その ID とセットアップの結果を返す
+async fn user_with_totp(
+    service: &TestAuthService,
+    repository: &MemoryAuthRepository,
+) -> (i32, TOTP, Vec<String>) {
+    repository
+        .create_user(
+            "admin_a",
+            &hash_password("password").unwrap(),
+            Role::Admin,
+            DEFAULT_ORGANIZATION_ID,
+        )
+        .await
+        .unwrap();
+    let user_id = repository
+        .find_user_by_username("admin_a")
+        .await
+        .unwrap()
+        .unwrap()
+        .id;
+    let setup = service.setup_totp(user_id, None).await.unwrap();
+    let totp = TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        Secret::Encoded(setup.secret).to_bytes().unwrap(),
+        None,
+        String::new(),
+    )
+    .unwrap();
+
+    (user_id, totp, setup.backup_codes)
+}
+
+async fn login_with(service: &TestAuthService, code: &str) -> Result<(), AppError> {
+    service
+        .login_user("admin_a", "password", Some(code), None, &Default::default())
+        .await
+        .map(|_| ())
+}
+
+#[actix_rt::test]
+async fn totp_code_cannot_be_replayed() {
+    let (service, repository) = auth_service();
+    let (user_id, totp, _) = user_with_totp(&service, &repository).await;
+    let code = totp.generate_current().unwrap();
+
+    service.enable_totp(user_id, &code).await.unwrap();
+
+    assert!(matches!(
+        login_with(&service, &code).await,
+        Err(AppError::Unauthorized)
+    ));
+}
+
+#[actix_rt::test]
+async fn backup_code_is_accepted_only_once() {
+    let (service, repository) = auth_service();
+    let (user_id, totp, backup_codes) = user_with_totp(&service, &repository).await;
+    service
+        .enable_totp(user_id, &totp.generate_current().unwrap())
+        .await
+        .unwrap();
+
+    login_with(&service, &backup_codes[0]).await.unwrap();
+
+    assert!(matches!(
+        login_with(&service, &backup_codes[0]).await,
+        Err(AppError::Unauthorized)
+    ));
+}
+
+#[actix_rt::test]
+async fn repeated_failures_lock_the_second_factor() {
+    let (service, repository) = auth_service();
+    let (user_id, totp, backup_codes) = user_with_totp(&service, &repository).await;
+    service
+        .enable_totp(user_id, &totp.generate_current().unwrap())
+        .await
+        .unwrap();
+
+    for _ in 0..5 {
+        assert!(matches!(
+            login_with(&service, "wrong").await,
+            Err(AppError::Unauthorized)
+        ));
+    }
+
+    assert!(matches!(
+        login_with(&service, &backup_codes[0]).await,
+        Err(AppError::Unauthorized)
+    ));
+    assert!(repository
+        .find_totp_locked_until(user_id)
+        .await
+        .unwrap()
+        .is_some_and(|locked_until| locked_until > Utc::now()));
+}
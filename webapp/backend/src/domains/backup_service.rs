@@ -0,0 +1,286 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures_util::stream::{self, Stream};
+use log::error;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use super::dto::backup::{BackupRestoreResponseDto, BackupTableDto};
+use super::events::DomainEvent;
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+
+// ベンチマーク前の状態として保存するテーブル。外部キーの参照先から順に並べる。
+// 行から作り直せる order_counters と change_log、キャッシュ、監査ログなどの記録は含めず、復元時にリポジトリで作り直す
+const BACKUP_TABLES: [&str; 29] = [
+    "organizations",
+    "areas",
+    "users",
+    "dispatchers",
+    "tow_trucks",
+    "nodes",
+    "edges",
+    "clients",
+    "locations",
+    "orders",
+    "completed_orders",
+    "order_notes",
+    "order_messages",
+    "order_message_reads",
+    "order_attachments",
+    "sessions",
+    "totp_backup_codes",
+    "external_identities",
+    "username_history",
+    "email_verification_tokens",
+    "api_keys",
+    "invite_codes",
+    "feature_flags",
+    "profile_images",
+    "notification_preferences",
+    "push_tokens",
+    "webhook_subscriptions",
+    "webhook_deliveries",
+    "dispatcher_stats",
+];
+// アーカイブの形式を変えた場合に上げる。異なる版のアーカイブは復元しない
+const ARCHIVE_VERSION: u32 = 2;
+// 読み込んだ行を受け渡すチャネルの容量。書き出しが追いつかない間は DB からの読み込みも止まる
+const BACKUP_CHANNEL_CAPACITY: usize = 256;
+// 圧縮済みのデータがこの大きさになるたびにレスポンスへ書き出す
+const ARCHIVE_CHUNK_BYTES: usize = 64 * 1024;
+// 展開後の大きさの上限を、圧縮されたリクエストの大きさの何倍までにするか
+const MAX_EXPANSION_RATIO: usize = 20;
+
+pub type BackupSender = mpsc::Sender<Result<BackupRecord, AppError>>;
+
+// リポジトリから順に受け取る、テーブルの列名とそのテーブルの行
+#[derive(Debug)]
+pub enum BackupRecord {
+    Table {
+        name: &'static str,
+        columns: Vec<String>,
+    },
+    Row(Vec<Option<String>>),
+}
+
+#[derive(Debug)]
+pub struct BackupTable {
+    pub name: &'static str,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Option<String>>>,
+}
+
+pub trait BackupRepository {
+    // すべてのテーブルを 1 つのトランザクションで読み、どのテーブルも同じ時点の内容を送る。
+    // 受信側が閉じられた場合は途中で読み込みをやめる
+    async fn stream_tables(
+        &self,
+        tables: &[&'static str],
+        sender: &BackupSender,
+    ) -> Result<(), AppError>;
+    // テーブルの中身を入れ替え、入れ替えた行から作る注文数を数え直す。途中で失敗した場合はどのテーブルも変更しない
+    async fn replace_tables(&self, tables: &[BackupTable]) -> Result<(), AppError>;
+}
+
+// gzip で圧縮した NDJSON の 1 行。ヘッダー、テーブルごとの列名と行、全体の行数の順に並ぶ。
+// 最後の行がないアーカイブは書き出しが途中で失敗したものとして扱う
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ArchiveLine {
+    Header {
+        version: u32,
+        created_at: DateTime<Utc>,
+    },
+    Table {
+        name: String,
+        columns: Vec<String>,
+    },
+    Row {
+        values: Vec<Option<String>>,
+    },
+    End {
+        rows: usize,
+    },
+}
+
+// 主要なテーブルの内容を 1 つの圧縮したアーカイブとして書き出し、ベンチマーク後に書き出した時点へ戻す
+#[derive(Debug)]
+pub struct BackupService<T: BackupRepository + std::fmt::Debug + Clone + 'static> {
+    repository: T,
+    event_bus: Arc<EventBus>,
+}
+
+impl<T: BackupRepository + std::fmt::Debug + Clone + 'static> BackupService<T> {
+    pub fn new(repository: T, event_bus: Arc<EventBus>) -> Self {
+        BackupService {
+            repository,
+            event_bus,
+        }
+    }
+
+    // 読み込みながら圧縮して書き出す。途中でエラーになった場合は最後の行を書かずにストリームを終える
+    pub fn export(&self) -> impl Stream<Item = Result<Vec<u8>, AppError>> + 'static {
+        let (sender, receiver) = mpsc::channel(BACKUP_CHANNEL_CAPACITY);
+        let repository = self.repository.clone();
+        actix_web::rt::spawn(async move {
+            if let Err(err) = repository.stream_tables(&BACKUP_TABLES, &sender).await {
+                let _ = sender.send(Err(err)).await;
+            }
+        });
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        let header = write_line(
+            &mut encoder,
+            &ArchiveLine::Header {
+                version: ARCHIVE_VERSION,
+                created_at: Utc::now(),
+            },
+        );
+        let writer = ArchiveWriter {
+            receiver,
+            encoder: header.ok().map(|_| encoder),
+            rows: 0,
+        };
+
+        stream::unfold(writer, |mut writer| async move {
+            let result = match writer.encoder.take() {
+                Some(encoder) => writer.next_chunk(encoder).await,
+                None => return None,
+            };
+            Some((result, writer))
+        })
+    }
+
+    pub async fn restore(&self, archive: &[u8]) -> Result<BackupRestoreResponseDto, AppError> {
+        let max_bytes = archive.len().saturating_mul(MAX_EXPANSION_RATIO);
+        let (created_at, tables) = read_archive(archive, max_bytes)?;
+
+        self.repository.replace_tables(&tables).await?;
+        self.event_bus.publish(DomainEvent::DataReset);
+
+        Ok(BackupRestoreResponseDto {
+            created_at,
+            tables: tables
+                .into_iter()
+                .map(|table| BackupTableDto {
+                    table: table.name.to_string(),
+                    rows: table.rows.len(),
+                })
+                .collect(),
+        })
+    }
+}
+
+struct ArchiveWriter {
+    receiver: mpsc::Receiver<Result<BackupRecord, AppError>>,
+    // 書き終えた場合とエラーになった場合は None にして、ストリームを終える
+    encoder: Option<GzEncoder<Vec<u8>>>,
+    rows: usize,
+}
+
+impl ArchiveWriter {
+    async fn next_chunk(&mut self, mut encoder: GzEncoder<Vec<u8>>) -> Result<Vec<u8>, AppError> {
+        loop {
+            let line = match self.receiver.recv().await {
+                Some(Ok(BackupRecord::Table { name, columns })) => ArchiveLine::Table {
+                    name: name.to_string(),
+                    columns,
+                },
+                Some(Ok(BackupRecord::Row(values))) => {
+                    self.rows += 1;
+                    ArchiveLine::Row { values }
+                }
+                Some(Err(err)) => return Err(err),
+                None => {
+                    write_line(&mut encoder, &ArchiveLine::End { rows: self.rows })?;
+                    return encoder.finish().map_err(archive_error);
+                }
+            };
+            write_line(&mut encoder, &line)?;
+
+            if encoder.get_ref().len() >= ARCHIVE_CHUNK_BYTES {
+                let chunk = std::mem::take(encoder.get_mut());
+                self.encoder = Some(encoder);
+                return Ok(chunk);
+            }
+        }
+    }
+}
+
+fn write_line(encoder: &mut GzEncoder<Vec<u8>>, line: &ArchiveLine) -> Result<(), AppError> {
+    serde_json::to_writer(&mut *encoder, line).map_err(|e| {
+        error!("アーカイブの行を書き出せませんでした: {:?}", e);
+        AppError::InternalServerError
+    })?;
+    encoder.write_all(b"\n").map_err(archive_error)
+}
+
+fn archive_error(e: std::io::Error) -> AppError {
+    error!("アーカイブを圧縮できませんでした: {:?}", e);
+    AppError::InternalServerError
+}
+
+// アーカイブをすべて読み、形式が正しいことを確かめてからテーブルごとの行を返す。
+// 途中で切れたアーカイブや、保存の対象と異なるテーブルを含むアーカイブは受け付けない
+fn read_archive(
+    archive: &[u8],
+    max_bytes: usize,
+) -> Result<(DateTime<Utc>, Vec<BackupTable>), AppError> {
+    let reader = BufReader::new(GzDecoder::new(archive).take(max_bytes as u64));
+    let mut lines = reader.lines().map(|line| {
+        let line = line.map_err(|_| AppError::BadRequest)?;
+        serde_json::from_str::<ArchiveLine>(&line).map_err(|_| AppError::BadRequest)
+    });
+
+    let created_at = match lines.next().transpose()? {
+        Some(ArchiveLine::Header {
+            version,
+            created_at,
+        }) if version == ARCHIVE_VERSION => created_at,
+        _ => return Err(AppError::BadRequest),
+    };
+
+    let mut tables: Vec<BackupTable> = Vec::new();
+    let mut rows = 0;
+    loop {
+        match lines.next().transpose()? {
+            Some(ArchiveLine::Table { name, columns }) => {
+                let name = BACKUP_TABLES
+                    .iter()
+                    .copied()
+                    .find(|table| *table == name)
+                    .ok_or(AppError::BadRequest)?;
+                if tables.iter().any(|table| table.name == name) {
+                    return Err(AppError::BadRequest);
+                }
+                tables.push(BackupTable {
+                    name,
+                    columns,
+                    rows: Vec::new(),
+                });
+            }
+            Some(ArchiveLine::Row { values }) => {
+                let table = tables.last_mut().ok_or(AppError::BadRequest)?;
+                if values.len() != table.columns.len() {
+                    return Err(AppError::BadRequest);
+                }
+                table.rows.push(values);
+                rows += 1;
+            }
+            Some(ArchiveLine::End { rows: expected }) if expected == rows => break,
+            // 最後の行がない、または行数が合わない場合は途中で切れたものとみなす
+            _ => return Err(AppError::BadRequest),
+        }
+    }
+    if lines.next().is_some() || tables.len() != BACKUP_TABLES.len() {
+        return Err(AppError::BadRequest);
+    }
+
+    Ok((created_at, tables))
+}
@@ -0,0 +1,37 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use tokio::sync::broadcast;
+
+// 1 つの部屋に溜められる未送信のメッセージ。読み出しが追いつかない接続では古いものから捨てる
+const CHANNEL_CAPACITY: usize = 64;
+
+// 注文ごとのチャットの部屋。WebSocket で接続中の参加者に新しいメッセージを配る
+#[derive(Debug, Default)]
+pub struct ChatHub {
+    rooms: Mutex<HashMap<i32, broadcast::Sender<String>>>,
+}
+
+impl ChatHub {
+    pub fn new() -> Self {
+        ChatHub::default()
+    }
+
+    pub fn join(&self, order_id: i32) -> broadcast::Receiver<String> {
+        let mut rooms = self.rooms.lock().unwrap();
+        rooms
+            .entry(order_id)
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    // 接続中の参加者がいなければ何もしない。誰もいなくなった部屋はここで片付ける
+    pub fn publish(&self, order_id: i32, payload: String) {
+        let mut rooms = self.rooms.lock().unwrap();
+        if let Some(sender) = rooms.get(&order_id) {
+            if sender.send(payload).is_err() {
+                rooms.remove(&order_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+
+use crate::errors::AppError;
+use crate::models::order::OrderMessage;
+use crate::models::organization::OrganizationScope;
+
+use super::chat_hub::ChatHub;
+use super::dto::chat::{MessageHistoryQueryDto, OrderMessageDto, UnreadCountDto};
+
+const MAX_MESSAGE_LENGTH: usize = 1000;
+const DEFAULT_HISTORY_LIMIT: i32 = 50;
+const MAX_HISTORY_LIMIT: i32 = 100;
+
+// チャットは organization_id の組織の注文に対してだけ読み書きする
+pub trait ChatRepository {
+    // 担当のディスパッチャーと運転手のユーザー ID。注文が組織にない場合は None
+    async fn find_chat_participant_ids(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<Option<Vec<i32>>, AppError>;
+    async fn create_message(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        sender_id: i32,
+        body: &str,
+    ) -> Result<OrderMessage, AppError>;
+    // before_id より前のメッセージを新しい順に返す
+    async fn find_messages(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        before_id: Option<i32>,
+        limit: i32,
+    ) -> Result<Vec<OrderMessage>, AppError>;
+    async fn find_latest_message_id(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<Option<i32>, AppError>;
+    // 既読の位置は進めるだけで、古いメッセージの ID では戻さない
+    async fn mark_read(&self, order_id: i32, user_id: i32, message_id: i32)
+        -> Result<(), AppError>;
+    // 参加しているチャットのうち、相手からの未読のメッセージがある注文ごとの件数
+    async fn count_unread_messages(
+        &self,
+        organization_id: i32,
+        user_id: i32,
+    ) -> Result<Vec<(i32, i64)>, AppError>;
+}
+
+// 配車した注文ごとに、担当のディスパッチャーと運転手がメッセージをやり取りする
+#[derive(Debug)]
+pub struct ChatService<T: ChatRepository + std::fmt::Debug> {
+    repository: T,
+    hub: Arc<ChatHub>,
+}
+
+impl<T: ChatRepository + std::fmt::Debug> ChatService<T> {
+    pub fn new(repository: T, hub: Arc<ChatHub>) -> Self {
+        ChatService { repository, hub }
+    }
+
+    // 接続した後に送られたメッセージを受け取る。それまでの履歴は get_messages で取得する
+    pub async fn join(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        user_id: i32,
+    ) -> Result<broadcast::Receiver<String>, AppError> {
+        self.check_participant(organization, order_id, user_id)
+            .await?;
+
+        Ok(self.hub.join(order_id))
+    }
+
+    pub async fn send_message(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        user_id: i32,
+        body: &str,
+    ) -> Result<OrderMessageDto, AppError> {
+        let body = body.trim();
+        if body.is_empty() || body.chars().count() > MAX_MESSAGE_LENGTH {
+            return Err(AppError::BadRequest);
+        }
+        self.check_participant(organization, order_id, user_id)
+            .await?;
+
+        let message = self
+            .repository
+            .create_message(organization.organization_id, order_id, user_id, body)
+            .await?;
+        // 自分のメッセージは未読に数えないが、それまでの相手のメッセージも読んだものとする
+        self.repository
+            .mark_read(order_id, user_id, message.id)
+            .await?;
+        let message = OrderMessageDto::from_entity(message);
+        let payload = serde_json::to_string(&message).map_err(|_| AppError::InternalServerError)?;
+        self.hub.publish(order_id, payload);
+
+        Ok(message)
+    }
+
+    pub async fn get_messages(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        user_id: i32,
+        query: MessageHistoryQueryDto,
+    ) -> Result<Vec<OrderMessageDto>, AppError> {
+        let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+        if !(1..=MAX_HISTORY_LIMIT).contains(&limit) {
+            return Err(AppError::BadRequest);
+        }
+        self.check_participant(organization, order_id, user_id)
+            .await?;
+
+        let messages = self
+            .repository
+            .find_messages(
+                organization.organization_id,
+                order_id,
+                query.before_id,
+                limit,
+            )
+            .await?;
+
+        // 画面にはそのまま並べられるよう古い順で返す
+        Ok(messages
+            .into_iter()
+            .rev()
+            .map(OrderMessageDto::from_entity)
+            .collect())
+    }
+
+    // 最新のメッセージまでを読んだものとする
+    pub async fn mark_read(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        user_id: i32,
+    ) -> Result<(), AppError> {
+        self.check_participant(organization, order_id, user_id)
+            .await?;
+        if let Some(message_id) = self
+            .repository
+            .find_latest_message_id(organization.organization_id, order_id)
+            .await?
+        {
+            self.repository
+                .mark_read(order_id, user_id, message_id)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_unread_counts(
+        &self,
+        organization: OrganizationScope,
+        user_id: i32,
+    ) -> Result<Vec<UnreadCountDto>, AppError> {
+        let counts = self
+            .repository
+            .count_unread_messages(organization.organization_id, user_id)
+            .await?;
+
+        Ok(counts
+            .into_iter()
+            .map(|(order_id, unread)| UnreadCountDto { order_id, unread })
+            .collect())
+    }
+
+    // 配車前の注文にはチャットの相手がいないため、参加者は担当のディスパッチャーと運転手に限る
+    async fn check_participant(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        user_id: i32,
+    ) -> Result<(), AppError> {
+        let participant_ids = self
+            .repository
+            .find_chat_participant_ids(organization.organization_id, order_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        match participant_ids.contains(&user_id) {
+            true => Ok(()),
+            false => Err(AppError::Forbidden),
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use std::sync::Arc;
+
+use super::dto::client::{
+    ClientDto, ClientOrderDto, CreateClientOrderRequestDto, CreateClientRequestDto,
+    CreatedClientOrderDto, UpdateClientRequestDto,
+};
+use super::events::DomainEvent;
+use super::geofence::Geofence;
+use super::map_service::MapRepository;
+use super::quota_service::{QuotaRepository, QuotaService};
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::models::client::{Client, NewClient};
+use crate::models::order::Order;
+use crate::models::organization::OrganizationScope;
+use crate::utils::normalize_phone_number;
+
+const CLIENT_NAME_MAX_LENGTH: usize = 255;
+const CLIENT_ORDERS_LIMIT: i32 = 100;
+
+pub trait ClientRepository {
+    async fn create_client(&self, client: &NewClient) -> Result<i32, AppError>;
+    async fn find_client_by_id(&self, id: i32) -> Result<Option<Client>, AppError>;
+    // phone_number は正規化済みのもの
+    async fn find_client_by_phone_number(
+        &self,
+        phone_number: &str,
+    ) -> Result<Option<Client>, AppError>;
+    async fn update_client(&self, client: &Client) -> Result<(), AppError>;
+    // organization_id の組織の注文だけを、新しい順に返す
+    async fn find_orders_by_client_id(
+        &self,
+        organization_id: i32,
+        client_id: i32,
+        limit: i32,
+    ) -> Result<Vec<Order>, AppError>;
+    // user_id は注文を登録したユーザー。顧客の注文として作成し、注文 ID を返す
+    async fn create_client_order(
+        &self,
+        organization_id: i32,
+        client_id: i32,
+        user_id: i32,
+        node_id: i32,
+        car_value: f64,
+    ) -> Result<i32, AppError>;
+}
+
+// 電話で注文を受けるディスパッチャーが、顧客を電話番号で探し、顧客に結び付けた注文を作成する
+#[derive(Debug)]
+pub struct ClientService<
+    T: ClientRepository + std::fmt::Debug,
+    U: MapRepository + std::fmt::Debug,
+    V: QuotaRepository + std::fmt::Debug,
+> {
+    client_repository: T,
+    map_repository: U,
+    geofence: Arc<Geofence>,
+    quotas: Arc<QuotaService<V>>,
+    event_bus: Arc<EventBus>,
+}
+
+impl<
+        T: ClientRepository + std::fmt::Debug,
+        U: MapRepository + std::fmt::Debug,
+        V: QuotaRepository + std::fmt::Debug,
+    > ClientService<T, U, V>
+{
+    pub fn new(
+        client_repository: T,
+        map_repository: U,
+        geofence: Arc<Geofence>,
+        quotas: Arc<QuotaService<V>>,
+        event_bus: Arc<EventBus>,
+    ) -> Self {
+        ClientService {
+            client_repository,
+            map_repository,
+            geofence,
+            quotas,
+            event_bus,
+        }
+    }
+
+    // 同じ電話番号やアカウントの顧客がすでにいる場合は Conflict を返す
+    pub async fn create_client(
+        &self,
+        organization: OrganizationScope,
+        req: CreateClientRequestDto,
+    ) -> Result<ClientDto, AppError> {
+        let client = NewClient {
+            user_id: req.user_id,
+            name: validate_name(&req.name)?,
+            phone_number: normalize_phone_number(&req.phone_number).ok_or(AppError::BadRequest)?,
+            default_pickup_node_id: req.default_pickup_node_id,
+        };
+        if let Some(node_id) = client.default_pickup_node_id {
+            self.validate_node(organization, node_id).await?;
+        }
+
+        let id = self.client_repository.create_client(&client).await?;
+
+        self.get_client(id).await
+    }
+
+    pub async fn get_client(&self, id: i32) -> Result<ClientDto, AppError> {
+        let client = self.find_client(id).await?;
+
+        Ok(ClientDto::from_entity(client))
+    }
+
+    pub async fn find_client_by_phone_number(
+        &self,
+        phone_number: &str,
+    ) -> Result<ClientDto, AppError> {
+        let phone_number = normalize_phone_number(phone_number).ok_or(AppError::BadRequest)?;
+        let client = self
+            .client_repository
+            .find_client_by_phone_number(&phone_number)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        Ok(ClientDto::from_entity(client))
+    }
+
+    pub async fn update_client(
+        &self,
+        organization: OrganizationScope,
+        id: i32,
+        req: UpdateClientRequestDto,
+    ) -> Result<ClientDto, AppError> {
+        let mut client = self.find_client(id).await?;
+        if let Some(name) = req.name {
+            client.name = validate_name(&name)?;
+        }
+        if let Some(phone_number) = req.phone_number {
+            client.phone_number =
+                normalize_phone_number(&phone_number).ok_or(AppError::BadRequest)?;
+        }
+        if let Some(node_id) = req.default_pickup_node_id {
+            self.validate_node(organization, node_id).await?;
+            client.default_pickup_node_id = Some(node_id);
+        }
+
+        self.client_repository.update_client(&client).await?;
+
+        Ok(ClientDto::from_entity(client))
+    }
+
+    pub async fn get_client_orders(
+        &self,
+        organization: OrganizationScope,
+        id: i32,
+    ) -> Result<Vec<ClientOrderDto>, AppError> {
+        self.find_client(id).await?;
+        let orders = self
+            .client_repository
+            .find_orders_by_client_id(organization.organization_id, id, CLIENT_ORDERS_LIMIT)
+            .await?;
+
+        Ok(orders
+            .into_iter()
+            .map(ClientOrderDto::from_entity)
+            .collect())
+    }
+
+    // アカウントを持つ顧客の注文はその顧客のユーザーで、持たない顧客の注文は受け付けたユーザーで登録する
+    pub async fn create_client_order(
+        &self,
+        organization: OrganizationScope,
+        id: i32,
+        taken_by_user_id: i32,
+        req: CreateClientOrderRequestDto,
+    ) -> Result<CreatedClientOrderDto, AppError> {
+        let client = self.find_client(id).await?;
+        if !req.car_value.is_finite() || req.car_value < 0.0 {
+            return Err(AppError::BadRequest);
+        }
+        let node_id = req
+            .node_id
+            .or(client.default_pickup_node_id)
+            .ok_or(AppError::BadRequest)?;
+        let area_id = self.validate_node(organization, node_id).await?;
+        // 登録済みの乗車地でも、その後に境界が変わって範囲外になっていることがある
+        let node = self
+            .map_repository
+            .find_node_by_id(node_id)
+            .await?
+            .ok_or(AppError::BadRequest)?;
+        self.geofence
+            .check(
+                &self.map_repository,
+                organization.organization_id,
+                node.x,
+                node.y,
+            )
+            .await?;
+        self.quotas.check_order_quota(organization).await?;
+
+        let order_id = self
+            .client_repository
+            .create_client_order(
+                organization.organization_id,
+                client.id,
+                client.user_id.unwrap_or(taken_by_user_id),
+                node_id,
+                req.car_value,
+            )
+            .await?;
+        self.event_bus
+            .publish(DomainEvent::OrderCreated { order_id, area_id });
+
+        Ok(CreatedClientOrderDto { order_id, area_id })
+    }
+
+    async fn find_client(&self, id: i32) -> Result<Client, AppError> {
+        self.client_repository
+            .find_client_by_id(id)
+            .await?
+            .ok_or(AppError::NotFound)
+    }
+
+    // 組織のエリアにあるノードであれば、そのエリアを返す
+    async fn validate_node(
+        &self,
+        organization: OrganizationScope,
+        node_id: i32,
+    ) -> Result<i32, AppError> {
+        self.map_repository
+            .find_node_area_id(organization.organization_id, node_id)
+            .await?
+            .ok_or(AppError::BadRequest)
+    }
+}
+
+fn validate_name(name: &str) -> Result<String, AppError> {
+    let name = name.trim();
+    if name.is_empty()
+        || name.chars().count() > CLIENT_NAME_MAX_LENGTH
+        || name.chars().any(char::is_control)
+    {
+        return Err(AppError::BadRequest);
+    }
+
+    Ok(name.to_string())
+}
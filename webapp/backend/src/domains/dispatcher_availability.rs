@@ -0,0 +1,239 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use super::events::DomainEvent;
+use crate::infrastructure::event_bus::EventBus;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AvailableDispatcher {
+    pub dispatcher_id: i32,
+    pub user_id: i32,
+    pub area_id: i32,
+    pub active_assignments: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AreaAvailability {
+    pub available_dispatchers: usize,
+    pub active_assignments: usize,
+    // 上限に達するまでに、あと何件の注文を担当できるか
+    pub remaining_capacity: usize,
+}
+
+#[derive(Debug, Default)]
+struct AvailabilityState {
+    // 受付中のディスパッチャー
+    dispatchers: HashMap<i32, AvailableDispatcher>,
+    // エリアごとの受付中のディスパッチャー ID
+    areas: HashMap<i32, BTreeSet<i32>>,
+}
+
+impl AvailabilityState {
+    fn remove(&mut self, dispatcher_id: i32) {
+        if let Some(dispatcher) = self.dispatchers.remove(&dispatcher_id) {
+            if let Some(area) = self.areas.get_mut(&dispatcher.area_id) {
+                area.remove(&dispatcher_id);
+                if area.is_empty() {
+                    self.areas.remove(&dispatcher.area_id);
+                }
+            }
+        }
+    }
+}
+
+// エリアごとに受付中のディスパッチャーをメモリに保持する。
+// 受付の開始と終了、配車と完了のイベントで更新するため、候補の取得で DB を参照しない。
+// 再起動すると空になり、各ディスパッチャーが受付を開始し直すまで候補には含まれない
+#[derive(Debug)]
+pub struct DispatcherAvailability {
+    state: RwLock<AvailabilityState>,
+    // 担当中の注文がこの件数に達したディスパッチャーは候補に含めない
+    max_active_assignments: AtomicUsize,
+}
+
+impl DispatcherAvailability {
+    pub fn new(max_active_assignments: usize, event_bus: &EventBus) -> Arc<Self> {
+        let availability = Arc::new(DispatcherAvailability {
+            state: RwLock::new(AvailabilityState::default()),
+            max_active_assignments: AtomicUsize::new(max_active_assignments),
+        });
+        let subscriber = availability.clone();
+        event_bus.subscribe(move |event| subscriber.apply(event));
+
+        availability
+    }
+
+    // 担当中の注文が少ない順に返す
+    pub fn candidates(&self, area_id: i32) -> Vec<AvailableDispatcher> {
+        let max_active_assignments = self.max_active_assignments.load(Ordering::Relaxed);
+        let state = self.state.read().unwrap();
+        let mut candidates: Vec<AvailableDispatcher> = state
+            .areas
+            .get(&area_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|dispatcher_id| state.dispatchers.get(dispatcher_id))
+            .filter(|dispatcher| dispatcher.active_assignments < max_active_assignments)
+            .cloned()
+            .collect();
+        candidates
+            .sort_by_key(|dispatcher| (dispatcher.active_assignments, dispatcher.dispatcher_id));
+
+        candidates
+    }
+
+    // エリアごとの受付中のディスパッチャーの数と、担当中の注文の合計
+    pub fn area_summaries(&self) -> HashMap<i32, AreaAvailability> {
+        let max_active_assignments = self.max_active_assignments.load(Ordering::Relaxed);
+        let state = self.state.read().unwrap();
+        let mut summaries: HashMap<i32, AreaAvailability> = HashMap::new();
+        for dispatcher in state.dispatchers.values() {
+            let summary = summaries.entry(dispatcher.area_id).or_default();
+            summary.available_dispatchers += 1;
+            summary.active_assignments += dispatcher.active_assignments;
+            summary.remaining_capacity +=
+                max_active_assignments.saturating_sub(dispatcher.active_assignments);
+        }
+
+        summaries
+    }
+
+    pub fn find(&self, dispatcher_id: i32) -> Option<AvailableDispatcher> {
+        self.state
+            .read()
+            .unwrap()
+            .dispatchers
+            .get(&dispatcher_id)
+            .cloned()
+    }
+
+    pub fn snapshot(&self) -> Vec<AvailableDispatcher> {
+        self.state
+            .read()
+            .unwrap()
+            .dispatchers
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    // 起動直後に、前回の停止時の受付状況を戻す。すでに受付を開始したディスパッチャーがいれば何もしない
+    pub fn restore(&self, dispatchers: Vec<AvailableDispatcher>) -> usize {
+        let mut state = self.state.write().unwrap();
+        if !state.dispatchers.is_empty() {
+            return 0;
+        }
+        for dispatcher in dispatchers {
+            state
+                .areas
+                .entry(dispatcher.area_id)
+                .or_default()
+                .insert(dispatcher.dispatcher_id);
+            state
+                .dispatchers
+                .insert(dispatcher.dispatcher_id, dispatcher);
+        }
+
+        state.dispatchers.len()
+    }
+
+    fn apply(&self, event: &DomainEvent) {
+        if let DomainEvent::ConfigChanged { tunables } = event {
+            self.max_active_assignments
+                .store(tunables.max_active_assignments, Ordering::Relaxed);
+            return;
+        }
+        let mut state = self.state.write().unwrap();
+        match *event {
+            DomainEvent::DispatcherAvailabilityChanged {
+                dispatcher_id,
+                user_id,
+                area_id,
+                available,
+                active_assignments,
+            } => {
+                state.remove(dispatcher_id);
+                if available {
+                    state.dispatchers.insert(
+                        dispatcher_id,
+                        AvailableDispatcher {
+                            dispatcher_id,
+                            user_id,
+                            area_id,
+                            active_assignments,
+                        },
+                    );
+                    state
+                        .areas
+                        .entry(area_id)
+                        .or_default()
+                        .insert(dispatcher_id);
+                }
+            }
+            DomainEvent::OrderDispatched { dispatcher_id, .. } => {
+                if let Some(dispatcher) = state.dispatchers.get_mut(&dispatcher_id) {
+                    dispatcher.active_assignments += 1;
+                }
+            }
+            DomainEvent::OrderReassigned {
+                from_dispatcher_id,
+                to_dispatcher_id,
+                ..
+            } => {
+                if let Some(dispatcher) = state.dispatchers.get_mut(&from_dispatcher_id) {
+                    dispatcher.active_assignments = dispatcher.active_assignments.saturating_sub(1);
+                }
+                if let Some(dispatcher) = state.dispatchers.get_mut(&to_dispatcher_id) {
+                    dispatcher.active_assignments += 1;
+                }
+            }
+            // 受付中のまま異動した場合は、新しいエリアの候補に移す
+            DomainEvent::DispatcherTransferred {
+                dispatcher_id,
+                to_area_id,
+                ..
+            } => {
+                if let Some(mut dispatcher) = state.dispatchers.get(&dispatcher_id).cloned() {
+                    state.remove(dispatcher_id);
+                    dispatcher.area_id = to_area_id;
+                    state.dispatchers.insert(dispatcher_id, dispatcher);
+                    state
+                        .areas
+                        .entry(to_area_id)
+                        .or_default()
+                        .insert(dispatcher_id);
+                }
+            }
+            DomainEvent::OrderCompleted {
+                dispatcher_id: Some(dispatcher_id),
+                ..
+            }
+            | DomainEvent::OrderCancelled {
+                dispatcher_id: Some(dispatcher_id),
+                ..
+            }
+            | DomainEvent::OrderRequeued { dispatcher_id, .. } => {
+                if let Some(dispatcher) = state.dispatchers.get_mut(&dispatcher_id) {
+                    dispatcher.active_assignments = dispatcher.active_assignments.saturating_sub(1);
+                }
+            }
+            // 無効化やロールの変更で、ディスパッチャーとして受け付けられなくなった
+            DomainEvent::UserDeactivated { user_id } | DomainEvent::UserRoleChanged { user_id } => {
+                let dispatcher_ids: Vec<i32> = state
+                    .dispatchers
+                    .values()
+                    .filter(|dispatcher| dispatcher.user_id == user_id)
+                    .map(|dispatcher| dispatcher.dispatcher_id)
+                    .collect();
+                for dispatcher_id in dispatcher_ids {
+                    state.remove(dispatcher_id);
+                }
+            }
+            DomainEvent::DataReset => *state = AvailabilityState::default(),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Utc};
+
+use super::auth_service::AuthRepository;
+use super::dispatcher_availability::DispatcherAvailability;
+use super::dto::dispatcher::{
+    AreaLoadDto, AreaLoadSummaryDto, AvailableDispatcherDto, DispatcherTransferDto,
+    ReassignedOrderDto,
+};
+use super::events::DomainEvent;
+use super::order_service::OrderRepository;
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::models::order::OrderStatus;
+use crate::models::organization::OrganizationScope;
+
+// 担当できる残りの件数に対する未割り当ての注文の割合で、混雑の度合いを分ける
+const LOAD_LEVEL_BUSY_RATIO: f64 = 1.0;
+const LOAD_LEVEL_SURGE_RATIO: f64 = 2.0;
+
+#[derive(Debug, Clone)]
+struct PendingOrderSnapshot {
+    counts: HashMap<i32, i64>,
+    counted_at: DateTime<Utc>,
+}
+
+// 組織ごと、エリアごとの未割り当ての注文の数。注文の状態が変わるイベントで破棄し、次に要求されたときに読み直す
+#[derive(Debug, Default)]
+struct PendingOrderCounts {
+    snapshots: RwLock<HashMap<i32, PendingOrderSnapshot>>,
+    // 読み込み中に破棄された場合に、古い値を保存しないための世代
+    generation: AtomicU64,
+}
+
+impl PendingOrderCounts {
+    fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.snapshots.write().unwrap().clear();
+    }
+}
+
+#[derive(Debug)]
+pub struct DispatcherService<
+    T: AuthRepository + std::fmt::Debug,
+    U: OrderRepository + std::fmt::Debug,
+> {
+    auth_repository: T,
+    order_repository: U,
+    availability: Arc<DispatcherAvailability>,
+    pending_order_counts: Arc<PendingOrderCounts>,
+    event_bus: Arc<EventBus>,
+}
+
+impl<T: AuthRepository + std::fmt::Debug, U: OrderRepository + std::fmt::Debug>
+    DispatcherService<T, U>
+{
+    pub fn new(
+        auth_repository: T,
+        order_repository: U,
+        availability: Arc<DispatcherAvailability>,
+        event_bus: Arc<EventBus>,
+    ) -> Self {
+        let pending_order_counts = Arc::new(PendingOrderCounts::default());
+        let subscriber = pending_order_counts.clone();
+        event_bus.subscribe(move |event| match event {
+            DomainEvent::OrderCreated { .. }
+            | DomainEvent::OrderDispatched { .. }
+            | DomainEvent::OrderCancelled { .. }
+            | DomainEvent::OrderRequeued { .. }
+            | DomainEvent::DataReset => subscriber.invalidate(),
+            _ => {}
+        });
+
+        DispatcherService {
+            auth_repository,
+            order_repository,
+            availability,
+            pending_order_counts,
+            event_bus,
+        }
+    }
+
+    // 受付の開始時だけ担当中の注文数を DB から読み込み、以降はイベントで増減させる
+    pub async fn set_availability(
+        &self,
+        organization: OrganizationScope,
+        user_id: i32,
+        available: bool,
+    ) -> Result<(), AppError> {
+        let dispatcher = self
+            .auth_repository
+            .find_dispatcher_by_user_id(user_id)
+            .await?
+            .ok_or(AppError::Forbidden)?;
+
+        let active_assignments = match available {
+            true => self
+                .order_repository
+                .find_orders_by_dispatcher_id(
+                    organization.organization_id,
+                    dispatcher.id,
+                    OrderStatus::Dispatched.as_str(),
+                )
+                .await?
+                .len(),
+            false => 0,
+        };
+
+        self.event_bus
+            .publish(DomainEvent::DispatcherAvailabilityChanged {
+                dispatcher_id: dispatcher.id,
+                user_id: dispatcher.user_id,
+                area_id: dispatcher.area_id,
+                available,
+                active_assignments,
+            });
+
+        Ok(())
+    }
+
+    // 未割り当ての注文の数には組織のすべてのエリアが含まれるため、エリアが組織のものかの確認にも使う
+    pub async fn get_available_dispatchers(
+        &self,
+        organization: OrganizationScope,
+        area_id: i32,
+    ) -> Result<Vec<AvailableDispatcherDto>, AppError> {
+        let pending = self.pending_order_counts(organization).await?;
+        if !pending.counts.contains_key(&area_id) {
+            return Err(AppError::NotFound);
+        }
+
+        Ok(self
+            .availability
+            .candidates(area_id)
+            .into_iter()
+            .map(|dispatcher| AvailableDispatcherDto {
+                dispatcher_id: dispatcher.dispatcher_id,
+                user_id: dispatcher.user_id,
+                active_assignments: dispatcher.active_assignments,
+            })
+            .collect())
+    }
+
+    // 受付状況はメモリの値をそのまま使い、未割り当ての注文の数だけを DB から読む
+    pub async fn get_area_load(
+        &self,
+        organization: OrganizationScope,
+    ) -> Result<AreaLoadSummaryDto, AppError> {
+        let pending = self.pending_order_counts(organization).await?;
+        let availability = self.availability.area_summaries();
+
+        let mut area_ids: Vec<i32> = pending.counts.keys().copied().collect();
+        area_ids.sort_unstable();
+        let areas = area_ids
+            .into_iter()
+            .map(|area_id| {
+                let pending_orders = pending.counts.get(&area_id).copied().unwrap_or(0);
+                let summary = availability.get(&area_id).cloned().unwrap_or_default();
+                let load_ratio = match summary.remaining_capacity {
+                    0 => None,
+                    capacity => Some(pending_orders as f64 / capacity as f64),
+                };
+                let level = match (pending_orders, load_ratio) {
+                    (0, _) => "idle",
+                    (_, None) => "critical",
+                    (_, Some(ratio)) if ratio >= LOAD_LEVEL_SURGE_RATIO => "surge",
+                    (_, Some(ratio)) if ratio >= LOAD_LEVEL_BUSY_RATIO => "busy",
+                    _ => "normal",
+                };
+                AreaLoadDto {
+                    area_id,
+                    pending_orders,
+                    available_dispatchers: summary.available_dispatchers,
+                    active_assignments: summary.active_assignments,
+                    remaining_capacity: summary.remaining_capacity,
+                    load_ratio,
+                    level,
+                }
+            })
+            .collect();
+
+        Ok(AreaLoadSummaryDto {
+            areas,
+            pending_counted_at: pending.counted_at,
+        })
+    }
+
+    async fn pending_order_counts(
+        &self,
+        organization: OrganizationScope,
+    ) -> Result<PendingOrderSnapshot, AppError> {
+        if let Some(snapshot) = self
+            .pending_order_counts
+            .snapshots
+            .read()
+            .unwrap()
+            .get(&organization.organization_id)
+        {
+            return Ok(snapshot.clone());
+        }
+
+        let generation = self.pending_order_counts.generation.load(Ordering::SeqCst);
+        let snapshot = PendingOrderSnapshot {
+            counts: self
+                .order_repository
+                .count_pending_orders_by_area(organization.organization_id)
+                .await?
+                .into_iter()
+                .collect(),
+            counted_at: Utc::now(),
+        };
+        let mut cached = self.pending_order_counts.snapshots.write().unwrap();
+        if self.pending_order_counts.generation.load(Ordering::SeqCst) == generation {
+            cached.insert(organization.organization_id, snapshot.clone());
+        }
+
+        Ok(snapshot)
+    }
+
+    // 担当エリアを変更し、担当中の注文を元のエリアで受付中の別のディスパッチャーに引き継ぐ。
+    // 受付中のディスパッチャーがいなければ、注文を未割り当てに戻して配車し直せるようにする
+    pub async fn transfer_dispatcher(
+        &self,
+        organization: OrganizationScope,
+        dispatcher_id: i32,
+        new_area_id: i32,
+    ) -> Result<DispatcherTransferDto, AppError> {
+        let dispatcher = self
+            .auth_repository
+            .find_dispatcher_by_id(dispatcher_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        match self
+            .auth_repository
+            .find_user_by_id(dispatcher.user_id)
+            .await?
+        {
+            Some(user) if user.organization_id == organization.organization_id => {}
+            _ => return Err(AppError::NotFound),
+        }
+        if dispatcher.area_id == new_area_id {
+            return Err(AppError::BadRequest);
+        }
+
+        // 担当中の注文が少ない順に並んでいるため、先頭から順に割り振る
+        let candidate_ids: Vec<i32> = self
+            .availability
+            .candidates(dispatcher.area_id)
+            .into_iter()
+            .map(|candidate| candidate.dispatcher_id)
+            .filter(|candidate_id| *candidate_id != dispatcher.id)
+            .collect();
+        let handovers = self
+            .order_repository
+            .transfer_dispatcher(
+                organization.organization_id,
+                dispatcher.id,
+                dispatcher.area_id,
+                new_area_id,
+                &candidate_ids,
+            )
+            .await?
+            .ok_or(AppError::Conflict)?;
+
+        let mut reassigned_orders = Vec::new();
+        let mut requeued_order_ids = Vec::new();
+        for handover in handovers {
+            match handover.new_dispatcher_id {
+                Some(new_dispatcher_id) => {
+                    self.event_bus.publish(DomainEvent::OrderReassigned {
+                        order_id: handover.order_id,
+                        from_dispatcher_id: dispatcher.id,
+                        to_dispatcher_id: new_dispatcher_id,
+                    });
+                    reassigned_orders.push(ReassignedOrderDto {
+                        order_id: handover.order_id,
+                        dispatcher_id: new_dispatcher_id,
+                    });
+                }
+                None => {
+                    self.event_bus.publish(DomainEvent::OrderRequeued {
+                        order_id: handover.order_id,
+                        dispatcher_id: dispatcher.id,
+                        tow_truck_id: handover.tow_truck_id,
+                    });
+                    requeued_order_ids.push(handover.order_id);
+                }
+            }
+        }
+        self.event_bus.publish(DomainEvent::DispatcherTransferred {
+            dispatcher_id: dispatcher.id,
+            user_id: dispatcher.user_id,
+            from_area_id: dispatcher.area_id,
+            to_area_id: new_area_id,
+        });
+
+        Ok(DispatcherTransferDto {
+            dispatcher_id: dispatcher.id,
+            from_area_id: dispatcher.area_id,
+            area_id: new_area_id,
+            reassigned_orders,
+            requeued_order_ids,
+        })
+    }
+}
@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+// Input Data Structure
+
+#[derive(Deserialize, Debug)]
+pub struct CreateApiKeyRequestDto {
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct ApiKeyCreatedDto {
+    pub id: i32,
+    pub name: String,
+    pub api_key: String,
+    pub scopes: Vec<String>,
+}
@@ -1,34 +1,248 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::models::role::Role;
+use crate::models::user::Session;
+
 // Input Data Structure
 
 #[derive(Deserialize, Debug)]
 pub struct RegisterRequestDto {
     pub username: String,
     pub password: String,
-    pub role: String,
+    pub role: Role,
     pub area_id: Option<i32>,
+    // メールアドレスの確認が有効な場合のみ必須
+    pub email: Option<String>,
+    // REGISTRATION_INVITE_ROLES に含まれるロールで登録する場合のみ必須
+    pub invite_code: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VerifyEmailRequestDto {
+    pub token: String,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct LoginRequestDto {
     pub username: String,
     pub password: String,
+    pub totp_code: Option<String>,
+    // 管理者が作成したアカウントで、仮のパスワードでログインする場合のみ必須
+    pub new_password: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct LogoutRequestDto {
+    #[serde(default)]
     pub session_token: String,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct ChangeUsernameRequestDto {
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequestDto {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ChangeRoleRequestDto {
+    pub role: Role,
+    pub area_id: Option<i32>,
+}
+
+// パスワードはサーバーで仮のものを生成し、初回のログインで変更させる
+#[derive(Deserialize, Debug)]
+pub struct CreateUserRequestDto {
+    pub username: String,
+    pub role: Role,
+    pub area_id: Option<i32>,
+}
+
+// reason は監査ログに残す。問い合わせの番号など、なりかわる理由を書く
+#[derive(Deserialize, Debug)]
+pub struct ImpersonateUserRequestDto {
+    pub user_id: i32,
+    pub reason: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TotpVerifyRequestDto {
+    pub code: String,
+}
+
+// 二要素認証を有効にしているアカウントで設定し直す場合は、現在の確認コードかバックアップコードが必要
+#[derive(Deserialize, Debug)]
+pub struct TotpSetupRequestDto {
+    pub code: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeMode {
+    #[default]
+    Exact,
+    Fit,
+}
+
 // Output Data Structure
 
+#[derive(Serialize, Debug)]
+pub struct CreatedUserDto {
+    pub user_id: i32,
+    pub username: String,
+    pub role: Role,
+    // 仮のパスワードを返すのは作成時のこの一度だけ
+    pub temporary_password: String,
+}
+
 #[derive(Serialize)]
 pub struct LoginResponseDto {
     pub user_id: i32,
     pub username: String,
+    // Cookie でセッションを受け渡す場合は空にしてレスポンスから省く
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub session_token: String,
+    pub role: Role,
+    pub dispatcher_id: Option<i32>,
+    pub area_id: Option<i32>,
+    pub expires_at: DateTime<Utc>,
+    pub server_time: DateTime<Utc>,
+}
+
+// /api/v2 のログインのレスポンス。複数のエリアを担当するディスパッチャーに備え、担当を配列で返す
+#[derive(Serialize)]
+pub struct LoginResponseV2Dto {
+    pub user_id: i32,
+    pub username: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub session_token: String,
+    pub role: Role,
+    pub dispatchers: Vec<DispatcherAssignmentDto>,
+    pub expires_at: DateTime<Utc>,
+    pub server_time: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct DispatcherAssignmentDto {
+    pub dispatcher_id: i32,
+    pub area_id: i32,
+}
+
+impl From<LoginResponseDto> for LoginResponseV2Dto {
+    fn from(response: LoginResponseDto) -> Self {
+        LoginResponseV2Dto {
+            user_id: response.user_id,
+            username: response.username,
+            session_token: response.session_token,
+            role: response.role,
+            dispatchers: match (response.dispatcher_id, response.area_id) {
+                (Some(dispatcher_id), Some(area_id)) => vec![DispatcherAssignmentDto {
+                    dispatcher_id,
+                    area_id,
+                }],
+                _ => Vec::new(),
+            },
+            expires_at: response.expires_at,
+            server_time: response.server_time,
+        }
+    }
+}
+
+// メールアドレスの確認が済むまではセッションを発行しない
+#[derive(Serialize)]
+pub struct PendingRegistrationResponseDto {
+    pub user_id: i32,
+    pub username: String,
+    pub email_verification_required: bool,
+}
+
+pub enum RegisterResponseDto {
+    LoggedIn(LoginResponseDto),
+    PendingVerification(PendingRegistrationResponseDto),
+}
+
+#[derive(Serialize)]
+pub struct CsrfTokenResponseDto {
+    pub csrf_token: String,
+}
+
+#[derive(Serialize)]
+pub struct TotpSetupResponseDto {
+    pub secret: String,
+    pub provisioning_uri: String,
+    pub backup_codes: Vec<String>,
+}
+
+// なりかわりのセッション。Cookie には設定せず、管理者の画面でトークンを受け取って使う
+#[derive(Serialize, Debug)]
+pub struct ImpersonationResponseDto {
+    pub session_id: i32,
     pub session_token: String,
-    pub role: String,
+    pub user_id: i32,
+    pub username: String,
+    pub role: Role,
     pub dispatcher_id: Option<i32>,
     pub area_id: Option<i32>,
+    pub impersonator_user_id: i32,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImpersonationDto {
+    pub session_id: i32,
+    pub user_id: i32,
+    pub impersonator_user_id: i32,
+    pub client_ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl ImpersonationDto {
+    pub fn from_entity(entity: Session) -> Option<Self> {
+        Some(ImpersonationDto {
+            session_id: entity.id,
+            user_id: entity.user_id,
+            impersonator_user_id: entity.impersonator_user_id?,
+            client_ip: entity.client_ip,
+            created_at: entity.created_at,
+            last_seen_at: entity.last_seen_at,
+            expires_at: entity.expires_at,
+        })
+    }
+}
+
+// ログイン中のセッションの一覧。他の端末のセッションに心当たりがないかを利用者が確認するために使う
+#[derive(Serialize, Debug)]
+pub struct SessionDto {
+    pub id: i32,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    // このリクエストに使われたセッションか
+    pub current: bool,
+    // 管理者がなりかわって使っているセッションか
+    pub impersonated: bool,
+}
+
+impl SessionDto {
+    pub fn from_entity(entity: Session, current: bool) -> Self {
+        SessionDto {
+            id: entity.id,
+            client_ip: entity.client_ip,
+            user_agent: entity.user_agent,
+            created_at: entity.created_at,
+            last_seen_at: entity.last_seen_at,
+            expires_at: entity.expires_at,
+            current,
+            impersonated: entity.impersonator_user_id.is_some(),
+        }
+    }
 }
@@ -0,0 +1,17 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct BackupTableDto {
+    pub table: String,
+    pub rows: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BackupRestoreResponseDto {
+    // 復元したアーカイブを書き出した時刻
+    pub created_at: DateTime<Utc>,
+    pub tables: Vec<BackupTableDto>,
+}
@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::order::OrderMessage;
+
+// Input Data Structure
+
+#[derive(Deserialize, Debug)]
+pub struct SendMessageRequestDto {
+    pub body: String,
+}
+
+// before_id より前のメッセージを新しい順に limit 件まで返す。指定しない場合は最新から
+#[derive(Deserialize, Debug)]
+pub struct MessageHistoryQueryDto {
+    pub before_id: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct OrderMessageDto {
+    pub id: i32,
+    pub order_id: i32,
+    pub sender_id: i32,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OrderMessageDto {
+    pub fn from_entity(entity: OrderMessage) -> Self {
+        OrderMessageDto {
+            id: entity.id,
+            order_id: entity.order_id,
+            sender_id: entity.sender_id,
+            body: entity.body,
+            created_at: entity.created_at,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct UnreadCountDto {
+    pub order_id: i32,
+    pub unread: i64,
+}
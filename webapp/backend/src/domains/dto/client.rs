@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::client::Client;
+use crate::models::order::Order;
+
+// Input Data Structure
+
+#[derive(Deserialize, Debug)]
+pub struct CreateClientRequestDto {
+    pub name: String,
+    pub phone_number: String,
+    // アカウントを持つ顧客の場合に指定する
+    pub user_id: Option<i32>,
+    pub default_pickup_node_id: Option<i32>,
+}
+
+// 指定した項目だけを変更する
+#[derive(Deserialize, Debug)]
+pub struct UpdateClientRequestDto {
+    pub name: Option<String>,
+    pub phone_number: Option<String>,
+    pub default_pickup_node_id: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ClientLookupQueryDto {
+    pub phone: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateClientOrderRequestDto {
+    // 省略した場合は顧客の既定の乗車地を使う
+    pub node_id: Option<i32>,
+    pub car_value: f64,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct ClientDto {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub name: String,
+    pub phone_number: String,
+    pub default_pickup_node_id: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ClientDto {
+    pub fn from_entity(entity: Client) -> Self {
+        ClientDto {
+            id: entity.id,
+            user_id: entity.user_id,
+            name: entity.name,
+            phone_number: entity.phone_number,
+            default_pickup_node_id: entity.default_pickup_node_id,
+            created_at: entity.created_at,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ClientOrderDto {
+    pub id: i32,
+    pub status: String,
+    pub node_id: i32,
+    pub car_value: f64,
+    pub order_time: DateTime<Utc>,
+    pub completed_time: Option<DateTime<Utc>>,
+}
+
+impl ClientOrderDto {
+    pub fn from_entity(entity: Order) -> Self {
+        ClientOrderDto {
+            id: entity.id,
+            status: entity.status,
+            node_id: entity.node_id,
+            car_value: entity.car_value,
+            order_time: entity.order_time,
+            completed_time: entity.completed_time,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct CreatedClientOrderDto {
+    pub order_id: i32,
+    pub area_id: i32,
+}
@@ -0,0 +1,66 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// Input Data Structure
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateAvailabilityRequestDto {
+    pub available: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AvailableDispatchersQueryDto {
+    pub area_id: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TransferDispatcherRequestDto {
+    pub area_id: i32,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct AvailableDispatcherDto {
+    pub dispatcher_id: i32,
+    pub user_id: i32,
+    pub active_assignments: usize,
+}
+
+// ダッシュボードのヒートマップに使う、エリアごとの需要と受付状況
+#[derive(Serialize, Debug)]
+pub struct AreaLoadDto {
+    pub area_id: i32,
+    pub pending_orders: i64,
+    pub available_dispatchers: usize,
+    pub active_assignments: usize,
+    pub remaining_capacity: usize,
+    // 未割り当ての注文の数を、受付中のディスパッチャーが担当できる残りの件数で割ったもの。
+    // 担当できるディスパッチャーがいない場合は null
+    pub load_ratio: Option<f64>,
+    pub level: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AreaLoadSummaryDto {
+    pub areas: Vec<AreaLoadDto>,
+    // 未割り当ての注文の数を DB から読み込んだ時刻
+    pub pending_counted_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DispatcherTransferDto {
+    pub dispatcher_id: i32,
+    pub from_area_id: i32,
+    pub area_id: i32,
+    // 元のエリアの別のディスパッチャーに引き継いだ注文
+    pub reassigned_orders: Vec<ReassignedOrderDto>,
+    // 引き継げず、未割り当てに戻した注文
+    pub requeued_order_ids: Vec<i32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReassignedOrderDto {
+    pub order_id: i32,
+    pub dispatcher_id: i32,
+}
@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+// Input Data Structure
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateFeatureFlagRequestDto {
+    pub enabled: bool,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct FeatureFlagDto {
+    pub name: &'static str,
+    pub enabled: bool,
+    // default・env・database のいずれか。どこで決まった値かを示す
+    pub source: &'static str,
+}
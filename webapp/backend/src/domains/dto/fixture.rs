@@ -0,0 +1,14 @@
+use serde::Serialize;
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct FixtureTableDto {
+    pub table: String,
+    pub rows: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FixtureSeedResponseDto {
+    pub tables: Vec<FixtureTableDto>,
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+// Input Data Structure
+
+#[derive(Deserialize, Debug)]
+pub struct GeocodeQueryDto {
+    pub address: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReverseGeocodeQueryDto {
+    pub x: i32,
+    pub y: i32,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct GeocodeDto {
+    // 正規化した住所
+    pub address: String,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReverseGeocodeDto {
+    pub x: i32,
+    pub y: i32,
+    pub address: String,
+}
@@ -0,0 +1,56 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::invite_code::InviteCode;
+use crate::models::role::Role;
+
+// Input Data Structure
+
+#[derive(Deserialize, Debug)]
+pub struct CreateInviteCodeRequestDto {
+    pub role: Role,
+    // 省略した場合は 1 回だけ使える
+    pub max_uses: Option<i32>,
+    // 省略した場合は期限なし
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct InviteCodeCreatedDto {
+    pub id: i32,
+    pub code: String,
+    pub role: Role,
+    pub max_uses: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct InviteCodeDto {
+    pub id: i32,
+    pub code_prefix: String,
+    pub role: Role,
+    pub max_uses: i32,
+    pub use_count: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_by: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl InviteCodeDto {
+    pub fn from_entity(entity: InviteCode) -> Self {
+        InviteCodeDto {
+            id: entity.id,
+            code_prefix: entity.code_prefix,
+            role: entity.role,
+            max_uses: entity.max_uses,
+            use_count: entity.use_count,
+            expires_at: entity.expires_at,
+            revoked_at: entity.revoked_at,
+            created_by: entity.created_by,
+            created_at: entity.created_at,
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+// Input Data Structure
+
+#[derive(Deserialize, Debug)]
+pub struct LeaderboardQueryDto {
+    pub page: Option<i32>,
+    pub page_size: Option<i32>,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct LeaderboardEntryDto {
+    pub rank: i64,
+    pub dispatcher_id: i32,
+    pub user_id: i32,
+    pub username: String,
+    pub completed_orders: i32,
+    // 見積もりのあった注文がない場合は None
+    pub average_eta_error_minutes: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LeaderboardDto {
+    pub page: i32,
+    pub page_size: i32,
+    pub total: i64,
+    pub entries: Vec<LeaderboardEntryDto>,
+}
@@ -1,6 +1,6 @@
 // Input Data Structure
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug)]
 pub struct UpdateEdgeRequestDto {
@@ -8,3 +8,40 @@ pub struct UpdateEdgeRequestDto {
     pub node_b_id: i32,
     pub weight: i32,
 }
+
+// null を指定すると境界を削除する
+#[derive(Deserialize, Debug)]
+pub struct UpdateAreaBoundaryRequestDto {
+    pub boundary: Option<Vec<[i32; 2]>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TravelTimeRequestDto {
+    pub origin_node_id: i32,
+    pub destination_node_ids: Vec<i32>,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AreaDto {
+    pub id: i32,
+    pub name: String,
+    pub organization_id: i32,
+}
+
+// 到達できない目的地と、出発地と別のエリアにある目的地は distance と eta_minutes が null になる
+#[derive(Serialize, Debug)]
+pub struct TravelTimeDto {
+    pub node_id: i32,
+    pub distance: Option<i32>,
+    pub eta_minutes: Option<i32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TravelTimeMatrixDto {
+    pub origin_node_id: i32,
+    pub area_id: i32,
+    // 指定された目的地の順に並べる
+    pub travel_times: Vec<TravelTimeDto>,
+}
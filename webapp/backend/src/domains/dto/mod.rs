@@ -1,4 +1,28 @@
+pub mod api_key;
 pub mod auth;
+pub mod backup;
+pub mod chat;
+pub mod client;
+pub mod dispatcher;
+pub mod export;
+pub mod feature_flag;
+pub mod fixture;
+pub mod geocode;
+pub mod invite_code;
+pub mod leaderboard;
 pub mod map;
+pub mod notification;
 pub mod order;
+pub mod order_note;
+pub mod organization;
+pub mod profile;
+pub mod quota;
+pub mod report;
+pub mod retention;
+pub mod runtime_config;
+pub mod stats;
+pub mod sync;
 pub mod tow_truck;
+pub mod user_import;
+pub mod vehicle;
+pub mod webhook;
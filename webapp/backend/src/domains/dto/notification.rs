@@ -0,0 +1,63 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// Input Data Structure
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateNotificationPreferencesRequestDto {
+    pub websocket: bool,
+    pub email: bool,
+    // 空文字列または null で Webhook への通知を止める
+    pub webhook_url: Option<String>,
+}
+
+// provider は "fcm" または "web_push"。登録の解除では token だけを見る
+#[derive(Deserialize, Debug)]
+pub struct PushTokenRequestDto {
+    #[serde(default)]
+    pub provider: String,
+    pub token: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DeadLetterQueryDto {
+    pub limit: Option<i32>,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct NotificationPreferencesDto {
+    pub websocket: bool,
+    pub email: bool,
+    pub webhook_url: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WebPushKeyDto {
+    pub public_key: String,
+}
+
+// WebSocket・メール・Webhook・プッシュ通知のいずれにも同じ内容を送る
+#[derive(Serialize, Debug, Clone)]
+pub struct NotificationDto {
+    pub event: &'static str,
+    pub order_id: Option<i32>,
+    pub message: String,
+    // 通知のきっかけになったリクエストの ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NotificationDeadLetterDto {
+    pub id: i32,
+    pub user_id: i32,
+    pub channel: String,
+    pub target: String,
+    pub payload: String,
+    pub error: String,
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+}
@@ -1,6 +1,11 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::models::{
+    order::{Order, OrderSyncRow, ScheduledOrder},
+    user::OnDutyDispatcher,
+};
+
 // Input Data Structure
 
 #[derive(Deserialize, Debug)]
@@ -10,12 +15,74 @@ pub struct ClientOrderRequestDto {
     pub car_value: f64,
 }
 
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct CoordinateDto {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateOrderRequestDto {
+    pub pickup: CoordinateDto,
+    pub dropoff: CoordinateDto,
+    pub car_value: f64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreateScheduledOrderRequestDto {
+    pub pickup: CoordinateDto,
+    pub dropoff: CoordinateDto,
+    pub car_value: f64,
+    // 車両の到着を希望する日時
+    pub scheduled_for: DateTime<Utc>,
+}
+
+// 指定した項目だけを変更する。乗車地と搬送先は見積もりをやり直すため、両方をそろえて指定する
+#[derive(Deserialize, Debug)]
+pub struct UpdateScheduledOrderRequestDto {
+    pub pickup: Option<CoordinateDto>,
+    pub dropoff: Option<CoordinateDto>,
+    pub car_value: Option<f64>,
+    pub scheduled_for: Option<DateTime<Utc>>,
+    // 予約を読み込んだ時点のバージョン。指定した場合、その後に更新された予約は変更しない
+    pub version: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CancelOrderRequestDto {
+    pub reason: String,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct DispatcherOrderRequestDto {
     pub order_id: i32,
     pub dispatcher_id: i32,
     pub tow_truck_id: i32,
     pub order_time: DateTime<Utc>,
+    // 注文を読み込んだ時点のバージョン。指定した場合、その後に更新された注文は割り当てない
+    pub version: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateOrderPriorityRequestDto {
+    // vip, scheduled, normal のいずれか
+    pub priority: String,
+    pub version: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OrderSearchQueryDto {
+    pub client_name: Option<String>,
+    pub address: Option<String>,
+    pub id_prefix: Option<String>,
+    pub status: Option<String>,
+    pub area: Option<i32>,
+    pub ordered_from: Option<DateTime<Utc>>,
+    pub ordered_to: Option<DateTime<Utc>>,
+    pub min_car_value: Option<f64>,
+    pub max_car_value: Option<f64>,
+    pub page: Option<i32>,
+    pub page_size: Option<i32>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -43,6 +110,138 @@ pub struct OrderDto {
     pub car_value: f64,
     pub order_time: DateTime<Utc>,
     pub completed_time: Option<DateTime<Utc>>,
+    pub version: i32,
+    pub priority: String,
+}
+
+impl OrderDto {
+    pub fn from_sync_row(row: OrderSyncRow) -> Self {
+        OrderDto {
+            id: row.id,
+            client_id: row.client_id,
+            client_username: row.client_username,
+            dispatcher_id: row.dispatcher_id,
+            dispatcher_user_id: row.dispatcher_user_id,
+            dispatcher_username: row.dispatcher_username,
+            tow_truck_id: row.tow_truck_id,
+            driver_user_id: row.driver_user_id,
+            driver_username: row.driver_username,
+            status: row.status,
+            node_id: row.node_id,
+            area_id: row.area_id,
+            car_value: row.car_value,
+            order_time: row.order_time,
+            completed_time: row.completed_time,
+            version: row.version,
+            priority: row.priority,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct OrderQuoteDto {
+    pub order_id: i32,
+    pub area_id: i32,
+    pub pickup_node_id: i32,
+    pub dropoff_node_id: i32,
+    pub distance: i32,
+    pub price: i32,
+    // 到着できる空き車両がない場合は None
+    pub eta_minutes: Option<i32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ScheduledOrderDto {
+    pub order_id: i32,
+    pub client_id: i32,
+    pub pickup_node_id: i32,
+    pub dropoff_node_id: Option<i32>,
+    pub car_value: f64,
+    pub price: Option<i32>,
+    pub scheduled_for: DateTime<Utc>,
+    // この日時に未割り当ての注文になり、配車の対象になる
+    pub activates_at: DateTime<Utc>,
+    pub order_time: DateTime<Utc>,
+    pub version: i32,
+}
+
+impl ScheduledOrderDto {
+    pub fn from_scheduled_order(order: ScheduledOrder, activates_at: DateTime<Utc>) -> Self {
+        ScheduledOrderDto {
+            order_id: order.id,
+            client_id: order.client_id,
+            pickup_node_id: order.node_id,
+            dropoff_node_id: order.dropoff_node_id,
+            car_value: order.car_value,
+            price: order.quoted_price,
+            scheduled_for: order.scheduled_for,
+            activates_at,
+            order_time: order.order_time,
+            version: order.version,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct DashboardOrderDto {
+    pub id: i32,
+    pub client_id: i32,
+    pub dispatcher_id: Option<i32>,
+    pub tow_truck_id: Option<i32>,
+    pub status: String,
+    pub node_id: i32,
+    pub car_value: f64,
+    pub order_time: DateTime<Utc>,
+    pub completed_time: Option<DateTime<Utc>>,
+    pub priority: String,
+}
+
+impl DashboardOrderDto {
+    pub fn from_order(order: Order) -> Self {
+        DashboardOrderDto {
+            id: order.id,
+            client_id: order.client_id,
+            dispatcher_id: order.dispatcher_id,
+            tow_truck_id: order.tow_truck_id,
+            status: order.status,
+            node_id: order.node_id,
+            car_value: order.car_value,
+            order_time: order.order_time,
+            completed_time: order.completed_time,
+            priority: order.priority,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct OnDutyDispatcherDto {
+    pub dispatcher_id: i32,
+    pub user_id: i32,
+    pub username: String,
+}
+
+impl OnDutyDispatcherDto {
+    pub fn from_on_duty_dispatcher(dispatcher: OnDutyDispatcher) -> Self {
+        OnDutyDispatcherDto {
+            dispatcher_id: dispatcher.dispatcher_id,
+            user_id: dispatcher.user_id,
+            username: dispatcher.username,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct DispatcherDashboardDto {
+    pub dispatcher_id: i32,
+    pub area_id: i32,
+    // 担当エリアの未割り当ての注文 (優先度と待ち時間から決まる、処理する順)
+    pub pending_orders: Vec<DashboardOrderDto>,
+    // 自分が配車した対応中の注文
+    pub active_assignments: Vec<DashboardOrderDto>,
+    // 同じエリアでログイン中の他のディスパッチャー
+    pub on_duty_dispatchers: Vec<OnDutyDispatcherDto>,
+    // 担当エリアで最近完了した注文 (新しい順)
+    pub recent_completions: Vec<DashboardOrderDto>,
 }
 
 #[derive(Serialize, Debug)]
@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::order::{OrderAttachment, OrderNote};
+
+// Input Data Structure
+
+#[derive(Deserialize, Debug)]
+pub struct CreateOrderNoteRequestDto {
+    pub body: String,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct OrderNoteDto {
+    pub id: i32,
+    pub order_id: i32,
+    pub author_id: i32,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OrderNoteDto {
+    pub fn from_entity(entity: OrderNote) -> Self {
+        OrderNoteDto {
+            id: entity.id,
+            order_id: entity.order_id,
+            author_id: entity.author_id,
+            body: entity.body,
+            created_at: entity.created_at,
+        }
+    }
+}
+
+// 保存先のキーは返さず、中身は /order/{order_id}/attachments/{id} から取得する
+#[derive(Serialize, Debug)]
+pub struct OrderAttachmentDto {
+    pub id: i32,
+    pub order_id: i32,
+    pub uploaded_by: i32,
+    pub content_type: String,
+    pub bytes: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OrderAttachmentDto {
+    pub fn from_entity(entity: OrderAttachment) -> Self {
+        OrderAttachmentDto {
+            id: entity.id,
+            order_id: entity.order_id,
+            uploaded_by: entity.uploaded_by,
+            content_type: entity.content_type,
+            bytes: entity.bytes,
+            created_at: entity.created_at,
+        }
+    }
+}
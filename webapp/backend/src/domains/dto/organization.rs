@@ -0,0 +1,35 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::organization::Organization;
+
+// Input Data Structure
+
+#[derive(Deserialize, Debug)]
+pub struct CreateOrganizationRequestDto {
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateUserOrganizationRequestDto {
+    pub organization_id: i32,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct OrganizationDto {
+    pub id: i32,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OrganizationDto {
+    pub fn from_entity(entity: Organization) -> Self {
+        OrganizationDto {
+            id: entity.id,
+            name: entity.name,
+            created_at: entity.created_at,
+        }
+    }
+}
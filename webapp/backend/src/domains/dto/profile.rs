@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+use super::notification::NotificationPreferencesDto;
+use crate::models::profile::UserProfile;
+use crate::models::role::Role;
+
+// Input Data Structure
+
+// 指定した項目だけを変更する。文字列の項目は空文字列で未設定に戻す
+#[derive(Deserialize, Debug)]
+pub struct UpdateProfileRequestDto {
+    pub display_name: Option<String>,
+    pub email: Option<String>,
+    pub phone_number: Option<String>,
+    pub notification: Option<UpdateProfileNotificationDto>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateProfileNotificationDto {
+    pub websocket: Option<bool>,
+    pub email: Option<bool>,
+    pub webhook_url: Option<String>,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct ProfileDto {
+    pub id: i32,
+    pub username: String,
+    pub role: Role,
+    pub display_name: Option<String>,
+    pub email: Option<String>,
+    pub phone_number: Option<String>,
+    pub profile_image_url: String,
+    pub notification: NotificationPreferencesDto,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProfileImageDto {
+    pub image_version: i32,
+    pub profile_image_url: String,
+}
+
+impl ProfileImageDto {
+    pub fn new(user_id: i32, image_version: i32) -> Self {
+        ProfileImageDto {
+            image_version,
+            profile_image_url: profile_image_url(user_id, image_version),
+        }
+    }
+}
+
+// CDN やメールなど、セッションを送れない場所から画像を取得するための URL
+#[derive(Serialize, Debug)]
+pub struct SignedProfileImageUrlDto {
+    pub profile_image_url: String,
+    // 署名を行わない設定の場合は None で、URL に期限はない
+    pub expires_at: Option<i64>,
+}
+
+impl SignedProfileImageUrlDto {
+    pub fn unsigned(user_id: i32, image_version: i32) -> Self {
+        SignedProfileImageUrlDto {
+            profile_image_url: profile_image_url(user_id, image_version),
+            expires_at: None,
+        }
+    }
+
+    pub fn signed(user_id: i32, image_version: i32, expires_at: i64, signature: &str) -> Self {
+        SignedProfileImageUrlDto {
+            profile_image_url: format!(
+                "{}&exp={}&sig={}",
+                profile_image_url(user_id, image_version),
+                expires_at,
+                signature
+            ),
+            expires_at: Some(expires_at),
+        }
+    }
+}
+
+// 画像を差し替えると版が変わり URL も変わるため、この URL の画像は長期間キャッシュしてよい
+pub fn profile_image_url(user_id: i32, image_version: i32) -> String {
+    format!("/api/user_image/{}?v={}", user_id, image_version)
+}
+
+impl ProfileDto {
+    pub fn from_entity(entity: UserProfile, notification: NotificationPreferencesDto) -> Self {
+        ProfileDto {
+            id: entity.id,
+            username: entity.username,
+            role: entity.role,
+            display_name: entity.display_name,
+            email: entity.email,
+            phone_number: entity.phone_number,
+            profile_image_url: profile_image_url(entity.id, entity.image_version),
+            notification,
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::organization::QuotaLimits;
+
+// Input Data Structure
+
+// null または省略した上限は、環境変数の既定の上限に戻す
+#[derive(Deserialize, Debug)]
+pub struct UpdateQuotaRequestDto {
+    pub orders_per_day: Option<i64>,
+    pub active_dispatchers: Option<i64>,
+    pub avatar_storage_bytes: Option<i64>,
+}
+
+impl UpdateQuotaRequestDto {
+    pub fn into_limits(self) -> QuotaLimits {
+        QuotaLimits {
+            orders_per_day: self.orders_per_day,
+            active_dispatchers: self.active_dispatchers,
+            avatar_storage_bytes: self.avatar_storage_bytes,
+        }
+    }
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct QuotaUsageDto {
+    pub used: i64,
+    // null の場合は上限なし
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct OrganizationUsageDto {
+    pub organization_id: i32,
+    pub orders_today: QuotaUsageDto,
+    // 1 日の注文数を数え直す時刻
+    pub orders_reset_at: DateTime<Utc>,
+    pub active_dispatchers: QuotaUsageDto,
+    pub avatar_storage_bytes: QuotaUsageDto,
+}
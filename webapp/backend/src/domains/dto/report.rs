@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+// Output Data Structure
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AreaCompletionDto {
+    pub area_id: i32,
+    pub completed_orders: i64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DispatcherUtilizationDto {
+    pub dispatcher_id: i32,
+    pub assigned_orders: i64,
+    pub busy_seconds: i64,
+    // 集計期間のうち注文を担当していた時間の割合 (0.0 - 1.0)
+    pub utilization: f64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct WindowReportDto {
+    pub window_seconds: u64,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub completed_orders_per_area: Vec<AreaCompletionDto>,
+    pub assigned_orders: i64,
+    // 期間内に配車された注文がない場合は None
+    pub average_assignment_latency_seconds: Option<f64>,
+    pub dispatcher_utilization: Vec<DispatcherUtilizationDto>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ReportDto {
+    pub generated_at: DateTime<Utc>,
+    pub windows: Vec<WindowReportDto>,
+}
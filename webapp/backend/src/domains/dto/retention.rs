@@ -0,0 +1,23 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+// Output Data Structure
+
+// dry_run が true の場合、count は処理の対象になる件数で、まだ何も変更していない
+#[derive(Serialize, Debug)]
+pub struct RetentionPolicyReportDto {
+    pub policy: &'static str,
+    pub action: &'static str,
+    pub retention_days: u32,
+    pub cutoff: DateTime<Utc>,
+    pub count: u64,
+    // 注文の匿名化で一緒に削除する添付ファイルの件数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attachment_count: Option<u64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RetentionReportDto {
+    pub dry_run: bool,
+    pub policies: Vec<RetentionPolicyReportDto>,
+}
@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::TunableConfig;
+
+// Input Data Structure
+
+// キーは環境変数と同じ名前。値は文字列と数値のどちらでもよい
+#[derive(Deserialize, Debug)]
+#[serde(transparent)]
+pub struct UpdateRuntimeConfigRequestDto(pub HashMap<String, serde_json::Value>);
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateLogLevelRequestDto {
+    // error・warn・info・debug・trace のいずれか
+    pub level: String,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct LogLevelDto {
+    pub level: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RuntimeConfigDto {
+    pub rust_log: String,
+    pub user_cache_ttl_ms: u128,
+    pub query_cache_ttl_ms: u128,
+    pub negative_cache_ttl_ms: u128,
+    pub db_slow_query_threshold_ms: u128,
+    pub dispatcher_max_active_assignments: usize,
+}
+
+impl RuntimeConfigDto {
+    pub fn from_tunables(tunables: &TunableConfig) -> Self {
+        RuntimeConfigDto {
+            rust_log: tunables.log_filter.clone(),
+            user_cache_ttl_ms: tunables.user_cache_ttl.as_millis(),
+            query_cache_ttl_ms: tunables.query_cache_ttl.as_millis(),
+            negative_cache_ttl_ms: tunables.negative_cache_ttl.as_millis(),
+            db_slow_query_threshold_ms: tunables.slow_query_threshold.as_millis(),
+            dispatcher_max_active_assignments: tunables.max_active_assignments,
+        }
+    }
+}
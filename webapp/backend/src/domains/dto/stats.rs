@@ -0,0 +1,21 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+// Output Data Structure
+
+#[derive(Serialize, Debug, Default)]
+pub struct OrderCountsDto {
+    pub total: i64,
+    pub scheduled: i64,
+    pub pending: i64,
+    pub dispatched: i64,
+    pub completed: i64,
+    pub cancelled: i64,
+}
+
+// 件数は orders への書き込みと同じ文でトリガーが増減させる。reconciled_at は最後に orders から数え直した日時
+#[derive(Serialize, Debug)]
+pub struct StatsSummaryDto {
+    pub orders: OrderCountsDto,
+    pub reconciled_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::role::Role;
+use crate::models::user::UserSyncRow;
+
+use super::order::OrderDto;
+
+// Input Data Structure
+
+// since には前回の応答の cursor を渡す。指定しない場合は読み込み直しを求める応答になる
+#[derive(Deserialize, Debug)]
+pub struct SyncQueryDto {
+    pub since: Option<i64>,
+    pub limit: Option<i32>,
+}
+
+// Output Data Structure
+
+// reset が true の場合、クライアントは一覧の API で全体を読み込み直してから cursor 以降を同期する。
+// has_more が true の場合は、続けて cursor から同期する
+#[derive(Serialize, Debug)]
+pub struct SyncResponseDto {
+    pub cursor: i64,
+    pub has_more: bool,
+    pub reset: bool,
+    pub orders: Vec<OrderDto>,
+    pub dispatchers: Vec<SyncDispatcherDto>,
+    pub users: Vec<SyncUserDto>,
+    // 変更されたが、組織から外れるなどして参照できなくなったもの
+    pub removed: Vec<SyncRemovedDto>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SyncDispatcherDto {
+    pub id: i32,
+    pub user_id: i32,
+    pub area_id: i32,
+    pub available: bool,
+    pub active_assignments: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SyncUserDto {
+    pub id: i32,
+    pub username: String,
+    pub role: Role,
+    pub is_active: bool,
+    pub image_version: i32,
+}
+
+impl SyncUserDto {
+    pub fn from_entity(entity: UserSyncRow) -> Self {
+        SyncUserDto {
+            id: entity.id,
+            username: entity.username,
+            role: entity.role,
+            is_active: entity.is_active,
+            image_version: entity.image_version,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct SyncRemovedDto {
+    pub entity_type: &'static str,
+    pub id: i32,
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::role::Role;
+
+// Input Data Structure
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserImportFormat {
+    // 1 行目はヘッダー。username, password, role, area_id の列を順不同で持つ
+    Csv,
+    Json,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ImportUserRequestDto {
+    pub username: String,
+    pub password: String,
+    pub role: Role,
+    pub area_id: Option<i32>,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct ImportUserResultDto {
+    // 1 始まりのデータ行の番号。CSV のヘッダー行は含めない
+    pub row: usize,
+    pub username: Option<String>,
+    pub user_id: Option<i32>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct UserImportResponseDto {
+    pub imported: usize,
+    pub failed: usize,
+    pub results: Vec<ImportUserResultDto>,
+}
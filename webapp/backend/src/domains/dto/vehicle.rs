@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::vehicle::{Vehicle, VehicleStatus};
+
+// Input Data Structure
+
+#[derive(Deserialize, Debug)]
+pub struct RegisterVehicleRequestDto {
+    pub driver_user_id: i32,
+    pub area_id: i32,
+    pub capacity: i32,
+    // 一覧や配車の検索は最新の位置と結合するため、登録時に初期位置を受け取る
+    pub node_id: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateVehicleRequestDto {
+    pub driver_user_id: i32,
+    pub area_id: i32,
+    pub capacity: i32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UpdateVehicleStatusRequestDto {
+    pub status: VehicleStatus,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Clone, Debug)]
+pub struct VehicleDto {
+    pub id: i32,
+    pub driver_user_id: i32,
+    pub status: String,
+    pub area_id: i32,
+    pub capacity: i32,
+}
+
+impl VehicleDto {
+    pub fn from_entity(entity: Vehicle) -> Self {
+        VehicleDto {
+            id: entity.id,
+            driver_user_id: entity.driver_id,
+            status: entity.status,
+            area_id: entity.area_id,
+            capacity: entity.capacity,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// Input Data Structure
+
+#[derive(Deserialize, Debug)]
+pub struct CreateWebhookSubscriptionRequestDto {
+    pub url: String,
+    // 省略した場合はサーバーで生成する
+    pub secret: Option<String>,
+    // 省略した場合はすべての注文イベントを送る
+    pub events: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WebhookDeliveryQueryDto {
+    pub limit: Option<i32>,
+}
+
+// Output Data Structure
+
+#[derive(Serialize, Debug)]
+pub struct WebhookSubscriptionCreatedDto {
+    pub id: i32,
+    pub url: String,
+    pub secret: String,
+    pub events: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WebhookSubscriptionDto {
+    pub id: i32,
+    pub url: String,
+    pub events: Option<Vec<String>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WebhookDeliveryDto {
+    pub id: i32,
+    pub subscription_id: i32,
+    pub event: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    // 送信待ちの場合は次に送信を試みる時刻
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
@@ -0,0 +1,145 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use log::info;
+
+use super::events::DomainEvent;
+use super::map_service::MapRepository;
+use super::order_service::{QUOTE_DISTANCE_PER_MINUTE, UNREACHABLE_DISTANCE};
+use crate::config::EtaConfig;
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::models::graph::Graph;
+use crate::models::order::InFlightOrder;
+
+pub trait EtaRepository {
+    async fn find_in_flight_orders(&self) -> Result<Vec<InFlightOrder>, AppError>;
+    // 配車中のままの注文だけを更新する
+    async fn update_etas(
+        &self,
+        etas: &[(i32, DateTime<Utc>)],
+        updated_at: DateTime<Utc>,
+    ) -> Result<(), AppError>;
+}
+
+// 配車中の注文の到着予定時刻を、割り当てた車両の最新の位置から計算し直す。
+// 乗車地で車両を拾ったかどうかは記録されていないため、常に車両から乗車地を経由して搬送先に向かうとみなす
+#[derive(Debug)]
+pub struct EtaService<T: EtaRepository + std::fmt::Debug, U: MapRepository + std::fmt::Debug> {
+    eta_repository: T,
+    map_repository: U,
+    event_bus: Arc<EventBus>,
+    notify_threshold_minutes: i64,
+    batch_size: usize,
+}
+
+impl<T: EtaRepository + std::fmt::Debug, U: MapRepository + std::fmt::Debug> EtaService<T, U> {
+    pub fn new(
+        eta_repository: T,
+        map_repository: U,
+        event_bus: Arc<EventBus>,
+        config: &EtaConfig,
+    ) -> Self {
+        EtaService {
+            eta_repository,
+            map_repository,
+            event_bus,
+            notify_threshold_minutes: config.notify_threshold_minutes,
+            batch_size: config.batch_size,
+        }
+    }
+
+    // 計算し直した注文の数を返す
+    pub async fn recalculate(&self) -> Result<usize, AppError> {
+        let orders = self.eta_repository.find_in_flight_orders().await?;
+        if orders.is_empty() {
+            return Ok(0);
+        }
+
+        let now = Utc::now();
+        // 同じエリアの注文が多いため、グラフはエリアごとに 1 回だけ読み込む
+        let mut graphs: HashMap<i32, Graph> = HashMap::new();
+        let mut etas = Vec::with_capacity(orders.len());
+        let mut changes = Vec::new();
+        for order in orders {
+            let graph = match graphs.entry(order.area_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(self.load_graph(order.area_id).await?),
+            };
+            let Some(minutes) = remaining_minutes(graph, &order) else {
+                continue;
+            };
+            let eta_at = now + chrono::Duration::minutes(minutes as i64);
+
+            // 前回の計算がなければ、受付時の見積もりと比べる
+            let previous = order.eta_at.or_else(|| {
+                order
+                    .quoted_eta_minutes
+                    .map(|minutes| order.order_time + chrono::Duration::minutes(minutes as i64))
+            });
+            let significant = match previous {
+                Some(previous) => {
+                    (eta_at - previous).num_minutes().abs() >= self.notify_threshold_minutes
+                }
+                None => true,
+            };
+            if significant {
+                changes.push(DomainEvent::OrderEtaChanged {
+                    order_id: order.id,
+                    eta_at,
+                    eta_minutes: minutes,
+                });
+            }
+            etas.push((order.id, eta_at));
+        }
+
+        for chunk in etas.chunks(self.batch_size) {
+            self.eta_repository.update_etas(chunk, now).await?;
+        }
+        // 書き込みが済んでから通知し、通知を受けて注文を読み直した場合に新しい値が見えるようにする
+        if !changes.is_empty() {
+            info!(
+                "{} 件の注文の到着予定時刻が大きく変わりました",
+                changes.len()
+            );
+        }
+        for change in changes {
+            self.event_bus.publish(change);
+        }
+
+        Ok(etas.len())
+    }
+
+    async fn load_graph(&self, area_id: i32) -> Result<Graph, AppError> {
+        let mut graph = Graph::new();
+        for node in self.map_repository.get_all_nodes(Some(area_id)).await? {
+            graph.add_node(node);
+        }
+        for edge in self.map_repository.get_all_edges(Some(area_id)).await? {
+            graph.add_edge(edge);
+        }
+
+        Ok(graph)
+    }
+}
+
+// 道路は双方向のため、乗車地からの距離で車両からの距離と搬送先までの距離を 1 回で求める。
+// 車両が乗車地に到達できない場合は None を返す
+fn remaining_minutes(graph: &Graph, order: &InFlightOrder) -> Option<i32> {
+    let distances = graph.dijkstra_distances_from(order.node_id);
+    let reachable = |node_id: i32| {
+        distances
+            .get(&node_id)
+            .copied()
+            .filter(|distance| *distance < UNREACHABLE_DISTANCE)
+    };
+    let approach = reachable(order.tow_truck_node_id)?;
+    let delivery = match order.dropoff_node_id {
+        Some(dropoff_node_id) => reachable(dropoff_node_id)?,
+        None => 0,
+    };
+
+    Some(approach.saturating_add(delivery) / QUOTE_DISTANCE_PER_MINUTE + 1)
+}
@@ -0,0 +1,159 @@
+use chrono::{DateTime, Utc};
+
+use crate::config::TunableConfig;
+use crate::models::login_attempt::LoginAnomaly;
+use crate::models::user::SessionClient;
+
+// ドメイン層で発生し、キャッシュなど他のコンポーネントに通知するイベント
+#[derive(Debug, Clone)]
+pub enum DomainEvent {
+    UserRoleChanged {
+        user_id: i32,
+    },
+    UserDeactivated {
+        user_id: i32,
+    },
+    PasswordChanged {
+        user_id: i32,
+    },
+    // ユーザーの所属する組織が変わった。セッションの組織も読み直す必要がある
+    UserOrganizationChanged {
+        user_id: i32,
+        organization_id: i32,
+    },
+    // フィクスチャの投入などでデータ全体が置き換えられた
+    DataReset,
+    OrderCreated {
+        order_id: i32,
+        area_id: i32,
+    },
+    OrderDispatched {
+        order_id: i32,
+        dispatcher_id: i32,
+        tow_truck_id: i32,
+    },
+    // 割り当てられていた車両は空き状態に戻されている
+    OrderCancelled {
+        order_id: i32,
+        dispatcher_id: Option<i32>,
+        tow_truck_id: Option<i32>,
+        reason: String,
+    },
+    // 到着時間の見積もりがなかった注文では eta_error_minutes は None
+    OrderCompleted {
+        order_id: i32,
+        dispatcher_id: Option<i32>,
+        eta_error_minutes: Option<i32>,
+    },
+    // 到着予定時刻が通知すべきほど変わった。eta_minutes は現在からの残り時間
+    OrderEtaChanged {
+        order_id: i32,
+        eta_at: DateTime<Utc>,
+        eta_minutes: i32,
+    },
+    // ディスパッチャーが受付を開始または終了した。開始時点で担当中の注文数をあわせて通知する
+    DispatcherAvailabilityChanged {
+        dispatcher_id: i32,
+        user_id: i32,
+        area_id: i32,
+        available: bool,
+        active_assignments: usize,
+    },
+    // 異動したディスパッチャーから、同じエリアの別のディスパッチャーに引き継いだ
+    OrderReassigned {
+        order_id: i32,
+        from_dispatcher_id: i32,
+        to_dispatcher_id: i32,
+    },
+    // 引き継げるディスパッチャーがいなかったため、未割り当てに戻して車両を解放した
+    OrderRequeued {
+        order_id: i32,
+        dispatcher_id: i32,
+        tow_truck_id: Option<i32>,
+    },
+    // ディスパッチャーの担当エリアが変わった。担当中の注文はすでに引き継がれている
+    DispatcherTransferred {
+        dispatcher_id: i32,
+        user_id: i32,
+        from_area_id: i32,
+        to_area_id: i32,
+    },
+    // 道路の重みが変更された
+    EdgeUpdated {
+        node_a_id: i32,
+        node_b_id: i32,
+    },
+    // エリアの境界が登録・変更・削除された
+    AreaBoundaryChanged {
+        area_id: i32,
+    },
+    // 実行中に変更できる設定が更新された。変更後のすべての値を通知する
+    ConfigChanged {
+        tunables: TunableConfig,
+    },
+    // プロフィール画像が差し替えられた。審査で戻す場合に備えて、差し替える前の画像もあわせて通知する
+    ProfileImageUploaded {
+        user_id: i32,
+        image_name: String,
+        previous_image_name: String,
+    },
+    // 審査で確認が必要と判定された。画像はそのまま公開されている
+    ProfileImageFlagged {
+        user_id: i32,
+        image_version: i32,
+        reason: String,
+    },
+    // 審査で不適切と判定され、差し替える前の画像に戻した。image_version は戻した後の版
+    ProfileImageReverted {
+        user_id: i32,
+        image_version: i32,
+        reason: String,
+    },
+    // パスワードなどの確認が済み、セッションを発行した
+    LoginSucceeded {
+        user_id: i32,
+        client: SessionClient,
+    },
+    // 存在するユーザーへのログインで、パスワードか 2 段階認証のコードが誤っていた
+    LoginFailed {
+        user_id: i32,
+        client: SessionClient,
+    },
+    // 成功したログインを不審と判断した。セッションはすでに発行されている
+    SuspiciousLoginDetected {
+        user_id: i32,
+        client_ip: Option<String>,
+        anomalies: Vec<LoginAnomaly>,
+    },
+}
+
+impl DomainEvent {
+    // 影響を受けるユーザー。特定のユーザーに関係しないイベントは None を返す
+    pub fn user_id(&self) -> Option<i32> {
+        match self {
+            DomainEvent::UserRoleChanged { user_id }
+            | DomainEvent::UserDeactivated { user_id }
+            | DomainEvent::PasswordChanged { user_id }
+            | DomainEvent::UserOrganizationChanged { user_id, .. }
+            | DomainEvent::DispatcherTransferred { user_id, .. } => Some(*user_id),
+            DomainEvent::DataReset
+            | DomainEvent::OrderCreated { .. }
+            | DomainEvent::OrderDispatched { .. }
+            | DomainEvent::OrderCancelled { .. }
+            | DomainEvent::OrderCompleted { .. }
+            | DomainEvent::OrderReassigned { .. }
+            | DomainEvent::OrderRequeued { .. }
+            | DomainEvent::OrderEtaChanged { .. }
+            | DomainEvent::DispatcherAvailabilityChanged { .. }
+            | DomainEvent::EdgeUpdated { .. }
+            | DomainEvent::AreaBoundaryChanged { .. }
+            | DomainEvent::ConfigChanged { .. }
+            | DomainEvent::ProfileImageUploaded { .. }
+            | DomainEvent::ProfileImageFlagged { .. }
+            | DomainEvent::ProfileImageReverted { .. }
+            | DomainEvent::LoginSucceeded { .. }
+            | DomainEvent::LoginFailed { .. }
+            | DomainEvent::SuspiciousLoginDetected { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use futures_util::future;
+use futures_util::stream::{self, Stream, StreamExt};
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use super::dto::export::ExportFormat;
+use crate::{
+    errors::AppError,
+    models::{audit_log::AuditLog, order::OrderExportRow, tow_truck::TowTruck},
+};
+
+// 読み込んだ行を受け渡すチャネルの容量。書き出しが追いつかない間は DB からの読み込みも止まる
+const EXPORT_CHANNEL_CAPACITY: usize = 256;
+// 1 回のチャンクで書き出す最大行数
+const EXPORT_CHUNK_ROWS: usize = 100;
+
+pub type ExportSender<R> = mpsc::Sender<Result<R, AppError>>;
+
+// 読み込む行の絞り込み。organization_id が None の場合はすべての組織の行を読む
+#[derive(Debug, Clone, Default)]
+pub struct ExportFilter {
+    pub organization_id: Option<i32>,
+    pub status: Option<String>,
+    pub area_id: Option<i32>,
+}
+
+pub trait ExportRepository {
+    // 行を読み込むたびに sender へ送る。受信側が閉じられた場合は途中で読み込みをやめる
+    async fn stream_orders(
+        &self,
+        filter: &ExportFilter,
+        sender: &ExportSender<OrderExportRow>,
+    ) -> Result<(), AppError>;
+    // 車両は運転手のユーザーが属する組織のものとして扱う。位置を記録していない車両は含めない
+    async fn stream_tow_trucks(
+        &self,
+        filter: &ExportFilter,
+        sender: &ExportSender<TowTruck>,
+    ) -> Result<(), AppError>;
+    async fn stream_audit_logs(&self, sender: &ExportSender<AuditLog>) -> Result<(), AppError>;
+}
+
+// CSV の 1 行と NDJSON の 1 行への変換
+trait ExportRecord {
+    const CSV_HEADER: &'static [&'static str];
+    fn csv_fields(&self) -> Vec<String>;
+    fn to_json(&self) -> serde_json::Value;
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportService<T: ExportRepository + std::fmt::Debug + Clone + 'static> {
+    repository: T,
+}
+
+impl<T: ExportRepository + std::fmt::Debug + Clone + 'static> ExportService<T> {
+    pub fn new(repository: T) -> Self {
+        ExportService { repository }
+    }
+
+    // 管理者のエクスポートに加え、一覧の API の stream=true でも、ページに分けずにすべての行を返すために使う
+    pub fn export_orders(
+        &self,
+        filter: ExportFilter,
+        format: ExportFormat,
+    ) -> impl Stream<Item = Result<String, AppError>> + 'static {
+        let (sender, receiver) = mpsc::channel(EXPORT_CHANNEL_CAPACITY);
+        let repository = self.repository.clone();
+        actix_web::rt::spawn(async move {
+            if let Err(err) = repository.stream_orders(&filter, &sender).await {
+                let _ = sender.send(Err(err)).await;
+            }
+        });
+
+        encode(receiver, format)
+    }
+
+    pub fn export_tow_trucks(
+        &self,
+        filter: ExportFilter,
+        format: ExportFormat,
+    ) -> impl Stream<Item = Result<String, AppError>> + 'static {
+        let (sender, receiver) = mpsc::channel(EXPORT_CHANNEL_CAPACITY);
+        let repository = self.repository.clone();
+        actix_web::rt::spawn(async move {
+            if let Err(err) = repository.stream_tow_trucks(&filter, &sender).await {
+                let _ = sender.send(Err(err)).await;
+            }
+        });
+
+        encode(receiver, format)
+    }
+
+    pub fn export_audit_logs(
+        &self,
+        format: ExportFormat,
+    ) -> impl Stream<Item = Result<String, AppError>> + 'static {
+        let (sender, receiver) = mpsc::channel(EXPORT_CHANNEL_CAPACITY);
+        let repository = self.repository.clone();
+        actix_web::rt::spawn(async move {
+            if let Err(err) = repository.stream_audit_logs(&sender).await {
+                let _ = sender.send(Err(err)).await;
+            }
+        });
+
+        encode(receiver, format)
+    }
+}
+
+// 受信した行を数行ずつまとめて書き出す。途中でエラーになった場合はそこでストリームを終えるため、
+// JSON の配列は閉じられず、クライアントは不完全な応答だと判断できる
+fn encode<R: ExportRecord + 'static>(
+    receiver: mpsc::Receiver<Result<R, AppError>>,
+    format: ExportFormat,
+) -> impl Stream<Item = Result<String, AppError>> + 'static {
+    let header = match format {
+        ExportFormat::Csv => Some(Ok(csv_line(
+            R::CSV_HEADER.iter().map(|column| column.to_string()),
+        ))),
+        ExportFormat::Ndjson => None,
+        ExportFormat::Json => Some(Ok("[".to_string())),
+    };
+    let footer = match format {
+        ExportFormat::Json => Some(Ok("]".to_string())),
+        ExportFormat::Csv | ExportFormat::Ndjson => None,
+    };
+    let rows = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|row| (row, receiver))
+    })
+    .ready_chunks(EXPORT_CHUNK_ROWS)
+    // 配列の要素の区切りを入れるため、最初の行を書き出したかを持ち回る
+    .scan(false, move |written, rows| {
+        let mut chunk = String::new();
+        for row in rows {
+            let row = match row {
+                Ok(row) => row,
+                Err(err) => return future::ready(Some(Err(err))),
+            };
+            match format {
+                ExportFormat::Csv => chunk.push_str(&csv_line(row.csv_fields().into_iter())),
+                ExportFormat::Ndjson => {
+                    chunk.push_str(&row.to_json().to_string());
+                    chunk.push('\n');
+                }
+                ExportFormat::Json => {
+                    if *written {
+                        chunk.push(',');
+                    }
+                    chunk.push_str(&row.to_json().to_string());
+                }
+            }
+            *written = true;
+        }
+        future::ready(Some(Ok(chunk)))
+    });
+
+    stream::iter(header).chain(rows).chain(stream::iter(footer))
+}
+
+fn csv_line(fields: impl Iterator<Item = String>) -> String {
+    let mut line = fields
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push_str("\r\n");
+    line
+}
+
+fn optional<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(ToString::to_string).unwrap_or_default()
+}
+
+impl ExportRecord for OrderExportRow {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "id",
+        "client_id",
+        "client_username",
+        "dispatcher_id",
+        "tow_truck_id",
+        "status",
+        "node_id",
+        "area_id",
+        "car_value",
+        "order_time",
+        "completed_time",
+    ];
+
+    fn csv_fields(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.client_id.to_string(),
+            self.client_username.clone(),
+            optional(&self.dispatcher_id),
+            optional(&self.tow_truck_id),
+            self.status.clone(),
+            self.node_id.to_string(),
+            self.area_id.to_string(),
+            self.car_value.to_string(),
+            self.order_time.to_rfc3339(),
+            self.completed_time
+                .map(|time| time.to_rfc3339())
+                .unwrap_or_default(),
+        ]
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "client_id": self.client_id,
+            "client_username": self.client_username,
+            "dispatcher_id": self.dispatcher_id,
+            "tow_truck_id": self.tow_truck_id,
+            "status": self.status,
+            "node_id": self.node_id,
+            "area_id": self.area_id,
+            "car_value": self.car_value,
+            "order_time": self.order_time,
+            "completed_time": self.completed_time,
+        })
+    }
+}
+
+// 一覧の API の TowTruckDto と同じ形で書き出す
+impl ExportRecord for TowTruck {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "id",
+        "driver_user_id",
+        "driver_username",
+        "status",
+        "node_id",
+        "area_id",
+    ];
+
+    fn csv_fields(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            self.driver_id.to_string(),
+            optional(&self.driver_username),
+            self.status.clone(),
+            self.node_id.to_string(),
+            self.area_id.to_string(),
+        ]
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "driver_user_id": self.driver_id,
+            "driver_username": self.driver_username,
+            "status": self.status,
+            "node_id": self.node_id,
+            "area_id": self.area_id,
+        })
+    }
+}
+
+impl ExportRecord for AuditLog {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "id",
+        "actor_user_id",
+        "action",
+        "target_type",
+        "target_id",
+        "detail",
+        "created_at",
+    ];
+
+    fn csv_fields(&self) -> Vec<String> {
+        vec![
+            self.id.to_string(),
+            optional(&self.actor_user_id),
+            self.action.clone(),
+            optional(&self.target_type),
+            optional(&self.target_id),
+            optional(&self.detail),
+            self.created_at.to_rfc3339(),
+        ]
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        // detail は JSON として保存されているため、文字列ではなく値として埋め込む
+        let detail = self
+            .detail
+            .as_deref()
+            .and_then(|detail| serde_json::from_str::<serde_json::Value>(detail).ok());
+        json!({
+            "id": self.id,
+            "actor_user_id": self.actor_user_id,
+            "action": self.action,
+            "target_type": self.target_type,
+            "target_id": self.target_id,
+            "detail": detail,
+            "created_at": self.created_at,
+        })
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use log::{info, warn};
+
+use super::dto::feature_flag::FeatureFlagDto;
+use crate::errors::AppError;
+use crate::infrastructure::feature_flags::{FeatureFlag, FeatureFlags};
+use crate::models::feature_flag::FeatureFlagRow;
+
+pub trait FeatureFlagRepository {
+    async fn find_all(&self) -> Result<Vec<FeatureFlagRow>, AppError>;
+    async fn upsert(&self, name: &str, enabled: bool) -> Result<(), AppError>;
+    async fn delete(&self, name: &str) -> Result<(), AppError>;
+}
+
+#[derive(Debug)]
+pub struct FeatureFlagService<T: FeatureFlagRepository + std::fmt::Debug> {
+    repository: T,
+    flags: Arc<FeatureFlags>,
+}
+
+impl<T: FeatureFlagRepository + std::fmt::Debug> FeatureFlagService<T> {
+    pub fn new(repository: T, flags: Arc<FeatureFlags>) -> Self {
+        FeatureFlagService { repository, flags }
+    }
+
+    // 他のインスタンスで変更された値も取り込めるよう、定期的に DB から読み直す
+    pub async fn reload(&self) -> Result<(), AppError> {
+        let rows = self.repository.find_all().await?;
+        let mut overrides = HashMap::new();
+        for row in rows {
+            match FeatureFlag::parse(&row.name) {
+                Some(flag) => {
+                    if self.flags.is_enabled(flag) != row.enabled {
+                        info!(
+                            "フィーチャーフラグ {} を {} にします",
+                            row.name, row.enabled
+                        );
+                    }
+                    overrides.insert(flag, row.enabled);
+                }
+                None => warn!("未知のフィーチャーフラグ {} は無視します", row.name),
+            }
+        }
+        self.flags.replace_overrides(overrides);
+
+        Ok(())
+    }
+
+    pub fn get_flags(&self) -> Vec<FeatureFlagDto> {
+        FeatureFlag::ALL
+            .into_iter()
+            .map(|flag| self.to_dto(flag))
+            .collect()
+    }
+
+    pub async fn set_flag(&self, name: &str, enabled: bool) -> Result<FeatureFlagDto, AppError> {
+        let flag = FeatureFlag::parse(name).ok_or(AppError::NotFound)?;
+        self.repository.upsert(flag.as_str(), enabled).await?;
+        // 定期的な読み直しを待たずに、このインスタンスにはすぐ反映する
+        self.flags.set_override(flag, Some(enabled));
+        info!("フィーチャーフラグ {} を {} にしました", name, enabled);
+
+        Ok(self.to_dto(flag))
+    }
+
+    // DB の値を消して、環境変数か既定値に戻す
+    pub async fn reset_flag(&self, name: &str) -> Result<FeatureFlagDto, AppError> {
+        let flag = FeatureFlag::parse(name).ok_or(AppError::NotFound)?;
+        self.repository.delete(flag.as_str()).await?;
+        self.flags.set_override(flag, None);
+        info!("フィーチャーフラグ {} を初期値に戻しました", name);
+
+        Ok(self.to_dto(flag))
+    }
+
+    fn to_dto(&self, flag: FeatureFlag) -> FeatureFlagDto {
+        let (enabled, source) = self.flags.get(flag);
+        FeatureFlagDto {
+            name: flag.as_str(),
+            enabled,
+            source: source.as_str(),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use log::error;
+
+use crate::config::FixtureConfig;
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::models::user::NewSession;
+use crate::utils::parse_csv_line;
+
+use super::dto::fixture::{FixtureSeedResponseDto, FixtureTableDto};
+use super::events::DomainEvent;
+
+// 外部キーの参照先から順に投入する。ファイル名とテーブル名、ヘッダー行とカラム名は一致している
+const FIXTURE_TABLES: [&str; 9] = [
+    "areas",
+    "users",
+    "dispatchers",
+    "tow_trucks",
+    "nodes",
+    "edges",
+    "locations",
+    "orders",
+    "completed_orders",
+];
+// init.sql と同じく、ベンチマーカーが初期状態で使うセッションを登録する
+const FIXTURE_SESSION_USER_ID: i32 = 100001;
+const FIXTURE_SESSION_TOKEN: &str = "GclZwGGYuogTIbhixe6D3nC6JIMkFH";
+
+pub trait FixtureRepository {
+    async fn truncate_all(&self) -> Result<(), AppError>;
+    async fn insert_rows(
+        &self,
+        table: &str,
+        columns: &[String],
+        rows: &[Vec<Option<String>>],
+    ) -> Result<(), AppError>;
+    async fn create_sessions(&self, sessions: &[NewSession]) -> Result<(), AppError>;
+}
+
+#[derive(Debug)]
+struct FixtureTable {
+    name: &'static str,
+    columns: Vec<String>,
+    rows: Vec<Vec<Option<String>>>,
+}
+
+#[derive(Debug)]
+pub struct FixtureService<T: FixtureRepository + std::fmt::Debug> {
+    repository: T,
+    fixture_dir: PathBuf,
+    event_bus: Arc<EventBus>,
+}
+
+impl<T: FixtureRepository + std::fmt::Debug> FixtureService<T> {
+    pub fn new(repository: T, config: &FixtureConfig, event_bus: Arc<EventBus>) -> Self {
+        FixtureService {
+            repository,
+            fixture_dir: config.dir.clone(),
+            event_bus,
+        }
+    }
+
+    pub async fn reset(&self) -> Result<(), AppError> {
+        self.repository.truncate_all().await?;
+        self.event_bus.publish(DomainEvent::DataReset);
+
+        Ok(())
+    }
+
+    // 初期データと同じ CSV を読み込み直し、ベンチマーク開始時の状態を再現する
+    pub async fn seed(&self) -> Result<FixtureSeedResponseDto, AppError> {
+        // 途中でファイルが見つからずにデータが空のまま残らないよう、先にすべて読み込んでおく
+        let tables = FIXTURE_TABLES
+            .iter()
+            .map(|name| read_fixture_table(&self.fixture_dir, name))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.repository.truncate_all().await?;
+        // 1 文あたりの行数はリポジトリが決め、複数行の INSERT で投入する
+        for table in &tables {
+            self.repository
+                .insert_rows(table.name, &table.columns, &table.rows)
+                .await?;
+        }
+
+        self.repository
+            .create_sessions(&[NewSession {
+                user_id: FIXTURE_SESSION_USER_ID,
+                session_token: FIXTURE_SESSION_TOKEN.to_string(),
+                expires_at: Utc::now() + chrono::Duration::days(1),
+            }])
+            .await?;
+        self.event_bus.publish(DomainEvent::DataReset);
+
+        Ok(FixtureSeedResponseDto {
+            tables: tables
+                .into_iter()
+                .map(|table| FixtureTableDto {
+                    table: table.name.to_string(),
+                    rows: table.rows.len(),
+                })
+                .collect(),
+        })
+    }
+}
+
+fn read_fixture_table(dir: &Path, name: &'static str) -> Result<FixtureTable, AppError> {
+    let path = dir.join(format!("{}.csv", name));
+    let content = fs::read_to_string(&path).map_err(|e| {
+        error!("フィクスチャの読み込みに失敗しました ({:?}): {:?}", path, e);
+        AppError::InternalServerError
+    })?;
+
+    let mut lines = content.lines().filter(|line| !line.is_empty());
+    let columns = match lines.next() {
+        Some(header) => parse_csv_line(header)
+            .into_iter()
+            .map(|column| column.unwrap_or_default())
+            .collect(),
+        None => return Err(AppError::InternalServerError),
+    };
+    let rows = lines
+        .map(|line| {
+            parse_csv_line(line)
+                .into_iter()
+                .map(|value| value.map(normalize_datetime))
+                .collect()
+        })
+        .collect();
+
+    Ok(FixtureTable {
+        name,
+        columns,
+        rows,
+    })
+}
+
+// CSV の日時は RFC 3339 形式のため、DATETIME カラムに入る UTC の形式に変換する
+fn normalize_datetime(value: String) -> String {
+    match DateTime::parse_from_rfc3339(&value) {
+        Ok(datetime) => to_mysql_datetime(datetime.with_timezone(&Utc)),
+        Err(_) => value,
+    }
+}
+
+fn to_mysql_datetime(datetime: DateTime<Utc>) -> String {
+    datetime.format("%Y-%m-%d %H:%M:%S%.6f").to_string()
+}
@@ -0,0 +1,188 @@
+use chrono::Utc;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+use super::dto::geocode::{GeocodeDto, ReverseGeocodeDto};
+use crate::config::GeocodingConfig;
+use crate::errors::AppError;
+use crate::infrastructure::geocoder::{Geocoder, GeocoderImpl};
+use crate::infrastructure::single_flight::SingleFlight;
+use crate::infrastructure::ttl_cache::TtlCache;
+use crate::models::geocode::{GeocodeCacheEntry, ReverseGeocodeCacheEntry};
+
+const ADDRESS_MAX_LENGTH: usize = 255;
+
+// メモリのキャッシュの内容。再起動の前後で引き継ぐために使う
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct GeocodeCacheSnapshot {
+    pub geocodes: Vec<(String, Option<(i32, i32)>)>,
+    pub reverse_geocodes: Vec<((i32, i32), Option<String>)>,
+}
+
+pub trait GeocodingRepository {
+    // address は正規化済みのもの
+    async fn find_geocode(&self, address: &str) -> Result<Option<GeocodeCacheEntry>, AppError>;
+    // None は見つからなかったという結果として保存する
+    async fn save_geocode(
+        &self,
+        address: &str,
+        coordinate: Option<(i32, i32)>,
+    ) -> Result<(), AppError>;
+    async fn find_reverse_geocode(
+        &self,
+        x: i32,
+        y: i32,
+    ) -> Result<Option<ReverseGeocodeCacheEntry>, AppError>;
+    async fn save_reverse_geocode(
+        &self,
+        x: i32,
+        y: i32,
+        address: Option<&str>,
+    ) -> Result<(), AppError>;
+}
+
+// 住所と座標の変換結果を、メモリと DB の 2 段でキャッシュする。
+// 負荷が高いときに外部の API を呼び過ぎないよう、見つからなかった結果もキャッシュし、
+// 同じ問い合わせが同時に来た場合は 1 回だけ API を呼び出す
+#[derive(Debug)]
+pub struct GeocodingService<T: GeocodingRepository + std::fmt::Debug> {
+    repository: T,
+    geocoder: GeocoderImpl,
+    geocodes: TtlCache<String, Option<(i32, i32)>>,
+    reverse_geocodes: TtlCache<(i32, i32), Option<String>>,
+    geocode_flights: SingleFlight<String, Option<(i32, i32)>>,
+    reverse_geocode_flights: SingleFlight<(i32, i32), Option<String>>,
+    negative_cache_ttl: chrono::Duration,
+}
+
+impl<T: GeocodingRepository + std::fmt::Debug> GeocodingService<T> {
+    pub fn new(repository: T, geocoder: GeocoderImpl, config: &GeocodingConfig) -> Self {
+        GeocodingService {
+            repository,
+            geocoder,
+            geocodes: TtlCache::new(config.memory_cache_ttl, config.memory_cache_capacity),
+            reverse_geocodes: TtlCache::new(config.memory_cache_ttl, config.memory_cache_capacity),
+            geocode_flights: SingleFlight::new(),
+            reverse_geocode_flights: SingleFlight::new(),
+            negative_cache_ttl: chrono::Duration::from_std(config.negative_cache_ttl)
+                .unwrap_or(chrono::Duration::MAX),
+        }
+    }
+
+    pub fn cache_snapshot(&self) -> GeocodeCacheSnapshot {
+        GeocodeCacheSnapshot {
+            geocodes: self.geocodes.entries(),
+            reverse_geocodes: self.reverse_geocodes.entries(),
+        }
+    }
+
+    // 戻したエントリは戻した時点から TTL を数え直す
+    pub fn restore_cache(&self, snapshot: GeocodeCacheSnapshot) {
+        for (address, coordinate) in snapshot.geocodes {
+            self.geocodes.insert(address, coordinate);
+        }
+        for (coordinate, address) in snapshot.reverse_geocodes {
+            self.reverse_geocodes.insert(coordinate, address);
+        }
+    }
+
+    pub async fn geocode(&self, address: &str) -> Result<GeocodeDto, AppError> {
+        let address = normalize_address(address).ok_or(AppError::BadRequest)?;
+        let coordinate = match self.geocodes.get(&address) {
+            Some(coordinate) => coordinate,
+            None => {
+                let coordinate = self
+                    .geocode_flights
+                    .run(address.clone(), || self.load_geocode(&address))
+                    .await?;
+                self.geocodes.insert(address.clone(), coordinate);
+                coordinate
+            }
+        };
+        let (x, y) = coordinate.ok_or(AppError::NotFound)?;
+
+        Ok(GeocodeDto { address, x, y })
+    }
+
+    pub async fn reverse_geocode(&self, x: i32, y: i32) -> Result<ReverseGeocodeDto, AppError> {
+        let address = match self.reverse_geocodes.get(&(x, y)) {
+            Some(address) => address,
+            None => {
+                let address = self
+                    .reverse_geocode_flights
+                    .run((x, y), || self.load_reverse_geocode(x, y))
+                    .await?;
+                self.reverse_geocodes.insert((x, y), address.clone());
+                address
+            }
+        };
+        let address = address.ok_or(AppError::NotFound)?;
+
+        Ok(ReverseGeocodeDto { x, y, address })
+    }
+
+    async fn load_geocode(&self, address: &str) -> Result<Option<(i32, i32)>, AppError> {
+        if let Some(entry) = self.repository.find_geocode(address).await? {
+            match (entry.x, entry.y) {
+                (Some(x), Some(y)) => return Ok(Some((x, y))),
+                _ if entry.created_at + self.negative_cache_ttl > Utc::now() => return Ok(None),
+                _ => {}
+            }
+        }
+        // 外部の API を使わない設定の場合は、保存済みの結果だけで応答する
+        if !self.geocoder.is_enabled() {
+            return Ok(None);
+        }
+
+        let coordinate = self.geocoder.geocode(address).await?;
+        // 保存に失敗しても変換の結果は返す
+        if let Err(e) = self.repository.save_geocode(address, coordinate).await {
+            warn!("ジオコーディングの結果を保存できませんでした: {:?}", e);
+        }
+
+        Ok(coordinate)
+    }
+
+    async fn load_reverse_geocode(&self, x: i32, y: i32) -> Result<Option<String>, AppError> {
+        if let Some(entry) = self.repository.find_reverse_geocode(x, y).await? {
+            match entry.address {
+                Some(address) => return Ok(Some(address)),
+                None if entry.created_at + self.negative_cache_ttl > Utc::now() => return Ok(None),
+                None => {}
+            }
+        }
+        if !self.geocoder.is_enabled() {
+            return Ok(None);
+        }
+
+        // 保存できる長さを超える住所は、見つからなかったものとして扱う
+        let address = self
+            .geocoder
+            .reverse_geocode(x, y)
+            .await?
+            .filter(|address| address.chars().count() <= ADDRESS_MAX_LENGTH);
+        if let Err(e) = self
+            .repository
+            .save_reverse_geocode(x, y, address.as_deref())
+            .await
+        {
+            warn!("逆ジオコーディングの結果を保存できませんでした: {:?}", e);
+        }
+
+        Ok(address)
+    }
+}
+
+// 表記の揺れで別の問い合わせにならないよう、NFKC で正規化し、連続する空白を 1 つにまとめて小文字にする
+fn normalize_address(address: &str) -> Option<String> {
+    let address = address
+        .nfkc()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+
+    (!address.is_empty() && address.chars().count() <= ADDRESS_MAX_LENGTH).then_some(address)
+}
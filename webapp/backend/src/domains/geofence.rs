@@ -0,0 +1,171 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use log::warn;
+
+use super::events::DomainEvent;
+use super::map_service::MapRepository;
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::models::area::NearbyArea;
+
+// 範囲外の座標を拒否するときに返す、近くのエリアの数
+const NEARBY_AREA_LIMIT: usize = 3;
+
+#[derive(Debug, Clone)]
+struct AreaBoundary {
+    area_id: i32,
+    name: String,
+    organization_id: i32,
+    points: Vec<(f64, f64)>,
+}
+
+// エリアの境界を保持し、座標が組織のサービス提供範囲に含まれるかを判定する。
+// 組織の境界が 1 つも登録されていない間は、すべての座標を受け付ける
+#[derive(Debug, Default)]
+pub struct Geofence {
+    boundaries: RwLock<Option<Arc<Vec<AreaBoundary>>>>,
+    // 読み込み中に破棄された場合に、古い境界を保存しないための世代
+    generation: AtomicU64,
+}
+
+impl Geofence {
+    pub fn new(event_bus: &EventBus) -> Arc<Self> {
+        let geofence = Arc::new(Geofence::default());
+        let subscriber = geofence.clone();
+        event_bus.subscribe(move |event| {
+            if let DomainEvent::DataReset | DomainEvent::AreaBoundaryChanged { .. } = event {
+                subscriber.generation.fetch_add(1, Ordering::SeqCst);
+                *subscriber.boundaries.write().unwrap() = None;
+            }
+        });
+
+        geofence
+    }
+
+    // 境界の内側か線上にあれば受け付ける。範囲外の場合は近いエリアを添えてエラーにする
+    pub async fn check<T: MapRepository>(
+        &self,
+        repository: &T,
+        organization_id: i32,
+        x: i32,
+        y: i32,
+    ) -> Result<(), AppError> {
+        let all_boundaries = self.boundaries(repository).await?;
+        let boundaries: Vec<&AreaBoundary> = all_boundaries
+            .iter()
+            .filter(|boundary| boundary.organization_id == organization_id)
+            .collect();
+        if boundaries.is_empty() {
+            return Ok(());
+        }
+
+        let point = (x as f64, y as f64);
+        if boundaries
+            .iter()
+            .any(|boundary| contains(&boundary.points, point))
+        {
+            return Ok(());
+        }
+
+        let mut nearby: Vec<NearbyArea> = boundaries
+            .iter()
+            .map(|boundary| NearbyArea {
+                area_id: boundary.area_id,
+                name: boundary.name.clone(),
+                distance: distance_to_boundary(&boundary.points, point),
+            })
+            .collect();
+        nearby.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        nearby.truncate(NEARBY_AREA_LIMIT);
+
+        Err(AppError::OutsideServiceArea(nearby))
+    }
+
+    async fn boundaries<T: MapRepository>(
+        &self,
+        repository: &T,
+    ) -> Result<Arc<Vec<AreaBoundary>>, AppError> {
+        if let Some(boundaries) = self.boundaries.read().unwrap().as_ref() {
+            return Ok(boundaries.clone());
+        }
+
+        let generation = self.generation.load(Ordering::SeqCst);
+        let boundaries: Arc<Vec<AreaBoundary>> = Arc::new(
+            repository
+                .get_area_boundaries()
+                .await?
+                .into_iter()
+                .filter_map(
+                    |row| match serde_json::from_str::<Vec<[i32; 2]>>(&row.boundary) {
+                        Ok(points) if points.len() >= 3 => Some(AreaBoundary {
+                            area_id: row.id,
+                            name: row.name,
+                            organization_id: row.organization_id,
+                            points: points
+                                .into_iter()
+                                .map(|[x, y]| (x as f64, y as f64))
+                                .collect(),
+                        }),
+                        _ => {
+                            warn!(
+                                "エリア {} の境界を解釈できないため、範囲の確認に使いません",
+                                row.id
+                            );
+                            None
+                        }
+                    },
+                )
+                .collect(),
+        );
+        let mut cached = self.boundaries.write().unwrap();
+        if self.generation.load(Ordering::SeqCst) == generation {
+            *cached = Some(boundaries.clone());
+        }
+
+        Ok(boundaries)
+    }
+}
+
+// 線上の点は内側として扱い、それ以外は交差数の偶奇で判定する
+fn contains(points: &[(f64, f64)], point: (f64, f64)) -> bool {
+    if distance_to_boundary(points, point) == 0.0 {
+        return true;
+    }
+
+    let (px, py) = point;
+    let mut inside = false;
+    let mut previous = points[points.len() - 1];
+    for &current in points {
+        let ((x1, y1), (x2, y2)) = (previous, current);
+        if (y1 > py) != (y2 > py) && px < (x2 - x1) * (py - y1) / (y2 - y1) + x1 {
+            inside = !inside;
+        }
+        previous = current;
+    }
+
+    inside
+}
+
+// 多角形の辺までの最短距離
+fn distance_to_boundary(points: &[(f64, f64)], point: (f64, f64)) -> f64 {
+    let mut previous = points[points.len() - 1];
+    let mut distance = f64::INFINITY;
+    for &current in points {
+        distance = distance.min(distance_to_segment(previous, current, point));
+        previous = current;
+    }
+
+    distance
+}
+
+fn distance_to_segment(a: (f64, f64), b: (f64, f64), p: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = match length {
+        0.0 => 0.0,
+        _ => (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length).clamp(0.0, 1.0),
+    };
+
+    ((a.0 + t * dx - p.0).powi(2) + (a.1 + t * dy - p.1).powi(2)).sqrt()
+}
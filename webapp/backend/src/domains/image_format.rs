@@ -0,0 +1,91 @@
+use super::dto::auth::ResizeMode;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// 保存された画像の実際の形式。拡張子ではなく先頭のバイト列で判定する。
+// 以前は変換せずに保存していたため、拡張子が .png でも中身が JPEG などの画像が残っている
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+    Bmp,
+}
+
+impl ImageFormat {
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            _ if bytes.starts_with(PNG_SIGNATURE) => Some(ImageFormat::Png),
+            [0xff, 0xd8, 0xff, ..] => Some(ImageFormat::Jpeg),
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(ImageFormat::Gif),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => {
+                Some(ImageFormat::Webp)
+            }
+            [b'B', b'M', ..] => Some(ImageFormat::Bmp),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Gif => "image/gif",
+            ImageFormat::Webp => "image/webp",
+            ImageFormat::Bmp => "image/bmp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Bmp => "bmp",
+        }
+    }
+
+    // convert の入力に付ける形式の指定 (png:- など)。拡張子や推測に頼らず、判定した形式として読ませる
+    pub fn magick_prefix(format: Option<Self>) -> &'static str {
+        match format {
+            Some(ImageFormat::Png) => "png:",
+            Some(ImageFormat::Jpeg) => "jpeg:",
+            Some(ImageFormat::Gif) => "gif:",
+            Some(ImageFormat::Webp) => "webp:",
+            Some(ImageFormat::Bmp) => "bmp:",
+            None => "",
+        }
+    }
+}
+
+// PNG の IHDR から幅と高さを読む。PNG でない場合や壊れている場合は None
+pub fn png_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    if ImageFormat::sniff(bytes) != Some(ImageFormat::Png) || bytes.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(bytes.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(bytes.get(20..24)?.try_into().ok()?);
+
+    Some((width, height))
+}
+
+// 変換しても大きさが変わらず、そのまま返してよい画像か。
+// 返す形式は PNG のため、それ以外の形式は大きさが合っていても変換する
+pub fn is_already_sized(source: &[u8], width: i32, height: i32, mode: ResizeMode) -> bool {
+    let Some((actual_width, actual_height)) = png_dimensions(source) else {
+        return false;
+    };
+    let (actual_width, actual_height) = (i64::from(actual_width), i64::from(actual_height));
+    let (width, height) = (i64::from(width), i64::from(height));
+
+    match mode {
+        ResizeMode::Exact => actual_width == width && actual_height == height,
+        // 縦横比を保って枠に収める場合、どちらかの辺が枠に一致し、もう一方が収まっていれば倍率は 1 になる
+        ResizeMode::Fit => {
+            (actual_width == width && actual_height <= height)
+                || (actual_height == height && actual_width <= width)
+        }
+    }
+}
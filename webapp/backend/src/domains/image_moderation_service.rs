@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use log::{error, info, warn};
+
+use super::auth_service::AuthRepository;
+use super::events::DomainEvent;
+use super::image_service::ImageStore;
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::image_moderator::{ImageModerator, ModerationVerdict};
+use crate::infrastructure::request_id;
+
+// アップロードされたプロフィール画像を審査し、確認が必要なものは管理者に通知し、不適切なものは元の画像に戻す。
+// 審査はアップロードのリクエストとは別に行うため、判定が出るまでの間は新しい画像が公開される
+#[derive(Debug)]
+pub struct ImageModerationService<T, U, M> {
+    auth_repository: T,
+    image_store: U,
+    moderator: M,
+    event_bus: Arc<EventBus>,
+}
+
+impl<T, U, M> ImageModerationService<T, U, M>
+where
+    T: AuthRepository + std::fmt::Debug + Send + Sync + 'static,
+    U: ImageStore + std::fmt::Debug + Send + Sync + 'static,
+    M: ImageModerator + std::fmt::Debug + Send + Sync + 'static,
+{
+    pub fn new(
+        auth_repository: T,
+        image_store: U,
+        moderator: M,
+        event_bus: Arc<EventBus>,
+    ) -> Arc<Self> {
+        let service = Arc::new(ImageModerationService {
+            auth_repository,
+            image_store,
+            moderator,
+            event_bus: event_bus.clone(),
+        });
+        let subscriber = service.clone();
+        event_bus.subscribe(move |event| {
+            if let DomainEvent::ProfileImageUploaded {
+                user_id,
+                image_name,
+                previous_image_name,
+            } = event
+            {
+                let service = subscriber.clone();
+                let (user_id, image_name, previous_image_name) =
+                    (*user_id, image_name.clone(), previous_image_name.clone());
+                request_id::spawn(async move {
+                    if let Err(err) = service
+                        .moderate(user_id, &image_name, &previous_image_name)
+                        .await
+                    {
+                        error!(
+                            "ユーザー {} のプロフィール画像を審査できませんでした: {:?}",
+                            user_id, err
+                        );
+                    }
+                });
+            }
+        });
+
+        service
+    }
+
+    async fn moderate(
+        &self,
+        user_id: i32,
+        image_name: &str,
+        previous_image_name: &str,
+    ) -> Result<(), AppError> {
+        let Some(image) = self.image_store.get(image_name).await? else {
+            // 審査の前にさらに差し替えられ、削除された場合
+            return Ok(());
+        };
+
+        match self.moderator.moderate(&image).await? {
+            ModerationVerdict::Allow => {}
+            ModerationVerdict::Flag(reason) => {
+                info!(
+                    "ユーザー {} のプロフィール画像 {} に確認が必要です: {}",
+                    user_id, image_name, reason
+                );
+                let image_version = self
+                    .auth_repository
+                    .find_profile_image_by_user_id(user_id)
+                    .await?
+                    .filter(|image| image.name == image_name)
+                    .map(|image| image.version);
+                if let Some(image_version) = image_version {
+                    self.event_bus.publish(DomainEvent::ProfileImageFlagged {
+                        user_id,
+                        image_version,
+                        reason,
+                    });
+                }
+            }
+            ModerationVerdict::Reject(reason) => {
+                // 審査の間に利用者が別の画像に差し替えていた場合は、その画像を上書きしない
+                match self
+                    .auth_repository
+                    .replace_profile_image_name(user_id, image_name, previous_image_name)
+                    .await?
+                {
+                    Some(image_version) => {
+                        warn!(
+                            "ユーザー {} のプロフィール画像 {} を元に戻しました: {}",
+                            user_id, image_name, reason
+                        );
+                        self.event_bus.publish(DomainEvent::ProfileImageReverted {
+                            user_id,
+                            image_version,
+                            reason,
+                        });
+                    }
+                    None => info!(
+                        "ユーザー {} のプロフィール画像 {} はすでに差し替えられています",
+                        user_id, image_name
+                    ),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,680 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use actix_web::web::Bytes;
+use chrono::Utc;
+use futures_util::stream::{self, Stream, StreamExt};
+use log::error;
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+
+use crate::errors::{AppError, ResultExt};
+use crate::infrastructure::deadline;
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::single_flight::SingleFlight;
+use crate::infrastructure::worker_pool::WorkerPool;
+use crate::models::organization::OrganizationScope;
+use crate::models::user::StoredProfileImage;
+use crate::utils::generate_session_token;
+
+use super::auth_service::AuthRepository;
+use super::dto::auth::ResizeMode;
+use super::dto::profile::{ProfileImageDto, SignedProfileImageUrlDto};
+use super::events::DomainEvent;
+use super::image_format::{is_already_sized, ImageFormat};
+use super::image_url_signer::ImageUrlSigner;
+use super::quota_service::{QuotaRepository, QuotaService};
+
+const MAX_PROFILE_IMAGE_DIMENSION: i32 = 2000;
+const MAX_PROFILE_IMAGE_PIXELS: i64 = 1_000_000;
+const MAX_DEFAULT_AVATAR_CACHE_ENTRIES: usize = 10_000;
+const IDENTICON_GRID_SIZE: usize = 5;
+// 期限を確認しながら変換の終了を待つ間隔
+const CONVERT_POLL_INTERVAL: Duration = Duration::from_millis(5);
+// 変換結果を返す際に一度に読み出す大きさと、送信待ちにできるチャンクの数。
+// 送信が追いつかない間は convert の出力の読み出しも止まる
+const IMAGE_STREAM_CHUNK_SIZE: usize = 64 * 1024;
+const IMAGE_STREAM_CHANNEL_CAPACITY: usize = 4;
+
+pub trait ImageStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AppError>;
+    async fn put(&self, key: &str, bytes: &[u8], content_type: &str) -> Result<(), AppError>;
+    // 存在しないキーを指定してもエラーにしない
+    async fn delete(&self, key: &str) -> Result<(), AppError>;
+}
+
+pub enum ProfileImage {
+    // 登録された画像。変換結果は stream_profile_image で受け取る
+    Stored {
+        image_name: String,
+        image_version: i32,
+        thumbnail_name: String,
+    },
+    // 画像を登録していないユーザーの既定のアバター。小さくキャッシュしてあるため、そのまま返す
+    Default(Bytes),
+}
+
+#[derive(Debug)]
+pub struct ImageService<
+    T: AuthRepository + std::fmt::Debug,
+    U: ImageStore + std::fmt::Debug,
+    V: QuotaRepository + std::fmt::Debug,
+> {
+    auth_repository: T,
+    image_store: U,
+    workers: Arc<WorkerPool>,
+    quotas: Arc<QuotaService<V>>,
+    // 設定されている場合、画像の取得には有効な署名かセッションが必要
+    url_signer: Option<ImageUrlSigner>,
+    event_bus: Arc<EventBus>,
+    default_avatar_cache: Mutex<HashMap<(i32, i32, i32, ResizeMode), Bytes>>,
+    // 同じ画像の同じサイズへの変換が同時に要求された場合は、1 回だけ変換する。キーはサムネイルのファイル名
+    offload_flights: SingleFlight<String, String>,
+}
+
+impl<
+        T: AuthRepository + std::fmt::Debug,
+        U: ImageStore + std::fmt::Debug,
+        V: QuotaRepository + std::fmt::Debug,
+    > ImageService<T, U, V>
+{
+    pub fn new(
+        auth_repository: T,
+        image_store: U,
+        workers: Arc<WorkerPool>,
+        quotas: Arc<QuotaService<V>>,
+        url_signer: Option<ImageUrlSigner>,
+        event_bus: Arc<EventBus>,
+    ) -> Self {
+        ImageService {
+            auth_repository,
+            image_store,
+            workers,
+            quotas,
+            url_signer,
+            event_bus,
+            default_avatar_cache: Mutex::new(HashMap::new()),
+            offload_flights: SingleFlight::new(),
+        }
+    }
+
+    pub async fn find_profile_image(
+        &self,
+        user_id: i32,
+        width: i32,
+        height: i32,
+        mode: ResizeMode,
+    ) -> Result<ProfileImage, AppError> {
+        validate_resize_dimensions(width, height)?;
+
+        match self.find_uploaded_image(user_id).await? {
+            Some(image) => Ok(ProfileImage::Stored {
+                thumbnail_name: thumbnail_file_name(&image.name, width, height, mode),
+                image_name: image.name,
+                image_version: image.version,
+            }),
+            None => Ok(ProfileImage::Default(
+                self.get_default_avatar(user_id, width, height, mode)
+                    .await?,
+            )),
+        }
+    }
+
+    // 変換した画像全体をメモリに溜めず、convert の出力を読んだ分から順に返す。
+    // 同時に複数の要求があってもまとめずに変換するが、1 リクエストあたりに保持するのはチャネルの容量分だけになる
+    pub async fn stream_profile_image(
+        &self,
+        image_name: &str,
+        width: i32,
+        height: i32,
+        mode: ResizeMode,
+    ) -> Result<impl Stream<Item = Result<Bytes, AppError>> + 'static, AppError> {
+        let source = self.load_image(image_name).await?;
+        if is_already_sized(&source, width, height, mode) {
+            return Ok(stream::once(std::future::ready(Ok(Bytes::from(source)))).left_stream());
+        }
+        let deadline = deadline::current();
+        deadline::check(deadline)?;
+        // 混雑していて断る場合に 503 を返せるよう、レスポンスを返し始める前に実行枠を確保する
+        let permit = self.workers.acquire().await?;
+
+        let geometry = resize_geometry(width, height, mode);
+        let (sender, receiver) = mpsc::channel(IMAGE_STREAM_CHANNEL_CAPACITY);
+        actix_web::rt::spawn(async move {
+            let worker_sender = sender.clone();
+            let result = permit
+                .run(move || {
+                    stream_convert(
+                        &source,
+                        &["-resize", &geometry],
+                        "png:-",
+                        deadline,
+                        &worker_sender,
+                    )
+                })
+                .await
+                .and_then(|result| result);
+            if let Err(err) = result {
+                let _ = sender.send(Err(err)).await;
+            }
+        });
+
+        Ok(stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|chunk| (chunk, receiver))
+        })
+        .right_stream())
+    }
+
+    pub async fn get_offloaded_profile_image_name(
+        &self,
+        user_id: i32,
+        width: i32,
+        height: i32,
+        mode: ResizeMode,
+        public_dir: &Path,
+    ) -> Result<String, AppError> {
+        validate_resize_dimensions(width, height)?;
+
+        let profile_image_name = self
+            .find_uploaded_image(user_id)
+            .await?
+            .map(|image| image.name);
+        let thumbnail_name = match &profile_image_name {
+            Some(name) => thumbnail_file_name(name, width, height, mode),
+            None => thumbnail_file_name(&format!("identicon_{}", user_id), width, height, mode),
+        };
+        let thumbnail_path = public_dir.join(&thumbnail_name);
+        if thumbnail_path.exists() {
+            return Ok(thumbnail_name);
+        }
+
+        self.offload_flights
+            .run(thumbnail_name.clone(), || async {
+                let output = match &profile_image_name {
+                    Some(name) => {
+                        let source = self.load_image(name).await?;
+                        if is_already_sized(&source, width, height, mode) {
+                            Bytes::from(source)
+                        } else {
+                            let geometry = resize_geometry(width, height, mode);
+                            Bytes::from(
+                                run_blocking(&self.workers, move |deadline| {
+                                    resize_image(&source, &geometry, "png:-", deadline)
+                                })
+                                .await?,
+                            )
+                        }
+                    }
+                    None => {
+                        self.get_default_avatar(user_id, width, height, mode)
+                            .await?
+                    }
+                };
+
+                fs::create_dir_all(public_dir).context("image_service.create_thumbnail_dir")?;
+
+                // 書き込み途中のファイルが配信されないよう、一時ファイルに出力してからリネームする
+                let temp_path =
+                    public_dir.join(format!(".{}.{}.tmp", thumbnail_name, rand::random::<u32>()));
+                fs::write(&temp_path, &output)
+                    .and_then(|_| fs::rename(&temp_path, &thumbnail_path))
+                    .context_with_id("image_service.write_thumbnail", user_id.into())?;
+
+                Ok(thumbnail_name.clone())
+            })
+            .await
+    }
+
+    pub fn requires_authorization(&self) -> bool {
+        self.url_signer.is_some()
+    }
+
+    pub fn verify_signed_url(&self, user_id: i32, expires_at: i64, signature: &str) -> bool {
+        self.url_signer
+            .as_ref()
+            .is_some_and(|signer| signer.verify(user_id, expires_at, signature, Utc::now()))
+    }
+
+    // 署名を行わない設定の場合は、誰でも取得できる通常の URL を返す
+    pub async fn issue_signed_profile_image_url(
+        &self,
+        user_id: i32,
+    ) -> Result<SignedProfileImageUrlDto, AppError> {
+        let image_version = self
+            .find_stored_image(user_id)
+            .await?
+            .map_or(0, |image| image.version);
+
+        Ok(match &self.url_signer {
+            Some(signer) => {
+                let (expires_at, signature) = signer.sign(user_id, Utc::now());
+                SignedProfileImageUrlDto::signed(user_id, image_version, expires_at, &signature)
+            }
+            None => SignedProfileImageUrlDto::unsigned(user_id, image_version),
+        })
+    }
+
+    // アップロードされた画像はハンドラが一時ファイルに書き出しており、サイズの上限もそこで確認している
+    pub async fn upload_profile_image(
+        &self,
+        organization: OrganizationScope,
+        user_id: i32,
+        source: &Path,
+    ) -> Result<ProfileImageDto, AppError> {
+        match fs::metadata(source) {
+            Ok(metadata) if metadata.len() > 0 => {}
+            _ => return Err(AppError::BadRequest),
+        }
+
+        // 位置情報などの EXIF を残さないよう、向きを反映したうえでメタデータを除去する
+        let source = source.to_path_buf();
+        let normalized = run_blocking(&self.workers, move |deadline| {
+            run_convert_file(&source, &["-auto-orient", "-strip"], "png:-", deadline)
+        })
+        .await
+        .map_err(|e| match e {
+            AppError::GatewayTimeout | AppError::ServiceUnavailable(_) => e,
+            _ => AppError::BadRequest,
+        })?;
+
+        let previous_image = self
+            .find_stored_image(user_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        // 上限は変換後の大きさで確認する。差し替える前の画像の分は使用量から除く
+        let bytes = normalized.len() as i64;
+        self.quotas
+            .check_avatar_storage_quota(organization, user_id, bytes)
+            .await?;
+        let profile_image_name = format!("{}_{}.png", user_id, generate_session_token());
+        self.image_store
+            .put(&profile_image_name, &normalized, "image/png")
+            .await?;
+        let image_version = self
+            .auth_repository
+            .update_profile_image_name(user_id, &profile_image_name)
+            .await?;
+        self.quotas
+            .record_profile_image(user_id, &profile_image_name, bytes)
+            .await?;
+        // 審査は購読側で非同期に行うため、アップロードは審査の完了を待たない
+        self.event_bus.publish(DomainEvent::ProfileImageUploaded {
+            user_id,
+            image_name: profile_image_name,
+            previous_image_name: previous_image.name,
+        });
+
+        Ok(ProfileImageDto::new(user_id, image_version))
+    }
+
+    async fn load_image(&self, image_name: &str) -> Result<Vec<u8>, AppError> {
+        match self.image_store.get(image_name).await? {
+            Some(bytes) => Ok(bytes),
+            None => Err(AppError::NotFound),
+        }
+    }
+
+    // 存在しないユーザーは None になる。DB のエラーは 404 にせず、そのまま返す
+    async fn find_stored_image(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<StoredProfileImage>, AppError> {
+        self.auth_repository
+            .find_profile_image_by_user_id(user_id)
+            .await
+            .context_with_id("image_service.find_stored_image", user_id.into())
+    }
+
+    // 存在しないユーザーは 404 にする。既定の default.png のままのユーザーは None で、アバターを生成して返す。
+    // 存在しない ID ごとにアバターを生成してキャッシュが埋まることはない
+    async fn find_uploaded_image(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<StoredProfileImage>, AppError> {
+        let image = self
+            .find_stored_image(user_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        Ok((!image.is_default()).then_some(image))
+    }
+
+    async fn get_default_avatar(
+        &self,
+        user_id: i32,
+        width: i32,
+        height: i32,
+        mode: ResizeMode,
+    ) -> Result<Bytes, AppError> {
+        let cache_key = (user_id, width, height, mode);
+        if let Some(avatar) = self.default_avatar_cache.lock().unwrap().get(&cache_key) {
+            return Ok(avatar.clone());
+        }
+
+        let geometry = resize_geometry(width, height, mode);
+        let avatar = Bytes::from(
+            run_blocking(&self.workers, move |deadline| {
+                run_convert(
+                    &identicon_ppm(user_id),
+                    &["-scale", &geometry],
+                    "png:-",
+                    deadline,
+                )
+            })
+            .await?,
+        );
+
+        let mut cache = self.default_avatar_cache.lock().unwrap();
+        if cache.len() >= MAX_DEFAULT_AVATAR_CACHE_ENTRIES {
+            cache.clear();
+        }
+        cache.insert(cache_key, avatar.clone());
+
+        Ok(avatar)
+    }
+}
+
+fn validate_resize_dimensions(width: i32, height: i32) -> Result<(), AppError> {
+    let dimension_range = 1..=MAX_PROFILE_IMAGE_DIMENSION;
+    if !dimension_range.contains(&width) || !dimension_range.contains(&height) {
+        return Err(AppError::BadRequest);
+    }
+
+    // 出力画素数の上限を超えるリサイズはメモリを圧迫するため拒否する
+    if i64::from(width) * i64::from(height) > MAX_PROFILE_IMAGE_PIXELS {
+        return Err(AppError::BadRequest);
+    }
+
+    Ok(())
+}
+
+fn thumbnail_file_name(
+    profile_image_name: &str,
+    width: i32,
+    height: i32,
+    mode: ResizeMode,
+) -> String {
+    let stem = Path::new(profile_image_name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(profile_image_name);
+    let mode = match mode {
+        ResizeMode::Exact => "exact",
+        ResizeMode::Fit => "fit",
+    };
+
+    format!("{}_{}x{}_{}.png", stem, width, height, mode)
+}
+
+fn resize_geometry(width: i32, height: i32, mode: ResizeMode) -> String {
+    match mode {
+        ResizeMode::Exact => format!("{}x{}!", width, height),
+        ResizeMode::Fit => format!("{}x{}", width, height),
+    }
+}
+
+// ユーザー ID から左右対称の 5x5 パターンを作り、PPM 形式で返す
+fn identicon_ppm(user_id: i32) -> Vec<u8> {
+    let hash = Sha256::digest(user_id.to_string().as_bytes());
+    let foreground = [hash[0] / 2 + 64, hash[1] / 2 + 64, hash[2] / 2 + 64];
+    let background = [240, 240, 240];
+
+    let mut ppm = format!("P6\n{0} {0}\n255\n", IDENTICON_GRID_SIZE).into_bytes();
+    for y in 0..IDENTICON_GRID_SIZE {
+        for x in 0..IDENTICON_GRID_SIZE {
+            let column = x.min(IDENTICON_GRID_SIZE - 1 - x);
+            let is_filled = hash[3 + column * IDENTICON_GRID_SIZE + y] & 1 == 1;
+            ppm.extend_from_slice(if is_filled { &foreground } else { &background });
+        }
+    }
+
+    ppm
+}
+
+// 画像の変換はワーカーのスレッドを止めないよう画像用のプールで実行し、リクエストの期限を引き継ぐ
+async fn run_blocking<T: Send + 'static>(
+    workers: &Arc<WorkerPool>,
+    work: impl FnOnce(Option<Instant>) -> Result<T, AppError> + Send + 'static,
+) -> Result<T, AppError> {
+    let deadline = deadline::current();
+    deadline::check(deadline)?;
+
+    workers.run(move || work(deadline)).await?
+}
+
+fn resize_image(
+    source: &[u8],
+    geometry: &str,
+    output_target: &str,
+    deadline: Option<Instant>,
+) -> Result<Vec<u8>, AppError> {
+    run_convert(source, &["-resize", geometry], output_target, deadline)
+}
+
+fn run_convert(
+    source: &[u8],
+    args: &[&str],
+    output_target: &str,
+    deadline: Option<Instant>,
+) -> Result<Vec<u8>, AppError> {
+    let child = Command::new("convert")
+        .arg(stdin_input(source))
+        .args(args)
+        .arg(output_target)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            error!("画像変換のコマンド実行に失敗しました: {:?}", e);
+            AppError::InternalServerError
+        })?;
+
+    convert_output(wait_for_output(child, Some(source), deadline)?)
+}
+
+// 標準入力から読ませる場合も、先頭のバイト列で判定した形式を指定する
+fn stdin_input(source: &[u8]) -> String {
+    format!(
+        "{}-",
+        ImageFormat::magick_prefix(ImageFormat::sniff(source))
+    )
+}
+
+// ファイルを直接 convert に渡し、画像全体をメモリに読み込まずに変換する
+fn run_convert_file(
+    source: &Path,
+    args: &[&str],
+    output_target: &str,
+    deadline: Option<Instant>,
+) -> Result<Vec<u8>, AppError> {
+    // 拡張子は当てにならないため、先頭のバイト列で判定した形式を指定して読ませる
+    let mut header = [0; 16];
+    let read = File::open(source)
+        .and_then(|mut file| file.read(&mut header))
+        .unwrap_or(0);
+    let mut input = OsString::from(ImageFormat::magick_prefix(ImageFormat::sniff(
+        &header[..read],
+    )));
+    input.push(source);
+    let child = Command::new("convert")
+        .arg(input)
+        .args(args)
+        .arg(output_target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            error!("画像変換のコマンド実行に失敗しました: {:?}", e);
+            AppError::InternalServerError
+        })?;
+
+    convert_output(wait_for_output(child, None, deadline)?)
+}
+
+// 期限を過ぎても終わらない場合はプロセスを終了させる
+fn wait_for_output(
+    mut child: Child,
+    input: Option<&[u8]>,
+    deadline: Option<Instant>,
+) -> Result<Output, AppError> {
+    let stdin = child.stdin.take();
+    let mut stdout = child.stdout.take().ok_or(AppError::InternalServerError)?;
+    let mut stderr = child.stderr.take().ok_or(AppError::InternalServerError)?;
+
+    // 標準出力の読み出しと並行して書き込まないと、パイプが詰まってデッドロックする
+    thread::scope(|scope| {
+        if let (Some(mut stdin), Some(input)) = (stdin, input) {
+            scope.spawn(move || {
+                let _ = stdin.write_all(input);
+            });
+        }
+        let stdout_reader = scope.spawn(move || {
+            let mut buffer = Vec::new();
+            stdout.read_to_end(&mut buffer).map(|_| buffer)
+        });
+        let stderr_reader = scope.spawn(move || {
+            let mut buffer = Vec::new();
+            stderr.read_to_end(&mut buffer).map(|_| buffer)
+        });
+
+        let status = match deadline {
+            None => child.wait(),
+            Some(deadline) => loop {
+                match child.try_wait() {
+                    Ok(Some(status)) => break Ok(status),
+                    Ok(None) if Instant::now() >= deadline => {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return Err(AppError::GatewayTimeout);
+                    }
+                    Ok(None) => thread::sleep(CONVERT_POLL_INTERVAL),
+                    Err(e) => break Err(e),
+                }
+            },
+        };
+
+        let output = status.and_then(|status| {
+            Ok(Output {
+                status,
+                stdout: stdout_reader.join().unwrap()?,
+                stderr: stderr_reader.join().unwrap()?,
+            })
+        });
+        output.map_err(|e| {
+            error!("画像変換のコマンド実行に失敗しました: {:?}", e);
+            AppError::InternalServerError
+        })
+    })
+}
+
+// 標準出力を読んだ分から sender に送る。受信側が閉じられた場合は変換を打ち切る
+fn stream_convert(
+    source: &[u8],
+    args: &[&str],
+    output_target: &str,
+    deadline: Option<Instant>,
+    sender: &mpsc::Sender<Result<Bytes, AppError>>,
+) -> Result<(), AppError> {
+    let mut child = Command::new("convert")
+        .arg(stdin_input(source))
+        .args(args)
+        .arg(output_target)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            error!("画像変換のコマンド実行に失敗しました: {:?}", e);
+            AppError::InternalServerError
+        })?;
+    let stdin = child.stdin.take();
+    let mut stdout = child.stdout.take().ok_or(AppError::InternalServerError)?;
+    let mut stderr = child.stderr.take().ok_or(AppError::InternalServerError)?;
+
+    thread::scope(|scope| {
+        if let Some(mut stdin) = stdin {
+            scope.spawn(move || {
+                let _ = stdin.write_all(source);
+            });
+        }
+        let stdout_reader = scope.spawn(move || -> std::io::Result<()> {
+            let mut buffer = vec![0; IMAGE_STREAM_CHUNK_SIZE];
+            loop {
+                let read = stdout.read(&mut buffer)?;
+                if read == 0 {
+                    return Ok(());
+                }
+                // クライアントが切断した場合は送れないため、読み出しをやめる
+                if sender
+                    .blocking_send(Ok(Bytes::copy_from_slice(&buffer[..read])))
+                    .is_err()
+                {
+                    return Ok(());
+                }
+            }
+        });
+        let stderr_reader = scope.spawn(move || {
+            let mut buffer = Vec::new();
+            stderr.read_to_end(&mut buffer).map(|_| buffer)
+        });
+
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break Ok(status),
+                Ok(None) if sender.is_closed() => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Ok(());
+                }
+                Ok(None) if deadline.is_some_and(|deadline| Instant::now() >= deadline) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(AppError::GatewayTimeout);
+                }
+                Ok(None) => thread::sleep(CONVERT_POLL_INTERVAL),
+                Err(e) => break Err(e),
+            }
+        };
+
+        let output = status.and_then(|status| {
+            stdout_reader.join().unwrap()?;
+            Ok((status, stderr_reader.join().unwrap()?))
+        });
+        match output {
+            Ok((status, _)) if status.success() => Ok(()),
+            Ok((_, stderr)) => {
+                error!(
+                    "画像変換のコマンド実行に失敗しました: {:?}",
+                    String::from_utf8_lossy(&stderr)
+                );
+                Err(AppError::InternalServerError)
+            }
+            Err(e) => {
+                error!("画像変換のコマンド実行に失敗しました: {:?}", e);
+                Err(AppError::InternalServerError)
+            }
+        }
+    })
+}
+
+fn convert_output(output: Output) -> Result<Vec<u8>, AppError> {
+    match output.status.success() {
+        true => Ok(output.stdout),
+        false => {
+            error!(
+                "画像変換のコマンド実行に失敗しました: {:?}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            Err(AppError::InternalServerError)
+        }
+    }
+}
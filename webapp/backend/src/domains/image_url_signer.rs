@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::config::ImageUrlConfig;
+use crate::utils::constant_time_eq;
+
+// 画像の URL に付ける署名。セッションを送れない CDN やメールからも、期限までは画像を取得できるようにする。
+// 署名はユーザーと期限だけを対象とし、サイズや版の指定は自由に変えてよい
+#[derive(Debug)]
+pub struct ImageUrlSigner {
+    secret: String,
+    ttl: Duration,
+}
+
+impl ImageUrlSigner {
+    // secret を設定していない場合は画像を誰でも取得できるため、署名も行わない
+    pub fn from_config(config: &ImageUrlConfig) -> Option<Self> {
+        config.secret.clone().map(|secret| ImageUrlSigner {
+            secret,
+            ttl: config.ttl,
+        })
+    }
+
+    // 期限 (UNIX 時刻の秒) と署名を返す
+    pub fn sign(&self, user_id: i32, now: DateTime<Utc>) -> (i64, String) {
+        let expires_at = now.timestamp() + self.ttl.as_secs() as i64;
+
+        (expires_at, self.signature(user_id, expires_at))
+    }
+
+    pub fn verify(
+        &self,
+        user_id: i32,
+        expires_at: i64,
+        signature: &str,
+        now: DateTime<Utc>,
+    ) -> bool {
+        expires_at > now.timestamp()
+            && constant_time_eq(
+                self.signature(user_id, expires_at).as_bytes(),
+                signature.as_bytes(),
+            )
+    }
+
+    fn signature(&self, user_id: i32, expires_at: i64) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC can take key of any size");
+        mac.update(format!("{}.{}", user_id, expires_at).as_bytes());
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
@@ -0,0 +1,132 @@
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::config::RegistrationConfig;
+use crate::errors::AppError;
+use crate::models::invite_code::InviteCode;
+use crate::models::role::Role;
+use crate::utils::generate_session_token;
+
+use super::dto::invite_code::{CreateInviteCodeRequestDto, InviteCodeCreatedDto, InviteCodeDto};
+
+const INVITE_CODE_PREFIX: &str = "inv_";
+const INVITE_CODE_MAX_USES: i32 = 10_000;
+
+pub trait InviteCodeRepository {
+    async fn create_invite_code(
+        &self,
+        code_prefix: &str,
+        code_hash: &str,
+        role: Role,
+        max_uses: i32,
+        expires_at: Option<DateTime<Utc>>,
+        created_by: i32,
+    ) -> Result<i32, AppError>;
+    async fn find_invite_codes(&self) -> Result<Vec<InviteCode>, AppError>;
+    async fn revoke_invite_code(&self, id: i32, now: DateTime<Utc>) -> Result<bool, AppError>;
+    // 有効なコードの使用回数を 1 増やし、コードの ID を返す。
+    // 失効・期限切れ・使用回数が上限に達している場合と、ロールが異なる場合は None
+    async fn redeem_invite_code(
+        &self,
+        code_hash: &str,
+        role: Role,
+        now: DateTime<Utc>,
+    ) -> Result<Option<i32>, AppError>;
+    // 登録に失敗した場合に、使用回数を元に戻す
+    async fn release_invite_code(&self, id: i32) -> Result<(), AppError>;
+}
+
+#[derive(Debug)]
+pub struct InviteCodeService<T: InviteCodeRepository + std::fmt::Debug> {
+    repository: T,
+    required_roles: Vec<Role>,
+}
+
+impl<T: InviteCodeRepository + std::fmt::Debug> InviteCodeService<T> {
+    pub fn new(repository: T, config: &RegistrationConfig) -> Self {
+        InviteCodeService {
+            repository,
+            required_roles: config.invite_required_roles.clone(),
+        }
+    }
+
+    pub async fn issue_invite_code(
+        &self,
+        request: CreateInviteCodeRequestDto,
+        created_by: i32,
+    ) -> Result<InviteCodeCreatedDto, AppError> {
+        let max_uses = request.max_uses.unwrap_or(1);
+        if !(1..=INVITE_CODE_MAX_USES).contains(&max_uses)
+            || request
+                .expires_at
+                .is_some_and(|expires_at| expires_at <= Utc::now())
+        {
+            return Err(AppError::BadRequest);
+        }
+
+        let code = format!("{}{}", INVITE_CODE_PREFIX, generate_session_token());
+        let code_prefix = &code[..INVITE_CODE_PREFIX.len() + 6];
+        let id = self
+            .repository
+            .create_invite_code(
+                code_prefix,
+                &hash_invite_code(&code),
+                request.role,
+                max_uses,
+                request.expires_at,
+                created_by,
+            )
+            .await?;
+
+        // 平文のコードを返すのは発行時のこの一度だけ
+        Ok(InviteCodeCreatedDto {
+            id,
+            code,
+            role: request.role,
+            max_uses,
+            expires_at: request.expires_at,
+        })
+    }
+
+    pub async fn get_invite_codes(&self) -> Result<Vec<InviteCodeDto>, AppError> {
+        let invite_codes = self.repository.find_invite_codes().await?;
+
+        Ok(invite_codes
+            .into_iter()
+            .map(InviteCodeDto::from_entity)
+            .collect())
+    }
+
+    pub async fn revoke_invite_code(&self, id: i32) -> Result<(), AppError> {
+        match self.repository.revoke_invite_code(id, Utc::now()).await? {
+            true => Ok(()),
+            false => Err(AppError::NotFound),
+        }
+    }
+
+    // 招待コードが必要なロールの場合はコードを 1 回分使う。
+    // 登録に失敗した場合に release で戻せるよう、使ったコードの ID を返す
+    pub async fn redeem(&self, role: Role, code: Option<&str>) -> Result<Option<i32>, AppError> {
+        if !self.required_roles.contains(&role) {
+            return Ok(None);
+        }
+        let code = code.ok_or(AppError::Forbidden)?;
+
+        match self
+            .repository
+            .redeem_invite_code(&hash_invite_code(code), role, Utc::now())
+            .await?
+        {
+            Some(id) => Ok(Some(id)),
+            None => Err(AppError::Forbidden),
+        }
+    }
+
+    pub async fn release(&self, id: i32) -> Result<(), AppError> {
+        self.repository.release_invite_code(id).await
+    }
+}
+
+fn hash_invite_code(code: &str) -> String {
+    format!("{:x}", Sha256::digest(code.as_bytes()))
+}
@@ -0,0 +1,190 @@
+// 招待コードの発行から使用までの扱いを、メモリに保持するリポジトリで確認する
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+
+use super::dto::invite_code::CreateInviteCodeRequestDto;
+use super::invite_code_service::{InviteCodeRepository, InviteCodeService};
+use crate::config::RegistrationConfig;
+use crate::errors::AppError;
+use crate::models::invite_code::InviteCode;
+use crate::models::role::Role;
+
+// invite_codes テーブルと同じ条件で使用回数を数える。コードのハッシュと行の組を保持する
+#[derive(Debug, Default)]
+struct MemoryInviteCodeRepository {
+    invite_codes: Mutex<Vec<(String, InviteCode)>>,
+}
+
+impl InviteCodeRepository for MemoryInviteCodeRepository {
+    async fn create_invite_code(
+        &self,
+        code_prefix: &str,
+        code_hash: &str,
+        role: Role,
+        max_uses: i32,
+        expires_at: Option<DateTime<Utc>>,
+        created_by: i32,
+    ) -> Result<i32, AppError> {
+        let mut invite_codes = self.invite_codes.lock().unwrap();
+        let id = invite_codes.len() as i32 + 1;
+        invite_codes.push((
+            code_hash.to_string(),
+            InviteCode {
+                id,
+                code_prefix: code_prefix.to_string(),
+                role,
+                max_uses,
+                use_count: 0,
+                expires_at,
+                revoked_at: None,
+                created_by: Some(created_by),
+                created_at: Utc::now(),
+            },
+        ));
+
+        Ok(id)
+    }
+
+    async fn find_invite_codes(&self) -> Result<Vec<InviteCode>, AppError> {
+        let invite_codes = self.invite_codes.lock().unwrap();
+        Ok(invite_codes.iter().map(|(_, code)| code.clone()).collect())
+    }
+
+    async fn revoke_invite_code(&self, id: i32, now: DateTime<Utc>) -> Result<bool, AppError> {
+        let mut invite_codes = self.invite_codes.lock().unwrap();
+        match invite_codes
+            .iter_mut()
+            .find(|(_, code)| code.id == id && code.revoked_at.is_none())
+        {
+            Some((_, code)) => {
+                code.revoked_at = Some(now);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn redeem_invite_code(
+        &self,
+        code_hash: &str,
+        role: Role,
+        now: DateTime<Utc>,
+    ) -> Result<Option<i32>, AppError> {
+        let mut invite_codes = self.invite_codes.lock().unwrap();
+        let redeemable = invite_codes.iter_mut().find(|(hash, code)| {
+            hash == code_hash
+                && code.role == role
+                && code.revoked_at.is_none()
+                && code.use_count < code.max_uses
+                && code.expires_at.is_none_or(|expires_at| expires_at > now)
+        });
+
+        Ok(redeemable.map(|(_, code)| {
+            code.use_count += 1;
+            code.id
+        }))
+    }
+
+    async fn release_invite_code(&self, id: i32) -> Result<(), AppError> {
+        let mut invite_codes = self.invite_codes.lock().unwrap();
+        if let Some((_, code)) = invite_codes
+            .iter_mut()
+            .find(|(_, code)| code.id == id && code.use_count > 0)
+        {
+            code.use_count -= 1;
+        }
+
+        Ok(())
+    }
+}
+
+fn invite_code_service() -> InviteCodeService<MemoryInviteCodeRepository> {
+    InviteCodeService::new(
+        MemoryInviteCodeRepository::default(),
+        &RegistrationConfig {
+            self_registration_enabled: true,
+            invite_required_roles: vec![Role::Dispatcher, Role::Admin],
+        },
+    )
+}
+
+async fn issue(service: &InviteCodeService<MemoryInviteCodeRepository>, role: Role) -> String {
+    service
+        .issue_invite_code(
+            CreateInviteCodeRequestDto {
+                role,
+                max_uses: None,
+                expires_at: None,
+            },
+            1,
+        )
+        .await
+        .unwrap()
+        .code
+}
+
+#[actix_rt::test]
+async fn redeemed_invite_code_cannot_be_reused() {
+    let service = invite_code_service();
+    let code = issue(&service, Role::Dispatcher).await;
+
+    assert!(service
+        .redeem(Role::Dispatcher, Some(&code))
+        .await
+        .unwrap()
+        .is_some());
+
+    assert!(matches!(
+        service.redeem(Role::Dispatcher, Some(&code)).await,
+        Err(AppError::Forbidden)
+    ));
+}
+
+#[actix_rt::test]
+async fn released_invite_code_can_be_redeemed_again() {
+    let service = invite_code_service();
+    let code = issue(&service, Role::Dispatcher).await;
+    let id = service
+        .redeem(Role::Dispatcher, Some(&code))
+        .await
+        .unwrap()
+        .unwrap();
+
+    service.release(id).await.unwrap();
+
+    assert_eq!(
+        service.redeem(Role::Dispatcher, Some(&code)).await.unwrap(),
+        Some(id)
+    );
+}
+
+#[actix_rt::test]
+async fn invite_code_is_required_and_bound_to_its_role() {
+    let service = invite_code_service();
+    let code = issue(&service, Role::Dispatcher).await;
+
+    assert!(matches!(
+        service.redeem(Role::Dispatcher, None).await,
+        Err(AppError::Forbidden)
+    ));
+    assert!(matches!(
+        service.redeem(Role::Admin, Some(&code)).await,
+        Err(AppError::Forbidden)
+    ));
+    assert_eq!(service.redeem(Role::Client, None).await.unwrap(), None);
+}
+
+#[actix_rt::test]
+async fn revoked_invite_code_cannot_be_redeemed() {
+    let service = invite_code_service();
+    let code = issue(&service, Role::Admin).await;
+    let id = service.get_invite_codes().await.unwrap()[0].id;
+
+    service.revoke_invite_code(id).await.unwrap();
+
+    assert!(matches!(
+        service.redeem(Role::Admin, Some(&code)).await,
+        Err(AppError::Forbidden)
+    ));
+}
@@ -0,0 +1,105 @@
+use log::error;
+
+use super::dto::leaderboard::{LeaderboardDto, LeaderboardEntryDto};
+use super::events::DomainEvent;
+use crate::{
+    errors::AppError, infrastructure::event_bus::EventBus, infrastructure::request_id,
+    models::leaderboard::DispatcherStats,
+};
+
+const LEADERBOARD_MAX_PAGE_SIZE: i32 = 100;
+
+pub trait LeaderboardRepository {
+    // 完了した注文 1 件分をディスパッチャーの実績に加算する
+    async fn record_completion(
+        &self,
+        dispatcher_id: i32,
+        eta_error_minutes: Option<i32>,
+    ) -> Result<(), AppError>;
+    // 完了件数の多い順、同じ件数では見積もりとの差が小さい順に返す
+    async fn get_dispatcher_stats(
+        &self,
+        limit: i32,
+        offset: i32,
+    ) -> Result<Vec<DispatcherStats>, AppError>;
+    async fn count_dispatcher_stats(&self) -> Result<i64, AppError>;
+}
+
+#[derive(Debug)]
+pub struct LeaderboardService<T: LeaderboardRepository + std::fmt::Debug> {
+    repository: T,
+}
+
+impl<T> LeaderboardService<T>
+where
+    T: LeaderboardRepository + std::fmt::Debug + Clone + Send + Sync + 'static,
+{
+    // 注文の完了イベントを購読し、その都度実績を加算する。
+    // 加算は非同期に行うため、完了のレスポンスは加算の完了を待たない
+    pub fn new(repository: T, event_bus: &EventBus) -> Self {
+        let subscriber = repository.clone();
+        event_bus.subscribe(move |event| {
+            if let DomainEvent::OrderCompleted {
+                order_id,
+                dispatcher_id: Some(dispatcher_id),
+                eta_error_minutes,
+            } = *event
+            {
+                let repository = subscriber.clone();
+                request_id::spawn(async move {
+                    if let Err(err) = repository
+                        .record_completion(dispatcher_id, eta_error_minutes)
+                        .await
+                    {
+                        error!(
+                            "注文 {} の実績をディスパッチャー {} に加算できませんでした: {:?}",
+                            order_id, dispatcher_id, err
+                        );
+                    }
+                });
+            }
+        });
+
+        LeaderboardService { repository }
+    }
+}
+
+impl<T: LeaderboardRepository + std::fmt::Debug> LeaderboardService<T> {
+    pub async fn get_leaderboard(
+        &self,
+        page: i32,
+        page_size: i32,
+    ) -> Result<LeaderboardDto, AppError> {
+        if page < 0 || !(1..=LEADERBOARD_MAX_PAGE_SIZE).contains(&page_size) {
+            return Err(AppError::BadRequest);
+        }
+
+        let offset = page * page_size;
+        let stats = self
+            .repository
+            .get_dispatcher_stats(page_size, offset)
+            .await?;
+        let total = self.repository.count_dispatcher_stats().await?;
+
+        Ok(LeaderboardDto {
+            page,
+            page_size,
+            total,
+            entries: stats
+                .into_iter()
+                .enumerate()
+                .map(|(index, stats)| LeaderboardEntryDto {
+                    rank: offset as i64 + index as i64 + 1,
+                    dispatcher_id: stats.dispatcher_id,
+                    user_id: stats.user_id,
+                    username: stats.username,
+                    completed_orders: stats.completed_orders,
+                    average_eta_error_minutes: match stats.eta_samples {
+                        0 => None,
+                        samples => Some(stats.eta_error_minutes_total as f64 / samples as f64),
+                    },
+                })
+                .collect(),
+        })
+    }
+}
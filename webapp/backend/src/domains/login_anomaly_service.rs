@@ -0,0 +1,236 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use log::{error, warn};
+use serde_json::json;
+
+use super::audit_log_service::{AuditLogRepository, AuditLogService};
+use super::events::DomainEvent;
+use crate::config::LoginAnomalyConfig;
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::request_id;
+use crate::models::audit_log::NewAuditLog;
+use crate::models::login_attempt::{LoginAnomaly, LoginAttempt, NewLoginAttempt};
+use crate::models::user::SessionClient;
+
+// 過去の接続元として比べる、成功したログインの件数の上限
+const LOGIN_HISTORY_LIMIT: i32 = 100;
+
+pub trait LoginAttemptRepository {
+    async fn create_login_attempt(&self, attempt: &NewLoginAttempt) -> Result<(), AppError>;
+    // since 以降に成功したログインを新しい順に返す
+    async fn find_successful_logins(
+        &self,
+        user_id: i32,
+        since: DateTime<Utc>,
+        limit: i32,
+    ) -> Result<Vec<LoginAttempt>, AppError>;
+    async fn count_failed_logins(
+        &self,
+        user_id: i32,
+        since: DateTime<Utc>,
+    ) -> Result<i64, AppError>;
+}
+
+// ログインの試行を記録し、成功したログインを過去の接続元や直前の失敗と比べて不審なものを検知する。
+// 検知してもログインは止めず、監査ログに残して本人と管理者に通知する
+#[derive(Debug)]
+pub struct LoginAnomalyService<T, A: AuditLogRepository + std::fmt::Debug> {
+    repository: T,
+    audit_log: Arc<AuditLogService<A>>,
+    config: LoginAnomalyConfig,
+    event_bus: Arc<EventBus>,
+}
+
+impl<T, A> LoginAnomalyService<T, A>
+where
+    T: LoginAttemptRepository + std::fmt::Debug + Send + Sync + 'static,
+    A: AuditLogRepository + std::fmt::Debug + Send + Sync + 'static,
+{
+    pub fn new(
+        repository: T,
+        audit_log: Arc<AuditLogService<A>>,
+        config: &LoginAnomalyConfig,
+        event_bus: Arc<EventBus>,
+    ) -> Arc<Self> {
+        let service = Arc::new(LoginAnomalyService {
+            repository,
+            audit_log,
+            config: config.clone(),
+            event_bus: event_bus.clone(),
+        });
+        let subscriber = service.clone();
+        event_bus.subscribe(move |event| {
+            let (user_id, client, succeeded) = match event {
+                DomainEvent::LoginSucceeded { user_id, client } => (*user_id, client.clone(), true),
+                DomainEvent::LoginFailed { user_id, client } => (*user_id, client.clone(), false),
+                _ => return,
+            };
+            let service = subscriber.clone();
+            request_id::spawn(async move {
+                let result = match succeeded {
+                    true => service.inspect_login(user_id, &client).await,
+                    false => {
+                        service
+                            .record_attempt(user_id, &client, false, Utc::now())
+                            .await
+                    }
+                };
+                if let Err(err) = result {
+                    error!(
+                        "ユーザー {} のログインの試行を記録できませんでした: {:?}",
+                        user_id, err
+                    );
+                }
+            });
+        });
+
+        service
+    }
+
+    async fn inspect_login(&self, user_id: i32, client: &SessionClient) -> Result<(), AppError> {
+        let now = Utc::now();
+        let history = self
+            .repository
+            .find_successful_logins(
+                user_id,
+                before(now, self.config.history),
+                LOGIN_HISTORY_LIMIT,
+            )
+            .await?;
+        // 直前の成功より前の失敗は、その成功の時点で判定済みのため数えない
+        let failures_since = match history.first() {
+            Some(last) => last
+                .created_at
+                .max(before(now, self.config.failure_burst_window)),
+            None => before(now, self.config.failure_burst_window),
+        };
+        let failures = match self.config.failure_burst_threshold {
+            0 => 0,
+            _ => {
+                self.repository
+                    .count_failed_logins(user_id, failures_since)
+                    .await?
+            }
+        };
+        let area_id = self.area_of(client);
+
+        let anomalies = self.detect(&history, client, area_id, failures, now);
+        self.record_attempt(user_id, client, true, now).await?;
+        if anomalies.is_empty() {
+            return Ok(());
+        }
+
+        warn!(
+            "ユーザー {} の不審なログインを検知しました (接続元 {:?}): {:?}",
+            user_id, client.ip, anomalies
+        );
+        self.audit_log
+            .record(NewAuditLog {
+                actor_user_id: Some(user_id),
+                action: "login.suspicious",
+                target_type: Some("user"),
+                target_id: Some(user_id.to_string()),
+                detail: Some(json!({
+                    "anomalies": anomalies.iter().map(LoginAnomaly::code).collect::<Vec<_>>(),
+                    "client_ip": client.ip,
+                    "user_agent": client.user_agent,
+                    "area_id": area_id,
+                    "failures": failures,
+                })),
+            })
+            .await?;
+        self.event_bus
+            .publish(DomainEvent::SuspiciousLoginDetected {
+                user_id,
+                client_ip: client.ip.clone(),
+                anomalies,
+            });
+
+        Ok(())
+    }
+
+    // 初めてログインしたユーザーは比べる接続元がないため、失敗の回数だけで判断する
+    fn detect(
+        &self,
+        history: &[LoginAttempt],
+        client: &SessionClient,
+        area_id: Option<i32>,
+        failures: i64,
+        now: DateTime<Utc>,
+    ) -> Vec<LoginAnomaly> {
+        let mut anomalies = Vec::new();
+
+        if !history.is_empty() {
+            if client.ip.is_some() && history.iter().all(|attempt| attempt.client_ip != client.ip) {
+                anomalies.push(LoginAnomaly::NewIp);
+            }
+            if client.user_agent.is_some()
+                && history
+                    .iter()
+                    .all(|attempt| attempt.user_agent != client.user_agent)
+            {
+                anomalies.push(LoginAnomaly::NewDevice);
+            }
+        }
+
+        let window_start = before(now, self.config.impossible_travel_window);
+        let last_located = history
+            .iter()
+            .take_while(|attempt| attempt.created_at > window_start)
+            .find_map(|attempt| attempt.area_id);
+        if let (Some(from_area_id), Some(to_area_id)) = (last_located, area_id) {
+            if from_area_id != to_area_id {
+                anomalies.push(LoginAnomaly::ImpossibleTravel {
+                    from_area_id,
+                    to_area_id,
+                });
+            }
+        }
+
+        if self.config.failure_burst_threshold > 0
+            && failures >= self.config.failure_burst_threshold
+        {
+            anomalies.push(LoginAnomaly::FailureBurst { failures });
+        }
+
+        anomalies
+    }
+
+    async fn record_attempt(
+        &self,
+        user_id: i32,
+        client: &SessionClient,
+        succeeded: bool,
+        now: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        self.repository
+            .create_login_attempt(&NewLoginAttempt {
+                user_id,
+                succeeded,
+                client_ip: client.ip.clone(),
+                user_agent: client.user_agent.clone(),
+                area_id: self.area_of(client),
+                created_at: now,
+            })
+            .await
+    }
+
+    fn area_of(&self, client: &SessionClient) -> Option<i32> {
+        let ip: IpAddr = client.ip.as_deref()?.parse().ok()?;
+        self.config
+            .ip_areas
+            .iter()
+            .find(|rule| rule.contains(ip))
+            .map(|rule| rule.area_id)
+    }
+}
+
+fn before(now: DateTime<Utc>, duration: std::time::Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|duration| now.checked_sub_signed(duration))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
@@ -1,39 +1,238 @@
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
 use crate::{
     errors::AppError,
-    models::graph::{Edge, Node},
+    infrastructure::event_bus::EventBus,
+    models::{
+        area::{Area, AreaBoundaryRow},
+        graph::{Edge, Node},
+        organization::OrganizationScope,
+    },
 };
 
+use super::dto::map::{AreaDto, TravelTimeDto, TravelTimeMatrixDto, TravelTimeRequestDto};
+use super::events::DomainEvent;
+use super::order_service::QUOTE_DISTANCE_PER_MINUTE;
+use super::route_planner::RoutePlanner;
+
+// エリアとそのノードは組織ごとに分かれている。
+// エリアの一覧と境界はすべての組織の分をまとめてキャッシュするため、organization_id を取らずに全件を返す
 pub trait MapRepository {
     async fn get_all_nodes(&self, area_id: Option<i32>) -> Result<Vec<Node>, sqlx::Error>;
     async fn get_all_edges(&self, area_id: Option<i32>) -> Result<Vec<Edge>, sqlx::Error>;
+    async fn get_all_areas(&self) -> Result<Vec<Area>, sqlx::Error>;
     async fn get_area_id_by_node_id(&self, node_id: i32) -> Result<i32, sqlx::Error>;
+    // 組織のエリアのノードであれば、そのエリア ID
+    async fn find_node_area_id(
+        &self,
+        organization_id: i32,
+        node_id: i32,
+    ) -> Result<Option<i32>, sqlx::Error>;
+    // 組織のエリアで座標に一致するノードの (ノード ID, エリア ID)
+    async fn find_node_by_coordinate(
+        &self,
+        organization_id: i32,
+        x: i32,
+        y: i32,
+    ) -> Result<Option<(i32, i32)>, sqlx::Error>;
+    // 組織のエリアの道路だけを更新する
     async fn update_edge(
         &self,
+        organization_id: i32,
         node_a_id: i32,
         node_b_id: i32,
         weight: i32,
     ) -> Result<(), sqlx::Error>;
+    async fn find_node_by_id(&self, node_id: i32) -> Result<Option<Node>, sqlx::Error>;
+    // 境界が登録されているエリアだけを返す
+    async fn get_area_boundaries(&self) -> Result<Vec<AreaBoundaryRow>, sqlx::Error>;
+    async fn update_area_boundary(
+        &self,
+        organization_id: i32,
+        area_id: i32,
+        boundary: Option<&str>,
+    ) -> Result<(), sqlx::Error>;
 }
 
+// エリアはフィクスチャの投入時以外に変わらないため、読み込んだ時刻とあわせて保持する
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AreaList {
+    pub areas: Vec<AreaDto>,
+    pub loaded_at: DateTime<Utc>,
+}
+
+// 境界の頂点の数の上限
+const AREA_BOUNDARY_MAX_POINTS: usize = 1000;
+// 1 回の要求で指定できる目的地の数の上限
+const TRAVEL_TIME_MAX_DESTINATIONS: usize = 200;
+
 #[derive(Debug)]
 pub struct MapService<T: MapRepository + std::fmt::Debug> {
     repository: T,
+    areas: Arc<RwLock<Option<AreaList>>>,
+    route_planner: Arc<RoutePlanner<T>>,
+    event_bus: Arc<EventBus>,
 }
 
 impl<T: MapRepository + std::fmt::Debug> MapService<T> {
-    pub fn new(repository: T) -> Self {
-        MapService { repository }
+    pub fn new(
+        repository: T,
+        route_planner: Arc<RoutePlanner<T>>,
+        event_bus: Arc<EventBus>,
+    ) -> Self {
+        let areas: Arc<RwLock<Option<AreaList>>> = Arc::new(RwLock::new(None));
+        let subscriber = areas.clone();
+        event_bus.subscribe(move |event| {
+            if let DomainEvent::DataReset = event {
+                *subscriber.write().unwrap() = None;
+            }
+        });
+
+        MapService {
+            repository,
+            areas,
+            route_planner,
+            event_bus,
+        }
+    }
+
+    pub fn cached_areas(&self) -> Option<AreaList> {
+        self.areas.read().unwrap().clone()
+    }
+
+    // まだ読み込んでいない場合だけ戻す
+    pub fn restore_areas(&self, areas: AreaList) {
+        self.areas.write().unwrap().get_or_insert(areas);
+    }
+
+    pub async fn get_areas(&self) -> Result<AreaList, AppError> {
+        if let Some(areas) = self.areas.read().unwrap().as_ref() {
+            return Ok(areas.clone());
+        }
+
+        let areas = AreaList {
+            areas: self
+                .repository
+                .get_all_areas()
+                .await?
+                .into_iter()
+                .map(|area| AreaDto {
+                    id: area.id,
+                    name: area.name,
+                    organization_id: area.organization_id,
+                })
+                .collect(),
+            loaded_at: Utc::now(),
+        };
+        *self.areas.write().unwrap() = Some(areas.clone());
+
+        Ok(areas)
+    }
+
+    // キャッシュしたすべての組織のエリアから、組織のエリアだけを返す
+    pub async fn get_organization_areas(
+        &self,
+        organization: OrganizationScope,
+    ) -> Result<AreaList, AppError> {
+        let mut areas = self.get_areas().await?;
+        areas
+            .areas
+            .retain(|area| area.organization_id == organization.organization_id);
+
+        Ok(areas)
     }
 
     pub async fn update_edge(
         &self,
+        organization: OrganizationScope,
         node_a_id: i32,
         node_b_id: i32,
         weight: i32,
     ) -> Result<(), AppError> {
         self.repository
-            .update_edge(node_a_id, node_b_id, weight)
+            .update_edge(organization.organization_id, node_a_id, node_b_id, weight)
+            .await?;
+        self.event_bus.publish(DomainEvent::EdgeUpdated {
+            node_a_id,
+            node_b_id,
+        });
+
+        Ok(())
+    }
+
+    // 配車画面で目的地ごとに経路を問い合わせる代わりに、1 回の要求で出発地からの到着予定をまとめて返す
+    pub async fn get_travel_times(
+        &self,
+        organization: OrganizationScope,
+        req: TravelTimeRequestDto,
+    ) -> Result<TravelTimeMatrixDto, AppError> {
+        if req.destination_node_ids.is_empty()
+            || req.destination_node_ids.len() > TRAVEL_TIME_MAX_DESTINATIONS
+        {
+            return Err(AppError::BadRequest);
+        }
+        let area_id = self
+            .repository
+            .find_node_area_id(organization.organization_id, req.origin_node_id)
+            .await?
+            .ok_or(AppError::BadRequest)?;
+
+        let distances = self
+            .route_planner
+            .distances_from(area_id, req.origin_node_id, &req.destination_node_ids)
+            .await?;
+        let travel_times = req
+            .destination_node_ids
+            .iter()
+            .zip(distances)
+            .map(|(&node_id, distance)| TravelTimeDto {
+                node_id,
+                distance,
+                eta_minutes: distance.map(|distance| distance / QUOTE_DISTANCE_PER_MINUTE + 1),
+            })
+            .collect();
+
+        Ok(TravelTimeMatrixDto {
+            origin_node_id: req.origin_node_id,
+            area_id,
+            travel_times,
+        })
+    }
+
+    // None を指定すると境界を削除し、そのエリアでは範囲を確認しなくなる
+    pub async fn update_area_boundary(
+        &self,
+        organization: OrganizationScope,
+        area_id: i32,
+        boundary: Option<Vec<[i32; 2]>>,
+    ) -> Result<(), AppError> {
+        if !self
+            .get_organization_areas(organization)
+            .await?
+            .areas
+            .iter()
+            .any(|area| area.id == area_id)
+        {
+            return Err(AppError::NotFound);
+        }
+        let boundary = match boundary {
+            Some(points) => {
+                if !(3..=AREA_BOUNDARY_MAX_POINTS).contains(&points.len()) {
+                    return Err(AppError::BadRequest);
+                }
+                Some(serde_json::to_string(&points).map_err(|_| AppError::InternalServerError)?)
+            }
+            None => None,
+        };
+
+        self.repository
+            .update_area_boundary(organization.organization_id, area_id, boundary.as_deref())
             .await?;
+        self.event_bus
+            .publish(DomainEvent::AreaBoundaryChanged { area_id });
 
         Ok(())
     }
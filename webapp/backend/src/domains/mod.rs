@@ -1,5 +1,47 @@
+pub mod api_key_service;
+pub mod audit_log_service;
 pub mod auth_service;
+#[cfg(test)]
+pub(crate) mod auth_service_tests;
+pub mod backup_service;
+pub mod chat_hub;
+pub mod chat_service;
+pub mod client_service;
+pub mod dispatcher_availability;
+pub mod dispatcher_service;
 pub mod dto;
+pub mod eta_service;
+pub mod events;
+pub mod export_service;
+pub mod feature_flag_service;
+pub mod fixture_service;
+pub mod geocoding_service;
+pub mod geofence;
+pub mod image_format;
+pub mod image_moderation_service;
+pub mod image_service;
+pub mod image_url_signer;
+pub mod invite_code_service;
+#[cfg(test)]
+mod invite_code_service_tests;
+pub mod leaderboard_service;
+pub mod login_anomaly_service;
 pub mod map_service;
+pub mod notification_hub;
+pub mod notification_service;
+pub mod order_note_service;
 pub mod order_service;
+pub mod organization_service;
+pub mod profile_service;
+pub mod quota_service;
+pub mod report_service;
+pub mod retention_service;
+pub mod route_planner;
+pub mod runtime_config_service;
+pub mod state_snapshot;
+pub mod stats_service;
+pub mod sync_service;
 pub mod tow_truck_service;
+pub mod user_import_service;
+pub mod vehicle_service;
+pub mod webhook_service;
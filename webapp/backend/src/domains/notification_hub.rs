@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use tokio::sync::broadcast;
+
+// 1 ユーザーあたりに溜められる未送信の通知。読み出しが追いつかない接続では古いものから捨てる
+const CHANNEL_CAPACITY: usize = 64;
+
+// WebSocket で接続中のユーザーに通知を配る。
+// 同じユーザーが複数の接続を持つ場合は、すべての接続に同じ通知を送る
+#[derive(Debug, Default)]
+pub struct NotificationHub {
+    channels: Mutex<HashMap<i32, broadcast::Sender<String>>>,
+}
+
+impl NotificationHub {
+    pub fn new() -> Self {
+        NotificationHub::default()
+    }
+
+    pub fn subscribe(&self, user_id: i32) -> broadcast::Receiver<String> {
+        let mut channels = self.channels.lock().unwrap();
+        channels
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    // 接続中の受信者がいなければ何もしない。受信者のいなくなったチャネルはここで片付ける
+    pub fn publish(&self, user_id: i32, payload: String) {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(sender) = channels.get(&user_id) {
+            if sender.send(payload).is_err() {
+                channels.remove(&user_id);
+            }
+        }
+    }
+
+    pub fn is_connected(&self, user_id: i32) -> bool {
+        let channels = self.channels.lock().unwrap();
+        channels
+            .get(&user_id)
+            .is_some_and(|sender| sender.receiver_count() > 0)
+    }
+
+    pub fn connected_users(&self) -> usize {
+        let mut channels = self.channels.lock().unwrap();
+        channels.retain(|_, sender| sender.receiver_count() > 0);
+        channels.len()
+    }
+}
@@ -0,0 +1,551 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use log::{error, warn};
+use reqwest::Client;
+
+use super::dto::notification::{
+    NotificationDeadLetterDto, NotificationDto, NotificationPreferencesDto, PushTokenRequestDto,
+    UpdateNotificationPreferencesRequestDto, WebPushKeyDto,
+};
+use super::events::DomainEvent;
+use super::notification_hub::NotificationHub;
+use crate::config::NotificationConfig;
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::mailer::{Mail, Mailer, MailerImpl};
+use crate::infrastructure::outbound_http::{ensure_public_url, outbound_client};
+use crate::infrastructure::push::{PushMessage, PushOutcome, PushSender, PushSenderImpl};
+use crate::infrastructure::request_id;
+use crate::models::notification::{
+    NewNotificationDeadLetter, NotificationDeadLetter, NotificationPreferences,
+    NotificationRecipient, PushProvider, PushToken,
+};
+
+const DEAD_LETTER_MAX_LIMIT: i32 = 1000;
+const PUSH_TOKEN_MAX_LENGTH: usize = 2048;
+
+pub trait NotificationRepository {
+    async fn find_preferences(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<NotificationPreferences>, AppError>;
+    async fn upsert_preferences(
+        &self,
+        preferences: &NotificationPreferences,
+    ) -> Result<(), AppError>;
+    // 設定を登録していないユーザーには既定値を補う
+    async fn find_recipients(
+        &self,
+        user_ids: &[i32],
+    ) -> Result<Vec<NotificationRecipient>, AppError>;
+    // 依頼者・担当ディスパッチャー・割り当てられた車両の運転手のユーザー ID
+    async fn find_order_participant_ids(&self, order_id: i32) -> Result<Vec<i32>, AppError>;
+    async fn find_administrator_ids(&self) -> Result<Vec<i32>, AppError>;
+    async fn create_dead_letter(
+        &self,
+        dead_letter: &NewNotificationDeadLetter,
+    ) -> Result<(), AppError>;
+    async fn find_dead_letters(&self, limit: i32) -> Result<Vec<NotificationDeadLetter>, AppError>;
+    async fn find_push_tokens(&self, user_ids: &[i32]) -> Result<Vec<PushToken>, AppError>;
+    // 別のユーザーが登録していたトークンは、新しく登録したユーザーのものにする
+    async fn upsert_push_token(
+        &self,
+        user_id: i32,
+        provider: PushProvider,
+        token: &str,
+    ) -> Result<(), AppError>;
+    // user_id を指定した場合は、そのユーザーが登録したトークンだけを削除する
+    async fn delete_push_token(&self, token: &str, user_id: Option<i32>) -> Result<(), AppError>;
+}
+
+#[derive(Debug)]
+pub struct NotificationService<T: NotificationRepository + std::fmt::Debug> {
+    repository: T,
+    push: Arc<PushSenderImpl>,
+}
+
+impl<T> NotificationService<T>
+where
+    T: NotificationRepository + std::fmt::Debug + Clone + Send + Sync + 'static,
+{
+    // 注文とアカウントに関するイベントを購読し、関係するユーザーの設定に従って配信する。
+    // 配信は非同期に行うため、イベントを発行したリクエストは配信の完了を待たない
+    pub fn new(
+        repository: T,
+        hub: Arc<NotificationHub>,
+        mailer: Arc<MailerImpl>,
+        push: Arc<PushSenderImpl>,
+        config: &NotificationConfig,
+        event_bus: &EventBus,
+    ) -> Self {
+        let delivery = Arc::new(NotificationDelivery {
+            repository: repository.clone(),
+            hub,
+            mailer,
+            push: push.clone(),
+            client: outbound_client(config.webhook_timeout),
+            webhook_max_attempts: config.webhook_max_attempts,
+            webhook_retry_base_delay: config.webhook_retry_base_delay,
+            push_max_attempts: config.push_max_attempts,
+            push_retry_base_delay: config.push_retry_base_delay,
+        });
+        event_bus.subscribe(move |event| {
+            if let Some((target, notification)) = build_notification(event) {
+                let delivery = delivery.clone();
+                request_id::spawn(async move {
+                    if let Err(err) = delivery.deliver(target, notification).await {
+                        error!("通知を配信できませんでした: {:?}", err);
+                    }
+                });
+            }
+        });
+
+        NotificationService { repository, push }
+    }
+}
+
+impl<T: NotificationRepository + std::fmt::Debug> NotificationService<T> {
+    pub async fn get_preferences(
+        &self,
+        user_id: i32,
+    ) -> Result<NotificationPreferencesDto, AppError> {
+        let preferences = self
+            .repository
+            .find_preferences(user_id)
+            .await?
+            .unwrap_or_else(|| NotificationPreferences::default_for(user_id));
+
+        Ok(NotificationPreferencesDto {
+            websocket: preferences.websocket,
+            email: preferences.email,
+            webhook_url: preferences.webhook_url,
+        })
+    }
+
+    pub async fn update_preferences(
+        &self,
+        user_id: i32,
+        request: UpdateNotificationPreferencesRequestDto,
+    ) -> Result<NotificationPreferencesDto, AppError> {
+        let webhook_url = match request.webhook_url.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(url) => {
+                ensure_public_url(url).await?;
+                Some(url.to_string())
+            }
+        };
+        let preferences = NotificationPreferences {
+            user_id,
+            websocket: request.websocket,
+            email: request.email,
+            webhook_url,
+        };
+        self.repository.upsert_preferences(&preferences).await?;
+
+        Ok(NotificationPreferencesDto {
+            websocket: preferences.websocket,
+            email: preferences.email,
+            webhook_url: preferences.webhook_url,
+        })
+    }
+
+    pub async fn get_dead_letters(
+        &self,
+        limit: i32,
+    ) -> Result<Vec<NotificationDeadLetterDto>, AppError> {
+        if !(1..=DEAD_LETTER_MAX_LIMIT).contains(&limit) {
+            return Err(AppError::BadRequest);
+        }
+
+        let dead_letters = self.repository.find_dead_letters(limit).await?;
+
+        Ok(dead_letters
+            .into_iter()
+            .map(|dead_letter| NotificationDeadLetterDto {
+                id: dead_letter.id,
+                user_id: dead_letter.user_id,
+                channel: dead_letter.channel,
+                target: dead_letter.target,
+                payload: dead_letter.payload,
+                error: dead_letter.error,
+                attempts: dead_letter.attempts,
+                created_at: dead_letter.created_at,
+            })
+            .collect())
+    }
+
+    // Web Push ではブラウザの購読のエンドポイントの URL をトークンとして登録する
+    pub async fn register_push_token(
+        &self,
+        user_id: i32,
+        request: PushTokenRequestDto,
+    ) -> Result<(), AppError> {
+        let provider: PushProvider = request.provider.parse()?;
+        let token = request.token.trim();
+        if !self.push.supports(provider) || token.is_empty() || token.len() > PUSH_TOKEN_MAX_LENGTH
+        {
+            return Err(AppError::BadRequest);
+        }
+        if provider == PushProvider::WebPush {
+            ensure_public_url(token).await?;
+        }
+
+        self.repository
+            .upsert_push_token(user_id, provider, token)
+            .await
+    }
+
+    // ログアウトした端末には届かないようにする。登録されていないトークンでもエラーにしない
+    pub async fn unregister_push_token(
+        &self,
+        user_id: i32,
+        request: PushTokenRequestDto,
+    ) -> Result<(), AppError> {
+        self.repository
+            .delete_push_token(request.token.trim(), Some(user_id))
+            .await
+    }
+
+    pub fn get_web_push_key(&self) -> Result<WebPushKeyDto, AppError> {
+        let public_key = self.push.web_push_public_key().ok_or(AppError::NotFound)?;
+
+        Ok(WebPushKeyDto {
+            public_key: public_key.to_string(),
+        })
+    }
+}
+
+// 通知を届ける相手。注文のイベントは配信時に関係者を引く
+enum NotificationTarget {
+    User(i32),
+    OrderParticipants(i32),
+    // 有効な管理者全員
+    Administrators,
+    // 本人と有効な管理者全員
+    UserAndAdministrators(i32),
+}
+
+#[derive(Debug)]
+struct NotificationDelivery<T> {
+    repository: T,
+    hub: Arc<NotificationHub>,
+    mailer: Arc<MailerImpl>,
+    push: Arc<PushSenderImpl>,
+    client: Client,
+    webhook_max_attempts: u32,
+    webhook_retry_base_delay: Duration,
+    push_max_attempts: u32,
+    push_retry_base_delay: Duration,
+}
+
+impl<T: NotificationRepository> NotificationDelivery<T> {
+    async fn deliver(
+        &self,
+        target: NotificationTarget,
+        notification: NotificationDto,
+    ) -> Result<(), AppError> {
+        let user_ids = match target {
+            NotificationTarget::User(user_id) => vec![user_id],
+            NotificationTarget::OrderParticipants(order_id) => {
+                self.repository.find_order_participant_ids(order_id).await?
+            }
+            NotificationTarget::Administrators => self.repository.find_administrator_ids().await?,
+            NotificationTarget::UserAndAdministrators(user_id) => {
+                let mut user_ids = self.repository.find_administrator_ids().await?;
+                if !user_ids.contains(&user_id) {
+                    user_ids.push(user_id);
+                }
+                user_ids
+            }
+        };
+        let recipients = self.repository.find_recipients(&user_ids).await?;
+        let push_tokens = self.repository.find_push_tokens(&user_ids).await?;
+        let payload =
+            serde_json::to_string(&notification).map_err(|_| AppError::InternalServerError)?;
+        let push_message = PushMessage {
+            title: notification.message.clone(),
+            payload: payload.clone(),
+        };
+
+        // 1 つのチャネルの失敗で他のチャネルへの配信を止めない
+        for recipient in recipients {
+            if recipient.websocket {
+                self.hub.publish(recipient.user_id, payload.clone());
+            }
+            // WebSocket で受け取れたユーザーの端末には重ねて送らない
+            if !(recipient.websocket && self.hub.is_connected(recipient.user_id)) {
+                for push_token in push_tokens
+                    .iter()
+                    .filter(|push_token| push_token.user_id == recipient.user_id)
+                {
+                    self.send_push(push_token, &push_message).await?;
+                }
+            }
+            if recipient.email {
+                if let Some(address) = &recipient.email_address {
+                    let mail = Mail {
+                        to: address.clone(),
+                        subject: notification.message.clone(),
+                        body: payload.clone(),
+                    };
+                    if let Err(err) = self.mailer.send(&mail).await {
+                        warn!(
+                            "ユーザー {} への通知メールを送信できませんでした: {:?}",
+                            recipient.user_id, err
+                        );
+                    }
+                }
+            }
+            if let Some(url) = &recipient.webhook_url {
+                self.send_webhook(recipient.user_id, url, &payload).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // 失敗した場合は間隔を倍にしながら再送し、上限に達したらデッドレターに記録する
+    async fn send_webhook(&self, user_id: i32, url: &str, payload: &str) -> Result<(), AppError> {
+        let mut delay = self.webhook_retry_base_delay;
+        let mut last_error = String::new();
+        for attempt in 1..=self.webhook_max_attempts {
+            // 登録した後で内部のアドレスを指すようになった送信先には送らない
+            if ensure_public_url(url).await.is_err() {
+                last_error = "destination not allowed".to_string();
+                break;
+            }
+            let result = self
+                .client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(payload.to_string())
+                .send()
+                .await;
+            match result {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => last_error = format!("status {}", response.status()),
+                Err(err) => last_error = err.to_string(),
+            }
+            if attempt < self.webhook_max_attempts {
+                actix_web::rt::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+
+        warn!(
+            "ユーザー {} の Webhook への通知を {} 回試みましたが届きませんでした: {}",
+            user_id, self.webhook_max_attempts, last_error
+        );
+        self.repository
+            .create_dead_letter(&NewNotificationDeadLetter {
+                user_id,
+                channel: "webhook",
+                target: url.to_string(),
+                payload: payload.to_string(),
+                error: last_error,
+                attempts: self.webhook_max_attempts as i32,
+            })
+            .await
+    }
+
+    // 混雑や障害による失敗は間隔を倍にしながら再送し、上限に達したらデッドレターに記録する。
+    // 使えなくなったトークンは再送せずに登録を消す
+    async fn send_push(
+        &self,
+        push_token: &PushToken,
+        message: &PushMessage,
+    ) -> Result<(), AppError> {
+        let provider: PushProvider = match push_token.provider.parse() {
+            Ok(provider) => provider,
+            Err(_) => return Ok(()),
+        };
+        let mut delay = self.push_retry_base_delay;
+        let mut last_error = String::new();
+        let mut attempts = 0;
+        while attempts < self.push_max_attempts {
+            if attempts > 0 {
+                actix_web::rt::time::sleep(delay).await;
+                delay *= 2;
+            }
+            attempts += 1;
+            match self.push.send(provider, &push_token.token, message).await {
+                PushOutcome::Delivered => return Ok(()),
+                PushOutcome::TokenExpired => {
+                    return self
+                        .repository
+                        .delete_push_token(&push_token.token, None)
+                        .await;
+                }
+                PushOutcome::Failed { error, retryable } => {
+                    last_error = error;
+                    if !retryable {
+                        break;
+                    }
+                }
+            }
+        }
+
+        warn!(
+            "ユーザー {} の端末への通知を {} 回試みましたが届きませんでした: {}",
+            push_token.user_id, attempts, last_error
+        );
+        self.repository
+            .create_dead_letter(&NewNotificationDeadLetter {
+                user_id: push_token.user_id,
+                channel: provider.as_str(),
+                target: push_token.token.clone(),
+                payload: message.payload.clone(),
+                error: last_error,
+                attempts: attempts as i32,
+            })
+            .await
+    }
+}
+
+fn build_notification(event: &DomainEvent) -> Option<(NotificationTarget, NotificationDto)> {
+    let (target, event_name, order_id, message) = match event {
+        DomainEvent::OrderCreated { order_id, .. } => (
+            NotificationTarget::OrderParticipants(*order_id),
+            "order_created",
+            Some(*order_id),
+            format!("注文 {} を受け付けました", order_id),
+        ),
+        DomainEvent::OrderDispatched { order_id, .. } => (
+            NotificationTarget::OrderParticipants(*order_id),
+            "order_dispatched",
+            Some(*order_id),
+            format!("注文 {} にレッカー車を手配しました", order_id),
+        ),
+        DomainEvent::OrderCancelled {
+            order_id, reason, ..
+        } => (
+            NotificationTarget::OrderParticipants(*order_id),
+            "order_cancelled",
+            Some(*order_id),
+            format!("注文 {} はキャンセルされました ({})", order_id, reason),
+        ),
+        DomainEvent::OrderCompleted { order_id, .. } => (
+            NotificationTarget::OrderParticipants(*order_id),
+            "order_completed",
+            Some(*order_id),
+            format!("注文 {} が完了しました", order_id),
+        ),
+        DomainEvent::OrderReassigned { order_id, .. } => (
+            NotificationTarget::OrderParticipants(*order_id),
+            "order_reassigned",
+            Some(*order_id),
+            format!("注文 {} の担当ディスパッチャーが変わりました", order_id),
+        ),
+        // 未割り当てに戻った注文の関係者は依頼者だけになる
+        DomainEvent::OrderRequeued { order_id, .. } => (
+            NotificationTarget::OrderParticipants(*order_id),
+            "order_requeued",
+            Some(*order_id),
+            format!("注文 {} は手配をやり直しています", order_id),
+        ),
+        DomainEvent::OrderEtaChanged {
+            order_id,
+            eta_minutes,
+            ..
+        } => (
+            NotificationTarget::OrderParticipants(*order_id),
+            "order_eta_changed",
+            Some(*order_id),
+            format!(
+                "注文 {} の到着予定が約 {} 分後に変わりました",
+                order_id, eta_minutes
+            ),
+        ),
+        DomainEvent::PasswordChanged { user_id } => (
+            NotificationTarget::User(*user_id),
+            "password_changed",
+            None,
+            "パスワードが変更されました".to_string(),
+        ),
+        DomainEvent::UserRoleChanged { user_id } => (
+            NotificationTarget::User(*user_id),
+            "user_role_changed",
+            None,
+            "アカウントの権限が変更されました".to_string(),
+        ),
+        DomainEvent::UserOrganizationChanged { user_id, .. } => (
+            NotificationTarget::User(*user_id),
+            "user_organization_changed",
+            None,
+            "アカウントの所属する組織が変更されました".to_string(),
+        ),
+        DomainEvent::UserDeactivated { user_id } => (
+            NotificationTarget::User(*user_id),
+            "user_deactivated",
+            None,
+            "アカウントが無効化されました".to_string(),
+        ),
+        DomainEvent::ProfileImageFlagged {
+            user_id,
+            image_version,
+            reason,
+        } => (
+            NotificationTarget::Administrators,
+            "profile_image_flagged",
+            None,
+            format!(
+                "ユーザー {} のプロフィール画像 (版 {}) の確認が必要です ({})",
+                user_id, image_version, reason
+            ),
+        ),
+        DomainEvent::ProfileImageReverted {
+            user_id,
+            image_version,
+            reason,
+        } => (
+            NotificationTarget::Administrators,
+            "profile_image_reverted",
+            None,
+            format!(
+                "ユーザー {} のプロフィール画像を元に戻しました (版 {}、{})",
+                user_id, image_version, reason
+            ),
+        ),
+        DomainEvent::SuspiciousLoginDetected {
+            user_id,
+            client_ip,
+            anomalies,
+        } => (
+            NotificationTarget::UserAndAdministrators(*user_id),
+            "suspicious_login",
+            None,
+            format!(
+                "ユーザー {} に不審なログインがありました (接続元 {}: {})",
+                user_id,
+                client_ip.as_deref().unwrap_or("不明"),
+                anomalies
+                    .iter()
+                    .map(|anomaly| anomaly.describe())
+                    .collect::<Vec<_>>()
+                    .join("、")
+            ),
+        ),
+        DomainEvent::DataReset
+        | DomainEvent::DispatcherAvailabilityChanged { .. }
+        | DomainEvent::DispatcherTransferred { .. }
+        | DomainEvent::EdgeUpdated { .. }
+        | DomainEvent::AreaBoundaryChanged { .. }
+        | DomainEvent::ConfigChanged { .. }
+        | DomainEvent::ProfileImageUploaded { .. }
+        | DomainEvent::LoginSucceeded { .. }
+        | DomainEvent::LoginFailed { .. } => return None,
+    };
+
+    Some((
+        target,
+        NotificationDto {
+            event: event_name,
+            order_id,
+            message,
+            request_id: request_id::current(),
+            created_at: Utc::now(),
+        },
+    ))
+}
@@ -0,0 +1,234 @@
+use crate::errors::AppError;
+use crate::models::order::{OrderAttachment, OrderNote};
+use crate::models::organization::OrganizationScope;
+use crate::models::role::Permission;
+use crate::utils::generate_session_token;
+
+use super::auth_service::AuthRepository;
+use super::dto::order_note::{CreateOrderNoteRequestDto, OrderAttachmentDto, OrderNoteDto};
+use super::image_format::ImageFormat;
+use super::image_service::ImageStore;
+
+const MAX_NOTE_LENGTH: usize = 2000;
+const MAX_ATTACHMENTS_PER_ORDER: i64 = 10;
+// 乗車地の写真を想定し、ブラウザでそのまま表示できる形式だけを受け付ける
+const ATTACHMENT_FORMATS: [ImageFormat; 3] =
+    [ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::Webp];
+
+// メモと添付ファイルは、organization_id の組織の注文に対してだけ読み書きする
+pub trait OrderNoteRepository {
+    // 注文の利用者・担当のディスパッチャー・運転手のユーザー ID。注文が組織にない場合は None
+    async fn find_order_participant_ids(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<Option<Vec<i32>>, AppError>;
+    async fn create_note(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        author_id: i32,
+        body: &str,
+    ) -> Result<OrderNote, AppError>;
+    async fn find_notes(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<Vec<OrderNote>, AppError>;
+    async fn count_attachments(&self, organization_id: i32, order_id: i32)
+        -> Result<i64, AppError>;
+    async fn create_attachment(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        uploaded_by: i32,
+        name: &str,
+        content_type: &str,
+        bytes: i64,
+    ) -> Result<OrderAttachment, AppError>;
+    async fn find_attachments(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<Vec<OrderAttachment>, AppError>;
+    async fn find_attachment(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        attachment_id: i32,
+    ) -> Result<Option<OrderAttachment>, AppError>;
+}
+
+// 注文のメモと添付ファイル。注文の関係者と、ディスパッチャー・管理者だけが読み書きできる
+#[derive(Debug)]
+pub struct OrderNoteService<
+    T: OrderNoteRepository + std::fmt::Debug,
+    U: AuthRepository + std::fmt::Debug,
+    V: ImageStore + std::fmt::Debug,
+> {
+    repository: T,
+    auth_repository: U,
+    image_store: V,
+}
+
+impl<
+        T: OrderNoteRepository + std::fmt::Debug,
+        U: AuthRepository + std::fmt::Debug,
+        V: ImageStore + std::fmt::Debug,
+    > OrderNoteService<T, U, V>
+{
+    pub fn new(repository: T, auth_repository: U, image_store: V) -> Self {
+        OrderNoteService {
+            repository,
+            auth_repository,
+            image_store,
+        }
+    }
+
+    pub async fn get_notes(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        requested_by: i32,
+    ) -> Result<Vec<OrderNoteDto>, AppError> {
+        self.check_access(organization, order_id, requested_by)
+            .await?;
+        let notes = self
+            .repository
+            .find_notes(organization.organization_id, order_id)
+            .await?;
+
+        Ok(notes.into_iter().map(OrderNoteDto::from_entity).collect())
+    }
+
+    pub async fn add_note(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        requested_by: i32,
+        req: CreateOrderNoteRequestDto,
+    ) -> Result<OrderNoteDto, AppError> {
+        let body = req.body.trim();
+        if body.is_empty() || body.chars().count() > MAX_NOTE_LENGTH {
+            return Err(AppError::BadRequest);
+        }
+        self.check_access(organization, order_id, requested_by)
+            .await?;
+        let note = self
+            .repository
+            .create_note(organization.organization_id, order_id, requested_by, body)
+            .await?;
+
+        Ok(OrderNoteDto::from_entity(note))
+    }
+
+    pub async fn get_attachments(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        requested_by: i32,
+    ) -> Result<Vec<OrderAttachmentDto>, AppError> {
+        self.check_access(organization, order_id, requested_by)
+            .await?;
+        let attachments = self
+            .repository
+            .find_attachments(organization.organization_id, order_id)
+            .await?;
+
+        Ok(attachments
+            .into_iter()
+            .map(OrderAttachmentDto::from_entity)
+            .collect())
+    }
+
+    // 形式は Content-Type ではなく先頭のバイト列で判定する。大きさの上限はリクエストの本文の上限で確認済み
+    pub async fn add_attachment(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        requested_by: i32,
+        bytes: &[u8],
+    ) -> Result<OrderAttachmentDto, AppError> {
+        let format = match ImageFormat::sniff(bytes) {
+            Some(format) if ATTACHMENT_FORMATS.contains(&format) => format,
+            _ => return Err(AppError::BadRequest),
+        };
+        self.check_access(organization, order_id, requested_by)
+            .await?;
+        if self
+            .repository
+            .count_attachments(organization.organization_id, order_id)
+            .await?
+            >= MAX_ATTACHMENTS_PER_ORDER
+        {
+            return Err(AppError::Conflict);
+        }
+
+        let name = format!(
+            "order_{}_{}.{}",
+            order_id,
+            generate_session_token(),
+            format.extension()
+        );
+        self.image_store
+            .put(&name, bytes, format.content_type())
+            .await?;
+        let attachment = self
+            .repository
+            .create_attachment(
+                organization.organization_id,
+                order_id,
+                requested_by,
+                &name,
+                format.content_type(),
+                bytes.len() as i64,
+            )
+            .await?;
+
+        Ok(OrderAttachmentDto::from_entity(attachment))
+    }
+
+    // 添付ファイルの Content-Type と中身を返す
+    pub async fn get_attachment_content(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        attachment_id: i32,
+        requested_by: i32,
+    ) -> Result<(String, Vec<u8>), AppError> {
+        self.check_access(organization, order_id, requested_by)
+            .await?;
+        let attachment = self
+            .repository
+            .find_attachment(organization.organization_id, order_id, attachment_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let bytes = self
+            .image_store
+            .get(&attachment.name)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        Ok((attachment.content_type, bytes))
+    }
+
+    async fn check_access(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        requested_by: i32,
+    ) -> Result<(), AppError> {
+        let participant_ids = self
+            .repository
+            .find_order_participant_ids(organization.organization_id, order_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if participant_ids.contains(&requested_by) {
+            return Ok(());
+        }
+        match self.auth_repository.find_user_by_id(requested_by).await? {
+            Some(user) if user.role.has_permission(Permission::DispatchOrders) => Ok(()),
+            _ => Err(AppError::Forbidden),
+        }
+    }
+}
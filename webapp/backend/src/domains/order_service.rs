@@ -1,16 +1,82 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
 use chrono::{DateTime, Utc};
+use futures_util::try_join;
 
 use super::{
-    auth_service::AuthRepository, dto::order::OrderDto, map_service::MapRepository,
+    auth_service::AuthRepository,
+    dto::order::{
+        CoordinateDto, CreateScheduledOrderRequestDto, DashboardOrderDto, DispatcherDashboardDto,
+        OnDutyDispatcherDto, OrderDto, OrderQuoteDto, OrderSearchQueryDto, ScheduledOrderDto,
+        UpdateScheduledOrderRequestDto,
+    },
+    events::DomainEvent,
+    geofence::Geofence,
+    map_service::MapRepository,
+    quota_service::{QuotaRepository, QuotaService},
     tow_truck_service::TowTruckRepository,
 };
-use crate::{errors::AppError, models::order::Order};
+use crate::{
+    config::OrderQueueConfig,
+    errors::AppError,
+    infrastructure::event_bus::EventBus,
+    models::{
+        graph::Graph,
+        order::{
+            NewOrder, Order, OrderCompletion, OrderHandover, OrderPriority, OrderSearchCriteria,
+            OrderStatus, ScheduledOrder,
+        },
+        organization::OrganizationScope,
+        role::Permission,
+        vehicle::VehicleStatus,
+    },
+};
+
+// 見積もりは搬送距離に比例させ、到着時間は最寄りの空き車両からの距離で見積もる
+const QUOTE_BASE_FARE: i64 = 5000;
+const QUOTE_FARE_PER_DISTANCE: i64 = 10;
+pub const QUOTE_DISTANCE_PER_MINUTE: i32 = 100;
+pub const UNREACHABLE_DISTANCE: i32 = 10_000_000;
+const MAX_CANCEL_REASON_LENGTH: usize = 255;
+const DASHBOARD_PENDING_ORDER_LIMIT: i32 = 50;
+const DASHBOARD_RECENT_COMPLETION_LIMIT: i32 = 10;
+// ngram の全文インデックスは 2 文字単位のため、1 文字の検索語では一致しない
+const SEARCH_TERM_MIN_LENGTH: usize = 2;
+const SEARCH_TERM_MAX_LENGTH: usize = 100;
+const SEARCH_MAX_PAGE_SIZE: i32 = 100;
+
+// 乗車地から搬送先までの距離と料金の見積もり。distances は乗車地から各ノードまでの距離
+struct RouteQuote {
+    pickup_node_id: i32,
+    dropoff_node_id: i32,
+    area_id: i32,
+    distance: i32,
+    price: i32,
+    distances: HashMap<i32, i32>,
+}
 
+// 注文はすべて組織ごとに分かれており、organization_id の組織の注文だけを読み書きする
 pub trait OrderRepository {
-    async fn find_order_by_id(&self, id: i32) -> Result<Order, AppError>;
-    async fn update_order_status(&self, order_id: i32, status: &str) -> Result<(), AppError>;
+    async fn find_order_by_id(&self, organization_id: i32, id: i32) -> Result<Order, AppError>;
+    async fn update_order_status(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        status: &str,
+    ) -> Result<(), AppError>;
+    // 未完了の注文を完了にした場合のみ、その注文を返す
+    async fn complete_order(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        completed_time: DateTime<Utc>,
+    ) -> Result<Option<OrderCompletion>, AppError>;
+    #[allow(clippy::too_many_arguments)]
     async fn get_paginated_orders(
         &self,
+        organization_id: i32,
         page: i32,
         page_size: i32,
         sort_by: Option<String>,
@@ -18,24 +84,125 @@ pub trait OrderRepository {
         status: Option<String>,
         area: Option<i32>,
     ) -> Result<Vec<Order>, AppError>;
+    async fn search_orders(&self, criteria: &OrderSearchCriteria) -> Result<Vec<Order>, AppError>;
+    async fn find_orders_by_dispatcher_id(
+        &self,
+        organization_id: i32,
+        dispatcher_id: i32,
+        status: &str,
+    ) -> Result<Vec<Order>, AppError>;
+    async fn find_recently_completed_orders(
+        &self,
+        organization_id: i32,
+        area_id: i32,
+        limit: i32,
+    ) -> Result<Vec<Order>, AppError>;
     async fn create_order(
         &self,
+        organization_id: i32,
         customer_id: i32,
         node_id: i32,
         car_value: f64,
     ) -> Result<(), AppError>;
-    async fn update_order_dispatched(
+    async fn create_orders(&self, orders: &[NewOrder]) -> Result<(), AppError>;
+    #[allow(clippy::too_many_arguments)]
+    async fn create_quoted_order(
         &self,
-        id: i32,
+        organization_id: i32,
+        client_id: i32,
+        node_id: i32,
+        dropoff_node_id: i32,
+        car_value: f64,
+        quoted_price: i32,
+        quoted_eta_minutes: Option<i32>,
+    ) -> Result<i32, AppError>;
+    // 未割り当てのまま更新できた場合のみ true を返す。ディスパッチャーと車両も同じ組織のものでなければならない
+    #[allow(clippy::too_many_arguments)]
+    async fn dispatch_order(
+        &self,
+        organization_id: i32,
+        order_id: i32,
         dispatcher_id: i32,
         tow_truck_id: i32,
-    ) -> Result<(), AppError>;
-    async fn create_completed_order(
+        order_time: DateTime<Utc>,
+        dispatched_at: DateTime<Utc>,
+        expected_version: Option<i32>,
+    ) -> Result<bool, AppError>;
+    // キャンセルできる状態のまま更新できた場合のみ true を返す。割り当て済みの車両も同じトランザクションで解放する
+    async fn cancel_order(
         &self,
+        organization_id: i32,
         order_id: i32,
-        tow_truck_id: i32,
-        completed_time: DateTime<Utc>,
-    ) -> Result<(), AppError>;
+        tow_truck_id: Option<i32>,
+        reason: &str,
+        cancelled_at: DateTime<Utc>,
+    ) -> Result<bool, AppError>;
+    // 組織のエリアごとの未割り当ての注文の数。注文のないエリアも 0 件として含む
+    async fn count_pending_orders_by_area(
+        &self,
+        organization_id: i32,
+    ) -> Result<Vec<(i32, i64)>, AppError>;
+    // 同じエリアの未割り当ての注文のうち、処理する順でこの注文より前にあるものの数
+    async fn count_pending_orders_ahead(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<i64, AppError>;
+    // 未割り当てのまま更新できた場合のみ true を返す。処理する順は注文日時を advance_secs 秒繰り上げた時刻になる
+    async fn update_order_priority(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        priority: OrderPriority,
+        advance_secs: i64,
+        expected_version: Option<i32>,
+    ) -> Result<bool, AppError>;
+    #[allow(clippy::too_many_arguments)]
+    async fn create_scheduled_order(
+        &self,
+        organization_id: i32,
+        client_id: i32,
+        node_id: i32,
+        dropoff_node_id: i32,
+        car_value: f64,
+        quoted_price: i32,
+        scheduled_for: DateTime<Utc>,
+    ) -> Result<i32, AppError>;
+    // まだ有効になっていない予約注文だけを返す
+    async fn find_scheduled_order(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<Option<ScheduledOrder>, AppError>;
+    async fn find_scheduled_orders_by_client_id(
+        &self,
+        organization_id: i32,
+        client_id: i32,
+    ) -> Result<Vec<ScheduledOrder>, AppError>;
+    // 予約注文のまま、読み込んだ時点のバージョンから変わっていない場合のみ true を返す
+    async fn update_scheduled_order(
+        &self,
+        organization_id: i32,
+        order: &ScheduledOrder,
+    ) -> Result<bool, AppError>;
+    // 希望日時が until までの予約注文を、組織を問わず古い順に limit 件まで未割り当ての注文にする。
+    // 有効にした注文の ID とエリアを返す
+    async fn activate_scheduled_orders(
+        &self,
+        until: DateTime<Utc>,
+        advance_secs: i64,
+        limit: i64,
+    ) -> Result<Vec<(i32, i32)>, AppError>;
+    // 担当エリアが from_area_id のまま変更できた場合のみ、引き継いだ注文を返す。
+    // 配車済みの注文は candidate_ids に順に割り振り、候補がなければ未割り当てに戻す
+    async fn transfer_dispatcher(
+        &self,
+        organization_id: i32,
+        dispatcher_id: i32,
+        from_area_id: i32,
+        to_area_id: i32,
+        candidate_ids: &[i32],
+    ) -> Result<Option<Vec<OrderHandover>>, AppError>;
 }
 
 #[derive(Debug)]
@@ -44,11 +211,16 @@ pub struct OrderService<
     U: TowTruckRepository + std::fmt::Debug,
     V: AuthRepository + std::fmt::Debug,
     W: MapRepository + std::fmt::Debug,
+    X: QuotaRepository + std::fmt::Debug,
 > {
     order_repository: T,
     tow_truck_repository: U,
     auth_repository: V,
     map_repository: W,
+    geofence: Arc<Geofence>,
+    quotas: Arc<QuotaService<X>>,
+    queue: OrderQueueConfig,
+    event_bus: Arc<EventBus>,
 }
 
 impl<
@@ -56,30 +228,78 @@ impl<
         U: TowTruckRepository + std::fmt::Debug,
         V: AuthRepository + std::fmt::Debug,
         W: MapRepository + std::fmt::Debug,
-    > OrderService<T, U, V, W>
+        X: QuotaRepository + std::fmt::Debug,
+    > OrderService<T, U, V, W, X>
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         order_repository: T,
         tow_truck_repository: U,
         auth_repository: V,
         map_repository: W,
+        geofence: Arc<Geofence>,
+        quotas: Arc<QuotaService<X>>,
+        config: &OrderQueueConfig,
+        event_bus: Arc<EventBus>,
     ) -> Self {
         OrderService {
             order_repository,
             tow_truck_repository,
             auth_repository,
             map_repository,
+            geofence,
+            quotas,
+            queue: config.clone(),
+            event_bus,
         }
     }
 
-    pub async fn update_order_status(&self, order_id: i32, status: &str) -> Result<(), AppError> {
-        self.order_repository
-            .update_order_status(order_id, status)
-            .await
+    pub async fn update_order_status(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        status: &str,
+    ) -> Result<(), AppError> {
+        if status != OrderStatus::Completed.as_str() {
+            return self
+                .order_repository
+                .update_order_status(organization.organization_id, order_id, status)
+                .await;
+        }
+
+        // 同じ注文の完了が重複して通知されても、実績は 1 回だけ加算する
+        let completion = self
+            .order_repository
+            .complete_order(organization.organization_id, order_id, Utc::now())
+            .await?;
+        if let Some(completion) = completion {
+            // 到着時間の見積もりの精度は、受付から完了までの時間との差で測る
+            let eta_error_minutes = completion.quoted_eta_minutes.map(|eta_minutes| {
+                let actual_minutes =
+                    (completion.completed_time - completion.order_time).num_minutes();
+                (actual_minutes - eta_minutes as i64)
+                    .unsigned_abs()
+                    .min(i32::MAX as u64) as i32
+            });
+            self.event_bus.publish(DomainEvent::OrderCompleted {
+                order_id: completion.id,
+                dispatcher_id: completion.dispatcher_id,
+                eta_error_minutes,
+            });
+        }
+
+        Ok(())
     }
 
-    pub async fn get_order_by_id(&self, id: i32) -> Result<OrderDto, AppError> {
-        let order = self.order_repository.find_order_by_id(id).await?;
+    pub async fn get_order_by_id(
+        &self,
+        organization: OrganizationScope,
+        id: i32,
+    ) -> Result<OrderDto, AppError> {
+        let order = self
+            .order_repository
+            .find_order_by_id(organization.organization_id, id)
+            .await?;
 
         let client_username = self
             .auth_repository
@@ -116,7 +336,7 @@ impl<
         let tow_truck = match order.tow_truck_id {
             Some(tow_truck_id) => self
                 .tow_truck_repository
-                .find_tow_truck_by_id(tow_truck_id)
+                .find_tow_truck_by_id(organization.organization_id, tow_truck_id)
                 .await
                 .unwrap(),
             None => None,
@@ -159,11 +379,15 @@ impl<
             car_value: order.car_value,
             order_time: order.order_time,
             completed_time: order.completed_time,
+            version: order.version,
+            priority: order.priority,
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_paginated_orders(
         &self,
+        organization: OrganizationScope,
         page: i32,
         page_size: i32,
         sort_by: Option<String>,
@@ -173,9 +397,70 @@ impl<
     ) -> Result<Vec<OrderDto>, AppError> {
         let orders = self
             .order_repository
-            .get_paginated_orders(page, page_size, sort_by, sort_order, status, area)
+            .get_paginated_orders(
+                organization.organization_id,
+                page,
+                page_size,
+                sort_by,
+                sort_order,
+                status,
+                area,
+            )
             .await?;
 
+        Ok(self.build_order_dtos(organization, orders).await)
+    }
+
+    pub async fn search_orders(
+        &self,
+        organization: OrganizationScope,
+        query: OrderSearchQueryDto,
+    ) -> Result<Vec<OrderDto>, AppError> {
+        let page = query.page.unwrap_or(0);
+        let page_size = query.page_size.unwrap_or(10);
+        if page < 0 || !(1..=SEARCH_MAX_PAGE_SIZE).contains(&page_size) {
+            return Err(AppError::BadRequest);
+        }
+        if let (Some(from), Some(to)) = (query.ordered_from, query.ordered_to) {
+            if from > to {
+                return Err(AppError::BadRequest);
+            }
+        }
+        if let (Some(min), Some(max)) = (query.min_car_value, query.max_car_value) {
+            if min > max {
+                return Err(AppError::BadRequest);
+            }
+        }
+
+        let criteria = OrderSearchCriteria {
+            organization_id: organization.organization_id,
+            client_name: full_text_phrase(query.client_name.as_deref())?,
+            address: full_text_phrase(query.address.as_deref())?,
+            id_ranges: match query.id_prefix.as_deref().map(str::trim) {
+                Some(prefix) if !prefix.is_empty() => {
+                    id_prefix_ranges(prefix).ok_or(AppError::BadRequest)?
+                }
+                _ => Vec::new(),
+            },
+            status: query.status,
+            area_id: query.area,
+            ordered_from: query.ordered_from,
+            ordered_to: query.ordered_to,
+            min_car_value: query.min_car_value,
+            max_car_value: query.max_car_value,
+            limit: page_size,
+            offset: page * page_size,
+        };
+        let orders = self.order_repository.search_orders(&criteria).await?;
+
+        Ok(self.build_order_dtos(organization, orders).await)
+    }
+
+    async fn build_order_dtos(
+        &self,
+        organization: OrganizationScope,
+        orders: Vec<Order>,
+    ) -> Vec<OrderDto> {
         let mut results = Vec::new();
 
         for order in orders {
@@ -214,7 +499,7 @@ impl<
             let tow_truck = match order.tow_truck_id {
                 Some(tow_truck_id) => self
                     .tow_truck_repository
-                    .find_tow_truck_by_id(tow_truck_id)
+                    .find_tow_truck_by_id(organization.organization_id, tow_truck_id)
                     .await
                     .unwrap(),
                 None => None,
@@ -257,21 +542,93 @@ impl<
                 car_value: order.car_value,
                 order_time: order.order_time,
                 completed_time: order.completed_time,
+                version: order.version,
+                priority: order.priority,
             });
         }
 
-        Ok(results)
+        results
+    }
+
+    // ディスパッチャーの画面に必要なデータを、互いに依存しないクエリを並行に実行してまとめて返す
+    pub async fn get_dispatcher_dashboard(
+        &self,
+        organization: OrganizationScope,
+        user_id: i32,
+    ) -> Result<DispatcherDashboardDto, AppError> {
+        let dispatcher = self
+            .auth_repository
+            .find_dispatcher_by_user_id(user_id)
+            .await?
+            .ok_or(AppError::Forbidden)?;
+
+        let (pending_orders, active_assignments, on_duty_dispatchers, recent_completions) = try_join!(
+            self.order_repository.get_paginated_orders(
+                organization.organization_id,
+                0,
+                DASHBOARD_PENDING_ORDER_LIMIT,
+                Some("priority".to_string()),
+                Some("asc".to_string()),
+                Some(OrderStatus::Pending.as_str().to_string()),
+                Some(dispatcher.area_id),
+            ),
+            self.order_repository.find_orders_by_dispatcher_id(
+                organization.organization_id,
+                dispatcher.id,
+                OrderStatus::Dispatched.as_str(),
+            ),
+            self.auth_repository
+                .find_on_duty_dispatchers_by_area_id(dispatcher.area_id, Utc::now()),
+            self.order_repository.find_recently_completed_orders(
+                organization.organization_id,
+                dispatcher.area_id,
+                DASHBOARD_RECENT_COMPLETION_LIMIT,
+            ),
+        )?;
+
+        Ok(DispatcherDashboardDto {
+            dispatcher_id: dispatcher.id,
+            area_id: dispatcher.area_id,
+            pending_orders: pending_orders
+                .into_iter()
+                .map(DashboardOrderDto::from_order)
+                .collect(),
+            active_assignments: active_assignments
+                .into_iter()
+                .map(DashboardOrderDto::from_order)
+                .collect(),
+            on_duty_dispatchers: on_duty_dispatchers
+                .into_iter()
+                .filter(|colleague| colleague.dispatcher_id != dispatcher.id)
+                .map(OnDutyDispatcherDto::from_on_duty_dispatcher)
+                .collect(),
+            recent_completions: recent_completions
+                .into_iter()
+                .map(DashboardOrderDto::from_order)
+                .collect(),
+        })
     }
 
     pub async fn create_client_order(
         &self,
+        organization: OrganizationScope,
         client_id: i32,
         node_id: i32,
         car_value: f64,
     ) -> Result<(), AppError> {
+        // 他の組織のエリアのノードでは注文を受け付けない
+        if self
+            .map_repository
+            .find_node_area_id(organization.organization_id, node_id)
+            .await?
+            .is_none()
+        {
+            return Err(AppError::BadRequest);
+        }
+        self.quotas.check_order_quota(organization).await?;
         match self
             .order_repository
-            .create_order(client_id, node_id, car_value)
+            .create_order(organization.organization_id, client_id, node_id, car_value)
             .await
         {
             Ok(_) => Ok(()),
@@ -279,30 +636,494 @@ impl<
         }
     }
 
-    pub async fn create_dispatcher_order(
+    // 利用者が指定した座標から注文を作成し、料金と到着時間の見積もりを返す
+    pub async fn create_order(
         &self,
+        organization: OrganizationScope,
+        pickup: CoordinateDto,
+        dropoff: CoordinateDto,
+        client_id: i32,
+        car_value: f64,
+    ) -> Result<OrderQuoteDto, AppError> {
+        match self.auth_repository.find_user_by_id(client_id).await? {
+            Some(user) if user.role.has_permission(Permission::CreateOrders) => {}
+            _ => return Err(AppError::Forbidden),
+        }
+        if !car_value.is_finite() || car_value < 0.0 {
+            return Err(AppError::BadRequest);
+        }
+        self.quotas.check_order_quota(organization).await?;
+
+        let RouteQuote {
+            pickup_node_id,
+            dropoff_node_id,
+            area_id,
+            distance,
+            price,
+            distances,
+        } = self.quote_route(organization, pickup, dropoff).await?;
+
+        let available_tow_trucks = self
+            .tow_truck_repository
+            .get_paginated_tow_trucks(
+                organization.organization_id,
+                0,
+                -1,
+                Some(VehicleStatus::Available.as_str().to_string()),
+                Some(area_id),
+            )
+            .await?;
+        let eta_minutes = available_tow_trucks
+            .iter()
+            .filter_map(|tow_truck| distances.get(&tow_truck.node_id).copied())
+            .filter(|&distance| distance < UNREACHABLE_DISTANCE)
+            .min()
+            .map(|approach| (approach + distance) / QUOTE_DISTANCE_PER_MINUTE + 1);
+
+        let order_id = self
+            .order_repository
+            .create_quoted_order(
+                organization.organization_id,
+                client_id,
+                pickup_node_id,
+                dropoff_node_id,
+                car_value,
+                price,
+                eta_minutes,
+            )
+            .await?;
+        self.event_bus
+            .publish(DomainEvent::OrderCreated { order_id, area_id });
+
+        Ok(OrderQuoteDto {
+            order_id,
+            area_id,
+            pickup_node_id,
+            dropoff_node_id,
+            distance,
+            price,
+            eta_minutes,
+        })
+    }
+
+    // 利用者は自分の注文だけを、ディスパッチャーと管理者は組織のすべての注文をキャンセルできる
+    pub async fn cancel_order(
+        &self,
+        organization: OrganizationScope,
         order_id: i32,
-        dispatcher_id: i32,
-        tow_truck_id: i32,
-        order_time: DateTime<Utc>,
+        reason: &str,
+        requested_by: i32,
+    ) -> Result<(), AppError> {
+        let reason = reason.trim();
+        if reason.is_empty() || reason.chars().count() > MAX_CANCEL_REASON_LENGTH {
+            return Err(AppError::BadRequest);
+        }
+
+        let order = self
+            .order_repository
+            .find_order_by_id(organization.organization_id, order_id)
+            .await
+            .map_err(|e| match e {
+                AppError::SqlxError(sqlx::Error::RowNotFound) => AppError::NotFound,
+                e => e.with_context("order_service.cancel_order", Some(order_id.into())),
+            })?;
+        let can_cancel = match self.auth_repository.find_user_by_id(requested_by).await? {
+            Some(user) => {
+                user.role.has_permission(Permission::CancelAnyOrder)
+                    || order.client_id == requested_by
+            }
+            None => false,
+        };
+        if !can_cancel {
+            return Err(AppError::Forbidden);
+        }
+        if !OrderStatus::is_cancellable(&order.status) {
+            return Err(AppError::Conflict);
+        }
+
+        // 確認してから更新するまでの間に配車や完了が行われた場合は、更新されずに false が返る
+        let cancelled = self
+            .order_repository
+            .cancel_order(
+                organization.organization_id,
+                order.id,
+                order.tow_truck_id,
+                reason,
+                Utc::now(),
+            )
+            .await?;
+        if !cancelled {
+            return Err(AppError::Conflict);
+        }
+
+        self.event_bus.publish(DomainEvent::OrderCancelled {
+            order_id: order.id,
+            dispatcher_id: order.dispatcher_id,
+            tow_truck_id: order.tow_truck_id,
+            reason: reason.to_string(),
+        });
+
+        Ok(())
+    }
+
+    // 優先度はディスパッチャーと管理者だけが変更できる。
+    // 優先度の高い注文は注文日時を繰り上げた位置に並ぶため、待ち時間の長い注文には追い越される
+    pub async fn update_order_priority(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        priority: &str,
+        expected_version: Option<i32>,
+        requested_by: i32,
     ) -> Result<(), AppError> {
-        if (self
+        let priority: OrderPriority = priority.parse()?;
+        match self.auth_repository.find_user_by_id(requested_by).await? {
+            Some(user) if user.role.has_permission(Permission::DispatchOrders) => {}
+            _ => return Err(AppError::Forbidden),
+        }
+        let order = self
+            .order_repository
+            .find_order_by_id(organization.organization_id, order_id)
+            .await
+            .map_err(|e| match e {
+                AppError::SqlxError(sqlx::Error::RowNotFound) => AppError::NotFound,
+                e => e.with_context("order_service.update_order_priority", Some(order_id.into())),
+            })?;
+        if order.status != OrderStatus::Pending.as_str() {
+            return Err(AppError::Conflict);
+        }
+
+        let advance_secs = self.priority_advance_secs(priority);
+        let updated = self
+            .order_repository
+            .update_order_priority(
+                organization.organization_id,
+                order.id,
+                priority,
+                advance_secs,
+                expected_version,
+            )
+            .await?;
+        if !updated {
+            return Err(AppError::Conflict);
+        }
+
+        Ok(())
+    }
+
+    // 予約注文は見積もりだけを行い、希望日時の scheduled_activation_lead 前まで配車の対象にしない
+    pub async fn create_scheduled_order(
+        &self,
+        organization: OrganizationScope,
+        client_id: i32,
+        req: CreateScheduledOrderRequestDto,
+    ) -> Result<ScheduledOrderDto, AppError> {
+        match self.auth_repository.find_user_by_id(client_id).await? {
+            Some(user) if user.role.has_permission(Permission::CreateOrders) => {}
+            _ => return Err(AppError::Forbidden),
+        }
+        if !req.car_value.is_finite() || req.car_value < 0.0 {
+            return Err(AppError::BadRequest);
+        }
+        self.check_schedule(req.scheduled_for)?;
+        self.quotas.check_order_quota(organization).await?;
+
+        let quote = self
+            .quote_route(organization, req.pickup, req.dropoff)
+            .await?;
+        let order_id = self
+            .order_repository
+            .create_scheduled_order(
+                organization.organization_id,
+                client_id,
+                quote.pickup_node_id,
+                quote.dropoff_node_id,
+                req.car_value,
+                quote.price,
+                req.scheduled_for,
+            )
+            .await?;
+
+        self.find_scheduled_order(organization, order_id).await
+    }
+
+    pub async fn get_scheduled_orders(
+        &self,
+        organization: OrganizationScope,
+        client_id: i32,
+    ) -> Result<Vec<ScheduledOrderDto>, AppError> {
+        let orders = self
+            .order_repository
+            .find_scheduled_orders_by_client_id(organization.organization_id, client_id)
+            .await?;
+
+        orders
+            .into_iter()
+            .map(|order| {
+                let activates_at = self.activates_at(order.scheduled_for)?;
+                Ok(ScheduledOrderDto::from_scheduled_order(order, activates_at))
+            })
+            .collect()
+    }
+
+    // 予約した利用者と、ディスパッチャー・管理者が変更できる。有効になった後の注文は変更できない
+    pub async fn update_scheduled_order(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        req: UpdateScheduledOrderRequestDto,
+        requested_by: i32,
+    ) -> Result<ScheduledOrderDto, AppError> {
+        let mut order = self
+            .order_repository
+            .find_scheduled_order(organization.organization_id, order_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let can_update = match self.auth_repository.find_user_by_id(requested_by).await? {
+            Some(user) => {
+                user.role.has_permission(Permission::DispatchOrders)
+                    || order.client_id == requested_by
+            }
+            None => false,
+        };
+        if !can_update {
+            return Err(AppError::Forbidden);
+        }
+        if req.version.is_some_and(|version| version != order.version) {
+            return Err(AppError::Conflict);
+        }
+
+        if let Some(car_value) = req.car_value {
+            if !car_value.is_finite() || car_value < 0.0 {
+                return Err(AppError::BadRequest);
+            }
+            order.car_value = car_value;
+        }
+        if let Some(scheduled_for) = req.scheduled_for {
+            self.check_schedule(scheduled_for)?;
+            order.scheduled_for = scheduled_for;
+        }
+        match (req.pickup, req.dropoff) {
+            (Some(pickup), Some(dropoff)) => {
+                let quote = self.quote_route(organization, pickup, dropoff).await?;
+                order.node_id = quote.pickup_node_id;
+                order.dropoff_node_id = Some(quote.dropoff_node_id);
+                order.quoted_price = Some(quote.price);
+            }
+            (None, None) => {}
+            _ => return Err(AppError::BadRequest),
+        }
+
+        // 確認してから更新するまでの間に有効化や変更が行われた場合は、更新されずに false が返る
+        let updated = self
+            .order_repository
+            .update_scheduled_order(organization.organization_id, &order)
+            .await?;
+        if !updated {
+            return Err(AppError::Conflict);
+        }
+
+        self.find_scheduled_order(organization, order_id).await
+    }
+
+    // 定期ジョブから呼び出し、希望日時が近づいた予約注文を未割り当ての注文にする
+    pub async fn activate_scheduled_orders(&self) -> Result<usize, AppError> {
+        let until = Utc::now() + to_chrono(self.queue.scheduled_activation_lead)?;
+        let activated = self
+            .order_repository
+            .activate_scheduled_orders(
+                until,
+                self.priority_advance_secs(OrderPriority::Scheduled),
+                self.queue.scheduled_activation_batch_size,
+            )
+            .await?;
+        // 配車の対象になった時点で、通常の注文と同じく受け付けたものとして通知する
+        for &(order_id, area_id) in &activated {
+            self.event_bus
+                .publish(DomainEvent::OrderCreated { order_id, area_id });
+        }
+
+        Ok(activated.len())
+    }
+
+    async fn find_scheduled_order(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+    ) -> Result<ScheduledOrderDto, AppError> {
+        let order = self
             .order_repository
-            .create_completed_order(order_id, tow_truck_id, order_time)
-            .await)
-            .is_err()
+            .find_scheduled_order(organization.organization_id, order_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let activates_at = self.activates_at(order.scheduled_for)?;
+
+        Ok(ScheduledOrderDto::from_scheduled_order(order, activates_at))
+    }
+
+    // すぐに有効になる日時は予約ではなく通常の注文として受け付ける
+    fn check_schedule(&self, scheduled_for: DateTime<Utc>) -> Result<(), AppError> {
+        let now = Utc::now();
+        if scheduled_for <= now + to_chrono(self.queue.scheduled_activation_lead)?
+            || scheduled_for > now + to_chrono(self.queue.scheduled_max_ahead)?
         {
             return Err(AppError::BadRequest);
         }
 
-        self.order_repository
-            .update_order_dispatched(order_id, dispatcher_id, tow_truck_id)
+        Ok(())
+    }
+
+    fn activates_at(&self, scheduled_for: DateTime<Utc>) -> Result<DateTime<Utc>, AppError> {
+        Ok(scheduled_for - to_chrono(self.queue.scheduled_activation_lead)?)
+    }
+
+    // 優先度の段階ごとに、処理する順を priority_aging ずつ繰り上げる
+    fn priority_advance_secs(&self, priority: OrderPriority) -> i64 {
+        self.queue.priority_aging.as_secs() as i64 * priority.level() as i64
+    }
+
+    // 座標はいずれかのエリアのノードに一致し、乗車地と搬送先は同じエリアでなければならない
+    async fn quote_route(
+        &self,
+        organization: OrganizationScope,
+        pickup: CoordinateDto,
+        dropoff: CoordinateDto,
+    ) -> Result<RouteQuote, AppError> {
+        let (pickup_node_id, area_id) = self.resolve_coordinate(organization, pickup).await?;
+        let (dropoff_node_id, dropoff_area_id) =
+            self.resolve_coordinate(organization, dropoff).await?;
+        if area_id != dropoff_area_id {
+            return Err(AppError::BadRequest);
+        }
+
+        let mut graph = Graph::new();
+        for node in self.map_repository.get_all_nodes(Some(area_id)).await? {
+            graph.add_node(node);
+        }
+        for edge in self.map_repository.get_all_edges(Some(area_id)).await? {
+            graph.add_edge(edge);
+        }
+        let distances = graph.distances_from(pickup_node_id);
+
+        let distance = match distances.get(&dropoff_node_id) {
+            Some(&distance) if distance < UNREACHABLE_DISTANCE => distance,
+            _ => return Err(AppError::BadRequest),
+        };
+        let price = QUOTE_BASE_FARE + QUOTE_FARE_PER_DISTANCE * distance as i64;
+        let price = i32::try_from(price).map_err(|_| AppError::BadRequest)?;
+
+        Ok(RouteQuote {
+            pickup_node_id,
+            dropoff_node_id,
+            area_id,
+            distance,
+            price,
+            distances,
+        })
+    }
+
+    // サービス提供範囲外の座標は、ノードを探す前に近くのエリアを添えて拒否する
+    async fn resolve_coordinate(
+        &self,
+        organization: OrganizationScope,
+        coordinate: CoordinateDto,
+    ) -> Result<(i32, i32), AppError> {
+        self.geofence
+            .check(
+                &self.map_repository,
+                organization.organization_id,
+                coordinate.x,
+                coordinate.y,
+            )
             .await?;
+        match self
+            .map_repository
+            .find_node_by_coordinate(organization.organization_id, coordinate.x, coordinate.y)
+            .await?
+        {
+            Some(node) => Ok(node),
+            None => Err(AppError::BadRequest),
+        }
+    }
+
+    pub async fn create_dispatcher_order(
+        &self,
+        organization: OrganizationScope,
+        order_id: i32,
+        dispatcher_id: i32,
+        tow_truck_id: i32,
+        order_time: DateTime<Utc>,
+        expected_version: Option<i32>,
+    ) -> Result<(), AppError> {
+        // 同じ注文を複数のディスパッチャーが同時に割り当てようとした場合、後の要求は競合として返す。
+        // 競合した側は注文を読み込み直してから再試行できる
+        let dispatched = self
+            .order_repository
+            .dispatch_order(
+                organization.organization_id,
+                order_id,
+                dispatcher_id,
+                tow_truck_id,
+                order_time,
+                Utc::now(),
+                expected_version,
+            )
+            .await?;
+        if !dispatched {
+            return Err(AppError::Conflict);
+        }
 
         self.tow_truck_repository
-            .update_status(tow_truck_id, "busy")
+            .update_status(tow_truck_id, VehicleStatus::Busy.as_str())
             .await?;
 
+        self.event_bus.publish(DomainEvent::OrderDispatched {
+            order_id,
+            dispatcher_id,
+            tow_truck_id,
+        });
+
         Ok(())
     }
 }
+
+fn to_chrono(duration: Duration) -> Result<chrono::Duration, AppError> {
+    chrono::Duration::from_std(duration).map_err(|_| AppError::InternalServerError)
+}
+
+// 検索語を ngram の全文インデックスで部分一致させるため、BOOLEAN MODE のフレーズ検索に変換する
+fn full_text_phrase(term: Option<&str>) -> Result<Option<String>, AppError> {
+    let term = match term.map(str::trim) {
+        Some(term) if !term.is_empty() => term,
+        _ => return Ok(None),
+    };
+    let length = term.chars().count();
+    if !(SEARCH_TERM_MIN_LENGTH..=SEARCH_TERM_MAX_LENGTH).contains(&length) {
+        return Err(AppError::BadRequest);
+    }
+
+    Ok(Some(format!("\"{}\"", term.replace('"', " "))))
+}
+
+// 注文 ID の前方一致を、主キーのインデックスで引ける範囲の列に変換する。
+// 例えば "12" は 12, 120..=129, 1200..=1299, ... となる
+fn id_prefix_ranges(prefix: &str) -> Option<Vec<(i32, i32)>> {
+    if !prefix.chars().all(|c| c.is_ascii_digit()) || prefix.starts_with('0') {
+        return None;
+    }
+    let base: i64 = prefix.parse().ok()?;
+    if base > i32::MAX as i64 {
+        return None;
+    }
+
+    let mut ranges = Vec::new();
+    let mut width: i64 = 1;
+    while base * width <= i32::MAX as i64 {
+        let start = base * width;
+        let end = (start + width - 1).min(i32::MAX as i64);
+        ranges.push((start as i32, end as i32));
+        width *= 10;
+    }
+
+    Some(ranges)
+}
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::models::organization::{Organization, OrganizationScope};
+use crate::models::role::Role;
+
+use super::auth_service::AuthRepository;
+use super::dto::organization::{CreateOrganizationRequestDto, OrganizationDto};
+use super::events::DomainEvent;
+
+const ORGANIZATION_NAME_MAX_LENGTH: usize = 255;
+
+pub trait OrganizationRepository {
+    // 同じ名前の組織がすでにある場合は Conflict を返す
+    async fn create_organization(&self, name: &str) -> Result<i32, AppError>;
+    async fn find_organizations(&self) -> Result<Vec<Organization>, AppError>;
+    async fn find_organization_by_id(&self, id: i32) -> Result<Option<Organization>, AppError>;
+}
+
+// 組織の登録と、ユーザーの所属の変更。どちらも既定の組織の管理者だけが行える
+#[derive(Debug)]
+pub struct OrganizationService<
+    T: OrganizationRepository + std::fmt::Debug,
+    U: AuthRepository + std::fmt::Debug,
+> {
+    repository: T,
+    auth_repository: U,
+    event_bus: Arc<EventBus>,
+}
+
+impl<T: OrganizationRepository + std::fmt::Debug, U: AuthRepository + std::fmt::Debug>
+    OrganizationService<T, U>
+{
+    pub fn new(repository: T, auth_repository: U, event_bus: Arc<EventBus>) -> Self {
+        OrganizationService {
+            repository,
+            auth_repository,
+            event_bus,
+        }
+    }
+
+    pub async fn get_organizations(
+        &self,
+        organization: OrganizationScope,
+    ) -> Result<Vec<OrganizationDto>, AppError> {
+        require_default_organization(organization)?;
+        let organizations = self.repository.find_organizations().await?;
+
+        Ok(organizations
+            .into_iter()
+            .map(OrganizationDto::from_entity)
+            .collect())
+    }
+
+    pub async fn create_organization(
+        &self,
+        organization: OrganizationScope,
+        req: CreateOrganizationRequestDto,
+    ) -> Result<OrganizationDto, AppError> {
+        require_default_organization(organization)?;
+        let name = req.name.trim();
+        if name.is_empty() || name.chars().count() > ORGANIZATION_NAME_MAX_LENGTH {
+            return Err(AppError::BadRequest);
+        }
+
+        let id = self.repository.create_organization(name).await?;
+        let created = self
+            .repository
+            .find_organization_by_id(id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+
+        Ok(OrganizationDto::from_entity(created))
+    }
+
+    // ディスパッチャーと運転手は担当エリアや車両が元の組織のエリアに結び付いているため、
+    // 先にロールを変更しない限り移せない
+    pub async fn move_user(
+        &self,
+        organization: OrganizationScope,
+        user_id: i32,
+        organization_id: i32,
+    ) -> Result<(), AppError> {
+        require_default_organization(organization)?;
+        let user = self
+            .auth_repository
+            .find_user_by_id(user_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        if self
+            .repository
+            .find_organization_by_id(organization_id)
+            .await?
+            .is_none()
+        {
+            return Err(AppError::BadRequest);
+        }
+        if user.organization_id == organization_id {
+            return Ok(());
+        }
+        if matches!(user.role, Role::Dispatcher | Role::Driver) {
+            return Err(AppError::Conflict);
+        }
+
+        self.auth_repository
+            .update_user_organization(user.id, organization_id)
+            .await?;
+        self.event_bus
+            .publish(DomainEvent::UserOrganizationChanged {
+                user_id: user.id,
+                organization_id,
+            });
+
+        Ok(())
+    }
+}
+
+fn require_default_organization(organization: OrganizationScope) -> Result<(), AppError> {
+    match organization.is_default() {
+        true => Ok(()),
+        false => Err(AppError::Forbidden),
+    }
+}
@@ -0,0 +1,149 @@
+use super::dto::notification::NotificationPreferencesDto;
+use super::dto::profile::{ProfileDto, UpdateProfileRequestDto};
+use super::notification_service::NotificationRepository;
+use crate::errors::AppError;
+use crate::infrastructure::outbound_http::ensure_public_url;
+use crate::models::notification::NotificationPreferences;
+use crate::models::profile::UserProfile;
+use crate::utils::{is_valid_email, is_valid_phone_number, is_valid_webhook_url};
+
+const DISPLAY_NAME_MAX_LENGTH: usize = 64;
+
+pub trait ProfileRepository {
+    async fn find_profile(&self, user_id: i32) -> Result<Option<UserProfile>, AppError>;
+    async fn update_profile(&self, profile: &UserProfile) -> Result<(), AppError>;
+}
+
+// ログイン中のユーザーが自分のプロフィールと通知設定を参照・変更する。
+// ユーザー名・パスワード・権限は変更できず、それぞれ専用の API と管理者向けの API で扱う
+#[derive(Debug)]
+pub struct ProfileService<
+    T: ProfileRepository + std::fmt::Debug,
+    U: NotificationRepository + std::fmt::Debug,
+> {
+    profile_repository: T,
+    notification_repository: U,
+}
+
+impl<T: ProfileRepository + std::fmt::Debug, U: NotificationRepository + std::fmt::Debug>
+    ProfileService<T, U>
+{
+    pub fn new(profile_repository: T, notification_repository: U) -> Self {
+        ProfileService {
+            profile_repository,
+            notification_repository,
+        }
+    }
+
+    pub async fn get_profile(&self, user_id: i32) -> Result<ProfileDto, AppError> {
+        let profile = self
+            .profile_repository
+            .find_profile(user_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let preferences = self.find_preferences(user_id).await?;
+
+        Ok(ProfileDto::from_entity(
+            profile,
+            preferences_to_dto(preferences),
+        ))
+    }
+
+    // すべての項目を検証してから書き込む。1 つでも不正な値があれば何も変更しない
+    pub async fn update_profile(
+        &self,
+        user_id: i32,
+        req: UpdateProfileRequestDto,
+    ) -> Result<ProfileDto, AppError> {
+        let mut profile = self
+            .profile_repository
+            .find_profile(user_id)
+            .await?
+            .ok_or(AppError::NotFound)?;
+        let current = profile.clone();
+        if let Some(display_name) = req.display_name {
+            profile.display_name = validate_display_name(&display_name)?;
+        }
+        if let Some(email) = req.email {
+            profile.email = validate_optional(&email, is_valid_email)?;
+        }
+        if let Some(phone_number) = req.phone_number {
+            profile.phone_number = validate_optional(&phone_number, is_valid_phone_number)?;
+        }
+
+        let mut preferences = self.find_preferences(user_id).await?;
+        let current_preferences = preferences.clone();
+        if let Some(notification) = req.notification {
+            if let Some(websocket) = notification.websocket {
+                preferences.websocket = websocket;
+            }
+            if let Some(email) = notification.email {
+                preferences.email = email;
+            }
+            if let Some(webhook_url) = notification.webhook_url {
+                preferences.webhook_url = validate_optional(&webhook_url, is_valid_webhook_url)?;
+                if let Some(url) = &preferences.webhook_url {
+                    ensure_public_url(url).await?;
+                }
+            }
+        }
+        // メールで通知を受け取るには宛先が必要
+        if preferences.email && profile.email.is_none() {
+            return Err(AppError::BadRequest);
+        }
+
+        if !same_profile(&profile, &current) {
+            self.profile_repository.update_profile(&profile).await?;
+        }
+        if !same_preferences(&preferences, &current_preferences) {
+            self.notification_repository
+                .upsert_preferences(&preferences)
+                .await?;
+        }
+
+        Ok(ProfileDto::from_entity(
+            profile,
+            preferences_to_dto(preferences),
+        ))
+    }
+
+    async fn find_preferences(&self, user_id: i32) -> Result<NotificationPreferences, AppError> {
+        Ok(self
+            .notification_repository
+            .find_preferences(user_id)
+            .await?
+            .unwrap_or_else(|| NotificationPreferences::default_for(user_id)))
+    }
+}
+
+fn preferences_to_dto(preferences: NotificationPreferences) -> NotificationPreferencesDto {
+    NotificationPreferencesDto {
+        websocket: preferences.websocket,
+        email: preferences.email,
+        webhook_url: preferences.webhook_url,
+    }
+}
+
+fn same_profile(a: &UserProfile, b: &UserProfile) -> bool {
+    a.display_name == b.display_name && a.email == b.email && a.phone_number == b.phone_number
+}
+
+fn same_preferences(a: &NotificationPreferences, b: &NotificationPreferences) -> bool {
+    a.websocket == b.websocket && a.email == b.email && a.webhook_url == b.webhook_url
+}
+
+// 前後の空白を除き、空になった場合は未設定として扱う
+fn validate_optional(value: &str, is_valid: fn(&str) -> bool) -> Result<Option<String>, AppError> {
+    match value.trim() {
+        "" => Ok(None),
+        value if is_valid(value) => Ok(Some(value.to_string())),
+        _ => Err(AppError::BadRequest),
+    }
+}
+
+fn validate_display_name(display_name: &str) -> Result<Option<String>, AppError> {
+    validate_optional(display_name, |display_name| {
+        display_name.chars().count() <= DISPLAY_NAME_MAX_LENGTH
+            && !display_name.chars().any(char::is_control)
+    })
+}
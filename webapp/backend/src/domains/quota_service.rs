@@ -0,0 +1,239 @@
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveTime, Utc};
+
+use crate::config::QuotaConfig;
+use crate::errors::AppError;
+use crate::infrastructure::ttl_cache::TtlCache;
+use crate::models::organization::{OrganizationScope, QuotaLimits};
+
+use super::dto::quota::{OrganizationUsageDto, QuotaUsageDto};
+
+// 上限を変更してから、ほかのインスタンスに反映されるまでの最大の時間
+const QUOTA_LIMITS_CACHE_TTL: Duration = Duration::from_secs(60);
+const QUOTA_LIMITS_CACHE_CAPACITY: usize = 1_000;
+
+pub trait QuotaRepository {
+    // 組織に個別に設定された上限。組織が存在しない場合は None
+    async fn find_quota_limits(
+        &self,
+        organization_id: i32,
+    ) -> Result<Option<QuotaLimits>, AppError>;
+    // 組織が存在しない場合は false を返す
+    async fn update_quota_limits(
+        &self,
+        organization_id: i32,
+        limits: &QuotaLimits,
+    ) -> Result<bool, AppError>;
+    async fn count_orders_since(
+        &self,
+        organization_id: i32,
+        since: DateTime<Utc>,
+    ) -> Result<i64, AppError>;
+    async fn count_active_dispatchers(&self, organization_id: i32) -> Result<i64, AppError>;
+    // 組織のユーザーの現在のプロフィール画像の合計のバイト数
+    async fn sum_profile_image_bytes(&self, organization_id: i32) -> Result<i64, AppError>;
+    // ユーザーの現在のプロフィール画像のバイト数。アップロードしていない場合は 0
+    async fn find_profile_image_bytes(&self, user_id: i32) -> Result<i64, AppError>;
+    async fn record_profile_image(
+        &self,
+        user_id: i32,
+        name: &str,
+        bytes: i64,
+    ) -> Result<(), AppError>;
+}
+
+// 組織ごとの利用上限を確認する。確認と作成は同じトランザクションではないため、
+// 同時に作成された場合はわずかに上限を超えることがある
+#[derive(Debug)]
+pub struct QuotaService<T: QuotaRepository + std::fmt::Debug> {
+    repository: T,
+    defaults: QuotaLimits,
+    limits: TtlCache<i32, QuotaLimits>,
+}
+
+impl<T: QuotaRepository + std::fmt::Debug> QuotaService<T> {
+    pub fn new(repository: T, config: &QuotaConfig) -> Self {
+        QuotaService {
+            repository,
+            defaults: QuotaLimits {
+                orders_per_day: config.orders_per_day,
+                active_dispatchers: config.active_dispatchers,
+                avatar_storage_bytes: config.avatar_storage_bytes,
+            },
+            limits: TtlCache::new(QUOTA_LIMITS_CACHE_TTL, QUOTA_LIMITS_CACHE_CAPACITY),
+        }
+    }
+
+    pub async fn check_order_quota(&self, organization: OrganizationScope) -> Result<(), AppError> {
+        let Some(limit) = self.limits(organization).await?.orders_per_day else {
+            return Ok(());
+        };
+        let now = Utc::now();
+        let used = self
+            .repository
+            .count_orders_since(organization.organization_id, start_of_day(now))
+            .await?;
+        if used >= limit {
+            let retry_after = (next_reset(now) - now).to_std().unwrap_or_default();
+            return Err(AppError::QuotaExceeded {
+                quota: "orders_per_day",
+                retry_after: Some(retry_after),
+            });
+        }
+
+        Ok(())
+    }
+
+    // ディスパッチャーを 1 人増やせるかを確認する
+    pub async fn check_dispatcher_quota(
+        &self,
+        organization: OrganizationScope,
+    ) -> Result<(), AppError> {
+        let Some(limit) = self.limits(organization).await?.active_dispatchers else {
+            return Ok(());
+        };
+        let used = self
+            .repository
+            .count_active_dispatchers(organization.organization_id)
+            .await?;
+        if used >= limit {
+            return Err(AppError::QuotaExceeded {
+                quota: "active_dispatchers",
+                retry_after: None,
+            });
+        }
+
+        Ok(())
+    }
+
+    // ユーザーの画像を bytes の大きさの画像に差し替えられるかを確認する
+    pub async fn check_avatar_storage_quota(
+        &self,
+        organization: OrganizationScope,
+        user_id: i32,
+        bytes: i64,
+    ) -> Result<(), AppError> {
+        let Some(limit) = self.limits(organization).await?.avatar_storage_bytes else {
+            return Ok(());
+        };
+        let used = self
+            .repository
+            .sum_profile_image_bytes(organization.organization_id)
+            .await?;
+        let replaced = self.repository.find_profile_image_bytes(user_id).await?;
+        if used - replaced + bytes > limit {
+            return Err(AppError::QuotaExceeded {
+                quota: "avatar_storage_bytes",
+                retry_after: None,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub async fn record_profile_image(
+        &self,
+        user_id: i32,
+        name: &str,
+        bytes: i64,
+    ) -> Result<(), AppError> {
+        self.repository
+            .record_profile_image(user_id, name, bytes)
+            .await
+    }
+
+    pub async fn get_usage(
+        &self,
+        organization: OrganizationScope,
+    ) -> Result<OrganizationUsageDto, AppError> {
+        let limits = self.limits(organization).await?;
+        let now = Utc::now();
+        let organization_id = organization.organization_id;
+
+        Ok(OrganizationUsageDto {
+            organization_id,
+            orders_today: QuotaUsageDto {
+                used: self
+                    .repository
+                    .count_orders_since(organization_id, start_of_day(now))
+                    .await?,
+                limit: limits.orders_per_day,
+            },
+            orders_reset_at: next_reset(now),
+            active_dispatchers: QuotaUsageDto {
+                used: self
+                    .repository
+                    .count_active_dispatchers(organization_id)
+                    .await?,
+                limit: limits.active_dispatchers,
+            },
+            avatar_storage_bytes: QuotaUsageDto {
+                used: self
+                    .repository
+                    .sum_profile_image_bytes(organization_id)
+                    .await?,
+                limit: limits.avatar_storage_bytes,
+            },
+        })
+    }
+
+    // 組織ごとの上限は既定の組織の管理者だけが変更できる
+    pub async fn update_quota(
+        &self,
+        organization: OrganizationScope,
+        organization_id: i32,
+        limits: QuotaLimits,
+    ) -> Result<(), AppError> {
+        if !organization.is_default() {
+            return Err(AppError::Forbidden);
+        }
+        if [
+            limits.orders_per_day,
+            limits.active_dispatchers,
+            limits.avatar_storage_bytes,
+        ]
+        .into_iter()
+        .flatten()
+        .any(|limit| limit < 0)
+        {
+            return Err(AppError::BadRequest);
+        }
+
+        match self
+            .repository
+            .update_quota_limits(organization_id, &limits)
+            .await?
+        {
+            true => {
+                self.limits.remove(&organization_id);
+                Ok(())
+            }
+            false => Err(AppError::NotFound),
+        }
+    }
+
+    async fn limits(&self, organization: OrganizationScope) -> Result<QuotaLimits, AppError> {
+        if let Some(limits) = self.limits.get(&organization.organization_id) {
+            return Ok(limits);
+        }
+
+        let limits = self
+            .repository
+            .find_quota_limits(organization.organization_id)
+            .await?
+            .unwrap_or_default()
+            .or(self.defaults);
+        self.limits.insert(organization.organization_id, limits);
+
+        Ok(limits)
+    }
+}
+
+fn start_of_day(now: DateTime<Utc>) -> DateTime<Utc> {
+    now.date_naive().and_time(NaiveTime::MIN).and_utc()
+}
+
+fn next_reset(now: DateTime<Utc>) -> DateTime<Utc> {
+    start_of_day(now) + chrono::Duration::days(1)
+}
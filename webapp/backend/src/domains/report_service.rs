@@ -0,0 +1,145 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::try_join;
+
+use super::dto::report::{AreaCompletionDto, DispatcherUtilizationDto, ReportDto, WindowReportDto};
+use super::events::DomainEvent;
+use crate::{
+    config::ReportConfig,
+    errors::AppError,
+    infrastructure::{event_bus::EventBus, single_flight::SingleFlight},
+    models::report::{AreaCompletionCount, AssignmentLatencyTotal, DispatcherActivity},
+};
+
+pub trait ReportRepository {
+    async fn count_completed_orders_by_area(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<AreaCompletionCount>, AppError>;
+    async fn sum_assignment_latency(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<AssignmentLatencyTotal, AppError>;
+    async fn find_dispatcher_activities(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<DispatcherActivity>, AppError>;
+}
+
+// 集計は重いため、定期ジョブで作成したレポートを返す
+#[derive(Debug)]
+pub struct ReportService<T: ReportRepository + std::fmt::Debug> {
+    repository: T,
+    windows: Vec<Duration>,
+    report: Arc<RwLock<Option<ReportDto>>>,
+    // 定期ジョブと要求時の作成が重なっても、集計は 1 回だけ実行する
+    refresh_flight: SingleFlight<(), ReportDto>,
+}
+
+impl<T: ReportRepository + std::fmt::Debug> ReportService<T> {
+    pub fn new(repository: T, config: &ReportConfig, event_bus: &EventBus) -> Self {
+        let report: Arc<RwLock<Option<ReportDto>>> = Arc::new(RwLock::new(None));
+        let subscriber = report.clone();
+        event_bus.subscribe(move |event| {
+            if let DomainEvent::DataReset = event {
+                *subscriber.write().unwrap() = None;
+            }
+        });
+
+        ReportService {
+            repository,
+            windows: config.windows.clone(),
+            report,
+            refresh_flight: SingleFlight::new(),
+        }
+    }
+
+    // 作成済みのレポートがない場合は、その場で作成する
+    pub async fn get_report(&self) -> Result<ReportDto, AppError> {
+        if let Some(report) = self.report.read().unwrap().as_ref() {
+            return Ok(report.clone());
+        }
+
+        self.refresh().await
+    }
+
+    pub async fn refresh(&self) -> Result<ReportDto, AppError> {
+        self.refresh_flight.run((), || self.build_report()).await
+    }
+
+    async fn build_report(&self) -> Result<ReportDto, AppError> {
+        let generated_at = Utc::now();
+        let mut windows = Vec::with_capacity(self.windows.len());
+        for window in &self.windows {
+            windows.push(self.build_window_report(*window, generated_at).await?);
+        }
+
+        let report = ReportDto {
+            generated_at,
+            windows,
+        };
+        *self.report.write().unwrap() = Some(report.clone());
+
+        Ok(report)
+    }
+
+    async fn build_window_report(
+        &self,
+        window: Duration,
+        to: DateTime<Utc>,
+    ) -> Result<WindowReportDto, AppError> {
+        let from =
+            to - chrono::Duration::from_std(window).map_err(|_| AppError::InternalServerError)?;
+        let (completions, latency, activities) = try_join!(
+            self.repository.count_completed_orders_by_area(from, to),
+            self.repository.sum_assignment_latency(from, to),
+            self.repository.find_dispatcher_activities(from, to),
+        )?;
+
+        let window_seconds = window.as_secs();
+        let average_assignment_latency_seconds =
+            match (latency.total_seconds, latency.assigned_orders) {
+                (Some(total_seconds), assigned_orders) if assigned_orders > 0 => {
+                    Some(total_seconds as f64 / assigned_orders as f64)
+                }
+                _ => None,
+            };
+
+        Ok(WindowReportDto {
+            window_seconds,
+            from,
+            to,
+            completed_orders_per_area: completions
+                .into_iter()
+                .map(|completion| AreaCompletionDto {
+                    area_id: completion.area_id,
+                    completed_orders: completion.completed_orders,
+                })
+                .collect(),
+            assigned_orders: latency.assigned_orders,
+            average_assignment_latency_seconds,
+            dispatcher_utilization: activities
+                .into_iter()
+                .map(|activity| {
+                    let busy_seconds = activity.busy_seconds.unwrap_or(0).max(0);
+                    // 複数の注文を同時に担当した時間は重複して数えるため、上限を 1.0 にする
+                    let utilization = match window_seconds {
+                        0 => 0.0,
+                        _ => (busy_seconds as f64 / window_seconds as f64).min(1.0),
+                    };
+                    DispatcherUtilizationDto {
+                        dispatcher_id: activity.dispatcher_id,
+                        assigned_orders: activity.assigned_orders,
+                        busy_seconds,
+                        utilization,
+                    }
+                })
+                .collect(),
+        })
+    }
+}
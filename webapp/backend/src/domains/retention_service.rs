@@ -0,0 +1,231 @@
+use chrono::{DateTime, Utc};
+use log::{error, info};
+
+use super::dto::retention::{RetentionPolicyReportDto, RetentionReportDto};
+use super::image_service::ImageStore;
+use crate::config::RetentionConfig;
+use crate::errors::AppError;
+
+pub trait RetentionRepository {
+    async fn count_audit_logs_before(&self, before: DateTime<Utc>) -> Result<u64, AppError>;
+    async fn delete_audit_logs_before(
+        &self,
+        before: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<u64, AppError>;
+    // まだ匿名化していない、before より前に完了した注文
+    async fn count_completed_orders_before(&self, before: DateTime<Utc>) -> Result<u64, AppError>;
+    async fn count_completed_order_attachments_before(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<u64, AppError>;
+    // 注文から顧客との結び付きと自由記述の内容、添付ファイルを消す。注文自体は集計のために残す。
+    // 添付ファイルは行だけを削除し、ストアから消すファイルの名前を返す
+    async fn anonymize_completed_orders_before(
+        &self,
+        before: DateTime<Utc>,
+        anonymized_at: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<AnonymizedOrders, AppError>;
+}
+
+#[derive(Debug, Default)]
+pub struct AnonymizedOrders {
+    pub count: u64,
+    pub attachment_names: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum RetentionPolicy {
+    AuditLogs,
+    CompletedOrders,
+}
+
+impl RetentionPolicy {
+    const ALL: [RetentionPolicy; 2] =
+        [RetentionPolicy::AuditLogs, RetentionPolicy::CompletedOrders];
+
+    fn name(&self) -> &'static str {
+        match self {
+            RetentionPolicy::AuditLogs => "audit_logs",
+            RetentionPolicy::CompletedOrders => "completed_orders",
+        }
+    }
+
+    fn action(&self) -> &'static str {
+        match self {
+            RetentionPolicy::AuditLogs => "delete",
+            RetentionPolicy::CompletedOrders => "anonymize",
+        }
+    }
+
+    fn retention_days(&self, config: &RetentionConfig) -> u32 {
+        match self {
+            RetentionPolicy::AuditLogs => config.audit_log_days,
+            RetentionPolicy::CompletedOrders => config.completed_order_days,
+        }
+    }
+}
+
+// 保持期間を過ぎた監査ログの削除と、完了した注文の匿名化を行う。
+// ロックが長引かないよう少しずつ処理し、処理の間に待ち時間を入れて他のクエリへの影響を抑える
+#[derive(Debug)]
+pub struct RetentionService<
+    T: RetentionRepository + std::fmt::Debug,
+    U: ImageStore + std::fmt::Debug,
+> {
+    repository: T,
+    image_store: U,
+    config: RetentionConfig,
+}
+
+impl<T: RetentionRepository + std::fmt::Debug, U: ImageStore + std::fmt::Debug>
+    RetentionService<T, U>
+{
+    pub fn new(repository: T, image_store: U, config: &RetentionConfig) -> Self {
+        RetentionService {
+            repository,
+            image_store,
+            config: config.clone(),
+        }
+    }
+
+    // 何も変更せず、期限が設定されたポリシーごとに対象になる件数を返す
+    pub async fn report(&self) -> Result<RetentionReportDto, AppError> {
+        let now = Utc::now();
+        let mut policies = Vec::new();
+        for policy in RetentionPolicy::ALL {
+            let Some(cutoff) = self.cutoff(policy, now) else {
+                continue;
+            };
+            let (count, attachment_count) = match policy {
+                RetentionPolicy::AuditLogs => {
+                    (self.repository.count_audit_logs_before(cutoff).await?, None)
+                }
+                RetentionPolicy::CompletedOrders => (
+                    self.repository
+                        .count_completed_orders_before(cutoff)
+                        .await?,
+                    Some(
+                        self.repository
+                            .count_completed_order_attachments_before(cutoff)
+                            .await?,
+                    ),
+                ),
+            };
+            policies.push(self.policy_report(policy, cutoff, count, attachment_count));
+        }
+
+        Ok(RetentionReportDto {
+            dry_run: true,
+            policies,
+        })
+    }
+
+    // 定期ジョブから呼ぶ。dry_run の場合は対象の件数を数えるだけにする
+    pub async fn run(&self) -> Result<RetentionReportDto, AppError> {
+        if self.config.dry_run {
+            return self.report().await;
+        }
+
+        let now = Utc::now();
+        let mut policies = Vec::new();
+        for policy in RetentionPolicy::ALL {
+            let Some(cutoff) = self.cutoff(policy, now) else {
+                continue;
+            };
+            let (count, attachment_count) = self.apply_in_batches(policy, cutoff, now).await?;
+            policies.push(self.policy_report(policy, cutoff, count, attachment_count));
+        }
+
+        Ok(RetentionReportDto {
+            dry_run: false,
+            policies,
+        })
+    }
+
+    async fn apply_in_batches(
+        &self,
+        policy: RetentionPolicy,
+        cutoff: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<(u64, Option<u64>), AppError> {
+        let batch_size = self.config.batch_size.max(1);
+        let mut total = 0;
+        let mut attachment_total = match policy {
+            RetentionPolicy::AuditLogs => None,
+            RetentionPolicy::CompletedOrders => Some(0),
+        };
+        for batch in 0..self.config.max_batches_per_run.max(1) {
+            if batch > 0 && !self.config.batch_pause.is_zero() {
+                actix_web::rt::time::sleep(self.config.batch_pause).await;
+            }
+
+            let count = match policy {
+                RetentionPolicy::AuditLogs => {
+                    self.repository
+                        .delete_audit_logs_before(cutoff, batch_size)
+                        .await?
+                }
+                RetentionPolicy::CompletedOrders => {
+                    let anonymized = self
+                        .repository
+                        .anonymize_completed_orders_before(cutoff, now, batch_size)
+                        .await?;
+                    attachment_total = attachment_total
+                        .map(|total| total + anonymized.attachment_names.len() as u64);
+                    self.delete_attachment_files(&anonymized.attachment_names)
+                        .await;
+                    anonymized.count
+                }
+            };
+            total += count;
+            if count < u64::from(batch_size) {
+                return Ok((total, attachment_total));
+            }
+        }
+
+        info!(
+            "保持期間のポリシー {} は 1 回の実行の上限に達したため、残りは次の実行で処理します",
+            policy.name()
+        );
+        Ok((total, attachment_total))
+    }
+
+    // 行は削除済みのため、ファイルを消せなくても参照されることはない。失敗はログに残して続ける
+    async fn delete_attachment_files(&self, names: &[String]) {
+        for name in names {
+            if let Err(err) = self.image_store.delete(name).await {
+                error!(
+                    "匿名化した注文の添付ファイル {} を削除できませんでした: {:?}",
+                    name, err
+                );
+            }
+        }
+    }
+
+    // 期限が 0 日のポリシーは無効として扱う
+    fn cutoff(&self, policy: RetentionPolicy, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match policy.retention_days(&self.config) {
+            0 => None,
+            days => Some(now - chrono::Duration::days(i64::from(days))),
+        }
+    }
+
+    fn policy_report(
+        &self,
+        policy: RetentionPolicy,
+        cutoff: DateTime<Utc>,
+        count: u64,
+        attachment_count: Option<u64>,
+    ) -> RetentionPolicyReportDto {
+        RetentionPolicyReportDto {
+            policy: policy.name(),
+            action: policy.action(),
+            retention_days: policy.retention_days(&self.config),
+            cutoff,
+            count,
+            attachment_count,
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use actix_web::web;
+use futures_util::future::try_join_all;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use super::events::DomainEvent;
+use super::map_service::MapRepository;
+use crate::config::RoutingConfig;
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::single_flight::SingleFlight;
+use crate::models::contraction_hierarchy::ContractionHierarchy;
+use crate::models::graph::{Edge, Graph, Node};
+use crate::utils::sha256_hex;
+
+// 1 つのスレッドで距離を求める目的地の数。これより多い場合は分けて並列に求める
+const DISTANCE_CHUNK_SIZE: usize = 32;
+
+// 保存したファイルが、どのグラフから作ったものかを確かめるために指紋を持たせる
+#[derive(Serialize, Deserialize)]
+struct RouteArtifact {
+    fingerprint: String,
+    hierarchy: ContractionHierarchy,
+}
+
+#[derive(Debug, Default)]
+struct HierarchyCache {
+    hierarchies: RwLock<HashMap<i32, Arc<ContractionHierarchy>>>,
+    // 前処理中に道路が変わった場合に、古いグラフから作ったものを保存しないための世代
+    generation: AtomicU64,
+}
+
+// エリアごとに縮約階層法で前処理したグラフを保持し、多対多の距離を求める。
+// 前処理は初めて要求されたときか起動時に行い、道路の重みが変わると破棄して次の要求で作り直す
+#[derive(Debug)]
+pub struct RoutePlanner<T: MapRepository + std::fmt::Debug> {
+    repository: T,
+    cache: Arc<HierarchyCache>,
+    builds: SingleFlight<i32, Arc<ContractionHierarchy>>,
+    cache_dir: Option<PathBuf>,
+}
+
+impl<T: MapRepository + std::fmt::Debug> RoutePlanner<T> {
+    pub fn new(repository: T, config: &RoutingConfig, event_bus: &EventBus) -> Self {
+        let cache = Arc::new(HierarchyCache::default());
+        let subscriber = cache.clone();
+        event_bus.subscribe(move |event| match event {
+            DomainEvent::DataReset => {
+                subscriber.generation.fetch_add(1, Ordering::SeqCst);
+                subscriber.hierarchies.write().unwrap().clear();
+            }
+            DomainEvent::EdgeUpdated {
+                node_a_id,
+                node_b_id,
+            } => {
+                subscriber.generation.fetch_add(1, Ordering::SeqCst);
+                subscriber
+                    .hierarchies
+                    .write()
+                    .unwrap()
+                    .retain(|_, hierarchy| {
+                        !hierarchy.contains(*node_a_id) && !hierarchy.contains(*node_b_id)
+                    });
+            }
+            _ => {}
+        });
+
+        RoutePlanner {
+            repository,
+            cache,
+            builds: SingleFlight::new(),
+            cache_dir: config.cache_dir.clone(),
+        }
+    }
+
+    // sources × targets の距離。到達できない組み合わせは None
+    pub async fn distance_matrix(
+        &self,
+        area_id: i32,
+        sources: &[i32],
+        targets: &[i32],
+    ) -> Result<Vec<Vec<Option<i32>>>, AppError> {
+        let hierarchy = self.hierarchy(area_id).await?;
+
+        Ok(hierarchy.distance_matrix(sources, targets))
+    }
+
+    // 1 つの出発地から多数の目的地への距離を、目的地を分けて別スレッドで並列に求める
+    pub async fn distances_from(
+        &self,
+        area_id: i32,
+        origin: i32,
+        destinations: &[i32],
+    ) -> Result<Vec<Option<i32>>, AppError> {
+        let hierarchy = self.hierarchy(area_id).await?;
+        let rows = try_join_all(destinations.chunks(DISTANCE_CHUNK_SIZE).map(|chunk| {
+            let hierarchy = hierarchy.clone();
+            let chunk = chunk.to_vec();
+            web::block(move || hierarchy.distance_matrix(&[origin], &chunk).remove(0))
+        }))
+        .await
+        .map_err(|e| {
+            error!("距離の計算のスレッドの実行に失敗しました: {:?}", e);
+            AppError::InternalServerError
+        })?;
+
+        Ok(rows.into_iter().flatten().collect())
+    }
+
+    // 全エリアのグラフを前処理し、前処理したエリアの数を返す
+    pub async fn preload(&self) -> Result<usize, AppError> {
+        let areas = self.repository.get_all_areas().await?;
+        for area in &areas {
+            self.hierarchy(area.id).await?;
+        }
+
+        Ok(areas.len())
+    }
+
+    async fn hierarchy(&self, area_id: i32) -> Result<Arc<ContractionHierarchy>, AppError> {
+        if let Some(hierarchy) = self.cache.hierarchies.read().unwrap().get(&area_id) {
+            return Ok(hierarchy.clone());
+        }
+
+        self.builds
+            .run(area_id, || self.load_hierarchy(area_id))
+            .await
+    }
+
+    async fn load_hierarchy(&self, area_id: i32) -> Result<Arc<ContractionHierarchy>, AppError> {
+        let generation = self.cache.generation.load(Ordering::SeqCst);
+        let nodes = self.repository.get_all_nodes(Some(area_id)).await?;
+        let edges = self.repository.get_all_edges(Some(area_id)).await?;
+        let fingerprint = fingerprint(&nodes, &edges);
+        let path = self
+            .cache_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("route_area_{}.json", area_id)));
+
+        // 前処理とファイルの読み書きはワーカーのスレッドを止めないよう別スレッドで行う
+        let hierarchy = web::block(move || {
+            if let Some(hierarchy) = path
+                .as_deref()
+                .and_then(|path| read_artifact(path, &fingerprint))
+            {
+                return hierarchy;
+            }
+
+            let started_at = std::time::Instant::now();
+            let mut graph = Graph::new();
+            for node in nodes {
+                graph.add_node(node);
+            }
+            for edge in edges {
+                graph.add_edge(edge);
+            }
+            let hierarchy = ContractionHierarchy::build(&graph);
+            info!(
+                "エリア {} のグラフを前処理しました ({:?})",
+                area_id,
+                started_at.elapsed()
+            );
+            let artifact = RouteArtifact {
+                fingerprint,
+                hierarchy,
+            };
+            if let Some(path) = &path {
+                write_artifact(path, &artifact);
+            }
+            artifact.hierarchy
+        })
+        .await
+        .map_err(|e| {
+            error!("グラフの前処理のスレッドの実行に失敗しました: {:?}", e);
+            AppError::InternalServerError
+        })?;
+
+        let hierarchy = Arc::new(hierarchy);
+        let mut hierarchies = self.cache.hierarchies.write().unwrap();
+        if self.cache.generation.load(Ordering::SeqCst) == generation {
+            hierarchies.insert(area_id, hierarchy.clone());
+        }
+
+        Ok(hierarchy)
+    }
+}
+
+// 取得した順序によらず同じ値になるよう、ノードと辺を並べ替えてから求める
+fn fingerprint(nodes: &[Node], edges: &[Edge]) -> String {
+    let mut node_ids: Vec<i32> = nodes.iter().map(|node| node.id).collect();
+    node_ids.sort_unstable();
+    let mut edges: Vec<(i32, i32, i32)> = edges
+        .iter()
+        .map(|edge| {
+            (
+                edge.node_a_id.min(edge.node_b_id),
+                edge.node_a_id.max(edge.node_b_id),
+                edge.weight,
+            )
+        })
+        .collect();
+    edges.sort_unstable();
+
+    sha256_hex(&format!("{:?}{:?}", node_ids, edges))
+}
+
+fn read_artifact(path: &Path, fingerprint: &str) -> Option<ContractionHierarchy> {
+    let content = std::fs::read(path).ok()?;
+    match serde_json::from_slice::<RouteArtifact>(&content) {
+        Ok(artifact) if artifact.fingerprint == fingerprint => {
+            info!("前処理したグラフを {} から読み込みました", path.display());
+            Some(artifact.hierarchy)
+        }
+        Ok(_) => None,
+        Err(e) => {
+            warn!(
+                "前処理したグラフ {} を読み込めませんでした: {:?}",
+                path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+// 書き込み途中のファイルを読まないよう、一時ファイルに書いてから置き換える
+fn write_artifact(path: &Path, artifact: &RouteArtifact) {
+    let temporary = path.with_extension("json.tmp");
+    let result = serde_json::to_vec(artifact)
+        .map_err(std::io::Error::from)
+        .and_then(|content| {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(&temporary, content)?;
+            std::fs::rename(&temporary, path)
+        });
+    if let Err(e) = result {
+        warn!(
+            "前処理したグラフを {} に保存できませんでした: {:?}",
+            path.display(),
+            e
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+
+use log::{error, info, warn, LevelFilter};
+
+use super::dto::runtime_config::{LogLevelDto, RuntimeConfigDto};
+use super::events::DomainEvent;
+use crate::config::{ConfigReloadConfig, TunableConfig};
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+
+// 実行中に変更できる設定を保持し、変更を ConfigChanged イベントで各コンポーネントに通知する
+#[derive(Debug)]
+pub struct RuntimeConfigService {
+    tunables: RwLock<TunableConfig>,
+    file: Option<PathBuf>,
+    // 最後に読み込んだときのファイルの更新時刻。変わった場合だけ読み直す
+    file_modified_at: Mutex<Option<SystemTime>>,
+    event_bus: Arc<EventBus>,
+}
+
+impl RuntimeConfigService {
+    pub fn new(
+        tunables: TunableConfig,
+        config: &ConfigReloadConfig,
+        event_bus: Arc<EventBus>,
+    ) -> Self {
+        RuntimeConfigService {
+            tunables: RwLock::new(tunables),
+            file: config.file.clone(),
+            file_modified_at: Mutex::new(None),
+            event_bus,
+        }
+    }
+
+    pub fn get_config(&self) -> RuntimeConfigDto {
+        RuntimeConfigDto::from_tunables(&self.tunables.read().unwrap())
+    }
+
+    // すべての値を検証してから反映する。1 つでも不正な値があれば何も変更しない
+    pub fn update<'a>(
+        &self,
+        changes: impl IntoIterator<Item = (&'a str, String)>,
+    ) -> Result<RuntimeConfigDto, AppError> {
+        let mut tunables = self.tunables.write().unwrap();
+        let mut updated = tunables.clone();
+        for (key, value) in changes {
+            updated.set(key, &value).map_err(|message| {
+                warn!("設定を変更できませんでした: {}", message);
+                AppError::BadRequest
+            })?;
+        }
+
+        if *tunables != updated {
+            info!("実行中の設定を変更しました: {:?}", updated);
+            *tunables = updated.clone();
+            // 購読者が設定を読み直しても待たされないよう、ロックを外してから通知する
+            drop(tunables);
+            self.event_bus
+                .publish(DomainEvent::ConfigChanged { tunables: updated });
+        }
+
+        Ok(self.get_config())
+    }
+
+    // 負荷をかけたまま不具合を再現する間だけ詳細なログを出せるよう、全体のログレベルを切り替える。
+    // モジュールごとのフィルタは指定したレベルで置き換えられる
+    pub fn set_log_level(&self, level: &str) -> Result<LogLevelDto, AppError> {
+        let level = match level.to_ascii_lowercase().as_str() {
+            "error" => LevelFilter::Error,
+            "warn" => LevelFilter::Warn,
+            "info" => LevelFilter::Info,
+            "debug" => LevelFilter::Debug,
+            "trace" => LevelFilter::Trace,
+            _ => return Err(AppError::BadRequest),
+        };
+        let filter = level.as_str().to_ascii_lowercase();
+        self.update([("RUST_LOG", filter.clone())])?;
+
+        Ok(LogLevelDto { level: filter })
+    }
+
+    pub fn reload_file(&self) -> Result<RuntimeConfigDto, AppError> {
+        let file = self.file.as_ref().ok_or(AppError::NotFound)?;
+        let modified_at = std::fs::metadata(file)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let content = std::fs::read_to_string(file).map_err(|e| {
+            error!(
+                "設定ファイル {} を読み込めませんでした: {:?}",
+                file.display(),
+                e
+            );
+            AppError::InternalServerError
+        })?;
+        // 不正な内容でも同じ版を繰り返し読み直さないよう、反映の前に更新時刻を記録する
+        *self.file_modified_at.lock().unwrap() = modified_at;
+        let changes = parse_config_file(&content);
+
+        self.update(
+            changes
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.clone())),
+        )
+    }
+
+    // 定期的に呼び出し、設定ファイルが更新されていれば読み直す
+    pub fn reload_file_if_modified(&self) -> Result<(), AppError> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let modified_at = std::fs::metadata(file)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if modified_at.is_none() || *self.file_modified_at.lock().unwrap() == modified_at {
+            return Ok(());
+        }
+
+        info!("設定ファイル {} を読み直します", file.display());
+        self.reload_file()?;
+
+        Ok(())
+    }
+}
+
+// 空行と # で始まる行は読み飛ばす
+fn parse_config_file(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| match line.split_once('=') {
+            Some((key, value)) => Some((key.trim().to_string(), value.trim().to_string())),
+            None => {
+                warn!("設定ファイルの行を解釈できませんでした: {}", line);
+                None
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,103 @@
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use super::dispatcher_availability::AvailableDispatcher;
+use super::geocoding_service::GeocodeCacheSnapshot;
+use super::map_service::AreaList;
+
+// 形式を変えた場合は上げる。異なる版のスナップショットは読み込まない
+const SNAPSHOT_VERSION: u32 = 1;
+
+// 再起動の直後から応答できるよう、停止時に保存するメモリ上の状態。
+// DB から読み直せば済むものだけを含め、読み込めなくても起動を続ける。
+// 前処理したグラフは ROUTE_CACHE_DIR に道路の指紋とあわせて保存しているため、ここには含めない
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StateSnapshot {
+    pub version: u32,
+    pub saved_at: DateTime<Utc>,
+    pub available_dispatchers: Vec<AvailableDispatcher>,
+    pub areas: Option<AreaList>,
+    pub geocode_cache: GeocodeCacheSnapshot,
+}
+
+impl StateSnapshot {
+    pub fn new(
+        available_dispatchers: Vec<AvailableDispatcher>,
+        areas: Option<AreaList>,
+        geocode_cache: GeocodeCacheSnapshot,
+    ) -> Self {
+        StateSnapshot {
+            version: SNAPSHOT_VERSION,
+            saved_at: Utc::now(),
+            available_dispatchers,
+            areas,
+            geocode_cache,
+        }
+    }
+
+    // 書き込み途中のファイルを読まないよう、一時ファイルに書いてから置き換える
+    pub fn save(&self, path: &Path) {
+        let temporary = path.with_extension("tmp");
+        let result = serde_json::to_vec(self)
+            .map_err(std::io::Error::from)
+            .and_then(|content| {
+                std::fs::write(&temporary, content)?;
+                std::fs::rename(&temporary, path)
+            });
+        match result {
+            Ok(()) => info!("メモリ上の状態を {} に保存しました", path.display()),
+            Err(e) => warn!(
+                "メモリ上の状態を {} に保存できませんでした: {:?}",
+                path.display(),
+                e
+            ),
+        }
+    }
+
+    // 読み込んだファイルは削除し、次に異常終了した場合に古い状態を読み込まないようにする
+    pub fn load(path: &Path, max_age: Duration) -> Option<Self> {
+        let content = std::fs::read(path).ok()?;
+        if let Err(e) = std::fs::remove_file(path) {
+            warn!(
+                "スナップショット {} を削除できませんでした: {:?}",
+                path.display(),
+                e
+            );
+        }
+
+        let snapshot = match serde_json::from_slice::<StateSnapshot>(&content) {
+            Ok(snapshot) if snapshot.version == SNAPSHOT_VERSION => snapshot,
+            Ok(snapshot) => {
+                warn!(
+                    "スナップショットの版 {} は読み込めないため無視します",
+                    snapshot.version
+                );
+                return None;
+            }
+            Err(e) => {
+                warn!(
+                    "スナップショット {} を解釈できませんでした: {:?}",
+                    path.display(),
+                    e
+                );
+                return None;
+            }
+        };
+        let age = (Utc::now() - snapshot.saved_at)
+            .to_std()
+            .unwrap_or_default();
+        if age > max_age {
+            info!(
+                "スナップショットは {} 秒前のもので古いため読み込みません",
+                age.as_secs()
+            );
+            return None;
+        }
+
+        Some(snapshot)
+    }
+}
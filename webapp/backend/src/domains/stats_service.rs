@@ -0,0 +1,80 @@
+use log::error;
+
+use super::dto::stats::{OrderCountsDto, StatsSummaryDto};
+use super::events::DomainEvent;
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::request_id;
+use crate::models::organization::OrganizationScope;
+use crate::models::stats::OrderCounter;
+
+pub trait StatsRepository {
+    // すべての組織の件数を orders から数え直す
+    async fn reconcile_order_counters(&self) -> Result<(), AppError>;
+    async fn find_order_counters(
+        &self,
+        organization_id: i32,
+    ) -> Result<Vec<OrderCounter>, AppError>;
+}
+
+// ダッシュボードの件数表示のために、COUNT(*) で数えずに保持している件数を返す。
+// 件数は orders への INSERT・UPDATE・DELETE と同じ文でトリガーが増減させるため、どの経路で注文を変更してもずれない
+#[derive(Debug)]
+pub struct StatsService<T: StatsRepository + std::fmt::Debug> {
+    repository: T,
+}
+
+impl<T> StatsService<T>
+where
+    T: StatsRepository + std::fmt::Debug + Clone + Send + Sync + 'static,
+{
+    // TRUNCATE ではトリガーが動かないため、データを置き換えた後は数え直す
+    pub fn new(repository: T, event_bus: &EventBus) -> Self {
+        let subscriber = repository.clone();
+        event_bus.subscribe(move |event| {
+            if let DomainEvent::DataReset = event {
+                let repository = subscriber.clone();
+                request_id::spawn(async move {
+                    if let Err(err) = repository.reconcile_order_counters().await {
+                        error!("注文数を数え直せませんでした: {:?}", err);
+                    }
+                });
+            }
+        });
+
+        StatsService { repository }
+    }
+}
+
+impl<T: StatsRepository + std::fmt::Debug> StatsService<T> {
+    pub async fn get_summary(
+        &self,
+        organization: OrganizationScope,
+    ) -> Result<StatsSummaryDto, AppError> {
+        let counters = self
+            .repository
+            .find_order_counters(organization.organization_id)
+            .await?;
+        let mut orders = OrderCountsDto::default();
+        for counter in &counters {
+            let count = match counter.status.as_str() {
+                "scheduled" => &mut orders.scheduled,
+                "pending" => &mut orders.pending,
+                "dispatched" => &mut orders.dispatched,
+                "completed" => &mut orders.completed,
+                "cancelled" => &mut orders.cancelled,
+                _ => continue,
+            };
+            *count = counter.count;
+            orders.total += *count;
+        }
+
+        Ok(StatsSummaryDto {
+            orders,
+            reconciled_at: counters
+                .iter()
+                .filter_map(|counter| counter.reconciled_at)
+                .min(),
+        })
+    }
+}
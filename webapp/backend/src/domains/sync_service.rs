@@ -0,0 +1,291 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::error;
+
+use super::dispatcher_availability::DispatcherAvailability;
+use super::dto::order::OrderDto;
+use super::dto::sync::{
+    SyncDispatcherDto, SyncQueryDto, SyncRemovedDto, SyncResponseDto, SyncUserDto,
+};
+use super::events::DomainEvent;
+use crate::config::SyncConfig;
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::request_id;
+use crate::models::change_log::{ChangeEntity, ChangeLogEntry};
+use crate::models::order::OrderSyncRow;
+use crate::models::organization::OrganizationScope;
+use crate::models::user::{Dispatcher, UserSyncRow};
+
+const DEFAULT_SYNC_LIMIT: i32 = 500;
+const MAX_SYNC_LIMIT: i32 = 1000;
+
+pub trait SyncRepository {
+    // 変更されたものが属する組織を引いて記録する。見つからない場合は何もしない
+    async fn record_change(&self, entity: ChangeEntity, entity_id: i32) -> Result<(), AppError>;
+    // すべての組織に読み込み直しを求める記録
+    async fn record_reset(&self) -> Result<(), AppError>;
+    // 記録の ID は INSERT の順に振られ、コミットの順とは限らない。後から小さい ID の記録が見えるようになって
+    // クライアントが読み飛ばさないよう、safety_lag より新しい記録がある場合はそのうち最も古いものの手前までを返す
+    async fn find_latest_change_id(&self, safety_lag: Duration) -> Result<i64, AppError>;
+    async fn find_oldest_change_id(&self) -> Result<Option<i64>, AppError>;
+    // since より後で until までの、組織の変更とデータ全体の置き換えを古い順に返す
+    async fn find_changes(
+        &self,
+        organization_id: i32,
+        since: i64,
+        until: i64,
+        limit: i32,
+    ) -> Result<Vec<ChangeLogEntry>, AppError>;
+    async fn find_orders(
+        &self,
+        organization_id: i32,
+        order_ids: &[i32],
+    ) -> Result<Vec<OrderSyncRow>, AppError>;
+    async fn find_dispatchers(
+        &self,
+        organization_id: i32,
+        dispatcher_ids: &[i32],
+    ) -> Result<Vec<Dispatcher>, AppError>;
+    async fn find_users(
+        &self,
+        organization_id: i32,
+        user_ids: &[i32],
+    ) -> Result<Vec<UserSyncRow>, AppError>;
+    async fn delete_changes_before(
+        &self,
+        before: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<u64, AppError>;
+}
+
+// オフラインでも動くクライアント向けに、前回の同期から変更された注文・ディスパッチャー・ユーザーの現在の値を返す
+#[derive(Debug)]
+pub struct SyncService<T: SyncRepository + std::fmt::Debug> {
+    repository: T,
+    availability: Arc<DispatcherAvailability>,
+    config: SyncConfig,
+}
+
+impl<T> SyncService<T>
+where
+    T: SyncRepository + std::fmt::Debug + Clone + Send + Sync + 'static,
+{
+    // イベントを購読して変更を記録する。記録は非同期に行うため、イベントを発行したリクエストは待たない
+    pub fn new(
+        repository: T,
+        availability: Arc<DispatcherAvailability>,
+        config: &SyncConfig,
+        event_bus: &EventBus,
+    ) -> Self {
+        let subscriber = repository.clone();
+        event_bus.subscribe(move |event| {
+            let change = match event {
+                DomainEvent::DataReset => None,
+                event => match changed_entity(event) {
+                    Some(change) => Some(change),
+                    None => return,
+                },
+            };
+            let repository = subscriber.clone();
+            request_id::spawn(async move {
+                let result = match change {
+                    Some((entity, entity_id)) => repository.record_change(entity, entity_id).await,
+                    None => repository.record_reset().await,
+                };
+                if let Err(err) = result {
+                    error!("差分同期の変更を記録できませんでした: {:?}", err);
+                }
+            });
+        });
+
+        SyncService {
+            repository,
+            availability,
+            config: config.clone(),
+        }
+    }
+}
+
+impl<T: SyncRepository + std::fmt::Debug> SyncService<T> {
+    pub async fn get_changes(
+        &self,
+        organization: OrganizationScope,
+        query: SyncQueryDto,
+    ) -> Result<SyncResponseDto, AppError> {
+        let limit = query.limit.unwrap_or(DEFAULT_SYNC_LIMIT);
+        if !(1..=MAX_SYNC_LIMIT).contains(&limit) {
+            return Err(AppError::BadRequest);
+        }
+        let since = match query.since {
+            Some(since) if since >= 0 => since,
+            Some(_) => return Err(AppError::BadRequest),
+            None => return self.reset_response().await,
+        };
+
+        // 記録が削除された位置からは差分を作れない。
+        // フィクスチャの投入で記録が空になり ID が振り直された場合も、クライアントの位置が先に進んでいる
+        let latest = self
+            .repository
+            .find_latest_change_id(self.config.change_log_safety_lag)
+            .await?;
+        let oldest = self.repository.find_oldest_change_id().await?;
+        if since > latest || oldest.is_some_and(|oldest| since + 1 < oldest) {
+            return self.reset_response().await;
+        }
+
+        let mut changes = self
+            .repository
+            .find_changes(organization.organization_id, since, latest, limit + 1)
+            .await?;
+        let has_more = changes.len() > limit as usize;
+        changes.truncate(limit as usize);
+        let cursor = changes.last().map(|change| change.id).unwrap_or(since);
+
+        let mut order_ids = BTreeSet::new();
+        let mut dispatcher_ids = BTreeSet::new();
+        let mut user_ids = BTreeSet::new();
+        for change in &changes {
+            match change.entity_type.parse()? {
+                ChangeEntity::Order => order_ids.insert(change.entity_id),
+                ChangeEntity::Dispatcher => dispatcher_ids.insert(change.entity_id),
+                ChangeEntity::User => user_ids.insert(change.entity_id),
+                ChangeEntity::Reset => return self.reset_response().await,
+            };
+        }
+
+        let order_ids: Vec<i32> = order_ids.into_iter().collect();
+        let dispatcher_ids: Vec<i32> = dispatcher_ids.into_iter().collect();
+        let user_ids: Vec<i32> = user_ids.into_iter().collect();
+        let orders = self
+            .repository
+            .find_orders(organization.organization_id, &order_ids)
+            .await?;
+        let dispatchers = self
+            .repository
+            .find_dispatchers(organization.organization_id, &dispatcher_ids)
+            .await?;
+        let users = self
+            .repository
+            .find_users(organization.organization_id, &user_ids)
+            .await?;
+
+        let mut removed = Vec::new();
+        removed.extend(removed_ids(
+            ChangeEntity::Order,
+            &order_ids,
+            orders.iter().map(|order| order.id),
+        ));
+        removed.extend(removed_ids(
+            ChangeEntity::Dispatcher,
+            &dispatcher_ids,
+            dispatchers.iter().map(|dispatcher| dispatcher.id),
+        ));
+        removed.extend(removed_ids(
+            ChangeEntity::User,
+            &user_ids,
+            users.iter().map(|user| user.id),
+        ));
+
+        Ok(SyncResponseDto {
+            cursor,
+            has_more,
+            reset: false,
+            orders: orders.into_iter().map(OrderDto::from_sync_row).collect(),
+            dispatchers: dispatchers
+                .into_iter()
+                .map(|dispatcher| {
+                    let available = self.availability.find(dispatcher.id);
+                    SyncDispatcherDto {
+                        id: dispatcher.id,
+                        user_id: dispatcher.user_id,
+                        area_id: dispatcher.area_id,
+                        available: available.is_some(),
+                        active_assignments: available
+                            .map(|available| available.active_assignments)
+                            .unwrap_or(0),
+                    }
+                })
+                .collect(),
+            users: users.into_iter().map(SyncUserDto::from_entity).collect(),
+            removed,
+        })
+    }
+
+    // 保持期間を過ぎた記録を削除し、削除した件数を返す
+    pub async fn prune_change_log(&self) -> Result<u64, AppError> {
+        let retention = chrono::Duration::from_std(self.config.change_log_retention)
+            .map_err(|_| AppError::InternalServerError)?;
+
+        self.repository
+            .delete_changes_before(Utc::now() - retention, self.config.prune_batch_size)
+            .await
+    }
+
+    // 現在の位置だけを返し、クライアントには一覧の API での読み込み直しを求める
+    async fn reset_response(&self) -> Result<SyncResponseDto, AppError> {
+        Ok(SyncResponseDto {
+            cursor: self
+                .repository
+                .find_latest_change_id(self.config.change_log_safety_lag)
+                .await?,
+            has_more: false,
+            reset: true,
+            orders: Vec::new(),
+            dispatchers: Vec::new(),
+            users: Vec::new(),
+            removed: Vec::new(),
+        })
+    }
+}
+
+fn removed_ids(
+    entity: ChangeEntity,
+    requested: &[i32],
+    found: impl Iterator<Item = i32>,
+) -> Vec<SyncRemovedDto> {
+    let found: BTreeSet<i32> = found.collect();
+    requested
+        .iter()
+        .filter(|id| !found.contains(id))
+        .map(|id| SyncRemovedDto {
+            entity_type: entity.as_str(),
+            id: *id,
+        })
+        .collect()
+}
+
+// 同期の対象の値を変えるイベントと、変わったもの
+fn changed_entity(event: &DomainEvent) -> Option<(ChangeEntity, i32)> {
+    match event {
+        DomainEvent::OrderCreated { order_id, .. }
+        | DomainEvent::OrderDispatched { order_id, .. }
+        | DomainEvent::OrderCancelled { order_id, .. }
+        | DomainEvent::OrderCompleted { order_id, .. }
+        | DomainEvent::OrderReassigned { order_id, .. }
+        | DomainEvent::OrderRequeued { order_id, .. } => Some((ChangeEntity::Order, *order_id)),
+        DomainEvent::DispatcherAvailabilityChanged { dispatcher_id, .. }
+        | DomainEvent::DispatcherTransferred { dispatcher_id, .. } => {
+            Some((ChangeEntity::Dispatcher, *dispatcher_id))
+        }
+        DomainEvent::UserRoleChanged { user_id }
+        | DomainEvent::UserDeactivated { user_id }
+        | DomainEvent::UserOrganizationChanged { user_id, .. }
+        | DomainEvent::ProfileImageUploaded { user_id, .. }
+        | DomainEvent::ProfileImageReverted { user_id, .. } => Some((ChangeEntity::User, *user_id)),
+        // 到着予定は注文の値に含まれないため、同期の対象にしない
+        DomainEvent::OrderEtaChanged { .. }
+        | DomainEvent::PasswordChanged { .. }
+        | DomainEvent::DataReset
+        | DomainEvent::EdgeUpdated { .. }
+        | DomainEvent::AreaBoundaryChanged { .. }
+        | DomainEvent::ConfigChanged { .. }
+        | DomainEvent::ProfileImageFlagged { .. }
+        | DomainEvent::LoginSucceeded { .. }
+        | DomainEvent::LoginFailed { .. }
+        | DomainEvent::SuspiciousLoginDetected { .. } => None,
+    }
+}
@@ -1,21 +1,50 @@
 use super::dto::tow_truck::TowTruckDto;
+use super::geofence::Geofence;
 use super::map_service::MapRepository;
 use super::order_service::OrderRepository;
+use super::route_planner::RoutePlanner;
+use crate::config::WriteBehindConfig;
 use crate::errors::AppError;
+use crate::infrastructure::feature_flags::{FeatureFlag, FeatureFlags};
+use crate::infrastructure::write_behind::{BatchWriter, WriteBehindBuffer};
 use crate::models::graph::Graph;
-use crate::models::tow_truck::TowTruck;
+use crate::models::order::OrderStatus;
+use crate::models::organization::OrganizationScope;
+use crate::models::tow_truck::{NewLocation, TowTruck};
+use crate::models::vehicle::VehicleStatus;
+use std::sync::Arc;
 
+// 車両は運転手のユーザーと同じ組織に属し、organization_id の組織の車両だけを返す
 pub trait TowTruckRepository {
     async fn get_paginated_tow_trucks(
         &self,
+        organization_id: i32,
         page: i32,
         page_size: i32,
         status: Option<String>,
         area_id: Option<i32>,
     ) -> Result<Vec<TowTruck>, AppError>;
     async fn update_location(&self, truck_id: i32, node_id: i32) -> Result<(), AppError>;
+    // 複数行の INSERT でまとめて書き込む
+    async fn create_locations(&self, locations: &[NewLocation]) -> Result<(), AppError>;
     async fn update_status(&self, truck_id: i32, status: &str) -> Result<(), AppError>;
-    async fn find_tow_truck_by_id(&self, id: i32) -> Result<Option<TowTruck>, AppError>;
+    async fn find_tow_truck_by_id(
+        &self,
+        organization_id: i32,
+        id: i32,
+    ) -> Result<Option<TowTruck>, AppError>;
+}
+
+// 溜めた車両の位置の書き込み先
+#[derive(Debug)]
+pub struct LocationWriter<T>(T);
+
+impl<T: TowTruckRepository> BatchWriter for LocationWriter<T> {
+    type Item = NewLocation;
+
+    async fn write_batch(&self, items: &[NewLocation]) -> Result<(), AppError> {
+        self.0.create_locations(items).await
+    }
 }
 
 #[derive(Debug)]
@@ -27,29 +56,59 @@ pub struct TowTruckService<
     tow_truck_repository: T,
     order_repository: U,
     map_repository: V,
+    geofence: Arc<Geofence>,
+    route_planner: Arc<RoutePlanner<V>>,
+    feature_flags: Arc<FeatureFlags>,
+    // 運転手のアプリから頻繁に送られる位置を溜めて、まとめて書き込む
+    locations: Arc<WriteBehindBuffer<LocationWriter<T>>>,
 }
 
 impl<
-        T: TowTruckRepository + std::fmt::Debug,
+        T: TowTruckRepository + std::fmt::Debug + Clone,
         U: OrderRepository + std::fmt::Debug,
         V: MapRepository + std::fmt::Debug,
     > TowTruckService<T, U, V>
 {
-    pub fn new(tow_truck_repository: T, order_repository: U, map_repository: V) -> Self {
+    pub fn new(
+        tow_truck_repository: T,
+        order_repository: U,
+        map_repository: V,
+        geofence: Arc<Geofence>,
+        route_planner: Arc<RoutePlanner<V>>,
+        feature_flags: Arc<FeatureFlags>,
+        write_behind: &WriteBehindConfig,
+    ) -> Self {
         TowTruckService {
+            locations: Arc::new(WriteBehindBuffer::new(
+                "車両の位置",
+                LocationWriter(tow_truck_repository.clone()),
+                write_behind.location_flush_interval,
+                write_behind,
+            )),
             tow_truck_repository,
             order_repository,
             map_repository,
+            geofence,
+            route_planner,
+            feature_flags,
         }
     }
 
-    pub async fn get_tow_truck_by_id(&self, id: i32) -> Result<Option<TowTruckDto>, AppError> {
-        let tow_truck = self.tow_truck_repository.find_tow_truck_by_id(id).await?;
+    pub async fn get_tow_truck_by_id(
+        &self,
+        organization: OrganizationScope,
+        id: i32,
+    ) -> Result<Option<TowTruckDto>, AppError> {
+        let tow_truck = self
+            .tow_truck_repository
+            .find_tow_truck_by_id(organization.organization_id, id)
+            .await?;
         Ok(tow_truck.map(TowTruckDto::from_entity))
     }
 
     pub async fn get_all_tow_trucks(
         &self,
+        organization: OrganizationScope,
         page: i32,
         page_size: i32,
         status: Option<String>,
@@ -57,7 +116,7 @@ impl<
     ) -> Result<Vec<TowTruckDto>, AppError> {
         let tow_trucks = self
             .tow_truck_repository
-            .get_paginated_tow_trucks(page, page_size, status, area)
+            .get_paginated_tow_trucks(organization.organization_id, page, page_size, status, area)
             .await?;
         let tow_truck_dtos = tow_trucks
             .into_iter()
@@ -67,47 +126,137 @@ impl<
         Ok(tow_truck_dtos)
     }
 
-    pub async fn update_location(&self, truck_id: i32, node_id: i32) -> Result<(), AppError> {
-        self.tow_truck_repository
-            .update_location(truck_id, node_id)
+    // 他の組織の車両や、存在しないノード、サービス提供範囲外のノードへの移動は受け付けない
+    pub async fn update_location(
+        &self,
+        organization: OrganizationScope,
+        truck_id: i32,
+        node_id: i32,
+    ) -> Result<(), AppError> {
+        if self
+            .tow_truck_repository
+            .find_tow_truck_by_id(organization.organization_id, truck_id)
+            .await?
+            .is_none()
+        {
+            return Err(AppError::NotFound);
+        }
+        if self
+            .map_repository
+            .find_node_area_id(organization.organization_id, node_id)
+            .await?
+            .is_none()
+        {
+            return Err(AppError::BadRequest);
+        }
+        let node = self
+            .map_repository
+            .find_node_by_id(node_id)
+            .await?
+            .ok_or(AppError::BadRequest)?;
+        self.geofence
+            .check(
+                &self.map_repository,
+                organization.organization_id,
+                node.x,
+                node.y,
+            )
             .await?;
+        if !self.locations.is_enabled() {
+            return self
+                .tow_truck_repository
+                .update_location(truck_id, node_id)
+                .await;
+        }
 
+        self.locations.push(NewLocation {
+            tow_truck_id: truck_id,
+            node_id,
+        });
         Ok(())
     }
 
+    // 停止時に、溜まっている位置を書き込む
+    pub async fn flush_locations(&self) -> Result<usize, AppError> {
+        self.locations.flush().await
+    }
+
     pub async fn get_nearest_available_tow_trucks(
         &self,
+        organization: OrganizationScope,
         order_id: i32,
     ) -> Result<Option<TowTruckDto>, AppError> {
-        let order = self.order_repository.find_order_by_id(order_id).await?;
+        let order = self
+            .order_repository
+            .find_order_by_id(organization.organization_id, order_id)
+            .await?;
         let area_id = self
             .map_repository
             .get_area_id_by_node_id(order.node_id)
             .await?;
         let tow_trucks = self
             .tow_truck_repository
-            .get_paginated_tow_trucks(0, -1, Some("available".to_string()), Some(area_id))
+            .get_paginated_tow_trucks(
+                organization.organization_id,
+                0,
+                -1,
+                Some(VehicleStatus::Available.as_str().to_string()),
+                Some(area_id),
+            )
             .await?;
 
-        let nodes = self.map_repository.get_all_nodes(Some(area_id)).await?;
-        let edges = self.map_repository.get_all_edges(Some(area_id)).await?;
-
-        let mut graph = Graph::new();
-        for node in nodes {
-            graph.add_node(node);
-        }
-        for edge in edges {
-            graph.add_edge(edge);
+        // 同じエリアで処理する順が先の未割り当ての注文に、空き車両を残しておく
+        if order.status == OrderStatus::Pending.as_str() {
+            let ahead = self
+                .order_repository
+                .count_pending_orders_ahead(organization.organization_id, order.id)
+                .await?;
+            if ahead >= tow_trucks.len() as i64 {
+                return Ok(None);
+            }
         }
 
-        let sorted_tow_trucks_by_distance = {
-            let mut tow_trucks_with_distance: Vec<_> = tow_trucks
+        let distances = if self
+            .feature_flags
+            .is_enabled(FeatureFlag::ContractionHierarchyMatching)
+        {
+            let truck_node_ids: Vec<i32> = tow_trucks.iter().map(|truck| truck.node_id).collect();
+            self.route_planner
+                .distance_matrix(area_id, &[order.node_id], &truck_node_ids)
+                .await?
+                .remove(0)
                 .into_iter()
-                .map(|truck| {
-                    let distance = calculate_distance(&graph, truck.node_id, order.node_id);
-                    (distance, truck)
+                .map(|distance| distance.unwrap_or(i32::MAX))
+                .collect()
+        } else {
+            let nodes = self.map_repository.get_all_nodes(Some(area_id)).await?;
+            let edges = self.map_repository.get_all_edges(Some(area_id)).await?;
+
+            let mut graph = Graph::new();
+            for node in nodes {
+                graph.add_node(node);
+            }
+            for edge in edges {
+                graph.add_edge(edge);
+            }
+
+            // 道路は双方向のため、注文のノードからの距離はレッカー車から注文までの距離と等しい
+            let distances_from_order = self
+                .feature_flags
+                .is_enabled(FeatureFlag::DijkstraMatching)
+                .then(|| graph.dijkstra_distances_from(order.node_id));
+            tow_trucks
+                .iter()
+                .map(|truck| match &distances_from_order {
+                    Some(distances) => *distances.get(&truck.node_id).unwrap_or(&i32::MAX),
+                    None => calculate_distance(&graph, truck.node_id, order.node_id),
                 })
-                .collect();
+                .collect::<Vec<i32>>()
+        };
+
+        let sorted_tow_trucks_by_distance = {
+            let mut tow_trucks_with_distance: Vec<_> =
+                distances.into_iter().zip(tow_trucks).collect();
 
             tow_trucks_with_distance.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
             tow_trucks_with_distance
@@ -130,3 +279,14 @@ impl<
 fn calculate_distance(graph: &Graph, node_id_1: i32, node_id_2: i32) -> i32 {
     graph.shortest_path(node_id_1, node_id_2)
 }
+
+impl<T, U, V> TowTruckService<T, U, V>
+where
+    T: TowTruckRepository + std::fmt::Debug + 'static,
+    U: OrderRepository + std::fmt::Debug,
+    V: MapRepository + std::fmt::Debug,
+{
+    pub fn spawn_location_flusher(&self) {
+        self.locations.spawn_flusher();
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt};
+use log::error;
+
+use crate::config::UserImportConfig;
+use crate::errors::AppError;
+use crate::infrastructure::i18n;
+use crate::infrastructure::worker_pool::WorkerPool;
+use crate::models::role::Role;
+use crate::models::user::NewUser;
+use crate::utils::{hash_password, normalize_username, parse_csv_line};
+
+use super::auth_service::USERNAME_RESERVATION_DAYS;
+use super::dto::user_import::{
+    ImportUserRequestDto, ImportUserResultDto, UserImportFormat, UserImportResponseDto,
+};
+
+pub trait UserImportRepository {
+    // 使用中のユーザー名と、最近ほかのユーザーが手放したユーザー名
+    async fn find_unavailable_usernames(
+        &self,
+        usernames: &[String],
+        since: DateTime<Utc>,
+    ) -> Result<HashSet<String>, AppError>;
+    async fn find_area_ids(&self) -> Result<HashSet<i32>, AppError>;
+    // users と同じ順で作成したユーザーの ID を返す
+    async fn create_users(&self, users: &[NewUser]) -> Result<Vec<i32>, AppError>;
+}
+
+#[derive(Debug)]
+pub struct UserImportService<T: UserImportRepository + std::fmt::Debug> {
+    repository: T,
+    config: UserImportConfig,
+    password_workers: Arc<WorkerPool>,
+}
+
+impl<T: UserImportRepository + std::fmt::Debug> UserImportService<T> {
+    pub fn new(
+        repository: T,
+        config: &UserImportConfig,
+        password_workers: Arc<WorkerPool>,
+    ) -> Self {
+        UserImportService {
+            repository,
+            config: config.clone(),
+            password_workers,
+        }
+    }
+
+    // 行ごとに成否を返す。失敗した行があっても、ほかの行は登録する
+    pub async fn import_users(
+        &self,
+        body: &[u8],
+        format: UserImportFormat,
+    ) -> Result<UserImportResponseDto, AppError> {
+        let rows: Vec<_> = match format {
+            UserImportFormat::Csv => parse_csv_rows(body)?,
+            UserImportFormat::Json => parse_json_rows(body)?,
+        }
+        .into_iter()
+        .map(|row| {
+            row.map(|mut row| {
+                row.username = normalize_username(&row.username);
+                row
+            })
+        })
+        .collect();
+        if rows.len() > self.config.max_rows {
+            return Err(AppError::PayloadTooLarge);
+        }
+
+        let mut results: Vec<ImportUserResultDto> = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| ImportUserResultDto {
+                row: index + 1,
+                username: row.as_ref().ok().map(|row| row.username.clone()),
+                user_id: None,
+                error: row.as_ref().err().map(|key| i18n::translate(key)),
+            })
+            .collect();
+
+        let usernames: Vec<String> = rows
+            .iter()
+            .flatten()
+            .map(|row| row.username.clone())
+            .collect();
+        let since = Utc::now() - chrono::Duration::days(USERNAME_RESERVATION_DAYS);
+        let unavailable = self
+            .repository
+            .find_unavailable_usernames(&usernames, since)
+            .await?;
+        let area_ids = self.repository.find_area_ids().await?;
+
+        let mut seen = HashSet::new();
+        let mut candidates = Vec::new();
+        for (index, row) in rows.into_iter().enumerate() {
+            let Ok(row) = row else { continue };
+            match validate_row(&row, &unavailable, &area_ids, &mut seen) {
+                Ok(()) => candidates.push((index, row)),
+                Err(key) => results[index].error = Some(i18n::translate(key)),
+            }
+        }
+
+        // Argon2 のハッシュ化は重いため、ログインと共用のプールを占有しないよう並列数を絞って投入する
+        let hashed: Vec<(usize, Result<NewUser, AppError>)> = stream::iter(candidates)
+            .map(|(index, row)| async move {
+                let ImportUserRequestDto {
+                    username,
+                    password,
+                    role,
+                    area_id,
+                } = row;
+                let hashed = self
+                    .password_workers
+                    .run(move || hash_password(&password))
+                    .await
+                    .and_then(|hashed| hashed);
+                let user = hashed.map(|password| NewUser {
+                    username,
+                    password,
+                    role,
+                    area_id,
+                });
+                (index, user)
+            })
+            .buffered(self.config.hash_parallelism.max(1))
+            .collect()
+            .await;
+
+        let mut users = Vec::new();
+        for (index, user) in hashed {
+            match user {
+                Ok(user) => users.push((index, user)),
+                Err(_) => results[index].error = Some(i18n::translate("user_import.hash_failed")),
+            }
+        }
+
+        for batch in users.chunks(self.config.batch_size.max(1)) {
+            let new_users: Vec<NewUser> = batch.iter().map(|(_, user)| user.clone()).collect();
+            match self.repository.create_users(&new_users).await {
+                Ok(user_ids) => {
+                    for ((index, _), user_id) in batch.iter().zip(user_ids) {
+                        results[*index].user_id = Some(user_id);
+                    }
+                }
+                // バッチ単位のトランザクションのため、失敗した場合はバッチ内のすべての行が未登録になる
+                Err(e) => {
+                    error!("ユーザーの一括登録に失敗しました: {:?}", e);
+                    for (index, _) in batch {
+                        results[*index].error = Some(i18n::translate("user_import.insert_failed"));
+                    }
+                }
+            }
+        }
+
+        let imported = results
+            .iter()
+            .filter(|result| result.user_id.is_some())
+            .count();
+
+        Ok(UserImportResponseDto {
+            imported,
+            failed: results.len() - imported,
+            results,
+        })
+    }
+}
+
+fn validate_row(
+    row: &ImportUserRequestDto,
+    unavailable: &HashSet<String>,
+    area_ids: &HashSet<i32>,
+    seen: &mut HashSet<String>,
+) -> Result<(), &'static str> {
+    if row.username.is_empty() || row.password.is_empty() {
+        return Err("user_import.credentials_required");
+    }
+    if unavailable.contains(&row.username) || !seen.insert(row.username.clone()) {
+        return Err("user_import.username_taken");
+    }
+    match (row.role, row.area_id) {
+        (Role::Dispatcher, None) => Err("user_import.area_required"),
+        (Role::Dispatcher, Some(area_id)) if !area_ids.contains(&area_id) => {
+            Err("user_import.area_not_found")
+        }
+        _ => Ok(()),
+    }
+}
+
+fn parse_json_rows(
+    body: &[u8],
+) -> Result<Vec<Result<ImportUserRequestDto, &'static str>>, AppError> {
+    let values: Vec<serde_json::Value> =
+        serde_json::from_slice(body).map_err(|_| AppError::BadRequest)?;
+
+    Ok(values
+        .into_iter()
+        .map(|value| serde_json::from_value(value).map_err(|_| "user_import.invalid_row"))
+        .collect())
+}
+
+fn parse_csv_rows(
+    body: &[u8],
+) -> Result<Vec<Result<ImportUserRequestDto, &'static str>>, AppError> {
+    let content = std::str::from_utf8(body).map_err(|_| AppError::BadRequest)?;
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+
+    let header: Vec<String> = match lines.next() {
+        Some(header) => parse_csv_line(header)
+            .into_iter()
+            .map(|column| column.unwrap_or_default().trim().to_string())
+            .collect(),
+        None => return Err(AppError::BadRequest),
+    };
+    let column = |name: &str| header.iter().position(|column| column == name);
+    let (Some(username), Some(password), Some(role)) =
+        (column("username"), column("password"), column("role"))
+    else {
+        return Err(AppError::BadRequest);
+    };
+    let area_id = column("area_id");
+
+    Ok(lines
+        .map(|line| {
+            let mut values = parse_csv_line(line);
+            let mut take = |index: usize| values.get_mut(index).and_then(Option::take);
+            Ok(ImportUserRequestDto {
+                username: take(username).ok_or("user_import.credentials_required")?,
+                password: take(password).ok_or("user_import.credentials_required")?,
+                role: take(role)
+                    .and_then(|role| role.parse().ok())
+                    .ok_or("user_import.invalid_role")?,
+                area_id: match area_id.and_then(&mut take) {
+                    Some(area_id) => {
+                        Some(area_id.parse().map_err(|_| "user_import.invalid_area_id")?)
+                    }
+                    None => None,
+                },
+            })
+        })
+        .collect())
+}
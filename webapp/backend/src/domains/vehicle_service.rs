@@ -0,0 +1,157 @@
+use super::auth_service::AuthRepository;
+use super::dto::vehicle::{RegisterVehicleRequestDto, UpdateVehicleRequestDto, VehicleDto};
+use super::map_service::MapRepository;
+use crate::errors::AppError;
+use crate::models::role::Role;
+use crate::models::vehicle::{Vehicle, VehicleStatus};
+
+pub trait VehicleRepository {
+    async fn create_vehicle(
+        &self,
+        driver_id: i32,
+        area_id: i32,
+        capacity: i32,
+        node_id: i32,
+    ) -> Result<i32, AppError>;
+    async fn find_vehicle_by_id(&self, id: i32) -> Result<Option<Vehicle>, AppError>;
+    async fn get_vehicles(
+        &self,
+        area_id: Option<i32>,
+        status: Option<VehicleStatus>,
+    ) -> Result<Vec<Vehicle>, AppError>;
+    async fn update_vehicle(
+        &self,
+        id: i32,
+        driver_id: i32,
+        area_id: i32,
+        capacity: i32,
+    ) -> Result<(), AppError>;
+    async fn update_vehicle_status(&self, id: i32, status: VehicleStatus) -> Result<(), AppError>;
+    async fn has_orders(&self, id: i32) -> Result<bool, AppError>;
+    async fn delete_vehicle(&self, id: i32) -> Result<(), AppError>;
+}
+
+#[derive(Debug)]
+pub struct VehicleService<
+    T: VehicleRepository + std::fmt::Debug,
+    U: AuthRepository + std::fmt::Debug,
+    V: MapRepository + std::fmt::Debug,
+> {
+    vehicle_repository: T,
+    auth_repository: U,
+    map_repository: V,
+}
+
+impl<
+        T: VehicleRepository + std::fmt::Debug,
+        U: AuthRepository + std::fmt::Debug,
+        V: MapRepository + std::fmt::Debug,
+    > VehicleService<T, U, V>
+{
+    pub fn new(vehicle_repository: T, auth_repository: U, map_repository: V) -> Self {
+        VehicleService {
+            vehicle_repository,
+            auth_repository,
+            map_repository,
+        }
+    }
+
+    pub async fn register_vehicle(
+        &self,
+        req: &RegisterVehicleRequestDto,
+    ) -> Result<VehicleDto, AppError> {
+        self.validate_assignment(req.driver_user_id, req.capacity)
+            .await?;
+        // 初期位置は担当エリア内のノードでなければならない
+        let node_area_id = self
+            .map_repository
+            .get_area_id_by_node_id(req.node_id)
+            .await
+            .map_err(|_| AppError::BadRequest)?;
+        if node_area_id != req.area_id {
+            return Err(AppError::BadRequest);
+        }
+
+        let id = self
+            .vehicle_repository
+            .create_vehicle(req.driver_user_id, req.area_id, req.capacity, req.node_id)
+            .await?;
+
+        self.get_vehicle(id).await
+    }
+
+    pub async fn get_vehicle(&self, id: i32) -> Result<VehicleDto, AppError> {
+        match self.vehicle_repository.find_vehicle_by_id(id).await? {
+            Some(vehicle) => Ok(VehicleDto::from_entity(vehicle)),
+            None => Err(AppError::NotFound),
+        }
+    }
+
+    pub async fn get_vehicles(
+        &self,
+        area_id: Option<i32>,
+        status: Option<VehicleStatus>,
+    ) -> Result<Vec<VehicleDto>, AppError> {
+        let vehicles = self
+            .vehicle_repository
+            .get_vehicles(area_id, status)
+            .await?;
+
+        Ok(vehicles.into_iter().map(VehicleDto::from_entity).collect())
+    }
+
+    pub async fn update_vehicle(
+        &self,
+        id: i32,
+        req: &UpdateVehicleRequestDto,
+    ) -> Result<VehicleDto, AppError> {
+        self.get_vehicle(id).await?;
+        self.validate_assignment(req.driver_user_id, req.capacity)
+            .await?;
+
+        self.vehicle_repository
+            .update_vehicle(id, req.driver_user_id, req.area_id, req.capacity)
+            .await?;
+
+        self.get_vehicle(id).await
+    }
+
+    // 配車の検索は available の車両だけを対象にするため、maintenance にすると候補から外れる
+    pub async fn update_vehicle_status(
+        &self,
+        id: i32,
+        status: VehicleStatus,
+    ) -> Result<VehicleDto, AppError> {
+        self.get_vehicle(id).await?;
+        self.vehicle_repository
+            .update_vehicle_status(id, status)
+            .await?;
+
+        self.get_vehicle(id).await
+    }
+
+    // 注文は車両の削除に連動して消えるため、配車の履歴がある車両は削除させない
+    pub async fn delete_vehicle(&self, id: i32) -> Result<(), AppError> {
+        self.get_vehicle(id).await?;
+        if self.vehicle_repository.has_orders(id).await? {
+            return Err(AppError::Conflict);
+        }
+
+        self.vehicle_repository.delete_vehicle(id).await
+    }
+
+    async fn validate_assignment(
+        &self,
+        driver_user_id: i32,
+        capacity: i32,
+    ) -> Result<(), AppError> {
+        if capacity <= 0 {
+            return Err(AppError::BadRequest);
+        }
+
+        match self.auth_repository.find_user_by_id(driver_user_id).await? {
+            Some(user) if user.role == Role::Driver && user.is_active => Ok(()),
+            _ => Err(AppError::BadRequest),
+        }
+    }
+}
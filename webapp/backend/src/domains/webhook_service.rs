@@ -0,0 +1,457 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt};
+use hmac::{Hmac, Mac};
+use log::{error, warn};
+use rand::Rng;
+use reqwest::Client;
+use serde_json::json;
+use sha2::Sha256;
+
+use super::dto::webhook::{
+    CreateWebhookSubscriptionRequestDto, WebhookDeliveryDto, WebhookSubscriptionCreatedDto,
+    WebhookSubscriptionDto,
+};
+use super::events::DomainEvent;
+use crate::config::WebhookConfig;
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::outbound_http::{ensure_public_url, outbound_client};
+use crate::infrastructure::request_id;
+use crate::models::webhook::{PendingWebhookDelivery, WebhookDelivery, WebhookSubscription};
+use crate::utils::generate_session_token;
+
+const ORDER_EVENTS: [&str; 7] = [
+    "order_created",
+    "order_dispatched",
+    "order_cancelled",
+    "order_completed",
+    "order_reassigned",
+    "order_requeued",
+    "order_eta_changed",
+];
+const DELIVERY_PENDING: &str = "pending";
+const DELIVERY_SUCCEEDED: &str = "succeeded";
+const DELIVERY_FAILED: &str = "failed";
+
+const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+const EVENT_HEADER: &str = "X-Webhook-Event";
+const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+const SECRET_MIN_LENGTH: usize = 16;
+const SECRET_MAX_LENGTH: usize = 255;
+const DELIVERY_MAX_LIMIT: i32 = 1000;
+// 記録する送信先のエラーメッセージの長さの上限
+const ERROR_MAX_LENGTH: usize = 1000;
+
+pub trait WebhookRepository {
+    async fn create_subscription(
+        &self,
+        url: &str,
+        secret: &str,
+        events: Option<&str>,
+    ) -> Result<i32, AppError>;
+    // 有効な登録だけを返す
+    async fn find_subscriptions(&self) -> Result<Vec<WebhookSubscription>, AppError>;
+    async fn find_subscription_by_id(
+        &self,
+        id: i32,
+    ) -> Result<Option<WebhookSubscription>, AppError>;
+    async fn deactivate_subscription(&self, id: i32) -> Result<bool, AppError>;
+    // イベントを受け取る有効な登録ごとに、送信待ちの配信を作る
+    async fn enqueue_deliveries(
+        &self,
+        event: &str,
+        payload: &str,
+        now: DateTime<Utc>,
+    ) -> Result<u64, AppError>;
+    async fn find_due_deliveries(
+        &self,
+        now: DateTime<Utc>,
+        limit: i32,
+    ) -> Result<Vec<PendingWebhookDelivery>, AppError>;
+    // 他のインスタンスと同じ配信を二重に送らないよう、次の送信時刻を lease_until に進めて確保する。
+    // 確保できた場合だけ true を返す
+    async fn claim_delivery(
+        &self,
+        id: i32,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+    ) -> Result<bool, AppError>;
+    // next_attempt_at が None の場合は再送せずに終える
+    async fn record_delivery_attempt(
+        &self,
+        id: i32,
+        status: &str,
+        attempts: i32,
+        status_code: Option<i32>,
+        error: Option<&str>,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) -> Result<(), AppError>;
+    async fn find_deliveries_by_subscription_id(
+        &self,
+        subscription_id: i32,
+        limit: i32,
+    ) -> Result<Vec<WebhookDelivery>, AppError>;
+}
+
+#[derive(Debug)]
+pub struct WebhookService<T: WebhookRepository + std::fmt::Debug> {
+    repository: T,
+    client: Client,
+    config: WebhookConfig,
+}
+
+impl<T> WebhookService<T>
+where
+    T: WebhookRepository + std::fmt::Debug + Clone + Send + Sync + 'static,
+{
+    // 注文のイベントを購読し、登録先ごとの配信を作る。送信は定期ジョブで行う
+    pub fn new(repository: T, config: &WebhookConfig, event_bus: &EventBus) -> Self {
+        let subscriber = repository.clone();
+        event_bus.subscribe(move |event| {
+            if let Some((event_name, payload)) = build_payload(event) {
+                let repository = subscriber.clone();
+                request_id::spawn(async move {
+                    if let Err(err) = repository
+                        .enqueue_deliveries(event_name, &payload, Utc::now())
+                        .await
+                    {
+                        error!(
+                            "イベント {} の Webhook の配信を登録できませんでした: {:?}",
+                            event_name, err
+                        );
+                    }
+                });
+            }
+        });
+
+        WebhookService {
+            repository,
+            client: outbound_client(config.timeout),
+            config: config.clone(),
+        }
+    }
+}
+
+impl<T: WebhookRepository + std::fmt::Debug> WebhookService<T> {
+    pub async fn create_subscription(
+        &self,
+        request: CreateWebhookSubscriptionRequestDto,
+    ) -> Result<WebhookSubscriptionCreatedDto, AppError> {
+        ensure_public_url(&request.url).await?;
+        let secret = match request.secret {
+            Some(secret) if (SECRET_MIN_LENGTH..=SECRET_MAX_LENGTH).contains(&secret.len()) => {
+                secret
+            }
+            Some(_) => return Err(AppError::BadRequest),
+            None => generate_session_token(),
+        };
+        if let Some(events) = &request.events {
+            if events.is_empty()
+                || events
+                    .iter()
+                    .any(|event| !ORDER_EVENTS.contains(&event.as_str()))
+            {
+                return Err(AppError::BadRequest);
+            }
+        }
+
+        let id = self
+            .repository
+            .create_subscription(
+                &request.url,
+                &secret,
+                request
+                    .events
+                    .as_ref()
+                    .map(|events| events.join(","))
+                    .as_deref(),
+            )
+            .await?;
+
+        // シークレットを返すのは登録時のこの一度だけ
+        Ok(WebhookSubscriptionCreatedDto {
+            id,
+            url: request.url,
+            secret,
+            events: request.events,
+        })
+    }
+
+    pub async fn get_subscriptions(&self) -> Result<Vec<WebhookSubscriptionDto>, AppError> {
+        let subscriptions = self.repository.find_subscriptions().await?;
+
+        Ok(subscriptions
+            .into_iter()
+            .map(|subscription| WebhookSubscriptionDto {
+                id: subscription.id,
+                events: subscription.event_list(),
+                url: subscription.url,
+                created_at: subscription.created_at,
+            })
+            .collect())
+    }
+
+    pub async fn delete_subscription(&self, id: i32) -> Result<(), AppError> {
+        match self.repository.deactivate_subscription(id).await? {
+            true => Ok(()),
+            false => Err(AppError::NotFound),
+        }
+    }
+
+    pub async fn get_deliveries(
+        &self,
+        subscription_id: i32,
+        limit: i32,
+    ) -> Result<Vec<WebhookDeliveryDto>, AppError> {
+        if !(1..=DELIVERY_MAX_LIMIT).contains(&limit) {
+            return Err(AppError::BadRequest);
+        }
+        if self
+            .repository
+            .find_subscription_by_id(subscription_id)
+            .await?
+            .is_none()
+        {
+            return Err(AppError::NotFound);
+        }
+
+        let deliveries = self
+            .repository
+            .find_deliveries_by_subscription_id(subscription_id, limit)
+            .await?;
+
+        Ok(deliveries
+            .into_iter()
+            .map(|delivery| WebhookDeliveryDto {
+                id: delivery.id,
+                subscription_id: delivery.subscription_id,
+                next_attempt_at: (delivery.status == DELIVERY_PENDING)
+                    .then_some(delivery.next_attempt_at),
+                event: delivery.event,
+                status: delivery.status,
+                attempts: delivery.attempts,
+                last_status_code: delivery.last_status_code,
+                last_error: delivery.last_error,
+                created_at: delivery.created_at,
+                updated_at: delivery.updated_at,
+            })
+            .collect())
+    }
+
+    // 定期ジョブから呼び出し、送信時刻を過ぎた配信を送る。送信した件数を返す
+    pub async fn deliver_due(&self) -> Result<usize, AppError> {
+        let now = Utc::now();
+        let deliveries = self
+            .repository
+            .find_due_deliveries(now, self.config.batch_size)
+            .await?;
+        // 送信中に落ちた場合でも、タイムアウトを過ぎれば他のインスタンスが送り直せる
+        let lease_until = now + self.config.timeout * 2;
+
+        let delivered = stream::iter(deliveries)
+            .map(|delivery| async move {
+                match self
+                    .repository
+                    .claim_delivery(delivery.id, now, lease_until)
+                    .await
+                {
+                    Ok(true) => self.deliver(delivery).await.map(|_| 1),
+                    Ok(false) => Ok(0),
+                    Err(err) => Err(err),
+                }
+            })
+            .buffer_unordered(self.config.concurrency)
+            .fold(Ok(0), |total, result| async move {
+                match (total, result) {
+                    (Ok(total), Ok(count)) => Ok(total + count),
+                    (Err(err), _) | (_, Err(err)) => Err(err),
+                }
+            })
+            .await?;
+
+        Ok(delivered)
+    }
+
+    async fn deliver(&self, delivery: PendingWebhookDelivery) -> Result<(), AppError> {
+        let attempts = delivery.attempts + 1;
+        let timestamp = Utc::now().timestamp().to_string();
+        let signature = sign(&delivery.secret, &timestamp, &delivery.payload);
+
+        // 登録した後で内部のアドレスを指すようになった送信先には送らず、失敗として記録する
+        let result = match ensure_public_url(&delivery.url).await {
+            Ok(()) => self
+                .client
+                .post(&delivery.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+                .header(TIMESTAMP_HEADER, &timestamp)
+                .header(EVENT_HEADER, &delivery.event)
+                .header(DELIVERY_HEADER, delivery.id.to_string())
+                .body(delivery.payload.clone())
+                .send()
+                .await
+                .map_err(|err| err.to_string()),
+            Err(_) => Err("destination not allowed".to_string()),
+        };
+        let (status_code, error) = match result {
+            Ok(response) if response.status().is_success() => {
+                return self
+                    .repository
+                    .record_delivery_attempt(
+                        delivery.id,
+                        DELIVERY_SUCCEEDED,
+                        attempts,
+                        Some(response.status().as_u16() as i32),
+                        None,
+                        None,
+                    )
+                    .await;
+            }
+            Ok(response) => (
+                Some(response.status().as_u16() as i32),
+                format!("status {}", response.status()),
+            ),
+            Err(error) => (None, error),
+        };
+        let error: String = error.chars().take(ERROR_MAX_LENGTH).collect();
+
+        if attempts >= self.config.max_attempts {
+            warn!(
+                "Webhook の配信 {} を {} 回試みましたが届きませんでした: {}",
+                delivery.id, attempts, error
+            );
+            return self
+                .repository
+                .record_delivery_attempt(
+                    delivery.id,
+                    DELIVERY_FAILED,
+                    attempts,
+                    status_code,
+                    Some(&error),
+                    None,
+                )
+                .await;
+        }
+
+        let next_attempt_at = Utc::now() + self.retry_delay(attempts);
+        self.repository
+            .record_delivery_attempt(
+                delivery.id,
+                DELIVERY_PENDING,
+                attempts,
+                status_code,
+                Some(&error),
+                Some(next_attempt_at),
+            )
+            .await
+    }
+
+    // 試行ごとに間隔を倍にし、送信先が復旧した直後に再送が集中しないよう後半をランダムにずらす
+    fn retry_delay(&self, attempts: i32) -> Duration {
+        let exponent = (attempts - 1).clamp(0, 30) as u32;
+        let delay = self
+            .config
+            .retry_base_delay
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.config.retry_max_delay);
+        delay / 2 + (delay / 2).mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+}
+
+// 受信側はタイムスタンプと本文を "." でつないだ文字列の HMAC-SHA256 で検証する
+fn sign(secret: &str, timestamp: &str, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn build_payload(event: &DomainEvent) -> Option<(&'static str, String)> {
+    let (event_name, order_id, data) = match event {
+        DomainEvent::OrderCreated { order_id, area_id } => {
+            (ORDER_EVENTS[0], *order_id, json!({ "area_id": area_id }))
+        }
+        DomainEvent::OrderDispatched {
+            order_id,
+            dispatcher_id,
+            tow_truck_id,
+        } => (
+            ORDER_EVENTS[1],
+            *order_id,
+            json!({ "dispatcher_id": dispatcher_id, "tow_truck_id": tow_truck_id }),
+        ),
+        DomainEvent::OrderCancelled {
+            order_id,
+            dispatcher_id,
+            tow_truck_id,
+            reason,
+        } => (
+            ORDER_EVENTS[2],
+            *order_id,
+            json!({
+                "dispatcher_id": dispatcher_id,
+                "tow_truck_id": tow_truck_id,
+                "reason": reason,
+            }),
+        ),
+        DomainEvent::OrderCompleted {
+            order_id,
+            dispatcher_id,
+            eta_error_minutes,
+        } => (
+            ORDER_EVENTS[3],
+            *order_id,
+            json!({ "dispatcher_id": dispatcher_id, "eta_error_minutes": eta_error_minutes }),
+        ),
+        DomainEvent::OrderReassigned {
+            order_id,
+            from_dispatcher_id,
+            to_dispatcher_id,
+        } => (
+            ORDER_EVENTS[4],
+            *order_id,
+            json!({
+                "from_dispatcher_id": from_dispatcher_id,
+                "to_dispatcher_id": to_dispatcher_id,
+            }),
+        ),
+        DomainEvent::OrderRequeued {
+            order_id,
+            dispatcher_id,
+            tow_truck_id,
+        } => (
+            ORDER_EVENTS[5],
+            *order_id,
+            json!({ "dispatcher_id": dispatcher_id, "tow_truck_id": tow_truck_id }),
+        ),
+        DomainEvent::OrderEtaChanged {
+            order_id,
+            eta_at,
+            eta_minutes,
+        } => (
+            ORDER_EVENTS[6],
+            *order_id,
+            json!({ "eta_at": eta_at, "eta_minutes": eta_minutes }),
+        ),
+        _ => return None,
+    };
+
+    let payload = json!({
+        "event": event_name,
+        "order_id": order_id,
+        "occurred_at": Utc::now(),
+        // イベントを発生させたリクエストの ID。受信側の調査でアクセスログと突き合わせる
+        "request_id": request_id::current(),
+        "data": data,
+    });
+    Some((event_name, payload.to_string()))
+}
@@ -1,44 +1,248 @@
-use actix_web::{HttpResponse, ResponseError};
+use std::time::Duration;
+
+use actix_web::error::{JsonPayloadError, QueryPayloadError};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use log::{debug, error};
 use serde::Serialize;
 use thiserror::Error;
 
+use crate::infrastructure::{i18n, request_id};
+use crate::models::area::NearbyArea;
+use crate::repositories::db_error;
+
 #[derive(Debug, Error)]
 pub enum AppError {
     #[error("Bad Request")]
     BadRequest,
     #[error("Unauthorized")]
     Unauthorized,
+    #[error("Two-factor authentication required")]
+    TwoFactorRequired,
+    // 仮のパスワードでログインした。new_password を付けて再度ログインさせる
+    #[error("Password change required")]
+    PasswordChangeRequired,
+    #[error("Forbidden")]
+    Forbidden,
     #[error("Not Found")]
     NotFound,
     #[error("Conflict")]
     Conflict,
+    #[error("Payload Too Large")]
+    PayloadTooLarge,
     #[error("Internal Server Error")]
     InternalServerError,
+    // 座標がどのエリアの境界にも含まれない。近くのエリアをレスポンスに含める
+    #[error("Outside Service Area")]
+    OutsideServiceArea(Vec<NearbyArea>),
+    // DB の障害で遮断中。再試行までの目安の時間を Retry-After で返す
+    #[error("Service Unavailable")]
+    ServiceUnavailable(Duration),
+    // リクエストの処理が期限内に終わらなかった
+    #[error("Gateway Timeout")]
+    GatewayTimeout,
+    // 組織の利用上限に達した。時間が経てば回復する上限は 429 と Retry-After、それ以外は 403 で返す
+    #[error("Quota Exceeded ({quota})")]
+    QuotaExceeded {
+        quota: &'static str,
+        retry_after: Option<Duration>,
+    },
+    // 重複やタイムアウトなど振り分けられる DB のエラーは、From の変換で対応する種類になる
+    #[error(transparent)]
+    SqlxError(sqlx::Error),
     #[error(transparent)]
-    SqlxError(#[from] sqlx::Error),
+    Io(#[from] std::io::Error),
+    // 失敗した操作と対象の ID を元のエラーに付けたもの。
+    // レスポンスは元のエラーから作り、操作と ID はログにだけ出す
+    #[error("{operation} failed{}", entity_id.map(|id| format!(" (id: {})", id)).unwrap_or_default())]
+    Context {
+        operation: &'static str,
+        entity_id: Option<i64>,
+        #[source]
+        source: Box<AppError>,
+    },
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(error: sqlx::Error) -> Self {
+        db_error::classify(error)
+    }
+}
+
+impl AppError {
+    pub fn with_context(self, operation: &'static str, entity_id: Option<i64>) -> Self {
+        AppError::Context {
+            operation,
+            entity_id,
+            source: Box::new(self),
+        }
+    }
+
+    // 文脈を取り除いた元のエラー。種類で分岐する場合はこちらを使う
+    pub fn root(&self) -> &AppError {
+        match self {
+            AppError::Context { source, .. } => source.root(),
+            e => e,
+        }
+    }
+
+    // メッセージカタログのキーとレスポンスの code に使う
+    pub fn code(&self) -> &'static str {
+        match self.root() {
+            AppError::BadRequest => "bad_request",
+            AppError::Unauthorized => "unauthorized",
+            AppError::TwoFactorRequired => "two_factor_required",
+            AppError::PasswordChangeRequired => "password_change_required",
+            AppError::Forbidden => "forbidden",
+            AppError::NotFound => "not_found",
+            AppError::Conflict => "conflict",
+            AppError::PayloadTooLarge => "payload_too_large",
+            AppError::OutsideServiceArea(_) => "outside_service_area",
+            AppError::ServiceUnavailable(_) => "service_unavailable",
+            AppError::GatewayTimeout => "gateway_timeout",
+            AppError::QuotaExceeded { .. } => "quota_exceeded",
+            AppError::InternalServerError
+            | AppError::SqlxError(_)
+            | AppError::Io(_)
+            | AppError::Context { .. } => "internal_server_error",
+        }
+    }
+
+    // 文脈と原因を外側から順に並べた、ログ向けの説明
+    pub fn chain(&self) -> String {
+        let mut message = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(e) = source {
+            message.push_str(": ");
+            message.push_str(&e.to_string());
+            source = e.source();
+        }
+        message
+    }
+}
+
+pub trait ResultExt<T> {
+    fn context(self, operation: &'static str) -> Result<T, AppError>;
+    fn context_with_id(self, operation: &'static str, entity_id: i64) -> Result<T, AppError>;
+}
+
+impl<T, E: Into<AppError>> ResultExt<T> for Result<T, E> {
+    fn context(self, operation: &'static str) -> Result<T, AppError> {
+        self.map_err(|e| e.into().with_context(operation, None))
+    }
+
+    fn context_with_id(self, operation: &'static str, entity_id: i64) -> Result<T, AppError> {
+        self.map_err(|e| e.into().with_context(operation, Some(entity_id)))
+    }
 }
 
 #[derive(Serialize)]
 struct ErrorResponse {
+    // 言語によらない識別子。クライアントはメッセージではなくこちらで分岐する
+    code: &'static str,
+    // Accept-Language に応じて翻訳したメッセージ
     message: String,
+    // 問い合わせの際にアクセスログと突き合わせられるよう、リクエスト ID を返す
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nearby_areas: Option<Vec<NearbyArea>>,
+    // 上限に達した利用上限の名前
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quota: Option<&'static str>,
 }
 
 impl ResponseError for AppError {
     fn error_response(&self) -> HttpResponse {
-        let error_message = self.to_string();
+        let root = self.root();
+        // DB やファイルのエラーの詳細はクライアントに返さず、ログにだけ残す
+        if matches!(root, AppError::SqlxError(_) | AppError::Io(_)) {
+            error!("{}", self.chain());
+        }
+        let code = self.code();
+        // カタログを読み込む前 (テストなど) は英語のメッセージを返す
+        let error_message = match (i18n::message(&format!("error.{}", code)), root) {
+            (Some(message), _) => message.to_string(),
+            (None, AppError::SqlxError(_) | AppError::Io(_)) => {
+                AppError::InternalServerError.to_string()
+            }
+            (None, root) => root.to_string(),
+        };
         let error_response = ErrorResponse {
+            code,
             message: error_message,
+            request_id: request_id::current(),
+            nearby_areas: match root {
+                AppError::OutsideServiceArea(nearby_areas) => Some(nearby_areas.clone()),
+                _ => None,
+            },
+            quota: match root {
+                AppError::QuotaExceeded { quota, .. } => Some(quota),
+                _ => None,
+            },
         };
 
-        match *self {
-            AppError::BadRequest => HttpResponse::BadRequest().json(error_response),
+        let mut response = match *root {
+            AppError::BadRequest | AppError::OutsideServiceArea(_) => {
+                HttpResponse::BadRequest().json(error_response)
+            }
             AppError::Unauthorized => HttpResponse::Unauthorized().json(error_response),
+            AppError::TwoFactorRequired => HttpResponse::Unauthorized().json(error_response),
+            AppError::PasswordChangeRequired => HttpResponse::Unauthorized().json(error_response),
+            AppError::Forbidden => HttpResponse::Forbidden().json(error_response),
             AppError::NotFound => HttpResponse::NotFound().json(error_response),
             AppError::Conflict => HttpResponse::Conflict().json(error_response),
+            AppError::PayloadTooLarge => HttpResponse::PayloadTooLarge().json(error_response),
             AppError::InternalServerError => {
                 HttpResponse::InternalServerError().json(error_response)
             }
-            AppError::SqlxError(_) => HttpResponse::InternalServerError().json(error_response),
+            AppError::ServiceUnavailable(retry_after) => HttpResponse::ServiceUnavailable()
+                .insert_header((
+                    header::RETRY_AFTER,
+                    retry_after.as_secs_f64().ceil().max(1.0) as u64,
+                ))
+                .json(error_response),
+            AppError::GatewayTimeout => HttpResponse::GatewayTimeout().json(error_response),
+            AppError::QuotaExceeded {
+                retry_after: Some(retry_after),
+                ..
+            } => HttpResponse::TooManyRequests()
+                .insert_header((
+                    header::RETRY_AFTER,
+                    retry_after.as_secs_f64().ceil().max(1.0) as u64,
+                ))
+                .json(error_response),
+            AppError::QuotaExceeded {
+                retry_after: None, ..
+            } => HttpResponse::Forbidden().json(error_response),
+            AppError::SqlxError(_) | AppError::Io(_) | AppError::Context { .. } => {
+                HttpResponse::InternalServerError().json(error_response)
+            }
+        };
+        if let Some(value) =
+            i18n::current_locale().and_then(|locale| HeaderValue::from_str(&locale).ok())
+        {
+            response
+                .headers_mut()
+                .insert(header::CONTENT_LANGUAGE, value);
+        }
+
+        response
+    }
+}
+
+// JSON の本文を解釈できなかった場合も、ほかのエラーと同じ形式で返す
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    debug!("リクエストの本文を解釈できませんでした: {}", err);
+    match err {
+        JsonPayloadError::OverflowKnownLength { .. } | JsonPayloadError::Overflow { .. } => {
+            AppError::PayloadTooLarge.into()
         }
+        _ => AppError::BadRequest.into(),
     }
 }
+
+pub fn query_error_handler(err: QueryPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    debug!("クエリ文字列を解釈できませんでした: {}", err);
+    AppError::BadRequest.into()
+}
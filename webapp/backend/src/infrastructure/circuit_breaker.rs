@@ -0,0 +1,173 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::rc::Rc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+tokio::task_local! {
+    static DB_ACTIVITY: Rc<DbActivity>;
+}
+
+// 1 件のリクエストの処理中に DB を使ったか、接続に関わる障害が起きたか
+#[derive(Debug, Default)]
+pub struct DbActivity {
+    used: Cell<bool>,
+    failed: Cell<bool>,
+}
+
+impl DbActivity {
+    pub fn used(&self) -> bool {
+        self.used.get()
+    }
+
+    pub fn failed(&self) -> bool {
+        self.failed.get()
+    }
+}
+
+// fut の中で mark_db_used と mark_db_failure を呼ぶと activity に記録される
+pub async fn track_db_activity<F: Future>(activity: Rc<DbActivity>, fut: F) -> F::Output {
+    DB_ACTIVITY.scope(activity, fut).await
+}
+
+// リクエストの処理中でない場合 (定期ジョブや spawn したタスク) は何もしない
+pub fn mark_db_used() {
+    let _ = DB_ACTIVITY.try_with(|activity| activity.used.set(true));
+}
+
+pub fn mark_db_failure() {
+    let _ = DB_ACTIVITY.try_with(|activity| {
+        activity.used.set(true);
+        activity.failed.set(true);
+    });
+}
+
+#[derive(Debug)]
+enum State {
+    // 直近 window 内の失敗時刻を保持する
+    Closed { failures: VecDeque<Instant> },
+    Open { until: Instant },
+    // 試行中のリクエストが結果を返さないまま破棄された場合に備え、開始時刻を持つ。
+    // 試行したリクエストが DB を使わなかった場合は None に戻し、次のリクエストで試す
+    HalfOpen { probe_started_at: Option<Instant> },
+}
+
+// DB の障害やタイムアウトが続いた場合にリクエストを即座に失敗させ、ワーカーが詰まるのを防ぐ
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: usize,
+    window: Duration,
+    open_duration: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    // failure_threshold が 0 の場合は常に通す
+    pub fn new(failure_threshold: usize, window: Duration, open_duration: Duration) -> Self {
+        CircuitBreaker {
+            failure_threshold,
+            window,
+            open_duration,
+            state: Mutex::new(State::Closed {
+                failures: VecDeque::new(),
+            }),
+        }
+    }
+
+    // 通せない場合は再試行までの目安の時間を返す
+    pub fn acquire(&self) -> Result<(), Duration> {
+        if self.failure_threshold == 0 {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::Closed { .. } => Ok(()),
+            State::Open { until } if now < until => Err(until - now),
+            // 期間が過ぎたら 1 件だけ試しに通し、その結果で閉じるか開き直すかを決める
+            State::Open { .. } => {
+                info!("DB のサーキットブレーカーを半開状態にします");
+                *state = State::HalfOpen {
+                    probe_started_at: Some(now),
+                };
+                Ok(())
+            }
+            State::HalfOpen {
+                probe_started_at: Some(probe_started_at),
+            } if now.duration_since(probe_started_at) < self.open_duration => {
+                Err(self.open_duration - now.duration_since(probe_started_at))
+            }
+            State::HalfOpen { .. } => {
+                *state = State::HalfOpen {
+                    probe_started_at: Some(now),
+                };
+                Ok(())
+            }
+        }
+    }
+
+    // DB を使わなかったリクエストは DB の状態を判断する材料にならないため、試行をやり直す
+    pub fn release_probe(&self) {
+        let mut state = self.state.lock().unwrap();
+        if let State::HalfOpen { .. } = *state {
+            *state = State::HalfOpen {
+                probe_started_at: None,
+            };
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if let State::HalfOpen { .. } = *state {
+            info!("DB のサーキットブレーカーを閉じます");
+            *state = State::Closed {
+                failures: VecDeque::new(),
+            };
+        }
+    }
+
+    pub fn record_failure(&self) {
+        if self.failure_threshold == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let trip = match &mut *state {
+            State::Closed { failures } => {
+                while failures
+                    .front()
+                    .is_some_and(|failed_at| now.duration_since(*failed_at) >= self.window)
+                {
+                    failures.pop_front();
+                }
+                failures.push_back(now);
+                failures.len() >= self.failure_threshold
+            }
+            State::Open { .. } => false,
+            State::HalfOpen { .. } => true,
+        };
+
+        if trip {
+            warn!(
+                "DB のエラーが続いたため、{:.1} 秒間リクエストを遮断します",
+                self.open_duration.as_secs_f64()
+            );
+            *state = State::Open {
+                until: now + self.open_duration,
+            };
+        }
+    }
+
+    pub fn state_name(&self) -> &'static str {
+        match *self.state.lock().unwrap() {
+            State::Closed { .. } => "closed",
+            State::Open { .. } => "open",
+            State::HalfOpen { .. } => "half_open",
+        }
+    }
+}
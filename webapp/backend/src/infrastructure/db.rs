@@ -1,9 +1,160 @@
-use sqlx::mysql::MySqlPool;
-use std::env;
+use futures_util::future::join_all;
+use log::{error, info};
+use sqlx::mysql::{MySqlConnectOptions, MySqlPool, MySqlPoolOptions};
+use sqlx::pool::PoolConnection;
+use sqlx::MySql;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
 
-pub async fn create_pool() -> MySqlPool {
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    MySqlPool::connect(&database_url)
+use crate::config::{DbConfig, RetryConfig};
+use crate::infrastructure::circuit_breaker::{self, CircuitBreaker};
+use crate::infrastructure::metrics::{AcquireMetrics, QueryMetrics, QueryTimer};
+use crate::infrastructure::retry;
+
+#[derive(Debug, Clone)]
+pub struct DbPools {
+    pub primary: MySqlPool,
+    pub replica: MySqlPool,
+    pub query_metrics: Arc<QueryMetrics>,
+    // レプリカを設定していない場合は primary と同じものを指す
+    pub primary_acquire_metrics: Arc<AcquireMetrics>,
+    pub replica_acquire_metrics: Arc<AcquireMetrics>,
+    pub circuit_breaker: Arc<CircuitBreaker>,
+    pub retry_config: Arc<RetryConfig>,
+}
+
+impl DbPools {
+    // リポジトリのメソッドの先頭で呼ばれるため、リクエストが DB を使ったことの記録も兼ねる
+    pub fn query_timer(&self, method: &'static str) -> QueryTimer<'_> {
+        circuit_breaker::mark_db_used();
+        self.query_metrics.start(method)
+    }
+
+    // 接続の取得にかかった時間を記録する。リポジトリはプールを直接渡さず、これで取得した接続でクエリを実行する
+    pub async fn acquire_primary(&self) -> Result<PoolConnection<MySql>, sqlx::Error> {
+        timed_acquire(&self.primary, &self.primary_acquire_metrics).await
+    }
+
+    pub async fn acquire_replica(&self) -> Result<PoolConnection<MySql>, sqlx::Error> {
+        timed_acquire(&self.replica, &self.replica_acquire_metrics).await
+    }
+
+    // デッドロックや接続の切断など一時的なエラーの場合に、バックオフを挟んで再実行する。冪等な読み取りにだけ使う
+    pub async fn retry<T, F, Fut>(
+        &self,
+        method: &'static str,
+        operation: F,
+    ) -> Result<T, sqlx::Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, sqlx::Error>>,
+    {
+        retry::retry(&self.retry_config, method, operation).await
+    }
+}
+
+async fn timed_acquire(
+    pool: &MySqlPool,
+    metrics: &AcquireMetrics,
+) -> Result<PoolConnection<MySql>, sqlx::Error> {
+    let started_at = Instant::now();
+    let conn = pool.acquire().await?;
+    metrics.record(started_at.elapsed());
+
+    Ok(conn)
+}
+
+pub async fn create_pools(config: &DbConfig) -> DbPools {
+    let primary = create_pool(config, &config.url).await;
+    let primary_acquire_metrics = Arc::new(AcquireMetrics::default());
+    let (replica, replica_acquire_metrics) = match &config.replica_url {
+        Some(replica_url) => (
+            create_pool(config, replica_url).await,
+            Arc::new(AcquireMetrics::default()),
+        ),
+        None => (primary.clone(), primary_acquire_metrics.clone()),
+    };
+
+    DbPools {
+        primary,
+        replica,
+        query_metrics: Arc::new(QueryMetrics::new(
+            config.slow_query_threshold,
+            config.explain_slow_queries,
+        )),
+        primary_acquire_metrics,
+        replica_acquire_metrics,
+        circuit_breaker: Arc::new(create_circuit_breaker(config)),
+        retry_config: Arc::new(config.retry.clone()),
+    }
+}
+
+// 起動時に接続しないプール。DB なしで起動する場合に使い、クエリを発行した時点で初めてエラーになる
+pub fn create_lazy_pools(config: &DbConfig) -> DbPools {
+    let primary = pool_options(config).connect_lazy_with(connect_options(config, &config.url));
+    let acquire_metrics = Arc::new(AcquireMetrics::default());
+
+    DbPools {
+        replica: primary.clone(),
+        primary,
+        query_metrics: Arc::new(QueryMetrics::new(
+            config.slow_query_threshold,
+            config.explain_slow_queries,
+        )),
+        primary_acquire_metrics: acquire_metrics.clone(),
+        replica_acquire_metrics: acquire_metrics,
+        circuit_breaker: Arc::new(create_circuit_breaker(config)),
+        retry_config: Arc::new(config.retry.clone()),
+    }
+}
+
+fn create_circuit_breaker(config: &DbConfig) -> CircuitBreaker {
+    CircuitBreaker::new(
+        config.breaker_failure_threshold,
+        config.breaker_window,
+        config.breaker_open_duration,
+    )
+}
+
+async fn create_pool(config: &DbConfig, url: &str) -> MySqlPool {
+    pool_options(config)
+        .connect_with(connect_options(config, url))
         .await
         .expect("Failed to create pool")
 }
+
+fn pool_options(config: &DbConfig) -> MySqlPoolOptions {
+    MySqlPoolOptions::new()
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .connect_timeout(config.acquire_timeout)
+}
+
+fn connect_options(config: &DbConfig, url: &str) -> MySqlConnectOptions {
+    MySqlConnectOptions::from_str(url)
+        .expect("DATABASE_URL is invalid")
+        .statement_cache_capacity(config.statement_cache_capacity)
+}
+
+// ベンチマーク開始直後の接続確立待ちを避けるため、起動時に接続を張っておく
+pub async fn warm_up_pool(pool: &MySqlPool, connections: u32) {
+    let results = join_all((0..connections).map(|_| async {
+        let mut conn = pool.acquire().await?;
+        sqlx::query("SELECT 1").execute(&mut conn).await
+    }))
+    .await;
+
+    let failed = results.iter().filter(|result| result.is_err()).count();
+    if failed > 0 {
+        error!(
+            "DB コネクションのウォームアップに {} 件失敗しました",
+            failed
+        );
+    }
+    info!(
+        "DB コネクションを {} 件ウォームアップしました",
+        results.len() - failed
+    );
+}
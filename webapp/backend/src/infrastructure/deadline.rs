@@ -0,0 +1,26 @@
+use std::future::Future;
+use std::time::Instant;
+
+use crate::errors::AppError;
+
+tokio::task_local! {
+    static DEADLINE: Instant;
+}
+
+// fut の中から current() で期限を参照できるようにする
+pub async fn scope<F: Future>(deadline: Instant, fut: F) -> F::Output {
+    DEADLINE.scope(deadline, fut).await
+}
+
+// リクエストの処理中でない場合 (定期ジョブなど) は None
+pub fn current() -> Option<Instant> {
+    DEADLINE.try_with(|deadline| *deadline).ok()
+}
+
+// spawn_blocking などタスクの外で実行する処理には、current() で取得した期限を渡して確認する
+pub fn check(deadline: Option<Instant>) -> Result<(), AppError> {
+    match deadline {
+        Some(deadline) if Instant::now() >= deadline => Err(AppError::GatewayTimeout),
+        _ => Ok(()),
+    }
+}
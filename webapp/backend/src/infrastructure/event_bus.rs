@@ -0,0 +1,36 @@
+use std::sync::RwLock;
+
+use crate::domains::events::DomainEvent;
+
+type Subscriber = Box<dyn Fn(&DomainEvent) + Send + Sync>;
+
+// 購読者は publish を呼んだスレッドで同期的に実行される。
+// キャッシュの無効化のように、発行直後から結果が反映されている必要がある処理を想定している
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: RwLock<Vec<Subscriber>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus::default()
+    }
+
+    pub fn subscribe(&self, subscriber: impl Fn(&DomainEvent) + Send + Sync + 'static) {
+        self.subscribers.write().unwrap().push(Box::new(subscriber));
+    }
+
+    pub fn publish(&self, event: DomainEvent) {
+        for subscriber in self.subscribers.read().unwrap().iter() {
+            subscriber(&event);
+        }
+    }
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("subscribers", &self.subscribers.read().unwrap().len())
+            .finish()
+    }
+}
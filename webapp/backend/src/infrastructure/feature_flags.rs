@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use log::warn;
+
+use crate::config::FeatureFlagConfig;
+
+// 再起動せずに切り替えたい最適化の一覧。フラグを増やす場合はここに追加する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FeatureFlag {
+    // ユーザーとディスパッチャーの行をメモリにキャッシュする
+    AuthUserCache,
+    // レッカー車の割り当てで、注文のノードから 1 回だけ Dijkstra 法で距離を求める
+    DijkstraMatching,
+    // レッカー車の割り当てで、縮約階層法で前処理したグラフを使って距離を求める。
+    // dijkstra_matching より優先する
+    ContractionHierarchyMatching,
+}
+
+impl FeatureFlag {
+    pub const ALL: [FeatureFlag; 3] = [
+        FeatureFlag::AuthUserCache,
+        FeatureFlag::DijkstraMatching,
+        FeatureFlag::ContractionHierarchyMatching,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeatureFlag::AuthUserCache => "auth_user_cache",
+            FeatureFlag::DijkstraMatching => "dijkstra_matching",
+            FeatureFlag::ContractionHierarchyMatching => "contraction_hierarchy_matching",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        FeatureFlag::ALL
+            .into_iter()
+            .find(|flag| flag.as_str() == name)
+    }
+
+    fn default_enabled(&self) -> bool {
+        match self {
+            FeatureFlag::AuthUserCache => true,
+            FeatureFlag::DijkstraMatching => false,
+            FeatureFlag::ContractionHierarchyMatching => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureFlagSource {
+    Default,
+    Env,
+    Database,
+}
+
+impl FeatureFlagSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FeatureFlagSource::Default => "default",
+            FeatureFlagSource::Env => "env",
+            FeatureFlagSource::Database => "database",
+        }
+    }
+}
+
+// フラグの現在値。既定値、環境変数、DB の順に後のものを優先する。
+// 判定は頻繁に呼ばれるため、DB の値はメモリに置き、定期的に読み直して差し替える
+#[derive(Debug)]
+pub struct FeatureFlags {
+    env: HashMap<FeatureFlag, bool>,
+    overrides: RwLock<HashMap<FeatureFlag, bool>>,
+}
+
+impl FeatureFlags {
+    pub fn new(config: &FeatureFlagConfig) -> Self {
+        let mut env = HashMap::new();
+        for (name, enabled) in &config.defaults {
+            match FeatureFlag::parse(name) {
+                Some(flag) => {
+                    env.insert(flag, *enabled);
+                }
+                None => warn!("未知のフィーチャーフラグ {} は無視します", name),
+            }
+        }
+
+        FeatureFlags {
+            env,
+            overrides: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self, flag: FeatureFlag) -> bool {
+        self.get(flag).0
+    }
+
+    pub fn get(&self, flag: FeatureFlag) -> (bool, FeatureFlagSource) {
+        if let Some(enabled) = self.overrides.read().unwrap().get(&flag) {
+            return (*enabled, FeatureFlagSource::Database);
+        }
+        match self.env.get(&flag) {
+            Some(enabled) => (*enabled, FeatureFlagSource::Env),
+            None => (flag.default_enabled(), FeatureFlagSource::Default),
+        }
+    }
+
+    // DB から読んだ値で置き換える。DB から消えたフラグは環境変数か既定値に戻る
+    pub fn replace_overrides(&self, overrides: HashMap<FeatureFlag, bool>) {
+        *self.overrides.write().unwrap() = overrides;
+    }
+
+    pub fn set_override(&self, flag: FeatureFlag, enabled: Option<bool>) {
+        let mut overrides = self.overrides.write().unwrap();
+        match enabled {
+            Some(enabled) => overrides.insert(flag, enabled),
+            None => overrides.remove(&flag),
+        };
+    }
+}
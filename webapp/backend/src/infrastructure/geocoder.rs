@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use log::warn;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::Deserialize;
+
+use crate::config::{GeocoderConfig, HttpGeocoderConfig};
+use crate::errors::AppError;
+
+// 外部の API が失敗した場合に、再試行までの目安として返す時間
+const GEOCODER_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+// 住所と座標を相互に変換する。見つからなかった場合は Ok(None) を返す
+pub trait Geocoder {
+    async fn geocode(&self, address: &str) -> Result<Option<(i32, i32)>, AppError>;
+    async fn reverse_geocode(&self, x: i32, y: i32) -> Result<Option<String>, AppError>;
+}
+
+#[derive(Debug)]
+pub enum GeocoderImpl {
+    Disabled,
+    Http(HttpGeocoder),
+}
+
+impl GeocoderImpl {
+    pub fn from_config(config: &GeocoderConfig) -> Self {
+        match config {
+            GeocoderConfig::Disabled => GeocoderImpl::Disabled,
+            GeocoderConfig::Http(http_config) => {
+                GeocoderImpl::Http(HttpGeocoder::new(http_config.clone()))
+            }
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, GeocoderImpl::Disabled)
+    }
+}
+
+impl Geocoder for GeocoderImpl {
+    async fn geocode(&self, address: &str) -> Result<Option<(i32, i32)>, AppError> {
+        match self {
+            GeocoderImpl::Disabled => Ok(None),
+            GeocoderImpl::Http(geocoder) => geocoder.geocode(address).await,
+        }
+    }
+
+    async fn reverse_geocode(&self, x: i32, y: i32) -> Result<Option<String>, AppError> {
+        match self {
+            GeocoderImpl::Disabled => Ok(None),
+            GeocoderImpl::Http(geocoder) => geocoder.reverse_geocode(x, y).await,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct GeocodeResponse {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Deserialize, Debug)]
+struct ReverseGeocodeResponse {
+    address: String,
+}
+
+// GET {base_url}/geocode?address=... と GET {base_url}/reverse?x=...&y=... を呼び出す。
+// 見つからない場合は 404 を返す API を想定する
+#[derive(Debug)]
+pub struct HttpGeocoder {
+    client: Client,
+    config: HttpGeocoderConfig,
+}
+
+impl HttpGeocoder {
+    pub fn new(config: HttpGeocoderConfig) -> Self {
+        HttpGeocoder {
+            client: Client::builder()
+                .timeout(config.timeout)
+                .build()
+                .unwrap_or_default(),
+            config,
+        }
+    }
+
+    async fn fetch<T: serde::de::DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<Option<T>, AppError> {
+        let request = match &self.config.api_key {
+            Some(api_key) => request.bearer_auth(api_key),
+            None => request,
+        };
+        let response = request.send().await.map_err(|e| {
+            warn!("ジオコーディング API を呼び出せませんでした: {:?}", e);
+            AppError::ServiceUnavailable(GEOCODER_RETRY_AFTER)
+        })?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        response
+            .error_for_status()
+            .map_err(|e| {
+                warn!("ジオコーディング API がエラーを返しました: {:?}", e);
+                AppError::ServiceUnavailable(GEOCODER_RETRY_AFTER)
+            })?
+            .json::<T>()
+            .await
+            .map(Some)
+            .map_err(|e| {
+                warn!(
+                    "ジオコーディング API のレスポンスを解析できませんでした: {:?}",
+                    e
+                );
+                AppError::ServiceUnavailable(GEOCODER_RETRY_AFTER)
+            })
+    }
+}
+
+impl Geocoder for HttpGeocoder {
+    async fn geocode(&self, address: &str) -> Result<Option<(i32, i32)>, AppError> {
+        let request = self
+            .client
+            .get(format!(
+                "{}/geocode",
+                self.config.base_url.trim_end_matches('/')
+            ))
+            .query(&[("address", address)]);
+
+        Ok(self
+            .fetch::<GeocodeResponse>(request)
+            .await?
+            .map(|response| (response.x, response.y)))
+    }
+
+    async fn reverse_geocode(&self, x: i32, y: i32) -> Result<Option<String>, AppError> {
+        let request = self
+            .client
+            .get(format!(
+                "{}/reverse",
+                self.config.base_url.trim_end_matches('/')
+            ))
+            .query(&[("x", x), ("y", y)]);
+
+        Ok(self
+            .fetch::<ReverseGeocodeResponse>(request)
+            .await?
+            .map(|response| response.address))
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::OnceLock;
+
+use log::{info, warn};
+
+use crate::config::I18nConfig;
+
+// ビルド時に埋め込むメッセージカタログ。LOCALE_DIR のファイルで上書き・追加できる
+const BUILTIN_CATALOGS: [(&str, &str); 2] = [
+    ("en", include_str!("../../locales/en.json")),
+    ("ja", include_str!("../../locales/ja.json")),
+];
+
+// どのカタログにもないメッセージは、最後にこのロケールで探す
+const FALLBACK_LOCALE: &str = "en";
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+tokio::task_local! {
+    static LOCALE: String;
+}
+
+#[derive(Debug)]
+struct Catalog {
+    default_locale: String,
+    messages: HashMap<String, HashMap<String, String>>,
+}
+
+// 起動時に一度だけ呼び出す
+pub fn init(config: &I18nConfig) {
+    let mut messages: HashMap<String, HashMap<String, String>> = HashMap::new();
+    for (locale, content) in BUILTIN_CATALOGS {
+        let catalog = serde_json::from_str(content).expect("builtin catalog must be valid JSON");
+        messages.insert(locale.to_string(), catalog);
+    }
+
+    if let Some(dir) = &config.dir {
+        match std::fs::read_dir(dir) {
+            Ok(entries) => {
+                for path in entries.flatten().map(|entry| entry.path()) {
+                    let Some(locale) = path
+                        .file_stem()
+                        .filter(|_| path.extension().is_some_and(|ext| ext == "json"))
+                        .and_then(|stem| stem.to_str())
+                        .map(str::to_ascii_lowercase)
+                    else {
+                        continue;
+                    };
+                    let catalog: HashMap<String, String> = match std::fs::read_to_string(&path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|content| {
+                            serde_json::from_str(&content).map_err(|e| e.to_string())
+                        }) {
+                        Ok(catalog) => catalog,
+                        Err(e) => {
+                            warn!(
+                                "メッセージカタログ {} を読み込めませんでした: {}",
+                                path.display(),
+                                e
+                            );
+                            continue;
+                        }
+                    };
+                    info!("メッセージカタログ {} を読み込みました", path.display());
+                    messages.entry(locale).or_default().extend(catalog);
+                }
+            }
+            Err(e) => warn!(
+                "メッセージカタログのディレクトリ {} を読み込めませんでした: {:?}",
+                dir.display(),
+                e
+            ),
+        }
+    }
+
+    let default_locale = match messages.contains_key(&config.default_locale) {
+        true => config.default_locale.clone(),
+        false => {
+            warn!(
+                "既定のロケール {} のカタログがないため {} を使います",
+                config.default_locale, FALLBACK_LOCALE
+            );
+            FALLBACK_LOCALE.to_string()
+        }
+    };
+    let _ = CATALOG.set(Catalog {
+        default_locale,
+        messages,
+    });
+}
+
+// Accept-Language のうち、カタログがあるロケールを q の高い順に探す。
+// "ja-JP" のように地域まで指定されたものは、なければ "ja" で探す
+pub fn negotiate(accept_language: Option<&str>) -> String {
+    let Some(catalog) = CATALOG.get() else {
+        return FALLBACK_LOCALE.to_string();
+    };
+
+    let mut candidates: Vec<(f32, String)> = accept_language
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';').map(str::trim);
+            let tag = parts.next().filter(|tag| !tag.is_empty() && *tag != "*")?;
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+            (quality > 0.0).then(|| (quality, tag.to_ascii_lowercase()))
+        })
+        .collect();
+    // 同じ q の場合はヘッダーに書かれた順を保つ
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    candidates
+        .into_iter()
+        .find_map(|(_, tag)| {
+            let primary = tag.split('-').next().unwrap_or_default().to_string();
+            [tag, primary]
+                .into_iter()
+                .find(|locale| catalog.messages.contains_key(locale))
+        })
+        .unwrap_or_else(|| catalog.default_locale.clone())
+}
+
+// fut の中の message() が locale のカタログを使うようにする
+pub async fn scope<F: Future>(locale: String, fut: F) -> F::Output {
+    LOCALE.scope(locale, fut).await
+}
+
+// リクエストのロケール、既定のロケール、英語の順に探す。どこにもなければ None
+pub fn message(key: &str) -> Option<&'static str> {
+    let catalog = CATALOG.get()?;
+    let locale = LOCALE.try_with(|locale| locale.clone()).ok();
+
+    let message = [
+        locale.as_deref(),
+        Some(catalog.default_locale.as_str()),
+        Some(FALLBACK_LOCALE),
+    ]
+    .into_iter()
+    .flatten()
+    .find_map(|locale| catalog.messages.get(locale)?.get(key))
+    .map(String::as_str);
+    message
+}
+
+// カタログにないキーはそのまま返す
+pub fn translate(key: &str) -> String {
+    message(key).unwrap_or(key).to_string()
+}
+
+// レスポンスの Content-Language に使う、現在のロケール
+pub fn current_locale() -> Option<String> {
+    LOCALE.try_with(|locale| locale.clone()).ok()
+}
@@ -0,0 +1,180 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+use log::{info, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::{HttpImageModeratorConfig, ImageModeratorConfig};
+use crate::errors::AppError;
+
+// 外部の API が失敗した場合に、再試行までの目安として返す時間
+const IMAGE_MODERATOR_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModerationVerdict {
+    Allow,
+    // 画像は残したまま、管理者に確認を求める
+    Flag(String),
+    // 画像を差し替える前のものに戻す
+    Reject(String),
+}
+
+// アップロードされた画像が公開してよいものかを判定する
+pub trait ImageModerator {
+    async fn moderate(&self, image: &[u8]) -> Result<ModerationVerdict, AppError>;
+}
+
+#[derive(Debug)]
+pub enum ImageModeratorImpl {
+    Disabled,
+    Local(LocalImageModerator),
+    Http(HttpImageModerator),
+}
+
+impl ImageModeratorImpl {
+    pub fn from_config(config: &ImageModeratorConfig) -> Self {
+        match config {
+            ImageModeratorConfig::Disabled => ImageModeratorImpl::Disabled,
+            ImageModeratorConfig::Local { blocklist } => {
+                ImageModeratorImpl::Local(LocalImageModerator::new(blocklist.as_deref()))
+            }
+            ImageModeratorConfig::Http(http_config) => {
+                ImageModeratorImpl::Http(HttpImageModerator::new(http_config.clone()))
+            }
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, ImageModeratorImpl::Disabled)
+    }
+}
+
+impl ImageModerator for ImageModeratorImpl {
+    async fn moderate(&self, image: &[u8]) -> Result<ModerationVerdict, AppError> {
+        match self {
+            ImageModeratorImpl::Disabled => Ok(ModerationVerdict::Allow),
+            ImageModeratorImpl::Local(moderator) => moderator.moderate(image).await,
+            ImageModeratorImpl::Http(moderator) => moderator.moderate(image).await,
+        }
+    }
+}
+
+// 判定モデルを組み込むまでの代わり。不適切と判断された画像の SHA-256 (16 進数、1 行に 1 つ) と一致すれば戻す
+#[derive(Debug)]
+pub struct LocalImageModerator {
+    blocked_digests: HashSet<String>,
+}
+
+impl LocalImageModerator {
+    pub fn new(blocklist: Option<&Path>) -> Self {
+        let blocked_digests: HashSet<String> = match blocklist {
+            Some(path) => match std::fs::read_to_string(path) {
+                Ok(content) => content
+                    .lines()
+                    .map(|line| line.trim().to_ascii_lowercase())
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .collect(),
+                Err(e) => {
+                    warn!(
+                        "画像の審査の一覧 {} を読み込めませんでした: {:?}",
+                        path.display(),
+                        e
+                    );
+                    HashSet::new()
+                }
+            },
+            None => HashSet::new(),
+        };
+        info!(
+            "画像の審査の一覧を {} 件読み込みました",
+            blocked_digests.len()
+        );
+
+        LocalImageModerator { blocked_digests }
+    }
+}
+
+impl ImageModerator for LocalImageModerator {
+    async fn moderate(&self, image: &[u8]) -> Result<ModerationVerdict, AppError> {
+        let digest = format!("{:x}", Sha256::digest(image));
+
+        Ok(match self.blocked_digests.contains(&digest) {
+            true => ModerationVerdict::Reject("blocklisted".to_string()),
+            false => ModerationVerdict::Allow,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+enum HttpVerdict {
+    Allow,
+    Flag,
+    Reject,
+}
+
+#[derive(Deserialize, Debug)]
+struct HttpModerationResponse {
+    verdict: HttpVerdict,
+    reason: Option<String>,
+}
+
+// POST {url} に画像をそのまま送り、{"verdict": "allow" | "flag" | "reject", "reason": "..."} を受け取る API を想定する
+#[derive(Debug)]
+pub struct HttpImageModerator {
+    client: Client,
+    config: HttpImageModeratorConfig,
+}
+
+impl HttpImageModerator {
+    pub fn new(config: HttpImageModeratorConfig) -> Self {
+        HttpImageModerator {
+            client: Client::builder()
+                .timeout(config.timeout)
+                .build()
+                .unwrap_or_default(),
+            config,
+        }
+    }
+}
+
+impl ImageModerator for HttpImageModerator {
+    async fn moderate(&self, image: &[u8]) -> Result<ModerationVerdict, AppError> {
+        let request = self
+            .client
+            .post(&self.config.url)
+            .header(reqwest::header::CONTENT_TYPE, "image/png")
+            .body(image.to_vec());
+        let request = match &self.config.api_key {
+            Some(api_key) => request.bearer_auth(api_key),
+            None => request,
+        };
+        let response = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| {
+                warn!("画像の審査の API を呼び出せませんでした: {:?}", e);
+                AppError::ServiceUnavailable(IMAGE_MODERATOR_RETRY_AFTER)
+            })?
+            .json::<HttpModerationResponse>()
+            .await
+            .map_err(|e| {
+                warn!(
+                    "画像の審査の API のレスポンスを解析できませんでした: {:?}",
+                    e
+                );
+                AppError::ServiceUnavailable(IMAGE_MODERATOR_RETRY_AFTER)
+            })?;
+        let reason = response.reason.unwrap_or_default();
+
+        Ok(match response.verdict {
+            HttpVerdict::Allow => ModerationVerdict::Allow,
+            HttpVerdict::Flag => ModerationVerdict::Flag(reason),
+            HttpVerdict::Reject => ModerationVerdict::Reject(reason),
+        })
+    }
+}
@@ -0,0 +1,240 @@
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use log::error;
+use reqwest::{Client, Method, StatusCode};
+use sha2::{Digest, Sha256};
+
+use crate::config::{ImageStoreConfig, S3Config};
+use crate::domains::image_service::ImageStore;
+use crate::errors::{AppError, ResultExt};
+
+#[derive(Debug, Clone)]
+pub enum ImageStoreImpl {
+    Local(LocalImageStore),
+    S3(S3ImageStore),
+}
+
+impl ImageStoreImpl {
+    pub fn from_config(config: &ImageStoreConfig) -> Self {
+        match config {
+            ImageStoreConfig::Local { root_dir } => {
+                ImageStoreImpl::Local(LocalImageStore::new(root_dir.clone()))
+            }
+            ImageStoreConfig::S3(s3_config) => {
+                ImageStoreImpl::S3(S3ImageStore::new(s3_config.clone()))
+            }
+        }
+    }
+}
+
+impl ImageStore for ImageStoreImpl {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AppError> {
+        match self {
+            ImageStoreImpl::Local(store) => store.get(key).await,
+            ImageStoreImpl::S3(store) => store.get(key).await,
+        }
+    }
+
+    async fn put(&self, key: &str, bytes: &[u8], content_type: &str) -> Result<(), AppError> {
+        match self {
+            ImageStoreImpl::Local(store) => store.put(key, bytes, content_type).await,
+            ImageStoreImpl::S3(store) => store.put(key, bytes, content_type).await,
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), AppError> {
+        match self {
+            ImageStoreImpl::Local(store) => store.delete(key).await,
+            ImageStoreImpl::S3(store) => store.delete(key).await,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalImageStore {
+    root_dir: PathBuf,
+}
+
+impl LocalImageStore {
+    pub fn new(root_dir: PathBuf) -> Self {
+        LocalImageStore { root_dir }
+    }
+}
+
+impl ImageStore for LocalImageStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AppError> {
+        match std::fs::read(self.root_dir.join(key)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(AppError::from(e).with_context("local_image_store.get", None)),
+        }
+    }
+
+    async fn put(&self, key: &str, bytes: &[u8], _content_type: &str) -> Result<(), AppError> {
+        std::fs::write(self.root_dir.join(key), bytes).context("local_image_store.put")
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), AppError> {
+        match std::fs::remove_file(self.root_dir.join(key)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(AppError::from(e).with_context("local_image_store.delete", None)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct S3ImageStore {
+    client: Client,
+    config: S3Config,
+}
+
+impl S3ImageStore {
+    pub fn new(config: S3Config) -> Self {
+        S3ImageStore {
+            client: Client::new(),
+            config,
+        }
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        body: Vec<u8>,
+        content_type: Option<&str>,
+    ) -> Result<reqwest::Response, AppError> {
+        // MinIO でも使えるよう path-style の URL でアクセスする
+        let canonical_uri = format!("/{}/{}", self.config.bucket, uri_encode(key));
+        let url = format!(
+            "{}{}",
+            self.config.endpoint.trim_end_matches('/'),
+            canonical_uri
+        );
+        let host = url
+            .split("://")
+            .nth(1)
+            .and_then(|rest| rest.split('/').next())
+            .unwrap_or_default()
+            .to_string();
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = format!("{:x}", Sha256::digest(&body));
+
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\nhost;x-amz-content-sha256;x-amz-date\n{}",
+            method, canonical_uri, host, payload_hash, amz_date, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+            amz_date,
+            scope,
+            Sha256::digest(canonical_request.as_bytes())
+        );
+
+        let signing_key = [self.config.region.as_bytes(), b"s3", b"aws4_request"]
+            .iter()
+            .fold(
+                hmac_sha256(
+                    format!("AWS4{}", self.config.secret_access_key).as_bytes(),
+                    date.as_bytes(),
+                ),
+                |key, data| hmac_sha256(&key, data),
+            );
+        let signature = hex_encode(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={}",
+            self.config.access_key_id, scope, signature
+        );
+
+        let mut request = self
+            .client
+            .request(method, &url)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("authorization", authorization);
+        if let Some(content_type) = content_type {
+            request = request.header("content-type", content_type);
+        }
+
+        request.body(body).send().await.map_err(|e| {
+            error!(
+                "オブジェクトストレージへのリクエストに失敗しました: {:?}",
+                e
+            );
+            AppError::InternalServerError
+        })
+    }
+}
+
+impl ImageStore for S3ImageStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, AppError> {
+        let response = self.send(Method::GET, key, Vec::new(), None).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => match response.bytes().await {
+                Ok(bytes) => Ok(Some(bytes.to_vec())),
+                Err(e) => {
+                    error!("オブジェクトの読み込みに失敗しました: {:?}", e);
+                    Err(AppError::InternalServerError)
+                }
+            },
+            status => {
+                error!("オブジェクトの取得に失敗しました: {}", status);
+                Err(AppError::InternalServerError)
+            }
+        }
+    }
+
+    async fn put(&self, key: &str, bytes: &[u8], content_type: &str) -> Result<(), AppError> {
+        let response = self
+            .send(Method::PUT, key, bytes.to_vec(), Some(content_type))
+            .await?;
+        match response.status().is_success() {
+            true => Ok(()),
+            false => {
+                error!("オブジェクトの保存に失敗しました: {}", response.status());
+                Err(AppError::InternalServerError)
+            }
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), AppError> {
+        let response = self.send(Method::DELETE, key, Vec::new(), None).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(()),
+            status if status.is_success() => Ok(()),
+            status => {
+                error!("オブジェクトの削除に失敗しました: {}", status);
+                Err(AppError::InternalServerError)
+            }
+        }
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn uri_encode(key: &str) -> String {
+    key.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
@@ -0,0 +1,106 @@
+use log::{info, warn};
+use sqlx::mysql::MySqlPool;
+
+use crate::errors::AppError;
+
+// 頻繁に実行される検索のために存在しているべきインデックス。(テーブル, 先頭の列, 作成する場合の名前)
+const EXPECTED_INDEXES: [(&str, &[&str], &str); 4] = [
+    // リクエストごとの認証でセッションを引く
+    ("sessions", &["session_token"], "idx_sessions_session_token"),
+    // ログインとユーザー名の重複の確認。全文検索のインデックスでは一致の検索に使われない
+    ("users", &["username"], "idx_users_username"),
+    // 状態とエリアで絞り込む注文の一覧。エリアはノードを経由して絞り込む
+    (
+        "orders",
+        &["organization_id", "status", "node_id"],
+        "idx_orders_organization_id_status_node_id",
+    ),
+    ("nodes", &["area_id"], "idx_nodes_area_id"),
+];
+
+// テーブルのインデックスの名前と、インデックスに含まれる列の順序・種類
+#[derive(sqlx::FromRow, Debug)]
+struct IndexColumn {
+    index_name: String,
+    column_name: String,
+    index_type: String,
+}
+
+// 期待するインデックスが存在するかを確認する。
+// 足りないインデックスは、create_missing が true の場合は作成し、false の場合は作成する DDL を警告として出力する。
+// 確認に失敗しても起動は止めない
+pub async fn check_indexes(pool: &MySqlPool, create_missing: bool) {
+    for (table, columns, index_name) in EXPECTED_INDEXES {
+        match has_index(pool, table, columns).await {
+            Ok(true) => {}
+            Ok(false) => {
+                let ddl = format!(
+                    "ALTER TABLE {} ADD INDEX {} ({}), ALGORITHM=INPLACE, LOCK=NONE",
+                    table,
+                    index_name,
+                    columns.join(", ")
+                );
+                if !create_missing {
+                    warn!(
+                        "!!! テーブル {} に ({}) のインデックスがありません。全件走査になるため、次の DDL で作成してください: {}",
+                        table,
+                        columns.join(", "),
+                        ddl
+                    );
+                    continue;
+                }
+
+                info!("テーブル {} にインデックスを作成します: {}", table, ddl);
+                if let Err(e) = sqlx::query(&ddl).execute(pool).await {
+                    warn!(
+                        "!!! テーブル {} のインデックスを作成できませんでした: {:?}",
+                        table, e
+                    );
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "テーブル {} のインデックスを確認できませんでした: {:?}",
+                    table, e
+                );
+            }
+        }
+    }
+}
+
+// columns を先頭の列に持つインデックスがあるか。全文検索・空間インデックスは数えない
+async fn has_index(pool: &MySqlPool, table: &str, columns: &[&str]) -> Result<bool, AppError> {
+    let index_columns = sqlx::query_as::<_, IndexColumn>(
+        "SELECT
+            INDEX_NAME AS index_name,
+            COLUMN_NAME AS column_name,
+            INDEX_TYPE AS index_type
+        FROM
+            information_schema.STATISTICS
+        WHERE
+            TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?
+        ORDER BY
+            INDEX_NAME, SEQ_IN_INDEX",
+    )
+    .bind(table)
+    .fetch_all(pool)
+    .await?;
+
+    let mut indexes: Vec<(&str, Vec<&str>)> = Vec::new();
+    for column in index_columns
+        .iter()
+        .filter(|column| column.index_type == "BTREE")
+    {
+        match indexes.last_mut() {
+            Some((name, index)) if *name == column.index_name => {
+                index.push(column.column_name.as_str())
+            }
+            _ => indexes.push((
+                column.index_name.as_str(),
+                vec![column.column_name.as_str()],
+            )),
+        }
+    }
+
+    Ok(indexes.iter().any(|(_, index)| index.starts_with(columns)))
+}
@@ -0,0 +1,32 @@
+use std::future::Future;
+use std::time::Duration;
+
+use actix_web::rt;
+use log::{error, info};
+
+use crate::errors::AppError;
+
+// 指定した間隔でジョブを繰り返し実行する。間隔が 0 の場合はジョブを登録しない
+pub fn spawn_periodic_job<F, Fut>(name: &'static str, period: Duration, job: F)
+where
+    F: Fn() -> Fut + 'static,
+    Fut: Future<Output = Result<(), AppError>> + 'static,
+{
+    if period.is_zero() {
+        info!("定期ジョブ {} は無効化されています", name);
+        return;
+    }
+
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(period);
+        // 起動直後の即時実行を避けるため、最初の tick を読み捨てる
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = job().await {
+                error!("定期ジョブ {} の実行に失敗しました: {:?}", name, e);
+            }
+        }
+    });
+}
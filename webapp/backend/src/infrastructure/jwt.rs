@@ -0,0 +1,58 @@
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::AppError;
+use crate::models::role::Role;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionClaims {
+    pub sub: i32,
+    pub sid: String,
+    pub role: Role,
+    pub dispatcher_id: Option<i32>,
+    pub area_id: Option<i32>,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+pub struct JwtCodec {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    validation: Validation,
+}
+
+impl JwtCodec {
+    pub fn new(secret: &str) -> Self {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+
+        JwtCodec {
+            encoding_key: EncodingKey::from_secret(secret.as_bytes()),
+            decoding_key: DecodingKey::from_secret(secret.as_bytes()),
+            validation,
+        }
+    }
+
+    pub fn encode(&self, claims: &SessionClaims) -> Result<String, AppError> {
+        jsonwebtoken::encode(&Header::new(Algorithm::HS256), claims, &self.encoding_key).map_err(
+            |e| {
+                error!("JWT の発行に失敗しました: {:?}", e);
+                AppError::InternalServerError
+            },
+        )
+    }
+
+    // 署名と有効期限を検証し、不正なトークンであれば None を返す
+    pub fn decode(&self, token: &str) -> Option<SessionClaims> {
+        jsonwebtoken::decode::<SessionClaims>(token, &self.decoding_key, &self.validation)
+            .map(|data| data.claims)
+            .ok()
+    }
+}
+
+impl std::fmt::Debug for JwtCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtCodec").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::Write;
+use std::sync::{OnceLock, RwLock};
+
+use log::{Log, Metadata, Record};
+
+use crate::infrastructure::request_id;
+
+static LOGGER: OnceLock<ReloadableLogger> = OnceLock::new();
+
+// env_logger のフィルタを実行中に差し替えられるようにしたロガー
+struct ReloadableLogger {
+    inner: RwLock<env_logger::Logger>,
+}
+
+impl Log for ReloadableLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.read().unwrap().enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.inner.read().unwrap().log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.read().unwrap().flush();
+    }
+}
+
+pub fn init(filter: &str) {
+    let logger = LOGGER.get_or_init(|| ReloadableLogger {
+        inner: RwLock::new(build(filter)),
+    });
+    log::set_logger(logger).expect("logger is already initialized");
+    log::set_max_level(logger.inner.read().unwrap().filter());
+}
+
+// RUST_LOG と同じ書式のフィルタに切り替える
+pub fn set_filter(filter: &str) {
+    if let Some(logger) = LOGGER.get() {
+        let inner = build(filter);
+        log::set_max_level(inner.filter());
+        *logger.inner.write().unwrap() = inner;
+    }
+}
+
+// リクエストの処理中に出力したログには、そのリクエストの ID を付ける
+fn build(filter: &str) -> env_logger::Logger {
+    env_logger::Builder::new()
+        .parse_filters(filter)
+        .format(|buf, record| {
+            let timestamp = buf.timestamp();
+            match request_id::current() {
+                Some(request_id) => writeln!(
+                    buf,
+                    "[{} {} {} request_id={}] {}",
+                    timestamp,
+                    record.level(),
+                    record.target(),
+                    request_id,
+                    record.args()
+                ),
+                None => writeln!(
+                    buf,
+                    "[{} {} {}] {}",
+                    timestamp,
+                    record.level(),
+                    record.target(),
+                    record.args()
+                ),
+            }
+        })
+        .build()
+}
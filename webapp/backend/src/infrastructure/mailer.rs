@@ -0,0 +1,176 @@
+use std::io;
+
+use actix_web::rt;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::Utc;
+use log::{error, info};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::config::{MailerConfig, SmtpConfig};
+use crate::errors::AppError;
+
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+pub trait Mailer {
+    async fn send(&self, mail: &Mail) -> Result<(), AppError>;
+}
+
+#[derive(Debug)]
+pub enum MailerImpl {
+    Log(LogMailer),
+    Smtp(SmtpMailer),
+}
+
+impl MailerImpl {
+    pub fn from_config(config: &MailerConfig) -> Self {
+        match config {
+            MailerConfig::Log => MailerImpl::Log(LogMailer),
+            MailerConfig::Smtp(smtp_config) => {
+                MailerImpl::Smtp(SmtpMailer::new(smtp_config.clone()))
+            }
+        }
+    }
+}
+
+impl Mailer for MailerImpl {
+    async fn send(&self, mail: &Mail) -> Result<(), AppError> {
+        // ヘッダーに改行を含めて任意のヘッダーを差し込まれないようにする
+        if [&mail.to, &mail.subject]
+            .iter()
+            .any(|value| value.contains(['\r', '\n']))
+        {
+            return Err(AppError::BadRequest);
+        }
+
+        match self {
+            MailerImpl::Log(mailer) => mailer.send(mail).await,
+            MailerImpl::Smtp(mailer) => mailer.send(mail).await,
+        }
+    }
+}
+
+// 送信せずにログへ出力する。ローカル開発と、メールサーバーのない環境向け
+#[derive(Debug)]
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+    async fn send(&self, mail: &Mail) -> Result<(), AppError> {
+        info!(
+            "メールを送信します (宛先: {}, 件名: {})\n{}",
+            mail.to, mail.subject, mail.body
+        );
+
+        Ok(())
+    }
+}
+
+// TLS は扱わないため、同じホストやプライベートネットワーク内のリレーに渡す想定
+#[derive(Debug)]
+pub struct SmtpMailer {
+    config: SmtpConfig,
+}
+
+impl SmtpMailer {
+    pub fn new(config: SmtpConfig) -> Self {
+        SmtpMailer { config }
+    }
+
+    async fn deliver(&self, mail: &Mail) -> io::Result<()> {
+        let stream = TcpStream::connect((self.config.host.as_str(), self.config.port)).await?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        expect_reply(&mut reader, &[220]).await?;
+        writer.write_all(b"EHLO localhost\r\n").await?;
+        expect_reply(&mut reader, &[250]).await?;
+        if let (Some(username), Some(password)) = (&self.config.username, &self.config.password) {
+            let credentials = STANDARD.encode(format!("\0{}\0{}", username, password));
+            writer
+                .write_all(format!("AUTH PLAIN {}\r\n", credentials).as_bytes())
+                .await?;
+            expect_reply(&mut reader, &[235]).await?;
+        }
+        writer
+            .write_all(format!("MAIL FROM:<{}>\r\n", self.config.from).as_bytes())
+            .await?;
+        expect_reply(&mut reader, &[250]).await?;
+        writer
+            .write_all(format!("RCPT TO:<{}>\r\n", mail.to).as_bytes())
+            .await?;
+        expect_reply(&mut reader, &[250, 251]).await?;
+        writer.write_all(b"DATA\r\n").await?;
+        expect_reply(&mut reader, &[354]).await?;
+        writer
+            .write_all(build_message(&self.config.from, mail).as_bytes())
+            .await?;
+        expect_reply(&mut reader, &[250]).await?;
+        writer.write_all(b"QUIT\r\n").await?;
+
+        Ok(())
+    }
+}
+
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: &Mail) -> Result<(), AppError> {
+        let result = match rt::time::timeout(self.config.timeout, self.deliver(mail)).await {
+            Ok(result) => result,
+            Err(_) => Err(io::ErrorKind::TimedOut.into()),
+        };
+
+        result.map_err(|e| {
+            error!("メールの送信に失敗しました (宛先: {}): {:?}", mail.to, e);
+            AppError::InternalServerError
+        })
+    }
+}
+
+// 本文を base64 で送るため、行頭の "." をエスケープする必要はない
+fn build_message(from: &str, mail: &Mail) -> String {
+    let body = STANDARD.encode(mail.body.as_bytes());
+    let body_lines: Vec<&str> = body
+        .as_bytes()
+        .chunks(76)
+        .map(|line| std::str::from_utf8(line).unwrap())
+        .collect();
+
+    format!(
+        "From: <{}>\r\nTo: <{}>\r\nSubject: =?UTF-8?B?{}?=\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=UTF-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n.\r\n",
+        from,
+        mail.to,
+        STANDARD.encode(mail.subject.as_bytes()),
+        Utc::now().to_rfc2822(),
+        body_lines.join("\r\n")
+    )
+}
+
+// 複数行の応答は "250-..." が続き、最後の行だけ "250 ..." になる
+async fn expect_reply<R: AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+    expected: &[u16],
+) -> io::Result<()> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if line.as_bytes().get(3) != Some(&b'-') {
+            break;
+        }
+    }
+
+    match line.get(..3).and_then(|code| code.parse::<u16>().ok()) {
+        Some(code) if expected.contains(&code) => Ok(()),
+        _ => Err(io::Error::other(format!(
+            "unexpected SMTP reply: {}",
+            line.trim_end()
+        ))),
+    }
+}
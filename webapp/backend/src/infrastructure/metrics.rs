@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::warn;
+use serde::Serialize;
+use sqlx::mysql::{MySqlArguments, MySqlRow};
+use sqlx::{MySqlPool, Row};
+
+use crate::infrastructure::request_id;
+
+#[derive(Serialize, Debug)]
+pub struct PoolMetrics {
+    pub size: u32,
+    pub idle: usize,
+    pub in_use: usize,
+    pub max_connections: u32,
+    // 起動してからリポジトリが接続を取得した回数と、取得までの待ち時間
+    pub acquire_count: u64,
+    pub acquire_wait_avg_ms: f64,
+    pub acquire_wait_max_ms: f64,
+}
+
+// 計測のために接続を取得すると、それ自体が混雑の原因になるため、実際の取得で記録した値だけを返す
+pub fn collect_pool_metrics(
+    pool: &MySqlPool,
+    max_connections: u32,
+    acquire_metrics: &AcquireMetrics,
+) -> PoolMetrics {
+    let size = pool.size();
+    let idle = pool.num_idle();
+    let acquire_count = acquire_metrics.count.load(Ordering::Relaxed);
+    let total_us = acquire_metrics.total_us.load(Ordering::Relaxed);
+
+    PoolMetrics {
+        size,
+        idle,
+        in_use: (size as usize).saturating_sub(idle),
+        max_connections,
+        acquire_count,
+        acquire_wait_avg_ms: total_us as f64 / acquire_count.max(1) as f64 / 1000.0,
+        acquire_wait_max_ms: acquire_metrics.max_us.load(Ordering::Relaxed) as f64 / 1000.0,
+    }
+}
+
+// プールから接続を取得するまでの待ち時間。リクエストのたびに記録するため、ロックを取らずに集計する
+#[derive(Debug, Default)]
+pub struct AcquireMetrics {
+    count: AtomicU64,
+    total_us: AtomicU64,
+    max_us: AtomicU64,
+}
+
+impl AcquireMetrics {
+    pub fn record(&self, elapsed: Duration) {
+        let elapsed_us = elapsed.as_micros() as u64;
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_us.fetch_add(elapsed_us, Ordering::Relaxed);
+        self.max_us.fetch_max(elapsed_us, Ordering::Relaxed);
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RepositoryMethodMetrics {
+    pub method: &'static str,
+    pub count: u64,
+    pub slow_count: u64,
+    pub total_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Default)]
+struct MethodStats {
+    count: u64,
+    slow_count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+#[derive(Debug)]
+pub struct QueryMetrics {
+    // 設定の再読み込みで変更できるよう、ミリ秒単位で保持する
+    slow_threshold_ms: AtomicU64,
+    // デバッグ用。スロークエリの実行計画を EXPLAIN で取得してログに添える
+    explain_slow_queries: bool,
+    stats: Mutex<HashMap<&'static str, MethodStats>>,
+}
+
+impl QueryMetrics {
+    pub fn new(slow_threshold: Duration, explain_slow_queries: bool) -> Self {
+        QueryMetrics {
+            slow_threshold_ms: AtomicU64::new(slow_threshold.as_millis() as u64),
+            explain_slow_queries,
+            stats: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_slow_threshold(&self, slow_threshold: Duration) {
+        self.slow_threshold_ms
+            .store(slow_threshold.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    // 返り値を保持している間の経過時間を、drop 時にメソッド単位で記録する
+    pub fn start(&self, method: &'static str) -> QueryTimer<'_> {
+        QueryTimer {
+            metrics: self,
+            method,
+            started_at: Instant::now(),
+            plan_target: None,
+        }
+    }
+
+    fn record(&self, method: &'static str, elapsed: Duration, plan_target: Option<PlanTarget>) {
+        let slow_threshold = Duration::from_millis(self.slow_threshold_ms.load(Ordering::Relaxed));
+        let is_slow = !slow_threshold.is_zero() && elapsed >= slow_threshold;
+        if is_slow {
+            // バインドパラメータには個人情報が含まれ得るため、メソッド名と時間のみを出力する。
+            // 実行計画も値を含まない従来の形式で取得する
+            match plan_target {
+                Some(target) => request_id::spawn(log_slow_query_plan(method, elapsed, target)),
+                None => warn!(
+                    "スロークエリ: {} に {:.1}ms かかりました",
+                    method,
+                    elapsed.as_secs_f64() * 1000.0
+                ),
+            }
+        }
+
+        let mut stats = self.stats.lock().unwrap();
+        let entry = stats.entry(method).or_default();
+        entry.count += 1;
+        entry.total += elapsed;
+        entry.max = entry.max.max(elapsed);
+        if is_slow {
+            entry.slow_count += 1;
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<RepositoryMethodMetrics> {
+        let stats = self.stats.lock().unwrap();
+        let mut metrics: Vec<RepositoryMethodMetrics> = stats
+            .iter()
+            .map(|(method, stats)| {
+                let total_ms = stats.total.as_secs_f64() * 1000.0;
+                RepositoryMethodMetrics {
+                    method,
+                    count: stats.count,
+                    slow_count: stats.slow_count,
+                    total_ms,
+                    avg_ms: total_ms / stats.count.max(1) as f64,
+                    max_ms: stats.max.as_secs_f64() * 1000.0,
+                }
+            })
+            .collect();
+        // 合計時間の大きいものがホットスポットなので先頭に並べる
+        metrics.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+
+        metrics
+    }
+}
+
+// 実行計画を取得するクエリと、バインドする値の複製
+#[derive(Debug)]
+struct PlanTarget {
+    pool: MySqlPool,
+    sql: String,
+    arguments: MySqlArguments,
+}
+
+pub struct QueryTimer<'a> {
+    metrics: &'a QueryMetrics,
+    method: &'static str,
+    started_at: Instant,
+    plan_target: Option<PlanTarget>,
+}
+
+impl QueryTimer<'_> {
+    // 閾値を超えた場合に実行計画を取得できるよう、実行するクエリを控える。
+    // 実行計画の取得が無効な場合は何もしないため、通常の運用では複製の負荷がかからない
+    pub fn explain_if_slow(
+        mut self,
+        pool: &MySqlPool,
+        sql: &str,
+        arguments: &MySqlArguments,
+    ) -> Self {
+        if self.metrics.explain_slow_queries {
+            self.plan_target = Some(PlanTarget {
+                pool: pool.clone(),
+                sql: sql.to_string(),
+                arguments: arguments.clone(),
+            });
+        }
+        self
+    }
+}
+
+impl Drop for QueryTimer<'_> {
+    fn drop(&mut self) {
+        self.metrics.record(
+            self.method,
+            self.started_at.elapsed(),
+            self.plan_target.take(),
+        );
+    }
+}
+
+async fn log_slow_query_plan(method: &'static str, elapsed: Duration, target: PlanTarget) {
+    let sql = format!("EXPLAIN FORMAT=TRADITIONAL {}", target.sql);
+    let plan = match sqlx::query_with(&sql, target.arguments)
+        .fetch_all(&target.pool)
+        .await
+    {
+        Ok(rows) => rows
+            .iter()
+            .map(format_plan_row)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Err(e) => format!("実行計画を取得できませんでした: {:?}", e),
+    };
+
+    warn!(
+        "スロークエリ: {} に {:.1}ms かかりました\n{}",
+        method,
+        elapsed.as_secs_f64() * 1000.0,
+        plan
+    );
+}
+
+// 全件走査 (type=ALL) や一時テーブル・ファイルソートが読み取れるよう、EXPLAIN の主な列を 1 行にまとめる
+fn format_plan_row(row: &MySqlRow) -> String {
+    [
+        "id",
+        "select_type",
+        "table",
+        "type",
+        "possible_keys",
+        "key",
+        "rows",
+        "filtered",
+        "Extra",
+    ]
+    .iter()
+    .map(|column| format!("{}={}", column, plan_column(row, column)))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+// EXPLAIN の列の型はサーバーのバージョンによって異なるため、文字列と数値を順に試す
+fn plan_column(row: &MySqlRow, column: &str) -> String {
+    if let Ok(value) = row.try_get::<Option<String>, _>(column) {
+        return value.unwrap_or_else(|| "NULL".to_string());
+    }
+    if let Ok(value) = row.try_get::<Option<i64>, _>(column) {
+        return value.map_or_else(|| "NULL".to_string(), |value| value.to_string());
+    }
+    if let Ok(value) = row.try_get::<Option<u64>, _>(column) {
+        return value.map_or_else(|| "NULL".to_string(), |value| value.to_string());
+    }
+    if let Ok(value) = row.try_get::<Option<f64>, _>(column) {
+        return value.map_or_else(|| "NULL".to_string(), |value| value.to_string());
+    }
+    "?".to_string()
+}
@@ -0,0 +1,83 @@
+use log::info;
+use sqlx::mysql::MySqlPool;
+use sqlx::{Executor, Row};
+
+use crate::errors::AppError;
+
+// build.rs が ../mysql/migration から生成する (バージョン, ファイル名, SQL) の一覧
+include!(concat!(env!("OUT_DIR"), "/migrations.rs"));
+
+const MIGRATION_LOCK_NAME: &str = "schema_migrations";
+const MIGRATION_LOCK_TIMEOUT_SECS: i64 = 60;
+
+// 未適用のマイグレーションを順に適用する。複数のインスタンスが同時に起動しても二重に適用されないよう、
+// 名前付きロックを取得した接続ですべての処理を行う。
+// baseline 以下のバージョンは restore_and_migration.sh などで適用済みとみなし、記録だけ行う
+pub async fn run_migrations(pool: &MySqlPool, baseline: Option<i64>) -> Result<(), AppError> {
+    let mut conn = pool.acquire().await?;
+
+    let locked: Option<i64> = sqlx::query_scalar("SELECT GET_LOCK(?, ?)")
+        .bind(MIGRATION_LOCK_NAME)
+        .bind(MIGRATION_LOCK_TIMEOUT_SECS)
+        .fetch_one(&mut conn)
+        .await?;
+    if locked != Some(1) {
+        return Err(AppError::InternalServerError);
+    }
+
+    let result = apply_pending(&mut conn, baseline).await;
+
+    sqlx::query("SELECT RELEASE_LOCK(?)")
+        .bind(MIGRATION_LOCK_NAME)
+        .execute(&mut conn)
+        .await?;
+
+    result
+}
+
+async fn apply_pending(
+    conn: &mut sqlx::pool::PoolConnection<sqlx::MySql>,
+    baseline: Option<i64>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version BIGINT PRIMARY KEY,
+            name VARCHAR(255) NOT NULL,
+            applied_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )",
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let applied: Vec<i64> = sqlx::query("SELECT version FROM schema_migrations")
+        .fetch_all(&mut *conn)
+        .await?
+        .iter()
+        .map(|row| row.get("version"))
+        .collect();
+
+    for (version, name, sql) in MIGRATIONS {
+        if applied.contains(version) {
+            continue;
+        }
+
+        match baseline {
+            Some(baseline) if *version <= baseline => {
+                info!("マイグレーション {} は適用済みとして記録します", name);
+            }
+            _ => {
+                info!("マイグレーション {} を適用します", name);
+                // 1 ファイルに複数の文が含まれるため、プリペアドステートメントを使わずに実行する
+                conn.execute(*sql).await?;
+            }
+        }
+
+        sqlx::query("INSERT INTO schema_migrations (version, name) VALUES (?, ?)")
+            .bind(version)
+            .bind(name)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(())
+}
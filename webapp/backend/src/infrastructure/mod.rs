@@ -1 +1,26 @@
+pub mod circuit_breaker;
 pub mod db;
+pub mod deadline;
+pub mod event_bus;
+pub mod feature_flags;
+pub mod geocoder;
+pub mod i18n;
+pub mod image_moderator;
+pub mod image_store;
+pub mod index_advisor;
+pub mod job_runner;
+pub mod jwt;
+pub mod logger;
+pub mod mailer;
+pub mod metrics;
+pub mod migrations;
+pub mod oidc;
+pub mod outbound_http;
+pub mod push;
+pub mod readiness;
+pub mod request_id;
+pub mod retry;
+pub mod single_flight;
+pub mod ttl_cache;
+pub mod worker_pool;
+pub mod write_behind;
@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use log::error;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::OidcConfig;
+use crate::errors::AppError;
+use crate::infrastructure::ttl_cache::TtlCache;
+use crate::models::role::Role;
+use crate::utils::generate_session_token;
+
+pub const PENDING_AUTHORIZATION_TTL: Duration = Duration::from_secs(10 * 60);
+const PENDING_AUTHORIZATION_CAPACITY: usize = 10_000;
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OidcUserInfo {
+    pub sub: String,
+    pub preferred_username: Option<String>,
+    pub email: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct OidcClient {
+    client: Client,
+    config: OidcConfig,
+    // state をキーに PKCE の code_verifier を保持する
+    pending_authorizations: TtlCache<String, String>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        OidcClient {
+            client: Client::new(),
+            config,
+            pending_authorizations: TtlCache::new(
+                PENDING_AUTHORIZATION_TTL,
+                PENDING_AUTHORIZATION_CAPACITY,
+            ),
+        }
+    }
+
+    pub fn provider(&self) -> &str {
+        &self.config.provider
+    }
+
+    pub fn default_role(&self) -> Role {
+        self.config.default_role
+    }
+
+    // 認可エンドポイントの URL と、コールバックまでブラウザに保持させる state を返す
+    pub fn authorization_url(&self) -> Result<(String, String), AppError> {
+        let state = generate_session_token();
+        let code_verifier = format!("{}{}", generate_session_token(), generate_session_token());
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+        let url = Url::parse_with_params(
+            &self.config.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_uri.as_str()),
+                ("scope", self.config.scopes.as_str()),
+                ("state", state.as_str()),
+                ("code_challenge", code_challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| {
+            error!("認可エンドポイントの URL が不正です: {:?}", e);
+            AppError::InternalServerError
+        })?;
+
+        self.pending_authorizations
+            .insert(state.clone(), code_verifier);
+
+        Ok((url.to_string(), state))
+    }
+
+    pub async fn exchange_code(&self, code: &str, state: &str) -> Result<OidcUserInfo, AppError> {
+        // 発行していない・期限切れ・使用済みの state は CSRF の可能性があるため拒否する
+        let code_verifier = match self.pending_authorizations.take(state) {
+            Some(code_verifier) => code_verifier,
+            None => return Err(AppError::Unauthorized),
+        };
+
+        let token_response = self
+            .client
+            .post(&self.config.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.config.redirect_uri.as_str()),
+                ("client_id", self.config.client_id.as_str()),
+                ("client_secret", self.config.client_secret.as_str()),
+                ("code_verifier", code_verifier.as_str()),
+            ])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| {
+                error!("トークンの取得に失敗しました: {:?}", e);
+                AppError::Unauthorized
+            })?
+            .json::<TokenResponse>()
+            .await
+            .map_err(|e| {
+                error!("トークンレスポンスの解析に失敗しました: {:?}", e);
+                AppError::Unauthorized
+            })?;
+
+        self.client
+            .get(&self.config.userinfo_endpoint)
+            .bearer_auth(&token_response.access_token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| {
+                error!("ユーザー情報の取得に失敗しました: {:?}", e);
+                AppError::Unauthorized
+            })?
+            .json::<OidcUserInfo>()
+            .await
+            .map_err(|e| {
+                error!("ユーザー情報の解析に失敗しました: {:?}", e);
+                AppError::Unauthorized
+            })
+    }
+}
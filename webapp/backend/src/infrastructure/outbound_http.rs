@@ -0,0 +1,100 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Url};
+
+use crate::errors::AppError;
+use crate::utils::is_valid_webhook_url;
+
+// 利用者が登録した URL へ送信するためのクライアント。
+// 名前解決の結果から内部のアドレスを除き、リダイレクトで別の宛先へ送られることもないようにする。
+// 制限のない既定のクライアントで代わりに動かないよう、作れない場合は起動時に止める
+pub fn outbound_client(timeout: Duration) -> Client {
+    Client::builder()
+        .timeout(timeout)
+        .redirect(Policy::none())
+        .dns_resolver(Arc::new(PublicOnlyResolver))
+        .build()
+        .expect("failed to build outbound HTTP client")
+}
+
+// 登録時と送信の直前に呼ぶ。ホスト名が解決するアドレスが 1 つでも内部のものであれば受け付けない。
+// 登録した後に DNS の内容を変えて内部のアドレスへ向けられることがあるため、送信の直前にも確かめる
+pub async fn ensure_public_url(url: &str) -> Result<(), AppError> {
+    if !is_valid_webhook_url(url) {
+        return Err(AppError::BadRequest);
+    }
+    let url = Url::parse(url).map_err(|_| AppError::BadRequest)?;
+    let port = url.port_or_known_default().ok_or(AppError::BadRequest)?;
+    let host = url.host_str().ok_or(AppError::BadRequest)?;
+    // IPv6 のアドレスは角括弧で囲まれている
+    let addresses: Vec<IpAddr> = match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(ip) => vec![ip],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|_| AppError::BadRequest)?
+            .map(|address| address.ip())
+            .collect(),
+    };
+    if addresses.is_empty() || !addresses.into_iter().all(is_public_ip) {
+        return Err(AppError::BadRequest);
+    }
+
+    Ok(())
+}
+
+// ループバック、プライベート、リンクローカル、未指定などの外部から到達できないアドレスを除く
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || first == 0
+                // 100.64.0.0/10 (キャリアグレード NAT)
+                || (first == 100 && (second & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ipv4) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(ipv4));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // fc00::/7 (ユニークローカル)
+                || (first & 0xfe00) == 0xfc00
+                // fe80::/10 (リンクローカル)
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+// 送信の直前に確かめた後で名前解決の結果が変わっても、内部のアドレスへは接続しない
+#[derive(Debug)]
+struct PublicOnlyResolver;
+
+impl Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|address| is_public_ip(address.ip()))
+                .collect();
+            if addresses.is_empty() {
+                return Err(format!("{} は送信先として許可されていません", name.as_str()).into());
+            }
+            let addresses: Addrs = Box::new(addresses.into_iter());
+
+            Ok(addresses)
+        })
+    }
+}
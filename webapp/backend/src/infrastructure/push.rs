@@ -0,0 +1,427 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use log::{error, info};
+use reqwest::{Client, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::config::{FcmConfig, PushConfig, WebPushConfig};
+use crate::infrastructure::outbound_http::{ensure_public_url, outbound_client};
+use crate::models::notification::PushProvider;
+
+const FCM_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+// アクセストークンは有効期限の少し前に取り直す
+const FCM_ACCESS_TOKEN_MARGIN: Duration = Duration::from_secs(60);
+// VAPID の JWT の有効期限は 24 時間までと決められている
+const VAPID_TOKEN_LIFETIME_SECS: i64 = 12 * 60 * 60;
+
+#[derive(Debug, Clone)]
+pub struct PushMessage {
+    pub title: String,
+    pub payload: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushOutcome {
+    Delivered,
+    // アプリの削除や購読の解除で使えなくなったトークン。再送せずに登録を消す
+    TokenExpired,
+    Failed { error: String, retryable: bool },
+}
+
+// 登録済みの端末に通知を届ける
+pub trait PushSender {
+    fn supports(&self, provider: PushProvider) -> bool;
+    async fn send(&self, provider: PushProvider, token: &str, message: &PushMessage)
+        -> PushOutcome;
+}
+
+#[derive(Debug)]
+pub enum PushSenderImpl {
+    Log,
+    Http(Box<HttpPushSender>),
+}
+
+impl PushSenderImpl {
+    pub fn from_config(config: &PushConfig) -> Self {
+        match (&config.fcm, &config.web_push) {
+            (None, None) => PushSenderImpl::Log,
+            _ => PushSenderImpl::Http(Box::new(HttpPushSender::new(config))),
+        }
+    }
+
+    // ブラウザが購読するときに applicationServerKey として渡す公開鍵
+    pub fn web_push_public_key(&self) -> Option<&str> {
+        match self {
+            PushSenderImpl::Log => None,
+            PushSenderImpl::Http(sender) => sender
+                .web_push
+                .as_ref()
+                .map(|web_push| web_push.public_key.as_str()),
+        }
+    }
+}
+
+impl PushSender for PushSenderImpl {
+    // ログに出力するだけの場合は、ローカル開発のためにどのプロバイダーのトークンも受け付ける
+    fn supports(&self, provider: PushProvider) -> bool {
+        match self {
+            PushSenderImpl::Log => true,
+            PushSenderImpl::Http(sender) => sender.supports(provider),
+        }
+    }
+
+    async fn send(
+        &self,
+        provider: PushProvider,
+        token: &str,
+        message: &PushMessage,
+    ) -> PushOutcome {
+        match self {
+            PushSenderImpl::Log => {
+                info!(
+                    "プッシュ通知を送信します ({}: {})\n{}",
+                    provider.as_str(),
+                    message.title,
+                    message.payload
+                );
+                PushOutcome::Delivered
+            }
+            PushSenderImpl::Http(sender) => sender.send(provider, token, message).await,
+        }
+    }
+}
+
+// 鍵を読み込めなかったプロバイダーは無効にし、そのトークンの登録を受け付けない
+#[derive(Debug)]
+pub struct HttpPushSender {
+    client: Client,
+    fcm: Option<FcmSender>,
+    web_push: Option<WebPushSender>,
+}
+
+impl HttpPushSender {
+    pub fn new(config: &PushConfig) -> Self {
+        HttpPushSender {
+            client: outbound_client(config.timeout),
+            fcm: config.fcm.as_ref().and_then(FcmSender::new),
+            web_push: config.web_push.as_ref().and_then(WebPushSender::new),
+        }
+    }
+}
+
+impl PushSender for HttpPushSender {
+    fn supports(&self, provider: PushProvider) -> bool {
+        match provider {
+            PushProvider::Fcm => self.fcm.is_some(),
+            PushProvider::WebPush => self.web_push.is_some(),
+        }
+    }
+
+    async fn send(
+        &self,
+        provider: PushProvider,
+        token: &str,
+        message: &PushMessage,
+    ) -> PushOutcome {
+        match (provider, &self.fcm, &self.web_push) {
+            (PushProvider::Fcm, Some(fcm), _) => fcm.send(&self.client, token, message).await,
+            (PushProvider::WebPush, _, Some(web_push)) => web_push.send(&self.client, token).await,
+            _ => PushOutcome::Failed {
+                error: format!("{} is not configured", provider.as_str()),
+                retryable: false,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ServiceAccount {
+    project_id: String,
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(Serialize)]
+struct ServiceAccountClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct AccessTokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+// FCM の HTTP v1 API。サービスアカウントの鍵で署名した JWT をアクセストークンに交換して呼び出す
+pub struct FcmSender {
+    project_id: String,
+    client_email: String,
+    token_uri: String,
+    key: EncodingKey,
+    access_token: Mutex<Option<(String, Instant)>>,
+}
+
+impl FcmSender {
+    fn new(config: &FcmConfig) -> Option<Self> {
+        let account = std::fs::read_to_string(&config.service_account_path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                serde_json::from_str::<ServiceAccount>(&content).map_err(|e| e.to_string())
+            });
+        let account = match account {
+            Ok(account) => account,
+            Err(e) => {
+                error!(
+                    "FCM のサービスアカウントの鍵 {} を読み込めませんでした: {}",
+                    config.service_account_path.display(),
+                    e
+                );
+                return None;
+            }
+        };
+        let key = match EncodingKey::from_rsa_pem(account.private_key.as_bytes()) {
+            Ok(key) => key,
+            Err(e) => {
+                error!("FCM のサービスアカウントの秘密鍵が不正です: {:?}", e);
+                return None;
+            }
+        };
+
+        Some(FcmSender {
+            project_id: account.project_id,
+            client_email: account.client_email,
+            token_uri: account.token_uri,
+            key,
+            access_token: Mutex::new(None),
+        })
+    }
+
+    async fn access_token(&self, client: &Client) -> Result<String, String> {
+        if let Some((token, expires_at)) = self.access_token.lock().unwrap().as_ref() {
+            if Instant::now() < *expires_at {
+                return Ok(token.clone());
+            }
+        }
+
+        let now = Utc::now().timestamp();
+        let assertion = jsonwebtoken::encode(
+            &Header::new(Algorithm::RS256),
+            &ServiceAccountClaims {
+                iss: &self.client_email,
+                scope: FCM_SCOPE,
+                aud: &self.token_uri,
+                iat: now,
+                exp: now + 3_600,
+            },
+            &self.key,
+        )
+        .map_err(|e| e.to_string())?;
+        let response = client
+            .post(&self.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?
+            .json::<AccessTokenResponse>()
+            .await
+            .map_err(|e| e.to_string())?;
+        let expires_at = Instant::now()
+            + Duration::from_secs(response.expires_in).saturating_sub(FCM_ACCESS_TOKEN_MARGIN);
+        *self.access_token.lock().unwrap() = Some((response.access_token.clone(), expires_at));
+
+        Ok(response.access_token)
+    }
+
+    async fn send(&self, client: &Client, token: &str, message: &PushMessage) -> PushOutcome {
+        let access_token = match self.access_token(client).await {
+            Ok(access_token) => access_token,
+            Err(error) => {
+                return PushOutcome::Failed {
+                    error,
+                    retryable: true,
+                }
+            }
+        };
+        // 配車の依頼はすぐに気付いてもらう必要があるため、端末が省電力中でも届く優先度で送る
+        let body = json!({
+            "message": {
+                "token": token,
+                "notification": { "title": message.title },
+                "data": { "payload": message.payload },
+                "android": { "priority": "high" },
+                "apns": { "headers": { "apns-priority": "10" } },
+            }
+        });
+        let result = client
+            .post(format!(
+                "https://fcm.googleapis.com/v1/projects/{}/messages:send",
+                self.project_id
+            ))
+            .bearer_auth(access_token)
+            .json(&body)
+            .send()
+            .await;
+
+        match result {
+            Ok(response) => {
+                let status = response.status();
+                if status == StatusCode::UNAUTHORIZED {
+                    // 失効したアクセストークンは次の試行で取り直す
+                    *self.access_token.lock().unwrap() = None;
+                }
+                let body = response.text().await.unwrap_or_default();
+                match status {
+                    status if status.is_success() => PushOutcome::Delivered,
+                    StatusCode::NOT_FOUND => PushOutcome::TokenExpired,
+                    StatusCode::BAD_REQUEST if body.contains("UNREGISTERED") => {
+                        PushOutcome::TokenExpired
+                    }
+                    status => failure(status, body),
+                }
+            }
+            Err(err) => PushOutcome::Failed {
+                error: err.to_string(),
+                retryable: true,
+            },
+        }
+    }
+}
+
+impl std::fmt::Debug for FcmSender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FcmSender")
+            .field("project_id", &self.project_id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Serialize)]
+struct VapidClaims<'a> {
+    aud: &'a str,
+    exp: i64,
+    sub: &'a str,
+}
+
+// Web Push (RFC 8030) に VAPID の署名を付けて送る。
+// 本文の暗号化 (RFC 8291) は行わず本文なしで送る。受け取った Service Worker が固定の文言で知らせ、
+// 利用者がアプリを開くと WebSocket で最新の状態を受け取る
+pub struct WebPushSender {
+    public_key: String,
+    subject: String,
+    ttl: Duration,
+    key: EncodingKey,
+}
+
+impl WebPushSender {
+    fn new(config: &WebPushConfig) -> Option<Self> {
+        let key = std::fs::read(&config.private_key_path)
+            .map_err(|e| e.to_string())
+            .and_then(|pem| EncodingKey::from_ec_pem(&pem).map_err(|e| e.to_string()));
+        match key {
+            Ok(key) => Some(WebPushSender {
+                public_key: config.public_key.clone(),
+                subject: config.subject.clone(),
+                ttl: config.ttl,
+                key,
+            }),
+            Err(e) => {
+                error!(
+                    "VAPID の秘密鍵 {} を読み込めませんでした: {}",
+                    config.private_key_path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    async fn send(&self, client: &Client, endpoint: &str) -> PushOutcome {
+        let audience = match Url::parse(endpoint) {
+            Ok(url) => url.origin().ascii_serialization(),
+            Err(e) => {
+                return PushOutcome::Failed {
+                    error: e.to_string(),
+                    retryable: false,
+                }
+            }
+        };
+        // 登録した後で内部のアドレスを指すようになったエンドポイントには送らない
+        if ensure_public_url(endpoint).await.is_err() {
+            return PushOutcome::Failed {
+                error: "destination not allowed".to_string(),
+                retryable: false,
+            };
+        }
+        let jwt = match jsonwebtoken::encode(
+            &Header::new(Algorithm::ES256),
+            &VapidClaims {
+                aud: &audience,
+                exp: Utc::now().timestamp() + VAPID_TOKEN_LIFETIME_SECS,
+                sub: &self.subject,
+            },
+            &self.key,
+        ) {
+            Ok(jwt) => jwt,
+            Err(e) => {
+                return PushOutcome::Failed {
+                    error: e.to_string(),
+                    retryable: false,
+                }
+            }
+        };
+        let result = client
+            .post(endpoint)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("vapid t={}, k={}", jwt, self.public_key),
+            )
+            .header("TTL", self.ttl.as_secs().to_string())
+            .header("Urgency", "high")
+            .header(reqwest::header::CONTENT_LENGTH, "0")
+            .send()
+            .await;
+
+        match result {
+            Ok(response) => match response.status() {
+                status if status.is_success() => PushOutcome::Delivered,
+                StatusCode::NOT_FOUND | StatusCode::GONE => PushOutcome::TokenExpired,
+                status => failure(status, response.text().await.unwrap_or_default()),
+            },
+            Err(err) => PushOutcome::Failed {
+                error: err.to_string(),
+                retryable: true,
+            },
+        }
+    }
+}
+
+impl std::fmt::Debug for WebPushSender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebPushSender")
+            .field("subject", &self.subject)
+            .finish_non_exhaustive()
+    }
+}
+
+// 混雑やサーバー側の障害は時間をおけば届く見込みがあるため再送する
+fn failure(status: StatusCode, body: String) -> PushOutcome {
+    PushOutcome::Failed {
+        error: format!("status {}: {}", status, body),
+        retryable: status == StatusCode::TOO_MANY_REQUESTS
+            || status == StatusCode::UNAUTHORIZED
+            || status.is_server_error(),
+    }
+}
@@ -0,0 +1,50 @@
+use std::future::Future;
+
+use rand::Rng;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+// クライアントから受け取る ID の長さの上限
+const REQUEST_ID_MAX_LENGTH: usize = 128;
+
+// fut の中から current() でリクエスト ID を参照できるようにする
+pub async fn scope<F: Future>(request_id: String, fut: F) -> F::Output {
+    REQUEST_ID.scope(request_id, fut).await
+}
+
+// リクエストの処理中でない場合 (定期ジョブなど) は None。
+// spawn したタスクには引き継がれないため、必要なら spawn する前に取得して scope で包む
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|request_id| request_id.clone()).ok()
+}
+
+pub fn generate() -> String {
+    format!("{:032x}", rand::thread_rng().gen::<u128>())
+}
+
+// ログやヘッダーにそのまま載せるため、英数字と一部の記号だけからなる ID を受け付ける
+pub fn is_valid(request_id: &str) -> bool {
+    !request_id.is_empty()
+        && request_id.len() <= REQUEST_ID_MAX_LENGTH
+        && request_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+// 呼び出し元のリクエスト ID を引き継いでタスクを起動する。イベントの購読者から非同期の処理を始めるときに使う
+pub fn spawn<F>(fut: F)
+where
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    match current() {
+        Some(request_id) => {
+            actix_web::rt::spawn(scope(request_id, fut));
+        }
+        None => {
+            actix_web::rt::spawn(fut);
+        }
+    }
+}
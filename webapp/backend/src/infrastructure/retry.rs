@@ -0,0 +1,78 @@
+use std::future::Future;
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+
+use log::warn;
+use rand::Rng;
+use sqlx::mysql::MySqlDatabaseError;
+
+use crate::config::RetryConfig;
+use crate::infrastructure::deadline;
+
+// デッドロックとロック待ちのタイムアウト
+const TRANSIENT_MYSQL_ERRORS: [u16; 2] = [1213, 1205];
+
+// 冪等な読み取りに限って使う。書き込みは再実行すると二重に反映される恐れがある
+pub async fn retry<T, F, Fut>(
+    config: &RetryConfig,
+    method: &'static str,
+    mut operation: F,
+) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let max_attempts = config.max_attempts_for(method);
+    let mut attempt = 1;
+    loop {
+        let error = match operation().await {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+        if attempt >= max_attempts || !is_transient(&error) {
+            return Err(error);
+        }
+
+        // 待っている間にリクエストの期限を過ぎる場合は、再試行せずにそのまま返す
+        let delay = backoff(config, attempt);
+        if deadline::current().is_some_and(|deadline| Instant::now() + delay >= deadline) {
+            return Err(error);
+        }
+        warn!(
+            "{} が一時的なエラーで失敗したため、{:.1}ms 後に再試行します ({}/{}): {:?}",
+            method,
+            delay.as_secs_f64() * 1000.0,
+            attempt,
+            max_attempts,
+            error
+        );
+        actix_web::rt::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+// 同時に失敗したリクエストが一斉に再試行しないよう、上限までの範囲でばらつかせる
+fn backoff(config: &RetryConfig, attempt: u32) -> Duration {
+    let ceiling = config
+        .base_delay
+        .saturating_mul(1 << (attempt - 1).min(16))
+        .min(config.max_delay);
+
+    ceiling.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+}
+
+fn is_transient(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Database(e) => e
+            .try_downcast_ref::<MySqlDatabaseError>()
+            .is_some_and(|e| TRANSIENT_MYSQL_ERRORS.contains(&e.number())),
+        sqlx::Error::Io(e) => matches!(
+            e.kind(),
+            ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::BrokenPipe
+                | ErrorKind::UnexpectedEof
+        ),
+        _ => false,
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+use tokio::sync::watch;
+
+use crate::errors::AppError;
+
+// 同じキーの処理が実行中の場合は、新たに実行せずにその結果を待つ。
+// 先に実行した処理が失敗した場合やキャンセルされた場合、待っていた側はそれぞれ自分で実行する
+#[derive(Debug)]
+pub struct SingleFlight<K, V> {
+    in_flight: Mutex<HashMap<K, watch::Receiver<Option<V>>>>,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> SingleFlight<K, V> {
+    pub fn new() -> Self {
+        SingleFlight {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn run<F, Fut>(&self, key: K, work: F) -> Result<V, AppError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, AppError>>,
+    {
+        let sender = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(&key) {
+                Some(receiver) => Err(receiver.clone()),
+                None => {
+                    let (sender, receiver) = watch::channel(None);
+                    in_flight.insert(key.clone(), receiver);
+                    Ok(sender)
+                }
+            }
+        };
+
+        let sender = match sender {
+            Ok(sender) => sender,
+            Err(mut receiver) => {
+                loop {
+                    if let Some(value) = receiver.borrow().clone() {
+                        return Ok(value);
+                    }
+                    if receiver.changed().await.is_err() {
+                        break;
+                    }
+                }
+                return work().await;
+            }
+        };
+
+        // キャンセルされた場合もキーを取り除くよう、ガードで後始末する
+        let _guard = FlightGuard {
+            in_flight: &self.in_flight,
+            key,
+        };
+        let result = work().await;
+        if let Ok(value) = &result {
+            let _ = sender.send(Some(value.clone()));
+        }
+
+        result
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Default for SingleFlight<K, V> {
+    fn default() -> Self {
+        SingleFlight::new()
+    }
+}
+
+struct FlightGuard<'a, K: Eq + Hash, V> {
+    in_flight: &'a Mutex<HashMap<K, watch::Receiver<Option<V>>>>,
+    key: K,
+}
+
+impl<K: Eq + Hash, V> Drop for FlightGuard<'_, K, V> {
+    fn drop(&mut self) {
+        self.in_flight.lock().unwrap().remove(&self.key);
+    }
+}
@@ -0,0 +1,118 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub struct TtlCache<K, V> {
+    // 設定の再読み込みで変更できるよう、ナノ秒単位で保持する
+    ttl_nanos: AtomicU64,
+    capacity: usize,
+    entries: RwLock<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        TtlCache {
+            ttl_nanos: AtomicU64::new(ttl.as_nanos() as u64),
+            capacity,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        Duration::from_nanos(self.ttl_nanos.load(Ordering::Relaxed))
+    }
+
+    // 短くした場合も既存のエントリは新しい TTL で判定される。0 にした場合はキャッシュを空にする
+    pub fn set_ttl(&self, ttl: Duration) {
+        self.ttl_nanos
+            .store(ttl.as_nanos() as u64, Ordering::Relaxed);
+        if ttl.is_zero() {
+            self.clear();
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.ttl().is_zero() && self.capacity > 0
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let entries = self.entries.read().unwrap();
+        match entries.get(key) {
+            Some((inserted_at, value)) if inserted_at.elapsed() < self.ttl() => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut entries = self.entries.write().unwrap();
+        if entries.len() >= self.capacity {
+            let ttl = self.ttl();
+            entries.retain(|_, (inserted_at, _)| inserted_at.elapsed() < ttl);
+            if entries.len() >= self.capacity {
+                entries.clear();
+            }
+        }
+        entries.insert(key, (Instant::now(), value));
+    }
+
+    pub fn remove<Q>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries.write().unwrap().remove(key);
+    }
+
+    pub fn take<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.entries.write().unwrap().remove(key) {
+            Some((inserted_at, value)) if inserted_at.elapsed() < self.ttl() => Some(value),
+            _ => None,
+        }
+    }
+
+    // 期限内のエントリ。保存して別のプロセスで insert し直すために使う
+    pub fn entries(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+    {
+        let ttl = self.ttl();
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, (inserted_at, _))| inserted_at.elapsed() < ttl)
+            .map(|(key, (_, value))| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.entries.write().unwrap().clear();
+    }
+
+    pub fn retain(&self, mut f: impl FnMut(&K, &V) -> bool) {
+        self.entries
+            .write()
+            .unwrap()
+            .retain(|key, (_, value)| f(key, value));
+    }
+}
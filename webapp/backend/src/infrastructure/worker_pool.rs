@@ -0,0 +1,178 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix_web::web;
+use log::error;
+use serde::Serialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::WorkerPoolConfig;
+use crate::errors::AppError;
+
+// 待ちが多すぎて断った場合に、再試行までの目安として返す時間
+const WORKER_POOL_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Debug)]
+pub struct WorkerPoolMetrics {
+    pub name: &'static str,
+    pub concurrency: usize,
+    pub queue_limit: usize,
+    pub running: usize,
+    pub queued: usize,
+    pub completed: u64,
+    // 待ちが上限に達していたため断った数
+    pub rejected: u64,
+    // 実行枠が空くまで待った時間。すぐに実行できた場合も 0 として含める
+    pub avg_wait_ms: f64,
+    pub max_wait_ms: f64,
+}
+
+// 重い処理を同時実行数を絞って別スレッドで実行する。
+// web::block のスレッドは共有のため、用途ごとに分けておかないと 1 つの用途が詰まったときに他も待たされる
+#[derive(Debug)]
+pub struct WorkerPool {
+    name: &'static str,
+    permits: Arc<Semaphore>,
+    concurrency: usize,
+    queue_limit: usize,
+    queued: AtomicUsize,
+    completed: AtomicU64,
+    rejected: AtomicU64,
+    // 待ち時間の合計と最大。マイクロ秒単位
+    wait_total_us: AtomicU64,
+    wait_max_us: AtomicU64,
+}
+
+impl WorkerPool {
+    pub fn new(name: &'static str, config: &WorkerPoolConfig) -> Arc<Self> {
+        Arc::new(WorkerPool {
+            name,
+            permits: Arc::new(Semaphore::new(config.concurrency)),
+            concurrency: config.concurrency,
+            queue_limit: config.queue_limit,
+            queued: AtomicUsize::new(0),
+            completed: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            wait_total_us: AtomicU64::new(0),
+            wait_max_us: AtomicU64::new(0),
+        })
+    }
+
+    pub async fn run<T: Send + 'static>(
+        self: &Arc<Self>,
+        work: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, AppError> {
+        self.acquire().await?.run(work).await
+    }
+
+    // 実行枠を確保する。レスポンスを返し始める前に確保しておけば、断る場合に 503 を返せる
+    pub async fn acquire(self: &Arc<Self>) -> Result<WorkerPermit, AppError> {
+        let started_at = Instant::now();
+        // 空きがなく、待ちも上限に達している場合は、待たせるより早く断って他のインスタンスに回してもらう
+        let permit = match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                let queued = Counted::increment(&self.queued);
+                if queued.value > self.queue_limit {
+                    self.rejected.fetch_add(1, Ordering::Relaxed);
+                    return Err(AppError::ServiceUnavailable(WORKER_POOL_RETRY_AFTER));
+                }
+                self.permits.clone().acquire_owned().await.map_err(|e| {
+                    error!("{} の実行枠を確保できませんでした: {:?}", self.name, e);
+                    AppError::InternalServerError
+                })?
+            }
+        };
+
+        Ok(WorkerPermit {
+            pool: self.clone(),
+            permit,
+            waited_us: started_at.elapsed().as_micros() as u64,
+        })
+    }
+
+    pub fn metrics(&self) -> WorkerPoolMetrics {
+        let completed = self.completed.load(Ordering::Relaxed);
+        WorkerPoolMetrics {
+            name: self.name,
+            concurrency: self.concurrency,
+            queue_limit: self.queue_limit,
+            running: self.concurrency - self.permits.available_permits(),
+            queued: self.queued.load(Ordering::Relaxed),
+            completed,
+            rejected: self.rejected.load(Ordering::Relaxed),
+            avg_wait_ms: self.wait_total_us.load(Ordering::Relaxed) as f64
+                / completed.max(1) as f64
+                / 1000.0,
+            max_wait_ms: self.wait_max_us.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+}
+
+pub struct WorkerPermit {
+    pool: Arc<WorkerPool>,
+    permit: OwnedSemaphorePermit,
+    waited_us: u64,
+}
+
+impl WorkerPermit {
+    pub async fn run<T: Send + 'static>(
+        self,
+        work: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, AppError> {
+        let WorkerPermit {
+            pool,
+            permit,
+            waited_us,
+        } = self;
+        // リクエストが破棄されても別スレッドの処理は続くため、枠は処理が終わるまで返さない
+        let output = web::block(move || {
+            let _permit = permit;
+            work()
+        })
+        .await
+        .map_err(|e| {
+            error!("{} のスレッドの実行に失敗しました: {:?}", pool.name, e);
+            AppError::InternalServerError
+        })?;
+        // 平均を完了した数で割るため、待ち時間も完了したものだけを数える
+        pool.completed.fetch_add(1, Ordering::Relaxed);
+        pool.wait_total_us.fetch_add(waited_us, Ordering::Relaxed);
+        pool.wait_max_us.fetch_max(waited_us, Ordering::Relaxed);
+
+        Ok(output)
+    }
+}
+
+// 用途ごとのプール。メトリクスの一覧に出すため、まとめて保持する
+#[derive(Debug)]
+pub struct WorkerPools {
+    pub image: Arc<WorkerPool>,
+    pub password: Arc<WorkerPool>,
+}
+
+impl WorkerPools {
+    pub fn metrics(&self) -> Vec<WorkerPoolMetrics> {
+        vec![self.image.metrics(), self.password.metrics()]
+    }
+}
+
+// 待っている間にリクエストが破棄された場合も数え漏れがないよう、drop で元に戻す
+struct Counted<'a> {
+    counter: &'a AtomicUsize,
+    value: usize,
+}
+
+impl<'a> Counted<'a> {
+    fn increment(counter: &'a AtomicUsize) -> Self {
+        let value = counter.fetch_add(1, Ordering::Relaxed) + 1;
+        Counted { counter, value }
+    }
+}
+
+impl Drop for Counted<'_> {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::rt;
+use log::{error, warn};
+use tokio::sync::Notify;
+
+use crate::config::WriteBehindConfig;
+use crate::errors::AppError;
+
+// まとめて書き込む先。items は受け付けた順に渡す
+pub trait BatchWriter {
+    type Item;
+    async fn write_batch(&self, items: &[Self::Item]) -> Result<(), AppError>;
+}
+
+// 頻繁な INSERT をメモリに溜め、一定の間隔か一定の件数ごとに複数行の INSERT でまとめて書き込む。
+// 書き込みに失敗した分は次の書き込みで再試行し、溜まりすぎた場合は古いものから捨てる
+pub struct WriteBehindBuffer<W: BatchWriter> {
+    name: &'static str,
+    writer: W,
+    pending: Mutex<VecDeque<W::Item>>,
+    // 書き込みの順序を保つため、同時には 1 つしか書き込まない
+    flushing: tokio::sync::Mutex<()>,
+    batch_size: usize,
+    max_pending: usize,
+    flush_interval: Duration,
+    batch_filled: Notify,
+}
+
+impl<W: BatchWriter> std::fmt::Debug for WriteBehindBuffer<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WriteBehindBuffer")
+            .field("name", &self.name)
+            .field("pending", &self.pending.lock().unwrap().len())
+            .field("batch_size", &self.batch_size)
+            .field("flush_interval", &self.flush_interval)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: BatchWriter> WriteBehindBuffer<W> {
+    // flush_interval が 0 の場合は溜めずに、呼び出し元がその場で書き込む
+    pub fn new(
+        name: &'static str,
+        writer: W,
+        flush_interval: Duration,
+        config: &WriteBehindConfig,
+    ) -> Self {
+        WriteBehindBuffer {
+            name,
+            writer,
+            pending: Mutex::new(VecDeque::new()),
+            flushing: tokio::sync::Mutex::new(()),
+            batch_size: config.batch_size.max(1),
+            max_pending: config.max_pending.max(1),
+            flush_interval,
+            batch_filled: Notify::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.flush_interval.is_zero()
+    }
+
+    // 溜めずに書き込む場合に、書き込み先を直接使う
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn push(&self, item: W::Item) {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.len() >= self.max_pending {
+            pending.pop_front();
+            warn!(
+                "{} の書き込み待ちが {} 件に達したため、最も古いものを捨てました",
+                self.name, self.max_pending
+            );
+        }
+        pending.push_back(item);
+        if pending.len() >= self.batch_size {
+            self.batch_filled.notify_one();
+        }
+    }
+
+    // 溜まっているものをすべて書き込み、書き込んだ件数を返す。
+    // 失敗した場合は書き込めなかった分を先頭に戻す
+    pub async fn flush(&self) -> Result<usize, AppError> {
+        let _flushing = self.flushing.lock().await;
+        let mut written = 0;
+        loop {
+            let batch: Vec<W::Item> = {
+                let mut pending = self.pending.lock().unwrap();
+                let size = pending.len().min(self.batch_size);
+                pending.drain(..size).collect()
+            };
+            if batch.is_empty() {
+                return Ok(written);
+            }
+
+            if let Err(err) = self.writer.write_batch(&batch).await {
+                let mut pending = self.pending.lock().unwrap();
+                let room = self.max_pending.saturating_sub(pending.len());
+                let dropped = batch.len().saturating_sub(room);
+                // 溜まりすぎないよう、戻しきれない分は古いものから捨てる
+                for item in batch.into_iter().skip(dropped).rev() {
+                    pending.push_front(item);
+                }
+                if dropped > 0 {
+                    warn!(
+                        "{} の書き込みに失敗し、{} 件を捨てました",
+                        self.name, dropped
+                    );
+                }
+                return Err(err);
+            }
+            written += batch.len();
+        }
+    }
+}
+
+impl<W> WriteBehindBuffer<W>
+where
+    W: BatchWriter + 'static,
+    W::Item: 'static,
+{
+    // 間隔が経過するか、1 回分の件数が溜まるたびに書き込む。停止時の書き込みは呼び出し元が flush で行う
+    pub fn spawn_flusher(self: &Arc<Self>) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let buffer = self.clone();
+        rt::spawn(async move {
+            loop {
+                let _ =
+                    rt::time::timeout(buffer.flush_interval, buffer.batch_filled.notified()).await;
+                if let Err(e) = buffer.flush().await {
+                    error!("{} を書き込めませんでした: {:?}", buffer.name, e);
+                }
+            }
+        });
+    }
+}
@@ -1,19 +1,32 @@
-use std::sync::Arc;
-
 use actix_cors::Cors;
-use actix_web::{web, App, HttpServer};
-use api::{auth_handler, health_check_handler, map_handler, order_handler, tow_truck_handler};
-use domains::map_service::MapService;
-use domains::{
-    auth_service::AuthService, order_service::OrderService, tow_truck_service::TowTruckService,
+use actix_web::middleware::{Compress, Condition};
+use actix_web::{guard, web, App, HttpServer};
+use api::versioning::ApiVersion;
+use api::{
+    admin_handler, auth_handler, chat_handler, client_handler, debug_handler, dispatcher_handler,
+    feature_flag_handler, geocoding_handler, graphql_handler, grpc_server, health_check_handler,
+    image_handler, impersonation_handler, invite_code_handler, leaderboard_handler, map_handler,
+    metrics_handler, notification_handler, oidc_handler, order_handler, order_note_handler,
+    organization_handler, profile_handler, quota_handler, runtime_config_handler, stats_handler,
+    sync_handler, tow_truck_handler, vehicle_handler, webhook_handler,
 };
+use app_state::AppState;
+use infrastructure::{i18n, logger};
+use middlewares::access_log_middleware::AccessLogMiddleware;
+use middlewares::api_key_middleware::{ApiKeyMiddleware, API_KEY_HEADER_NAME};
 use middlewares::auth_middleware::AuthMiddleware;
-use repositories::auth_repository::AuthRepositoryImpl;
-use repositories::map_repository::MapRepositoryImpl;
-use repositories::order_repository::OrderRepositoryImpl;
-use repositories::tow_truck_repository::TowTruckRepositoryImpl;
+use middlewares::circuit_breaker_middleware::CircuitBreakerMiddleware;
+use middlewares::compression_middleware::CompressionPolicyMiddleware;
+use middlewares::csrf_middleware::{CsrfMiddleware, CSRF_HEADER_NAME};
+use middlewares::deadline_middleware::RequestDeadlineMiddleware;
+use middlewares::locale_middleware::LocaleMiddleware;
+use middlewares::organization_middleware::DefaultOrganizationMiddleware;
+use middlewares::request_id_middleware::{RequestIdMiddleware, REQUEST_ID_HEADER_NAME};
+use models::role::Permission;
 
 mod api;
+mod app_state;
+mod config;
 mod domains;
 mod errors;
 mod infrastructure;
@@ -24,29 +37,30 @@ mod utils;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let pool = infrastructure::db::create_pool().await;
+    let config = config::AppConfig::from_env();
+    logger::init(&config.log_filter);
+    i18n::init(&config.i18n);
+
+    let state = AppState::builder(config).build().await;
+    state.restore_snapshot();
+    state.resolve_username_collisions().await;
+    state.spawn_warm_up();
+    state.spawn_background_jobs();
+    if let Some(grpc_config) = state.config.grpc.clone() {
+        actix_web::rt::spawn(grpc_server::serve(grpc_config, state.auth_service.clone()));
+    }
+    let auth_json_config = web::JsonConfig::default()
+        .limit(state.config.payload.auth_json_limit)
+        .error_handler(errors::json_error_handler);
+    let state = web::Data::new(state);
+    let snapshot_state = state.clone();
+
     let mut port = 8080;
 
     if cfg!(debug_assertions) {
         port = 18080;
     }
 
-    let auth_service = web::Data::new(AuthService::new(AuthRepositoryImpl::new(pool.clone())));
-    let auth_service_for_middleware =
-        Arc::new(AuthService::new(AuthRepositoryImpl::new(pool.clone())));
-    let tow_truck_service = web::Data::new(TowTruckService::new(
-        TowTruckRepositoryImpl::new(pool.clone()),
-        OrderRepositoryImpl::new(pool.clone()),
-        MapRepositoryImpl::new(pool.clone()),
-    ));
-    let order_service = web::Data::new(OrderService::new(
-        OrderRepositoryImpl::new(pool.clone()),
-        TowTruckRepositoryImpl::new(pool.clone()),
-        AuthRepositoryImpl::new(pool.clone()),
-        MapRepositoryImpl::new(pool.clone()),
-    ));
-    let map_service = web::Data::new(MapService::new(MapRepositoryImpl::new(pool.clone())));
-
     HttpServer::new(move || {
         let mut cors = Cors::default();
 
@@ -57,99 +71,613 @@ async fn main() -> std::io::Result<()> {
                 actix_web::http::header::ACCEPT,
             ])
             .allowed_header(actix_web::http::header::CONTENT_TYPE)
+            .allowed_header(CSRF_HEADER_NAME)
+            .allowed_header(API_KEY_HEADER_NAME)
+            .allowed_header(REQUEST_ID_HEADER_NAME)
+            .expose_headers(vec![REQUEST_ID_HEADER_NAME])
             .supports_credentials()
             .max_age(3600);
 
         App::new()
-            .app_data(tow_truck_service.clone())
-            .app_data(auth_service.clone())
-            .app_data(order_service.clone())
-            .app_data(map_service.clone())
+            .configure(|cfg| state.configure_app_data(cfg))
+            .wrap(RequestDeadlineMiddleware::new(state.config.request_timeout))
+            .wrap(CircuitBreakerMiddleware::new(
+                state.pools.circuit_breaker.clone(),
+            ))
+            .wrap(CsrfMiddleware)
             .wrap(cors)
+            .wrap(AccessLogMiddleware)
+            .wrap(CompressionPolicyMiddleware::new(
+                state.config.compression.clone(),
+            ))
+            .wrap(Condition::new(
+                state.config.compression.enabled,
+                Compress::default(),
+            ))
+            .wrap(LocaleMiddleware)
+            .wrap(RequestIdMiddleware)
+            // 既存のフロントエンドが使うバージョンなしのパスは v1 として扱う。
+            // /api のスコープは /api/v1 などにも前方一致するため、バージョン付きのスコープを先に登録する
+            .service(
+                web::scope("/api/v1")
+                    .app_data(ApiVersion::V1)
+                    .configure(|cfg| configure_api_routes(cfg, &state, &auth_json_config)),
+            )
+            .service(
+                web::scope("/api/v2")
+                    .app_data(ApiVersion::V2)
+                    .configure(|cfg| configure_api_routes(cfg, &state, &auth_json_config)),
+            )
             .service(
                 web::scope("/api")
-                    .service(
-                        web::resource("/health_check")
-                            .route(web::get().to(health_check_handler::health_check_handler)),
-                    )
-                    .service(
-                        web::resource("/validate_session")
-                            .route(web::get().to(auth_handler::validate_session_handler)),
-                    )
-                    .service(
-                        web::resource("/register")
-                            .route(web::post().to(auth_handler::register_handler)),
-                    )
-                    .service(
-                        web::resource("/login").route(web::post().to(auth_handler::login_handler)),
-                    )
-                    .service(
-                        web::resource("/logout")
-                            .route(web::post().to(auth_handler::logout_handler)),
-                    )
-                    .service(
-                        web::resource("/user_image/{user_id}")
-                            .route(web::get().to(auth_handler::user_profile_image_handler)),
-                    )
-                    .service(
-                        web::scope("/tow_truck")
-                            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
-                            .service(web::resource("/list").route(
-                                web::get().to(tow_truck_handler::get_paginated_tow_trucks_handler),
-                            ))
-                            .service(
-                                web::resource("/location").route(
-                                    web::post().to(tow_truck_handler::update_location_handler),
-                                ),
-                            )
-                            .service(web::resource("/nearest").route(
-                                web::get().to(
-                                    tow_truck_handler::get_nearest_available_tow_trucks_handler,
-                                ),
-                            ))
-                            .service(
-                                web::resource("/{id}")
-                                    .route(web::get().to(tow_truck_handler::get_tow_truck_handler)),
-                            ),
-                    )
-                    .service(
-                        web::scope("/order")
-                            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
-                            .service(
-                                web::resource("/list").route(
-                                    web::get().to(order_handler::get_paginated_orders_handler),
-                                ),
-                            )
-                            .service(
-                                web::resource("/status").route(
-                                    web::post().to(order_handler::update_order_status_handler),
-                                ),
-                            )
-                            .service(
-                                web::resource("/client").route(
-                                    web::post().to(order_handler::create_client_order_handler),
-                                ),
-                            )
-                            .service(web::resource("/dispatcher").route(
-                                web::post().to(order_handler::create_dispatcher_order_handler),
-                            ))
-                            .service(
-                                web::resource("/{id}")
-                                    .route(web::get().to(order_handler::get_order_handler)),
-                            ),
-                    )
-                    .service(
-                        web::scope("/map")
-                            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
-                            .service(
-                                web::resource("/update_edge")
-                                    .route(web::put().to(map_handler::update_edge_handler)),
-                            ),
-                    ),
+                    .app_data(ApiVersion::V1)
+                    .configure(|cfg| configure_api_routes(cfg, &state, &auth_json_config)),
             )
     })
     .bind(format!("0.0.0.0:{port}"))?
     //.workers(1)
     .run()
-    .await
+    .await?;
+
+    // 停止の合図を受けて処理中のリクエストが終わった後に、次の起動のために状態を保存する
+    snapshot_state.save_snapshot();
+    snapshot_state.flush_pending_writes().await;
+
+    Ok(())
+}
+
+// /api 以下のルート。API のバージョンごとのスコープに同じものを登録する
+fn configure_api_routes(
+    cfg: &mut web::ServiceConfig,
+    state: &AppState,
+    auth_json_config: &web::JsonConfig,
+) {
+    let auth_service_for_middleware = state.auth_service.clone();
+    let api_key_service_for_middleware = state.api_key_service.clone();
+
+    cfg.service(
+        web::resource("/health_check")
+            .route(web::get().to(health_check_handler::health_check_handler)),
+    )
+    .service(
+        web::resource("/readiness").route(web::get().to(health_check_handler::readiness_handler)),
+    )
+    // 接続プールや内部の状態を含むため、管理者にだけ見せる
+    .service(
+        web::resource("/metrics")
+            .wrap(
+                AuthMiddleware::new(auth_service_for_middleware.clone())
+                    .require_permission(Permission::Administer),
+            )
+            .route(web::get().to(metrics_handler::metrics_handler)),
+    )
+    .service(
+        web::resource("/validate_session")
+            .route(web::get().to(auth_handler::validate_session_handler)),
+    )
+    .service(
+        web::resource("/register")
+            .app_data(auth_json_config.clone())
+            .route(web::post().to(auth_handler::register_handler)),
+    )
+    .service(
+        web::resource("/verify_email")
+            .app_data(auth_json_config.clone())
+            .route(web::post().to(auth_handler::verify_email_handler)),
+    )
+    .service(
+        web::resource("/login")
+            .app_data(auth_json_config.clone())
+            .route(web::post().to(auth_handler::login_handler)),
+    )
+    .service(
+        web::resource("/refresh")
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()).forbid_impersonation())
+            .route(web::post().to(auth_handler::refresh_handler)),
+    )
+    .service(
+        web::resource("/change_username")
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()).forbid_impersonation())
+            .route(web::post().to(auth_handler::change_username_handler)),
+    )
+    .service(
+        web::resource("/change_password")
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()).forbid_impersonation())
+            .route(web::post().to(auth_handler::change_password_handler)),
+    )
+    .service(
+        web::scope("/totp")
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()).forbid_impersonation())
+            .service(
+                web::resource("/setup").route(web::post().to(auth_handler::setup_totp_handler)),
+            )
+            .service(
+                web::resource("/enable").route(web::post().to(auth_handler::enable_totp_handler)),
+            ),
+    )
+    .service(
+        web::resource("/oidc/authorize").route(web::get().to(oidc_handler::oidc_authorize_handler)),
+    )
+    .service(
+        web::resource("/oidc/callback").route(web::get().to(oidc_handler::oidc_callback_handler)),
+    )
+    .service(
+        web::resource("/sessions")
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+            .route(web::get().to(auth_handler::get_sessions_handler)),
+    )
+    .service(web::resource("/csrf_token").route(web::get().to(auth_handler::csrf_token_handler)))
+    .service(web::resource("/logout").route(web::post().to(auth_handler::logout_handler)))
+    .service(
+        web::resource("/me")
+            .guard(guard::Get())
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+            .route(web::get().to(profile_handler::get_profile_handler)),
+    )
+    // なりかわり中でもプロフィールは見られるが、連絡先や通知先は変えさせない
+    .service(
+        web::resource("/me")
+            .guard(guard::Patch())
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()).forbid_impersonation())
+            .route(web::patch().to(profile_handler::update_profile_handler)),
+    )
+    .service(
+        web::resource("/profile_image")
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+            .route(web::post().to(image_handler::upload_profile_image_handler)),
+    )
+    .service(
+        web::resource("/areas")
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+            .route(web::get().to(map_handler::get_areas_handler)),
+    )
+    .service(
+        web::resource("/user_image/{user_id}")
+            .route(web::get().to(image_handler::user_profile_image_handler)),
+    )
+    .service(
+        web::resource("/user_image/{user_id}/signed_url")
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+            .route(web::get().to(image_handler::signed_profile_image_url_handler)),
+    )
+    .service(
+        web::scope("/admin")
+            .wrap(
+                AuthMiddleware::new(auth_service_for_middleware.clone())
+                    .require_permission(Permission::Administer)
+                    .forbid_impersonation(),
+            )
+            .service(
+                web::resource("/sessions/purge")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::post().to(admin_handler::purge_sessions_handler)),
+            )
+            .service(
+                web::resource("/api_keys")
+                    .route(web::post().to(admin_handler::create_api_key_handler)),
+            )
+            .service(
+                web::resource("/invite_codes")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::get().to(invite_code_handler::get_invite_codes_handler))
+                    .route(web::post().to(invite_code_handler::create_invite_code_handler)),
+            )
+            .service(
+                web::resource("/invite_codes/{id}")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::delete().to(invite_code_handler::revoke_invite_code_handler)),
+            )
+            .service(
+                web::resource("/api_keys/{id}")
+                    .route(web::delete().to(admin_handler::revoke_api_key_handler)),
+            )
+            .service(
+                web::resource("/users").route(web::post().to(admin_handler::create_user_handler)),
+            )
+            .service(
+                web::resource("/impersonations")
+                    .route(web::get().to(impersonation_handler::get_impersonations_handler))
+                    .route(web::post().to(impersonation_handler::impersonate_user_handler)),
+            )
+            .service(
+                web::resource("/impersonations/{id}")
+                    .route(web::delete().to(impersonation_handler::revoke_impersonation_handler)),
+            )
+            .service(
+                web::resource("/users/import")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .app_data(web::PayloadConfig::new(
+                        state.config.payload.user_import_limit,
+                    ))
+                    .route(web::post().to(admin_handler::import_users_handler)),
+            )
+            .service(
+                web::resource("/users/{id}/role")
+                    .route(web::put().to(admin_handler::change_user_role_handler)),
+            )
+            .service(
+                web::resource("/users/{id}/deactivate")
+                    .route(web::post().to(admin_handler::deactivate_user_handler)),
+            )
+            .service(
+                web::resource("/users/{id}/organization")
+                    .route(web::put().to(organization_handler::update_user_organization_handler)),
+            )
+            .service(
+                web::resource("/organizations")
+                    .route(web::get().to(organization_handler::get_organizations_handler))
+                    .route(web::post().to(organization_handler::create_organization_handler)),
+            )
+            .service(
+                web::resource("/organizations/{id}/quota")
+                    .route(web::put().to(quota_handler::update_quota_handler)),
+            )
+            .service(web::resource("/usage").route(web::get().to(quota_handler::get_usage_handler)))
+            .service(
+                web::resource("/dispatchers/{id}/area")
+                    .route(web::put().to(dispatcher_handler::transfer_dispatcher_handler)),
+            )
+            .service(
+                web::resource("/reports")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::get().to(admin_handler::get_reports_handler)),
+            )
+            .service(
+                web::resource("/retention/report")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::get().to(admin_handler::get_retention_report_handler)),
+            )
+            .service(
+                web::resource("/orders/export")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::get().to(admin_handler::export_orders_handler)),
+            )
+            .service(
+                web::resource("/audit_logs/export")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::get().to(admin_handler::export_audit_logs_handler)),
+            )
+            .service(
+                web::resource("/backup")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .app_data(web::PayloadConfig::new(
+                        state.config.payload.backup_import_limit,
+                    ))
+                    .route(web::get().to(admin_handler::export_backup_handler))
+                    .route(web::post().to(admin_handler::restore_backup_handler)),
+            )
+            .service(
+                web::resource("/notifications/dead_letters")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::get().to(notification_handler::get_dead_letters_handler)),
+            )
+            .service(
+                web::resource("/webhooks")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::get().to(webhook_handler::get_webhook_subscriptions_handler))
+                    .route(web::post().to(webhook_handler::create_webhook_subscription_handler)),
+            )
+            .service(
+                web::resource("/webhooks/{id}")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::delete().to(webhook_handler::delete_webhook_subscription_handler)),
+            )
+            .service(
+                web::resource("/webhooks/{id}/deliveries")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::get().to(webhook_handler::get_webhook_deliveries_handler)),
+            )
+            .service(
+                web::resource("/feature_flags")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::get().to(feature_flag_handler::get_feature_flags_handler)),
+            )
+            .service(
+                web::resource("/feature_flags/{name}")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::put().to(feature_flag_handler::update_feature_flag_handler))
+                    .route(web::delete().to(feature_flag_handler::reset_feature_flag_handler)),
+            )
+            .service(
+                web::resource("/areas/{id}/boundary")
+                    .route(web::put().to(map_handler::update_area_boundary_handler)),
+            )
+            .service(
+                web::resource("/config")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::get().to(runtime_config_handler::get_runtime_config_handler))
+                    .route(web::put().to(runtime_config_handler::update_runtime_config_handler)),
+            )
+            .service(
+                web::resource("/config/reload")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::post().to(runtime_config_handler::reload_runtime_config_handler)),
+            )
+            .service(
+                web::resource("/log-level")
+                    .wrap(DefaultOrganizationMiddleware)
+                    .route(web::put().to(runtime_config_handler::update_log_level_handler)),
+            ),
+    )
+    .service(
+        web::scope("/debug")
+            // 全組織のテーブルを消して入れ直すため、既定の組織の管理者だけに許す。
+            // DEBUG_ENDPOINTS_ENABLED が設定されていない場合はハンドラーが 404 を返す
+            .wrap(DefaultOrganizationMiddleware)
+            .wrap(
+                AuthMiddleware::new(auth_service_for_middleware.clone())
+                    .require_permission(Permission::UseDebugEndpoints),
+            )
+            .service(web::resource("/seed").route(web::post().to(debug_handler::seed_handler)))
+            .service(web::resource("/reset").route(web::post().to(debug_handler::reset_handler))),
+    )
+    // 外部システム向けの読み取り専用ルート。ユーザーセッションではなく API キーで認証する
+    .service(
+        web::scope("/partner")
+            .wrap(ApiKeyMiddleware::new(
+                api_key_service_for_middleware.clone(),
+            ))
+            .service(
+                web::resource("/order/list")
+                    .route(web::get().to(order_handler::get_paginated_orders_handler)),
+            )
+            .service(
+                web::resource("/order/{id}").route(web::get().to(order_handler::get_order_handler)),
+            )
+            .service(
+                web::resource("/tow_truck/list")
+                    .route(web::get().to(tow_truck_handler::get_paginated_tow_trucks_handler)),
+            )
+            .service(
+                web::resource("/tow_truck/{id}")
+                    .route(web::get().to(tow_truck_handler::get_tow_truck_handler)),
+            ),
+    )
+    .service(
+        web::scope("/tow_truck")
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+            .service(
+                web::resource("/list")
+                    .route(web::get().to(tow_truck_handler::get_paginated_tow_trucks_handler)),
+            )
+            .service(
+                web::resource("/location")
+                    .route(web::post().to(tow_truck_handler::update_location_handler)),
+            )
+            .service(
+                web::resource("/nearest").route(
+                    web::get().to(tow_truck_handler::get_nearest_available_tow_trucks_handler),
+                ),
+            )
+            .service(
+                web::resource("/{id}")
+                    .route(web::get().to(tow_truck_handler::get_tow_truck_handler)),
+            ),
+    )
+    .service(
+        web::scope("/order")
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+            .service(web::resource("").route(web::post().to(order_handler::create_order_handler)))
+            .service(
+                web::resource("/list")
+                    .route(web::get().to(order_handler::get_paginated_orders_handler)),
+            )
+            .service(
+                web::resource("/search").route(web::get().to(order_handler::search_orders_handler)),
+            )
+            .service(
+                web::resource("/dashboard")
+                    .route(web::get().to(order_handler::get_dispatcher_dashboard_handler)),
+            )
+            .service(
+                web::resource("/status")
+                    .route(web::post().to(order_handler::update_order_status_handler)),
+            )
+            .service(
+                web::resource("/client")
+                    .route(web::post().to(order_handler::create_client_order_handler)),
+            )
+            .service(
+                web::resource("/dispatcher")
+                    .route(web::post().to(order_handler::create_dispatcher_order_handler)),
+            )
+            .service(
+                web::resource("/scheduled")
+                    .route(web::get().to(order_handler::get_scheduled_orders_handler))
+                    .route(web::post().to(order_handler::create_scheduled_order_handler)),
+            )
+            .service(
+                web::resource("/scheduled/{id}")
+                    .route(web::put().to(order_handler::update_scheduled_order_handler)),
+            )
+            .service(
+                web::resource("/scheduled/{id}/cancel")
+                    .route(web::post().to(order_handler::cancel_order_handler)),
+            )
+            .service(
+                web::resource("/chats/unread")
+                    .route(web::get().to(chat_handler::get_unread_counts_handler)),
+            )
+            .service(web::resource("/{id}").route(web::get().to(order_handler::get_order_handler)))
+            .service(
+                web::resource("/{id}/cancel")
+                    .route(web::post().to(order_handler::cancel_order_handler)),
+            )
+            .service(
+                web::resource("/{id}/priority")
+                    .route(web::put().to(order_handler::update_order_priority_handler)),
+            )
+            .service(
+                web::resource("/{id}/notes")
+                    .route(web::get().to(order_note_handler::get_order_notes_handler))
+                    .route(web::post().to(order_note_handler::create_order_note_handler)),
+            )
+            .service(
+                web::resource("/{id}/attachments")
+                    .app_data(web::PayloadConfig::new(
+                        state.config.payload.order_attachment_limit,
+                    ))
+                    .route(web::get().to(order_note_handler::get_order_attachments_handler))
+                    .route(web::post().to(order_note_handler::upload_order_attachment_handler)),
+            )
+            .service(
+                web::resource("/{id}/attachments/{attachment_id}")
+                    .route(web::get().to(order_note_handler::get_order_attachment_handler)),
+            )
+            .service(
+                web::resource("/{id}/messages")
+                    .route(web::get().to(chat_handler::get_order_messages_handler))
+                    .route(web::post().to(chat_handler::send_order_message_handler)),
+            )
+            .service(
+                web::resource("/{id}/messages/read")
+                    .route(web::post().to(chat_handler::mark_order_messages_read_handler)),
+            )
+            .service(
+                web::resource("/{id}/messages/ws")
+                    .route(web::get().to(chat_handler::order_chat_ws_handler)),
+            ),
+    )
+    .service(
+        web::scope("/dispatcher")
+            .wrap(
+                AuthMiddleware::new(auth_service_for_middleware.clone())
+                    .require_permission(Permission::DispatchOrders),
+            )
+            .service(
+                web::resource("/availability")
+                    .route(web::put().to(dispatcher_handler::update_availability_handler)),
+            )
+            .service(
+                web::resource("/available")
+                    .route(web::get().to(dispatcher_handler::get_available_dispatchers_handler)),
+            )
+            .service(
+                web::resource("/load")
+                    .route(web::get().to(dispatcher_handler::get_area_load_handler)),
+            ),
+    )
+    .service(
+        web::scope("/clients")
+            .wrap(
+                AuthMiddleware::new(auth_service_for_middleware.clone())
+                    .require_permission(Permission::DispatchOrders),
+            )
+            .service(web::resource("").route(web::post().to(client_handler::create_client_handler)))
+            .service(
+                web::resource("/lookup")
+                    .route(web::get().to(client_handler::lookup_client_handler)),
+            )
+            .service(
+                web::resource("/{id}")
+                    .route(web::get().to(client_handler::get_client_handler))
+                    .route(web::patch().to(client_handler::update_client_handler)),
+            )
+            .service(
+                web::resource("/{id}/orders")
+                    .route(web::get().to(client_handler::get_client_orders_handler))
+                    .route(web::post().to(client_handler::create_client_order_handler)),
+            ),
+    )
+    .service(
+        web::scope("/leaderboard")
+            .wrap(
+                AuthMiddleware::new(auth_service_for_middleware.clone())
+                    .require_permission(Permission::ViewLeaderboard),
+            )
+            .service(
+                web::resource("")
+                    .route(web::get().to(leaderboard_handler::get_leaderboard_handler)),
+            ),
+    )
+    .service(
+        web::scope("/stats")
+            .wrap(
+                AuthMiddleware::new(auth_service_for_middleware.clone())
+                    .require_permission(Permission::DispatchOrders),
+            )
+            .service(
+                web::resource("/summary")
+                    .route(web::get().to(stats_handler::get_stats_summary_handler)),
+            ),
+    )
+    .service(
+        web::resource("/sync")
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+            .route(web::get().to(sync_handler::get_sync_handler)),
+    )
+    .service(
+        web::resource("/graphql")
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+            .route(web::post().to(graphql_handler::graphql_handler)),
+    )
+    .service(
+        web::scope("/notification")
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+            .service(
+                web::resource("/preferences")
+                    .route(
+                        web::get().to(notification_handler::get_notification_preferences_handler),
+                    )
+                    .route(
+                        web::put()
+                            .to(notification_handler::update_notification_preferences_handler),
+                    ),
+            )
+            .service(
+                web::resource("/push_tokens")
+                    .route(web::post().to(notification_handler::register_push_token_handler))
+                    .route(web::delete().to(notification_handler::unregister_push_token_handler)),
+            )
+            .service(
+                web::resource("/push_tokens/web_push_key")
+                    .route(web::get().to(notification_handler::get_web_push_key_handler)),
+            )
+            .service(
+                web::resource("/ws")
+                    .route(web::get().to(notification_handler::notification_ws_handler)),
+            ),
+    )
+    .service(
+        web::scope("/vehicle")
+            .wrap(
+                AuthMiddleware::new(auth_service_for_middleware.clone())
+                    .require_permission(Permission::ManageVehicles),
+            )
+            .service(
+                web::resource("").route(web::post().to(vehicle_handler::register_vehicle_handler)),
+            )
+            .service(
+                web::resource("/list").route(web::get().to(vehicle_handler::get_vehicles_handler)),
+            )
+            .service(
+                web::resource("/{id}")
+                    .route(web::get().to(vehicle_handler::get_vehicle_handler))
+                    .route(web::put().to(vehicle_handler::update_vehicle_handler))
+                    .route(web::delete().to(vehicle_handler::delete_vehicle_handler)),
+            )
+            .service(
+                web::resource("/{id}/status")
+                    .route(web::put().to(vehicle_handler::update_vehicle_status_handler)),
+            ),
+    )
+    .service(
+        web::scope("/map")
+            .wrap(AuthMiddleware::new(auth_service_for_middleware.clone()))
+            .service(
+                web::resource("/update_edge")
+                    .route(web::put().to(map_handler::update_edge_handler)),
+            )
+            .service(
+                web::resource("/travel_times")
+                    .route(web::post().to(map_handler::get_travel_times_handler)),
+            )
+            .service(
+                web::resource("/geocode").route(web::get().to(geocoding_handler::geocode_handler)),
+            )
+            .service(
+                web::resource("/reverse_geocode")
+                    .route(web::get().to(geocoding_handler::reverse_geocode_handler)),
+            ),
+    );
 }
@@ -0,0 +1,122 @@
+use std::rc::Rc;
+use std::time::Instant;
+
+use actix_web::{
+    body::{BodySize, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use log::info;
+use serde::Serialize;
+
+use crate::errors::AppError;
+use crate::infrastructure::request_id;
+use crate::models::user::Session;
+
+pub struct AccessLogMiddleware;
+
+#[derive(Serialize)]
+struct AccessLogEntry<'a> {
+    method: &'a str,
+    path: &'a str,
+    status: u16,
+    duration_ms: f64,
+    user_id: Option<i32>,
+    bytes_out: Option<u64>,
+    error: Option<String>,
+    request_id: Option<String>,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AccessLogMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AccessLogMiddlewareMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AccessLogMiddlewareMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct AccessLogMiddlewareMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AccessLogMiddlewareMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started_at = Instant::now();
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+        let service = self.service.clone();
+        let request_id = request_id::current();
+
+        Box::pin(async move {
+            let result = service.call(req).await;
+            let duration_ms = started_at.elapsed().as_secs_f64() * 1000.0;
+
+            // 認証ミドルウェアが設定したセッションから、リクエストしたユーザーを特定する
+            let entry = match &result {
+                Ok(res) => AccessLogEntry {
+                    method: &method,
+                    path: &path,
+                    status: res.status().as_u16(),
+                    duration_ms,
+                    user_id: res
+                        .request()
+                        .extensions()
+                        .get::<Session>()
+                        .map(|session| session.user_id),
+                    bytes_out: match res.response().body().size() {
+                        BodySize::Sized(size) => Some(size),
+                        _ => None,
+                    },
+                    error: res.response().error().map(describe_error),
+                    request_id,
+                },
+                Err(e) => AccessLogEntry {
+                    method: &method,
+                    path: &path,
+                    status: e.as_response_error().status_code().as_u16(),
+                    duration_ms,
+                    user_id: None,
+                    bytes_out: None,
+                    error: Some(describe_error(e)),
+                    request_id,
+                },
+            };
+
+            if let Ok(line) = serde_json::to_string(&entry) {
+                info!(target: "access_log", "{}", line);
+            }
+
+            result
+        })
+    }
+}
+
+// AppError の場合は付けられた文脈と原因もあわせて記録する
+fn describe_error(error: &actix_web::Error) -> String {
+    match error.as_error::<AppError>() {
+        Some(e) => e.chain(),
+        None => error.to_string(),
+    }
+}
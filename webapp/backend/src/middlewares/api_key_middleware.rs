@@ -0,0 +1,107 @@
+use std::rc::Rc;
+use std::sync::Arc;
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use log::debug;
+
+use crate::{
+    api::versioning::unversioned_path, app_state::AppApiKeyService, errors::AppError,
+    models::organization::OrganizationScope,
+};
+
+pub const API_KEY_HEADER_NAME: &str = "X-API-Key";
+
+pub struct ApiKeyMiddleware {
+    api_key_service: Arc<AppApiKeyService>,
+}
+
+impl ApiKeyMiddleware {
+    pub fn new(api_key_service: Arc<AppApiKeyService>) -> Self {
+        ApiKeyMiddleware { api_key_service }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ApiKeyMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ApiKeyMiddlewareMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiKeyMiddlewareMiddleware {
+            service: Rc::new(service),
+            api_key_service: self.api_key_service.clone(),
+        }))
+    }
+}
+
+pub struct ApiKeyMiddlewareMiddleware<S> {
+    service: Rc<S>,
+    api_key_service: Arc<AppApiKeyService>,
+}
+
+impl<S, B> Service<ServiceRequest> for ApiKeyMiddlewareMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let api_key = req
+            .headers()
+            .get(API_KEY_HEADER_NAME)
+            .and_then(|h| h.to_str().ok())
+            .map(|s| s.to_string());
+
+        let api_key_service = self.api_key_service.clone();
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            let result = match &api_key {
+                Some(api_key) => {
+                    api_key_service
+                        .authenticate(api_key, &unversioned_path(req.path()))
+                        .await
+                }
+                None => Err(AppError::Unauthorized),
+            };
+
+            match result {
+                Ok(key) => {
+                    debug!(
+                        "API キー {} ({}, id={}) で {} にアクセスしました",
+                        key.name,
+                        key.key_prefix,
+                        key.id,
+                        req.path()
+                    );
+                    req.extensions_mut()
+                        .insert(OrganizationScope::new(key.organization_id));
+                    req.extensions_mut().insert(key);
+                    service.call(req).await
+                }
+                Err(AppError::Forbidden) => Err(actix_web::error::ErrorForbidden(
+                    "API key is not allowed for this route",
+                )),
+                Err(_) => Err(actix_web::error::ErrorUnauthorized(
+                    "Invalid or missing API key",
+                )),
+            }
+        })
+    }
+}
@@ -1,22 +1,45 @@
+use std::rc::Rc;
 use std::sync::Arc;
 
 use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    Error,
+    web, Error, HttpMessage,
 };
 use futures_util::future::{ready, LocalBoxFuture, Ready};
+use log::error;
+use serde_json::json;
 
 use crate::{
-    domains::auth_service::AuthService, repositories::auth_repository::AuthRepositoryImpl,
+    app_state::{AppAuditLogService, AppAuthService},
+    config::AppConfig,
+    models::{audit_log::NewAuditLog, role::Permission, user::Session},
+    utils::{session_client_from_request, session_token_from_request},
 };
 
 pub struct AuthMiddleware {
-    auth_service: Arc<AuthService<AuthRepositoryImpl>>,
+    auth_service: Arc<AppAuthService>,
+    required_permission: Option<Permission>,
+    forbid_impersonation: bool,
 }
 
 impl AuthMiddleware {
-    pub fn new(auth_service: Arc<AuthService<AuthRepositoryImpl>>) -> Self {
-        AuthMiddleware { auth_service }
+    pub fn new(auth_service: Arc<AppAuthService>) -> Self {
+        AuthMiddleware {
+            auth_service,
+            required_permission: None,
+            forbid_impersonation: false,
+        }
+    }
+
+    pub fn require_permission(mut self, permission: Permission) -> Self {
+        self.required_permission = Some(permission);
+        self
+    }
+
+    // 管理者がなりかわったセッションでは使わせない。パスワードやロールの変更など
+    pub fn forbid_impersonation(mut self) -> Self {
+        self.forbid_impersonation = true;
+        self
     }
 }
 
@@ -34,15 +57,19 @@ where
 
     fn new_transform(&self, service: S) -> Self::Future {
         ready(Ok(AuthMiddlewareMiddleware {
-            service,
+            service: Rc::new(service),
             auth_service: self.auth_service.clone(),
+            required_permission: self.required_permission,
+            forbid_impersonation: self.forbid_impersonation,
         }))
     }
 }
 
 pub struct AuthMiddlewareMiddleware<S> {
-    service: S,
-    auth_service: Arc<AuthService<AuthRepositoryImpl>>,
+    service: Rc<S>,
+    auth_service: Arc<AppAuthService>,
+    required_permission: Option<Permission>,
+    forbid_impersonation: bool,
 }
 
 impl<S, B> Service<ServiceRequest> for AuthMiddlewareMiddleware<S>
@@ -57,29 +84,104 @@ where
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        let auth_header = req
-            .headers()
-            .get("Authorization")
-            .and_then(|h| h.to_str().ok())
-            .map(|s| s.to_string());
-        let auth_header = Arc::new(auth_header);
+        let auth_header = match req.app_data::<web::Data<AppConfig>>() {
+            Some(config) => session_token_from_request(req.request(), &config.session),
+            None => req
+                .headers()
+                .get("Authorization")
+                .and_then(|h| h.to_str().ok())
+                .map(|s| s.to_string()),
+        };
 
         let auth_service = self.auth_service.clone();
-        let fut = self.service.call(req);
+        let service = self.service.clone();
+        let required_permission = self.required_permission;
+        let forbid_impersonation = self.forbid_impersonation;
 
         Box::pin(async move {
-            let is_valid_token = match &*auth_header {
-                Some(token) => auth_service.validate_session(token).await.is_ok(),
-                None => false,
+            let authenticated = match &auth_header {
+                Some(token) => auth_service
+                    .authenticate_with_organization(token)
+                    .await
+                    .ok(),
+                None => None,
             };
 
-            if is_valid_token {
-                fut.await
-            } else {
-                Err(actix_web::error::ErrorUnauthorized(
+            match authenticated {
+                Some((session, organization)) => {
+                    if forbid_impersonation && session.is_impersonation() {
+                        return Err(actix_web::error::ErrorForbidden(
+                            "Not allowed while impersonating",
+                        ));
+                    }
+                    if let Some(permission) = required_permission {
+                        if auth_service.authorize(&session, permission).await.is_err() {
+                            return Err(actix_web::error::ErrorForbidden("Permission denied"));
+                        }
+                    }
+                    auth_service.record_session_activity(
+                        &session,
+                        session_client_from_request(req.request()),
+                    );
+                    // 以降のハンドラーは、この組織のデータだけを扱う
+                    req.extensions_mut().insert(organization);
+                    if !session.is_impersonation() {
+                        req.extensions_mut().insert(session);
+                        return service.call(req).await;
+                    }
+
+                    // なりかわったセッションでのリクエストは、すべて監査ログに残す
+                    let audit_log = req.app_data::<web::Data<AppAuditLogService>>().cloned();
+                    let (method, path) = (req.method().to_string(), req.path().to_string());
+                    req.extensions_mut().insert(session.clone());
+                    let result = service.call(req).await;
+                    let status = match &result {
+                        Ok(response) => response.status(),
+                        Err(err) => err.as_response_error().status_code(),
+                    };
+                    if let Some(audit_log) = audit_log {
+                        record_impersonated_request(
+                            &audit_log,
+                            &session,
+                            &method,
+                            &path,
+                            status.as_u16(),
+                        )
+                        .await;
+                    }
+                    result
+                }
+                None => Err(actix_web::error::ErrorUnauthorized(
                     "Invalid or missing token",
-                ))
+                )),
             }
         })
     }
 }
+
+async fn record_impersonated_request(
+    audit_log: &AppAuditLogService,
+    session: &Session,
+    method: &str,
+    path: &str,
+    status: u16,
+) {
+    let entry = NewAuditLog {
+        actor_user_id: session.impersonator_user_id,
+        action: "impersonation.request",
+        target_type: Some("user"),
+        target_id: Some(session.user_id.to_string()),
+        detail: Some(json!({
+            "session_id": session.id,
+            "method": method,
+            "path": path,
+            "status": status,
+        })),
+    };
+    if let Err(err) = audit_log.record(entry).await {
+        error!(
+            "なりかわったセッション {} のリクエストを監査ログに残せませんでした: {:?}",
+            session.id, err
+        );
+    }
+}
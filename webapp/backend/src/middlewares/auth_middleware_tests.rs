@@ -0,0 +1,174 @@
+// なりかわったセッションの扱いを、メモリに保持するリポジトリを使った AuthService で確認する
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+
+use super::auth_middleware::AuthMiddleware;
+use crate::app_state::AppAuthService;
+use crate::config::{
+    EmailVerificationConfig, FeatureFlagConfig, MailerConfig, QuotaConfig, RegistrationConfig,
+    WorkerPoolConfig,
+};
+use crate::domains::auth_service::{AuthRepository, AuthService};
+use crate::domains::auth_service_tests::session_config;
+use crate::domains::quota_service::QuotaService;
+use crate::infrastructure::db::create_lazy_pools;
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::feature_flags::FeatureFlags;
+use crate::infrastructure::mailer::MailerImpl;
+use crate::infrastructure::worker_pool::WorkerPool;
+use crate::models::organization::{OrganizationScope, DEFAULT_ORGANIZATION_ID};
+use crate::models::role::Role;
+use crate::repositories::auth_repository_backend::AuthRepositoryBackend;
+use crate::repositories::auth_repository_contract_tests::test_db_config;
+use crate::repositories::cached_auth_repository::CachedAuthRepository;
+use crate::repositories::memory_auth_repository::MemoryAuthRepository;
+use crate::repositories::quota_repository::QuotaRepositoryImpl;
+use crate::utils::hash_password;
+
+// 認証では上限を確認しないため、上限の取得先には接続しないプールを渡す
+fn auth_service(repository: MemoryAuthRepository) -> Arc<AppAuthService> {
+    let db_config = test_db_config("mysql://localhost/app".to_string());
+    let event_bus = Arc::new(EventBus::new());
+
+    Arc::new(AuthService::new(
+        CachedAuthRepository::new(
+            AuthRepositoryBackend::Memory(repository),
+            &db_config,
+            &event_bus,
+            Arc::new(FeatureFlags::new(&FeatureFlagConfig {
+                defaults: HashMap::new(),
+                reload_interval: Duration::ZERO,
+            })),
+        ),
+        Arc::new(QuotaService::new(
+            QuotaRepositoryImpl::new(create_lazy_pools(&db_config)),
+            &QuotaConfig {
+                orders_per_day: None,
+                active_dispatchers: None,
+                avatar_storage_bytes: None,
+            },
+        )),
+        &session_config(),
+        &EmailVerificationConfig {
+            required: false,
+            token_ttl: Duration::from_secs(60),
+            verify_url: String::new(),
+        },
+        &RegistrationConfig {
+            self_registration_enabled: true,
+            invite_required_roles: Vec::new(),
+        },
+        Arc::new(MailerImpl::from_config(&MailerConfig::Log)),
+        WorkerPool::new(
+            "password",
+            &WorkerPoolConfig {
+                concurrency: 1,
+                queue_limit: 16,
+            },
+        ),
+        event_bus,
+    ))
+}
+
+async fn login(
+    auth_service: &AppAuthService,
+    repository: &MemoryAuthRepository,
+    username: &str,
+    role: Role,
+) -> String {
+    repository
+        .create_user(
+            username,
+            &hash_password("password").unwrap(),
+            role,
+            DEFAULT_ORGANIZATION_ID,
+        )
+        .await
+        .unwrap();
+    auth_service
+        .login_user(username, "password", None, None, &Default::default())
+        .await
+        .unwrap()
+        .session_token
+}
+
+// 管理者のトークンと、管理者がクライアントになりかわったセッションのトークンを返す
+async fn impersonation_tokens() -> (Arc<AppAuthService>, String, String) {
+    let repository = MemoryAuthRepository::new();
+    let auth_service = auth_service(repository.clone());
+    let admin_token = login(&auth_service, &repository, "admin_a", Role::Admin).await;
+    let client_token = login(&auth_service, &repository, "client_a", Role::Client).await;
+
+    let admin = auth_service.authenticate(&admin_token).await.unwrap();
+    let client = auth_service.authenticate(&client_token).await.unwrap();
+    let impersonation = auth_service
+        .impersonate_user(
+            &admin,
+            OrganizationScope::new(DEFAULT_ORGANIZATION_ID),
+            client.user_id,
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+
+    (auth_service, admin_token, impersonation.session_token)
+}
+
+async fn status_of(auth_service: Arc<AppAuthService>, uri: &str, token: &str) -> StatusCode {
+    let app = test::init_service(
+        App::new()
+            .service(
+                web::scope("/forbidden")
+                    .wrap(AuthMiddleware::new(auth_service.clone()).forbid_impersonation())
+                    .route("", web::post().to(HttpResponse::Ok)),
+            )
+            .service(
+                web::scope("/allowed")
+                    .wrap(AuthMiddleware::new(auth_service))
+                    .route("", web::post().to(HttpResponse::Ok)),
+            ),
+    )
+    .await;
+    let request = test::TestRequest::post()
+        .uri(uri)
+        .insert_header(("Authorization", token))
+        .to_request();
+
+    match test::try_call_service(&app, request).await {
+        Ok(response) => response.status(),
+        Err(err) => err.as_response_error().status_code(),
+    }
+}
+
+#[actix_rt::test]
+async fn impersonated_session_is_rejected_where_impersonation_is_forbidden() {
+    let (auth_service, _, impersonation_token) = impersonation_tokens().await;
+
+    assert_eq!(
+        status_of(auth_service, "/forbidden", &impersonation_token).await,
+        StatusCode::FORBIDDEN
+    );
+}
+
+#[actix_rt::test]
+async fn impersonated_session_is_accepted_elsewhere() {
+    let (auth_service, _, impersonation_token) = impersonation_tokens().await;
+
+    assert_eq!(
+        status_of(auth_service, "/allowed", &impersonation_token).await,
+        StatusCode::OK
+    );
+}
+
+#[actix_rt::test]
+async fn own_session_is_accepted_where_impersonation_is_forbidden() {
+    let (auth_service, admin_token, _) = impersonation_tokens().await;
+
+    assert_eq!(
+        status_of(auth_service, "/forbidden", &admin_token).await,
+        StatusCode::OK
+    );
+}
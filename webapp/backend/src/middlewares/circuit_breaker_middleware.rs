@@ -0,0 +1,83 @@
+use std::rc::Rc;
+use std::sync::Arc;
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+
+use crate::errors::AppError;
+use crate::infrastructure::circuit_breaker::{track_db_activity, CircuitBreaker, DbActivity};
+
+// DB の接続に関わる障害が起きたリクエストを数え、遮断中は処理せずに 503 を返す。
+// 外部 API のタイムアウトなど DB 以外の理由で失敗したリクエストは数えない
+pub struct CircuitBreakerMiddleware {
+    circuit_breaker: Arc<CircuitBreaker>,
+}
+
+impl CircuitBreakerMiddleware {
+    pub fn new(circuit_breaker: Arc<CircuitBreaker>) -> Self {
+        CircuitBreakerMiddleware { circuit_breaker }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for CircuitBreakerMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CircuitBreakerMiddlewareMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CircuitBreakerMiddlewareMiddleware {
+            service: Rc::new(service),
+            circuit_breaker: self.circuit_breaker.clone(),
+        }))
+    }
+}
+
+pub struct CircuitBreakerMiddlewareMiddleware<S> {
+    service: Rc<S>,
+    circuit_breaker: Arc<CircuitBreaker>,
+}
+
+impl<S, B> Service<ServiceRequest> for CircuitBreakerMiddlewareMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let circuit_breaker = self.circuit_breaker.clone();
+
+        Box::pin(async move {
+            if let Err(retry_after) = circuit_breaker.acquire() {
+                return Err(AppError::ServiceUnavailable(retry_after).into());
+            }
+
+            let activity = Rc::new(DbActivity::default());
+            let result = track_db_activity(activity.clone(), service.call(req)).await;
+            if activity.failed() {
+                circuit_breaker.record_failure();
+            } else if activity.used() {
+                circuit_breaker.record_success();
+            } else {
+                circuit_breaker.release_probe();
+            }
+
+            result
+        })
+    }
+}
@@ -0,0 +1,102 @@
+use std::rc::Rc;
+
+use actix_web::{
+    body::{BodySize, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{self, ContentEncoding},
+    Error,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+
+use crate::config::CompressionConfig;
+
+// actix-web の Compress の内側に置き、圧縮しないレスポンスに Content-Encoding: identity を付けて除外する。
+// 画像のようにすでに圧縮されているデータや、小さすぎて効果のないレスポンスは圧縮しない
+pub struct CompressionPolicyMiddleware {
+    config: Rc<CompressionConfig>,
+}
+
+impl CompressionPolicyMiddleware {
+    pub fn new(config: CompressionConfig) -> Self {
+        CompressionPolicyMiddleware {
+            config: Rc::new(config),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for CompressionPolicyMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CompressionPolicyMiddlewareMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CompressionPolicyMiddlewareMiddleware {
+            service: Rc::new(service),
+            config: self.config.clone(),
+        }))
+    }
+}
+
+pub struct CompressionPolicyMiddlewareMiddleware<S> {
+    service: Rc<S>,
+    config: Rc<CompressionConfig>,
+}
+
+impl<S, B> Service<ServiceRequest> for CompressionPolicyMiddlewareMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let config = self.config.clone();
+
+        Box::pin(async move {
+            let mut res = service.call(req).await?;
+
+            let headers = res.headers();
+            let is_allowed_type = headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.split(';').next().unwrap_or_default().trim())
+                .is_some_and(|content_type| {
+                    config
+                        .content_types
+                        .iter()
+                        .any(|allowed| allowed.eq_ignore_ascii_case(content_type))
+                });
+            // ストリーミングなどでサイズが分からないレスポンスは、許可された種類であれば圧縮する
+            let is_large_enough = match res.response().body().size() {
+                BodySize::Sized(size) => size >= config.min_bytes,
+                BodySize::None => false,
+                BodySize::Stream => true,
+            };
+
+            let should_compress = is_allowed_type && is_large_enough;
+            if config.enabled
+                && !should_compress
+                && !res.headers().contains_key(header::CONTENT_ENCODING)
+            {
+                res.headers_mut().insert(
+                    header::CONTENT_ENCODING,
+                    ContentEncoding::Identity.to_header_value(),
+                );
+            }
+
+            Ok(res)
+        })
+    }
+}
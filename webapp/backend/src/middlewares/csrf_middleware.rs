@@ -0,0 +1,102 @@
+use std::rc::Rc;
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::Method,
+    web, Error,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+
+use crate::{
+    config::{AppConfig, SessionTransport},
+    utils::constant_time_eq,
+};
+
+pub const CSRF_COOKIE_NAME: &str = "csrf_token";
+pub const CSRF_HEADER_NAME: &str = "X-CSRF-Token";
+
+pub struct CsrfMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for CsrfMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CsrfMiddlewareMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CsrfMiddlewareMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct CsrfMiddlewareMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for CsrfMiddlewareMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let is_valid = match req.app_data::<web::Data<AppConfig>>() {
+            Some(config) => is_csrf_valid(&req, config),
+            None => true,
+        };
+
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            match is_valid {
+                true => service.call(req).await,
+                false => Err(actix_web::error::ErrorForbidden("Invalid CSRF token")),
+            }
+        })
+    }
+}
+
+fn is_csrf_valid(req: &ServiceRequest, config: &AppConfig) -> bool {
+    if config.session.transport != SessionTransport::Cookie {
+        return true;
+    }
+
+    if matches!(
+        *req.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    ) {
+        return true;
+    }
+
+    // セッション Cookie が付与されていないリクエストは、ブラウザに勝手に送られる認証情報を持たない
+    if req.cookie(&config.session.cookie_name).is_none() {
+        return true;
+    }
+
+    // Cookie と同じ値をヘッダーにも載せられるのは同一オリジンのスクリプトだけ (double submit cookie)
+    let cookie_token = req.cookie(CSRF_COOKIE_NAME);
+    let header_token = req
+        .headers()
+        .get(CSRF_HEADER_NAME)
+        .and_then(|h| h.to_str().ok());
+
+    match (cookie_token, header_token) {
+        (Some(cookie_token), Some(header_token)) => {
+            !header_token.is_empty()
+                && constant_time_eq(cookie_token.value().as_bytes(), header_token.as_bytes())
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,58 @@
+// Cookie でセッションを受け渡す場合に、変更を伴うリクエストで CSRF トークンを確かめることを確認する
+use actix_web::{cookie::Cookie, http::StatusCode, test, web, App, HttpResponse};
+
+use super::csrf_middleware::{CsrfMiddleware, CSRF_COOKIE_NAME, CSRF_HEADER_NAME};
+use crate::config::{AppConfig, SessionTransport};
+
+async fn status_of(request: test::TestRequest) -> StatusCode {
+    let mut config = AppConfig::from_env_without_db();
+    config.session.transport = SessionTransport::Cookie;
+    let session_cookie = Cookie::new(config.session.cookie_name.clone(), "session");
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(config))
+            .wrap(CsrfMiddleware)
+            .route("/orders", web::get().to(HttpResponse::Ok))
+            .route("/orders", web::post().to(HttpResponse::Ok)),
+    )
+    .await;
+    let request = request
+        .uri("/orders")
+        .cookie(session_cookie)
+        .cookie(Cookie::new(CSRF_COOKIE_NAME, "token"))
+        .to_request();
+
+    match test::try_call_service(&app, request).await {
+        Ok(response) => response.status(),
+        Err(err) => err.as_response_error().status_code(),
+    }
+}
+
+#[actix_rt::test]
+async fn post_without_csrf_header_is_rejected() {
+    assert_eq!(
+        status_of(test::TestRequest::post()).await,
+        StatusCode::FORBIDDEN
+    );
+}
+
+#[actix_rt::test]
+async fn post_with_mismatched_csrf_header_is_rejected() {
+    assert_eq!(
+        status_of(test::TestRequest::post().insert_header((CSRF_HEADER_NAME, "other"))).await,
+        StatusCode::FORBIDDEN
+    );
+}
+
+#[actix_rt::test]
+async fn post_with_matching_csrf_header_is_accepted() {
+    assert_eq!(
+        status_of(test::TestRequest::post().insert_header((CSRF_HEADER_NAME, "token"))).await,
+        StatusCode::OK
+    );
+}
+
+#[actix_rt::test]
+async fn get_does_not_require_csrf_header() {
+    assert_eq!(status_of(test::TestRequest::get()).await, StatusCode::OK);
+}
@@ -0,0 +1,82 @@
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    rt, Error,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+
+use crate::errors::AppError;
+use crate::infrastructure::deadline;
+
+// リクエストごとに処理の期限を設け、超えた場合は処理を打ち切って 504 を返す。
+// 打ち切ると実行中のクエリも破棄される。期限は deadline::current() でハンドラ以下に伝わる。
+// 書き込みのリクエストは途中で打ち切ると複数の更新の一部だけが反映されうるため、期限を伝えるだけで打ち切らない
+pub struct RequestDeadlineMiddleware {
+    timeout: Duration,
+}
+
+impl RequestDeadlineMiddleware {
+    // 0 を指定した場合は期限を設けない
+    pub fn new(timeout: Duration) -> Self {
+        RequestDeadlineMiddleware { timeout }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestDeadlineMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestDeadlineMiddlewareMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestDeadlineMiddlewareMiddleware {
+            service: Rc::new(service),
+            timeout: self.timeout,
+        }))
+    }
+}
+
+pub struct RequestDeadlineMiddlewareMiddleware<S> {
+    service: Rc<S>,
+    timeout: Duration,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestDeadlineMiddlewareMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let timeout = self.timeout;
+
+        Box::pin(async move {
+            if timeout.is_zero() {
+                return service.call(req).await;
+            }
+
+            let deadline = Instant::now() + timeout;
+            if !req.method().is_safe() {
+                return deadline::scope(deadline, service.call(req)).await;
+            }
+            match rt::time::timeout(timeout, deadline::scope(deadline, service.call(req))).await {
+                Ok(result) => result,
+                Err(_) => Err(AppError::GatewayTimeout.into()),
+            }
+        })
+    }
+}
@@ -0,0 +1,71 @@
+use std::rc::Rc;
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    Error,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+
+use crate::infrastructure::i18n;
+
+// Accept-Language からロケールを決め、リクエストの処理全体でエラーメッセージをそのロケールで返す。
+// 内側で発生したエラーもロケールを参照できるよう、RequestIdMiddleware のすぐ内側で包む
+pub struct LocaleMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for LocaleMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = LocaleMiddlewareMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(LocaleMiddlewareMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct LocaleMiddlewareMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for LocaleMiddlewareMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let locale = i18n::negotiate(
+            req.headers()
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok()),
+        );
+
+        Box::pin(i18n::scope(locale, async move {
+            let http_req = req.request().clone();
+            let res = match service.call(req).await {
+                Ok(res) => res.map_into_left_body(),
+                Err(err) => {
+                    ServiceResponse::new(http_req, err.error_response()).map_into_right_body()
+                }
+            };
+
+            Ok(res)
+        }))
+    }
+}
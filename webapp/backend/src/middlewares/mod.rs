@@ -1 +1,14 @@
+pub mod access_log_middleware;
+pub mod api_key_middleware;
 pub mod auth_middleware;
+#[cfg(test)]
+mod auth_middleware_tests;
+pub mod circuit_breaker_middleware;
+pub mod compression_middleware;
+pub mod csrf_middleware;
+#[cfg(test)]
+mod csrf_middleware_tests;
+pub mod deadline_middleware;
+pub mod locale_middleware;
+pub mod organization_middleware;
+pub mod request_id_middleware;
@@ -0,0 +1,65 @@
+use std::rc::Rc;
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+
+use crate::errors::AppError;
+use crate::models::organization::OrganizationScope;
+
+// 組織に分けていない、環境全体のデータや設定を扱うルートを既定の組織だけに許可する。
+// 組織は外側の認証ミドルウェアが添えたものを使うため、その内側に置く
+pub struct DefaultOrganizationMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for DefaultOrganizationMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = DefaultOrganizationMiddlewareMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(DefaultOrganizationMiddlewareMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct DefaultOrganizationMiddlewareMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for DefaultOrganizationMiddlewareMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let is_default = req
+            .extensions()
+            .get::<OrganizationScope>()
+            .is_some_and(|organization| organization.is_default());
+
+        Box::pin(async move {
+            if !is_default {
+                return Err(AppError::Forbidden.into());
+            }
+
+            service.call(req).await
+        })
+    }
+}
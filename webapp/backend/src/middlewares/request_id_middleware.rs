@@ -0,0 +1,80 @@
+use std::rc::Rc;
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderName, HeaderValue},
+    Error,
+};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+
+use crate::infrastructure::request_id;
+
+pub const REQUEST_ID_HEADER_NAME: &str = "X-Request-Id";
+
+// 受け取った X-Request-Id、なければ生成した ID をリクエストの処理全体に request_id::current() で伝え、
+// レスポンスのヘッダーにも付ける。ログやエラーのレスポンスにも同じ ID が載るよう、最も外側で包む
+pub struct RequestIdMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestIdMiddlewareMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdMiddlewareMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestIdMiddlewareMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdMiddlewareMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let request_id = req
+            .headers()
+            .get(REQUEST_ID_HEADER_NAME)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| request_id::is_valid(value))
+            .map(|value| value.to_string())
+            .unwrap_or_else(request_id::generate);
+
+        Box::pin(request_id::scope(request_id.clone(), async move {
+            let http_req = req.request().clone();
+            // 内側のミドルウェアが返したエラーも、ID を参照できるこの中でレスポンスに変換する
+            let mut res = match service.call(req).await {
+                Ok(res) => res.map_into_left_body(),
+                Err(err) => {
+                    ServiceResponse::new(http_req, err.error_response()).map_into_right_body()
+                }
+            };
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                res.headers_mut()
+                    .insert(HeaderName::from_static("x-request-id"), value);
+            }
+
+            Ok(res)
+        }))
+    }
+}
@@ -0,0 +1,22 @@
+use sqlx::FromRow;
+
+#[derive(FromRow, Clone, Debug)]
+pub struct ApiKey {
+    pub id: i32,
+    pub organization_id: i32,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: String,
+    pub is_active: bool,
+}
+
+impl ApiKey {
+    // scopes はカンマ区切りのパスプレフィックス。"*" はすべてのルートを許可する
+    pub fn allows_path(&self, path: &str) -> bool {
+        self.scopes
+            .split(',')
+            .map(|scope| scope.trim())
+            .filter(|scope| !scope.is_empty())
+            .any(|scope| scope == "*" || path.starts_with(scope))
+    }
+}
@@ -0,0 +1,26 @@
+use serde::Serialize;
+use sqlx::FromRow;
+
+#[derive(FromRow, Clone, Debug)]
+pub struct Area {
+    pub id: i32,
+    pub name: String,
+    pub organization_id: i32,
+}
+
+// boundary は頂点の JSON 配列
+#[derive(FromRow, Clone, Debug)]
+pub struct AreaBoundaryRow {
+    pub id: i32,
+    pub name: String,
+    pub organization_id: i32,
+    pub boundary: String,
+}
+
+// 範囲外の座標を指定された場合に、近くのエリアとして返すもの
+#[derive(Serialize, Clone, Debug)]
+pub struct NearbyArea {
+    pub area_id: i32,
+    pub name: String,
+    pub distance: f64,
+}
@@ -0,0 +1,23 @@
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+#[derive(FromRow, Clone, Debug)]
+pub struct AuditLog {
+    pub id: i64,
+    pub actor_user_id: Option<i32>,
+    pub action: String,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    // JSON 型のカラムは文字列として読み込む
+    pub detail: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NewAuditLog {
+    pub actor_user_id: Option<i32>,
+    pub action: &'static str,
+    pub target_type: Option<&'static str>,
+    pub target_id: Option<String>,
+    pub detail: Option<serde_json::Value>,
+}
@@ -0,0 +1,46 @@
+use std::str::FromStr;
+
+use sqlx::FromRow;
+
+use crate::errors::AppError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeEntity {
+    Order,
+    Dispatcher,
+    User,
+    // データ全体が置き換えられた。entity_id は使わない
+    Reset,
+}
+
+impl ChangeEntity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeEntity::Order => "order",
+            ChangeEntity::Dispatcher => "dispatcher",
+            ChangeEntity::User => "user",
+            ChangeEntity::Reset => "reset",
+        }
+    }
+}
+
+impl FromStr for ChangeEntity {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "order" => Ok(ChangeEntity::Order),
+            "dispatcher" => Ok(ChangeEntity::Dispatcher),
+            "user" => Ok(ChangeEntity::User),
+            "reset" => Ok(ChangeEntity::Reset),
+            _ => Err(AppError::InternalServerError),
+        }
+    }
+}
+
+#[derive(FromRow, Clone, Debug)]
+pub struct ChangeLogEntry {
+    pub id: i64,
+    pub entity_type: String,
+    pub entity_id: i32,
+}
@@ -0,0 +1,21 @@
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+// 注文を受ける顧客。アカウントを持たない顧客では user_id は None
+#[derive(FromRow, Clone, Debug)]
+pub struct Client {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub name: String,
+    pub phone_number: String,
+    pub default_pickup_node_id: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NewClient {
+    pub user_id: Option<i32>,
+    pub name: String,
+    pub phone_number: String,
+    pub default_pickup_node_id: Option<i32>,
+}
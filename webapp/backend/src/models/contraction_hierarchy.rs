@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::graph::Graph;
+
+// 近道を追加するかを決める探索で確定させるノードの上限。
+// 上限に達した場合は近道を追加するため、結果の正しさは変わらず、前処理の時間と近道の数の釣り合いだけが変わる
+const WITNESS_SEARCH_SETTLE_LIMIT: usize = 128;
+
+// 縮約階層法で前処理したグラフ。道路は双方向のため、上り方向の辺だけを持てば
+// 出発地と目的地の両方からの探索に使える
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContractionHierarchy {
+    indices: HashMap<i32, u32>,
+    // 各ノードから、後に縮約した (順位の高い) ノードへの辺。近道を含む
+    upward: Vec<Vec<(u32, i32)>>,
+}
+
+impl ContractionHierarchy {
+    // 順位は「追加する近道の数 - 辺の数 + 縮約済みの隣接ノードの数」の小さい順に、縮約しながら決める
+    pub fn build(graph: &Graph) -> Self {
+        let mut node_ids: Vec<i32> = graph
+            .nodes
+            .keys()
+            .copied()
+            .chain(graph.edges.values().flatten().map(|edge| edge.node_b_id))
+            .chain(graph.edges.keys().copied())
+            .collect();
+        node_ids.sort_unstable();
+        node_ids.dedup();
+        let indices: HashMap<i32, u32> = node_ids
+            .iter()
+            .enumerate()
+            .map(|(index, node_id)| (*node_id, index as u32))
+            .collect();
+
+        // 縮約していないノードの間の辺。同じノードの間に複数の辺がある場合は短いものだけを残す
+        let mut adjacency: Vec<HashMap<u32, i32>> = vec![HashMap::new(); node_ids.len()];
+        for edge in graph.edges.values().flatten() {
+            let (a, b) = (indices[&edge.node_a_id], indices[&edge.node_b_id]);
+            if a == b || edge.weight < 0 {
+                continue;
+            }
+            let weight = adjacency[a as usize].entry(b).or_insert(edge.weight);
+            *weight = (*weight).min(edge.weight);
+        }
+
+        let mut upward = vec![Vec::new(); node_ids.len()];
+        let mut contracted_neighbors = vec![0i64; node_ids.len()];
+        let mut queue: BinaryHeap<Reverse<(i64, u32)>> = (0..node_ids.len() as u32)
+            .map(|node| {
+                let priority = find_shortcuts(&adjacency, node).len() as i64
+                    - adjacency[node as usize].len() as i64;
+                Reverse((priority, node))
+            })
+            .collect();
+
+        while let Some(Reverse((priority, node))) = queue.pop() {
+            // 周りのノードを縮約すると優先度が変わるため、取り出した時点で計算し直し、悪化していれば後に回す
+            let shortcuts = find_shortcuts(&adjacency, node);
+            let current = shortcuts.len() as i64 - adjacency[node as usize].len() as i64
+                + contracted_neighbors[node as usize];
+            if current > priority {
+                queue.push(Reverse((current, node)));
+                continue;
+            }
+
+            let neighbors = std::mem::take(&mut adjacency[node as usize]);
+            for (&neighbor, &weight) in &neighbors {
+                upward[node as usize].push((neighbor, weight));
+                adjacency[neighbor as usize].remove(&node);
+                contracted_neighbors[neighbor as usize] += 1;
+            }
+            for (a, b, weight) in shortcuts {
+                for (from, to) in [(a, b), (b, a)] {
+                    let current = adjacency[from as usize].entry(to).or_insert(weight);
+                    *current = (*current).min(weight);
+                }
+            }
+        }
+
+        ContractionHierarchy { indices, upward }
+    }
+
+    pub fn contains(&self, node_id: i32) -> bool {
+        self.indices.contains_key(&node_id)
+    }
+
+    // 出発地ごと・目的地ごとに 1 回ずつ上り方向に探索し、目的地側の結果をノードごとのバケットに置いて突き合わせる。
+    // 到達できない組み合わせと、グラフにないノードを含む組み合わせは None になる
+    pub fn distance_matrix(&self, sources: &[i32], targets: &[i32]) -> Vec<Vec<Option<i32>>> {
+        let mut buckets: HashMap<u32, Vec<(usize, i64)>> = HashMap::new();
+        for (target_index, target) in targets.iter().enumerate() {
+            let Some(&target) = self.indices.get(target) else {
+                continue;
+            };
+            for (node, distance) in self.upward_search(target) {
+                buckets
+                    .entry(node)
+                    .or_default()
+                    .push((target_index, distance));
+            }
+        }
+
+        sources
+            .iter()
+            .map(|source| {
+                let mut row = vec![i64::MAX; targets.len()];
+                if let Some(&source) = self.indices.get(source) {
+                    for (node, distance) in self.upward_search(source) {
+                        for (target_index, target_distance) in
+                            buckets.get(&node).into_iter().flatten()
+                        {
+                            row[*target_index] = row[*target_index].min(distance + target_distance);
+                        }
+                    }
+                }
+                row.into_iter()
+                    .map(|distance| i32::try_from(distance).ok())
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn upward_search(&self, from: u32) -> HashMap<u32, i64> {
+        let mut distances = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        distances.insert(from, 0i64);
+        queue.push(Reverse((0i64, from)));
+
+        while let Some(Reverse((distance, node))) = queue.pop() {
+            if distances.get(&node).is_some_and(|d| *d < distance) {
+                continue;
+            }
+            for &(next, weight) in &self.upward[node as usize] {
+                let new_distance = distance + weight as i64;
+                if new_distance < *distances.get(&next).unwrap_or(&i64::MAX) {
+                    distances.insert(next, new_distance);
+                    queue.push(Reverse((new_distance, next)));
+                }
+            }
+        }
+
+        distances
+    }
+}
+
+// node を除いた残りのグラフで、隣接ノードの組の間に node を通るより短い道がなければ近道が要る
+fn find_shortcuts(adjacency: &[HashMap<u32, i32>], node: u32) -> Vec<(u32, u32, i32)> {
+    let neighbors: Vec<(u32, i32)> = adjacency[node as usize]
+        .iter()
+        .map(|(neighbor, weight)| (*neighbor, *weight))
+        .collect();
+    let Some(max_weight) = neighbors.iter().map(|(_, weight)| *weight as i64).max() else {
+        return Vec::new();
+    };
+
+    let mut shortcuts = Vec::new();
+    for (i, &(from, from_weight)) in neighbors.iter().enumerate() {
+        let targets = &neighbors[i + 1..];
+        if targets.is_empty() {
+            continue;
+        }
+        let witness = witness_search(adjacency, from, node, from_weight as i64 + max_weight);
+        for &(to, to_weight) in targets {
+            let via_node = from_weight as i64 + to_weight as i64;
+            let has_witness = match witness.get(&to) {
+                Some(distance) => *distance <= via_node,
+                None => false,
+            };
+            if !has_witness {
+                shortcuts.push((from, to, i32::try_from(via_node).unwrap_or(i32::MAX)));
+            }
+        }
+    }
+
+    shortcuts
+}
+
+fn witness_search(
+    adjacency: &[HashMap<u32, i32>],
+    from: u32,
+    excluded: u32,
+    max_distance: i64,
+) -> HashMap<u32, i64> {
+    let mut distances = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    let mut settled = 0;
+    distances.insert(from, 0i64);
+    queue.push(Reverse((0i64, from)));
+
+    while let Some(Reverse((distance, node))) = queue.pop() {
+        if distances.get(&node).is_some_and(|d| *d < distance) {
+            continue;
+        }
+        settled += 1;
+        if distance > max_distance || settled > WITNESS_SEARCH_SETTLE_LIMIT {
+            break;
+        }
+        for (&next, &weight) in &adjacency[node as usize] {
+            if next == excluded {
+                continue;
+            }
+            let new_distance = distance + weight as i64;
+            if new_distance < *distances.get(&next).unwrap_or(&i64::MAX) {
+                distances.insert(next, new_distance);
+                queue.push(Reverse((new_distance, next)));
+            }
+        }
+    }
+
+    distances
+}
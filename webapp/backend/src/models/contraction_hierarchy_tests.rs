@@ -0,0 +1,120 @@
+// 縮約階層法の距離行列が、前処理をしないダイクストラ法と同じ距離を返すことを確認する
+use super::contraction_hierarchy::ContractionHierarchy;
+use super::graph::{Edge, Graph, Node};
+
+// 実行のたびに同じグラフになるよう、乱数の代わりに線形合同法で重みを決める
+struct Weights(u64);
+
+impl Weights {
+    fn next(&mut self, max: i32) -> i32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.0 >> 33) % max as u64) as i32 + 1
+    }
+}
+
+fn add_node(graph: &mut Graph, id: i32) {
+    graph.add_node(Node { id, x: 0, y: 0 });
+}
+
+fn add_edge(graph: &mut Graph, node_a_id: i32, node_b_id: i32, weight: i32) {
+    graph.add_edge(Edge {
+        node_a_id,
+        node_b_id,
+        weight,
+    });
+}
+
+// 格子状の道路に斜めの近道と同じノードの間の複数の辺を加えたものと、そこから到達できない小さな連結成分、
+// 辺のないノードからなるグラフ
+fn sample_graph(seed: u64) -> Graph {
+    const SIZE: i32 = 8;
+    let mut weights = Weights(seed);
+    let mut graph = Graph::new();
+    for id in 0..SIZE * SIZE {
+        add_node(&mut graph, id);
+    }
+    for row in 0..SIZE {
+        for column in 0..SIZE {
+            let id = row * SIZE + column;
+            if column + 1 < SIZE {
+                add_edge(&mut graph, id, id + 1, weights.next(20));
+            }
+            if row + 1 < SIZE {
+                add_edge(&mut graph, id, id + SIZE, weights.next(20));
+            }
+            if row + 1 < SIZE && column + 1 < SIZE && weights.next(4) == 1 {
+                add_edge(&mut graph, id, id + SIZE + 1, weights.next(40));
+            }
+        }
+    }
+    for _ in 0..10 {
+        let a = weights.next(SIZE * SIZE) - 1;
+        let b = weights.next(SIZE * SIZE) - 1;
+        add_edge(&mut graph, a, b, weights.next(60));
+    }
+
+    for id in 100..104 {
+        add_node(&mut graph, id);
+    }
+    add_edge(&mut graph, 100, 101, 5);
+    add_edge(&mut graph, 101, 102, 7);
+    add_edge(&mut graph, 100, 102, 15);
+    add_edge(&mut graph, 102, 103, 0);
+    add_node(&mut graph, 200);
+
+    graph
+}
+
+fn assert_matches_dijkstra(graph: &Graph, node_ids: &[i32]) {
+    let hierarchy = ContractionHierarchy::build(graph);
+    let matrix = hierarchy.distance_matrix(node_ids, node_ids);
+
+    for (source, row) in node_ids.iter().zip(&matrix) {
+        let expected = graph.dijkstra_distances_from(*source);
+        for (target, distance) in node_ids.iter().zip(row) {
+            let expected = if graph.nodes.contains_key(source) {
+                expected.get(target).copied()
+            } else {
+                None
+            };
+            assert_eq!(
+                *distance, expected,
+                "{} から {} への距離が一致しません",
+                source, target
+            );
+        }
+    }
+}
+
+#[test]
+fn distance_matrix_matches_dijkstra() {
+    for seed in [1, 7, 42] {
+        let graph = sample_graph(seed);
+        let mut node_ids: Vec<i32> = graph.nodes.keys().copied().collect();
+        node_ids.sort_unstable();
+        assert_matches_dijkstra(&graph, &node_ids);
+    }
+}
+
+#[test]
+fn distance_matrix_returns_none_for_unreachable_and_unknown_nodes() {
+    let graph = sample_graph(3);
+    let hierarchy = ContractionHierarchy::build(&graph);
+    assert!(hierarchy.contains(200));
+    assert!(!hierarchy.contains(999));
+
+    let matrix = hierarchy.distance_matrix(&[0, 100, 200, 999], &[63, 103, 200, 999]);
+
+    assert!(matrix[0][0].is_some());
+    assert_eq!(matrix[0][1], None);
+    assert_eq!(matrix[1][0], None);
+    assert_eq!(matrix[1][1], Some(12));
+    assert_eq!(matrix[2][2], Some(0));
+    assert_eq!(matrix[2][0], None);
+    assert_eq!(matrix[3], vec![None; 4]);
+    assert!(matrix.iter().all(|row| row[3].is_none()));
+    assert_matches_dijkstra(&graph, &[0, 63, 100, 103, 200, 999]);
+}
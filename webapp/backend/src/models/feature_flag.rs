@@ -0,0 +1,7 @@
+use sqlx::FromRow;
+
+#[derive(FromRow, Clone, Debug)]
+pub struct FeatureFlagRow {
+    pub name: String,
+    pub enabled: bool,
+}
@@ -0,0 +1,17 @@
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+// x と y が NULL の行は、見つからなかったという結果
+#[derive(FromRow, Clone, Debug)]
+pub struct GeocodeCacheEntry {
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+// address が NULL の行は、見つからなかったという結果
+#[derive(FromRow, Clone, Debug)]
+pub struct ReverseGeocodeCacheEntry {
+    pub address: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
@@ -1,5 +1,6 @@
 use sqlx::FromRow;
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
 
 #[derive(FromRow, Clone, Debug)]
 pub struct Node {
@@ -51,6 +52,14 @@ impl Graph {
     }
 
     pub fn shortest_path(&self, from_node_id: i32, to_node_id: i32) -> i32 {
+        self.distances_from(from_node_id)
+            .get(&to_node_id)
+            .cloned()
+            .unwrap_or(i32::MAX)
+    }
+
+    // 1 つのノードから到達できるすべてのノードへの最短距離。複数の目的地を比べる場合に 1 回の計算で済ませる
+    pub fn distances_from(&self, from_node_id: i32) -> HashMap<i32, i32> {
         let mut distances = HashMap::new();
         distances.insert(from_node_id, 0);
 
@@ -71,6 +80,34 @@ impl Graph {
             }
         }
 
-        distances.get(&to_node_id).cloned().unwrap_or(i32::MAX)
+        distances
+    }
+
+    // distances_from と同じ結果を優先度付きキューで求める。辺の重みが負にならない前提で使う
+    pub fn dijkstra_distances_from(&self, from_node_id: i32) -> HashMap<i32, i32> {
+        let mut distances = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        distances.insert(from_node_id, 0);
+        queue.push(Reverse((0i32, from_node_id)));
+
+        while let Some(Reverse((distance, node_id))) = queue.pop() {
+            if distances.get(&node_id).is_some_and(|d| *d < distance) {
+                continue;
+            }
+            let Some(edges) = self.edges.get(&node_id) else {
+                continue;
+            };
+            for edge in edges {
+                let Some(new_distance) = distance.checked_add(edge.weight) else {
+                    continue;
+                };
+                if new_distance < *distances.get(&edge.node_b_id).unwrap_or(&i32::MAX) {
+                    distances.insert(edge.node_b_id, new_distance);
+                    queue.push(Reverse((new_distance, edge.node_b_id)));
+                }
+            }
+        }
+
+        distances
     }
 }
@@ -0,0 +1,17 @@
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+use super::role::Role;
+
+#[derive(FromRow, Clone, Debug)]
+pub struct InviteCode {
+    pub id: i32,
+    pub code_prefix: String,
+    pub role: Role,
+    pub max_uses: i32,
+    pub use_count: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_by: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
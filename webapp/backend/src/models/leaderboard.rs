@@ -0,0 +1,11 @@
+use sqlx::FromRow;
+
+#[derive(FromRow, Clone, Debug)]
+pub struct DispatcherStats {
+    pub dispatcher_id: i32,
+    pub user_id: i32,
+    pub username: String,
+    pub completed_orders: i32,
+    pub eta_samples: i32,
+    pub eta_error_minutes_total: i64,
+}
@@ -0,0 +1,63 @@
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+// 過去のログインのうち、接続元の比較に使う項目
+#[derive(FromRow, Clone, Debug)]
+pub struct LoginAttempt {
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub area_id: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NewLoginAttempt {
+    pub user_id: i32,
+    pub succeeded: bool,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub area_id: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+// 成功したログインを不審と判断した理由
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoginAnomaly {
+    // 過去に成功したログインにない IP アドレス
+    NewIp,
+    // 過去に成功したログインにない User-Agent
+    NewDevice,
+    // 直前のログインから移動できないほど短い時間で、別のエリアからログインした
+    ImpossibleTravel { from_area_id: i32, to_area_id: i32 },
+    // 短い時間に続けて失敗した後に成功した
+    FailureBurst { failures: i64 },
+}
+
+impl LoginAnomaly {
+    // 監査ログに記録する識別子
+    pub fn code(&self) -> &'static str {
+        match self {
+            LoginAnomaly::NewIp => "new_ip",
+            LoginAnomaly::NewDevice => "new_device",
+            LoginAnomaly::ImpossibleTravel { .. } => "impossible_travel",
+            LoginAnomaly::FailureBurst { .. } => "failure_burst",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            LoginAnomaly::NewIp => "初めての接続元".to_string(),
+            LoginAnomaly::NewDevice => "初めての端末".to_string(),
+            LoginAnomaly::ImpossibleTravel {
+                from_area_id,
+                to_area_id,
+            } => format!(
+                "エリア {} から {} への短時間の移動",
+                from_area_id, to_area_id
+            ),
+            LoginAnomaly::FailureBurst { failures } => {
+                format!("直前に {} 回の失敗", failures)
+            }
+        }
+    }
+}
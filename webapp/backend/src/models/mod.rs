@@ -1,4 +1,25 @@
+pub mod api_key;
+pub mod area;
+pub mod audit_log;
+pub mod change_log;
+pub mod client;
+pub mod contraction_hierarchy;
+#[cfg(test)]
+mod contraction_hierarchy_tests;
+pub mod feature_flag;
+pub mod geocode;
 pub mod graph;
+pub mod invite_code;
+pub mod leaderboard;
+pub mod login_attempt;
+pub mod notification;
 pub mod order;
+pub mod organization;
+pub mod profile;
+pub mod report;
+pub mod role;
+pub mod stats;
 pub mod tow_truck;
 pub mod user;
+pub mod vehicle;
+pub mod webhook;
@@ -0,0 +1,93 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+use crate::errors::AppError;
+
+#[derive(FromRow, Clone, Debug)]
+pub struct NotificationPreferences {
+    pub user_id: i32,
+    pub websocket: bool,
+    pub email: bool,
+    pub webhook_url: Option<String>,
+}
+
+impl NotificationPreferences {
+    // 設定を登録していないユーザーの既定値
+    pub fn default_for(user_id: i32) -> Self {
+        NotificationPreferences {
+            user_id,
+            websocket: true,
+            email: false,
+            webhook_url: None,
+        }
+    }
+}
+
+// 通知先のユーザーの設定と、メールで送る場合の宛先
+#[derive(FromRow, Clone, Debug)]
+pub struct NotificationRecipient {
+    pub user_id: i32,
+    pub websocket: bool,
+    pub email: bool,
+    pub webhook_url: Option<String>,
+    pub email_address: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NewNotificationDeadLetter {
+    pub user_id: i32,
+    pub channel: &'static str,
+    pub target: String,
+    pub payload: String,
+    pub error: String,
+    pub attempts: i32,
+}
+
+#[derive(FromRow, Clone, Debug)]
+pub struct NotificationDeadLetter {
+    pub id: i32,
+    pub user_id: i32,
+    pub channel: String,
+    pub target: String,
+    pub payload: String,
+    pub error: String,
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushProvider {
+    Fcm,
+    WebPush,
+}
+
+impl PushProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PushProvider::Fcm => "fcm",
+            PushProvider::WebPush => "web_push",
+        }
+    }
+}
+
+impl FromStr for PushProvider {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "fcm" => Ok(PushProvider::Fcm),
+            "web_push" => Ok(PushProvider::WebPush),
+            _ => Err(AppError::BadRequest),
+        }
+    }
+}
+
+// 端末の登録トークン。Web Push では購読のエンドポイントの URL をトークンとして扱う
+#[derive(FromRow, Clone, Debug)]
+pub struct PushToken {
+    pub user_id: i32,
+    pub provider: String,
+    pub token: String,
+}
@@ -1,6 +1,10 @@
+use std::str::FromStr;
+
 use chrono::{DateTime, Utc};
 use sqlx::FromRow;
 
+use crate::errors::AppError;
+
 #[derive(FromRow, Clone, Debug)]
 pub struct Order {
     pub id: i32,
@@ -12,4 +16,235 @@ pub struct Order {
     pub car_value: f64,
     pub order_time: DateTime<Utc>,
     pub completed_time: Option<DateTime<Utc>>,
+    pub version: i32,
+    pub priority: String,
+}
+
+// 希望日時まで配車の対象にならない予約注文
+// 一括で作成する未割り当ての注文
+#[derive(Clone, Debug)]
+pub struct NewOrder {
+    pub organization_id: i32,
+    pub client_id: i32,
+    pub node_id: i32,
+    pub car_value: f64,
+}
+
+#[derive(FromRow, Clone, Debug)]
+pub struct ScheduledOrder {
+    pub id: i32,
+    pub client_id: i32,
+    pub node_id: i32,
+    pub dropoff_node_id: Option<i32>,
+    pub car_value: f64,
+    pub quoted_price: Option<i32>,
+    pub scheduled_for: DateTime<Utc>,
+    pub order_time: DateTime<Utc>,
+    pub version: i32,
+}
+
+#[derive(FromRow, Clone, Debug)]
+pub struct OrderNote {
+    pub id: i32,
+    pub order_id: i32,
+    pub author_id: i32,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// 注文の添付ファイル。name は画像のストアに保存したキー
+#[derive(FromRow, Clone, Debug)]
+pub struct OrderAttachment {
+    pub id: i32,
+    pub order_id: i32,
+    pub uploaded_by: i32,
+    pub name: String,
+    pub content_type: String,
+    pub bytes: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+// 注文のチャットのメッセージ
+#[derive(FromRow, Clone, Debug)]
+pub struct OrderMessage {
+    pub id: i32,
+    pub order_id: i32,
+    pub sender_id: i32,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// ノードの属するエリアを結合した注文
+#[derive(FromRow, Clone, Debug)]
+pub struct OrderWithArea {
+    pub id: i32,
+    pub client_id: i32,
+    pub dispatcher_id: Option<i32>,
+    pub tow_truck_id: Option<i32>,
+    pub status: String,
+    pub area_id: i32,
+    pub car_value: f64,
+    pub order_time: DateTime<Utc>,
+    pub completed_time: Option<DateTime<Utc>>,
+}
+
+// エクスポート用に、利用者名とエリアを結合した注文
+#[derive(FromRow, Clone, Debug)]
+pub struct OrderExportRow {
+    pub id: i32,
+    pub client_id: i32,
+    pub client_username: String,
+    pub dispatcher_id: Option<i32>,
+    pub tow_truck_id: Option<i32>,
+    pub status: String,
+    pub node_id: i32,
+    pub area_id: i32,
+    pub car_value: f64,
+    pub order_time: DateTime<Utc>,
+    pub completed_time: Option<DateTime<Utc>>,
+}
+
+// 差分同期用に、関係者の名前とエリアを結合した注文。一覧の OrderDto と同じ値を 1 回の問い合わせで読む
+#[derive(FromRow, Clone, Debug)]
+pub struct OrderSyncRow {
+    pub id: i32,
+    pub client_id: i32,
+    pub client_username: Option<String>,
+    pub dispatcher_id: Option<i32>,
+    pub dispatcher_user_id: Option<i32>,
+    pub dispatcher_username: Option<String>,
+    pub tow_truck_id: Option<i32>,
+    pub driver_user_id: Option<i32>,
+    pub driver_username: Option<String>,
+    pub status: String,
+    pub node_id: i32,
+    pub area_id: i32,
+    pub car_value: f64,
+    pub order_time: DateTime<Utc>,
+    pub completed_time: Option<DateTime<Utc>>,
+    pub version: i32,
+    pub priority: String,
+}
+
+// 完了した注文の、実績の集計に使う値
+#[derive(FromRow, Clone, Debug)]
+pub struct OrderCompletion {
+    pub id: i32,
+    pub dispatcher_id: Option<i32>,
+    pub order_time: DateTime<Utc>,
+    pub completed_time: DateTime<Utc>,
+    pub quoted_eta_minutes: Option<i32>,
+}
+
+// 到着予定時刻を計算し直す、配車済みの注文と割り当てた車両の最新の位置
+#[derive(FromRow, Clone, Debug)]
+pub struct InFlightOrder {
+    pub id: i32,
+    pub area_id: i32,
+    pub node_id: i32,
+    pub dropoff_node_id: Option<i32>,
+    pub tow_truck_node_id: i32,
+    pub order_time: DateTime<Utc>,
+    pub quoted_eta_minutes: Option<i32>,
+    pub eta_at: Option<DateTime<Utc>>,
+}
+
+// ディスパッチャーの異動で引き継いだ配車済みの注文。
+// new_dispatcher_id が None の注文は未割り当てに戻し、車両を解放した
+#[derive(Clone, Debug)]
+pub struct OrderHandover {
+    pub order_id: i32,
+    pub tow_truck_id: Option<i32>,
+    pub new_dispatcher_id: Option<i32>,
+}
+
+// 注文検索の条件。None の条件では絞り込まない
+#[derive(Debug, Clone)]
+pub struct OrderSearchCriteria {
+    pub organization_id: i32,
+    // 全文検索の BOOLEAN MODE にそのまま渡す検索語
+    pub client_name: Option<String>,
+    pub address: Option<String>,
+    // 注文 ID の前方一致を主キーの範囲に変換したもの。空の場合は絞り込まない
+    pub id_ranges: Vec<(i32, i32)>,
+    pub status: Option<String>,
+    pub area_id: Option<i32>,
+    pub ordered_from: Option<DateTime<Utc>>,
+    pub ordered_to: Option<DateTime<Utc>>,
+    pub min_car_value: Option<f64>,
+    pub max_car_value: Option<f64>,
+    pub limit: i32,
+    pub offset: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    // 予約注文。希望日時が近づくまで配車の対象にならない
+    Scheduled,
+    Pending,
+    Dispatched,
+    Completed,
+    Cancelled,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Scheduled => "scheduled",
+            OrderStatus::Pending => "pending",
+            OrderStatus::Dispatched => "dispatched",
+            OrderStatus::Completed => "completed",
+            OrderStatus::Cancelled => "cancelled",
+        }
+    }
+
+    // 完了した注文と、すでにキャンセルされた注文はキャンセルできない
+    pub fn is_cancellable(status: &str) -> bool {
+        [
+            OrderStatus::Scheduled,
+            OrderStatus::Pending,
+            OrderStatus::Dispatched,
+        ]
+        .iter()
+        .any(|cancellable| cancellable.as_str() == status)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderPriority {
+    Vip,
+    Scheduled,
+    Normal,
+}
+
+impl OrderPriority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderPriority::Vip => "vip",
+            OrderPriority::Scheduled => "scheduled",
+            OrderPriority::Normal => "normal",
+        }
+    }
+
+    // 通常の注文に対して何段階優先するか。1 段階は待ち時間の priority_aging に相当する
+    pub fn level(&self) -> u32 {
+        match self {
+            OrderPriority::Vip => 2,
+            OrderPriority::Scheduled => 1,
+            OrderPriority::Normal => 0,
+        }
+    }
+}
+
+impl FromStr for OrderPriority {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "vip" => Ok(OrderPriority::Vip),
+            "scheduled" => Ok(OrderPriority::Scheduled),
+            "normal" => Ok(OrderPriority::Normal),
+            _ => Err(AppError::BadRequest),
+        }
+    }
 }
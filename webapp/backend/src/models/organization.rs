@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+// 組織を導入する前のデータと、自分で登録したユーザーが属する組織。この組織の管理者が組織を管理する
+pub const DEFAULT_ORGANIZATION_ID: i32 = 1;
+
+#[derive(FromRow, Clone, Debug)]
+pub struct Organization {
+    pub id: i32,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// リクエストの対象とする組織。認証ミドルウェアがセッションのユーザーや API キーから決めて添える
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrganizationScope {
+    pub organization_id: i32,
+}
+
+impl OrganizationScope {
+    pub fn new(organization_id: i32) -> Self {
+        OrganizationScope { organization_id }
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.organization_id == DEFAULT_ORGANIZATION_ID
+    }
+}
+
+// 組織の利用上限。None の場合は上限なし
+#[derive(FromRow, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuotaLimits {
+    pub orders_per_day: Option<i64>,
+    pub active_dispatchers: Option<i64>,
+    pub avatar_storage_bytes: Option<i64>,
+}
+
+impl QuotaLimits {
+    // 個別に設定されていない上限は defaults の値を使う
+    pub fn or(self, defaults: QuotaLimits) -> QuotaLimits {
+        QuotaLimits {
+            orders_per_day: self.orders_per_day.or(defaults.orders_per_day),
+            active_dispatchers: self.active_dispatchers.or(defaults.active_dispatchers),
+            avatar_storage_bytes: self.avatar_storage_bytes.or(defaults.avatar_storage_bytes),
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use sqlx::FromRow;
+
+use super::role::Role;
+
+#[derive(FromRow, Clone, Debug)]
+pub struct UserProfile {
+    pub id: i32,
+    pub username: String,
+    pub role: Role,
+    pub display_name: Option<String>,
+    pub email: Option<String>,
+    pub phone_number: Option<String>,
+    pub image_version: i32,
+}
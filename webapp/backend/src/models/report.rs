@@ -0,0 +1,22 @@
+use sqlx::FromRow;
+
+#[derive(FromRow, Clone, Debug)]
+pub struct AreaCompletionCount {
+    pub area_id: i32,
+    pub completed_orders: i64,
+}
+
+// 平均は DECIMAL で返るため、合計と件数を読み込んで割る
+#[derive(FromRow, Clone, Debug)]
+pub struct AssignmentLatencyTotal {
+    pub assigned_orders: i64,
+    pub total_seconds: Option<i64>,
+}
+
+#[derive(FromRow, Clone, Debug)]
+pub struct DispatcherActivity {
+    pub dispatcher_id: i32,
+    pub assigned_orders: i64,
+    // 集計期間内で注文を担当していた時間の合計
+    pub busy_seconds: Option<i64>,
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::mysql::{MySql, MySqlTypeInfo, MySqlValueRef};
+use sqlx::{Decode, Encode, Type};
+
+use crate::errors::AppError;
+
+// users.role に保存される値。DB と JSON のどちらも小文字の文字列で表す
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Client,
+    Dispatcher,
+    Driver,
+    Admin,
+}
+
+// ロールごとに許可される操作。ルートやサービスはロール名ではなく権限で判定する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Permission {
+    // /admin 以下のユーザー管理・API キー管理など
+    Administer,
+    UseDebugEndpoints,
+    UseTotp,
+    // 車両の登録・更新と稼働状況の変更
+    ManageVehicles,
+    CreateOrders,
+    // 自分以外の注文のキャンセル
+    CancelAnyOrder,
+    ViewLeaderboard,
+    // 受付状態の切り替えと、受付中のディスパッチャーの参照
+    DispatchOrders,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Client => "client",
+            Role::Dispatcher => "dispatcher",
+            Role::Driver => "driver",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn permissions(&self) -> &'static [Permission] {
+        match self {
+            Role::Client => &[Permission::CreateOrders],
+            Role::Driver => &[],
+            Role::Dispatcher => &[
+                Permission::UseTotp,
+                Permission::ManageVehicles,
+                Permission::CancelAnyOrder,
+                Permission::ViewLeaderboard,
+                Permission::DispatchOrders,
+            ],
+            Role::Admin => &[
+                Permission::Administer,
+                Permission::UseDebugEndpoints,
+                Permission::UseTotp,
+                Permission::ManageVehicles,
+                Permission::CancelAnyOrder,
+                Permission::ViewLeaderboard,
+                Permission::DispatchOrders,
+            ],
+        }
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "client" => Ok(Role::Client),
+            "dispatcher" => Ok(Role::Dispatcher),
+            "driver" => Ok(Role::Driver),
+            "admin" => Ok(Role::Admin),
+            _ => Err(AppError::BadRequest),
+        }
+    }
+}
+
+// users.role は VARCHAR のため、文字列として読み書きする
+impl Type<MySql> for Role {
+    fn type_info() -> MySqlTypeInfo {
+        <str as Type<MySql>>::type_info()
+    }
+
+    fn compatible(ty: &MySqlTypeInfo) -> bool {
+        <str as Type<MySql>>::compatible(ty)
+    }
+}
+
+impl<'r> Decode<'r, MySql> for Role {
+    fn decode(value: MySqlValueRef<'r>) -> Result<Self, BoxDynError> {
+        let value = <&str as Decode<MySql>>::decode(value)?;
+        Ok(value.parse()?)
+    }
+}
+
+impl Encode<'_, MySql> for Role {
+    fn encode_by_ref(&self, buf: &mut Vec<u8>) -> IsNull {
+        <&str as Encode<MySql>>::encode(self.as_str(), buf)
+    }
+}
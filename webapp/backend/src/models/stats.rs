@@ -0,0 +1,10 @@
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+// 組織のある状態の注文数。reconciled_at は最後に orders から数え直した日時
+#[derive(FromRow, Clone, Debug)]
+pub struct OrderCounter {
+    pub status: String,
+    pub count: i64,
+    pub reconciled_at: Option<DateTime<Utc>>,
+}
@@ -9,3 +9,10 @@ pub struct TowTruck {
     pub area_id: i32,
     pub node_id: i32,
 }
+
+// 書き込みを待っている車両の位置
+#[derive(Clone, Debug)]
+pub struct NewLocation {
+    pub tow_truck_id: i32,
+    pub node_id: i32,
+}
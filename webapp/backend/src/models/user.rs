@@ -1,12 +1,57 @@
+use chrono::{DateTime, Utc};
 use sqlx::FromRow;
 
+use super::role::Role;
+
+// users.profile_image の既定値。画像をアップロードしていないことを表す
+pub const DEFAULT_PROFILE_IMAGE: &str = "default.png";
+
 #[derive(FromRow, Clone, Debug)]
 pub struct User {
     pub id: i32,
     pub username: String,
     pub password: String,
     pub profile_image: String,
-    pub role: String,
+    // プロフィール画像をアップロードするたびに上がる。画像の URL に含め、古い画像のキャッシュを使わせない
+    pub image_version: i32,
+    pub role: Role,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    pub is_active: bool,
+    // メールアドレスの確認が済むまではログインできない
+    pub email_verified: bool,
+    // 仮のパスワードが設定されている。ログイン時に新しいパスワードの設定を求める
+    pub password_reset_required: bool,
+    pub organization_id: i32,
+}
+
+// 一括登録で作成するユーザー。password はハッシュ化済み
+#[derive(Clone, Debug)]
+pub struct NewUser {
+    pub username: String,
+    pub password: String,
+    pub role: Role,
+    pub area_id: Option<i32>,
+}
+
+// 一括で作成するセッション
+#[derive(Clone, Debug)]
+pub struct NewSession {
+    pub user_id: i32,
+    pub session_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(FromRow, Clone, Debug)]
+pub struct StoredProfileImage {
+    pub name: String,
+    pub version: i32,
+}
+
+impl StoredProfileImage {
+    pub fn is_default(&self) -> bool {
+        self.name == DEFAULT_PROFILE_IMAGE
+    }
 }
 
 #[derive(FromRow, Clone, Debug)]
@@ -15,6 +60,43 @@ pub struct Session {
     pub user_id: i32,
     pub session_token: String,
     pub is_valid: bool,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    // 利用状況はまとめて書き込むため、直近のリクエストはまだ反映されていない場合がある
+    pub last_seen_at: Option<DateTime<Utc>>,
+    // 管理者がなりかわって発行したセッションの場合、その管理者のユーザー ID
+    pub impersonator_user_id: Option<i32>,
+}
+
+impl Session {
+    pub fn is_impersonation(&self) -> bool {
+        self.impersonator_user_id.is_some()
+    }
+}
+
+// セッションを使ったクライアント。ログイン時と、認証が必要なリクエストのたびに記録する
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SessionClient {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+// DB に書き込むまでの間、メモリに溜めておくセッションの利用状況
+#[derive(Clone, Debug)]
+pub struct SessionActivity {
+    pub session_token: String,
+    pub client: SessionClient,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+// パスワードなどを含まない、他のユーザーに見せてよい項目
+#[derive(FromRow, Clone, Debug)]
+pub struct UserSummary {
+    pub id: i32,
+    pub username: String,
+    pub role: Role,
 }
 
 #[derive(FromRow, Clone, Debug)]
@@ -23,3 +105,27 @@ pub struct Dispatcher {
     pub user_id: i32,
     pub area_id: i32,
 }
+
+// 有効なセッションを持つディスパッチャー
+#[derive(FromRow, Clone, Debug)]
+pub struct OnDutyDispatcher {
+    pub dispatcher_id: i32,
+    pub user_id: i32,
+    pub username: String,
+}
+
+#[derive(FromRow, Clone, Debug)]
+pub struct TotpBackupCode {
+    pub id: i32,
+    pub code_hash: String,
+}
+
+// 差分同期で返すユーザーの公開してよい値
+#[derive(FromRow, Clone, Debug)]
+pub struct UserSyncRow {
+    pub id: i32,
+    pub username: String,
+    pub role: Role,
+    pub is_active: bool,
+    pub image_version: i32,
+}
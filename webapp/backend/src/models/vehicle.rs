@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+// 車両は tow_trucks テーブルで管理する。位置情報を持たない登録情報だけを扱う
+#[derive(FromRow, Clone, Debug)]
+pub struct Vehicle {
+    pub id: i32,
+    pub driver_id: i32,
+    pub status: String,
+    pub area_id: i32,
+    pub capacity: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VehicleStatus {
+    Available,
+    Busy,
+    Maintenance,
+}
+
+impl VehicleStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VehicleStatus::Available => "available",
+            VehicleStatus::Busy => "busy",
+            VehicleStatus::Maintenance => "maintenance",
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+#[derive(FromRow, Clone, Debug)]
+pub struct WebhookSubscription {
+    pub id: i32,
+    pub url: String,
+    pub events: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WebhookSubscription {
+    // events はカンマ区切りのイベント名。未指定の場合はすべてのイベントを受け取る
+    pub fn event_list(&self) -> Option<Vec<String>> {
+        self.events.as_ref().map(|events| {
+            events
+                .split(',')
+                .map(|event| event.trim().to_string())
+                .filter(|event| !event.is_empty())
+                .collect()
+        })
+    }
+}
+
+#[derive(FromRow, Clone, Debug)]
+pub struct WebhookDelivery {
+    pub id: i32,
+    pub subscription_id: i32,
+    pub event: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// 送信に必要な登録先の情報をあわせて読み込んだ配信
+#[derive(FromRow, Clone, Debug)]
+pub struct PendingWebhookDelivery {
+    pub id: i32,
+    pub event: String,
+    pub payload: String,
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+}
@@ -0,0 +1,70 @@
+use crate::domains::api_key_service::ApiKeyRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::api_key::ApiKey;
+
+#[derive(Debug)]
+pub struct ApiKeyRepositoryImpl {
+    pools: DbPools,
+}
+
+impl ApiKeyRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        ApiKeyRepositoryImpl { pools }
+    }
+}
+
+impl ApiKeyRepository for ApiKeyRepositoryImpl {
+    async fn create_api_key(
+        &self,
+        organization_id: i32,
+        name: &str,
+        key_prefix: &str,
+        key_hash: &str,
+        scopes: &str,
+    ) -> Result<i32, AppError> {
+        let _timer = self.pools.query_timer("api_key_repository.create_api_key");
+        let result = sqlx::query(
+            "INSERT INTO api_keys (organization_id, name, key_prefix, key_hash, scopes) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(organization_id)
+        .bind(name)
+        .bind(key_prefix)
+        .bind(key_hash)
+        .bind(scopes)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.last_insert_id() as i32)
+    }
+
+    async fn find_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("api_key_repository.find_api_key_by_hash");
+        // 失効直後のキーが使われないよう、プライマリで確認する
+        let api_key = sqlx::query_as::<_, ApiKey>(
+            "SELECT id, organization_id, name, key_prefix, scopes, is_active FROM api_keys WHERE key_hash = ?",
+        )
+        .bind(key_hash)
+        .fetch_optional(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(api_key)
+    }
+
+    async fn deactivate_api_key(&self, organization_id: i32, id: i32) -> Result<bool, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("api_key_repository.deactivate_api_key");
+        let result = sqlx::query(
+            "UPDATE api_keys SET is_active = FALSE WHERE id = ? AND organization_id = ?",
+        )
+        .bind(id)
+        .bind(organization_id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,69 @@
+use crate::domains::audit_log_service::AuditLogRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::audit_log::NewAuditLog;
+use crate::repositories::bulk_insert;
+
+#[derive(Debug)]
+pub struct AuditLogRepositoryImpl {
+    pools: DbPools,
+}
+
+impl AuditLogRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        AuditLogRepositoryImpl { pools }
+    }
+}
+
+impl AuditLogRepository for AuditLogRepositoryImpl {
+    async fn create_audit_log(&self, entry: &NewAuditLog) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("audit_log_repository.create_audit_log");
+        sqlx::query(
+            "INSERT INTO audit_logs (actor_user_id, action, target_type, target_id, detail)
+            VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(entry.actor_user_id)
+        .bind(entry.action)
+        .bind(entry.target_type)
+        .bind(&entry.target_id)
+        .bind(entry.detail.as_ref().map(|detail| detail.to_string()))
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn create_audit_logs(&self, entries: &[NewAuditLog]) -> Result<(), AppError> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let _timer = self
+            .pools
+            .query_timer("audit_log_repository.create_audit_logs");
+        // 失敗した分を再試行したときに重複しないよう、文を分けた場合もまとめてコミットする
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        for chunk in entries.chunks(bulk_insert::rows_per_statement(5)) {
+            let sql = format!(
+                "INSERT INTO audit_logs (actor_user_id, action, target_type, target_id, detail) VALUES {}",
+                bulk_insert::values_placeholders(5, chunk.len())
+            );
+            let mut query = sqlx::query(&sql);
+            for entry in chunk {
+                query = query
+                    .bind(entry.actor_user_id)
+                    .bind(entry.action)
+                    .bind(entry.target_type)
+                    .bind(&entry.target_id)
+                    .bind(entry.detail.as_ref().map(|detail| detail.to_string()));
+            }
+            query.execute(&mut tx).await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
@@ -1,102 +1,793 @@
+use chrono::{DateTime, Utc};
+
 use crate::errors::AppError;
-use crate::models::user::{Dispatcher, User};
+use crate::infrastructure::db::DbPools;
+use crate::models::role::Role;
+use crate::models::user::{
+    Dispatcher, OnDutyDispatcher, SessionActivity, SessionClient, StoredProfileImage,
+    TotpBackupCode, User,
+};
 use crate::{domains::auth_service::AuthRepository, models::user::Session};
-use sqlx::mysql::MySqlPool;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct AuthRepositoryImpl {
-    pool: MySqlPool,
+    pools: DbPools,
 }
 
 impl AuthRepositoryImpl {
-    pub fn new(pool: MySqlPool) -> Self {
-        AuthRepositoryImpl { pool }
+    pub fn new(pools: DbPools) -> Self {
+        AuthRepositoryImpl { pools }
     }
 }
 
 impl AuthRepository for AuthRepositoryImpl {
     async fn find_user_by_id(&self, id: i32) -> Result<Option<User>, AppError> {
+        let _timer = self.pools.query_timer("auth_repository.find_user_by_id");
         let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
             .bind(id)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *self.pools.acquire_replica().await?)
             .await?;
 
         Ok(user)
     }
 
     async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_user_by_username");
         let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = ?")
             .bind(username)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *self.pools.acquire_replica().await?)
             .await?;
 
         Ok(user)
     }
 
-    async fn find_profile_image_name_by_user_id(
+    async fn find_user_by_username_from_primary(
         &self,
-        user_id: i32,
-    ) -> Result<Option<String>, AppError> {
-        let profile_image_name = sqlx::query_scalar("SELECT profile_image FROM users WHERE id = ?")
-            .bind(user_id)
-            .fetch_optional(&self.pool)
+        username: &str,
+    ) -> Result<Option<User>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_user_by_username_from_primary");
+        let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE username = ?")
+            .bind(username)
+            .fetch_optional(&mut *self.pools.acquire_primary().await?)
             .await?;
 
-        Ok(profile_image_name)
+        Ok(user)
+    }
+
+    async fn find_all_usernames(&self) -> Result<Vec<(i32, String)>, AppError> {
+        let _timer = self.pools.query_timer("auth_repository.find_all_usernames");
+        let usernames =
+            sqlx::query_as::<_, (i32, String)>("SELECT id, username FROM users ORDER BY id")
+                .fetch_all(&mut *self.pools.acquire_primary().await?)
+                .await?;
+
+        Ok(usernames)
+    }
+
+    async fn find_on_duty_dispatchers_by_area_id(
+        &self,
+        area_id: i32,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<OnDutyDispatcher>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_on_duty_dispatchers_by_area_id");
+        let dispatchers = sqlx::query_as::<_, OnDutyDispatcher>(
+            "SELECT
+                d.id AS dispatcher_id,
+                d.user_id,
+                u.username
+            FROM
+                dispatchers d
+            JOIN
+                users u
+            ON
+                u.id = d.user_id
+            WHERE
+                d.area_id = ?
+                AND u.is_active = TRUE
+                AND EXISTS (
+                    SELECT 1 FROM sessions s
+                    WHERE s.user_id = d.user_id AND s.is_valid = TRUE AND s.expires_at > ?
+                )
+            ORDER BY
+                d.id",
+        )
+        .bind(area_id)
+        .bind(now)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(dispatchers)
+    }
+
+    async fn find_profile_image_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<StoredProfileImage>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_profile_image_by_user_id");
+        let profile_image = sqlx::query_as::<_, StoredProfileImage>(
+            "SELECT profile_image AS name, image_version AS version FROM users WHERE id = ?",
+        )
+        .bind(user_id)
+        .fetch_optional(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(profile_image)
+    }
+
+    async fn update_profile_image_name(
+        &self,
+        user_id: i32,
+        profile_image_name: &str,
+    ) -> Result<i32, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.update_profile_image_name");
+        // 同時にアップロードされても上げた後の値を正しく返せるよう、LAST_INSERT_ID に渡して同じ文で受け取る
+        let result = sqlx::query(
+            "UPDATE users SET profile_image = ?, image_version = LAST_INSERT_ID(image_version + 1) WHERE id = ?",
+        )
+        .bind(profile_image_name)
+        .bind(user_id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound);
+        }
+
+        Ok(result.last_insert_id() as i32)
+    }
+
+    async fn replace_profile_image_name(
+        &self,
+        user_id: i32,
+        current: &str,
+        replacement: &str,
+    ) -> Result<Option<i32>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.replace_profile_image_name");
+        let result = sqlx::query(
+            "UPDATE users SET profile_image = ?, image_version = LAST_INSERT_ID(image_version + 1) WHERE id = ? AND profile_image = ?",
+        )
+        .bind(replacement)
+        .bind(user_id)
+        .bind(current)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(match result.rows_affected() {
+            0 => None,
+            _ => Some(result.last_insert_id() as i32),
+        })
     }
 
     async fn create_user(
         &self,
         username: &str,
         password: &str,
-        role: &str,
+        role: Role,
+        organization_id: i32,
     ) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO users (username, password, role) VALUES (?, ?, ?)")
-            .bind(username)
+        let _timer = self.pools.query_timer("auth_repository.create_user");
+        sqlx::query(
+            "INSERT INTO users (username, password, role, organization_id) VALUES (?, ?, ?, ?)",
+        )
+        .bind(username)
+        .bind(password)
+        .bind(role)
+        .bind(organization_id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn create_unverified_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        email: &str,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.create_unverified_user");
+        sqlx::query(
+            "INSERT INTO users (username, password, role, email, email_verified) VALUES (?, ?, ?, ?, FALSE)",
+        )
+        .bind(username)
+        .bind(password)
+        .bind(role)
+        .bind(email)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_recent_username_owner(
+        &self,
+        username: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<i32>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_recent_username_owner");
+        let user_id = sqlx::query_scalar(
+            "SELECT user_id FROM username_history WHERE username = ? AND changed_at >= ? ORDER BY changed_at DESC LIMIT 1",
+        )
+        .bind(username)
+        .bind(since)
+        .fetch_optional(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    async fn update_username(
+        &self,
+        user_id: i32,
+        old_username: &str,
+        new_username: &str,
+        changed_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("auth_repository.update_username");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+
+        sqlx::query(
+            "INSERT INTO username_history (user_id, username, changed_at) VALUES (?, ?, ?)",
+        )
+        .bind(user_id)
+        .bind(old_username)
+        .bind(changed_at)
+        .execute(&mut tx)
+        .await?;
+        sqlx::query("UPDATE users SET username = ? WHERE id = ?")
+            .bind(new_username)
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn update_password(&self, user_id: i32, password: &str) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("auth_repository.update_password");
+        sqlx::query("UPDATE users SET password = ?, password_reset_required = FALSE WHERE id = ?")
             .bind(password)
+            .bind(user_id)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn require_password_reset(&self, user_id: i32) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.require_password_reset");
+        sqlx::query("UPDATE users SET password_reset_required = TRUE WHERE id = ?")
+            .bind(user_id)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_user_role(&self, user_id: i32, role: Role) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("auth_repository.update_user_role");
+        sqlx::query("UPDATE users SET role = ? WHERE id = ?")
             .bind(role)
-            .execute(&self.pool)
+            .bind(user_id)
+            .execute(&mut *self.pools.acquire_primary().await?)
             .await?;
 
         Ok(())
     }
 
-    async fn create_session(&self, user_id: i32, session_token: &str) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO sessions (user_id, session_token) VALUES (?, ?)")
+    async fn deactivate_user(&self, user_id: i32) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("auth_repository.deactivate_user");
+        sqlx::query("UPDATE users SET is_active = FALSE WHERE id = ?")
             .bind(user_id)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_user_organization(
+        &self,
+        user_id: i32,
+        organization_id: i32,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.update_user_organization");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        sqlx::query("UPDATE users SET organization_id = ? WHERE id = ?")
+            .bind(organization_id)
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("UPDATE dispatchers SET organization_id = ? WHERE user_id = ?")
+            .bind(organization_id)
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn update_totp_secret(
+        &self,
+        user_id: i32,
+        totp_secret: Option<&str>,
+    ) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("auth_repository.update_totp_secret");
+        sqlx::query("UPDATE users SET totp_secret = ?, totp_enabled = FALSE WHERE id = ?")
+            .bind(totp_secret)
+            .bind(user_id)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn enable_totp(&self, user_id: i32) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("auth_repository.enable_totp");
+        sqlx::query(
+            "UPDATE users SET totp_enabled = TRUE WHERE id = ? AND totp_secret IS NOT NULL",
+        )
+        .bind(user_id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn replace_totp_backup_codes(
+        &self,
+        user_id: i32,
+        code_hashes: &[String],
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.replace_totp_backup_codes");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+
+        sqlx::query("DELETE FROM totp_backup_codes WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+        for code_hash in code_hashes {
+            sqlx::query("INSERT INTO totp_backup_codes (user_id, code_hash) VALUES (?, ?)")
+                .bind(user_id)
+                .bind(code_hash)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn find_unused_totp_backup_codes(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<TotpBackupCode>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_unused_totp_backup_codes");
+        let codes = sqlx::query_as::<_, TotpBackupCode>(
+            "SELECT id, code_hash FROM totp_backup_codes WHERE user_id = ? AND used_at IS NULL",
+        )
+        .bind(user_id)
+        .fetch_all(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(codes)
+    }
+
+    async fn mark_totp_backup_code_used(&self, id: i32) -> Result<bool, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.mark_totp_backup_code_used");
+        // 同じコードが並行して使われた場合に、片方だけが成功するよう未使用の行に限定する
+        let result = sqlx::query(
+            "UPDATE totp_backup_codes SET used_at = CURRENT_TIMESTAMP WHERE id = ? AND used_at IS NULL",
+        )
+        .bind(id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn find_totp_locked_until(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<DateTime<Utc>>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_totp_locked_until");
+        // 直前の失敗で書き込まれた期限を読み落とさないよう、プライマリから読む
+        let locked_until: Option<Option<DateTime<Utc>>> =
+            sqlx::query_scalar("SELECT totp_locked_until FROM users WHERE id = ?")
+                .bind(user_id)
+                .fetch_optional(&mut *self.pools.acquire_primary().await?)
+                .await?;
+
+        Ok(locked_until.flatten())
+    }
+
+    async fn record_totp_failure(
+        &self,
+        user_id: i32,
+        max_attempts: i32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.record_totp_failure");
+        // 並行した失敗を取りこぼさないよう、回数の加算と判定を 1 つの UPDATE で行う。
+        // MySQL は SET を左から順に評価するため、期限の判定には加算前の回数が使われる
+        sqlx::query(
+            "UPDATE users
+            SET totp_locked_until = IF(totp_failed_attempts + 1 >= ?, ?, totp_locked_until),
+                totp_failed_attempts = IF(totp_failed_attempts + 1 >= ?, 0, totp_failed_attempts + 1)
+            WHERE id = ?",
+        )
+        .bind(max_attempts)
+        .bind(locked_until)
+        .bind(max_attempts)
+        .bind(user_id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn accept_totp_step(&self, user_id: i32, step: i64) -> Result<bool, AppError> {
+        let _timer = self.pools.query_timer("auth_repository.accept_totp_step");
+        // 同じコードが並行して使われた場合に、片方だけが成功するよう古い時間枠の行に限定する
+        let result = sqlx::query(
+            "UPDATE users SET totp_last_step = ?, totp_failed_attempts = 0
+            WHERE id = ? AND (totp_last_step IS NULL OR totp_last_step < ?)",
+        )
+        .bind(step)
+        .bind(user_id)
+        .bind(step)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn reset_totp_failures(&self, user_id: i32) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.reset_totp_failures");
+        sqlx::query("UPDATE users SET totp_failed_attempts = 0 WHERE id = ?")
+            .bind(user_id)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn find_user_id_by_external_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<i32>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_user_id_by_external_identity");
+        let user_id = sqlx::query_scalar(
+            "SELECT user_id FROM external_identities WHERE provider = ? AND subject = ?",
+        )
+        .bind(provider)
+        .bind(subject)
+        .fetch_optional(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    async fn create_external_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+        user_id: i32,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.create_external_identity");
+        sqlx::query(
+            "INSERT INTO external_identities (provider, subject, user_id) VALUES (?, ?, ?)",
+        )
+        .bind(provider)
+        .bind(subject)
+        .bind(user_id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn create_session(
+        &self,
+        user_id: i32,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+        client: &SessionClient,
+    ) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("auth_repository.create_session");
+        sqlx::query(
+            "INSERT INTO sessions (user_id, session_token, expires_at, client_ip, user_agent, last_seen_at)
+            VALUES (?, ?, ?, ?, ?, UTC_TIMESTAMP())",
+        )
+        .bind(user_id)
+        .bind(session_token)
+        .bind(expires_at)
+        .bind(&client.ip)
+        .bind(&client.user_agent)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn create_impersonation_session(
+        &self,
+        user_id: i32,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+        impersonator_user_id: i32,
+        client: &SessionClient,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.create_impersonation_session");
+        sqlx::query(
+            "INSERT INTO sessions (user_id, session_token, expires_at, client_ip, user_agent, last_seen_at, impersonator_user_id)
+            VALUES (?, ?, ?, ?, ?, UTC_TIMESTAMP(), ?)",
+        )
+        .bind(user_id)
+        .bind(session_token)
+        .bind(expires_at)
+        .bind(&client.ip)
+        .bind(&client.user_agent)
+        .bind(impersonator_user_id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn update_session_activities(
+        &self,
+        activities: &[SessionActivity],
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.update_session_activities");
+        if activities.is_empty() {
+            return Ok(());
+        }
+
+        let query = format!(
+            "UPDATE sessions s
+            JOIN ({}) AS a ON a.session_token = s.session_token
+            SET
+                s.client_ip = COALESCE(a.client_ip, s.client_ip),
+                s.user_agent = COALESCE(a.user_agent, s.user_agent),
+                s.last_seen_at = GREATEST(COALESCE(s.last_seen_at, a.last_seen_at), a.last_seen_at)",
+            vec![
+                "SELECT ? AS session_token, ? AS client_ip, ? AS user_agent, ? AS last_seen_at";
+                activities.len()
+            ]
+            .join(" UNION ALL ")
+        );
+        let mut update = sqlx::query(&query);
+        for activity in activities {
+            update = update
+                .bind(&activity.session_token)
+                .bind(&activity.client.ip)
+                .bind(&activity.client.user_agent)
+                .bind(activity.last_seen_at);
+        }
+        update
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_session_expires_at(
+        &self,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.update_session_expires_at");
+        sqlx::query("UPDATE sessions SET expires_at = ? WHERE session_token = ?")
+            .bind(expires_at)
             .bind(session_token)
-            .execute(&self.pool)
+            .execute(&mut *self.pools.acquire_primary().await?)
             .await?;
 
         Ok(())
     }
 
     async fn delete_session(&self, session_token: &str) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("auth_repository.delete_session");
         sqlx::query("DELETE FROM sessions WHERE session_token = ?")
             .bind(session_token)
-            .execute(&self.pool)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_other_sessions_by_user_id(
+        &self,
+        user_id: i32,
+        keep_session_token: &str,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.delete_other_sessions_by_user_id");
+        sqlx::query("DELETE FROM sessions WHERE user_id = ? AND session_token <> ?")
+            .bind(user_id)
+            .bind(keep_session_token)
+            .execute(&mut *self.pools.acquire_primary().await?)
             .await?;
 
         Ok(())
     }
 
+    async fn delete_expired_sessions(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<u64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.delete_expired_sessions");
+        // 期限切れ・無効化済みのセッションに加え、ユーザーが削除されて孤立したセッションも対象にする
+        let result = sqlx::query(
+            "DELETE FROM sessions WHERE id IN (
+                SELECT id FROM (
+                    SELECT s.id FROM sessions s
+                    LEFT JOIN users u ON u.id = s.user_id
+                    WHERE s.expires_at <= ? OR s.is_valid = FALSE OR u.id IS NULL
+                    LIMIT ?
+                ) AS expired_sessions
+            )",
+        )
+        .bind(now)
+        .bind(limit)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     async fn find_session_by_session_token(
         &self,
         session_token: &str,
     ) -> Result<Session, AppError> {
-        let session =
-            sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE session_token = ?")
-                .bind(session_token)
-                .fetch_one(&self.pool)
-                .await?;
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_session_by_session_token");
+        let query = "SELECT * FROM sessions WHERE session_token = ?";
+        let session = sqlx::query_as::<_, Session>(query)
+            .bind(session_token)
+            .fetch_optional(&mut *self.pools.acquire_replica().await?)
+            .await?;
+
+        // ログイン直後のトークンはレプリカに未反映の場合があるため、プライマリで再確認する
+        let session = match session {
+            Some(session) => session,
+            None => {
+                sqlx::query_as::<_, Session>(query)
+                    .bind(session_token)
+                    .fetch_one(&mut *self.pools.acquire_primary().await?)
+                    .await?
+            }
+        };
 
         Ok(session)
     }
 
+    async fn find_sessions_by_user_id(
+        &self,
+        user_id: i32,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_sessions_by_user_id");
+        let sessions = sqlx::query_as::<_, Session>(
+            "SELECT * FROM sessions
+            WHERE user_id = ? AND is_valid = TRUE AND expires_at > ?
+            ORDER BY created_at DESC, id DESC",
+        )
+        .bind(user_id)
+        .bind(now)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    async fn find_impersonation_sessions(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_impersonation_sessions");
+        // 取り消した直後の一覧に残らないよう、プライマリから読む
+        let sessions = sqlx::query_as::<_, Session>(
+            "SELECT * FROM sessions
+            WHERE impersonator_user_id IS NOT NULL AND is_valid = TRUE AND expires_at > ?
+            ORDER BY created_at DESC, id DESC",
+        )
+        .bind(now)
+        .fetch_all(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    async fn find_recent_session_tokens(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<String>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_recent_session_tokens");
+        let session_tokens = sqlx::query_scalar::<_, String>(
+            "SELECT s.session_token FROM sessions s
+            JOIN users u ON u.id = s.user_id
+            WHERE s.is_valid = TRUE AND s.expires_at > ? AND u.is_active = TRUE
+            ORDER BY s.expires_at DESC
+            LIMIT ?",
+        )
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(session_tokens)
+    }
+
     async fn find_dispatcher_by_id(&self, id: i32) -> Result<Option<Dispatcher>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_dispatcher_by_id");
         let dispatcher = sqlx::query_as::<_, Dispatcher>("SELECT * FROM dispatchers WHERE id = ?")
             .bind(id)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&mut *self.pools.acquire_replica().await?)
             .await?;
 
         Ok(dispatcher)
@@ -106,22 +797,118 @@ impl AuthRepository for AuthRepositoryImpl {
         &self,
         user_id: i32,
     ) -> Result<Option<Dispatcher>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_dispatcher_by_user_id");
         let dispatcher =
             sqlx::query_as::<_, Dispatcher>("SELECT * FROM dispatchers WHERE user_id = ?")
                 .bind(user_id)
-                .fetch_optional(&self.pool)
+                .fetch_optional(&mut *self.pools.acquire_replica().await?)
                 .await?;
 
         Ok(dispatcher)
     }
 
+    async fn find_dispatcher_by_user_id_from_primary(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<Dispatcher>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_dispatcher_by_user_id_from_primary");
+        let dispatcher =
+            sqlx::query_as::<_, Dispatcher>("SELECT * FROM dispatchers WHERE user_id = ?")
+                .bind(user_id)
+                .fetch_optional(&mut *self.pools.acquire_primary().await?)
+                .await?;
+
+        Ok(dispatcher)
+    }
+
+    async fn find_area_organization_id(&self, area_id: i32) -> Result<Option<i32>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.find_area_organization_id");
+        let organization_id =
+            sqlx::query_scalar::<_, i32>("SELECT organization_id FROM areas WHERE id = ?")
+                .bind(area_id)
+                .fetch_optional(&mut *self.pools.acquire_replica().await?)
+                .await?;
+
+        Ok(organization_id)
+    }
+
     async fn create_dispatcher(&self, user_id: i32, area_id: i32) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO dispatchers (user_id, area_id) VALUES (?, ?)")
+        let _timer = self.pools.query_timer("auth_repository.create_dispatcher");
+        // ディスパッチャーはユーザーと同じ組織に属し、その組織のエリアだけを担当できる
+        let result = sqlx::query(
+            "INSERT INTO dispatchers (user_id, area_id, organization_id)
+            SELECT u.id, a.id, u.organization_id FROM users u JOIN areas a ON a.organization_id = u.organization_id
+            WHERE u.id = ? AND a.id = ?",
+        )
+        .bind(user_id)
+        .bind(area_id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::BadRequest);
+        }
+
+        Ok(())
+    }
+
+    async fn create_email_verification_token(
+        &self,
+        user_id: i32,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("auth_repository.create_email_verification_token");
+        sqlx::query(
+            "INSERT INTO email_verification_tokens (token_hash, user_id, expires_at) VALUES (?, ?, ?)",
+        )
+        .bind(token_hash)
+        .bind(user_id)
+        .bind(expires_at)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn verify_email(
+        &self,
+        token_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<i32>, AppError> {
+        let _timer = self.pools.query_timer("auth_repository.verify_email");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+
+        // 同じトークンで同時に確認された場合に二重に処理しないよう、行をロックして読む
+        let user_id: Option<i32> = sqlx::query_scalar(
+            "SELECT user_id FROM email_verification_tokens WHERE token_hash = ? AND expires_at > ? FOR UPDATE",
+        )
+        .bind(token_hash)
+        .bind(now)
+        .fetch_optional(&mut tx)
+        .await?;
+        let Some(user_id) = user_id else {
+            return Ok(None);
+        };
+
+        sqlx::query("DELETE FROM email_verification_tokens WHERE user_id = ?")
             .bind(user_id)
-            .bind(area_id)
-            .execute(&self.pool)
+            .execute(&mut tx)
             .await?;
+        sqlx::query("UPDATE users SET email_verified = TRUE WHERE id = ?")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
 
-        Ok(())
+        Ok(Some(user_id))
     }
 }
@@ -0,0 +1,724 @@
+use chrono::{DateTime, Utc};
+
+use crate::domains::auth_service::AuthRepository;
+use crate::errors::AppError;
+use crate::models::role::Role;
+use crate::models::user::{
+    Dispatcher, OnDutyDispatcher, Session, SessionActivity, SessionClient, StoredProfileImage,
+    TotpBackupCode, User,
+};
+use crate::repositories::auth_repository::AuthRepositoryImpl;
+use crate::repositories::memory_auth_repository::MemoryAuthRepository;
+
+// 起動時に保存先を切り替えられるよう、AuthRepository の実装を列挙型でまとめる
+#[derive(Debug, Clone)]
+pub enum AuthRepositoryBackend {
+    MySql(AuthRepositoryImpl),
+    Memory(MemoryAuthRepository),
+}
+
+impl AuthRepository for AuthRepositoryBackend {
+    async fn create_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        organization_id: i32,
+    ) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .create_user(username, password, role, organization_id)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .create_user(username, password, role, organization_id)
+                    .await
+            }
+        }
+    }
+
+    async fn create_unverified_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        email: &str,
+    ) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .create_unverified_user(username, password, role, email)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .create_unverified_user(username, password, role, email)
+                    .await
+            }
+        }
+    }
+
+    async fn find_user_by_id(&self, id: i32) -> Result<Option<User>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => repository.find_user_by_id(id).await,
+            AuthRepositoryBackend::Memory(repository) => repository.find_user_by_id(id).await,
+        }
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.find_user_by_username(username).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.find_user_by_username(username).await
+            }
+        }
+    }
+
+    async fn find_user_by_username_from_primary(
+        &self,
+        username: &str,
+    ) -> Result<Option<User>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .find_user_by_username_from_primary(username)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .find_user_by_username_from_primary(username)
+                    .await
+            }
+        }
+    }
+
+    async fn find_all_usernames(&self) -> Result<Vec<(i32, String)>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => repository.find_all_usernames().await,
+            AuthRepositoryBackend::Memory(repository) => repository.find_all_usernames().await,
+        }
+    }
+
+    async fn create_dispatcher(&self, user_id: i32, area_id: i32) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.create_dispatcher(user_id, area_id).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.create_dispatcher(user_id, area_id).await
+            }
+        }
+    }
+
+    async fn find_area_organization_id(&self, area_id: i32) -> Result<Option<i32>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.find_area_organization_id(area_id).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.find_area_organization_id(area_id).await
+            }
+        }
+    }
+
+    async fn find_dispatcher_by_id(&self, id: i32) -> Result<Option<Dispatcher>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => repository.find_dispatcher_by_id(id).await,
+            AuthRepositoryBackend::Memory(repository) => repository.find_dispatcher_by_id(id).await,
+        }
+    }
+
+    async fn find_dispatcher_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<Dispatcher>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.find_dispatcher_by_user_id(user_id).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.find_dispatcher_by_user_id(user_id).await
+            }
+        }
+    }
+
+    async fn find_dispatcher_by_user_id_from_primary(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<Dispatcher>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .find_dispatcher_by_user_id_from_primary(user_id)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .find_dispatcher_by_user_id_from_primary(user_id)
+                    .await
+            }
+        }
+    }
+
+    async fn find_on_duty_dispatchers_by_area_id(
+        &self,
+        area_id: i32,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<OnDutyDispatcher>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .find_on_duty_dispatchers_by_area_id(area_id, now)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .find_on_duty_dispatchers_by_area_id(area_id, now)
+                    .await
+            }
+        }
+    }
+
+    async fn find_profile_image_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<StoredProfileImage>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.find_profile_image_by_user_id(user_id).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.find_profile_image_by_user_id(user_id).await
+            }
+        }
+    }
+
+    async fn update_profile_image_name(
+        &self,
+        user_id: i32,
+        profile_image_name: &str,
+    ) -> Result<i32, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .update_profile_image_name(user_id, profile_image_name)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .update_profile_image_name(user_id, profile_image_name)
+                    .await
+            }
+        }
+    }
+
+    async fn replace_profile_image_name(
+        &self,
+        user_id: i32,
+        current: &str,
+        replacement: &str,
+    ) -> Result<Option<i32>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .replace_profile_image_name(user_id, current, replacement)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .replace_profile_image_name(user_id, current, replacement)
+                    .await
+            }
+        }
+    }
+
+    async fn find_recent_username_owner(
+        &self,
+        username: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<i32>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.find_recent_username_owner(username, since).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.find_recent_username_owner(username, since).await
+            }
+        }
+    }
+
+    async fn update_username(
+        &self,
+        user_id: i32,
+        old_username: &str,
+        new_username: &str,
+        changed_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .update_username(user_id, old_username, new_username, changed_at)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .update_username(user_id, old_username, new_username, changed_at)
+                    .await
+            }
+        }
+    }
+
+    async fn update_password(&self, user_id: i32, password: &str) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.update_password(user_id, password).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.update_password(user_id, password).await
+            }
+        }
+    }
+
+    async fn require_password_reset(&self, user_id: i32) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.require_password_reset(user_id).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.require_password_reset(user_id).await
+            }
+        }
+    }
+
+    async fn update_user_role(&self, user_id: i32, role: Role) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.update_user_role(user_id, role).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.update_user_role(user_id, role).await
+            }
+        }
+    }
+
+    async fn deactivate_user(&self, user_id: i32) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => repository.deactivate_user(user_id).await,
+            AuthRepositoryBackend::Memory(repository) => repository.deactivate_user(user_id).await,
+        }
+    }
+
+    async fn update_user_organization(
+        &self,
+        user_id: i32,
+        organization_id: i32,
+    ) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .update_user_organization(user_id, organization_id)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .update_user_organization(user_id, organization_id)
+                    .await
+            }
+        }
+    }
+
+    async fn update_totp_secret(
+        &self,
+        user_id: i32,
+        totp_secret: Option<&str>,
+    ) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.update_totp_secret(user_id, totp_secret).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.update_totp_secret(user_id, totp_secret).await
+            }
+        }
+    }
+
+    async fn enable_totp(&self, user_id: i32) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => repository.enable_totp(user_id).await,
+            AuthRepositoryBackend::Memory(repository) => repository.enable_totp(user_id).await,
+        }
+    }
+
+    async fn replace_totp_backup_codes(
+        &self,
+        user_id: i32,
+        code_hashes: &[String],
+    ) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .replace_totp_backup_codes(user_id, code_hashes)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .replace_totp_backup_codes(user_id, code_hashes)
+                    .await
+            }
+        }
+    }
+
+    async fn find_unused_totp_backup_codes(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<TotpBackupCode>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.find_unused_totp_backup_codes(user_id).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.find_unused_totp_backup_codes(user_id).await
+            }
+        }
+    }
+
+    async fn mark_totp_backup_code_used(&self, id: i32) -> Result<bool, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.mark_totp_backup_code_used(id).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.mark_totp_backup_code_used(id).await
+            }
+        }
+    }
+
+    async fn find_totp_locked_until(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<DateTime<Utc>>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.find_totp_locked_until(user_id).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.find_totp_locked_until(user_id).await
+            }
+        }
+    }
+
+    async fn record_totp_failure(
+        &self,
+        user_id: i32,
+        max_attempts: i32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .record_totp_failure(user_id, max_attempts, locked_until)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .record_totp_failure(user_id, max_attempts, locked_until)
+                    .await
+            }
+        }
+    }
+
+    async fn accept_totp_step(&self, user_id: i32, step: i64) -> Result<bool, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.accept_totp_step(user_id, step).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.accept_totp_step(user_id, step).await
+            }
+        }
+    }
+
+    async fn reset_totp_failures(&self, user_id: i32) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.reset_totp_failures(user_id).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.reset_totp_failures(user_id).await
+            }
+        }
+    }
+
+    async fn find_user_id_by_external_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<i32>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .find_user_id_by_external_identity(provider, subject)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .find_user_id_by_external_identity(provider, subject)
+                    .await
+            }
+        }
+    }
+
+    async fn create_external_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+        user_id: i32,
+    ) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .create_external_identity(provider, subject, user_id)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .create_external_identity(provider, subject, user_id)
+                    .await
+            }
+        }
+    }
+
+    async fn create_session(
+        &self,
+        user_id: i32,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+        client: &SessionClient,
+    ) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .create_session(user_id, session_token, expires_at, client)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .create_session(user_id, session_token, expires_at, client)
+                    .await
+            }
+        }
+    }
+
+    async fn create_impersonation_session(
+        &self,
+        user_id: i32,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+        impersonator_user_id: i32,
+        client: &SessionClient,
+    ) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .create_impersonation_session(
+                        user_id,
+                        session_token,
+                        expires_at,
+                        impersonator_user_id,
+                        client,
+                    )
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .create_impersonation_session(
+                        user_id,
+                        session_token,
+                        expires_at,
+                        impersonator_user_id,
+                        client,
+                    )
+                    .await
+            }
+        }
+    }
+
+    async fn update_session_activities(
+        &self,
+        activities: &[SessionActivity],
+    ) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.update_session_activities(activities).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.update_session_activities(activities).await
+            }
+        }
+    }
+
+    async fn update_session_expires_at(
+        &self,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .update_session_expires_at(session_token, expires_at)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .update_session_expires_at(session_token, expires_at)
+                    .await
+            }
+        }
+    }
+
+    async fn delete_session(&self, session_token: &str) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.delete_session(session_token).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.delete_session(session_token).await
+            }
+        }
+    }
+
+    async fn delete_other_sessions_by_user_id(
+        &self,
+        user_id: i32,
+        keep_session_token: &str,
+    ) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .delete_other_sessions_by_user_id(user_id, keep_session_token)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .delete_other_sessions_by_user_id(user_id, keep_session_token)
+                    .await
+            }
+        }
+    }
+
+    async fn delete_expired_sessions(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<u64, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.delete_expired_sessions(now, limit).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.delete_expired_sessions(now, limit).await
+            }
+        }
+    }
+
+    async fn find_session_by_session_token(
+        &self,
+        session_token: &str,
+    ) -> Result<Session, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .find_session_by_session_token(session_token)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .find_session_by_session_token(session_token)
+                    .await
+            }
+        }
+    }
+
+    async fn find_sessions_by_user_id(
+        &self,
+        user_id: i32,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.find_sessions_by_user_id(user_id, now).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.find_sessions_by_user_id(user_id, now).await
+            }
+        }
+    }
+
+    async fn find_impersonation_sessions(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.find_impersonation_sessions(now).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.find_impersonation_sessions(now).await
+            }
+        }
+    }
+
+    async fn find_recent_session_tokens(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<String>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.find_recent_session_tokens(now, limit).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.find_recent_session_tokens(now, limit).await
+            }
+        }
+    }
+
+    async fn create_email_verification_token(
+        &self,
+        user_id: i32,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository
+                    .create_email_verification_token(user_id, token_hash, expires_at)
+                    .await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository
+                    .create_email_verification_token(user_id, token_hash, expires_at)
+                    .await
+            }
+        }
+    }
+
+    async fn verify_email(
+        &self,
+        token_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<i32>, AppError> {
+        match self {
+            AuthRepositoryBackend::MySql(repository) => {
+                repository.verify_email(token_hash, now).await
+            }
+            AuthRepositoryBackend::Memory(repository) => {
+                repository.verify_email(token_hash, now).await
+            }
+        }
+    }
+}
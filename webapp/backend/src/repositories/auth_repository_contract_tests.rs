@@ -0,0 +1,930 @@
+// AuthRepository の実装がすべて同じ振る舞いをすることを確認する共通テスト。
+// 新しい実装を追加した場合は、末尾で auth_repository_contract_tests! を呼び出して登録する。
+// MySQL の実装は TEST_DATABASE_URL (マイグレーション適用済みの DB) が必要なため ignore にしており、--ignored を付けた場合だけ実行する
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::config::{DbConfig, FeatureFlagConfig, RetryConfig};
+use crate::domains::auth_service::AuthRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::create_pools;
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::feature_flags::FeatureFlags;
+use crate::models::organization::DEFAULT_ORGANIZATION_ID;
+use crate::models::role::Role;
+use crate::models::user::{SessionActivity, SessionClient};
+use crate::repositories::auth_repository::AuthRepositoryImpl;
+use crate::repositories::cached_auth_repository::CachedAuthRepository;
+use crate::repositories::memory_auth_repository::MemoryAuthRepository;
+
+// MySQL の DATETIME は秒単位で保存されるため、比較に使う時刻も秒単位にそろえる
+fn now() -> DateTime<Utc> {
+    Utc.timestamp_opt(Utc::now().timestamp(), 0).unwrap()
+}
+
+// 共有の DB で実行しても他のテストのデータと衝突しないよう、ランダムな接尾辞を付ける
+fn unique(prefix: &str) -> String {
+    format!("{}_{}", prefix, rand::random::<u32>())
+}
+
+pub(crate) fn test_db_config(url: String) -> DbConfig {
+    DbConfig {
+        url,
+        replica_url: None,
+        max_connections: 2,
+        min_connections: 0,
+        acquire_timeout: Duration::from_secs(5),
+        warmup_connections: 0,
+        statement_cache_capacity: 100,
+        query_cache_ttl: Duration::from_secs(60),
+        negative_cache_ttl: Duration::from_secs(60),
+        user_cache_ttl: Duration::from_secs(60),
+        slow_query_threshold: Duration::from_secs(1),
+        explain_slow_queries: false,
+        breaker_failure_threshold: 0,
+        breaker_window: Duration::from_secs(10),
+        breaker_open_duration: Duration::from_secs(5),
+        retry: RetryConfig {
+            max_attempts: 1,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(10),
+            method_max_attempts: HashMap::new(),
+        },
+        auto_migrate: false,
+        migration_baseline: None,
+        create_missing_indexes: false,
+    }
+}
+
+async fn mysql_repository() -> AuthRepositoryImpl {
+    let url = env::var("TEST_DATABASE_URL")
+        .expect("MySQL のテストには TEST_DATABASE_URL の設定が必要です");
+    AuthRepositoryImpl::new(create_pools(&test_db_config(url)).await)
+}
+
+async fn memory_repository() -> MemoryAuthRepository {
+    MemoryAuthRepository::new()
+}
+
+// キャッシュを有効にした状態でも、書き込み直後の読み取りが古い値を返さないことを確認する
+async fn cached_memory_repository() -> CachedAuthRepository<MemoryAuthRepository> {
+    CachedAuthRepository::new(
+        MemoryAuthRepository::new(),
+        &test_db_config(String::new()),
+        &EventBus::new(),
+        Arc::new(FeatureFlags::new(&FeatureFlagConfig {
+            defaults: HashMap::new(),
+            reload_interval: Duration::ZERO,
+        })),
+    )
+}
+
+async fn create_user<T: AuthRepository>(repository: &T, role: Role) -> i32 {
+    let username = unique("user");
+    repository
+        .create_user(&username, "password_hash", role, DEFAULT_ORGANIZATION_ID)
+        .await
+        .unwrap();
+    repository
+        .find_user_by_username_from_primary(&username)
+        .await
+        .unwrap()
+        .unwrap()
+        .id
+}
+
+async fn check_user_lifecycle<T: AuthRepository>(repository: &T) {
+    let username = unique("user");
+    assert!(repository
+        .find_user_by_username(&username)
+        .await
+        .unwrap()
+        .is_none());
+
+    repository
+        .create_user(
+            &username,
+            "password_hash",
+            Role::Client,
+            DEFAULT_ORGANIZATION_ID,
+        )
+        .await
+        .unwrap();
+
+    let user = repository
+        .find_user_by_username_from_primary(&username)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(user.username, username);
+    assert_eq!(user.password, "password_hash");
+    assert_eq!(user.role, Role::Client);
+    assert!(user.is_active);
+    assert!(user.email_verified);
+    assert!(!user.totp_enabled);
+    assert!(user.totp_secret.is_none());
+
+    let by_username = repository
+        .find_user_by_username(&username)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(by_username.id, user.id);
+    let by_id = repository.find_user_by_id(user.id).await.unwrap().unwrap();
+    assert_eq!(by_id.username, username);
+}
+
+async fn check_user_updates<T: AuthRepository>(repository: &T) {
+    let user_id = create_user(repository, Role::Client).await;
+
+    repository
+        .update_password(user_id, "new_password_hash")
+        .await
+        .unwrap();
+    repository
+        .update_user_role(user_id, Role::Dispatcher)
+        .await
+        .unwrap();
+    let image_version = repository
+        .update_profile_image_name(user_id, "avatar.png")
+        .await
+        .unwrap();
+
+    let user = repository.find_user_by_id(user_id).await.unwrap().unwrap();
+    assert_eq!(user.password, "new_password_hash");
+    assert_eq!(user.role, Role::Dispatcher);
+    assert!(!user.password_reset_required);
+    // 仮のパスワードは、パスワードを変更すると設定済みになる
+    repository.require_password_reset(user_id).await.unwrap();
+    let user = repository.find_user_by_id(user_id).await.unwrap().unwrap();
+    assert!(user.password_reset_required);
+    repository
+        .update_password(user_id, "new_password_hash2")
+        .await
+        .unwrap();
+    let user = repository.find_user_by_id(user_id).await.unwrap().unwrap();
+    assert!(!user.password_reset_required);
+    let profile_image = repository
+        .find_profile_image_by_user_id(user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(profile_image.name, "avatar.png");
+    assert_eq!(profile_image.version, image_version);
+    assert_eq!(
+        repository
+            .update_profile_image_name(user_id, "avatar2.png")
+            .await
+            .unwrap(),
+        image_version + 1
+    );
+    // 差し替わった後の古い画像を指定した場合は戻さない
+    assert_eq!(
+        repository
+            .replace_profile_image_name(user_id, "avatar.png", "default.png")
+            .await
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        repository
+            .replace_profile_image_name(user_id, "avatar2.png", "avatar.png")
+            .await
+            .unwrap(),
+        Some(image_version + 2)
+    );
+    let profile_image = repository
+        .find_profile_image_by_user_id(user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(profile_image.name, "avatar.png");
+
+    repository.deactivate_user(user_id).await.unwrap();
+    let user = repository.find_user_by_id(user_id).await.unwrap().unwrap();
+    assert!(!user.is_active);
+    let user = repository
+        .find_user_by_username(&user.username)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!user.is_active);
+}
+
+async fn check_user_organization<T: AuthRepository>(repository: &T) {
+    let user_id = create_user(repository, Role::Dispatcher).await;
+    let user = repository.find_user_by_id(user_id).await.unwrap().unwrap();
+    assert_eq!(user.organization_id, DEFAULT_ORGANIZATION_ID);
+
+    let organization_id = DEFAULT_ORGANIZATION_ID + 1;
+    repository
+        .update_user_organization(user_id, organization_id)
+        .await
+        .unwrap();
+    let user = repository.find_user_by_id(user_id).await.unwrap().unwrap();
+    assert_eq!(user.organization_id, organization_id);
+    let user = repository
+        .find_user_by_username(&user.username)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(user.organization_id, organization_id);
+}
+
+async fn check_username_change<T: AuthRepository>(repository: &T) {
+    let user_id = create_user(repository, Role::Client).await;
+    let old_username = repository
+        .find_user_by_id(user_id)
+        .await
+        .unwrap()
+        .unwrap()
+        .username;
+    let new_username = unique("renamed");
+    let changed_at = now();
+
+    repository
+        .update_username(user_id, &old_username, &new_username, changed_at)
+        .await
+        .unwrap();
+
+    assert!(repository
+        .find_user_by_username(&old_username)
+        .await
+        .unwrap()
+        .is_none());
+    let user = repository
+        .find_user_by_username(&new_username)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(user.id, user_id);
+    assert_eq!(
+        repository
+            .find_recent_username_owner(&old_username, changed_at)
+            .await
+            .unwrap(),
+        Some(user_id)
+    );
+    assert!(repository
+        .find_all_usernames()
+        .await
+        .unwrap()
+        .contains(&(user_id, new_username.clone())));
+    assert_eq!(
+        repository
+            .find_recent_username_owner(&old_username, changed_at + chrono::Duration::seconds(1))
+            .await
+            .unwrap(),
+        None
+    );
+}
+
+async fn check_dispatchers<T: AuthRepository>(repository: &T) {
+    let user_id = create_user(repository, Role::Dispatcher).await;
+    assert!(repository
+        .find_dispatcher_by_user_id(user_id)
+        .await
+        .unwrap()
+        .is_none());
+
+    repository.create_dispatcher(user_id, 3).await.unwrap();
+
+    let dispatcher = repository
+        .find_dispatcher_by_user_id_from_primary(user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(dispatcher.user_id, user_id);
+    assert_eq!(dispatcher.area_id, 3);
+    let by_id = repository
+        .find_dispatcher_by_id(dispatcher.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(by_id.user_id, user_id);
+    let by_user_id = repository
+        .find_dispatcher_by_user_id(user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(by_user_id.id, dispatcher.id);
+
+    // 他の組織のユーザーには、既定の組織のエリアを担当させない
+    let other_user_id = create_user(repository, Role::Dispatcher).await;
+    repository
+        .update_user_organization(other_user_id, DEFAULT_ORGANIZATION_ID + 1)
+        .await
+        .unwrap();
+    assert_eq!(
+        repository.find_area_organization_id(3).await.unwrap(),
+        Some(DEFAULT_ORGANIZATION_ID)
+    );
+    assert!(matches!(
+        repository.create_dispatcher(other_user_id, 3).await,
+        Err(AppError::BadRequest)
+    ));
+    assert!(repository
+        .find_dispatcher_by_user_id_from_primary(other_user_id)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+async fn check_email_verification<T: AuthRepository>(repository: &T) {
+    let username = unique("user");
+    repository
+        .create_unverified_user(&username, "password_hash", Role::Client, "user@example.com")
+        .await
+        .unwrap();
+    let user_id = repository
+        .find_user_by_username_from_primary(&username)
+        .await
+        .unwrap()
+        .unwrap()
+        .id;
+    assert!(
+        !repository
+            .find_user_by_id(user_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .email_verified
+    );
+
+    let now = now();
+    let expired = unique("token");
+    let token = unique("token");
+    repository
+        .create_email_verification_token(user_id, &expired, now - chrono::Duration::minutes(1))
+        .await
+        .unwrap();
+    repository
+        .create_email_verification_token(user_id, &token, now + chrono::Duration::hours(1))
+        .await
+        .unwrap();
+
+    assert_eq!(repository.verify_email(&expired, now).await.unwrap(), None);
+    assert_eq!(
+        repository
+            .verify_email(&unique("token"), now)
+            .await
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        repository.verify_email(&token, now).await.unwrap(),
+        Some(user_id)
+    );
+    assert!(
+        repository
+            .find_user_by_id(user_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .email_verified
+    );
+    // 一度使ったトークンは再利用できない
+    assert_eq!(repository.verify_email(&token, now).await.unwrap(), None);
+}
+
+async fn check_on_duty_dispatchers<T: AuthRepository>(repository: &T) {
+    let on_duty = create_user(repository, Role::Dispatcher).await;
+    let off_duty = create_user(repository, Role::Dispatcher).await;
+    repository.create_dispatcher(on_duty, 3).await.unwrap();
+    repository.create_dispatcher(off_duty, 3).await.unwrap();
+    let now = now();
+
+    repository
+        .create_session(
+            on_duty,
+            &unique("session"),
+            now + chrono::Duration::hours(1),
+            &SessionClient::default(),
+        )
+        .await
+        .unwrap();
+    repository
+        .create_session(
+            off_duty,
+            &unique("session"),
+            now - chrono::Duration::minutes(1),
+            &SessionClient::default(),
+        )
+        .await
+        .unwrap();
+
+    let dispatchers = repository
+        .find_on_duty_dispatchers_by_area_id(3, now)
+        .await
+        .unwrap();
+    assert!(dispatchers
+        .iter()
+        .any(|dispatcher| dispatcher.user_id == on_duty));
+    assert!(!dispatchers
+        .iter()
+        .any(|dispatcher| dispatcher.user_id == off_duty));
+    assert!(repository
+        .find_on_duty_dispatchers_by_area_id(4, now)
+        .await
+        .unwrap()
+        .iter()
+        .all(|dispatcher| dispatcher.user_id != on_duty));
+
+    repository.deactivate_user(on_duty).await.unwrap();
+    assert!(!repository
+        .find_on_duty_dispatchers_by_area_id(3, now)
+        .await
+        .unwrap()
+        .iter()
+        .any(|dispatcher| dispatcher.user_id == on_duty));
+}
+
+async fn check_totp<T: AuthRepository>(repository: &T) {
+    let user_id = create_user(repository, Role::Admin).await;
+
+    // シークレットが未設定の間は有効化できない
+    repository.enable_totp(user_id).await.unwrap();
+    let user = repository.find_user_by_id(user_id).await.unwrap().unwrap();
+    assert!(!user.totp_enabled);
+
+    repository
+        .update_totp_secret(user_id, Some("SECRET"))
+        .await
+        .unwrap();
+    repository.enable_totp(user_id).await.unwrap();
+    let user = repository.find_user_by_id(user_id).await.unwrap().unwrap();
+    assert_eq!(user.totp_secret.as_deref(), Some("SECRET"));
+    assert!(user.totp_enabled);
+
+    // シークレットを更新すると、再度確認されるまで無効に戻る
+    repository
+        .update_totp_secret(user_id, Some("ROTATED"))
+        .await
+        .unwrap();
+    let user = repository.find_user_by_id(user_id).await.unwrap().unwrap();
+    assert!(!user.totp_enabled);
+
+    repository
+        .replace_totp_backup_codes(user_id, &["old".to_string()])
+        .await
+        .unwrap();
+    repository
+        .replace_totp_backup_codes(user_id, &["a".to_string(), "b".to_string()])
+        .await
+        .unwrap();
+    let codes = repository
+        .find_unused_totp_backup_codes(user_id)
+        .await
+        .unwrap();
+    let mut hashes: Vec<&str> = codes.iter().map(|code| code.code_hash.as_str()).collect();
+    hashes.sort();
+    assert_eq!(hashes, vec!["a", "b"]);
+
+    assert!(repository
+        .mark_totp_backup_code_used(codes[0].id)
+        .await
+        .unwrap());
+    assert!(!repository
+        .mark_totp_backup_code_used(codes[0].id)
+        .await
+        .unwrap());
+    assert_eq!(
+        repository
+            .find_unused_totp_backup_codes(user_id)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // 受け付けた時間枠より古いもの、同じものは受け付けない
+    assert!(repository.accept_totp_step(user_id, 100).await.unwrap());
+    assert!(!repository.accept_totp_step(user_id, 100).await.unwrap());
+    assert!(!repository.accept_totp_step(user_id, 99).await.unwrap());
+    assert!(repository.accept_totp_step(user_id, 101).await.unwrap());
+
+    // 上限に達するまでは期限を設定せず、達したら回数を戻して期限を設定する
+    let locked_until = now() + chrono::Duration::minutes(15);
+    for _ in 0..2 {
+        repository
+            .record_totp_failure(user_id, 3, locked_until)
+            .await
+            .unwrap();
+    }
+    assert_eq!(
+        repository.find_totp_locked_until(user_id).await.unwrap(),
+        None
+    );
+    repository
+        .record_totp_failure(user_id, 3, locked_until)
+        .await
+        .unwrap();
+    assert_eq!(
+        repository.find_totp_locked_until(user_id).await.unwrap(),
+        Some(locked_until)
+    );
+
+    // 成功すると続けて失敗した回数が戻る
+    repository
+        .record_totp_failure(user_id, 2, now())
+        .await
+        .unwrap();
+    repository.reset_totp_failures(user_id).await.unwrap();
+    repository
+        .record_totp_failure(user_id, 2, now())
+        .await
+        .unwrap();
+    assert_eq!(
+        repository.find_totp_locked_until(user_id).await.unwrap(),
+        Some(locked_until)
+    );
+}
+
+async fn check_external_identities<T: AuthRepository>(repository: &T) {
+    let user_id = create_user(repository, Role::Client).await;
+    let subject = unique("subject");
+
+    assert!(repository
+        .find_user_id_by_external_identity("oidc", &subject)
+        .await
+        .unwrap()
+        .is_none());
+    repository
+        .create_external_identity("oidc", &subject, user_id)
+        .await
+        .unwrap();
+    assert_eq!(
+        repository
+            .find_user_id_by_external_identity("oidc", &subject)
+            .await
+            .unwrap(),
+        Some(user_id)
+    );
+    assert!(repository
+        .find_user_id_by_external_identity("other", &subject)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(repository
+        .create_external_identity("oidc", &subject, user_id)
+        .await
+        .is_err());
+}
+
+async fn check_sessions<T: AuthRepository>(repository: &T) {
+    let user_id = create_user(repository, Role::Client).await;
+    let current = unique("session");
+    let other = unique("session");
+    let expires_at = now() + chrono::Duration::hours(1);
+
+    assert!(repository
+        .find_session_by_session_token(&current)
+        .await
+        .is_err());
+
+    repository
+        .create_session(user_id, &current, expires_at, &SessionClient::default())
+        .await
+        .unwrap();
+    repository
+        .create_session(user_id, &other, expires_at, &SessionClient::default())
+        .await
+        .unwrap();
+
+    let session = repository
+        .find_session_by_session_token(&current)
+        .await
+        .unwrap();
+    assert_eq!(session.user_id, user_id);
+    assert!(session.is_valid);
+    assert_eq!(session.expires_at, expires_at);
+
+    let extended = expires_at + chrono::Duration::hours(1);
+    repository
+        .update_session_expires_at(&current, extended)
+        .await
+        .unwrap();
+    let session = repository
+        .find_session_by_session_token(&current)
+        .await
+        .unwrap();
+    assert_eq!(session.expires_at, extended);
+
+    repository
+        .delete_other_sessions_by_user_id(user_id, &current)
+        .await
+        .unwrap();
+    assert!(repository
+        .find_session_by_session_token(&current)
+        .await
+        .is_ok());
+    assert!(repository
+        .find_session_by_session_token(&other)
+        .await
+        .is_err());
+
+    repository.delete_session(&current).await.unwrap();
+    assert!(repository
+        .find_session_by_session_token(&current)
+        .await
+        .is_err());
+}
+
+async fn check_expired_sessions<T: AuthRepository>(repository: &T) {
+    let user_id = create_user(repository, Role::Client).await;
+    let expired = unique("session");
+    let active = unique("session");
+    let now = now();
+
+    repository
+        .create_session(
+            user_id,
+            &expired,
+            now - chrono::Duration::minutes(1),
+            &SessionClient::default(),
+        )
+        .await
+        .unwrap();
+    repository
+        .create_session(
+            user_id,
+            &active,
+            now + chrono::Duration::hours(1),
+            &SessionClient::default(),
+        )
+        .await
+        .unwrap();
+
+    assert!(repository.delete_expired_sessions(now, 0).await.unwrap() == 0);
+    while repository.delete_expired_sessions(now, 100).await.unwrap() > 0 {}
+
+    assert!(repository
+        .find_session_by_session_token(&expired)
+        .await
+        .is_err());
+    assert!(repository
+        .find_session_by_session_token(&active)
+        .await
+        .is_ok());
+
+    let recent = repository
+        .find_recent_session_tokens(now, 10_000)
+        .await
+        .unwrap();
+    assert!(recent.contains(&active));
+    assert!(!recent.contains(&expired));
+}
+
+async fn check_session_activities<T: AuthRepository>(repository: &T) {
+    let user_id = create_user(repository, Role::Client).await;
+    let first = unique("session");
+    let second = unique("session");
+    let expired = unique("session");
+    let now = now();
+    let expires_at = now + chrono::Duration::hours(1);
+    let client = SessionClient {
+        ip: Some("192.0.2.1".to_string()),
+        user_agent: Some("contract-test/1.0".to_string()),
+    };
+
+    repository
+        .create_session(user_id, &first, expires_at, &client)
+        .await
+        .unwrap();
+    repository
+        .create_session(user_id, &second, expires_at, &SessionClient::default())
+        .await
+        .unwrap();
+    repository
+        .create_session(
+            user_id,
+            &expired,
+            now - chrono::Duration::minutes(1),
+            &client,
+        )
+        .await
+        .unwrap();
+
+    let session = repository
+        .find_session_by_session_token(&first)
+        .await
+        .unwrap();
+    assert_eq!(session.client_ip, client.ip);
+    assert_eq!(session.user_agent, client.user_agent);
+
+    let seen_at = now + chrono::Duration::minutes(10);
+    repository
+        .update_session_activities(&[
+            SessionActivity {
+                session_token: first.clone(),
+                client: SessionClient {
+                    ip: Some("198.51.100.2".to_string()),
+                    user_agent: None,
+                },
+                last_seen_at: seen_at,
+            },
+            SessionActivity {
+                session_token: second.clone(),
+                client: client.clone(),
+                last_seen_at: seen_at,
+            },
+        ])
+        .await
+        .unwrap();
+    // 古い記録で新しい日時を巻き戻さない
+    repository
+        .update_session_activities(&[SessionActivity {
+            session_token: first.clone(),
+            client: SessionClient::default(),
+            last_seen_at: now,
+        }])
+        .await
+        .unwrap();
+
+    let session = repository
+        .find_session_by_session_token(&first)
+        .await
+        .unwrap();
+    assert_eq!(session.client_ip.as_deref(), Some("198.51.100.2"));
+    assert_eq!(session.user_agent, client.user_agent);
+    assert_eq!(session.last_seen_at, Some(seen_at));
+    let session = repository
+        .find_session_by_session_token(&second)
+        .await
+        .unwrap();
+    assert_eq!(session.client_ip, client.ip);
+
+    let sessions = repository
+        .find_sessions_by_user_id(user_id, now)
+        .await
+        .unwrap();
+    let mut tokens: Vec<&str> = sessions
+        .iter()
+        .map(|session| session.session_token.as_str())
+        .collect();
+    tokens.sort_unstable();
+    let mut expected = vec![first.as_str(), second.as_str()];
+    expected.sort_unstable();
+    assert_eq!(tokens, expected);
+}
+
+async fn check_impersonation_sessions<T: AuthRepository>(repository: &T) {
+    let admin_id = create_user(repository, Role::Admin).await;
+    let user_id = create_user(repository, Role::Dispatcher).await;
+    let normal = unique("session");
+    let impersonation = unique("session");
+    let now = now();
+    let expires_at = now + chrono::Duration::minutes(30);
+
+    repository
+        .create_session(user_id, &normal, expires_at, &SessionClient::default())
+        .await
+        .unwrap();
+    repository
+        .create_impersonation_session(
+            user_id,
+            &impersonation,
+            expires_at,
+            admin_id,
+            &SessionClient::default(),
+        )
+        .await
+        .unwrap();
+
+    let session = repository
+        .find_session_by_session_token(&normal)
+        .await
+        .unwrap();
+    assert_eq!(session.impersonator_user_id, None);
+    let session = repository
+        .find_session_by_session_token(&impersonation)
+        .await
+        .unwrap();
+    assert_eq!(session.user_id, user_id);
+    assert_eq!(session.impersonator_user_id, Some(admin_id));
+
+    let sessions = repository.find_impersonation_sessions(now).await.unwrap();
+    assert!(sessions
+        .iter()
+        .any(|session| session.session_token == impersonation));
+    assert!(!sessions
+        .iter()
+        .any(|session| session.session_token == normal));
+
+    repository.delete_session(&impersonation).await.unwrap();
+    let sessions = repository.find_impersonation_sessions(now).await.unwrap();
+    assert!(!sessions
+        .iter()
+        .any(|session| session.session_token == impersonation));
+}
+
+macro_rules! auth_repository_contract_tests {
+    ($name:ident, $factory:expr $(, #[$attribute:meta])*) => {
+        mod $name {
+            use super::*;
+
+            #[actix_rt::test]
+            $(#[$attribute])*
+            async fn user_lifecycle() {
+                let repository = $factory.await;
+                check_user_lifecycle(&repository).await;
+            }
+
+            #[actix_rt::test]
+            $(#[$attribute])*
+            async fn user_updates() {
+                let repository = $factory.await;
+                check_user_updates(&repository).await;
+            }
+
+            #[actix_rt::test]
+            $(#[$attribute])*
+            async fn user_organization() {
+                let repository = $factory.await;
+                check_user_organization(&repository).await;
+            }
+
+            #[actix_rt::test]
+            $(#[$attribute])*
+            async fn username_change() {
+                let repository = $factory.await;
+                check_username_change(&repository).await;
+            }
+
+            #[actix_rt::test]
+            $(#[$attribute])*
+            async fn dispatchers() {
+                let repository = $factory.await;
+                check_dispatchers(&repository).await;
+            }
+
+            #[actix_rt::test]
+            $(#[$attribute])*
+            async fn email_verification() {
+                let repository = $factory.await;
+                check_email_verification(&repository).await;
+            }
+
+            #[actix_rt::test]
+            $(#[$attribute])*
+            async fn on_duty_dispatchers() {
+                let repository = $factory.await;
+                check_on_duty_dispatchers(&repository).await;
+            }
+
+            #[actix_rt::test]
+            $(#[$attribute])*
+            async fn totp() {
+                let repository = $factory.await;
+                check_totp(&repository).await;
+            }
+
+            #[actix_rt::test]
+            $(#[$attribute])*
+            async fn external_identities() {
+                let repository = $factory.await;
+                check_external_identities(&repository).await;
+            }
+
+            #[actix_rt::test]
+            $(#[$attribute])*
+            async fn sessions() {
+                let repository = $factory.await;
+                check_sessions(&repository).await;
+            }
+
+            #[actix_rt::test]
+            $(#[$attribute])*
+            async fn expired_sessions() {
+                let repository = $factory.await;
+                check_expired_sessions(&repository).await;
+            }
+
+            #[actix_rt::test]
+            $(#[$attribute])*
+            async fn session_activities() {
+                let repository = $factory.await;
+                check_session_activities(&repository).await;
+            }
+
+            #[actix_rt::test]
+            $(#[$attribute])*
+            async fn impersonation_sessions() {
+                let repository = $factory.await;
+                check_impersonation_sessions(&repository).await;
+            }
+        }
+    };
+}
+
+auth_repository_contract_tests!(
+    mysql,
+    mysql_repository(),
+    #[ignore = "TEST_DATABASE_URL の MySQL が必要"]
+);
+auth_repository_contract_tests!(memory, memory_repository());
+auth_repository_contract_tests!(cached_memory, cached_memory_repository());
@@ -0,0 +1,168 @@
+use futures_util::TryStreamExt;
+use sqlx::Row;
+
+use crate::domains::backup_service::{BackupRecord, BackupRepository, BackupSender, BackupTable};
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::repositories::bulk_insert;
+
+// 書き出した直後の状態を保存するため、レプリカではなくプライマリから読む
+#[derive(Debug, Clone)]
+pub struct BackupRepositoryImpl {
+    pools: DbPools,
+}
+
+impl BackupRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        BackupRepositoryImpl { pools }
+    }
+}
+
+impl BackupRepository for BackupRepositoryImpl {
+    async fn stream_tables(
+        &self,
+        tables: &[&'static str],
+        sender: &BackupSender,
+    ) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("backup_repository.stream_tables");
+        let mut conn = self.pools.acquire_primary().await?;
+
+        // 生成列は値を書き込めないため、列名は読み込むトランザクションの外で先に調べておく
+        let mut columns = Vec::with_capacity(tables.len());
+        for table in tables {
+            let table_columns = sqlx::query_scalar::<_, String>(
+                "SELECT
+                    COLUMN_NAME AS column_name
+                FROM
+                    information_schema.COLUMNS
+                WHERE
+                    TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND EXTRA NOT LIKE '%GENERATED%'
+                ORDER BY
+                    ORDINAL_POSITION",
+            )
+            .bind(table)
+            .fetch_all(&mut conn)
+            .await?;
+            if table_columns.is_empty()
+                || !table_columns
+                    .iter()
+                    .all(|column| bulk_insert::is_identifier(column))
+            {
+                return Err(AppError::InternalServerError);
+            }
+            columns.push(table_columns);
+        }
+
+        // REPEATABLE READ では最初に読んだ時点のスナップショットをトランザクションの終わりまで使うため、
+        // 読み込み中に書き込まれても、すべてのテーブルが同じ時点の内容になる
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+            .execute(&mut conn)
+            .await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        for (table, columns) in tables.iter().zip(columns) {
+            // 型によらず復元時と同じ文字列で受け渡す
+            let query = format!(
+                "SELECT {} FROM {}",
+                columns
+                    .iter()
+                    .map(|column| format!("CAST({} AS CHAR)", column))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                table
+            );
+            let column_count = columns.len();
+            let record = BackupRecord::Table {
+                name: table,
+                columns,
+            };
+            if sender.send(Ok(record)).await.is_err() {
+                return Ok(());
+            }
+
+            let mut rows = sqlx::query(&query).fetch(&mut tx);
+            while let Some(row) = rows.try_next().await? {
+                let values = (0..column_count)
+                    .map(|index| row.try_get::<Option<String>, _>(index))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if sender.send(Ok(BackupRecord::Row(values))).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn replace_tables(&self, tables: &[BackupTable]) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("backup_repository.replace_tables");
+        if !tables.iter().all(|table| {
+            bulk_insert::is_identifier(table.name)
+                && table
+                    .columns
+                    .iter()
+                    .all(|column| bulk_insert::is_identifier(column))
+        }) {
+            return Err(AppError::BadRequest);
+        }
+
+        // FOREIGN_KEY_CHECKS はセッション単位の設定のため、同じ接続で入れ替える。
+        // 失敗した場合に元に戻せるよう、TRUNCATE ではなくトランザクションの中で DELETE する
+        let mut conn = self.pools.acquire_primary().await?;
+        sqlx::query("SET FOREIGN_KEY_CHECKS = 0")
+            .execute(&mut conn)
+            .await?;
+        let result = async {
+            let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+            for table in tables.iter().rev() {
+                sqlx::query(&format!("DELETE FROM {}", table.name))
+                    .execute(&mut tx)
+                    .await?;
+            }
+            for table in tables {
+                let columns = table.columns.len();
+                for chunk in table.rows.chunks(bulk_insert::rows_per_statement(columns)) {
+                    let query = format!(
+                        "INSERT INTO {} ({}) VALUES {}",
+                        table.name,
+                        table.columns.join(", "),
+                        bulk_insert::values_placeholders(columns, chunk.len())
+                    );
+                    let mut insert = sqlx::query(&query);
+                    for value in chunk.iter().flatten() {
+                        insert = insert.bind(value.as_deref());
+                    }
+                    insert.execute(&mut tx).await?;
+                }
+            }
+
+            // 注文数は復元した orders から数え直す。保存した時点より後の注文に対する冪等キーは、
+            // 存在しない注文のレスポンスを返してしまうため捨てる。
+            // 差分同期の位置は DataReset で記録する読み込み直しの指示で無効になる
+            sqlx::query("UPDATE order_counters SET count = 0, reconciled_at = NOW()")
+                .execute(&mut tx)
+                .await?;
+            sqlx::query(
+                "INSERT INTO order_counters (organization_id, status, count, reconciled_at)
+                SELECT organization_id, status, COUNT(*), NOW()
+                FROM orders
+                GROUP BY organization_id, status
+                ON DUPLICATE KEY UPDATE count = VALUES(count), reconciled_at = VALUES(reconciled_at)",
+            )
+            .execute(&mut tx)
+            .await?;
+            sqlx::query("DELETE FROM idempotency_keys")
+                .execute(&mut tx)
+                .await?;
+            tx.commit().await?;
+
+            Ok::<(), AppError>(())
+        }
+        .await;
+        sqlx::query("SET FOREIGN_KEY_CHECKS = 1")
+            .execute(&mut conn)
+            .await?;
+
+        result
+    }
+}
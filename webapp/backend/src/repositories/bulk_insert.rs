@@ -0,0 +1,23 @@
+// MySQL のプリペアドステートメントに渡せるプレースホルダの上限
+const MAX_PLACEHOLDERS: usize = 65535;
+// 1 文に含める行数の上限。列が少なくても文が max_allowed_packet を超えないよう抑える
+const MAX_ROWS_PER_STATEMENT: usize = 1000;
+
+// columns 列の行を 1 文の複数行 INSERT で挿入できる行数
+pub fn rows_per_statement(columns: usize) -> usize {
+    (MAX_PLACEHOLDERS / columns.max(1)).clamp(1, MAX_ROWS_PER_STATEMENT)
+}
+
+// columns 列の行を rows 行挿入する VALUES 句のプレースホルダ。例: "(?, ?), (?, ?)"
+pub fn values_placeholders(columns: usize, rows: usize) -> String {
+    let row = format!("({})", vec!["?"; columns].join(", "));
+    vec![row; rows].join(", ")
+}
+
+// テーブル名とカラム名はプレースホルダにできないため、識別子として安全な文字だけを許可する
+pub fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
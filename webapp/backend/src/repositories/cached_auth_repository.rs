@@ -0,0 +1,658 @@
+use crate::config::DbConfig;
+use crate::domains::auth_service::AuthRepository;
+use crate::domains::events::DomainEvent;
+use crate::errors::AppError;
+use crate::infrastructure::event_bus::EventBus;
+use crate::infrastructure::feature_flags::{FeatureFlag, FeatureFlags};
+use crate::infrastructure::ttl_cache::TtlCache;
+use crate::models::role::Role;
+use crate::models::user::{
+    Dispatcher, OnDutyDispatcher, Session, SessionActivity, SessionClient, StoredProfileImage,
+    TotpBackupCode, User,
+};
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const QUERY_CACHE_CAPACITY: usize = 100_000;
+
+// ユーザーとディスパッチャーの行を id・ユーザー名の両方から引けるように保持する。
+// ロール変更・停止・パスワード変更はドメインイベント経由で無効化される
+#[derive(Debug)]
+struct UserCache {
+    users_by_id: TtlCache<i32, User>,
+    users_by_username: TtlCache<String, User>,
+    dispatchers_by_id: TtlCache<i32, Dispatcher>,
+    dispatchers_by_user_id: TtlCache<i32, Dispatcher>,
+}
+
+impl UserCache {
+    fn new(ttl: Duration) -> Self {
+        UserCache {
+            users_by_id: TtlCache::new(ttl, QUERY_CACHE_CAPACITY),
+            users_by_username: TtlCache::new(ttl, QUERY_CACHE_CAPACITY),
+            dispatchers_by_id: TtlCache::new(ttl, QUERY_CACHE_CAPACITY),
+            dispatchers_by_user_id: TtlCache::new(ttl, QUERY_CACHE_CAPACITY),
+        }
+    }
+
+    fn insert_user(&self, user: &User) {
+        self.users_by_id.insert(user.id, user.clone());
+        self.users_by_username
+            .insert(user.username.clone(), user.clone());
+    }
+
+    fn insert_dispatcher(&self, dispatcher: &Dispatcher) {
+        self.dispatchers_by_id
+            .insert(dispatcher.id, dispatcher.clone());
+        self.dispatchers_by_user_id
+            .insert(dispatcher.user_id, dispatcher.clone());
+    }
+
+    fn evict_user(&self, user_id: i32) {
+        self.users_by_id.remove(&user_id);
+        self.users_by_username.retain(|_, user| user.id != user_id);
+        self.dispatchers_by_user_id.remove(&user_id);
+        self.dispatchers_by_id
+            .retain(|_, dispatcher| dispatcher.user_id != user_id);
+    }
+
+    fn set_ttl(&self, ttl: Duration) {
+        self.users_by_id.set_ttl(ttl);
+        self.users_by_username.set_ttl(ttl);
+        self.dispatchers_by_id.set_ttl(ttl);
+        self.dispatchers_by_user_id.set_ttl(ttl);
+    }
+
+    fn clear(&self) {
+        self.users_by_id.clear();
+        self.users_by_username.clear();
+        self.dispatchers_by_id.clear();
+        self.dispatchers_by_user_id.clear();
+    }
+}
+
+#[derive(Debug)]
+pub struct CachedAuthRepository<T: AuthRepository + std::fmt::Debug> {
+    inner: T,
+    users: Arc<UserCache>,
+    missing_usernames: Arc<TtlCache<String, ()>>,
+    // 問い合わせ中に作成されたユーザー名を「存在しない」とキャッシュしないための世代番号
+    username_generation: AtomicU64,
+    sessions_by_token: Arc<TtlCache<String, Session>>,
+    feature_flags: Arc<FeatureFlags>,
+}
+
+impl<T: AuthRepository + std::fmt::Debug> CachedAuthRepository<T> {
+    pub fn new(
+        inner: T,
+        config: &DbConfig,
+        event_bus: &EventBus,
+        feature_flags: Arc<FeatureFlags>,
+    ) -> Self {
+        let users = Arc::new(UserCache::new(config.user_cache_ttl));
+        let missing_usernames = Arc::new(TtlCache::new(
+            config.negative_cache_ttl,
+            QUERY_CACHE_CAPACITY,
+        ));
+        let sessions_by_token =
+            Arc::new(TtlCache::new(config.query_cache_ttl, QUERY_CACHE_CAPACITY));
+
+        let (users_subscriber, missing_subscriber, sessions_subscriber) = (
+            users.clone(),
+            missing_usernames.clone(),
+            sessions_by_token.clone(),
+        );
+        event_bus.subscribe(move |event| match (event, event.user_id()) {
+            (DomainEvent::DataReset, _) => {
+                users_subscriber.clear();
+                missing_subscriber.clear();
+                sessions_subscriber.clear();
+            }
+            (DomainEvent::ConfigChanged { tunables }, _) => {
+                users_subscriber.set_ttl(tunables.user_cache_ttl);
+                missing_subscriber.set_ttl(tunables.negative_cache_ttl);
+                sessions_subscriber.set_ttl(tunables.query_cache_ttl);
+            }
+            (_, Some(user_id)) => users_subscriber.evict_user(user_id),
+            _ => {}
+        });
+
+        CachedAuthRepository {
+            inner,
+            users,
+            missing_usernames,
+            username_generation: AtomicU64::new(0),
+            sessions_by_token,
+            feature_flags,
+        }
+    }
+
+    // フラグで無効にしている間はユーザーとディスパッチャーのキャッシュを読み書きしない。
+    // 無効化はイベントで続けて行うため、有効に戻しても古い行は返らない
+    fn user_cache_enabled(&self) -> bool {
+        self.feature_flags.is_enabled(FeatureFlag::AuthUserCache)
+    }
+
+    fn cache_missing_username(&self, username: &str, generation: u64) {
+        if self.username_generation.load(Ordering::SeqCst) == generation {
+            self.missing_usernames.insert(username.to_string(), ());
+        }
+    }
+
+    fn invalidate_missing_username(&self, username: &str) {
+        self.username_generation.fetch_add(1, Ordering::SeqCst);
+        self.missing_usernames.remove(username);
+    }
+}
+
+impl<T: AuthRepository + std::fmt::Debug> AuthRepository for CachedAuthRepository<T> {
+    async fn create_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        organization_id: i32,
+    ) -> Result<(), AppError> {
+        self.inner
+            .create_user(username, password, role, organization_id)
+            .await?;
+        self.users.users_by_username.remove(username);
+        self.invalidate_missing_username(username);
+
+        Ok(())
+    }
+
+    async fn create_unverified_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        email: &str,
+    ) -> Result<(), AppError> {
+        self.inner
+            .create_unverified_user(username, password, role, email)
+            .await?;
+        self.users.users_by_username.remove(username);
+        self.invalidate_missing_username(username);
+
+        Ok(())
+    }
+
+    async fn find_user_by_id(&self, id: i32) -> Result<Option<User>, AppError> {
+        if !self.user_cache_enabled() {
+            return self.inner.find_user_by_id(id).await;
+        }
+        if let Some(user) = self.users.users_by_id.get(&id) {
+            return Ok(Some(user));
+        }
+
+        let user = self.inner.find_user_by_id(id).await?;
+        if let Some(user) = &user {
+            self.users.insert_user(user);
+        }
+
+        Ok(user)
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, AppError> {
+        if !self.user_cache_enabled() {
+            return self.inner.find_user_by_username(username).await;
+        }
+        if let Some(user) = self.users.users_by_username.get(username) {
+            return Ok(Some(user));
+        }
+        if self.missing_usernames.get(username).is_some() {
+            return Ok(None);
+        }
+
+        let generation = self.username_generation.load(Ordering::SeqCst);
+        let user = self.inner.find_user_by_username(username).await?;
+        match &user {
+            Some(user) => self.users.insert_user(user),
+            None => self.cache_missing_username(username, generation),
+        }
+
+        Ok(user)
+    }
+
+    // 存在しないユーザー名の問い合わせだけをキャッシュする。create_user で必ず無効化されるため、
+    // 書き込み前の確認に使っても古い結果は返らない
+    async fn find_user_by_username_from_primary(
+        &self,
+        username: &str,
+    ) -> Result<Option<User>, AppError> {
+        if self.missing_usernames.get(username).is_some() {
+            return Ok(None);
+        }
+
+        let generation = self.username_generation.load(Ordering::SeqCst);
+        let user = self
+            .inner
+            .find_user_by_username_from_primary(username)
+            .await?;
+        if user.is_none() {
+            self.cache_missing_username(username, generation);
+        }
+
+        Ok(user)
+    }
+
+    async fn find_all_usernames(&self) -> Result<Vec<(i32, String)>, AppError> {
+        self.inner.find_all_usernames().await
+    }
+
+    async fn create_dispatcher(&self, user_id: i32, area_id: i32) -> Result<(), AppError> {
+        self.inner.create_dispatcher(user_id, area_id).await?;
+        self.users.dispatchers_by_user_id.remove(&user_id);
+
+        Ok(())
+    }
+
+    async fn find_area_organization_id(&self, area_id: i32) -> Result<Option<i32>, AppError> {
+        self.inner.find_area_organization_id(area_id).await
+    }
+
+    async fn find_dispatcher_by_id(&self, id: i32) -> Result<Option<Dispatcher>, AppError> {
+        if !self.user_cache_enabled() {
+            return self.inner.find_dispatcher_by_id(id).await;
+        }
+        if let Some(dispatcher) = self.users.dispatchers_by_id.get(&id) {
+            return Ok(Some(dispatcher));
+        }
+
+        let dispatcher = self.inner.find_dispatcher_by_id(id).await?;
+        if let Some(dispatcher) = &dispatcher {
+            self.users.insert_dispatcher(dispatcher);
+        }
+
+        Ok(dispatcher)
+    }
+
+    async fn find_dispatcher_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<Dispatcher>, AppError> {
+        if !self.user_cache_enabled() {
+            return self.inner.find_dispatcher_by_user_id(user_id).await;
+        }
+        if let Some(dispatcher) = self.users.dispatchers_by_user_id.get(&user_id) {
+            return Ok(Some(dispatcher));
+        }
+
+        let dispatcher = self.inner.find_dispatcher_by_user_id(user_id).await?;
+        if let Some(dispatcher) = &dispatcher {
+            self.users.insert_dispatcher(dispatcher);
+        }
+
+        Ok(dispatcher)
+    }
+
+    async fn find_dispatcher_by_user_id_from_primary(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<Dispatcher>, AppError> {
+        self.inner
+            .find_dispatcher_by_user_id_from_primary(user_id)
+            .await
+    }
+
+    async fn find_on_duty_dispatchers_by_area_id(
+        &self,
+        area_id: i32,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<OnDutyDispatcher>, AppError> {
+        self.inner
+            .find_on_duty_dispatchers_by_area_id(area_id, now)
+            .await
+    }
+
+    async fn find_profile_image_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<StoredProfileImage>, AppError> {
+        self.inner.find_profile_image_by_user_id(user_id).await
+    }
+
+    async fn update_profile_image_name(
+        &self,
+        user_id: i32,
+        profile_image_name: &str,
+    ) -> Result<i32, AppError> {
+        let image_version = self
+            .inner
+            .update_profile_image_name(user_id, profile_image_name)
+            .await?;
+        self.users.evict_user(user_id);
+
+        Ok(image_version)
+    }
+
+    async fn replace_profile_image_name(
+        &self,
+        user_id: i32,
+        current: &str,
+        replacement: &str,
+    ) -> Result<Option<i32>, AppError> {
+        let image_version = self
+            .inner
+            .replace_profile_image_name(user_id, current, replacement)
+            .await?;
+        if image_version.is_some() {
+            self.users.evict_user(user_id);
+        }
+
+        Ok(image_version)
+    }
+
+    async fn find_recent_username_owner(
+        &self,
+        username: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<i32>, AppError> {
+        self.inner.find_recent_username_owner(username, since).await
+    }
+
+    async fn update_username(
+        &self,
+        user_id: i32,
+        old_username: &str,
+        new_username: &str,
+        changed_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        self.inner
+            .update_username(user_id, old_username, new_username, changed_at)
+            .await?;
+        // 旧ユーザー名でのログインがキャッシュ経由で通らないよう、該当ユーザーのエントリを落とす
+        self.users.evict_user(user_id);
+        self.users.users_by_username.remove(new_username);
+        self.invalidate_missing_username(new_username);
+
+        Ok(())
+    }
+
+    // このリポジトリを経由しない変更は、AuthService が発行するイベントで無効化される
+    async fn update_password(&self, user_id: i32, password: &str) -> Result<(), AppError> {
+        self.inner.update_password(user_id, password).await?;
+        self.users.evict_user(user_id);
+
+        Ok(())
+    }
+
+    async fn require_password_reset(&self, user_id: i32) -> Result<(), AppError> {
+        self.inner.require_password_reset(user_id).await?;
+        self.users.evict_user(user_id);
+
+        Ok(())
+    }
+
+    async fn update_user_role(&self, user_id: i32, role: Role) -> Result<(), AppError> {
+        self.inner.update_user_role(user_id, role).await?;
+        self.users.evict_user(user_id);
+
+        Ok(())
+    }
+
+    async fn deactivate_user(&self, user_id: i32) -> Result<(), AppError> {
+        self.inner.deactivate_user(user_id).await?;
+        self.users.evict_user(user_id);
+
+        Ok(())
+    }
+
+    async fn update_user_organization(
+        &self,
+        user_id: i32,
+        organization_id: i32,
+    ) -> Result<(), AppError> {
+        self.inner
+            .update_user_organization(user_id, organization_id)
+            .await?;
+        self.users.evict_user(user_id);
+
+        Ok(())
+    }
+
+    async fn update_totp_secret(
+        &self,
+        user_id: i32,
+        totp_secret: Option<&str>,
+    ) -> Result<(), AppError> {
+        self.inner.update_totp_secret(user_id, totp_secret).await?;
+        self.users.evict_user(user_id);
+
+        Ok(())
+    }
+
+    async fn enable_totp(&self, user_id: i32) -> Result<(), AppError> {
+        self.inner.enable_totp(user_id).await?;
+        self.users.evict_user(user_id);
+
+        Ok(())
+    }
+
+    async fn replace_totp_backup_codes(
+        &self,
+        user_id: i32,
+        code_hashes: &[String],
+    ) -> Result<(), AppError> {
+        self.inner
+            .replace_totp_backup_codes(user_id, code_hashes)
+            .await
+    }
+
+    async fn find_unused_totp_backup_codes(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<TotpBackupCode>, AppError> {
+        self.inner.find_unused_totp_backup_codes(user_id).await
+    }
+
+    async fn mark_totp_backup_code_used(&self, id: i32) -> Result<bool, AppError> {
+        self.inner.mark_totp_backup_code_used(id).await
+    }
+
+    async fn find_totp_locked_until(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<DateTime<Utc>>, AppError> {
+        self.inner.find_totp_locked_until(user_id).await
+    }
+
+    async fn record_totp_failure(
+        &self,
+        user_id: i32,
+        max_attempts: i32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        self.inner
+            .record_totp_failure(user_id, max_attempts, locked_until)
+            .await
+    }
+
+    async fn accept_totp_step(&self, user_id: i32, step: i64) -> Result<bool, AppError> {
+        self.inner.accept_totp_step(user_id, step).await
+    }
+
+    async fn reset_totp_failures(&self, user_id: i32) -> Result<(), AppError> {
+        self.inner.reset_totp_failures(user_id).await
+    }
+
+    async fn find_user_id_by_external_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<i32>, AppError> {
+        self.inner
+            .find_user_id_by_external_identity(provider, subject)
+            .await
+    }
+
+    async fn create_external_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+        user_id: i32,
+    ) -> Result<(), AppError> {
+        self.inner
+            .create_external_identity(provider, subject, user_id)
+            .await
+    }
+
+    async fn create_session(
+        &self,
+        user_id: i32,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+        client: &SessionClient,
+    ) -> Result<(), AppError> {
+        self.inner
+            .create_session(user_id, session_token, expires_at, client)
+            .await
+    }
+
+    async fn create_impersonation_session(
+        &self,
+        user_id: i32,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+        impersonator_user_id: i32,
+        client: &SessionClient,
+    ) -> Result<(), AppError> {
+        self.inner
+            .create_impersonation_session(
+                user_id,
+                session_token,
+                expires_at,
+                impersonator_user_id,
+                client,
+            )
+            .await
+    }
+
+    async fn update_session_activities(
+        &self,
+        activities: &[SessionActivity],
+    ) -> Result<(), AppError> {
+        self.inner.update_session_activities(activities).await?;
+        for activity in activities {
+            self.sessions_by_token.remove(&activity.session_token);
+        }
+
+        Ok(())
+    }
+
+    async fn update_session_expires_at(
+        &self,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        self.inner
+            .update_session_expires_at(session_token, expires_at)
+            .await?;
+        self.sessions_by_token.remove(session_token);
+
+        Ok(())
+    }
+
+    async fn delete_session(&self, session_token: &str) -> Result<(), AppError> {
+        self.inner.delete_session(session_token).await?;
+        self.sessions_by_token.remove(session_token);
+
+        Ok(())
+    }
+
+    async fn delete_other_sessions_by_user_id(
+        &self,
+        user_id: i32,
+        keep_session_token: &str,
+    ) -> Result<(), AppError> {
+        self.inner
+            .delete_other_sessions_by_user_id(user_id, keep_session_token)
+            .await?;
+        self.sessions_by_token.retain(|session_token, session| {
+            session.user_id != user_id || session_token == keep_session_token
+        });
+
+        Ok(())
+    }
+
+    async fn delete_expired_sessions(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<u64, AppError> {
+        let deleted = self.inner.delete_expired_sessions(now, limit).await?;
+        // 削除されたトークンは特定できないため、期限切れのものに加えて無効なものも落としておく
+        self.sessions_by_token
+            .retain(|_, session| session.is_valid && session.expires_at > now);
+
+        Ok(deleted)
+    }
+
+    async fn find_session_by_session_token(
+        &self,
+        session_token: &str,
+    ) -> Result<Session, AppError> {
+        if let Some(session) = self.sessions_by_token.get(session_token) {
+            return Ok(session);
+        }
+
+        let session = self
+            .inner
+            .find_session_by_session_token(session_token)
+            .await?;
+        self.sessions_by_token
+            .insert(session_token.to_string(), session.clone());
+
+        Ok(session)
+    }
+
+    async fn find_sessions_by_user_id(
+        &self,
+        user_id: i32,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, AppError> {
+        self.inner.find_sessions_by_user_id(user_id, now).await
+    }
+
+    async fn find_impersonation_sessions(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, AppError> {
+        self.inner.find_impersonation_sessions(now).await
+    }
+
+    async fn find_recent_session_tokens(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<String>, AppError> {
+        self.inner.find_recent_session_tokens(now, limit).await
+    }
+
+    async fn create_email_verification_token(
+        &self,
+        user_id: i32,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        self.inner
+            .create_email_verification_token(user_id, token_hash, expires_at)
+            .await
+    }
+
+    async fn verify_email(
+        &self,
+        token_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<i32>, AppError> {
+        let user_id = self.inner.verify_email(token_hash, now).await?;
+        if let Some(user_id) = user_id {
+            self.users.evict_user(user_id);
+        }
+
+        Ok(user_id)
+    }
+}
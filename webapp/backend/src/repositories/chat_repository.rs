@@ -0,0 +1,210 @@
+use crate::domains::chat_service::ChatRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::order::OrderMessage;
+
+#[derive(Debug)]
+pub struct ChatRepositoryImpl {
+    pools: DbPools,
+}
+
+impl ChatRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        ChatRepositoryImpl { pools }
+    }
+}
+
+impl ChatRepository for ChatRepositoryImpl {
+    async fn find_chat_participant_ids(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<Option<Vec<i32>>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("chat_repository.find_chat_participant_ids");
+        let participants = sqlx::query_as::<_, (Option<i32>, Option<i32>)>(
+            "SELECT
+                d.user_id,
+                t.driver_id
+            FROM
+                orders o
+            LEFT JOIN
+                dispatchers d
+            ON
+                d.id = o.dispatcher_id
+            LEFT JOIN
+                tow_trucks t
+            ON
+                t.id = o.tow_truck_id
+            WHERE
+                o.id = ? AND o.organization_id = ?",
+        )
+        .bind(order_id)
+        .bind(organization_id)
+        .fetch_optional(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(participants.map(|(dispatcher_user_id, driver_id)| {
+            [dispatcher_user_id, driver_id]
+                .into_iter()
+                .flatten()
+                .collect()
+        }))
+    }
+
+    async fn create_message(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        sender_id: i32,
+        body: &str,
+    ) -> Result<OrderMessage, AppError> {
+        let _timer = self.pools.query_timer("chat_repository.create_message");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        let result = sqlx::query(
+            "INSERT INTO order_messages (order_id, organization_id, sender_id, body) VALUES (?, ?, ?, ?)",
+        )
+        .bind(order_id)
+        .bind(organization_id)
+        .bind(sender_id)
+        .bind(body)
+        .execute(&mut tx)
+        .await?;
+        let message = sqlx::query_as::<_, OrderMessage>(
+            "SELECT id, order_id, sender_id, body, created_at FROM order_messages WHERE id = ?",
+        )
+        .bind(result.last_insert_id() as i32)
+        .fetch_one(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(message)
+    }
+
+    async fn find_messages(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        before_id: Option<i32>,
+        limit: i32,
+    ) -> Result<Vec<OrderMessage>, AppError> {
+        let _timer = self.pools.query_timer("chat_repository.find_messages");
+        let messages = sqlx::query_as::<_, OrderMessage>(
+            "SELECT
+                id,
+                order_id,
+                sender_id,
+                body,
+                created_at
+            FROM
+                order_messages
+            WHERE
+                order_id = ? AND organization_id = ? AND (? IS NULL OR id < ?)
+            ORDER BY
+                id DESC
+            LIMIT ?",
+        )
+        .bind(order_id)
+        .bind(organization_id)
+        .bind(before_id)
+        .bind(before_id)
+        .bind(limit)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(messages)
+    }
+
+    async fn find_latest_message_id(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<Option<i32>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("chat_repository.find_latest_message_id");
+        // 受信した直後に既読にするため、レプリカの遅延で取りこぼさないようプライマリから読む
+        let (message_id,) = sqlx::query_as::<_, (Option<i32>,)>(
+            "SELECT MAX(id) FROM order_messages WHERE order_id = ? AND organization_id = ?",
+        )
+        .bind(order_id)
+        .bind(organization_id)
+        .fetch_one(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(message_id)
+    }
+
+    async fn mark_read(
+        &self,
+        order_id: i32,
+        user_id: i32,
+        message_id: i32,
+    ) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("chat_repository.mark_read");
+        sqlx::query(
+            "INSERT INTO order_message_reads (order_id, user_id, last_read_message_id) VALUES (?, ?, ?)
+            ON DUPLICATE KEY UPDATE last_read_message_id = GREATEST(last_read_message_id, VALUES(last_read_message_id))",
+        )
+        .bind(order_id)
+        .bind(user_id)
+        .bind(message_id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn count_unread_messages(
+        &self,
+        organization_id: i32,
+        user_id: i32,
+    ) -> Result<Vec<(i32, i64)>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("chat_repository.count_unread_messages");
+        let counts = sqlx::query_as::<_, (i32, i64)>(
+            "SELECT
+                m.order_id,
+                COUNT(m.id)
+            FROM
+                order_messages m
+            JOIN
+                orders o
+            ON
+                o.id = m.order_id
+            LEFT JOIN
+                dispatchers d
+            ON
+                d.id = o.dispatcher_id
+            LEFT JOIN
+                tow_trucks t
+            ON
+                t.id = o.tow_truck_id
+            LEFT JOIN
+                order_message_reads r
+            ON
+                r.order_id = m.order_id AND r.user_id = ?
+            WHERE
+                m.organization_id = ?
+                AND (d.user_id = ? OR t.driver_id = ?)
+                AND m.sender_id <> ?
+                AND m.id > COALESCE(r.last_read_message_id, 0)
+            GROUP BY
+                m.order_id
+            ORDER BY
+                m.order_id",
+        )
+        .bind(user_id)
+        .bind(organization_id)
+        .bind(user_id)
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(counts)
+    }
+}
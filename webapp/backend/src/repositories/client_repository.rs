@@ -0,0 +1,131 @@
+use crate::domains::client_service::ClientRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::client::{Client, NewClient};
+use crate::models::order::{Order, OrderStatus};
+
+#[derive(Debug)]
+pub struct ClientRepositoryImpl {
+    pools: DbPools,
+}
+
+impl ClientRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        ClientRepositoryImpl { pools }
+    }
+}
+
+impl ClientRepository for ClientRepositoryImpl {
+    async fn create_client(&self, client: &NewClient) -> Result<i32, AppError> {
+        let _timer = self.pools.query_timer("client_repository.create_client");
+        let result = sqlx::query(
+            "INSERT INTO clients (user_id, name, phone_number, default_pickup_node_id) VALUES (?, ?, ?, ?)",
+        )
+        .bind(client.user_id)
+        .bind(&client.name)
+        .bind(&client.phone_number)
+        .bind(client.default_pickup_node_id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.last_insert_id() as i32)
+    }
+
+    async fn find_client_by_id(&self, id: i32) -> Result<Option<Client>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("client_repository.find_client_by_id");
+        // 作成や変更の直後に読み直すため、プライマリから読む
+        let client = sqlx::query_as::<_, Client>(
+            "SELECT id, user_id, name, phone_number, default_pickup_node_id, created_at FROM clients WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(client)
+    }
+
+    async fn find_client_by_phone_number(
+        &self,
+        phone_number: &str,
+    ) -> Result<Option<Client>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("client_repository.find_client_by_phone_number");
+        let client = sqlx::query_as::<_, Client>(
+            "SELECT id, user_id, name, phone_number, default_pickup_node_id, created_at FROM clients WHERE phone_number = ?",
+        )
+        .bind(phone_number)
+        .fetch_optional(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(client)
+    }
+
+    async fn update_client(&self, client: &Client) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("client_repository.update_client");
+        sqlx::query(
+            "UPDATE clients SET name = ?, phone_number = ?, default_pickup_node_id = ? WHERE id = ?",
+        )
+        .bind(&client.name)
+        .bind(&client.phone_number)
+        .bind(client.default_pickup_node_id)
+        .bind(client.id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_orders_by_client_id(
+        &self,
+        organization_id: i32,
+        client_id: i32,
+        limit: i32,
+    ) -> Result<Vec<Order>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("client_repository.find_orders_by_client_id");
+        let orders = sqlx::query_as::<_, Order>(
+            "SELECT id, client_id, dispatcher_id, tow_truck_id, status, node_id, car_value, order_time, completed_time, version, priority
+            FROM orders
+            WHERE customer_id = ? AND organization_id = ?
+            ORDER BY order_time DESC
+            LIMIT ?",
+        )
+        .bind(client_id)
+        .bind(organization_id)
+        .bind(limit)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(orders)
+    }
+
+    async fn create_client_order(
+        &self,
+        organization_id: i32,
+        client_id: i32,
+        user_id: i32,
+        node_id: i32,
+        car_value: f64,
+    ) -> Result<i32, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("client_repository.create_client_order");
+        let result = sqlx::query(
+            "INSERT INTO orders (organization_id, client_id, customer_id, node_id, status, car_value) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .bind(client_id)
+        .bind(node_id)
+        .bind(OrderStatus::Pending.as_str())
+        .bind(car_value)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.last_insert_id() as i32)
+    }
+}
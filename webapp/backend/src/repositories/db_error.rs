@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use sqlx::mysql::MySqlDatabaseError;
+
+use crate::errors::AppError;
+use crate::infrastructure::circuit_breaker;
+
+// 一意キーの重複
+const DUPLICATE_KEY_ERRORS: [u16; 2] = [1062, 1586];
+// 参照先の行がない、または参照されている行を削除しようとした
+const FOREIGN_KEY_ERRORS: [u16; 4] = [1216, 1217, 1451, 1452];
+// ロック待ちのタイムアウトと、max_execution_time による打ち切り
+const TIMEOUT_ERRORS: [u16; 2] = [1205, 3024];
+
+// タイムアウトしたクエリの再試行までの目安
+const TIMEOUT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+// sqlx のエラーを、クライアントが対処を判断できる AppError に振り分ける。
+// どれにも当たらないエラーは SqlxError のまま 500 として返す
+pub fn classify(error: sqlx::Error) -> AppError {
+    // クエリ自体の誤りや制約違反、ロック待ちは DB の障害ではないため、サーキットブレーカーには数えない
+    if matches!(
+        error,
+        sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::Protocol(_)
+            | sqlx::Error::WorkerCrashed
+    ) {
+        circuit_breaker::mark_db_failure();
+    }
+
+    let number = match &error {
+        sqlx::Error::PoolTimedOut => return AppError::ServiceUnavailable(TIMEOUT_RETRY_AFTER),
+        sqlx::Error::Database(e) => e
+            .try_downcast_ref::<MySqlDatabaseError>()
+            .map(|e| e.number()),
+        _ => None,
+    };
+
+    match number {
+        Some(number) if DUPLICATE_KEY_ERRORS.contains(&number) => AppError::Conflict,
+        Some(number) if FOREIGN_KEY_ERRORS.contains(&number) => AppError::BadRequest,
+        Some(number) if TIMEOUT_ERRORS.contains(&number) => {
+            AppError::ServiceUnavailable(TIMEOUT_RETRY_AFTER)
+        }
+        _ => AppError::SqlxError(error),
+    }
+}
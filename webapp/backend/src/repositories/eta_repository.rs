@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+
+use crate::domains::eta_service::EtaRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::order::{InFlightOrder, OrderStatus};
+
+#[derive(Debug)]
+pub struct EtaRepositoryImpl {
+    pools: DbPools,
+}
+
+impl EtaRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        EtaRepositoryImpl { pools }
+    }
+}
+
+impl EtaRepository for EtaRepositoryImpl {
+    async fn find_in_flight_orders(&self) -> Result<Vec<InFlightOrder>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("eta_repository.find_in_flight_orders");
+        let orders = sqlx::query_as::<_, InFlightOrder>(
+            "SELECT
+                o.id,
+                n.area_id,
+                o.node_id,
+                o.dropoff_node_id,
+                l.node_id AS tow_truck_node_id,
+                o.order_time,
+                o.quoted_eta_minutes,
+                o.eta_at
+            FROM
+                orders o
+            JOIN
+                nodes n
+            ON
+                n.id = o.node_id
+            JOIN
+                locations l
+            ON
+                l.tow_truck_id = o.tow_truck_id
+                AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = o.tow_truck_id)
+            WHERE
+                o.status = ?",
+        )
+        .bind(OrderStatus::Dispatched.as_str())
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(orders)
+    }
+
+    async fn update_etas(
+        &self,
+        etas: &[(i32, DateTime<Utc>)],
+        updated_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("eta_repository.update_etas");
+        if etas.is_empty() {
+            return Ok(());
+        }
+
+        let query = format!(
+            "UPDATE orders SET eta_at = CASE id {} END, eta_updated_at = ? WHERE id IN ({}) AND status = ?",
+            vec!["WHEN ? THEN ?"; etas.len()].join(" "),
+            vec!["?"; etas.len()].join(", ")
+        );
+        let mut update = sqlx::query(&query);
+        for (order_id, eta_at) in etas {
+            update = update.bind(order_id).bind(eta_at);
+        }
+        update = update.bind(updated_at);
+        for (order_id, _) in etas {
+            update = update.bind(order_id);
+        }
+        update
+            .bind(OrderStatus::Dispatched.as_str())
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use futures_util::TryStreamExt;
+
+use crate::domains::export_service::{ExportFilter, ExportRepository, ExportSender};
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::audit_log::AuditLog;
+use crate::models::order::OrderExportRow;
+use crate::models::tow_truck::TowTruck;
+
+// 結果セットを一度に読み込まず、サーバーから届いた行から順に送る
+#[derive(Debug, Clone)]
+pub struct ExportRepositoryImpl {
+    pools: DbPools,
+}
+
+impl ExportRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        ExportRepositoryImpl { pools }
+    }
+}
+
+impl ExportRepository for ExportRepositoryImpl {
+    async fn stream_orders(
+        &self,
+        filter: &ExportFilter,
+        sender: &ExportSender<OrderExportRow>,
+    ) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("export_repository.stream_orders");
+        let mut rows = sqlx::query_as::<_, OrderExportRow>(
+            "SELECT
+                o.id,
+                o.client_id,
+                u.username AS client_username,
+                o.dispatcher_id,
+                o.tow_truck_id,
+                o.status,
+                o.node_id,
+                n.area_id,
+                o.car_value,
+                o.order_time,
+                o.completed_time
+            FROM
+                orders o
+            JOIN
+                users u
+            ON
+                u.id = o.client_id
+            JOIN
+                nodes n
+            ON
+                n.id = o.node_id
+            WHERE
+                (? IS NULL OR o.organization_id = ?)
+                AND (? IS NULL OR o.status = ?)
+                AND (? IS NULL OR n.area_id = ?)
+            ORDER BY
+                o.id",
+        )
+        .bind(filter.organization_id)
+        .bind(filter.organization_id)
+        .bind(&filter.status)
+        .bind(&filter.status)
+        .bind(filter.area_id)
+        .bind(filter.area_id)
+        .fetch(&self.pools.replica);
+
+        while let Some(row) = rows.try_next().await? {
+            if sender.send(Ok(row)).await.is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn stream_tow_trucks(
+        &self,
+        filter: &ExportFilter,
+        sender: &ExportSender<TowTruck>,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("export_repository.stream_tow_trucks");
+        let mut rows = sqlx::query_as::<_, TowTruck>(
+            "SELECT
+                tt.id,
+                tt.driver_id,
+                u.username AS driver_username,
+                tt.status,
+                tt.area_id,
+                l.node_id
+            FROM
+                tow_trucks tt
+            JOIN
+                users u
+            ON
+                tt.driver_id = u.id
+            JOIN
+                locations l
+            ON
+                tt.id = l.tow_truck_id
+            WHERE
+                (? IS NULL OR u.organization_id = ?)
+                AND (? IS NULL OR tt.status = ?)
+                AND (? IS NULL OR tt.area_id = ?)
+                AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)
+            ORDER BY
+                tt.id",
+        )
+        .bind(filter.organization_id)
+        .bind(filter.organization_id)
+        .bind(&filter.status)
+        .bind(&filter.status)
+        .bind(filter.area_id)
+        .bind(filter.area_id)
+        .fetch(&self.pools.replica);
+
+        while let Some(row) = rows.try_next().await? {
+            if sender.send(Ok(row)).await.is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn stream_audit_logs(&self, sender: &ExportSender<AuditLog>) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("export_repository.stream_audit_logs");
+        let mut rows = sqlx::query_as::<_, AuditLog>(
+            "SELECT
+                id,
+                actor_user_id,
+                action,
+                target_type,
+                target_id,
+                CAST(detail AS CHAR) AS detail,
+                created_at
+            FROM
+                audit_logs
+            ORDER BY
+                id",
+        )
+        .fetch(&self.pools.replica);
+
+        while let Some(row) = rows.try_next().await? {
+            if sender.send(Ok(row)).await.is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use crate::domains::feature_flag_service::FeatureFlagRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::feature_flag::FeatureFlagRow;
+
+#[derive(Debug, Clone)]
+pub struct FeatureFlagRepositoryImpl {
+    pools: DbPools,
+}
+
+impl FeatureFlagRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        FeatureFlagRepositoryImpl { pools }
+    }
+}
+
+impl FeatureFlagRepository for FeatureFlagRepositoryImpl {
+    // 切り替えた直後に読み直しても古い値に戻らないよう、プライマリから読む
+    async fn find_all(&self) -> Result<Vec<FeatureFlagRow>, AppError> {
+        let _timer = self.pools.query_timer("feature_flag_repository.find_all");
+        let rows = sqlx::query_as::<_, FeatureFlagRow>(
+            "SELECT name, enabled FROM feature_flags ORDER BY name",
+        )
+        .fetch_all(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(rows)
+    }
+
+    async fn upsert(&self, name: &str, enabled: bool) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("feature_flag_repository.upsert");
+        sqlx::query(
+            "INSERT INTO feature_flags (name, enabled) VALUES (?, ?)
+            ON DUPLICATE KEY UPDATE enabled = VALUES(enabled)",
+        )
+        .bind(name)
+        .bind(enabled)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, name: &str) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("feature_flag_repository.delete");
+        sqlx::query("DELETE FROM feature_flags WHERE name = ?")
+            .bind(name)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+}
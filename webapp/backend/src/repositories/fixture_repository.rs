@@ -0,0 +1,143 @@
+use crate::domains::fixture_service::FixtureRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::user::NewSession;
+use crate::repositories::bulk_insert;
+
+// マイグレーションで追加したテーブルも含め、アプリケーションが書き込むすべてのテーブル。
+// organizations はマイグレーションで作る既定の組織 (id = 1) を残すため対象にしない
+const TRUNCATE_TABLES: [&str; 36] = [
+    "completed_orders",
+    "order_messages",
+    "order_message_reads",
+    "order_notes",
+    "order_attachments",
+    "orders",
+    "locations",
+    "edges",
+    "nodes",
+    "tow_trucks",
+    "dispatchers",
+    "sessions",
+    "totp_backup_codes",
+    "external_identities",
+    "username_history",
+    "api_keys",
+    "audit_logs",
+    "login_attempts",
+    "idempotency_keys",
+    "email_verification_tokens",
+    "notification_preferences",
+    "notification_dead_letters",
+    "push_tokens",
+    "webhook_subscriptions",
+    "webhook_deliveries",
+    "dispatcher_stats",
+    "change_log",
+    "order_counters",
+    "profile_images",
+    "clients",
+    "invite_codes",
+    "feature_flags",
+    "geocode_cache",
+    "reverse_geocode_cache",
+    "users",
+    "areas",
+];
+
+#[derive(Debug)]
+pub struct FixtureRepositoryImpl {
+    pools: DbPools,
+}
+
+impl FixtureRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        FixtureRepositoryImpl { pools }
+    }
+}
+
+impl FixtureRepository for FixtureRepositoryImpl {
+    async fn truncate_all(&self) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("fixture_repository.truncate_all");
+        // FOREIGN_KEY_CHECKS はセッション単位の設定のため、同じ接続で TRUNCATE する。
+        // 途中で失敗したり打ち切られたりしても無効にしたままの接続が使い回されないよう、プールから切り離して使い捨てる
+        let mut conn = self.pools.acquire_primary().await?.detach();
+
+        sqlx::query("SET FOREIGN_KEY_CHECKS = 0")
+            .execute(&mut conn)
+            .await?;
+        for table in TRUNCATE_TABLES {
+            sqlx::query(&format!("TRUNCATE TABLE {}", table))
+                .execute(&mut conn)
+                .await?;
+        }
+        sqlx::query("SET FOREIGN_KEY_CHECKS = 1")
+            .execute(&mut conn)
+            .await?;
+        sqlx::Connection::close(conn).await?;
+
+        Ok(())
+    }
+
+    async fn insert_rows(
+        &self,
+        table: &str,
+        columns: &[String],
+        rows: &[Vec<Option<String>>],
+    ) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("fixture_repository.insert_rows");
+        if rows.is_empty() {
+            return Ok(());
+        }
+        if !bulk_insert::is_identifier(table)
+            || !columns
+                .iter()
+                .all(|column| bulk_insert::is_identifier(column))
+        {
+            return Err(AppError::BadRequest);
+        }
+        if rows.iter().any(|row| row.len() != columns.len()) {
+            return Err(AppError::BadRequest);
+        }
+
+        for chunk in rows.chunks(bulk_insert::rows_per_statement(columns.len())) {
+            let query = format!(
+                "INSERT INTO {} ({}) VALUES {}",
+                table,
+                columns.join(", "),
+                bulk_insert::values_placeholders(columns.len(), chunk.len())
+            );
+            let mut insert = sqlx::query(&query);
+            for value in chunk.iter().flatten() {
+                insert = insert.bind(value.as_deref());
+            }
+            insert
+                .execute(&mut *self.pools.acquire_primary().await?)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn create_sessions(&self, sessions: &[NewSession]) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("fixture_repository.create_sessions");
+        for chunk in sessions.chunks(bulk_insert::rows_per_statement(3)) {
+            let query = format!(
+                "INSERT INTO sessions (user_id, session_token, expires_at) VALUES {}",
+                bulk_insert::values_placeholders(3, chunk.len())
+            );
+            let mut insert = sqlx::query(&query);
+            for session in chunk {
+                insert = insert
+                    .bind(session.user_id)
+                    .bind(&session.session_token)
+                    .bind(session.expires_at);
+            }
+            insert
+                .execute(&mut *self.pools.acquire_primary().await?)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use crate::domains::geocoding_service::GeocodingRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::geocode::{GeocodeCacheEntry, ReverseGeocodeCacheEntry};
+
+#[derive(Debug)]
+pub struct GeocodingRepositoryImpl {
+    pools: DbPools,
+}
+
+impl GeocodingRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        GeocodingRepositoryImpl { pools }
+    }
+}
+
+impl GeocodingRepository for GeocodingRepositoryImpl {
+    async fn find_geocode(&self, address: &str) -> Result<Option<GeocodeCacheEntry>, AppError> {
+        let _timer = self.pools.query_timer("geocoding_repository.find_geocode");
+        let entry = sqlx::query_as::<_, GeocodeCacheEntry>(
+            "SELECT x, y, created_at FROM geocode_cache WHERE address = ?",
+        )
+        .bind(address)
+        .fetch_optional(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(entry)
+    }
+
+    async fn save_geocode(
+        &self,
+        address: &str,
+        coordinate: Option<(i32, i32)>,
+    ) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("geocoding_repository.save_geocode");
+        sqlx::query(
+            "INSERT INTO geocode_cache (address, x, y) VALUES (?, ?, ?)
+            ON DUPLICATE KEY UPDATE x = VALUES(x), y = VALUES(y), created_at = CURRENT_TIMESTAMP",
+        )
+        .bind(address)
+        .bind(coordinate.map(|(x, _)| x))
+        .bind(coordinate.map(|(_, y)| y))
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_reverse_geocode(
+        &self,
+        x: i32,
+        y: i32,
+    ) -> Result<Option<ReverseGeocodeCacheEntry>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("geocoding_repository.find_reverse_geocode");
+        let entry = sqlx::query_as::<_, ReverseGeocodeCacheEntry>(
+            "SELECT address, created_at FROM reverse_geocode_cache WHERE x = ? AND y = ?",
+        )
+        .bind(x)
+        .bind(y)
+        .fetch_optional(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(entry)
+    }
+
+    async fn save_reverse_geocode(
+        &self,
+        x: i32,
+        y: i32,
+        address: Option<&str>,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("geocoding_repository.save_reverse_geocode");
+        sqlx::query(
+            "INSERT INTO reverse_geocode_cache (x, y, address) VALUES (?, ?, ?)
+            ON DUPLICATE KEY UPDATE address = VALUES(address), created_at = CURRENT_TIMESTAMP",
+        )
+        .bind(x)
+        .bind(y)
+        .bind(address)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::area::Area;
+use crate::models::order::OrderWithArea;
+use crate::models::user::{Dispatcher, UserSummary};
+
+const ORDER_COLUMNS: &str = "o.id,
+                o.client_id,
+                o.dispatcher_id,
+                o.tow_truck_id,
+                o.status,
+                n.area_id,
+                o.car_value,
+                o.order_time,
+                o.completed_time";
+
+// GraphQL のデータローダーから呼び出す、ID の一覧でまとめて読み込むクエリ。
+// どのクエリも organization_id の組織のデータだけを返す
+#[derive(Debug, Clone)]
+pub struct GraphqlRepositoryImpl {
+    pools: DbPools,
+}
+
+impl GraphqlRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        GraphqlRepositoryImpl { pools }
+    }
+
+    pub async fn find_users_by_ids(
+        &self,
+        organization_id: i32,
+        ids: &[i32],
+    ) -> Result<Vec<UserSummary>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("graphql_repository.find_users_by_ids");
+        let query = format!(
+            "SELECT id, username, role FROM users WHERE organization_id = ? AND id IN ({})",
+            placeholders(ids.len())
+        );
+        let mut select = sqlx::query_as::<_, UserSummary>(&query).bind(organization_id);
+        for id in ids {
+            select = select.bind(id);
+        }
+
+        Ok(select
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?)
+    }
+
+    pub async fn find_dispatchers_by_ids(
+        &self,
+        organization_id: i32,
+        ids: &[i32],
+    ) -> Result<Vec<Dispatcher>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("graphql_repository.find_dispatchers_by_ids");
+        let query = format!(
+            "SELECT id, user_id, area_id FROM dispatchers WHERE organization_id = ? AND id IN ({})",
+            placeholders(ids.len())
+        );
+        let mut select = sqlx::query_as::<_, Dispatcher>(&query).bind(organization_id);
+        for id in ids {
+            select = select.bind(id);
+        }
+
+        Ok(select
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?)
+    }
+
+    pub async fn find_dispatchers_by_user_ids(
+        &self,
+        organization_id: i32,
+        user_ids: &[i32],
+    ) -> Result<Vec<Dispatcher>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("graphql_repository.find_dispatchers_by_user_ids");
+        let query = format!(
+            "SELECT id, user_id, area_id FROM dispatchers WHERE organization_id = ? AND user_id IN ({})",
+            placeholders(user_ids.len())
+        );
+        let mut select = sqlx::query_as::<_, Dispatcher>(&query).bind(organization_id);
+        for user_id in user_ids {
+            select = select.bind(user_id);
+        }
+
+        Ok(select
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?)
+    }
+
+    pub async fn find_dispatchers_by_area_ids(
+        &self,
+        organization_id: i32,
+        area_ids: &[i32],
+    ) -> Result<Vec<Dispatcher>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("graphql_repository.find_dispatchers_by_area_ids");
+        let query = format!(
+            "SELECT id, user_id, area_id FROM dispatchers WHERE organization_id = ? AND area_id IN ({}) ORDER BY id",
+            placeholders(area_ids.len())
+        );
+        let mut select = sqlx::query_as::<_, Dispatcher>(&query).bind(organization_id);
+        for area_id in area_ids {
+            select = select.bind(area_id);
+        }
+
+        Ok(select
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?)
+    }
+
+    pub async fn find_areas(&self, organization_id: i32) -> Result<Vec<Area>, AppError> {
+        let _timer = self.pools.query_timer("graphql_repository.find_areas");
+        let areas = sqlx::query_as::<_, Area>(
+            "SELECT id, name, organization_id FROM areas WHERE organization_id = ? ORDER BY id",
+        )
+        .bind(organization_id)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(areas)
+    }
+
+    pub async fn find_areas_by_ids(
+        &self,
+        organization_id: i32,
+        ids: &[i32],
+    ) -> Result<Vec<Area>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("graphql_repository.find_areas_by_ids");
+        let query = format!(
+            "SELECT id, name, organization_id FROM areas WHERE organization_id = ? AND id IN ({})",
+            placeholders(ids.len())
+        );
+        let mut select = sqlx::query_as::<_, Area>(&query).bind(organization_id);
+        for id in ids {
+            select = select.bind(id);
+        }
+
+        Ok(select
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?)
+    }
+
+    pub async fn find_orders_by_ids(
+        &self,
+        organization_id: i32,
+        ids: &[i32],
+    ) -> Result<Vec<OrderWithArea>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("graphql_repository.find_orders_by_ids");
+        let query = format!(
+            "SELECT
+                {}
+            FROM
+                orders o
+            JOIN
+                nodes n
+            ON
+                o.node_id = n.id
+            WHERE
+                o.organization_id = ?
+            AND
+                o.id IN ({})",
+            ORDER_COLUMNS,
+            placeholders(ids.len())
+        );
+        let mut select = sqlx::query_as::<_, OrderWithArea>(&query).bind(organization_id);
+        for id in ids {
+            select = select.bind(id);
+        }
+
+        Ok(select
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?)
+    }
+
+    pub async fn find_orders(
+        &self,
+        organization_id: i32,
+        status: Option<&str>,
+        area_id: Option<i32>,
+        limit: i32,
+        offset: i32,
+    ) -> Result<Vec<OrderWithArea>, AppError> {
+        let _timer = self.pools.query_timer("graphql_repository.find_orders");
+        let query = format!(
+            "SELECT
+                {}
+            FROM
+                orders o
+            JOIN
+                nodes n
+            ON
+                o.node_id = n.id
+            WHERE
+                o.organization_id = ?
+            AND
+                (? IS NULL OR o.status = ?)
+            AND
+                (? IS NULL OR n.area_id = ?)
+            ORDER BY
+                o.order_time DESC
+            LIMIT ?
+            OFFSET ?",
+            ORDER_COLUMNS
+        );
+        let orders = sqlx::query_as::<_, OrderWithArea>(&query)
+            .bind(organization_id)
+            .bind(status)
+            .bind(status)
+            .bind(area_id)
+            .bind(area_id)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?;
+
+        Ok(orders)
+    }
+}
+
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
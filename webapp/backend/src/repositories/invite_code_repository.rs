@@ -0,0 +1,139 @@
+use chrono::{DateTime, Utc};
+
+use crate::domains::invite_code_service::InviteCodeRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::invite_code::InviteCode;
+use crate::models::role::Role;
+
+#[derive(Debug)]
+pub struct InviteCodeRepositoryImpl {
+    pools: DbPools,
+}
+
+impl InviteCodeRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        InviteCodeRepositoryImpl { pools }
+    }
+}
+
+impl InviteCodeRepository for InviteCodeRepositoryImpl {
+    async fn create_invite_code(
+        &self,
+        code_prefix: &str,
+        code_hash: &str,
+        role: Role,
+        max_uses: i32,
+        expires_at: Option<DateTime<Utc>>,
+        created_by: i32,
+    ) -> Result<i32, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("invite_code_repository.create_invite_code");
+        let result = sqlx::query(
+            "INSERT INTO invite_codes (code_prefix, code_hash, role, max_uses, expires_at, created_by)
+            VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(code_prefix)
+        .bind(code_hash)
+        .bind(role)
+        .bind(max_uses)
+        .bind(expires_at)
+        .bind(created_by)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.last_insert_id() as i32)
+    }
+
+    async fn find_invite_codes(&self) -> Result<Vec<InviteCode>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("invite_code_repository.find_invite_codes");
+        let invite_codes = sqlx::query_as::<_, InviteCode>(
+            "SELECT
+                id,
+                code_prefix,
+                role,
+                max_uses,
+                use_count,
+                expires_at,
+                revoked_at,
+                created_by,
+                created_at
+            FROM
+                invite_codes
+            ORDER BY
+                id DESC",
+        )
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(invite_codes)
+    }
+
+    async fn revoke_invite_code(&self, id: i32, now: DateTime<Utc>) -> Result<bool, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("invite_code_repository.revoke_invite_code");
+        let result = sqlx::query(
+            "UPDATE invite_codes SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL",
+        )
+        .bind(now)
+        .bind(id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn redeem_invite_code(
+        &self,
+        code_hash: &str,
+        role: Role,
+        now: DateTime<Utc>,
+    ) -> Result<Option<i32>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("invite_code_repository.redeem_invite_code");
+        // 同時に使われても上限を超えないよう、条件の確認と使用回数の更新を 1 つの文で行い、
+        // 更新した行の ID は LAST_INSERT_ID で受け取る
+        let result = sqlx::query(
+            "UPDATE
+                invite_codes
+            SET
+                use_count = use_count + 1,
+                id = LAST_INSERT_ID(id)
+            WHERE
+                code_hash = ?
+                AND role = ?
+                AND revoked_at IS NULL
+                AND use_count < max_uses
+                AND (expires_at IS NULL OR expires_at > ?)",
+        )
+        .bind(code_hash)
+        .bind(role)
+        .bind(now)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(match result.rows_affected() {
+            0 => None,
+            _ => Some(result.last_insert_id() as i32),
+        })
+    }
+
+    async fn release_invite_code(&self, id: i32) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("invite_code_repository.release_invite_code");
+        sqlx::query(
+            "UPDATE invite_codes SET use_count = use_count - 1 WHERE id = ? AND use_count > 0",
+        )
+        .bind(id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use crate::domains::leaderboard_service::LeaderboardRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::leaderboard::DispatcherStats;
+
+#[derive(Debug, Clone)]
+pub struct LeaderboardRepositoryImpl {
+    pools: DbPools,
+}
+
+impl LeaderboardRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        LeaderboardRepositoryImpl { pools }
+    }
+}
+
+impl LeaderboardRepository for LeaderboardRepositoryImpl {
+    async fn record_completion(
+        &self,
+        dispatcher_id: i32,
+        eta_error_minutes: Option<i32>,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("leaderboard_repository.record_completion");
+        // 同時に完了した注文があっても取りこぼさないよう、読み込まずに加算する
+        sqlx::query(
+            "INSERT INTO dispatcher_stats (dispatcher_id, completed_orders, eta_samples, eta_error_minutes_total)
+            VALUES (?, 1, ?, ?)
+            ON DUPLICATE KEY UPDATE
+                completed_orders = completed_orders + 1,
+                eta_samples = eta_samples + VALUES(eta_samples),
+                eta_error_minutes_total = eta_error_minutes_total + VALUES(eta_error_minutes_total)",
+        )
+        .bind(dispatcher_id)
+        .bind(eta_error_minutes.is_some() as i32)
+        .bind(eta_error_minutes.unwrap_or(0) as i64)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_dispatcher_stats(
+        &self,
+        limit: i32,
+        offset: i32,
+    ) -> Result<Vec<DispatcherStats>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("leaderboard_repository.get_dispatcher_stats");
+        let stats = sqlx::query_as::<_, DispatcherStats>(
+            "SELECT
+                s.dispatcher_id,
+                d.user_id,
+                u.username,
+                s.completed_orders,
+                s.eta_samples,
+                s.eta_error_minutes_total
+            FROM
+                dispatcher_stats s
+            JOIN
+                dispatchers d
+            ON
+                d.id = s.dispatcher_id
+            JOIN
+                users u
+            ON
+                u.id = d.user_id
+            ORDER BY
+                s.completed_orders DESC,
+                s.eta_error_minutes_total / GREATEST(s.eta_samples, 1) ASC,
+                s.dispatcher_id ASC
+            LIMIT ?
+            OFFSET ?",
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(stats)
+    }
+
+    async fn count_dispatcher_stats(&self) -> Result<i64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("leaderboard_repository.count_dispatcher_stats");
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM dispatcher_stats")
+            .fetch_one(&mut *self.pools.acquire_replica().await?)
+            .await?;
+
+        Ok(count)
+    }
+}
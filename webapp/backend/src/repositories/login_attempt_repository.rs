@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+
+use crate::domains::login_anomaly_service::LoginAttemptRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::login_attempt::{LoginAttempt, NewLoginAttempt};
+
+#[derive(Debug)]
+pub struct LoginAttemptRepositoryImpl {
+    pools: DbPools,
+}
+
+impl LoginAttemptRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        LoginAttemptRepositoryImpl { pools }
+    }
+}
+
+impl LoginAttemptRepository for LoginAttemptRepositoryImpl {
+    async fn create_login_attempt(&self, attempt: &NewLoginAttempt) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("login_attempt_repository.create_login_attempt");
+        sqlx::query(
+            "INSERT INTO login_attempts (user_id, succeeded, client_ip, user_agent, area_id, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(attempt.user_id)
+        .bind(attempt.succeeded)
+        .bind(&attempt.client_ip)
+        .bind(&attempt.user_agent)
+        .bind(attempt.area_id)
+        .bind(attempt.created_at)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_successful_logins(
+        &self,
+        user_id: i32,
+        since: DateTime<Utc>,
+        limit: i32,
+    ) -> Result<Vec<LoginAttempt>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("login_attempt_repository.find_successful_logins");
+        // 直前の試行と比べるため、レプリカの遅延の影響を受けないプライマリから読む
+        let attempts = sqlx::query_as::<_, LoginAttempt>(
+            "SELECT client_ip, user_agent, area_id, created_at FROM login_attempts
+            WHERE user_id = ? AND succeeded = TRUE AND created_at >= ?
+            ORDER BY created_at DESC, id DESC
+            LIMIT ?",
+        )
+        .bind(user_id)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(attempts)
+    }
+
+    async fn count_failed_logins(
+        &self,
+        user_id: i32,
+        since: DateTime<Utc>,
+    ) -> Result<i64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("login_attempt_repository.count_failed_logins");
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM login_attempts
+            WHERE user_id = ? AND succeeded = FALSE AND created_at >= ?",
+        )
+        .bind(user_id)
+        .bind(since)
+        .fetch_one(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(count)
+    }
+}
@@ -1,23 +1,26 @@
-use sqlx::MySqlPool;
-
 use crate::{
     domains::map_service::MapRepository,
-    models::graph::{Edge, Node},
+    infrastructure::db::DbPools,
+    models::{
+        area::{Area, AreaBoundaryRow},
+        graph::{Edge, Node},
+    },
 };
 
 #[derive(Debug)]
 pub struct MapRepositoryImpl {
-    pool: MySqlPool,
+    pools: DbPools,
 }
 
 impl MapRepositoryImpl {
-    pub fn new(pool: MySqlPool) -> Self {
-        MapRepositoryImpl { pool }
+    pub fn new(pools: DbPools) -> Self {
+        MapRepositoryImpl { pools }
     }
 }
 
 impl MapRepository for MapRepositoryImpl {
     async fn get_all_nodes(&self, area_id: Option<i32>) -> Result<Vec<Node>, sqlx::Error> {
+        let _timer = self.pools.query_timer("map_repository.get_all_nodes");
         let where_clause = match area_id {
             Some(_) => "WHERE area_id = ?",
             None => "",
@@ -34,24 +37,30 @@ impl MapRepository for MapRepositoryImpl {
             where_clause
         );
 
-        let nodes = match area_id {
-            Some(area_id) => {
-                sqlx::query_as::<_, Node>(&sql)
-                    .bind(area_id)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-            None => {
-                sqlx::query_as::<_, Node>(&sql)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-        };
+        let nodes = self
+            .pools
+            .retry("map_repository.get_all_nodes", || async {
+                match area_id {
+                    Some(area_id) => {
+                        sqlx::query_as::<_, Node>(&sql)
+                            .bind(area_id)
+                            .fetch_all(&mut *self.pools.acquire_replica().await?)
+                            .await
+                    }
+                    None => {
+                        sqlx::query_as::<_, Node>(&sql)
+                            .fetch_all(&mut *self.pools.acquire_replica().await?)
+                            .await
+                    }
+                }
+            })
+            .await?;
 
         Ok(nodes)
     }
 
     async fn get_all_edges(&self, area_id: Option<i32>) -> Result<Vec<Edge>, sqlx::Error> {
+        let _timer = self.pools.query_timer("map_repository.get_all_edges");
         let where_clause = match area_id {
             Some(_) => "JOIN nodes n ON e.node_a_id = n.id WHERE n.area_id = ?",
             None => "",
@@ -68,45 +77,176 @@ impl MapRepository for MapRepositoryImpl {
             where_clause
         );
 
-        let edges = match area_id {
-            Some(area_id) => {
-                sqlx::query_as::<_, Edge>(&sql)
-                    .bind(area_id)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-            None => {
-                sqlx::query_as::<_, Edge>(&sql)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-        };
+        let edges = self
+            .pools
+            .retry("map_repository.get_all_edges", || async {
+                match area_id {
+                    Some(area_id) => {
+                        sqlx::query_as::<_, Edge>(&sql)
+                            .bind(area_id)
+                            .fetch_all(&mut *self.pools.acquire_replica().await?)
+                            .await
+                    }
+                    None => {
+                        sqlx::query_as::<_, Edge>(&sql)
+                            .fetch_all(&mut *self.pools.acquire_replica().await?)
+                            .await
+                    }
+                }
+            })
+            .await?;
 
         Ok(edges)
     }
 
+    async fn get_all_areas(&self) -> Result<Vec<Area>, sqlx::Error> {
+        let _timer = self.pools.query_timer("map_repository.get_all_areas");
+        let areas = self
+            .pools
+            .retry("map_repository.get_all_areas", || async move {
+                sqlx::query_as::<_, Area>("SELECT id, name, organization_id FROM areas ORDER BY id")
+                    .fetch_all(&mut *self.pools.acquire_replica().await?)
+                    .await
+            })
+            .await?;
+
+        Ok(areas)
+    }
+
     async fn get_area_id_by_node_id(&self, node_id: i32) -> Result<i32, sqlx::Error> {
-        let area_id = sqlx::query_scalar("SELECT area_id FROM nodes WHERE id = ?")
-            .bind(node_id)
-            .fetch_one(&self.pool)
+        let _timer = self
+            .pools
+            .query_timer("map_repository.get_area_id_by_node_id");
+        let area_id = self
+            .pools
+            .retry("map_repository.get_area_id_by_node_id", || async move {
+                sqlx::query_scalar("SELECT area_id FROM nodes WHERE id = ?")
+                    .bind(node_id)
+                    .fetch_one(&mut *self.pools.acquire_replica().await?)
+                    .await
+            })
             .await?;
 
         Ok(area_id)
     }
 
+    async fn find_node_area_id(
+        &self,
+        organization_id: i32,
+        node_id: i32,
+    ) -> Result<Option<i32>, sqlx::Error> {
+        let _timer = self.pools.query_timer("map_repository.find_node_area_id");
+        let area_id = self
+            .pools
+            .retry("map_repository.find_node_area_id", || async move {
+                sqlx::query_scalar(
+                    "SELECT n.area_id FROM nodes n JOIN areas a ON a.id = n.area_id WHERE n.id = ? AND a.organization_id = ?",
+                )
+                .bind(node_id)
+                .bind(organization_id)
+                .fetch_optional(&mut *self.pools.acquire_replica().await?).await
+            })
+            .await?;
+
+        Ok(area_id)
+    }
+
+    async fn find_node_by_coordinate(
+        &self,
+        organization_id: i32,
+        x: i32,
+        y: i32,
+    ) -> Result<Option<(i32, i32)>, sqlx::Error> {
+        let _timer = self
+            .pools
+            .query_timer("map_repository.find_node_by_coordinate");
+        let node = self
+            .pools
+            .retry("map_repository.find_node_by_coordinate", || async move {
+                sqlx::query_as::<_, (i32, i32)>(
+                    "SELECT n.id, n.area_id FROM nodes n JOIN areas a ON a.id = n.area_id
+                    WHERE n.x = ? AND n.y = ? AND a.organization_id = ? ORDER BY n.id LIMIT 1",
+                )
+                .bind(x)
+                .bind(y)
+                .bind(organization_id)
+                .fetch_optional(&mut *self.pools.acquire_replica().await?)
+                .await
+            })
+            .await?;
+
+        Ok(node)
+    }
+
     async fn update_edge(
         &self,
+        organization_id: i32,
         node_a_id: i32,
         node_b_id: i32,
         weight: i32,
     ) -> Result<(), sqlx::Error> {
-        sqlx::query("UPDATE edges SET weight = ? WHERE (node_a_id = ? AND node_b_id = ?) OR (node_a_id = ? AND node_b_id = ?)")
-            .bind(weight)
-            .bind(node_a_id)
-            .bind(node_b_id)
-            .bind(node_b_id)
-            .bind(node_a_id)
-            .execute(&self.pool)
+        let _timer = self.pools.query_timer("map_repository.update_edge");
+        sqlx::query(
+            "UPDATE edges e JOIN nodes n ON n.id = e.node_a_id JOIN areas a ON a.id = n.area_id
+            SET e.weight = ?
+            WHERE ((e.node_a_id = ? AND e.node_b_id = ?) OR (e.node_a_id = ? AND e.node_b_id = ?)) AND a.organization_id = ?",
+        )
+        .bind(weight)
+        .bind(node_a_id)
+        .bind(node_b_id)
+        .bind(node_b_id)
+        .bind(node_a_id)
+        .bind(organization_id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_node_by_id(&self, node_id: i32) -> Result<Option<Node>, sqlx::Error> {
+        let _timer = self.pools.query_timer("map_repository.find_node_by_id");
+        let node = self
+            .pools
+            .retry("map_repository.find_node_by_id", || async move {
+                sqlx::query_as::<_, Node>("SELECT id, x, y FROM nodes WHERE id = ?")
+                    .bind(node_id)
+                    .fetch_optional(&mut *self.pools.acquire_replica().await?)
+                    .await
+            })
+            .await?;
+
+        Ok(node)
+    }
+
+    async fn get_area_boundaries(&self) -> Result<Vec<AreaBoundaryRow>, sqlx::Error> {
+        let _timer = self.pools.query_timer("map_repository.get_area_boundaries");
+        let boundaries = self
+            .pools
+            .retry("map_repository.get_area_boundaries", || async move {
+                sqlx::query_as::<_, AreaBoundaryRow>(
+                    "SELECT id, name, organization_id, boundary FROM areas WHERE boundary IS NOT NULL ORDER BY id",
+                )
+                .fetch_all(&mut *self.pools.acquire_replica().await?).await
+            })
+            .await?;
+
+        Ok(boundaries)
+    }
+
+    async fn update_area_boundary(
+        &self,
+        organization_id: i32,
+        area_id: i32,
+        boundary: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let _timer = self
+            .pools
+            .query_timer("map_repository.update_area_boundary");
+        sqlx::query("UPDATE areas SET boundary = ? WHERE id = ? AND organization_id = ?")
+            .bind(boundary)
+            .bind(area_id)
+            .bind(organization_id)
+            .execute(&mut *self.pools.acquire_primary().await?)
             .await?;
 
         Ok(())
@@ -0,0 +1,818 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Utc};
+
+use crate::domains::auth_service::AuthRepository;
+use crate::errors::AppError;
+use crate::models::organization::DEFAULT_ORGANIZATION_ID;
+use crate::models::role::Role;
+use crate::models::user::{
+    Dispatcher, OnDutyDispatcher, Session, SessionActivity, SessionClient, StoredProfileImage,
+    TotpBackupCode, User, DEFAULT_PROFILE_IMAGE,
+};
+
+#[derive(Debug)]
+struct StoredBackupCode {
+    user_id: i32,
+    code: TotpBackupCode,
+    used: bool,
+}
+
+#[derive(Debug)]
+struct StoredUsernameHistory {
+    user_id: i32,
+    username: String,
+    changed_at: DateTime<Utc>,
+}
+
+// users テーブルの二要素認証の試行に関する列にあたる
+#[derive(Debug, Default)]
+struct TotpGuard {
+    last_step: Option<i64>,
+    failed_attempts: i32,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default)]
+struct Tables {
+    users: HashMap<i32, User>,
+    dispatchers: HashMap<i32, Dispatcher>,
+    sessions: HashMap<i32, Session>,
+    totp_backup_codes: HashMap<i32, StoredBackupCode>,
+    totp_guards: HashMap<i32, TotpGuard>,
+    external_identities: HashMap<(String, String), i32>,
+    username_history: Vec<StoredUsernameHistory>,
+    // トークンのハッシュからユーザー ID と有効期限を引く
+    email_verification_tokens: HashMap<String, (i32, DateTime<Utc>)>,
+    // エリアの ID から組織の ID を引く。エリアの表を持たないため、登録されていないエリアは既定の組織のものとして扱う
+    area_organizations: HashMap<i32, i32>,
+    last_id: i32,
+}
+
+impl Tables {
+    // テーブルごとの AUTO_INCREMENT を区別する必要はないため、共通の連番を使う
+    fn next_id(&mut self) -> i32 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    fn user_mut(&mut self, user_id: i32) -> Option<&mut User> {
+        self.users.get_mut(&user_id)
+    }
+
+    fn insert_session(
+        &mut self,
+        user_id: i32,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+        impersonator_user_id: Option<i32>,
+        client: &SessionClient,
+    ) {
+        let id = self.next_id();
+        let now = Utc::now();
+        self.sessions.insert(
+            id,
+            Session {
+                id,
+                user_id,
+                session_token: session_token.to_string(),
+                is_valid: true,
+                created_at: now,
+                expires_at,
+                client_ip: client.ip.clone(),
+                user_agent: client.user_agent.clone(),
+                last_seen_at: Some(now),
+                impersonator_user_id,
+            },
+        );
+    }
+}
+
+// MySQL の代わりにプロセス内の HashMap にデータを保持する実装。
+// サービス層のテストと、DB なしでバックエンドを起動するローカル開発用
+#[derive(Debug, Clone, Default)]
+pub struct MemoryAuthRepository {
+    tables: Arc<RwLock<Tables>>,
+}
+
+impl MemoryAuthRepository {
+    pub fn new() -> Self {
+        MemoryAuthRepository::default()
+    }
+
+    #[cfg(test)]
+    pub fn insert_area(&self, area_id: i32, organization_id: i32) {
+        self.tables
+            .write()
+            .unwrap()
+            .area_organizations
+            .insert(area_id, organization_id);
+    }
+
+    // email_verified が false のユーザーは、メールアドレスの確認が済むまでログインできない
+    fn insert_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        organization_id: i32,
+        email_verified: bool,
+    ) {
+        let mut tables = self.tables.write().unwrap();
+        let id = tables.next_id();
+        tables.users.insert(
+            id,
+            User {
+                id,
+                username: username.to_string(),
+                password: password.to_string(),
+                profile_image: DEFAULT_PROFILE_IMAGE.to_string(),
+                image_version: 0,
+                role,
+                totp_secret: None,
+                totp_enabled: false,
+                is_active: true,
+                email_verified,
+                password_reset_required: false,
+                organization_id,
+            },
+        );
+    }
+}
+
+impl AuthRepository for MemoryAuthRepository {
+    async fn create_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        organization_id: i32,
+    ) -> Result<(), AppError> {
+        self.insert_user(username, password, role, organization_id, true);
+
+        Ok(())
+    }
+
+    async fn create_unverified_user(
+        &self,
+        username: &str,
+        password: &str,
+        role: Role,
+        _email: &str,
+    ) -> Result<(), AppError> {
+        // メールアドレスは確認メールの送信にしか使わないため保持しない
+        self.insert_user(username, password, role, DEFAULT_ORGANIZATION_ID, false);
+
+        Ok(())
+    }
+
+    async fn find_user_by_id(&self, id: i32) -> Result<Option<User>, AppError> {
+        Ok(self.tables.read().unwrap().users.get(&id).cloned())
+    }
+
+    async fn find_user_by_username(&self, username: &str) -> Result<Option<User>, AppError> {
+        let tables = self.tables.read().unwrap();
+        let user = tables
+            .users
+            .values()
+            .filter(|user| user.username == username)
+            .min_by_key(|user| user.id)
+            .cloned();
+
+        Ok(user)
+    }
+
+    async fn find_user_by_username_from_primary(
+        &self,
+        username: &str,
+    ) -> Result<Option<User>, AppError> {
+        self.find_user_by_username(username).await
+    }
+
+    async fn find_all_usernames(&self) -> Result<Vec<(i32, String)>, AppError> {
+        let tables = self.tables.read().unwrap();
+        let mut usernames: Vec<(i32, String)> = tables
+            .users
+            .values()
+            .map(|user| (user.id, user.username.clone()))
+            .collect();
+        usernames.sort_by_key(|(user_id, _)| *user_id);
+
+        Ok(usernames)
+    }
+
+    async fn find_area_organization_id(&self, area_id: i32) -> Result<Option<i32>, AppError> {
+        let tables = self.tables.read().unwrap();
+
+        Ok(Some(
+            tables
+                .area_organizations
+                .get(&area_id)
+                .copied()
+                .unwrap_or(DEFAULT_ORGANIZATION_ID),
+        ))
+    }
+
+    async fn create_dispatcher(&self, user_id: i32, area_id: i32) -> Result<(), AppError> {
+        let mut tables = self.tables.write().unwrap();
+        let area_organization_id = tables
+            .area_organizations
+            .get(&area_id)
+            .copied()
+            .unwrap_or(DEFAULT_ORGANIZATION_ID);
+        match tables.users.get(&user_id) {
+            Some(user) if user.organization_id == area_organization_id => {}
+            _ => return Err(AppError::BadRequest),
+        }
+        let id = tables.next_id();
+        tables.dispatchers.insert(
+            id,
+            Dispatcher {
+                id,
+                user_id,
+                area_id,
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn find_dispatcher_by_id(&self, id: i32) -> Result<Option<Dispatcher>, AppError> {
+        Ok(self.tables.read().unwrap().dispatchers.get(&id).cloned())
+    }
+
+    async fn find_dispatcher_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<Dispatcher>, AppError> {
+        let tables = self.tables.read().unwrap();
+        let dispatcher = tables
+            .dispatchers
+            .values()
+            .filter(|dispatcher| dispatcher.user_id == user_id)
+            .min_by_key(|dispatcher| dispatcher.id)
+            .cloned();
+
+        Ok(dispatcher)
+    }
+
+    async fn find_dispatcher_by_user_id_from_primary(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<Dispatcher>, AppError> {
+        self.find_dispatcher_by_user_id(user_id).await
+    }
+
+    async fn find_on_duty_dispatchers_by_area_id(
+        &self,
+        area_id: i32,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<OnDutyDispatcher>, AppError> {
+        let tables = self.tables.read().unwrap();
+        let mut dispatchers: Vec<OnDutyDispatcher> = tables
+            .dispatchers
+            .values()
+            .filter(|dispatcher| dispatcher.area_id == area_id)
+            .filter(|dispatcher| {
+                tables.sessions.values().any(|session| {
+                    session.user_id == dispatcher.user_id
+                        && session.is_valid
+                        && session.expires_at > now
+                })
+            })
+            .filter_map(|dispatcher| {
+                let user = tables.users.get(&dispatcher.user_id)?;
+                user.is_active.then(|| OnDutyDispatcher {
+                    dispatcher_id: dispatcher.id,
+                    user_id: user.id,
+                    username: user.username.clone(),
+                })
+            })
+            .collect();
+        dispatchers.sort_by_key(|dispatcher| dispatcher.dispatcher_id);
+
+        Ok(dispatchers)
+    }
+
+    async fn find_profile_image_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<StoredProfileImage>, AppError> {
+        let tables = self.tables.read().unwrap();
+        Ok(tables.users.get(&user_id).map(|user| StoredProfileImage {
+            name: user.profile_image.clone(),
+            version: user.image_version,
+        }))
+    }
+
+    async fn update_profile_image_name(
+        &self,
+        user_id: i32,
+        profile_image_name: &str,
+    ) -> Result<i32, AppError> {
+        let mut tables = self.tables.write().unwrap();
+        let user = tables.user_mut(user_id).ok_or(AppError::NotFound)?;
+        user.profile_image = profile_image_name.to_string();
+        user.image_version += 1;
+
+        Ok(user.image_version)
+    }
+
+    async fn replace_profile_image_name(
+        &self,
+        user_id: i32,
+        current: &str,
+        replacement: &str,
+    ) -> Result<Option<i32>, AppError> {
+        let mut tables = self.tables.write().unwrap();
+        let Some(user) = tables.user_mut(user_id) else {
+            return Ok(None);
+        };
+        if user.profile_image != current {
+            return Ok(None);
+        }
+        user.profile_image = replacement.to_string();
+        user.image_version += 1;
+
+        Ok(Some(user.image_version))
+    }
+
+    async fn find_recent_username_owner(
+        &self,
+        username: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Option<i32>, AppError> {
+        let tables = self.tables.read().unwrap();
+        let owner = tables
+            .username_history
+            .iter()
+            .filter(|history| history.username == username && history.changed_at >= since)
+            .max_by_key(|history| history.changed_at)
+            .map(|history| history.user_id);
+
+        Ok(owner)
+    }
+
+    async fn update_username(
+        &self,
+        user_id: i32,
+        old_username: &str,
+        new_username: &str,
+        changed_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let mut tables = self.tables.write().unwrap();
+        tables.username_history.push(StoredUsernameHistory {
+            user_id,
+            username: old_username.to_string(),
+            changed_at,
+        });
+        if let Some(user) = tables.user_mut(user_id) {
+            user.username = new_username.to_string();
+        }
+
+        Ok(())
+    }
+
+    async fn update_password(&self, user_id: i32, password: &str) -> Result<(), AppError> {
+        if let Some(user) = self.tables.write().unwrap().user_mut(user_id) {
+            user.password = password.to_string();
+            user.password_reset_required = false;
+        }
+
+        Ok(())
+    }
+
+    async fn require_password_reset(&self, user_id: i32) -> Result<(), AppError> {
+        if let Some(user) = self.tables.write().unwrap().user_mut(user_id) {
+            user.password_reset_required = true;
+        }
+
+        Ok(())
+    }
+
+    async fn update_user_role(&self, user_id: i32, role: Role) -> Result<(), AppError> {
+        if let Some(user) = self.tables.write().unwrap().user_mut(user_id) {
+            user.role = role;
+        }
+
+        Ok(())
+    }
+
+    async fn deactivate_user(&self, user_id: i32) -> Result<(), AppError> {
+        if let Some(user) = self.tables.write().unwrap().user_mut(user_id) {
+            user.is_active = false;
+        }
+
+        Ok(())
+    }
+
+    // ディスパッチャーの組織はユーザーの組織と同じものとして扱うため、ユーザーだけを更新する
+    async fn update_user_organization(
+        &self,
+        user_id: i32,
+        organization_id: i32,
+    ) -> Result<(), AppError> {
+        if let Some(user) = self.tables.write().unwrap().user_mut(user_id) {
+            user.organization_id = organization_id;
+        }
+
+        Ok(())
+    }
+
+    async fn update_totp_secret(
+        &self,
+        user_id: i32,
+        totp_secret: Option<&str>,
+    ) -> Result<(), AppError> {
+        if let Some(user) = self.tables.write().unwrap().user_mut(user_id) {
+            user.totp_secret = totp_secret.map(str::to_string);
+            user.totp_enabled = false;
+        }
+
+        Ok(())
+    }
+
+    async fn enable_totp(&self, user_id: i32) -> Result<(), AppError> {
+        if let Some(user) = self.tables.write().unwrap().user_mut(user_id) {
+            if user.totp_secret.is_some() {
+                user.totp_enabled = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn replace_totp_backup_codes(
+        &self,
+        user_id: i32,
+        code_hashes: &[String],
+    ) -> Result<(), AppError> {
+        let mut tables = self.tables.write().unwrap();
+        tables
+            .totp_backup_codes
+            .retain(|_, stored| stored.user_id != user_id);
+        for code_hash in code_hashes {
+            let id = tables.next_id();
+            tables.totp_backup_codes.insert(
+                id,
+                StoredBackupCode {
+                    user_id,
+                    code: TotpBackupCode {
+                        id,
+                        code_hash: code_hash.clone(),
+                    },
+                    used: false,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn find_unused_totp_backup_codes(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<TotpBackupCode>, AppError> {
+        let tables = self.tables.read().unwrap();
+        let mut codes: Vec<TotpBackupCode> = tables
+            .totp_backup_codes
+            .values()
+            .filter(|stored| stored.user_id == user_id && !stored.used)
+            .map(|stored| stored.code.clone())
+            .collect();
+        codes.sort_by_key(|code| code.id);
+
+        Ok(codes)
+    }
+
+    async fn mark_totp_backup_code_used(&self, id: i32) -> Result<bool, AppError> {
+        let mut tables = self.tables.write().unwrap();
+        match tables.totp_backup_codes.get_mut(&id) {
+            Some(stored) if !stored.used => {
+                stored.used = true;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn find_totp_locked_until(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<DateTime<Utc>>, AppError> {
+        let tables = self.tables.read().unwrap();
+        Ok(tables
+            .totp_guards
+            .get(&user_id)
+            .and_then(|guard| guard.locked_until))
+    }
+
+    async fn record_totp_failure(
+        &self,
+        user_id: i32,
+        max_attempts: i32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let mut tables = self.tables.write().unwrap();
+        if !tables.users.contains_key(&user_id) {
+            return Ok(());
+        }
+        let guard = tables.totp_guards.entry(user_id).or_default();
+        if guard.failed_attempts + 1 >= max_attempts {
+            guard.locked_until = Some(locked_until);
+            guard.failed_attempts = 0;
+        } else {
+            guard.failed_attempts += 1;
+        }
+        Ok(())
+    }
+
+    async fn accept_totp_step(&self, user_id: i32, step: i64) -> Result<bool, AppError> {
+        let mut tables = self.tables.write().unwrap();
+        if !tables.users.contains_key(&user_id) {
+            return Ok(false);
+        }
+        let guard = tables.totp_guards.entry(user_id).or_default();
+        if guard.last_step.is_some_and(|last_step| last_step >= step) {
+            return Ok(false);
+        }
+        guard.last_step = Some(step);
+        guard.failed_attempts = 0;
+        Ok(true)
+    }
+
+    async fn reset_totp_failures(&self, user_id: i32) -> Result<(), AppError> {
+        let mut tables = self.tables.write().unwrap();
+        if let Some(guard) = tables.totp_guards.get_mut(&user_id) {
+            guard.failed_attempts = 0;
+        }
+        Ok(())
+    }
+
+    async fn find_user_id_by_external_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<i32>, AppError> {
+        let tables = self.tables.read().unwrap();
+        Ok(tables
+            .external_identities
+            .get(&(provider.to_string(), subject.to_string()))
+            .copied())
+    }
+
+    async fn create_external_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+        user_id: i32,
+    ) -> Result<(), AppError> {
+        let mut tables = self.tables.write().unwrap();
+        let key = (provider.to_string(), subject.to_string());
+        // MySQL 側の UNIQUE (provider, subject) 制約に合わせる
+        if tables.external_identities.contains_key(&key) {
+            return Err(AppError::Conflict);
+        }
+        tables.external_identities.insert(key, user_id);
+
+        Ok(())
+    }
+
+    async fn create_session(
+        &self,
+        user_id: i32,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+        client: &SessionClient,
+    ) -> Result<(), AppError> {
+        self.tables.write().unwrap().insert_session(
+            user_id,
+            session_token,
+            expires_at,
+            None,
+            client,
+        );
+
+        Ok(())
+    }
+
+    async fn create_impersonation_session(
+        &self,
+        user_id: i32,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+        impersonator_user_id: i32,
+        client: &SessionClient,
+    ) -> Result<(), AppError> {
+        self.tables.write().unwrap().insert_session(
+            user_id,
+            session_token,
+            expires_at,
+            Some(impersonator_user_id),
+            client,
+        );
+
+        Ok(())
+    }
+
+    async fn update_session_activities(
+        &self,
+        activities: &[SessionActivity],
+    ) -> Result<(), AppError> {
+        let mut tables = self.tables.write().unwrap();
+        for activity in activities {
+            if let Some(session) = tables
+                .sessions
+                .values_mut()
+                .find(|session| session.session_token == activity.session_token)
+            {
+                if let Some(ip) = &activity.client.ip {
+                    session.client_ip = Some(ip.clone());
+                }
+                if let Some(user_agent) = &activity.client.user_agent {
+                    session.user_agent = Some(user_agent.clone());
+                }
+                session.last_seen_at = session.last_seen_at.max(Some(activity.last_seen_at));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn update_session_expires_at(
+        &self,
+        session_token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let mut tables = self.tables.write().unwrap();
+        tables
+            .sessions
+            .values_mut()
+            .filter(|session| session.session_token == session_token)
+            .for_each(|session| session.expires_at = expires_at);
+
+        Ok(())
+    }
+
+    async fn delete_session(&self, session_token: &str) -> Result<(), AppError> {
+        self.tables
+            .write()
+            .unwrap()
+            .sessions
+            .retain(|_, session| session.session_token != session_token);
+
+        Ok(())
+    }
+
+    async fn delete_other_sessions_by_user_id(
+        &self,
+        user_id: i32,
+        keep_session_token: &str,
+    ) -> Result<(), AppError> {
+        self.tables.write().unwrap().sessions.retain(|_, session| {
+            session.user_id != user_id || session.session_token == keep_session_token
+        });
+
+        Ok(())
+    }
+
+    async fn delete_expired_sessions(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<u64, AppError> {
+        let mut tables = self.tables.write().unwrap();
+        let expired: Vec<i32> = tables
+            .sessions
+            .values()
+            .filter(|session| {
+                session.expires_at <= now
+                    || !session.is_valid
+                    || !tables.users.contains_key(&session.user_id)
+            })
+            .map(|session| session.id)
+            .take(limit as usize)
+            .collect();
+        for id in &expired {
+            tables.sessions.remove(id);
+        }
+
+        Ok(expired.len() as u64)
+    }
+
+    async fn find_session_by_session_token(
+        &self,
+        session_token: &str,
+    ) -> Result<Session, AppError> {
+        let tables = self.tables.read().unwrap();
+        match tables
+            .sessions
+            .values()
+            .find(|session| session.session_token == session_token)
+        {
+            Some(session) => Ok(session.clone()),
+            None => Err(sqlx::Error::RowNotFound.into()),
+        }
+    }
+
+    async fn find_sessions_by_user_id(
+        &self,
+        user_id: i32,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, AppError> {
+        let tables = self.tables.read().unwrap();
+        let mut sessions: Vec<Session> = tables
+            .sessions
+            .values()
+            .filter(|session| {
+                session.user_id == user_id && session.is_valid && session.expires_at > now
+            })
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse((session.created_at, session.id)));
+
+        Ok(sessions)
+    }
+
+    async fn find_impersonation_sessions(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Session>, AppError> {
+        let tables = self.tables.read().unwrap();
+        let mut sessions: Vec<Session> = tables
+            .sessions
+            .values()
+            .filter(|session| {
+                session.is_impersonation() && session.is_valid && session.expires_at > now
+            })
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse((session.created_at, session.id)));
+
+        Ok(sessions)
+    }
+
+    async fn find_recent_session_tokens(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<String>, AppError> {
+        let tables = self.tables.read().unwrap();
+        let mut sessions: Vec<&Session> = tables
+            .sessions
+            .values()
+            .filter(|session| {
+                session.is_valid
+                    && session.expires_at > now
+                    && tables
+                        .users
+                        .get(&session.user_id)
+                        .is_some_and(|user| user.is_active)
+            })
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.expires_at));
+
+        Ok(sessions
+            .into_iter()
+            .take(limit as usize)
+            .map(|session| session.session_token.clone())
+            .collect())
+    }
+
+    async fn create_email_verification_token(
+        &self,
+        user_id: i32,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        self.tables
+            .write()
+            .unwrap()
+            .email_verification_tokens
+            .insert(token_hash.to_string(), (user_id, expires_at));
+
+        Ok(())
+    }
+
+    async fn verify_email(
+        &self,
+        token_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<i32>, AppError> {
+        let mut tables = self.tables.write().unwrap();
+        let user_id = match tables.email_verification_tokens.get(token_hash) {
+            Some((user_id, expires_at)) if *expires_at > now => *user_id,
+            _ => return Ok(None),
+        };
+
+        tables
+            .email_verification_tokens
+            .retain(|_, (owner_id, _)| *owner_id != user_id);
+        if let Some(user) = tables.user_mut(user_id) {
+            user.email_verified = true;
+        }
+
+        Ok(Some(user_id))
+    }
+}
@@ -1,4 +1,37 @@
+pub mod api_key_repository;
+pub mod audit_log_repository;
 pub mod auth_repository;
+pub mod auth_repository_backend;
+#[cfg(test)]
+pub(crate) mod auth_repository_contract_tests;
+pub mod backup_repository;
+pub mod bulk_insert;
+pub mod cached_auth_repository;
+pub mod chat_repository;
+pub mod client_repository;
+pub mod db_error;
+pub mod eta_repository;
+pub mod export_repository;
+pub mod feature_flag_repository;
+pub mod fixture_repository;
+pub mod geocoding_repository;
+pub mod graphql_repository;
+pub mod invite_code_repository;
+pub mod leaderboard_repository;
+pub mod login_attempt_repository;
 pub mod map_repository;
+pub mod memory_auth_repository;
+pub mod notification_repository;
+pub mod order_note_repository;
 pub mod order_repository;
+pub mod organization_repository;
+pub mod profile_repository;
+pub mod quota_repository;
+pub mod report_repository;
+pub mod retention_repository;
+pub mod stats_repository;
+pub mod sync_repository;
 pub mod tow_truck_repository;
+pub mod user_import_repository;
+pub mod vehicle_repository;
+pub mod webhook_repository;
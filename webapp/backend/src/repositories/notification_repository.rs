@@ -0,0 +1,258 @@
+use crate::domains::notification_service::NotificationRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::notification::{
+    NewNotificationDeadLetter, NotificationDeadLetter, NotificationPreferences,
+    NotificationRecipient, PushProvider, PushToken,
+};
+use crate::models::role::Role;
+use crate::utils::sha256_hex;
+
+#[derive(Debug, Clone)]
+pub struct NotificationRepositoryImpl {
+    pools: DbPools,
+}
+
+impl NotificationRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        NotificationRepositoryImpl { pools }
+    }
+}
+
+impl NotificationRepository for NotificationRepositoryImpl {
+    async fn find_preferences(
+        &self,
+        user_id: i32,
+    ) -> Result<Option<NotificationPreferences>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("notification_repository.find_preferences");
+        let preferences = sqlx::query_as::<_, NotificationPreferences>(
+            "SELECT user_id, websocket, email, webhook_url FROM notification_preferences WHERE user_id = ?",
+        )
+        .bind(user_id)
+        .fetch_optional(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(preferences)
+    }
+
+    async fn upsert_preferences(
+        &self,
+        preferences: &NotificationPreferences,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("notification_repository.upsert_preferences");
+        sqlx::query(
+            "INSERT INTO notification_preferences (user_id, websocket, email, webhook_url)
+            VALUES (?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+                websocket = VALUES(websocket),
+                email = VALUES(email),
+                webhook_url = VALUES(webhook_url)",
+        )
+        .bind(preferences.user_id)
+        .bind(preferences.websocket)
+        .bind(preferences.email)
+        .bind(&preferences.webhook_url)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_recipients(
+        &self,
+        user_ids: &[i32],
+    ) -> Result<Vec<NotificationRecipient>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("notification_repository.find_recipients");
+        if user_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let query = format!(
+            "SELECT
+                u.id AS user_id,
+                COALESCE(p.websocket, TRUE) AS websocket,
+                COALESCE(p.email, FALSE) AS email,
+                p.webhook_url,
+                u.email AS email_address
+            FROM
+                users u
+            LEFT JOIN
+                notification_preferences p
+            ON
+                p.user_id = u.id
+            WHERE
+                u.id IN ({})",
+            vec!["?"; user_ids.len()].join(", ")
+        );
+        let mut select = sqlx::query_as::<_, NotificationRecipient>(&query);
+        for user_id in user_ids {
+            select = select.bind(user_id);
+        }
+        let recipients = select
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?;
+
+        Ok(recipients)
+    }
+
+    async fn find_order_participant_ids(&self, order_id: i32) -> Result<Vec<i32>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("notification_repository.find_order_participant_ids");
+        let participants = sqlx::query_as::<_, (i32, Option<i32>, Option<i32>)>(
+            "SELECT
+                o.client_id,
+                d.user_id,
+                t.driver_id
+            FROM
+                orders o
+            LEFT JOIN
+                dispatchers d
+            ON
+                d.id = o.dispatcher_id
+            LEFT JOIN
+                tow_trucks t
+            ON
+                t.id = o.tow_truck_id
+            WHERE
+                o.id = ?",
+        )
+        .bind(order_id)
+        // 発行直後のイベントから呼ばれるため、レプリカ遅延の影響を受けないようプライマリから読む
+        .fetch_optional(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(match participants {
+            Some((client_id, dispatcher_user_id, driver_id)) => {
+                [Some(client_id), dispatcher_user_id, driver_id]
+                    .into_iter()
+                    .flatten()
+                    .collect()
+            }
+            None => Vec::new(),
+        })
+    }
+
+    async fn find_administrator_ids(&self) -> Result<Vec<i32>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("notification_repository.find_administrator_ids");
+        let user_ids =
+            sqlx::query_scalar("SELECT id FROM users WHERE role = ? AND is_active = TRUE")
+                .bind(Role::Admin)
+                .fetch_all(&mut *self.pools.acquire_replica().await?)
+                .await?;
+
+        Ok(user_ids)
+    }
+
+    async fn create_dead_letter(
+        &self,
+        dead_letter: &NewNotificationDeadLetter,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("notification_repository.create_dead_letter");
+        sqlx::query(
+            "INSERT INTO notification_dead_letters (user_id, channel, target, payload, error, attempts)
+            VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(dead_letter.user_id)
+        .bind(dead_letter.channel)
+        .bind(&dead_letter.target)
+        .bind(&dead_letter.payload)
+        .bind(&dead_letter.error)
+        .bind(dead_letter.attempts)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_dead_letters(&self, limit: i32) -> Result<Vec<NotificationDeadLetter>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("notification_repository.find_dead_letters");
+        let dead_letters = sqlx::query_as::<_, NotificationDeadLetter>(
+            "SELECT id, user_id, channel, target, payload, error, attempts, created_at
+            FROM notification_dead_letters
+            ORDER BY id DESC
+            LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(dead_letters)
+    }
+
+    async fn find_push_tokens(&self, user_ids: &[i32]) -> Result<Vec<PushToken>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("notification_repository.find_push_tokens");
+        if user_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let query = format!(
+            "SELECT user_id, provider, token FROM push_tokens WHERE user_id IN ({})",
+            vec!["?"; user_ids.len()].join(", ")
+        );
+        let mut select = sqlx::query_as::<_, PushToken>(&query);
+        for user_id in user_ids {
+            select = select.bind(user_id);
+        }
+        let push_tokens = select
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?;
+
+        Ok(push_tokens)
+    }
+
+    async fn upsert_push_token(
+        &self,
+        user_id: i32,
+        provider: PushProvider,
+        token: &str,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("notification_repository.upsert_push_token");
+        sqlx::query(
+            "INSERT INTO push_tokens (user_id, provider, token, token_hash)
+            VALUES (?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+                user_id = VALUES(user_id),
+                provider = VALUES(provider),
+                updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(user_id)
+        .bind(provider.as_str())
+        .bind(token)
+        .bind(sha256_hex(token))
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_push_token(&self, token: &str, user_id: Option<i32>) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("notification_repository.delete_push_token");
+        sqlx::query("DELETE FROM push_tokens WHERE token_hash = ? AND (? IS NULL OR user_id = ?)")
+            .bind(sha256_hex(token))
+            .bind(user_id)
+            .bind(user_id)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+}
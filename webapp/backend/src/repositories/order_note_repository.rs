@@ -0,0 +1,236 @@
+use crate::domains::order_note_service::OrderNoteRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::order::{OrderAttachment, OrderNote};
+
+#[derive(Debug)]
+pub struct OrderNoteRepositoryImpl {
+    pools: DbPools,
+}
+
+impl OrderNoteRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        OrderNoteRepositoryImpl { pools }
+    }
+}
+
+impl OrderNoteRepository for OrderNoteRepositoryImpl {
+    async fn find_order_participant_ids(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<Option<Vec<i32>>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_note_repository.find_order_participant_ids");
+        let participants = sqlx::query_as::<_, (i32, Option<i32>, Option<i32>)>(
+            "SELECT
+                o.client_id,
+                d.user_id,
+                t.driver_id
+            FROM
+                orders o
+            LEFT JOIN
+                dispatchers d
+            ON
+                d.id = o.dispatcher_id
+            LEFT JOIN
+                tow_trucks t
+            ON
+                t.id = o.tow_truck_id
+            WHERE
+                o.id = ? AND o.organization_id = ?",
+        )
+        .bind(order_id)
+        .bind(organization_id)
+        .fetch_optional(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(
+            participants.map(|(client_id, dispatcher_user_id, driver_id)| {
+                [Some(client_id), dispatcher_user_id, driver_id]
+                    .into_iter()
+                    .flatten()
+                    .collect()
+            }),
+        )
+    }
+
+    async fn create_note(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        author_id: i32,
+        body: &str,
+    ) -> Result<OrderNote, AppError> {
+        let _timer = self.pools.query_timer("order_note_repository.create_note");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        let result = sqlx::query(
+            "INSERT INTO order_notes (order_id, organization_id, author_id, body) VALUES (?, ?, ?, ?)",
+        )
+        .bind(order_id)
+        .bind(organization_id)
+        .bind(author_id)
+        .bind(body)
+        .execute(&mut tx)
+        .await?;
+        let note = sqlx::query_as::<_, OrderNote>(
+            "SELECT id, order_id, author_id, body, created_at FROM order_notes WHERE id = ?",
+        )
+        .bind(result.last_insert_id() as i32)
+        .fetch_one(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(note)
+    }
+
+    async fn find_notes(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<Vec<OrderNote>, AppError> {
+        let _timer = self.pools.query_timer("order_note_repository.find_notes");
+        let notes = sqlx::query_as::<_, OrderNote>(
+            "SELECT
+                id,
+                order_id,
+                author_id,
+                body,
+                created_at
+            FROM
+                order_notes
+            WHERE
+                order_id = ? AND organization_id = ?
+            ORDER BY
+                id",
+        )
+        .bind(order_id)
+        .bind(organization_id)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(notes)
+    }
+
+    async fn count_attachments(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<i64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_note_repository.count_attachments");
+        // 追加の直前に数えるため、プライマリから読む
+        let (count,) = sqlx::query_as::<_, (i64,)>(
+            "SELECT COUNT(*) FROM order_attachments WHERE order_id = ? AND organization_id = ?",
+        )
+        .bind(order_id)
+        .bind(organization_id)
+        .fetch_one(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn create_attachment(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        uploaded_by: i32,
+        name: &str,
+        content_type: &str,
+        bytes: i64,
+    ) -> Result<OrderAttachment, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_note_repository.create_attachment");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        let result = sqlx::query(
+            "INSERT INTO order_attachments (order_id, organization_id, uploaded_by, name, content_type, bytes) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(order_id)
+        .bind(organization_id)
+        .bind(uploaded_by)
+        .bind(name)
+        .bind(content_type)
+        .bind(bytes)
+        .execute(&mut tx)
+        .await?;
+        let attachment = sqlx::query_as::<_, OrderAttachment>(
+            "SELECT id, order_id, uploaded_by, name, content_type, bytes, created_at FROM order_attachments WHERE id = ?",
+        )
+        .bind(result.last_insert_id() as i32)
+        .fetch_one(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(attachment)
+    }
+
+    async fn find_attachments(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<Vec<OrderAttachment>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_note_repository.find_attachments");
+        let attachments = sqlx::query_as::<_, OrderAttachment>(
+            "SELECT
+                id,
+                order_id,
+                uploaded_by,
+                name,
+                content_type,
+                bytes,
+                created_at
+            FROM
+                order_attachments
+            WHERE
+                order_id = ? AND organization_id = ?
+            ORDER BY
+                id",
+        )
+        .bind(order_id)
+        .bind(organization_id)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(attachments)
+    }
+
+    async fn find_attachment(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        attachment_id: i32,
+    ) -> Result<Option<OrderAttachment>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_note_repository.find_attachment");
+        let attachment = sqlx::query_as::<_, OrderAttachment>(
+            "SELECT
+                id,
+                order_id,
+                uploaded_by,
+                name,
+                content_type,
+                bytes,
+                created_at
+            FROM
+                order_attachments
+            WHERE
+                id = ? AND order_id = ? AND organization_id = ?",
+        )
+        .bind(attachment_id)
+        .bind(order_id)
+        .bind(organization_id)
+        .fetch_optional(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(attachment)
+    }
+}
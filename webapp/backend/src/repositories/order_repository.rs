@@ -1,49 +1,119 @@
 use crate::domains::order_service::OrderRepository;
 use crate::errors::AppError;
-use crate::models::order::Order;
+use crate::infrastructure::db::DbPools;
+use crate::models::order::{
+    NewOrder, Order, OrderCompletion, OrderHandover, OrderPriority, OrderSearchCriteria,
+    OrderStatus, ScheduledOrder,
+};
+use crate::models::vehicle::VehicleStatus;
+use crate::repositories::bulk_insert;
 use chrono::{DateTime, Utc};
-use sqlx::mysql::MySqlPool;
+use sqlx::mysql::MySqlArguments;
+use sqlx::Arguments;
 
 #[derive(Debug)]
 pub struct OrderRepositoryImpl {
-    pool: MySqlPool,
+    pools: DbPools,
 }
 
 impl OrderRepositoryImpl {
-    pub fn new(pool: MySqlPool) -> Self {
-        OrderRepositoryImpl { pool }
+    pub fn new(pools: DbPools) -> Self {
+        OrderRepositoryImpl { pools }
     }
 }
 
 impl OrderRepository for OrderRepositoryImpl {
-    async fn find_order_by_id(&self, id: i32) -> Result<Order, AppError> {
-        let order = sqlx::query_as::<_, Order>(
-            "SELECT 
-                *
-            FROM
-                orders 
-            WHERE
-                id = ?",
-        )
-        .bind(id)
-        .fetch_one(&self.pool)
-        .await?;
+    async fn find_order_by_id(&self, organization_id: i32, id: i32) -> Result<Order, AppError> {
+        let _timer = self.pools.query_timer("order_repository.find_order_by_id");
+        let order = self
+            .pools
+            .retry("order_repository.find_order_by_id", || async move {
+                sqlx::query_as::<_, Order>(
+                    "SELECT 
+                        *
+                    FROM
+                        orders 
+                    WHERE
+                        id = ? AND organization_id = ?",
+                )
+                .bind(id)
+                .bind(organization_id)
+                .fetch_one(&mut *self.pools.acquire_replica().await?)
+                .await
+            })
+            .await?;
 
         Ok(order)
     }
 
-    async fn update_order_status(&self, order_id: i32, status: &str) -> Result<(), AppError> {
-        sqlx::query("UPDATE orders SET status = ? WHERE id = ?")
-            .bind(status)
-            .bind(order_id)
-            .execute(&self.pool)
+    async fn update_order_status(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        status: &str,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_repository.update_order_status");
+        sqlx::query(
+            "UPDATE orders SET status = ?, version = version + 1 WHERE id = ? AND organization_id = ?",
+        )
+        .bind(status)
+        .bind(order_id)
+        .bind(organization_id)
+        .execute(&mut *self.pools.acquire_primary().await?)
             .await?;
 
         Ok(())
     }
 
+    async fn complete_order(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        completed_time: DateTime<Utc>,
+    ) -> Result<Option<OrderCompletion>, AppError> {
+        let _timer = self.pools.query_timer("order_repository.complete_order");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+
+        let result = sqlx::query(
+            "UPDATE orders SET status = ?, completed_time = COALESCE(completed_time, ?), version = version + 1 WHERE id = ? AND organization_id = ? AND status <> ?",
+        )
+        .bind(OrderStatus::Completed.as_str())
+        .bind(completed_time)
+        .bind(order_id)
+        .bind(organization_id)
+        .bind(OrderStatus::Completed.as_str())
+        .execute(&mut tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let completion = sqlx::query_as::<_, OrderCompletion>(
+            "SELECT
+                id,
+                dispatcher_id,
+                order_time,
+                completed_time,
+                quoted_eta_minutes
+            FROM
+                orders
+            WHERE
+                id = ?",
+        )
+        .bind(order_id)
+        .fetch_one(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(Some(completion))
+    }
+
     async fn get_paginated_orders(
         &self,
+        organization_id: i32,
         page: i32,
         page_size: i32,
         sort_by: Option<String>,
@@ -51,13 +121,21 @@ impl OrderRepository for OrderRepositoryImpl {
         status: Option<String>,
         area: Option<i32>,
     ) -> Result<Vec<Order>, AppError> {
+        let timer = self
+            .pools
+            .query_timer("order_repository.get_paginated_orders");
         let offset = page * page_size;
+        // 未割り当ての注文は、並べ替えを指定しなければ処理する順 (優先度と待ち時間の順) に並べる
         let order_clause = format!(
             "ORDER BY {} {}",
             match sort_by.as_deref() {
                 Some("car_value") => "o.car_value",
                 Some("status") => "o.status",
                 Some("order_time") => "o.order_time",
+                Some("priority") => "o.queue_time",
+                None if status.as_deref() == Some(OrderStatus::Pending.as_str()) => {
+                    "o.queue_time"
+                }
                 _ => "o.order_time",
             },
             match sort_order.as_deref() {
@@ -67,11 +145,13 @@ impl OrderRepository for OrderRepositoryImpl {
             }
         );
 
-        let where_clause = match (status.clone(), area) {
-            (Some(_), Some(_)) => "WHERE o.status = ? AND n.area_id = ?".to_string(),
-            (None, Some(_)) => "WHERE n.area_id = ?".to_string(),
-            (Some(_), None) => "WHERE o.status = ?".to_string(),
-            _ => "".to_string(),
+        let where_clause = match (&status, area) {
+            (Some(_), Some(_)) => {
+                "WHERE o.organization_id = ? AND o.status = ? AND n.area_id = ?".to_string()
+            }
+            (None, Some(_)) => "WHERE o.organization_id = ? AND n.area_id = ?".to_string(),
+            (Some(_), None) => "WHERE o.organization_id = ? AND o.status = ?".to_string(),
+            _ => "WHERE o.organization_id = ?".to_string(),
         };
 
         let sql = format!(
@@ -84,7 +164,9 @@ impl OrderRepository for OrderRepositoryImpl {
                 o.node_id, 
                 o.car_value, 
                 o.order_time, 
-                o.completed_time
+                o.completed_time,
+                o.version,
+                o.priority
             FROM
                 orders o
             JOIN
@@ -98,91 +180,769 @@ impl OrderRepository for OrderRepositoryImpl {
             where_clause, order_clause
         );
 
-        let orders = match (status, area) {
-            (Some(status), Some(area)) => {
-                sqlx::query_as::<_, Order>(&sql)
-                    .bind(status)
-                    .bind(area)
-                    .bind(page_size)
-                    .bind(offset)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-            (None, Some(area)) => {
-                sqlx::query_as::<_, Order>(&sql)
-                    .bind(area)
-                    .bind(page_size)
-                    .bind(offset)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-            (Some(status), None) => {
-                sqlx::query_as::<_, Order>(&sql)
-                    .bind(status)
-                    .bind(page_size)
-                    .bind(offset)
-                    .fetch_all(&self.pool)
-                    .await?
-            }
-            _ => {
-                sqlx::query_as::<_, Order>(&sql)
-                    .bind(page_size)
-                    .bind(offset)
-                    .fetch_all(&self.pool)
-                    .await?
+        let mut arguments = MySqlArguments::default();
+        arguments.add(organization_id);
+        if let Some(status) = status {
+            arguments.add(status);
+        }
+        if let Some(area) = area {
+            arguments.add(area);
+        }
+        arguments.add(page_size);
+        arguments.add(offset);
+        let _timer = timer.explain_if_slow(&self.pools.replica, &sql, &arguments);
+        let orders = sqlx::query_as_with::<_, Order, _>(&sql, arguments)
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?;
+
+        Ok(orders)
+    }
+
+    // 各条件はインデックスで絞り込める形にする。
+    // 名前と地点は全文インデックスで ID を引いてから IN で結合し、注文 ID の前方一致は主キーの範囲検索にする
+    async fn search_orders(&self, criteria: &OrderSearchCriteria) -> Result<Vec<Order>, AppError> {
+        let timer = self.pools.query_timer("order_repository.search_orders");
+        // 検索条件の組み合わせによってプレースホルダの数と型が変わるため、条件と同時にバインドする値を追加する
+        let mut conditions = vec!["o.organization_id = ?".to_string()];
+        let mut arguments = MySqlArguments::default();
+        arguments.add(criteria.organization_id);
+
+        if let Some(client_name) = &criteria.client_name {
+            conditions.push(
+                "o.client_id IN (SELECT id FROM users WHERE MATCH (username) AGAINST (? IN BOOLEAN MODE))"
+                    .to_string(),
+            );
+            arguments.add(client_name.clone());
+        }
+        if let Some(address) = &criteria.address {
+            conditions.push(
+                "o.node_id IN (SELECT id FROM nodes WHERE MATCH (name) AGAINST (? IN BOOLEAN MODE))"
+                    .to_string(),
+            );
+            arguments.add(address.clone());
+        }
+        if !criteria.id_ranges.is_empty() {
+            conditions.push(format!(
+                "({})",
+                vec!["o.id BETWEEN ? AND ?"; criteria.id_ranges.len()].join(" OR ")
+            ));
+            for (start, end) in &criteria.id_ranges {
+                arguments.add(*start);
+                arguments.add(*end);
             }
-        };
+        }
+        if let Some(status) = &criteria.status {
+            conditions.push("o.status = ?".to_string());
+            arguments.add(status.clone());
+        }
+        if let Some(area_id) = criteria.area_id {
+            conditions.push("n.area_id = ?".to_string());
+            arguments.add(area_id);
+        }
+        if let Some(ordered_from) = criteria.ordered_from {
+            conditions.push("o.order_time >= ?".to_string());
+            arguments.add(ordered_from);
+        }
+        if let Some(ordered_to) = criteria.ordered_to {
+            conditions.push("o.order_time <= ?".to_string());
+            arguments.add(ordered_to);
+        }
+        if let Some(min_car_value) = criteria.min_car_value {
+            conditions.push("o.car_value >= ?".to_string());
+            arguments.add(min_car_value);
+        }
+        if let Some(max_car_value) = criteria.max_car_value {
+            conditions.push("o.car_value <= ?".to_string());
+            arguments.add(max_car_value);
+        }
+
+        let where_clause = format!("WHERE {}", conditions.join(" AND "));
+        let sql = format!(
+            "SELECT
+                o.id,
+                o.client_id,
+                o.dispatcher_id,
+                o.tow_truck_id,
+                o.status,
+                o.node_id,
+                o.car_value,
+                o.order_time,
+                o.completed_time,
+                o.version,
+                o.priority
+            FROM
+                orders o
+            JOIN
+                nodes n
+            ON
+                o.node_id = n.id
+            {}
+            ORDER BY
+                o.order_time DESC, o.id DESC
+            LIMIT ?
+            OFFSET ?",
+            where_clause
+        );
+
+        arguments.add(criteria.limit);
+        arguments.add(criteria.offset);
+        let _timer = timer.explain_if_slow(&self.pools.replica, &sql, &arguments);
+        let orders = sqlx::query_as_with::<_, Order, _>(&sql, arguments)
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?;
+
+        Ok(orders)
+    }
+
+    async fn find_orders_by_dispatcher_id(
+        &self,
+        organization_id: i32,
+        dispatcher_id: i32,
+        status: &str,
+    ) -> Result<Vec<Order>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_repository.find_orders_by_dispatcher_id");
+        let orders = sqlx::query_as::<_, Order>(
+            "SELECT
+                id,
+                client_id,
+                dispatcher_id,
+                tow_truck_id,
+                status,
+                node_id,
+                car_value,
+                order_time,
+                completed_time,
+                version,
+                priority
+            FROM
+                orders
+            WHERE
+                dispatcher_id = ? AND status = ? AND organization_id = ?
+            ORDER BY
+                order_time",
+        )
+        .bind(dispatcher_id)
+        .bind(status)
+        .bind(organization_id)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(orders)
+    }
+
+    async fn find_recently_completed_orders(
+        &self,
+        organization_id: i32,
+        area_id: i32,
+        limit: i32,
+    ) -> Result<Vec<Order>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_repository.find_recently_completed_orders");
+        let orders = sqlx::query_as::<_, Order>(
+            "SELECT
+                o.id,
+                o.client_id,
+                o.dispatcher_id,
+                o.tow_truck_id,
+                o.status,
+                o.node_id,
+                o.car_value,
+                o.order_time,
+                o.completed_time,
+                o.version,
+                o.priority
+            FROM
+                orders o
+            JOIN
+                nodes n
+            ON
+                o.node_id = n.id
+            WHERE
+                o.organization_id = ? AND n.area_id = ? AND o.completed_time IS NOT NULL
+            ORDER BY
+                o.completed_time DESC
+            LIMIT ?",
+        )
+        .bind(organization_id)
+        .bind(area_id)
+        .bind(limit)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
 
         Ok(orders)
     }
 
     async fn create_order(
         &self,
+        organization_id: i32,
         client_id: i32,
         node_id: i32,
         car_value: f64,
     ) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO orders (client_id, node_id, status, car_value) VALUES (?, ?, 'pending', ?)")
-            .bind(client_id)
-            .bind(node_id)
-            .bind(car_value)
-            .execute(&self.pool)
-            .await?;
+        self.create_orders(&[NewOrder {
+            organization_id,
+            client_id,
+            node_id,
+            car_value,
+        }])
+        .await
+    }
+
+    async fn create_orders(&self, orders: &[NewOrder]) -> Result<(), AppError> {
+        if orders.is_empty() {
+            return Ok(());
+        }
+
+        let _timer = self.pools.query_timer("order_repository.create_orders");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        for chunk in orders.chunks(bulk_insert::rows_per_statement(5)) {
+            let query = format!(
+                "INSERT INTO orders (organization_id, client_id, node_id, status, car_value) VALUES {}",
+                bulk_insert::values_placeholders(5, chunk.len())
+            );
+            let mut insert = sqlx::query(&query);
+            for order in chunk {
+                insert = insert
+                    .bind(order.organization_id)
+                    .bind(order.client_id)
+                    .bind(order.node_id)
+                    .bind(OrderStatus::Pending.as_str())
+                    .bind(order.car_value);
+            }
+            insert.execute(&mut tx).await?;
+        }
+        tx.commit().await?;
 
         Ok(())
     }
 
-    async fn update_order_dispatched(
+    async fn create_quoted_order(
         &self,
-        id: i32,
+        organization_id: i32,
+        client_id: i32,
+        node_id: i32,
+        dropoff_node_id: i32,
+        car_value: f64,
+        quoted_price: i32,
+        quoted_eta_minutes: Option<i32>,
+    ) -> Result<i32, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_repository.create_quoted_order");
+        // 顧客台帳に結び付いたアカウントからの注文は、その顧客の注文として記録する
+        let result = sqlx::query(
+            "INSERT INTO orders (organization_id, client_id, customer_id, node_id, dropoff_node_id, status, car_value, quoted_price, quoted_eta_minutes)
+            VALUES (?, ?, (SELECT id FROM clients WHERE user_id = ?), ?, ?, 'pending', ?, ?, ?)",
+        )
+        .bind(organization_id)
+        .bind(client_id)
+        .bind(client_id)
+        .bind(node_id)
+        .bind(dropoff_node_id)
+        .bind(car_value)
+        .bind(quoted_price)
+        .bind(quoted_eta_minutes)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.last_insert_id() as i32)
+    }
+
+    // 未割り当ての注文だけを更新し、他のディスパッチャーが先に割り当てていた場合は false を返す。
+    // expected_version を指定した場合は、読み込んだ時点から注文が更新されていないことも確認する
+    async fn dispatch_order(
+        &self,
+        organization_id: i32,
+        order_id: i32,
         dispatcher_id: i32,
         tow_truck_id: i32,
-    ) -> Result<(), AppError> {
-        sqlx::query(
-            "UPDATE orders SET dispatcher_id = ?, tow_truck_id = ?, status = 'dispatched' WHERE id = ?",
+        order_time: DateTime<Utc>,
+        dispatched_at: DateTime<Utc>,
+        expected_version: Option<i32>,
+    ) -> Result<bool, AppError> {
+        let _timer = self.pools.query_timer("order_repository.dispatch_order");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+
+        let result = sqlx::query(
+            "UPDATE orders
+            SET dispatcher_id = ?, tow_truck_id = ?, status = ?, dispatched_at = ?, version = version + 1
+            WHERE id = ? AND organization_id = ? AND status = ? AND (? IS NULL OR version = ?)
+                AND ? IN (SELECT id FROM dispatchers WHERE organization_id = ?)
+                AND ? IN (SELECT t.id FROM tow_trucks t JOIN users u ON u.id = t.driver_id WHERE u.organization_id = ?)",
         )
         .bind(dispatcher_id)
         .bind(tow_truck_id)
-        .bind(id)
-        .execute(&self.pool)
+        .bind(OrderStatus::Dispatched.as_str())
+        .bind(dispatched_at)
+        .bind(order_id)
+        .bind(organization_id)
+        .bind(OrderStatus::Pending.as_str())
+        .bind(expected_version)
+        .bind(expected_version)
+        .bind(dispatcher_id)
+        .bind(organization_id)
+        .bind(tow_truck_id)
+        .bind(organization_id)
+        .execute(&mut tx)
         .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
 
-        Ok(())
+        // 車両がすでに他の注文に割り当てられている場合は一意制約に違反する
+        if sqlx::query(
+            "INSERT INTO completed_orders (order_id, tow_truck_id, completed_time) VALUES (?, ?, ?)",
+        )
+        .bind(order_id)
+        .bind(tow_truck_id)
+        .bind(order_time)
+        .execute(&mut tx)
+        .await
+        .is_err()
+        {
+            return Err(AppError::BadRequest);
+        }
+
+        tx.commit().await?;
+
+        Ok(true)
     }
 
-    async fn create_completed_order(
+    async fn cancel_order(
         &self,
+        organization_id: i32,
         order_id: i32,
-        tow_truck_id: i32,
-        completed_time: DateTime<Utc>,
-    ) -> Result<(), AppError> {
-        sqlx::query("INSERT INTO completed_orders (order_id, tow_truck_id, completed_time) VALUES (?, ?, ?)")
+        tow_truck_id: Option<i32>,
+        reason: &str,
+        cancelled_at: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let _timer = self.pools.query_timer("order_repository.cancel_order");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+
+        let result = sqlx::query(
+            "UPDATE orders SET status = ?, cancel_reason = ?, cancelled_at = ?, version = version + 1 WHERE id = ? AND organization_id = ? AND status IN (?, ?, ?)",
+        )
+        .bind(OrderStatus::Cancelled.as_str())
+        .bind(reason)
+        .bind(cancelled_at)
+        .bind(order_id)
+        .bind(organization_id)
+        .bind(OrderStatus::Scheduled.as_str())
+        .bind(OrderStatus::Pending.as_str())
+        .bind(OrderStatus::Dispatched.as_str())
+        .execute(&mut tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        // 配車時に登録した completed_orders は車両ごとに一意のため、削除しないと車両を再び割り当てられない
+        sqlx::query("DELETE FROM completed_orders WHERE order_id = ?")
             .bind(order_id)
-            .bind(tow_truck_id)
-            .bind(completed_time)
-            .execute(&self.pool)
+            .execute(&mut tx)
             .await?;
+        if let Some(tow_truck_id) = tow_truck_id {
+            sqlx::query("UPDATE tow_trucks SET status = ? WHERE id = ?")
+                .bind(VehicleStatus::Available.as_str())
+                .bind(tow_truck_id)
+                .execute(&mut tx)
+                .await?;
+        }
 
-        Ok(())
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    async fn count_pending_orders_by_area(
+        &self,
+        organization_id: i32,
+    ) -> Result<Vec<(i32, i64)>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_repository.count_pending_orders_by_area");
+        let counts = sqlx::query_as::<_, (i32, i64)>(
+            "SELECT
+                a.id,
+                COUNT(o.id)
+            FROM
+                areas a
+            LEFT JOIN
+                (orders o JOIN nodes n ON n.id = o.node_id)
+            ON
+                n.area_id = a.id AND o.organization_id = a.organization_id AND o.status = ?
+            WHERE
+                a.organization_id = ?
+            GROUP BY
+                a.id",
+        )
+        .bind(OrderStatus::Pending.as_str())
+        .bind(organization_id)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(counts)
+    }
+
+    async fn count_pending_orders_ahead(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<i64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_repository.count_pending_orders_ahead");
+        let (count,) = sqlx::query_as::<_, (i64,)>(
+            "SELECT
+                COUNT(o.id)
+            FROM
+                orders t
+            JOIN
+                nodes tn
+            ON
+                tn.id = t.node_id
+            JOIN
+                nodes n
+            ON
+                n.area_id = tn.area_id
+            JOIN
+                orders o
+            ON
+                o.node_id = n.id AND o.organization_id = t.organization_id AND o.status = ?
+            WHERE
+                t.id = ? AND t.organization_id = ?
+                AND (o.queue_time < t.queue_time OR (o.queue_time = t.queue_time AND o.id < t.id))",
+        )
+        .bind(OrderStatus::Pending.as_str())
+        .bind(order_id)
+        .bind(organization_id)
+        .fetch_one(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn update_order_priority(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+        priority: OrderPriority,
+        advance_secs: i64,
+        expected_version: Option<i32>,
+    ) -> Result<bool, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_repository.update_order_priority");
+        let result = sqlx::query(
+            "UPDATE orders
+            SET priority = ?, queue_time = DATE_SUB(order_time, INTERVAL ? SECOND), version = version + 1
+            WHERE id = ? AND organization_id = ? AND status = ? AND (? IS NULL OR version = ?)",
+        )
+        .bind(priority.as_str())
+        .bind(advance_secs)
+        .bind(order_id)
+        .bind(organization_id)
+        .bind(OrderStatus::Pending.as_str())
+        .bind(expected_version)
+        .bind(expected_version)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn create_scheduled_order(
+        &self,
+        organization_id: i32,
+        client_id: i32,
+        node_id: i32,
+        dropoff_node_id: i32,
+        car_value: f64,
+        quoted_price: i32,
+        scheduled_for: DateTime<Utc>,
+    ) -> Result<i32, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_repository.create_scheduled_order");
+        let result = sqlx::query(
+            "INSERT INTO orders (organization_id, client_id, customer_id, node_id, dropoff_node_id, status, car_value, quoted_price, priority, scheduled_for)
+            VALUES (?, ?, (SELECT id FROM clients WHERE user_id = ?), ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(organization_id)
+        .bind(client_id)
+        .bind(client_id)
+        .bind(node_id)
+        .bind(dropoff_node_id)
+        .bind(OrderStatus::Scheduled.as_str())
+        .bind(car_value)
+        .bind(quoted_price)
+        .bind(OrderPriority::Scheduled.as_str())
+        .bind(scheduled_for)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.last_insert_id() as i32)
+    }
+
+    async fn find_scheduled_order(
+        &self,
+        organization_id: i32,
+        order_id: i32,
+    ) -> Result<Option<ScheduledOrder>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_repository.find_scheduled_order");
+        // 更新の直前に読むため、有効化のジョブとの食い違いを避けてプライマリから読む
+        let order = sqlx::query_as::<_, ScheduledOrder>(
+            "SELECT
+                id,
+                client_id,
+                node_id,
+                dropoff_node_id,
+                car_value,
+                quoted_price,
+                scheduled_for,
+                order_time,
+                version
+            FROM
+                orders
+            WHERE
+                id = ? AND organization_id = ? AND status = ?",
+        )
+        .bind(order_id)
+        .bind(organization_id)
+        .bind(OrderStatus::Scheduled.as_str())
+        .fetch_optional(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(order)
+    }
+
+    async fn find_scheduled_orders_by_client_id(
+        &self,
+        organization_id: i32,
+        client_id: i32,
+    ) -> Result<Vec<ScheduledOrder>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_repository.find_scheduled_orders_by_client_id");
+        let orders = sqlx::query_as::<_, ScheduledOrder>(
+            "SELECT
+                id,
+                client_id,
+                node_id,
+                dropoff_node_id,
+                car_value,
+                quoted_price,
+                scheduled_for,
+                order_time,
+                version
+            FROM
+                orders
+            WHERE
+                client_id = ? AND organization_id = ? AND status = ?
+            ORDER BY
+                scheduled_for",
+        )
+        .bind(client_id)
+        .bind(organization_id)
+        .bind(OrderStatus::Scheduled.as_str())
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(orders)
+    }
+
+    async fn update_scheduled_order(
+        &self,
+        organization_id: i32,
+        order: &ScheduledOrder,
+    ) -> Result<bool, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_repository.update_scheduled_order");
+        let result = sqlx::query(
+            "UPDATE orders
+            SET node_id = ?, dropoff_node_id = ?, car_value = ?, quoted_price = ?, scheduled_for = ?, version = version + 1
+            WHERE id = ? AND organization_id = ? AND status = ? AND version = ?",
+        )
+        .bind(order.node_id)
+        .bind(order.dropoff_node_id)
+        .bind(order.car_value)
+        .bind(order.quoted_price)
+        .bind(order.scheduled_for)
+        .bind(order.id)
+        .bind(organization_id)
+        .bind(OrderStatus::Scheduled.as_str())
+        .bind(order.version)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn activate_scheduled_orders(
+        &self,
+        until: DateTime<Utc>,
+        advance_secs: i64,
+        limit: i64,
+    ) -> Result<Vec<(i32, i32)>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_repository.activate_scheduled_orders");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+
+        // 複数のインスタンスでジョブが動いていても、同じ注文を重ねて有効にしない
+        let due = sqlx::query_as::<_, (i32, i32)>(
+            "SELECT
+                o.id,
+                n.area_id
+            FROM
+                orders o
+            JOIN
+                nodes n
+            ON
+                n.id = o.node_id
+            WHERE
+                o.status = ? AND o.scheduled_for <= ?
+            ORDER BY
+                o.scheduled_for
+            LIMIT ?
+            FOR UPDATE OF o SKIP LOCKED",
+        )
+        .bind(OrderStatus::Scheduled.as_str())
+        .bind(until)
+        .bind(limit)
+        .fetch_all(&mut tx)
+        .await?;
+        if due.is_empty() {
+            return Ok(due);
+        }
+
+        // 処理する順は、有効にした時点から予約注文の優先度の分だけ繰り上げる
+        let sql = format!(
+            "UPDATE orders
+            SET status = ?, priority = ?, queue_time = DATE_SUB(CURRENT_TIMESTAMP, INTERVAL ? SECOND), version = version + 1
+            WHERE id IN ({})",
+            vec!["?"; due.len()].join(", ")
+        );
+        let mut query = sqlx::query(&sql)
+            .bind(OrderStatus::Pending.as_str())
+            .bind(OrderPriority::Scheduled.as_str())
+            .bind(advance_secs);
+        for (order_id, _) in &due {
+            query = query.bind(order_id);
+        }
+        query.execute(&mut tx).await?;
+        tx.commit().await?;
+
+        Ok(due)
+    }
+
+    async fn transfer_dispatcher(
+        &self,
+        organization_id: i32,
+        dispatcher_id: i32,
+        from_area_id: i32,
+        to_area_id: i32,
+        candidate_ids: &[i32],
+    ) -> Result<Option<Vec<OrderHandover>>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("order_repository.transfer_dispatcher");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+
+        let area_exists = sqlx::query_scalar::<_, i32>(
+            "SELECT id FROM areas WHERE id = ? AND organization_id = ?",
+        )
+        .bind(to_area_id)
+        .bind(organization_id)
+        .fetch_optional(&mut tx)
+        .await?
+        .is_some();
+        if !area_exists {
+            return Err(AppError::BadRequest);
+        }
+        // 同時に異動させた場合は後の方を失敗させる
+        let result = sqlx::query(
+            "UPDATE dispatchers SET area_id = ? WHERE id = ? AND area_id = ? AND organization_id = ?",
+        )
+        .bind(to_area_id)
+        .bind(dispatcher_id)
+        .bind(from_area_id)
+        .bind(organization_id)
+        .execute(&mut tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        // 引き継ぎの途中で完了やキャンセルされないよう、対象の注文をロックする
+        let orders = sqlx::query_as::<_, (i32, Option<i32>)>(
+            "SELECT id, tow_truck_id FROM orders WHERE dispatcher_id = ? AND status = ? ORDER BY id FOR UPDATE",
+        )
+        .bind(dispatcher_id)
+        .bind(OrderStatus::Dispatched.as_str())
+        .fetch_all(&mut tx)
+        .await?;
+
+        let mut handovers = Vec::with_capacity(orders.len());
+        for (index, (order_id, tow_truck_id)) in orders.into_iter().enumerate() {
+            let new_dispatcher_id = match candidate_ids {
+                [] => None,
+                candidate_ids => Some(candidate_ids[index % candidate_ids.len()]),
+            };
+            match new_dispatcher_id {
+                Some(new_dispatcher_id) => {
+                    sqlx::query(
+                        "UPDATE orders SET dispatcher_id = ?, version = version + 1 WHERE id = ?",
+                    )
+                    .bind(new_dispatcher_id)
+                    .bind(order_id)
+                    .execute(&mut tx)
+                    .await?;
+                }
+                None => {
+                    sqlx::query(
+                        "UPDATE orders
+                        SET status = ?, dispatcher_id = NULL, tow_truck_id = NULL, dispatched_at = NULL, version = version + 1
+                        WHERE id = ?",
+                    )
+                    .bind(OrderStatus::Pending.as_str())
+                    .bind(order_id)
+                    .execute(&mut tx)
+                    .await?;
+                    // キャンセルと同様、completed_orders を消さないと車両を再び割り当てられない
+                    sqlx::query("DELETE FROM completed_orders WHERE order_id = ?")
+                        .bind(order_id)
+                        .execute(&mut tx)
+                        .await?;
+                    if let Some(tow_truck_id) = tow_truck_id {
+                        sqlx::query("UPDATE tow_trucks SET status = ? WHERE id = ?")
+                            .bind(VehicleStatus::Available.as_str())
+                            .bind(tow_truck_id)
+                            .execute(&mut tx)
+                            .await?;
+                    }
+                }
+            }
+            handovers.push(OrderHandover {
+                order_id,
+                tow_truck_id,
+                new_dispatcher_id,
+            });
+        }
+
+        tx.commit().await?;
+
+        Ok(Some(handovers))
     }
 }
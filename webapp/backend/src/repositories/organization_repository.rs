@@ -0,0 +1,57 @@
+use crate::domains::organization_service::OrganizationRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::organization::Organization;
+
+#[derive(Debug)]
+pub struct OrganizationRepositoryImpl {
+    pools: DbPools,
+}
+
+impl OrganizationRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        OrganizationRepositoryImpl { pools }
+    }
+}
+
+impl OrganizationRepository for OrganizationRepositoryImpl {
+    async fn create_organization(&self, name: &str) -> Result<i32, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("organization_repository.create_organization");
+        let result = sqlx::query("INSERT INTO organizations (name) VALUES (?)")
+            .bind(name)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(result.last_insert_id() as i32)
+    }
+
+    async fn find_organizations(&self) -> Result<Vec<Organization>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("organization_repository.find_organizations");
+        let organizations = sqlx::query_as::<_, Organization>(
+            "SELECT id, name, created_at FROM organizations ORDER BY id",
+        )
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(organizations)
+    }
+
+    // 作成した直後に読み直すため、プライマリから読む
+    async fn find_organization_by_id(&self, id: i32) -> Result<Option<Organization>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("organization_repository.find_organization_by_id");
+        let organization = sqlx::query_as::<_, Organization>(
+            "SELECT id, name, created_at FROM organizations WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(organization)
+    }
+}
@@ -0,0 +1,43 @@
+use crate::domains::profile_service::ProfileRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::profile::UserProfile;
+
+#[derive(Debug)]
+pub struct ProfileRepositoryImpl {
+    pools: DbPools,
+}
+
+impl ProfileRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        ProfileRepositoryImpl { pools }
+    }
+}
+
+impl ProfileRepository for ProfileRepositoryImpl {
+    async fn find_profile(&self, user_id: i32) -> Result<Option<UserProfile>, AppError> {
+        let _timer = self.pools.query_timer("profile_repository.find_profile");
+        // 変更の直後に読み直した場合も古い値を返さないよう、プライマリから読む
+        let profile = sqlx::query_as::<_, UserProfile>(
+            "SELECT id, username, role, display_name, email, phone_number, image_version FROM users WHERE id = ?",
+        )
+        .bind(user_id)
+        .fetch_optional(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(profile)
+    }
+
+    async fn update_profile(&self, profile: &UserProfile) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("profile_repository.update_profile");
+        sqlx::query("UPDATE users SET display_name = ?, email = ?, phone_number = ? WHERE id = ?")
+            .bind(&profile.display_name)
+            .bind(&profile.email)
+            .bind(&profile.phone_number)
+            .bind(profile.id)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use chrono::{DateTime, Utc};
+
+use crate::domains::quota_service::QuotaRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::organization::QuotaLimits;
+use crate::models::role::Role;
+
+#[derive(Debug)]
+pub struct QuotaRepositoryImpl {
+    pools: DbPools,
+}
+
+impl QuotaRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        QuotaRepositoryImpl { pools }
+    }
+}
+
+// 上限の確認に使う件数は、作成の直後でも正しく数えられるようプライマリから読む
+impl QuotaRepository for QuotaRepositoryImpl {
+    async fn find_quota_limits(
+        &self,
+        organization_id: i32,
+    ) -> Result<Option<QuotaLimits>, AppError> {
+        let _timer = self.pools.query_timer("quota_repository.find_quota_limits");
+        let limits = sqlx::query_as::<_, QuotaLimits>(
+            "SELECT
+                max_orders_per_day AS orders_per_day,
+                max_active_dispatchers AS active_dispatchers,
+                max_avatar_storage_bytes AS avatar_storage_bytes
+            FROM organizations
+            WHERE id = ?",
+        )
+        .bind(organization_id)
+        .fetch_optional(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(limits)
+    }
+
+    async fn update_quota_limits(
+        &self,
+        organization_id: i32,
+        limits: &QuotaLimits,
+    ) -> Result<bool, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("quota_repository.update_quota_limits");
+        // 値が変わらない行は更新件数に含まれないため、組織の存在は別に確認する
+        let exists = sqlx::query_scalar::<_, i32>("SELECT id FROM organizations WHERE id = ?")
+            .bind(organization_id)
+            .fetch_optional(&mut *self.pools.acquire_primary().await?)
+            .await?
+            .is_some();
+        if !exists {
+            return Ok(false);
+        }
+        sqlx::query(
+            "UPDATE organizations
+            SET max_orders_per_day = ?, max_active_dispatchers = ?, max_avatar_storage_bytes = ?
+            WHERE id = ?",
+        )
+        .bind(limits.orders_per_day)
+        .bind(limits.active_dispatchers)
+        .bind(limits.avatar_storage_bytes)
+        .bind(organization_id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(true)
+    }
+
+    async fn count_orders_since(
+        &self,
+        organization_id: i32,
+        since: DateTime<Utc>,
+    ) -> Result<i64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("quota_repository.count_orders_since");
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM orders WHERE organization_id = ? AND order_time >= ?",
+        )
+        .bind(organization_id)
+        .bind(since)
+        .fetch_one(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn count_active_dispatchers(&self, organization_id: i32) -> Result<i64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("quota_repository.count_active_dispatchers");
+        // ロールを変更した後もディスパッチャーの行は残るため、現在のロールで絞り込む
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*)
+            FROM dispatchers d
+            JOIN users u ON u.id = d.user_id
+            WHERE d.organization_id = ? AND u.role = ? AND u.is_active",
+        )
+        .bind(organization_id)
+        .bind(Role::Dispatcher.as_str())
+        .fetch_one(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn sum_profile_image_bytes(&self, organization_id: i32) -> Result<i64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("quota_repository.sum_profile_image_bytes");
+        let bytes = sqlx::query_scalar::<_, i64>(
+            "SELECT CAST(COALESCE(SUM(p.bytes), 0) AS SIGNED)
+            FROM users u
+            JOIN profile_images p ON p.name = u.profile_image
+            WHERE u.organization_id = ?",
+        )
+        .bind(organization_id)
+        .fetch_one(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(bytes)
+    }
+
+    async fn find_profile_image_bytes(&self, user_id: i32) -> Result<i64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("quota_repository.find_profile_image_bytes");
+        let bytes = sqlx::query_scalar::<_, i64>(
+            "SELECT p.bytes FROM users u JOIN profile_images p ON p.name = u.profile_image WHERE u.id = ?",
+        )
+        .bind(user_id)
+        .fetch_optional(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(bytes.unwrap_or(0))
+    }
+
+    async fn record_profile_image(
+        &self,
+        user_id: i32,
+        name: &str,
+        bytes: i64,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("quota_repository.record_profile_image");
+        sqlx::query("INSERT INTO profile_images (name, user_id, bytes) VALUES (?, ?, ?)")
+            .bind(name)
+            .bind(user_id)
+            .bind(bytes)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+use chrono::{DateTime, Utc};
+
+use crate::domains::report_service::ReportRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::report::{AreaCompletionCount, AssignmentLatencyTotal, DispatcherActivity};
+
+#[derive(Debug)]
+pub struct ReportRepositoryImpl {
+    pools: DbPools,
+}
+
+impl ReportRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        ReportRepositoryImpl { pools }
+    }
+}
+
+impl ReportRepository for ReportRepositoryImpl {
+    async fn count_completed_orders_by_area(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<AreaCompletionCount>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("report_repository.count_completed_orders_by_area");
+        let counts = sqlx::query_as::<_, AreaCompletionCount>(
+            "SELECT
+                n.area_id,
+                COUNT(*) AS completed_orders
+            FROM
+                orders o
+            JOIN
+                nodes n
+            ON
+                n.id = o.node_id
+            WHERE
+                o.completed_time >= ? AND o.completed_time < ?
+            GROUP BY
+                n.area_id
+            ORDER BY
+                n.area_id",
+        )
+        .bind(from)
+        .bind(to)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(counts)
+    }
+
+    async fn sum_assignment_latency(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<AssignmentLatencyTotal, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("report_repository.sum_assignment_latency");
+        let total = sqlx::query_as::<_, AssignmentLatencyTotal>(
+            "SELECT
+                COUNT(*) AS assigned_orders,
+                CAST(SUM(TIMESTAMPDIFF(SECOND, order_time, dispatched_at)) AS SIGNED) AS total_seconds
+            FROM
+                orders
+            WHERE
+                dispatched_at >= ? AND dispatched_at < ?",
+        )
+        .bind(from)
+        .bind(to)
+        .fetch_one(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(total)
+    }
+
+    // 担当時間は配車から完了 (またはキャンセル) までのうち、集計期間に含まれる部分とする
+    async fn find_dispatcher_activities(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<DispatcherActivity>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("report_repository.find_dispatcher_activities");
+        let activities = sqlx::query_as::<_, DispatcherActivity>(
+            "SELECT
+                dispatcher_id,
+                COUNT(*) AS assigned_orders,
+                CAST(SUM(TIMESTAMPDIFF(
+                    SECOND,
+                    GREATEST(dispatched_at, ?),
+                    LEAST(COALESCE(completed_time, cancelled_at, ?), ?)
+                )) AS SIGNED) AS busy_seconds
+            FROM
+                orders
+            WHERE
+                dispatcher_id IS NOT NULL
+                AND dispatched_at < ?
+                AND COALESCE(completed_time, cancelled_at, ?) > ?
+            GROUP BY
+                dispatcher_id
+            ORDER BY
+                dispatcher_id",
+        )
+        .bind(from)
+        .bind(to)
+        .bind(to)
+        .bind(to)
+        .bind(to)
+        .bind(from)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(activities)
+    }
+}
@@ -0,0 +1,158 @@
+use chrono::{DateTime, Utc};
+
+use crate::domains::retention_service::{AnonymizedOrders, RetentionRepository};
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::order::OrderStatus;
+
+#[derive(Debug)]
+pub struct RetentionRepositoryImpl {
+    pools: DbPools,
+}
+
+impl RetentionRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        RetentionRepositoryImpl { pools }
+    }
+}
+
+impl RetentionRepository for RetentionRepositoryImpl {
+    async fn count_audit_logs_before(&self, before: DateTime<Utc>) -> Result<u64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("retention_repository.count_audit_logs_before");
+        let count =
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM audit_logs WHERE created_at < ?")
+                .bind(before)
+                .fetch_one(&mut *self.pools.acquire_replica().await?)
+                .await?;
+
+        Ok(count as u64)
+    }
+
+    async fn delete_audit_logs_before(
+        &self,
+        before: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<u64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("retention_repository.delete_audit_logs_before");
+        let result = sqlx::query("DELETE FROM audit_logs WHERE created_at < ? ORDER BY id LIMIT ?")
+            .bind(before)
+            .bind(limit)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn count_completed_orders_before(&self, before: DateTime<Utc>) -> Result<u64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("retention_repository.count_completed_orders_before");
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM orders WHERE status = ? AND anonymized_at IS NULL AND completed_time < ?",
+        )
+        .bind(OrderStatus::Completed.as_str())
+        .bind(before)
+        .fetch_one(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(count as u64)
+    }
+
+    async fn count_completed_order_attachments_before(
+        &self,
+        before: DateTime<Utc>,
+    ) -> Result<u64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("retention_repository.count_completed_order_attachments_before");
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM order_attachments a
+            JOIN orders o ON o.id = a.order_id
+            WHERE o.status = ? AND o.anonymized_at IS NULL AND o.completed_time < ?",
+        )
+        .bind(OrderStatus::Completed.as_str())
+        .bind(before)
+        .fetch_one(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(count as u64)
+    }
+
+    async fn anonymize_completed_orders_before(
+        &self,
+        before: DateTime<Utc>,
+        anonymized_at: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<AnonymizedOrders, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("retention_repository.anonymize_completed_orders_before");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        let order_ids = sqlx::query_scalar::<_, i32>(
+            "SELECT id FROM orders
+            WHERE status = ? AND anonymized_at IS NULL AND completed_time < ?
+            ORDER BY id
+            LIMIT ?
+            FOR UPDATE SKIP LOCKED",
+        )
+        .bind(OrderStatus::Completed.as_str())
+        .bind(before)
+        .bind(limit)
+        .fetch_all(&mut tx)
+        .await?;
+        if order_ids.is_empty() {
+            return Ok(AnonymizedOrders::default());
+        }
+
+        let placeholders = vec!["?"; order_ids.len()].join(", ");
+        let query = format!(
+            "SELECT name FROM order_attachments WHERE order_id IN ({})",
+            placeholders
+        );
+        let mut select = sqlx::query_scalar::<_, String>(&query);
+        for order_id in &order_ids {
+            select = select.bind(order_id);
+        }
+        let attachment_names = select.fetch_all(&mut tx).await?;
+
+        // メモとチャット、添付ファイル (乗車地の写真など) は内容に個人を特定できる情報を含みうるため、内容ごと削除する
+        for table in [
+            "order_notes",
+            "order_messages",
+            "order_message_reads",
+            "order_attachments",
+        ] {
+            let query = format!("DELETE FROM {} WHERE order_id IN ({})", table, placeholders);
+            let mut delete = sqlx::query(&query);
+            for order_id in &order_ids {
+                delete = delete.bind(order_id);
+            }
+            delete.execute(&mut tx).await?;
+        }
+
+        // client_id は注文したアカウント (電話注文では受け付けたディスパッチャー) で、本人が自分の注文履歴を見るために残す。
+        // NOT NULL の外部キーで、users の行を削除した場合は ON DELETE CASCADE で注文ごと消えるため、アカウントより長くは残らない
+        let query = format!(
+            "UPDATE orders
+            SET customer_id = NULL, cancel_reason = NULL, anonymized_at = ?, version = version + 1
+            WHERE id IN ({})",
+            placeholders
+        );
+        let mut update = sqlx::query(&query).bind(anonymized_at);
+        for order_id in &order_ids {
+            update = update.bind(order_id);
+        }
+        let result = update.execute(&mut tx).await?;
+        tx.commit().await?;
+
+        Ok(AnonymizedOrders {
+            count: result.rows_affected(),
+            attachment_names,
+        })
+    }
+}
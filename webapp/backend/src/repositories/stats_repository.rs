@@ -0,0 +1,58 @@
+use crate::domains::stats_service::StatsRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::stats::OrderCounter;
+
+#[derive(Debug, Clone)]
+pub struct StatsRepositoryImpl {
+    pools: DbPools,
+}
+
+impl StatsRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        StatsRepositoryImpl { pools }
+    }
+}
+
+impl StatsRepository for StatsRepositoryImpl {
+    async fn reconcile_order_counters(&self) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("stats_repository.reconcile_order_counters");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        // 注文がなくなった状態の件数も 0 に戻すため、先にすべて 0 にしてから数えた件数で上書きする
+        sqlx::query("UPDATE order_counters SET count = 0, reconciled_at = NOW()")
+            .execute(&mut tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO order_counters (organization_id, status, count, reconciled_at)
+            SELECT organization_id, status, COUNT(*), NOW()
+            FROM orders
+            GROUP BY organization_id, status
+            ON DUPLICATE KEY UPDATE count = VALUES(count), reconciled_at = VALUES(reconciled_at)",
+        )
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn find_order_counters(
+        &self,
+        organization_id: i32,
+    ) -> Result<Vec<OrderCounter>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("stats_repository.find_order_counters");
+        let counters = sqlx::query_as::<_, OrderCounter>(
+            "SELECT status, count, reconciled_at FROM order_counters WHERE organization_id = ?",
+        )
+        .bind(organization_id)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(counters)
+    }
+}
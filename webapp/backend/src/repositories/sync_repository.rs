@@ -0,0 +1,272 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::domains::sync_service::SyncRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::change_log::{ChangeEntity, ChangeLogEntry};
+use crate::models::order::OrderSyncRow;
+use crate::models::user::{Dispatcher, UserSyncRow};
+
+#[derive(Debug, Clone)]
+pub struct SyncRepositoryImpl {
+    pools: DbPools,
+}
+
+impl SyncRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        SyncRepositoryImpl { pools }
+    }
+}
+
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+impl SyncRepository for SyncRepositoryImpl {
+    async fn record_change(&self, entity: ChangeEntity, entity_id: i32) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("sync_repository.record_change");
+        let table = match entity {
+            ChangeEntity::Order => "orders",
+            ChangeEntity::Dispatcher => "dispatchers",
+            ChangeEntity::User => "users",
+            ChangeEntity::Reset => return self.record_reset().await,
+        };
+        let sql = format!(
+            "INSERT INTO change_log (organization_id, entity_type, entity_id)
+            SELECT organization_id, ?, id FROM {} WHERE id = ?",
+            table
+        );
+        sqlx::query(&sql)
+            .bind(entity.as_str())
+            .bind(entity_id)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn record_reset(&self) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("sync_repository.record_reset");
+        sqlx::query(
+            "INSERT INTO change_log (organization_id, entity_type, entity_id) VALUES (NULL, ?, 0)",
+        )
+        .bind(ChangeEntity::Reset.as_str())
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    // レプリカはコミットの順に反映するため、ID の順と入れ替わった記録が遅れて見えることがある。
+    // 返す位置と find_changes はプライマリで読む。新しい記録だけを範囲で引くため、どちらも読む行は少ない
+    async fn find_latest_change_id(&self, safety_lag: Duration) -> Result<i64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("sync_repository.find_latest_change_id");
+        let mut conn = self.pools.acquire_primary().await?;
+        let (recent_id,) = sqlx::query_as::<_, (Option<i64>,)>(
+            "SELECT MIN(id) FROM change_log WHERE created_at >= NOW() - INTERVAL ? SECOND",
+        )
+        .bind(safety_lag.as_secs())
+        .fetch_one(&mut conn)
+        .await?;
+        if let Some(recent_id) = recent_id {
+            return Ok(recent_id - 1);
+        }
+
+        let (change_id,) = sqlx::query_as::<_, (Option<i64>,)>("SELECT MAX(id) FROM change_log")
+            .fetch_one(&mut conn)
+            .await?;
+
+        Ok(change_id.unwrap_or(0))
+    }
+
+    async fn find_oldest_change_id(&self) -> Result<Option<i64>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("sync_repository.find_oldest_change_id");
+        let (change_id,) = sqlx::query_as::<_, (Option<i64>,)>("SELECT MIN(id) FROM change_log")
+            .fetch_one(&mut *self.pools.acquire_replica().await?)
+            .await?;
+
+        Ok(change_id)
+    }
+
+    async fn find_changes(
+        &self,
+        organization_id: i32,
+        since: i64,
+        until: i64,
+        limit: i32,
+    ) -> Result<Vec<ChangeLogEntry>, AppError> {
+        let _timer = self.pools.query_timer("sync_repository.find_changes");
+        let changes = sqlx::query_as::<_, ChangeLogEntry>(
+            "SELECT
+                id,
+                entity_type,
+                entity_id
+            FROM
+                change_log
+            WHERE
+                id > ? AND id <= ? AND (organization_id = ? OR organization_id IS NULL)
+            ORDER BY
+                id
+            LIMIT ?",
+        )
+        .bind(since)
+        .bind(until)
+        .bind(organization_id)
+        .bind(limit)
+        .fetch_all(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(changes)
+    }
+
+    async fn find_orders(
+        &self,
+        organization_id: i32,
+        order_ids: &[i32],
+    ) -> Result<Vec<OrderSyncRow>, AppError> {
+        if order_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let _timer = self.pools.query_timer("sync_repository.find_orders");
+        let sql = format!(
+            "SELECT
+                o.id,
+                o.client_id,
+                c.username AS client_username,
+                o.dispatcher_id,
+                d.user_id AS dispatcher_user_id,
+                du.username AS dispatcher_username,
+                o.tow_truck_id,
+                t.driver_id AS driver_user_id,
+                tu.username AS driver_username,
+                o.status,
+                o.node_id,
+                n.area_id,
+                o.car_value,
+                o.order_time,
+                o.completed_time,
+                o.version,
+                o.priority
+            FROM
+                orders o
+            JOIN
+                nodes n
+            ON
+                n.id = o.node_id
+            LEFT JOIN
+                users c
+            ON
+                c.id = o.client_id
+            LEFT JOIN
+                dispatchers d
+            ON
+                d.id = o.dispatcher_id
+            LEFT JOIN
+                users du
+            ON
+                du.id = d.user_id
+            LEFT JOIN
+                tow_trucks t
+            ON
+                t.id = o.tow_truck_id
+            LEFT JOIN
+                users tu
+            ON
+                tu.id = t.driver_id
+            WHERE
+                o.organization_id = ? AND o.id IN ({})
+            ORDER BY
+                o.id",
+            placeholders(order_ids.len())
+        );
+        let mut query = sqlx::query_as::<_, OrderSyncRow>(&sql).bind(organization_id);
+        for order_id in order_ids {
+            query = query.bind(order_id);
+        }
+        let orders = query
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?;
+
+        Ok(orders)
+    }
+
+    async fn find_dispatchers(
+        &self,
+        organization_id: i32,
+        dispatcher_ids: &[i32],
+    ) -> Result<Vec<Dispatcher>, AppError> {
+        if dispatcher_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let _timer = self.pools.query_timer("sync_repository.find_dispatchers");
+        let sql = format!(
+            "SELECT id, user_id, area_id FROM dispatchers WHERE organization_id = ? AND id IN ({}) ORDER BY id",
+            placeholders(dispatcher_ids.len())
+        );
+        let mut query = sqlx::query_as::<_, Dispatcher>(&sql).bind(organization_id);
+        for dispatcher_id in dispatcher_ids {
+            query = query.bind(dispatcher_id);
+        }
+        let dispatchers = query
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?;
+
+        Ok(dispatchers)
+    }
+
+    async fn find_users(
+        &self,
+        organization_id: i32,
+        user_ids: &[i32],
+    ) -> Result<Vec<UserSyncRow>, AppError> {
+        if user_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let _timer = self.pools.query_timer("sync_repository.find_users");
+        let sql = format!(
+            "SELECT id, username, role, is_active, image_version FROM users WHERE organization_id = ? AND id IN ({}) ORDER BY id",
+            placeholders(user_ids.len())
+        );
+        let mut query = sqlx::query_as::<_, UserSyncRow>(&sql).bind(organization_id);
+        for user_id in user_ids {
+            query = query.bind(user_id);
+        }
+        let users = query
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?;
+
+        Ok(users)
+    }
+
+    async fn delete_changes_before(
+        &self,
+        before: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<u64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("sync_repository.delete_changes_before");
+        let result = sqlx::query(
+            "DELETE FROM change_log WHERE id IN (
+                SELECT id FROM (
+                    SELECT id FROM change_log WHERE created_at < ? ORDER BY id LIMIT ?
+                ) AS expired_changes
+            )",
+        )
+        .bind(before)
+        .bind(limit)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -1,42 +1,50 @@
 use crate::domains::tow_truck_service::TowTruckRepository;
 use crate::errors::AppError;
-use crate::models::tow_truck::TowTruck;
-use sqlx::mysql::MySqlPool;
+use crate::infrastructure::db::DbPools;
+use crate::models::tow_truck::{NewLocation, TowTruck};
+use crate::repositories::bulk_insert;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TowTruckRepositoryImpl {
-    pool: MySqlPool,
+    pools: DbPools,
 }
 
 impl TowTruckRepositoryImpl {
-    pub fn new(pool: MySqlPool) -> Self {
-        TowTruckRepositoryImpl { pool }
+    pub fn new(pools: DbPools) -> Self {
+        TowTruckRepositoryImpl { pools }
     }
 }
 
 impl TowTruckRepository for TowTruckRepositoryImpl {
     async fn get_paginated_tow_trucks(
         &self,
+        organization_id: i32,
         page: i32,
         page_size: i32,
         status: Option<String>,
         area_id: Option<i32>,
     ) -> Result<Vec<TowTruck>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("tow_truck_repository.get_paginated_tow_trucks");
+        // 車両は運転手のユーザーが属する組織のものとして扱う
         let where_clause = match (status, area_id) {
             (Some(status), Some(area_id)) => format!(
-                "WHERE tt.status = '{}' AND tt.area_id = {} AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
-                status, area_id
+                "WHERE u.organization_id = {} AND tt.status = '{}' AND tt.area_id = {} AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
+                organization_id, status, area_id
             ),
             (None, Some(area_id)) => format!(
-                "WHERE tt.area_id = {} AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
-                area_id
+                "WHERE u.organization_id = {} AND tt.area_id = {} AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
+                organization_id, area_id
             ),
             (Some(status), None) => format!(
-                "WHERE tt.status = '{}' AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
-                status
+                "WHERE u.organization_id = {} AND tt.status = '{}' AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
+                organization_id, status
+            ),
+            (None, None) => format!(
+                "WHERE u.organization_id = {} AND l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
+                organization_id
             ),
-            (None, None) => "WHERE l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)"
-                .to_string(),
         };
         let limit_clause = match page_size {
             -1 => "".to_string(),
@@ -73,54 +81,108 @@ impl TowTruckRepository for TowTruckRepositoryImpl {
             where_clause, limit_clause, offset_clause
         );
 
-        let tow_trucks = sqlx::query_as::<_, TowTruck>(&query)
-            .fetch_all(&self.pool)
+        let query = query.as_str();
+        let tow_trucks = self
+            .pools
+            .retry(
+                "tow_truck_repository.get_paginated_tow_trucks",
+                || async move {
+                    sqlx::query_as::<_, TowTruck>(query)
+                        .fetch_all(&mut *self.pools.acquire_replica().await?)
+                        .await
+                },
+            )
             .await?;
 
         Ok(tow_trucks)
     }
 
     async fn update_location(&self, tow_truck_id: i32, node_id: i32) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("tow_truck_repository.update_location");
         sqlx::query("INSERT INTO locations (tow_truck_id, node_id) VALUES (?, ?)")
             .bind(tow_truck_id)
             .bind(node_id)
-            .execute(&self.pool)
+            .execute(&mut *self.pools.acquire_primary().await?)
             .await?;
         Ok(())
     }
 
+    async fn create_locations(&self, locations: &[NewLocation]) -> Result<(), AppError> {
+        if locations.is_empty() {
+            return Ok(());
+        }
+
+        let _timer = self
+            .pools
+            .query_timer("tow_truck_repository.create_locations");
+        // 失敗した分を再試行したときに重複しないよう、文を分けた場合もまとめてコミットする
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        for chunk in locations.chunks(bulk_insert::rows_per_statement(2)) {
+            let sql = format!(
+                "INSERT INTO locations (tow_truck_id, node_id) VALUES {}",
+                bulk_insert::values_placeholders(2, chunk.len())
+            );
+            let mut query = sqlx::query(&sql);
+            for location in chunk {
+                query = query.bind(location.tow_truck_id).bind(location.node_id);
+            }
+            query.execute(&mut tx).await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
     async fn update_status(&self, tow_truck_id: i32, status: &str) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("tow_truck_repository.update_status");
         sqlx::query("UPDATE tow_trucks SET status = ? WHERE id = ?")
             .bind(status)
             .bind(tow_truck_id)
-            .execute(&self.pool)
+            .execute(&mut *self.pools.acquire_primary().await?)
             .await?;
 
         Ok(())
     }
 
-    async fn find_tow_truck_by_id(&self, id: i32) -> Result<Option<TowTruck>, AppError> {
-        let tow_truck = sqlx::query_as::<_, TowTruck>(
-            "SELECT
-                tt.id, tt.driver_id, u.username AS driver_username, tt.status, l.node_id, tt.area_id
-            FROM
-                tow_trucks tt
-            JOIN
-                users u 
-            ON
-                tt.driver_id = u.id
-            JOIN
-                locations l
-            ON
-                tt.id = l.tow_truck_id
-            WHERE
-                tt.id = ?
-            AND
-                l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
-        )
-        .bind(id)
-        .fetch_optional(&self.pool)
-        .await?;
+    async fn find_tow_truck_by_id(
+        &self,
+        organization_id: i32,
+        id: i32,
+    ) -> Result<Option<TowTruck>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("tow_truck_repository.find_tow_truck_by_id");
+        let tow_truck = self
+            .pools
+            .retry("tow_truck_repository.find_tow_truck_by_id", || async move {
+                sqlx::query_as::<_, TowTruck>(
+                    "SELECT
+                        tt.id, tt.driver_id, u.username AS driver_username, tt.status, l.node_id, tt.area_id
+                    FROM
+                        tow_trucks tt
+                    JOIN
+                        users u 
+                    ON
+                        tt.driver_id = u.id
+                    JOIN
+                        locations l
+                    ON
+                        tt.id = l.tow_truck_id
+                    WHERE
+                        tt.id = ?
+                    AND
+                        u.organization_id = ?
+                    AND
+                        l.timestamp = (SELECT MAX(timestamp) FROM locations WHERE tow_truck_id = tt.id)",
+                )
+                .bind(id)
+                .bind(organization_id)
+                .fetch_optional(&mut *self.pools.acquire_replica().await?).await
+            })
+            .await?;
 
         Ok(tow_truck)
     }
@@ -0,0 +1,137 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+
+use crate::domains::user_import_service::UserImportRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::role::Role;
+use crate::models::user::NewUser;
+use crate::repositories::bulk_insert;
+
+#[derive(Debug)]
+pub struct UserImportRepositoryImpl {
+    pools: DbPools,
+}
+
+impl UserImportRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        UserImportRepositoryImpl { pools }
+    }
+}
+
+impl UserImportRepository for UserImportRepositoryImpl {
+    async fn find_unavailable_usernames(
+        &self,
+        usernames: &[String],
+        since: DateTime<Utc>,
+    ) -> Result<HashSet<String>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("user_import_repository.find_unavailable_usernames");
+        if usernames.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let placeholders = vec!["?"; usernames.len()].join(", ");
+        let query = format!(
+            "SELECT username FROM users WHERE username IN ({0})
+            UNION
+            SELECT username FROM username_history WHERE username IN ({0}) AND changed_at >= ?",
+            placeholders
+        );
+        let mut select = sqlx::query_scalar::<_, String>(&query);
+        for username in usernames.iter().chain(usernames) {
+            select = select.bind(username);
+        }
+        // 書き込み直前の確認のため、レプリカ遅延の影響を受けないようプライマリから読む
+        let unavailable = select
+            .bind(since)
+            .fetch_all(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(unavailable.into_iter().collect())
+    }
+
+    async fn find_area_ids(&self) -> Result<HashSet<i32>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("user_import_repository.find_area_ids");
+        let area_ids = sqlx::query_scalar::<_, i32>("SELECT id FROM areas")
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?;
+
+        Ok(area_ids.into_iter().collect())
+    }
+
+    async fn create_users(&self, users: &[NewUser]) -> Result<Vec<i32>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("user_import_repository.create_users");
+        if users.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+
+        for chunk in users.chunks(bulk_insert::rows_per_statement(3)) {
+            let query = format!(
+                "INSERT INTO users (username, password, role) VALUES {}",
+                bulk_insert::values_placeholders(3, chunk.len())
+            );
+            let mut insert = sqlx::query(&query);
+            for user in chunk {
+                insert = insert
+                    .bind(&user.username)
+                    .bind(&user.password)
+                    .bind(user.role);
+            }
+            insert.execute(&mut tx).await?;
+        }
+
+        // 複数行の INSERT で採番された ID は連続するとは限らないため、ユーザー名で引き直す
+        let mut user_ids = HashMap::new();
+        for chunk in users.chunks(bulk_insert::rows_per_statement(1)) {
+            let query = format!(
+                "SELECT id, username FROM users WHERE username IN ({})",
+                vec!["?"; chunk.len()].join(", ")
+            );
+            let mut select = sqlx::query_as::<_, (i32, String)>(&query);
+            for user in chunk {
+                select = select.bind(&user.username);
+            }
+            for (id, username) in select.fetch_all(&mut tx).await? {
+                user_ids.insert(username, id);
+            }
+        }
+        let user_ids: Vec<i32> = users
+            .iter()
+            .map(|user| user_ids.get(&user.username).copied())
+            .collect::<Option<_>>()
+            .ok_or(AppError::InternalServerError)?;
+
+        let dispatchers: Vec<(i32, i32)> = users
+            .iter()
+            .zip(&user_ids)
+            .filter_map(|(user, user_id)| match (user.role, user.area_id) {
+                (Role::Dispatcher, Some(area_id)) => Some((*user_id, area_id)),
+                _ => None,
+            })
+            .collect();
+        for chunk in dispatchers.chunks(bulk_insert::rows_per_statement(2)) {
+            let query = format!(
+                "INSERT INTO dispatchers (user_id, area_id) VALUES {}",
+                bulk_insert::values_placeholders(2, chunk.len())
+            );
+            let mut insert = sqlx::query(&query);
+            for (user_id, area_id) in chunk {
+                insert = insert.bind(user_id).bind(area_id);
+            }
+            insert.execute(&mut tx).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(user_ids)
+    }
+}
@@ -0,0 +1,164 @@
+use crate::domains::vehicle_service::VehicleRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::vehicle::{Vehicle, VehicleStatus};
+
+#[derive(Debug)]
+pub struct VehicleRepositoryImpl {
+    pools: DbPools,
+}
+
+impl VehicleRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        VehicleRepositoryImpl { pools }
+    }
+}
+
+impl VehicleRepository for VehicleRepositoryImpl {
+    async fn create_vehicle(
+        &self,
+        driver_id: i32,
+        area_id: i32,
+        capacity: i32,
+        node_id: i32,
+    ) -> Result<i32, AppError> {
+        let _timer = self.pools.query_timer("vehicle_repository.create_vehicle");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+
+        let result = sqlx::query(
+            "INSERT INTO tow_trucks (driver_id, status, area_id, capacity) VALUES (?, ?, ?, ?)",
+        )
+        .bind(driver_id)
+        .bind(VehicleStatus::Available.as_str())
+        .bind(area_id)
+        .bind(capacity)
+        .execute(&mut tx)
+        .await?;
+        let id = result.last_insert_id() as i32;
+        sqlx::query("INSERT INTO locations (tow_truck_id, node_id) VALUES (?, ?)")
+            .bind(id)
+            .bind(node_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(id)
+    }
+
+    async fn find_vehicle_by_id(&self, id: i32) -> Result<Option<Vehicle>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("vehicle_repository.find_vehicle_by_id");
+        let vehicle = sqlx::query_as::<_, Vehicle>(
+            "SELECT id, driver_id, status, area_id, capacity FROM tow_trucks WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(vehicle)
+    }
+
+    async fn get_vehicles(
+        &self,
+        area_id: Option<i32>,
+        status: Option<VehicleStatus>,
+    ) -> Result<Vec<Vehicle>, AppError> {
+        let _timer = self.pools.query_timer("vehicle_repository.get_vehicles");
+        let mut conditions = Vec::new();
+        if area_id.is_some() {
+            conditions.push("area_id = ?");
+        }
+        if status.is_some() {
+            conditions.push("status = ?");
+        }
+        let where_clause = match conditions.is_empty() {
+            true => String::new(),
+            false => format!("WHERE {}", conditions.join(" AND ")),
+        };
+        let query = format!(
+            "SELECT id, driver_id, status, area_id, capacity FROM tow_trucks {} ORDER BY id ASC",
+            where_clause
+        );
+
+        let mut select = sqlx::query_as::<_, Vehicle>(&query);
+        if let Some(area_id) = area_id {
+            select = select.bind(area_id);
+        }
+        if let Some(status) = status {
+            select = select.bind(status.as_str());
+        }
+        let vehicles = select
+            .fetch_all(&mut *self.pools.acquire_replica().await?)
+            .await?;
+
+        Ok(vehicles)
+    }
+
+    async fn update_vehicle(
+        &self,
+        id: i32,
+        driver_id: i32,
+        area_id: i32,
+        capacity: i32,
+    ) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("vehicle_repository.update_vehicle");
+        sqlx::query("UPDATE tow_trucks SET driver_id = ?, area_id = ?, capacity = ? WHERE id = ?")
+            .bind(driver_id)
+            .bind(area_id)
+            .bind(capacity)
+            .bind(id)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_vehicle_status(&self, id: i32, status: VehicleStatus) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("vehicle_repository.update_vehicle_status");
+        sqlx::query("UPDATE tow_trucks SET status = ? WHERE id = ?")
+            .bind(status.as_str())
+            .bind(id)
+            .execute(&mut *self.pools.acquire_primary().await?)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn has_orders(&self, id: i32) -> Result<bool, AppError> {
+        let _timer = self.pools.query_timer("vehicle_repository.has_orders");
+        let has_orders: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM orders WHERE tow_truck_id = ?)
+                OR EXISTS (SELECT 1 FROM completed_orders WHERE tow_truck_id = ?)",
+        )
+        .bind(id)
+        .bind(id)
+        .fetch_one(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(has_orders)
+    }
+
+    async fn delete_vehicle(&self, id: i32) -> Result<(), AppError> {
+        let _timer = self.pools.query_timer("vehicle_repository.delete_vehicle");
+        let mut conn = self.pools.acquire_primary().await?;
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+
+        sqlx::query("DELETE FROM locations WHERE tow_truck_id = ?")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM tow_trucks WHERE id = ?")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use chrono::{DateTime, Utc};
+
+use crate::domains::webhook_service::WebhookRepository;
+use crate::errors::AppError;
+use crate::infrastructure::db::DbPools;
+use crate::models::webhook::{PendingWebhookDelivery, WebhookDelivery, WebhookSubscription};
+
+#[derive(Debug, Clone)]
+pub struct WebhookRepositoryImpl {
+    pools: DbPools,
+}
+
+impl WebhookRepositoryImpl {
+    pub fn new(pools: DbPools) -> Self {
+        WebhookRepositoryImpl { pools }
+    }
+}
+
+impl WebhookRepository for WebhookRepositoryImpl {
+    async fn create_subscription(
+        &self,
+        url: &str,
+        secret: &str,
+        events: Option<&str>,
+    ) -> Result<i32, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("webhook_repository.create_subscription");
+        let result =
+            sqlx::query("INSERT INTO webhook_subscriptions (url, secret, events) VALUES (?, ?, ?)")
+                .bind(url)
+                .bind(secret)
+                .bind(events)
+                .execute(&mut *self.pools.acquire_primary().await?)
+                .await?;
+
+        Ok(result.last_insert_id() as i32)
+    }
+
+    async fn find_subscriptions(&self) -> Result<Vec<WebhookSubscription>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("webhook_repository.find_subscriptions");
+        let subscriptions = sqlx::query_as::<_, WebhookSubscription>(
+            "SELECT id, url, events, created_at
+            FROM webhook_subscriptions
+            WHERE is_active = TRUE
+            ORDER BY id",
+        )
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(subscriptions)
+    }
+
+    async fn find_subscription_by_id(
+        &self,
+        id: i32,
+    ) -> Result<Option<WebhookSubscription>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("webhook_repository.find_subscription_by_id");
+        let subscription = sqlx::query_as::<_, WebhookSubscription>(
+            "SELECT id, url, events, created_at FROM webhook_subscriptions WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(subscription)
+    }
+
+    async fn deactivate_subscription(&self, id: i32) -> Result<bool, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("webhook_repository.deactivate_subscription");
+        let result = sqlx::query(
+            "UPDATE webhook_subscriptions SET is_active = FALSE WHERE id = ? AND is_active = TRUE",
+        )
+        .bind(id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn enqueue_deliveries(
+        &self,
+        event: &str,
+        payload: &str,
+        now: DateTime<Utc>,
+    ) -> Result<u64, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("webhook_repository.enqueue_deliveries");
+        let result = sqlx::query(
+            "INSERT INTO webhook_deliveries (subscription_id, event, payload, next_attempt_at)
+            SELECT id, ?, ?, ?
+            FROM webhook_subscriptions
+            WHERE is_active = TRUE
+            AND (events IS NULL OR FIND_IN_SET(?, events) > 0)",
+        )
+        .bind(event)
+        .bind(payload)
+        .bind(now)
+        .bind(event)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn find_due_deliveries(
+        &self,
+        now: DateTime<Utc>,
+        limit: i32,
+    ) -> Result<Vec<PendingWebhookDelivery>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("webhook_repository.find_due_deliveries");
+        // 登録を解除した送信先への配信は送らずに残す
+        let deliveries = sqlx::query_as::<_, PendingWebhookDelivery>(
+            "SELECT
+                d.id,
+                d.event,
+                d.payload,
+                d.attempts,
+                s.url,
+                s.secret
+            FROM
+                webhook_deliveries d
+            JOIN
+                webhook_subscriptions s
+            ON
+                s.id = d.subscription_id
+            WHERE
+                d.status = 'pending'
+            AND
+                d.next_attempt_at <= ?
+            AND
+                s.is_active = TRUE
+            ORDER BY
+                d.next_attempt_at
+            LIMIT ?",
+        )
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    async fn claim_delivery(
+        &self,
+        id: i32,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let _timer = self.pools.query_timer("webhook_repository.claim_delivery");
+        let result = sqlx::query(
+            "UPDATE webhook_deliveries
+            SET next_attempt_at = ?
+            WHERE id = ? AND status = 'pending' AND next_attempt_at <= ?",
+        )
+        .bind(lease_until)
+        .bind(id)
+        .bind(now)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn record_delivery_attempt(
+        &self,
+        id: i32,
+        status: &str,
+        attempts: i32,
+        status_code: Option<i32>,
+        error: Option<&str>,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) -> Result<(), AppError> {
+        let _timer = self
+            .pools
+            .query_timer("webhook_repository.record_delivery_attempt");
+        sqlx::query(
+            "UPDATE webhook_deliveries
+            SET
+                status = ?,
+                attempts = ?,
+                last_status_code = ?,
+                last_error = ?,
+                next_attempt_at = COALESCE(?, next_attempt_at)
+            WHERE id = ?",
+        )
+        .bind(status)
+        .bind(attempts)
+        .bind(status_code)
+        .bind(error)
+        .bind(next_attempt_at)
+        .bind(id)
+        .execute(&mut *self.pools.acquire_primary().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_deliveries_by_subscription_id(
+        &self,
+        subscription_id: i32,
+        limit: i32,
+    ) -> Result<Vec<WebhookDelivery>, AppError> {
+        let _timer = self
+            .pools
+            .query_timer("webhook_repository.find_deliveries_by_subscription_id");
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+            "SELECT
+                id,
+                subscription_id,
+                event,
+                status,
+                attempts,
+                last_status_code,
+                last_error,
+                next_attempt_at,
+                created_at,
+                updated_at
+            FROM
+                webhook_deliveries
+            WHERE
+                subscription_id = ?
+            ORDER BY
+                id DESC
+            LIMIT ?",
+        )
+        .bind(subscription_id)
+        .bind(limit)
+        .fetch_all(&mut *self.pools.acquire_replica().await?)
+        .await?;
+
+        Ok(deliveries)
+    }
+}
@@ -1,10 +1,21 @@
+use actix_web::HttpRequest;
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
 };
 use rand::Rng;
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use unicode_normalization::UnicodeNormalization;
 
+use crate::config::{SessionConfig, SessionTransport};
 use crate::errors::AppError;
+use crate::models::user::SessionClient;
+
+const WEBHOOK_URL_MAX_LENGTH: usize = 2048;
+const PHONE_NUMBER_MAX_LENGTH: usize = 32;
+// sessions.user_agent の長さ
+const USER_AGENT_MAX_LENGTH: usize = 512;
 
 pub fn generate_session_token() -> String {
     let mut rng = rand::thread_rng();
@@ -18,6 +29,12 @@ pub fn generate_session_token() -> String {
     token
 }
 
+// 登録とログインで同じ綴りとみなす形にそろえる。全角英数字などを NFKC で半角にし、
+// 前後の空白を除いてから小文字にする。大文字と小文字は区別しない方針とする
+pub fn normalize_username(username: &str) -> String {
+    username.nfkc().collect::<String>().trim().to_lowercase()
+}
+
 pub fn hash_password(password: &str) -> Result<String, AppError> {
     let password_bytes = password.as_bytes();
     let salt = SaltString::generate(&mut OsRng);
@@ -43,3 +60,136 @@ pub fn verify_password(hashed_password: &str, input_password: &str) -> Result<bo
         Err(_) => Ok(false),
     }
 }
+
+pub fn sha256_hex(value: &str) -> String {
+    format!("{:x}", Sha256::digest(value.as_bytes()))
+}
+
+// 宛先としてそのまま SMTP に渡すため、空白や制御文字を含むものは受け付けない
+pub fn is_valid_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && domain.contains('.')
+                && email.len() <= 255
+                && !email
+                    .chars()
+                    .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '<' | '>'))
+        }
+        None => false,
+    }
+}
+
+// 国際表記の + と、区切りとして使われる空白・ハイフン・括弧を許す
+pub fn is_valid_phone_number(phone_number: &str) -> bool {
+    let digits = phone_number.chars().filter(char::is_ascii_digit).count();
+    phone_number.len() <= PHONE_NUMBER_MAX_LENGTH
+        && digits >= 3
+        && phone_number.chars().enumerate().all(|(i, c)| {
+            c.is_ascii_digit() || matches!(c, ' ' | '-' | '(' | ')') || (c == '+' && i == 0)
+        })
+}
+
+// 検索で表記の揺れを吸収するため、先頭の + と数字だけを残す
+pub fn normalize_phone_number(phone_number: &str) -> Option<String> {
+    let phone_number = phone_number.trim();
+    if !is_valid_phone_number(phone_number) {
+        return None;
+    }
+
+    Some(
+        phone_number
+            .chars()
+            .filter(|c| c.is_ascii_digit() || *c == '+')
+            .collect(),
+    )
+}
+
+// Webhook の送信先として登録できる URL。カラムの長さに収まる http または https の URL に限る
+pub fn is_valid_webhook_url(url: &str) -> bool {
+    url.len() <= WEBHOOK_URL_MAX_LENGTH
+        && Url::parse(url)
+            .map(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some())
+            .unwrap_or(false)
+}
+
+pub fn compute_etag(bytes: &[u8]) -> String {
+    format!("\"{:x}\"", Sha256::digest(bytes))
+}
+
+pub fn if_none_match_satisfied(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+pub fn session_token_from_request(req: &HttpRequest, config: &SessionConfig) -> Option<String> {
+    let header_token = req
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .map(|s| s.to_string());
+
+    match config.transport {
+        SessionTransport::Cookie => req
+            .cookie(&config.cookie_name)
+            .map(|cookie| cookie.value().to_string())
+            .or(header_token),
+        SessionTransport::Header => header_token,
+    }
+}
+
+// プロキシを経由する場合は Forwarded や X-Forwarded-For のアドレスを使う。ポートは記録しない
+pub fn session_client_from_request(req: &HttpRequest) -> SessionClient {
+    let ip = req
+        .connection_info()
+        .realip_remote_addr()
+        .map(|addr| match addr.parse::<std::net::SocketAddr>() {
+            Ok(addr) => addr.ip().to_string(),
+            Err(_) => addr.trim_matches(|c| c == '[' || c == ']').to_string(),
+        })
+        .filter(|ip| ip.parse::<std::net::IpAddr>().is_ok());
+    let user_agent = req
+        .headers()
+        .get(actix_web::http::header::USER_AGENT)
+        .and_then(|h| h.to_str().ok())
+        .filter(|user_agent| !user_agent.is_empty())
+        .map(|user_agent| user_agent.chars().take(USER_AGENT_MAX_LENGTH).collect());
+
+    SessionClient { ip, user_agent }
+}
+
+// タイミング攻撃で一致する長さや前方部分を推測されないよう、全バイトを比較する
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// LOAD DATA INFILE の FIELDS TERMINATED BY ',' ENCLOSED BY '"' に合わせて分割する。空の値は NULL として扱う
+pub fn parse_csv_line(line: &str) -> Vec<Option<String>> {
+    let mut values = Vec::new();
+    let mut value = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                value.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => values.push(std::mem::take(&mut value)),
+            _ => value.push(c),
+        }
+    }
+    values.push(value);
+
+    values
+        .into_iter()
+        .map(|value| match value.is_empty() {
+            true => None,
+            false => Some(value),
+        })
+        .collect()
+}
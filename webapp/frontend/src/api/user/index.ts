@@ -6,6 +6,8 @@ export type User = {
   user_id: number;
   user_name: string;
   session_token: string;
+  expires_at: string;
+  server_time: string;
 } & (
   | {
       role: "dispatcher";
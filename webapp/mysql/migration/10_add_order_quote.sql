@@ -0,0 +1,5 @@
+-- 利用者が座標から作成した注文の搬送先と、作成時に提示した見積もり
+ALTER TABLE orders
+    ADD COLUMN dropoff_node_id INT NULL,
+    ADD COLUMN quoted_price INT NULL,
+    ADD COLUMN quoted_eta_minutes INT NULL;
@@ -0,0 +1,4 @@
+-- キャンセルされた注文の理由と日時
+ALTER TABLE orders
+    ADD COLUMN cancel_reason VARCHAR(255) NULL,
+    ADD COLUMN cancelled_at DATETIME NULL;
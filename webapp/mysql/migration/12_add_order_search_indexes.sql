@@ -0,0 +1,6 @@
+-- 注文検索用のインデックス。部分一致で検索できるよう、利用者名と地点名には ngram パーサーの全文インデックスを張る
+ALTER TABLE users ADD FULLTEXT INDEX ft_users_username (username) WITH PARSER ngram;
+ALTER TABLE nodes ADD FULLTEXT INDEX ft_nodes_name (name) WITH PARSER ngram;
+
+-- 状態と期間での絞り込みと、注文日時の降順での並べ替えに使う
+ALTER TABLE orders ADD INDEX idx_orders_status_order_time (status, order_time);
@@ -0,0 +1,5 @@
+-- 受付から配車までの時間を集計できるよう、配車した時刻を記録する
+ALTER TABLE orders
+    ADD COLUMN dispatched_at DATETIME NULL,
+    ADD INDEX idx_orders_dispatched_at (dispatched_at),
+    ADD INDEX idx_orders_completed_time (completed_time);
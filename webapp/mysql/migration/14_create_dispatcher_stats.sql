@@ -0,0 +1,10 @@
+-- ランキング用にディスパッチャーごとの実績を保持する。注文の完了時に加算し、集計クエリでは求めない
+CREATE TABLE IF NOT EXISTS dispatcher_stats (
+    dispatcher_id INT PRIMARY KEY,
+    completed_orders INT NOT NULL DEFAULT 0,
+    -- 到着時間の見積もりがあった注文の件数と、見積もりとの差 (分) の絶対値の合計
+    eta_samples INT NOT NULL DEFAULT 0,
+    eta_error_minutes_total BIGINT NOT NULL DEFAULT 0,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    INDEX idx_dispatcher_stats_completed_orders (completed_orders)
+);
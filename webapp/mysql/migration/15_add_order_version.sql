@@ -0,0 +1,2 @@
+-- 配車の競合を検出するためのバージョン。注文を更新するたびに 1 ずつ増やす
+ALTER TABLE orders ADD COLUMN version INT NOT NULL DEFAULT 0;
@@ -0,0 +1,13 @@
+-- 登録時のメールアドレス確認。既存のユーザーと外部 ID で登録したユーザーは確認済みとして扱う
+ALTER TABLE users
+    ADD COLUMN email VARCHAR(255) NULL,
+    ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- 確認用のトークン。平文のトークンは保存せず、SHA-256 のハッシュのみを持つ
+CREATE TABLE IF NOT EXISTS email_verification_tokens (
+    token_hash CHAR(64) PRIMARY KEY,
+    user_id INT NOT NULL,
+    expires_at DATETIME NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_email_verification_tokens_user_id (user_id)
+);
@@ -0,0 +1,21 @@
+-- ユーザーごとの通知の受け取り方。行がないユーザーは WebSocket のみで受け取る
+CREATE TABLE IF NOT EXISTS notification_preferences (
+    user_id INT PRIMARY KEY,
+    websocket BOOLEAN NOT NULL DEFAULT TRUE,
+    email BOOLEAN NOT NULL DEFAULT FALSE,
+    webhook_url VARCHAR(2048) NULL,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
+
+-- 再試行しても届かなかった通知。調査と再送のために内容をそのまま残す
+CREATE TABLE IF NOT EXISTS notification_dead_letters (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    channel VARCHAR(16) NOT NULL,
+    target VARCHAR(2048) NOT NULL,
+    payload TEXT NOT NULL,
+    error TEXT NOT NULL,
+    attempts INT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_notification_dead_letters_created_at (created_at)
+);
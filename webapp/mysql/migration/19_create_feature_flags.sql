@@ -0,0 +1,6 @@
+-- 実行中に切り替えるフィーチャーフラグ。行がないフラグは環境変数か既定値に従う
+CREATE TABLE IF NOT EXISTS feature_flags (
+    name VARCHAR(64) PRIMARY KEY,
+    enabled BOOLEAN NOT NULL,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+);
@@ -0,0 +1,12 @@
+-- セッションに有効期限を持たせ、期限切れセッションを定期的に削除できるようにする
+ALTER TABLE sessions
+    ADD COLUMN created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN expires_at DATETIME NULL;
+
+-- 既存のセッションには移行時点から 1 日の猶予を与える
+UPDATE sessions SET expires_at = UTC_TIMESTAMP() + INTERVAL 1 DAY WHERE expires_at IS NULL;
+
+ALTER TABLE sessions
+    MODIFY COLUMN expires_at DATETIME NOT NULL,
+    ADD INDEX idx_sessions_expires_at (expires_at),
+    ADD INDEX idx_sessions_session_token (session_token);
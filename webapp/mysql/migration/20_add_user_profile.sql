@@ -0,0 +1,4 @@
+-- ユーザー自身が編集するプロフィール。未設定の場合は表示名の代わりにユーザー名を使う
+ALTER TABLE users
+    ADD COLUMN display_name VARCHAR(64) NULL,
+    ADD COLUMN phone_number VARCHAR(32) NULL;
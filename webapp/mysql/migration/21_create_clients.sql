@@ -0,0 +1,20 @@
+-- 電話で注文を受けるための顧客台帳。アカウントを持たない顧客も登録でき、持っている場合は user_id で結び付ける。
+-- phone_number は検索のため、先頭の + と数字だけに正規化して保存する
+CREATE TABLE IF NOT EXISTS clients (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NULL,
+    name VARCHAR(255) NOT NULL,
+    phone_number VARCHAR(32) NOT NULL,
+    default_pickup_node_id INT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    UNIQUE KEY uk_clients_phone_number (phone_number),
+    UNIQUE KEY uk_clients_user_id (user_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL,
+    FOREIGN KEY (default_pickup_node_id) REFERENCES nodes(id) ON DELETE SET NULL
+);
+
+-- 注文した顧客。client_id は注文を登録したユーザーで、電話注文では受け付けたディスパッチャーになる
+ALTER TABLE orders
+    ADD COLUMN customer_id INT NULL,
+    ADD INDEX idx_orders_customer_id (customer_id, order_time);
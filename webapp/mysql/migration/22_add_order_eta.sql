@@ -0,0 +1,5 @@
+-- 配車中の注文の到着予定時刻。車両の最新の位置から定期的に計算し直す。
+-- 見積もりの精度の集計には受付時の quoted_eta_minutes を使い続ける
+ALTER TABLE orders
+    ADD COLUMN eta_at DATETIME NULL,
+    ADD COLUMN eta_updated_at DATETIME NULL;
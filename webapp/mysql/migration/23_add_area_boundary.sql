@@ -0,0 +1,3 @@
+-- エリアの境界。ノードと同じ座標系の頂点の JSON 配列 [[x, y], ...] で持つ。
+-- 境界のないエリアは範囲を確認しない
+ALTER TABLE areas ADD COLUMN boundary TEXT NULL;
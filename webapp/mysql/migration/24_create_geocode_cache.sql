@@ -0,0 +1,16 @@
+-- 外部のジオコーディング API の結果を保存しておくキャッシュ。見つからなかった結果も座標や住所を NULL で保存し、
+-- 同じ問い合わせを繰り返し API に送らないようにする。address は正規化したもの
+CREATE TABLE IF NOT EXISTS geocode_cache (
+    address VARCHAR(255) NOT NULL PRIMARY KEY,
+    x INT NULL,
+    y INT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS reverse_geocode_cache (
+    x INT NOT NULL,
+    y INT NOT NULL,
+    address VARCHAR(255) NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (x, y)
+);
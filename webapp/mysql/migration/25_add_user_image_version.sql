@@ -0,0 +1,2 @@
+-- プロフィール画像をアップロードするたびに上げる。画像の URL に含め、CDN やブラウザに古い画像を使わせない
+ALTER TABLE users ADD COLUMN image_version INT NOT NULL DEFAULT 0;
@@ -0,0 +1,15 @@
+-- ディスパッチャーや管理者として登録するための招待コード。平文のコードは保存せず、SHA-256 のハッシュのみを持つ
+CREATE TABLE IF NOT EXISTS invite_codes (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    code_prefix VARCHAR(16) NOT NULL,
+    code_hash CHAR(64) NOT NULL UNIQUE,
+    -- このコードで登録できるロール
+    role VARCHAR(16) NOT NULL,
+    max_uses INT NOT NULL,
+    use_count INT NOT NULL DEFAULT 0,
+    -- NULL の場合は期限なし
+    expires_at DATETIME NULL,
+    revoked_at DATETIME NULL,
+    created_by INT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,2 @@
+-- 管理者が仮のパスワードで作成したアカウント。初回のログインで新しいパスワードを設定するまでセッションを発行しない
+ALTER TABLE users ADD COLUMN password_reset_required BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,7 @@
+-- セッションを使ったクライアントと最後に使われた日時。一覧の表示と、不審なログインの検知に使う。
+-- last_seen_at はリクエストのたびではなく、まとめて定期的に更新する
+ALTER TABLE sessions
+    ADD COLUMN client_ip VARCHAR(45) NULL,
+    ADD COLUMN user_agent VARCHAR(512) NULL,
+    ADD COLUMN last_seen_at DATETIME NULL,
+    ADD INDEX idx_sessions_user_id (user_id);
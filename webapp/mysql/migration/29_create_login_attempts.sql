@@ -0,0 +1,12 @@
+-- 存在するユーザーへのログインの試行。不審なログインの検知で、過去の接続元や直前の失敗と比べるために使う
+CREATE TABLE IF NOT EXISTS login_attempts (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    succeeded BOOLEAN NOT NULL,
+    client_ip VARCHAR(45) NULL,
+    user_agent VARCHAR(512) NULL,
+    -- 接続元の IP アドレスから推定したエリア。推定できない場合は NULL
+    area_id INT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_login_attempts_user_id (user_id, succeeded, created_at)
+);
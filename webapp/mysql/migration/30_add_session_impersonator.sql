@@ -0,0 +1,4 @@
+-- 管理者が利用者になりかわって発行したセッション。発行した管理者を記録し、通常のセッションと区別する
+ALTER TABLE sessions
+    ADD COLUMN impersonator_user_id INT NULL,
+    ADD INDEX idx_sessions_impersonator_user_id (impersonator_user_id);
@@ -0,0 +1,28 @@
+-- 複数の事業者で同じ環境を共有するための組織。既存のデータはすべて既定の組織 (id = 1) に属する
+CREATE TABLE IF NOT EXISTS organizations (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(255) NOT NULL UNIQUE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT IGNORE INTO organizations (id, name) VALUES (1, 'default');
+
+ALTER TABLE users
+    ADD COLUMN organization_id INT NOT NULL DEFAULT 1,
+    ADD INDEX idx_users_organization_id (organization_id);
+
+ALTER TABLE dispatchers
+    ADD COLUMN organization_id INT NOT NULL DEFAULT 1,
+    ADD INDEX idx_dispatchers_organization_id (organization_id);
+
+ALTER TABLE areas
+    ADD COLUMN organization_id INT NOT NULL DEFAULT 1,
+    ADD INDEX idx_areas_organization_id (organization_id);
+
+ALTER TABLE orders
+    ADD COLUMN organization_id INT NOT NULL DEFAULT 1,
+    ADD INDEX idx_orders_organization_id_status (organization_id, status);
+
+-- 外部システムの API キーも、発行した管理者の組織のデータだけを参照できる
+ALTER TABLE api_keys
+    ADD COLUMN organization_id INT NOT NULL DEFAULT 1;
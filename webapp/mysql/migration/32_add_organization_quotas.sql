@@ -0,0 +1,18 @@
+-- 組織ごとの利用上限。NULL の場合は環境変数で設定した既定の上限を使う
+ALTER TABLE organizations
+    ADD COLUMN max_orders_per_day BIGINT NULL,
+    ADD COLUMN max_active_dispatchers BIGINT NULL,
+    ADD COLUMN max_avatar_storage_bytes BIGINT NULL;
+
+-- 1 日の注文数を数えるため
+ALTER TABLE orders
+    ADD INDEX idx_orders_organization_id_order_time (organization_id, order_time);
+
+-- アップロードされたプロフィール画像の大きさ。組織ごとの使用量はユーザーの現在の画像の合計とする
+CREATE TABLE IF NOT EXISTS profile_images (
+    name VARCHAR(255) NOT NULL PRIMARY KEY,
+    user_id INT NOT NULL,
+    bytes BIGINT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_profile_images_user_id (user_id)
+);
@@ -0,0 +1,9 @@
+-- 注文の優先度 (vip, scheduled, normal)。
+-- queue_time は優先度の分だけ注文日時を繰り上げた時刻で、未割り当ての注文はこの順に処理する。
+-- 優先度の高い注文も待ち時間では追い越せるため、古い通常の注文がいつまでも後回しにはならない
+ALTER TABLE orders
+    ADD COLUMN priority VARCHAR(16) NOT NULL DEFAULT 'normal',
+    ADD COLUMN queue_time DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD INDEX idx_orders_organization_id_status_queue_time (organization_id, status, queue_time);
+
+UPDATE orders SET queue_time = order_time;
@@ -0,0 +1,4 @@
+-- 予約注文。status が 'scheduled' の間は配車の対象にならず、希望日時の少し前に 'pending' に変わる
+ALTER TABLE orders
+    ADD COLUMN scheduled_for DATETIME NULL,
+    ADD INDEX idx_orders_status_scheduled_for (status, scheduled_for);
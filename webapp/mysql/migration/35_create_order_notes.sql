@@ -0,0 +1,22 @@
+-- 注文に付けるメモと添付ファイル (乗車地の写真など)。添付ファイルの中身は画像と同じストアに保存する
+CREATE TABLE IF NOT EXISTS order_notes (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    order_id INT NOT NULL,
+    organization_id INT NOT NULL,
+    author_id INT NOT NULL,
+    body TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_order_notes_order_id (order_id, id)
+);
+
+CREATE TABLE IF NOT EXISTS order_attachments (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    order_id INT NOT NULL,
+    organization_id INT NOT NULL,
+    uploaded_by INT NOT NULL,
+    name VARCHAR(255) NOT NULL UNIQUE,
+    content_type VARCHAR(64) NOT NULL,
+    bytes BIGINT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_order_attachments_order_id (order_id, id)
+);
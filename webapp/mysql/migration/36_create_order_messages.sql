@@ -0,0 +1,18 @@
+-- 配車した注文ごとの、ディスパッチャーと運転手のチャット
+CREATE TABLE IF NOT EXISTS order_messages (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    order_id INT NOT NULL,
+    organization_id INT NOT NULL,
+    sender_id INT NOT NULL,
+    body TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_order_messages_order_id (order_id, id)
+);
+
+-- ユーザーが注文のチャットをどこまで読んだか。これより後の相手のメッセージを未読として数える
+CREATE TABLE IF NOT EXISTS order_message_reads (
+    order_id INT NOT NULL,
+    user_id INT NOT NULL,
+    last_read_message_id INT NOT NULL,
+    PRIMARY KEY (order_id, user_id)
+);
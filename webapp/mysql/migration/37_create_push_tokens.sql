@@ -0,0 +1,13 @@
+-- 通知をモバイル端末やブラウザにプッシュするための登録トークン。WebSocket に接続していない間の通知に使う
+CREATE TABLE IF NOT EXISTS push_tokens (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    provider VARCHAR(16) NOT NULL,
+    token VARCHAR(2048) NOT NULL,
+    -- トークンは長いため、重複の確認には SHA-256 を使う
+    token_hash CHAR(64) NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    UNIQUE INDEX idx_push_tokens_token_hash (token_hash),
+    INDEX idx_push_tokens_user_id (user_id)
+);
@@ -0,0 +1,11 @@
+-- 差分同期のための変更の記録。ドメインイベントから、変更された注文・ディスパッチャー・ユーザーの ID を記録する。
+-- organization_id が NULL の行はデータ全体の置き換えを表し、すべての組織のクライアントに読み込み直しを求める
+CREATE TABLE IF NOT EXISTS change_log (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    organization_id INT NULL,
+    entity_type VARCHAR(16) NOT NULL,
+    entity_id INT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_change_log_organization_id (organization_id, id),
+    INDEX idx_change_log_created_at (created_at)
+);
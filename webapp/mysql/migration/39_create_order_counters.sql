@@ -0,0 +1,10 @@
+-- ダッシュボードの件数表示用に、組織ごと・状態ごとの注文数を保持する。
+-- 注文のイベントごとに増減させ、定期的に orders から数え直してずれを直すため、数え直しの間は概算になる
+CREATE TABLE IF NOT EXISTS order_counters (
+    organization_id INT NOT NULL,
+    status VARCHAR(16) NOT NULL,
+    count BIGINT NOT NULL DEFAULT 0,
+    reconciled_at DATETIME NULL,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    PRIMARY KEY (organization_id, status)
+);
@@ -0,0 +1,4 @@
+-- 保持期間を過ぎて個人を特定できる情報を消した注文。集計に使うため注文自体は残す
+ALTER TABLE orders
+    ADD COLUMN anonymized_at DATETIME NULL,
+    ADD INDEX idx_orders_status_anonymized_at_completed_time (status, anonymized_at, completed_time);
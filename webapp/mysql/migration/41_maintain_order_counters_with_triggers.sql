@@ -0,0 +1,32 @@
+-- 注文数を orders への書き込みと同じ文で増減させ、イベントを発行しない経路 (予約注文の作成など) の変更も取りこぼさないようにする
+CREATE TRIGGER trg_orders_counters_insert AFTER INSERT ON orders
+FOR EACH ROW
+    INSERT INTO order_counters (organization_id, status, count)
+    VALUES (NEW.organization_id, NEW.status, 1)
+    ON DUPLICATE KEY UPDATE count = count + 1;
+
+CREATE TRIGGER trg_orders_counters_update AFTER UPDATE ON orders
+FOR EACH ROW
+    INSERT INTO order_counters (organization_id, status, count)
+    SELECT changes.organization_id, changes.status, changes.delta
+    FROM (
+        SELECT OLD.organization_id AS organization_id, OLD.status AS status, -1 AS delta
+        UNION ALL
+        SELECT NEW.organization_id, NEW.status, 1
+    ) AS changes
+    WHERE NOT (OLD.organization_id <=> NEW.organization_id AND OLD.status <=> NEW.status)
+    ON DUPLICATE KEY UPDATE count = count + VALUES(count);
+
+CREATE TRIGGER trg_orders_counters_delete AFTER DELETE ON orders
+FOR EACH ROW
+    INSERT INTO order_counters (organization_id, status, count)
+    VALUES (OLD.organization_id, OLD.status, -1)
+    ON DUPLICATE KEY UPDATE count = count - 1;
+
+-- トリガーを作る前の注文を数えておく
+UPDATE order_counters SET count = 0, reconciled_at = NOW();
+INSERT INTO order_counters (organization_id, status, count, reconciled_at)
+SELECT organization_id, status, COUNT(*), NOW()
+FROM orders
+GROUP BY organization_id, status
+ON DUPLICATE KEY UPDATE count = VALUES(count), reconciled_at = VALUES(reconciled_at);
@@ -0,0 +1,5 @@
+-- 二要素認証のコードの総当たりと再利用を防ぐため、最後に受け付けたコードの時間枠と続けて失敗した回数を保持する
+ALTER TABLE users
+    ADD COLUMN totp_last_step BIGINT NULL,
+    ADD COLUMN totp_failed_attempts INT NOT NULL DEFAULT 0,
+    ADD COLUMN totp_locked_until DATETIME NULL;
@@ -0,0 +1,10 @@
+-- 外部 IdP (OpenID Connect) のアカウントとローカルユーザーの紐付け
+CREATE TABLE IF NOT EXISTS external_identities (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    provider VARCHAR(64) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    user_id INT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (provider, subject),
+    INDEX idx_external_identities_user_id (user_id)
+);
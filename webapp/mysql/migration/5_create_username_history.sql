@@ -0,0 +1,8 @@
+-- 変更前のユーザー名を一定期間ほかのユーザーに取得させないための履歴
+CREATE TABLE IF NOT EXISTS username_history (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    username VARCHAR(255) NOT NULL,
+    changed_at DATETIME NOT NULL,
+    INDEX idx_username_history_username (username, changed_at)
+);
@@ -0,0 +1,2 @@
+-- 管理者がアカウントを停止できるようにする。停止中のユーザーはログインできず、既存のセッションも使えない
+ALTER TABLE users ADD COLUMN is_active BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,12 @@
+-- 管理者による操作などを後から追跡するための監査ログ
+CREATE TABLE IF NOT EXISTS audit_logs (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    actor_user_id INT NULL,
+    action VARCHAR(64) NOT NULL,
+    target_type VARCHAR(64) NULL,
+    target_id VARCHAR(255) NULL,
+    detail JSON NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_audit_logs_actor_user_id (actor_user_id, created_at),
+    INDEX idx_audit_logs_created_at (created_at)
+);
@@ -0,0 +1,13 @@
+-- 再送されたリクエストを二重に処理しないよう、Idempotency-Key ごとに最初の応答を保存する
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    idempotency_key VARCHAR(255) NOT NULL,
+    request_hash CHAR(64) NOT NULL,
+    response_status INT NULL,
+    response_body MEDIUMTEXT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME NOT NULL,
+    UNIQUE (user_id, idempotency_key),
+    INDEX idx_idempotency_keys_expires_at (expires_at)
+);
@@ -0,0 +1,2 @@
+-- 車両として登録するレッカー車の積載台数。既存の車両は 1 台積みとして扱う
+ALTER TABLE tow_trucks ADD COLUMN capacity INT NOT NULL DEFAULT 1;